@@ -0,0 +1,474 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::cron::CronSchedule;
+use crate::digest::Digest;
+use crate::package_manager::PackageManager;
+use crate::plugin::LoadedPlugin;
+use crate::report::{ExecutionReport, ExecutionStatus};
+use crate::schedule::{Cadence, Interval};
+use crate::stats::format_duration;
+use crate::timezone::Timezone;
+
+// Commands run when a manager fails, escalated once it keeps failing
+//
+//   alerts:
+//     command: 'notify-send updog "$UPDOG_MESSAGE"'
+//     escalate_after: 3    # consecutive failures
+//     escalate_command: 'notify-send -u critical updog "$UPDOG_MESSAGE"'
+//
+// The commands get UPDOG_MANAGER, UPDOG_MESSAGE, UPDOG_STREAK,
+// UPDOG_URGENCY (normal or critical) and UPDOG_EVENT (failed, verify_failed
+// for an update whose `verify` commands failed, slow for a manager still
+// running after its `warn_after`, or commands_changed for a manager whose
+// commands ran off a terminal before being confirmed) in their environment.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AlertConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default = "AlertConfig::default_escalate_after")]
+    pub escalate_after: usize,
+    // Falls back to `command` (with UPDOG_URGENCY=critical)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub escalate_command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<DigestConfig>,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self {
+            command: None,
+            escalate_after: Self::default_escalate_after(),
+            escalate_command: None,
+            digest: None,
+        }
+    }
+}
+
+// A digest of all runs since the previous one, sent by the daemon on a
+// cadence of its own (failures are still alerted as they happen)
+//
+//   alerts:
+//     digest:
+//       every: daily               # or schedule: "0 18 * * *"
+//       command: 'mail -s "updog digest" me@example.com'
+//
+// The digest goes to `command` (the alert command when unset) on stdin,
+// with UPDOG_EVENT=digest and its headline in UPDOG_MESSAGE, and to the
+// notifier plugins.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DigestConfig {
+    #[serde(default = "DigestConfig::default_every")]
+    pub every: Interval,
+    // Takes precedence over `every`, evaluated in the daemon's timezone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<CronSchedule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
+
+impl DigestConfig {
+    fn default_every() -> Interval {
+        Interval::DAY
+    }
+
+    pub fn cadence(&self, timezone: Timezone) -> Cadence {
+        match &self.schedule {
+            Some(schedule) => Cadence::Cron(schedule.clone(), timezone),
+            None => Cadence::Every(self.every),
+        }
+    }
+}
+
+impl AlertConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.command.is_some() || self.escalate_command.is_some()
+    }
+
+    fn default_escalate_after() -> usize {
+        3
+    }
+
+    fn command_for(&self, escalated: bool) -> Option<&str> {
+        if escalated {
+            self.escalate_command.as_deref().or(self.command.as_deref())
+        } else {
+            self.command.as_deref()
+        }
+    }
+}
+
+// What an alert is about
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum AlertKind {
+    #[default]
+    Failed,
+    // Still running after `elapsed` (the manager's `warn_after`)
+    Slow {
+        elapsed: Duration,
+    },
+    // The update ran, but its `verify` commands failed
+    VerifyFailed,
+    // The manager's commands ran without being confirmed since they changed
+    CommandsChanged,
+    // The manager's ssh host couldn't be reached
+    Unreachable,
+}
+
+impl AlertKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AlertKind::Failed => "failed",
+            AlertKind::Slow { .. } => "slow",
+            AlertKind::VerifyFailed => "verify_failed",
+            AlertKind::CommandsChanged => "commands_changed",
+            AlertKind::Unreachable => "unreachable",
+        }
+    }
+}
+
+// A failure (or a slow manager) to report
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub manager: String,
+    pub message: String,
+    // Consecutive failures including this one
+    pub streak: usize,
+    pub escalated: bool,
+    pub kind: AlertKind,
+}
+
+impl Alert {
+    // Warning for a manager that's still running, which may just be slow
+    pub fn slow(manager: &str, elapsed: Duration) -> Self {
+        let kind = AlertKind::Slow { elapsed };
+        Self {
+            manager: manager.to_string(),
+            message: format!("still running after {}", format_duration(elapsed)),
+            streak: 0,
+            escalated: false,
+            kind,
+        }
+    }
+
+    // Commands that changed since they were confirmed ran unattended
+    pub fn commands_changed(manager: &str, new: bool) -> Self {
+        let message = match new {
+            true => "new commands ran without being confirmed",
+            false => "changed commands ran without being confirmed",
+        };
+        Self {
+            manager: manager.to_string(),
+            message: message.to_string(),
+            streak: 0,
+            escalated: false,
+            kind: AlertKind::CommandsChanged,
+        }
+    }
+
+    pub fn urgency(&self) -> &'static str {
+        if self.escalated {
+            "critical"
+        } else {
+            "normal"
+        }
+    }
+
+    pub fn text(&self) -> String {
+        match self.kind {
+            AlertKind::Slow { elapsed } => {
+                return format!(
+                    "{} has been running {}",
+                    self.manager,
+                    format_duration(elapsed)
+                )
+            }
+            AlertKind::VerifyFailed => {
+                return format!("{} updated. {}", self.manager, self.message)
+            }
+            AlertKind::CommandsChanged => {
+                return format!(
+                    "{}: {}; run updog on a terminal to review them",
+                    self.manager, self.message
+                )
+            }
+            AlertKind::Failed | AlertKind::Unreachable => {}
+        }
+        match self.streak {
+            0 | 1 => format!("{} failed: {}", self.manager, self.message),
+            n => format!(
+                "{} failed {} times in a row: {}",
+                self.manager, n, self.message
+            ),
+        }
+    }
+}
+
+// Alerts for the failures in `report`, given failure streaks that already
+// include it (see `stats::failure_streaks`)
+pub fn alerts(
+    report: &ExecutionReport,
+    streaks: &BTreeMap<String, usize>,
+    config: &AlertConfig,
+) -> Vec<Alert> {
+    report
+        .results
+        .iter()
+        .filter(|r| r.status.is_failure())
+        .map(|r| {
+            let manager = r.name();
+            let streak = streaks.get(&manager).copied().unwrap_or(1);
+            Alert {
+                escalated: streak >= config.escalate_after,
+                message: r.message.clone(),
+                streak,
+                manager,
+                kind: match r.status {
+                    ExecutionStatus::VerifyFailed => AlertKind::VerifyFailed,
+                    ExecutionStatus::Unreachable => AlertKind::Unreachable,
+                    _ => AlertKind::Failed,
+                },
+            }
+        })
+        .collect()
+}
+
+// Whether failures go anywhere: an alert command or a notifier plugin
+pub fn has_targets(config: &Config) -> bool {
+    config.alerts.is_enabled() || notifiers(config).next().is_some()
+}
+
+fn notifiers(config: &Config) -> impl Iterator<Item = &LoadedPlugin> {
+    config
+        .loaded_plugins
+        .iter()
+        .filter(|p| p.handshake.notifier)
+}
+
+// Run the configured alert command and the notifier plugins for each alert
+pub fn send(pm: &PackageManager, alerts: &[Alert]) {
+    for alert in alerts {
+        if alert.escalated {
+            info!("Escalating alert for {}", alert.manager);
+        }
+        let env = vec![
+            ("UPDOG_MANAGER".to_string(), alert.manager.clone()),
+            ("UPDOG_MESSAGE".to_string(), alert.text()),
+            ("UPDOG_STREAK".to_string(), alert.streak.to_string()),
+            ("UPDOG_URGENCY".to_string(), alert.urgency().to_string()),
+            ("UPDOG_EVENT".to_string(), alert.kind.label().to_string()),
+        ];
+
+        let mut targets: Vec<(String, String, Option<String>)> = Vec::new();
+        if let Some(command) = pm.config.alerts.command_for(alert.escalated) {
+            targets.push(("Alert command".to_string(), command.to_string(), None));
+        }
+        for notifier in notifiers(&pm.config) {
+            let payload = serde_json::json!({
+                "manager": alert.manager,
+                "message": alert.message,
+                "text": alert.text(),
+                "streak": alert.streak,
+                "urgency": alert.urgency(),
+                "event": alert.kind.label(),
+                "run_id": pm.run_id,
+            });
+            targets.push((
+                format!("Notifier {}", notifier.plugin.name),
+                notifier.plugin.command(&["notify"]),
+                Some(payload.to_string()),
+            ));
+        }
+
+        run_targets(pm, targets, &env, &alert.manager);
+    }
+}
+
+// Send `digest` to the digest command (or the alert command) and the
+// notifier plugins
+pub fn send_digest(pm: &PackageManager, digest: &Digest) {
+    let env = vec![
+        ("UPDOG_MESSAGE".to_string(), digest.headline.clone()),
+        ("UPDOG_URGENCY".to_string(), "normal".to_string()),
+        ("UPDOG_EVENT".to_string(), "digest".to_string()),
+    ];
+    let mut targets: Vec<(String, String, Option<String>)> = Vec::new();
+    let alerts = &pm.config.alerts;
+    let command = alerts
+        .digest
+        .as_ref()
+        .and_then(|d| d.command.as_deref())
+        .or(alerts.command.as_deref());
+    if let Some(command) = command {
+        targets.push((
+            "Digest command".to_string(),
+            command.to_string(),
+            Some(digest.text.clone()),
+        ));
+    }
+    for notifier in notifiers(&pm.config) {
+        let payload = serde_json::json!({
+            "message": digest.headline,
+            "text": digest.text,
+            "runs": digest.runs,
+            "failing": digest.failing,
+            "urgency": "normal",
+            "event": "digest",
+        });
+        targets.push((
+            format!("Notifier {}", notifier.plugin.name),
+            notifier.plugin.command(&["notify"]),
+            Some(payload.to_string()),
+        ));
+    }
+    info!("Sending digest: {}", digest.headline);
+    run_targets(pm, targets, &env, "the digest");
+}
+
+fn run_targets(
+    pm: &PackageManager,
+    targets: Vec<(String, String, Option<String>)>,
+    env: &[(String, String)],
+    subject: &str,
+) {
+    for (target, command, stdin) in targets {
+        match pm.run_hook(&command, env.to_vec(), stdin) {
+            Ok(output) if !output.success() => warn!(
+                "{} for {} failed with {}",
+                target,
+                subject,
+                output.status_description()
+            ),
+            Ok(_) => {}
+            Err(e) => warn!("Failed to run {} for {}: {}", target, subject, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::{Handshake, Plugin};
+    use crate::report::{ExecutionResult, ExecutionStatus, Operation};
+    use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+    use crate::Config;
+    use std::sync::Arc;
+    use std::time::UNIX_EPOCH;
+
+    fn report(results: &[(&str, ExecutionStatus)]) -> ExecutionReport {
+        let mut report = ExecutionReport::new(Operation::Update, UNIX_EPOCH);
+        for (manager, status) in results {
+            report.results.push(ExecutionResult {
+                manager: manager.to_string(),
+                subcommand: None,
+                status: status.clone(),
+                message: "Error: boom".to_string(),
+                duration: Duration::ZERO,
+                output: String::new(),
+                steps: Vec::new(),
+                exit_code: None,
+                upgraded: None,
+                cached: false,
+                reboot_required: false,
+                cleaned: Vec::new(),
+                warnings: Vec::new(),
+                columns: BTreeMap::new(),
+            });
+        }
+        report
+    }
+
+    #[test]
+    fn test_alerts_escalate_after_streak() {
+        let report = report(&[
+            ("brew", ExecutionStatus::Failed),
+            ("npm", ExecutionStatus::TimedOut),
+            ("cargo", ExecutionStatus::Success),
+            ("rustup", ExecutionStatus::VerifyFailed),
+        ]);
+        let streaks = BTreeMap::from([("brew".to_string(), 3), ("npm".to_string(), 1)]);
+
+        let alerts = alerts(&report, &streaks, &AlertConfig::default());
+        assert_eq!(alerts.len(), 3);
+        assert_eq!(alerts[2].kind.label(), "verify_failed");
+        assert_eq!(alerts[2].text(), "rustup updated. Error: boom");
+        assert!(alerts[0].escalated);
+        assert_eq!(
+            alerts[0].text(),
+            "brew failed 3 times in a row: Error: boom"
+        );
+        assert!(!alerts[1].escalated);
+        assert_eq!(alerts[1].text(), "npm failed: Error: boom");
+    }
+
+    #[test]
+    fn test_send_uses_escalation_command() {
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor
+            .script("notify", ScriptedCommand::success())
+            .script("page", ScriptedCommand::success());
+        let mut config = Config::default();
+        config.alerts.command = Some("notify".to_string());
+        config.alerts.escalate_command = Some("page".to_string());
+        let pm = PackageManager::with_executor(config, executor.clone(), clock);
+
+        let alert = |streak, escalated| Alert {
+            manager: "brew".to_string(),
+            message: "Error: boom".to_string(),
+            streak,
+            escalated,
+            kind: AlertKind::Failed,
+        };
+        send(&pm, &[alert(1, false), alert(3, true)]);
+
+        assert_eq!(executor.commands(), vec!["notify", "page"]);
+        let env = &executor.calls()[1].request.env;
+        assert!(env.contains(&("UPDOG_URGENCY".to_string(), "critical".to_string())));
+        assert!(env.contains(&("UPDOG_STREAK".to_string(), "3".to_string())));
+    }
+
+    #[test]
+    fn test_send_to_notifier_plugin() {
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor.script("'/bin/updog-ntfy' 'notify'", ScriptedCommand::success());
+        let mut config = Config::default();
+        config.loaded_plugins.push(LoadedPlugin {
+            plugin: Plugin {
+                name: "ntfy".to_string(),
+                path: "/bin/updog-ntfy".into(),
+            },
+            handshake: Handshake {
+                protocol: 1,
+                notifier: true,
+                ..Default::default()
+            },
+        });
+        assert!(has_targets(&config));
+        let pm = PackageManager::with_executor(config, executor.clone(), clock).for_run("3f2a9c1e");
+
+        send(
+            &pm,
+            &[Alert {
+                manager: "brew".to_string(),
+                message: "Error: boom".to_string(),
+                streak: 1,
+                escalated: false,
+                kind: AlertKind::Failed,
+            }],
+        );
+
+        let calls = executor.calls();
+        assert_eq!(calls.len(), 1);
+        let payload: serde_json::Value =
+            serde_json::from_str(calls[0].request.stdin.as_deref().unwrap()).unwrap();
+        assert_eq!(payload["manager"], "brew");
+        assert_eq!(payload["urgency"], "normal");
+        assert_eq!(payload["run_id"], "3f2a9c1e");
+    }
+}
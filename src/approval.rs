@@ -0,0 +1,376 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use thiserror::Error;
+
+use crate::config::{CommandSequence, Config};
+use crate::hash::sha256_hex;
+use crate::history::unix_seconds;
+use crate::state_file::write_atomic;
+
+#[derive(Debug, Error)]
+pub enum ApprovalError {
+    #[error("Failed to access approved configs: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Failed to parse approved configs: {0}")]
+    ParseError(#[from] serde_yaml::Error),
+}
+
+// Config revisions approved for unattended runs, by config path. A synced
+// dotfile change must be approved with `updog config approve` before runs
+// started with `--require-approval` execute its commands.
+//
+// Apart from that, each manager's commands are confirmed on their own: a
+// check or update on a terminal asks before running commands that are new
+// or changed since they were last confirmed, and other runs flag them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Approvals {
+    #[serde(default)]
+    pub configs: BTreeMap<String, ApprovedConfig>,
+    // Hash of each manager's confirmed commands (see `command_hashes`), by
+    // config path and manager id
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub commands: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApprovedConfig {
+    // SHA-256 of the approved config file contents
+    pub hash: String,
+    pub approved_at: u64,
+}
+
+// Outcome of checking a config against the approved revision
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Approved,
+    // Never seen before: trusted on first use and recorded
+    FirstUse,
+    Changed { approved_hash: String },
+}
+
+// A manager whose commands weren't confirmed in their current form
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandChange {
+    pub manager: String,
+    // Not confirmed in any form yet (a manager added to the config)
+    pub new: bool,
+    pub hash: String,
+}
+
+impl fmt::Display for CommandChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.new {
+            true => write!(f, "{} (new)", self.manager),
+            false => write!(f, "{} (changed)", self.manager),
+        }
+    }
+}
+
+impl Approvals {
+    // Default location: <data dir>/updog/approved.yaml
+    pub fn default_path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("updog")
+            .join("approved.yaml")
+    }
+
+    // A missing file means nothing was approved yet
+    pub fn load(path: &Path) -> Result<Self, ApprovalError> {
+        match fs::read_to_string(path) {
+            Ok(content) if content.trim().is_empty() => Ok(Self::default()),
+            Ok(content) => Ok(serde_yaml::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ApprovalError> {
+        write_atomic(path, serde_yaml::to_string(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn approve(&mut self, config_path: &str, hash: &str, now: SystemTime) {
+        self.configs.insert(
+            key(config_path),
+            ApprovedConfig {
+                hash: hash.to_string(),
+                approved_at: unix_seconds(now),
+            },
+        );
+    }
+
+    // Compare `hash` with the approved revision of `config_path`, approving
+    // it if the config has never been seen
+    pub fn verify(&mut self, config_path: &str, hash: &str, now: SystemTime) -> Verdict {
+        match self.configs.get(&key(config_path)) {
+            Some(approved) if approved.hash == hash => Verdict::Approved,
+            Some(approved) => Verdict::Changed {
+                approved_hash: approved.hash.clone(),
+            },
+            None => {
+                self.approve(config_path, hash, now);
+                Verdict::FirstUse
+            }
+        }
+    }
+
+    // Managers among `hashes` (from `command_hashes`) whose commands differ
+    // from the confirmed ones. Commands of a config seen for the first time
+    // are trusted and recorded.
+    pub fn unconfirmed_commands(
+        &mut self,
+        config_path: &str,
+        hashes: &BTreeMap<String, String>,
+    ) -> Vec<CommandChange> {
+        let Some(confirmed) = self.commands.get(&key(config_path)) else {
+            self.confirm_commands(config_path, hashes);
+            return Vec::new();
+        };
+        hashes
+            .iter()
+            .filter(|(manager, hash)| confirmed.get(*manager) != Some(hash))
+            .map(|(manager, hash)| CommandChange {
+                manager: manager.clone(),
+                new: !confirmed.contains_key(manager),
+                hash: hash.clone(),
+            })
+            .collect()
+    }
+
+    pub fn confirm_commands(&mut self, config_path: &str, hashes: &BTreeMap<String, String>) {
+        let confirmed = self.commands.entry(key(config_path)).or_default();
+        for (manager, hash) in hashes {
+            confirmed.insert(manager.clone(), hash.clone());
+        }
+    }
+}
+
+// What a manager runs, hashed: every command and step of it and its
+// subcommands and actions, its `env`, `when_script` and `transform_output`.
+// Descriptions, tags and schedules don't count.
+pub fn command_hashes(config: &Config) -> BTreeMap<String, String> {
+    let mut hashes = BTreeMap::new();
+    for manager in config.managers() {
+        let mut text = String::new();
+        for (_, name, sequence) in config
+            .sequences()
+            .into_iter()
+            .filter(|(m, _, _)| m.id == manager.id)
+        {
+            text.push_str(&format!("{}\n", name));
+            for step in sequence.steps() {
+                // Built-in steps that can't be serialized are described instead
+                let step = serde_json::to_string(&step).unwrap_or_else(|_| step.to_string());
+                text.push_str(&format!("  {}\n", step));
+            }
+        }
+        for (name, value) in &manager.env {
+            text.push_str(&format!("env {}={}\n", name, value));
+        }
+        // The same commands on another host are new commands
+        if let Some(target) = &manager.ssh {
+            text.push_str(&format!("ssh {}\n", target.args(&[]).join(" ")));
+        }
+        for script in [&manager.when_script, &manager.transform_output]
+            .into_iter()
+            .flatten()
+        {
+            text.push_str(&format!("script {}\n", script));
+        }
+        hashes.insert(manager.id.clone(), sha256_hex(text.as_bytes()));
+    }
+    hashes
+}
+
+// "check: apt list --upgradable" per command of the manager, as the
+// confirmation shows them
+pub fn describe_commands(config: &Config, manager: &str) -> Vec<String> {
+    config
+        .sequences()
+        .into_iter()
+        .filter(|(m, _, _)| m.id == manager)
+        .map(|(_, name, sequence)| {
+            let name = name.strip_prefix(&format!("{} ", manager)).unwrap_or(&name);
+            let steps: Vec<String> = match &sequence {
+                CommandSequence::Single(command) => vec![command.clone()],
+                CommandSequence::Multiple(steps) => steps.iter().map(ToString::to_string).collect(),
+            };
+            format!("{}: {}", name, steps.join(" && "))
+        })
+        .collect()
+}
+
+// Show `changes` with their commands and ask whether to run them
+pub fn confirm<R: BufRead, W: Write>(
+    config: &Config,
+    changes: &[CommandChange],
+    input: &mut R,
+    output: &mut W,
+) -> io::Result<bool> {
+    writeln!(
+        output,
+        "These commands are new or changed since they were last confirmed:"
+    )?;
+    for change in changes {
+        writeln!(output, "  {}", change)?;
+        for command in describe_commands(config, &change.manager) {
+            writeln!(output, "    {}", command)?;
+        }
+    }
+    write!(output, "Run them? [y/N] ")?;
+    output.flush()?;
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    Ok(matches!(line.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+// Approvals are kept by absolute path, so relative `--config` paths given
+// from different directories don't mix
+fn key(config_path: &str) -> String {
+    fs::canonicalize(config_path)
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| config_path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::time::UNIX_EPOCH;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_trust_on_first_use() {
+        let mut approvals = Approvals::default();
+        let now = UNIX_EPOCH;
+        assert_eq!(
+            approvals.verify("/etc/updog.yaml", "aaa", now),
+            Verdict::FirstUse
+        );
+        assert_eq!(
+            approvals.verify("/etc/updog.yaml", "aaa", now),
+            Verdict::Approved
+        );
+        assert_eq!(
+            approvals.verify("/etc/updog.yaml", "bbb", now),
+            Verdict::Changed {
+                approved_hash: "aaa".to_string()
+            }
+        );
+        // A changed config stays unapproved until approved explicitly
+        assert!(matches!(
+            approvals.verify("/etc/updog.yaml", "bbb", now),
+            Verdict::Changed { .. }
+        ));
+        approvals.approve("/etc/updog.yaml", "bbb", now);
+        assert_eq!(
+            approvals.verify("/etc/updog.yaml", "bbb", now),
+            Verdict::Approved
+        );
+    }
+
+    fn config(yaml: &str) -> Config {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    const CONFIG: &str = r#"
+commands:
+  - id: apt
+    description: Debian packages
+    check: apt list --upgradable
+    update: sudo apt upgrade -y
+  - id: npm
+    subcommands:
+      - id: global
+        update: npm update -g
+"#;
+
+    #[test]
+    fn test_command_hashes() {
+        let hashes = command_hashes(&config(CONFIG));
+        assert_eq!(hashes.keys().collect::<Vec<_>>(), ["apt", "npm"]);
+        // Only what runs counts
+        let described = command_hashes(&config(&CONFIG.replace("Debian packages", "apt")));
+        assert_eq!(described, hashes);
+        let edited = command_hashes(&config(&CONFIG.replace("npm update -g", "npm i -g x")));
+        assert_eq!(edited["apt"], hashes["apt"]);
+        assert_ne!(edited["npm"], hashes["npm"]);
+        let env = CONFIG.replace(
+            "    update: sudo apt upgrade -y\n",
+            "    update: sudo apt upgrade -y\n    env:\n      PATH: /tmp/evil\n",
+        );
+        assert_ne!(command_hashes(&config(&env))["apt"], hashes["apt"]);
+    }
+
+    #[test]
+    fn test_unconfirmed_commands() {
+        let mut approvals = Approvals::default();
+        let hashes = command_hashes(&config(CONFIG));
+        // Trusted on first use
+        assert!(approvals
+            .unconfirmed_commands("updog.yaml", &hashes)
+            .is_empty());
+        assert!(approvals
+            .unconfirmed_commands("updog.yaml", &hashes)
+            .is_empty());
+
+        let edited = CONFIG.replace("npm update -g", "npm i -g x")
+            + "  - id: pipx\n    update: pipx upgrade-all\n";
+        let edited = command_hashes(&config(&edited));
+        let changes = approvals.unconfirmed_commands("updog.yaml", &edited);
+        let shown: Vec<String> = changes.iter().map(ToString::to_string).collect();
+        assert_eq!(shown, ["npm (changed)", "pipx (new)"]);
+        // Still unconfirmed until confirmed
+        assert_eq!(
+            approvals.unconfirmed_commands("updog.yaml", &edited).len(),
+            2
+        );
+        approvals.confirm_commands("updog.yaml", &edited);
+        assert!(approvals
+            .unconfirmed_commands("updog.yaml", &edited)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_confirm() {
+        let config = config(CONFIG);
+        let changes = vec![CommandChange {
+            manager: "apt".to_string(),
+            new: false,
+            hash: "abc".to_string(),
+        }];
+        let mut output = Vec::new();
+        let yes = confirm(&config, &changes, &mut Cursor::new("y\n"), &mut output).unwrap();
+        assert!(yes);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "These commands are new or changed since they were last confirmed:\n  \
+             apt (changed)\n    \
+             check: apt list --upgradable\n    \
+             update: sudo apt upgrade -y\n\
+             Run them? [y/N] "
+        );
+        for answer in ["\n", "n\n", ""] {
+            let mut output = Vec::new();
+            assert!(!confirm(&config, &changes, &mut Cursor::new(answer), &mut output).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("updog").join("approved.yaml");
+        assert_eq!(Approvals::load(&path).unwrap(), Approvals::default());
+
+        let mut approvals = Approvals::default();
+        approvals.approve("config.yaml", "abc", UNIX_EPOCH);
+        approvals.save(&path).unwrap();
+        assert_eq!(Approvals::load(&path).unwrap(), approvals);
+    }
+}
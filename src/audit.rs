@@ -0,0 +1,297 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+use crate::executor::{Clock, CommandOutput, CommandRequest, Executor};
+use crate::hash::sha256_hex;
+use crate::history::unix_seconds;
+use crate::metadata;
+use crate::package_manager::UpdateError;
+use crate::state_file::StateLock;
+
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error("Failed to access audit log: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Failed to parse audit log line {line}: {source}")]
+    ParseError {
+        line: usize,
+        source: serde_json::Error,
+    },
+    #[error("Audit log hash chain is broken at line {0}")]
+    BrokenChain(usize),
+}
+
+// Append-only record of every command updog actually runs, kept regardless
+// of the log level
+//
+//   audit:
+//     enabled: true
+//     path: /var/log/updog/audit.log   # default: <data dir>/updog/audit.log
+//     hash_chain: true                 # make edits detectable by `updog audit verify`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AuditConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    #[serde(default)]
+    pub hash_chain: bool,
+}
+
+impl AuditConfig {
+    pub fn path(&self) -> PathBuf {
+        self.path.clone().unwrap_or_else(default_path)
+    }
+}
+
+// Default location: <data dir>/updog/audit.log
+pub fn default_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("updog")
+        .join("audit.log")
+}
+
+// One executed command, stored as a line of JSON
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditEntry {
+    pub command: String,
+    // Run the command was part of (UPDOG_RUN_ID)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    pub started_at: u64,
+    pub ended_at: u64,
+    // None when the command was killed by a signal or couldn't be started
+    pub exit_code: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    // SHA-256 of the previous line, with hash chaining
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev_hash: Option<String>,
+}
+
+// Appends entries to the audit file
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+    hash_chain: bool,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf, hash_chain: bool) -> Self {
+        Self { path, hash_chain }
+    }
+
+    pub fn from_config(config: &AuditConfig) -> Option<Self> {
+        config
+            .enabled
+            .then(|| Self::new(config.path(), config.hash_chain))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, mut entry: AuditEntry) -> Result<(), AuditError> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Held from reading the last line to appending after it
+        let _lock = StateLock::exclusive(&self.path)?;
+        if self.hash_chain {
+            // Read back the last line rather than remembering it, so that
+            // several updog processes writing the same log keep one chain
+            entry.prev_hash = match fs::read_to_string(&self.path) {
+                Ok(content) => content
+                    .lines()
+                    .last()
+                    .map(|line| sha256_hex(line.as_bytes())),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e.into()),
+            };
+        }
+        let mut line = serde_json::to_string(&entry).expect("audit entry serializes");
+        line.push('\n');
+
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        // A single write keeps concurrent appends from interleaving
+        options.open(&self.path)?.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+// Check that every line is an entry and, from the first chained entry on,
+// that each one carries the hash of the line before it. Returns the number
+// of entries.
+pub fn verify(path: &Path) -> Result<usize, AuditError> {
+    let content = fs::read_to_string(path)?;
+    let mut previous: Option<&str> = None;
+    let mut chained = false;
+    for (index, line) in content.lines().enumerate() {
+        let entry: AuditEntry =
+            serde_json::from_str(line).map_err(|source| AuditError::ParseError {
+                line: index + 1,
+                source,
+            })?;
+        chained |= entry.prev_hash.is_some();
+        if chained && entry.prev_hash != previous.map(|p| sha256_hex(p.as_bytes())) {
+            return Err(AuditError::BrokenChain(index + 1));
+        }
+        previous = Some(line);
+    }
+    Ok(content.lines().count())
+}
+
+// Executor that records every command the wrapped executor runs
+pub struct AuditingExecutor {
+    inner: Arc<dyn Executor>,
+    clock: Arc<dyn Clock>,
+    log: AuditLog,
+    // Serializes appends from parallel runs, keeping the chain intact
+    lock: Mutex<()>,
+}
+
+impl AuditingExecutor {
+    pub fn new(inner: Arc<dyn Executor>, clock: Arc<dyn Clock>, log: AuditLog) -> Self {
+        Self {
+            inner,
+            clock,
+            log,
+            lock: Mutex::new(()),
+        }
+    }
+}
+
+impl Executor for AuditingExecutor {
+    fn run(&self, request: &CommandRequest) -> Result<CommandOutput, UpdateError> {
+        let started_at = self.clock.now();
+        let result = self.inner.run(request);
+        let entry = AuditEntry {
+            command: request.command.clone(),
+            run_id: request.run_id().map(str::to_string),
+            cwd: std::env::current_dir()
+                .ok()
+                .map(|dir| dir.display().to_string()),
+            user: metadata::username(),
+            started_at: unix_seconds(started_at),
+            ended_at: unix_seconds(self.clock.now()),
+            exit_code: result.as_ref().ok().and_then(|output| output.exit_code),
+            error: result.as_ref().err().map(|e| e.message.clone()),
+            prev_hash: None,
+        };
+
+        let _guard = self.lock.lock().unwrap();
+        if let Err(e) = self.log.append(entry) {
+            tracing::warn!("Failed to write audit log {:?}: {}", self.log.path(), e);
+        }
+        result
+    }
+
+    fn cleanup(&self) {
+        self.inner.cleanup();
+    }
+
+    fn interrupt(&self) {
+        self.inner.interrupt();
+    }
+
+    fn shutdown_requested(&self) -> bool {
+        self.inner.shutdown_requested()
+    }
+
+    fn finds(&self, program: &str) -> bool {
+        self.inner.finds(program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::RUN_ID_ENV;
+    use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    fn entry(command: &str) -> AuditEntry {
+        AuditEntry {
+            command: command.to_string(),
+            run_id: None,
+            cwd: None,
+            user: None,
+            started_at: 0,
+            ended_at: 0,
+            exit_code: Some(0),
+            error: None,
+            prev_hash: None,
+        }
+    }
+
+    #[test]
+    fn test_executor_records_commands() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let clock = Arc::new(FakeClock::new());
+        let inner = Arc::new(ScriptedExecutor::new(clock.clone()));
+        inner
+            .script(
+                "apt upgrade",
+                ScriptedCommand::success().delay(Duration::from_secs(5)),
+            )
+            .script("false", ScriptedCommand::exit(1));
+        let executor =
+            AuditingExecutor::new(inner, clock.clone(), AuditLog::new(path.clone(), false));
+
+        let mut request = CommandRequest::new("apt upgrade");
+        request.env = vec![(RUN_ID_ENV.to_string(), "3f2a9c1e".to_string())];
+        executor.run(&request).unwrap();
+        executor.run(&CommandRequest::new("false")).unwrap();
+
+        let entries: Vec<AuditEntry> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].command, "apt upgrade");
+        assert_eq!(entries[0].ended_at - entries[0].started_at, 5);
+        assert_eq!(entries[0].run_id.as_deref(), Some("3f2a9c1e"));
+        assert_eq!(entries[1].exit_code, Some(1));
+        assert_eq!(entries[1].run_id, None);
+        assert!(entries[1].prev_hash.is_none());
+        assert_eq!(verify(&path).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_hash_chain_detects_edits() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let log = AuditLog::new(path.clone(), true);
+        for command in ["one", "two", "three"] {
+            log.append(entry(command)).unwrap();
+        }
+        assert_eq!(verify(&path).unwrap(), 3);
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::write(&path, content.replacen("\"two\"", "\"rm -rf /\"", 1)).unwrap();
+        assert!(matches!(verify(&path), Err(AuditError::BrokenChain(3))));
+
+        // Deleting a line breaks the chain as well
+        let lines: Vec<&str> = content.lines().collect();
+        fs::write(&path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        assert!(matches!(verify(&path), Err(AuditError::BrokenChain(2))));
+    }
+}
@@ -0,0 +1,213 @@
+use serde::Serialize;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::state_file::write_atomic;
+use crate::stats::ManagerStatus;
+use crate::timezone::Timezone;
+
+// Pending update totals for status bars and dashboards, written by
+// `--write-badge` as JSON, or as an SVG badge when the path ends in .svg
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Badge {
+    pub pending: usize,
+    pub failed: usize,
+    // Newest run, in seconds since the Unix epoch
+    pub updated_at: Option<u64>,
+    // The same time as a human readable string
+    pub updated: Option<String>,
+}
+
+impl Badge {
+    pub fn from_status(statuses: &[ManagerStatus], timezone: Timezone) -> Self {
+        let updated_at = statuses.iter().filter_map(|s| s.last_run).max();
+        Self {
+            pending: statuses.iter().filter_map(|s| s.pending).sum(),
+            failed: statuses.iter().filter(|s| s.failed).count(),
+            updated_at,
+            updated: updated_at.map(|secs| {
+                timezone
+                    .civil(UNIX_EPOCH + Duration::from_secs(secs))
+                    .to_string()
+            }),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = serde_json::to_string(self).expect("badge serializes");
+        json.push('\n');
+        json
+    }
+
+    // Flat two-part badge: "updates | 3"
+    pub fn to_svg(&self) -> String {
+        let (value, color) = if self.failed > 0 {
+            (format!("{} failed", self.failed), "#e05d44")
+        } else if self.pending > 0 {
+            (self.pending.to_string(), "#fe7d37")
+        } else {
+            ("up to date".to_string(), "#4c1")
+        };
+        let label = "updates";
+        // Rough width of Verdana 11px text
+        let text_width = |text: &str| text.chars().count() * 7 + 10;
+        let (left, right) = (text_width(label), text_width(&value));
+        let width = left + right;
+        let title = match &self.updated {
+            Some(updated) => format!("{}: {} (as of {})", label, value, updated),
+            None => format!("{}: {}", label, value),
+        };
+
+        format!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{title}">
+  <title>{title}</title>
+  <rect width="{left}" height="20" fill="#555"/>
+  <rect x="{left}" width="{right}" height="20" fill="{color}"/>
+  <g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,sans-serif" font-size="11">
+    <text x="{label_x}" y="14">{label}</text>
+    <text x="{value_x}" y="14">{value}</text>
+  </g>
+</svg>
+"##,
+            label_x = left / 2,
+            value_x = left + right / 2,
+        )
+    }
+
+    // Write atomically, so a status bar never reads a half-written file
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let content = match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("svg") => self.to_svg(),
+            _ => self.to_json(),
+        };
+        write_atomic(path, content.as_bytes())
+    }
+}
+
+// Status bar state, also used as waybar's CSS class
+fn state_class(badge: &Badge) -> &'static str {
+    if badge.failed > 0 {
+        "failed"
+    } else if badge.pending > 0 {
+        "pending"
+    } else {
+        "ok"
+    }
+}
+
+// One line per manager, for tooltips
+fn tooltip(statuses: &[ManagerStatus], badge: &Badge) -> String {
+    let mut lines: Vec<String> = statuses
+        .iter()
+        .filter(|s| s.last_run.is_some())
+        .map(|s| match (s.failed, s.pending) {
+            (true, _) => format!("{}: failed", s.name),
+            (false, Some(count)) => format!("{}: {} pending", s.name, count),
+            (false, None) => format!("{}: {}", s.name, s.last_status.as_deref().unwrap_or("-")),
+        })
+        .collect();
+    match &badge.updated {
+        Some(updated) => lines.push(format!("Last run: {}", updated)),
+        None => lines.push("No runs recorded yet".to_string()),
+    }
+    lines.join("\n")
+}
+
+// Custom module output for waybar (`"return-type": "json"`)
+pub fn waybar(statuses: &[ManagerStatus], timezone: Timezone) -> String {
+    #[derive(Serialize)]
+    struct Module<'a> {
+        text: String,
+        alt: &'a str,
+        tooltip: String,
+        class: &'a str,
+    }
+
+    let badge = Badge::from_status(statuses, timezone);
+    let module = Module {
+        text: badge.pending.to_string(),
+        alt: state_class(&badge),
+        tooltip: tooltip(statuses, &badge),
+        class: state_class(&badge),
+    };
+    let mut json = serde_json::to_string(&module).expect("module serializes");
+    json.push('\n');
+    json
+}
+
+// Single line for a polybar `custom/script` module
+pub fn polybar(statuses: &[ManagerStatus], timezone: Timezone) -> String {
+    let badge = Badge::from_status(statuses, timezone);
+    match badge.failed {
+        0 => format!("{}\n", badge.pending),
+        failed => format!("{} ({} failed)\n", badge.pending, failed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn status(name: &str, pending: Option<usize>, failed: bool, last_run: u64) -> ManagerStatus {
+        ManagerStatus {
+            name: name.to_string(),
+            last_run: Some(last_run),
+            last_status: None,
+            failed,
+            pending,
+            failure_streak: usize::from(failed),
+            columns: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_badge_totals() {
+        let statuses = [
+            status("brew", Some(3), false, 60),
+            status("npm", None, true, 120),
+            status("cargo", Some(1), false, 30),
+        ];
+        let badge = Badge::from_status(&statuses, Timezone::Utc);
+        assert_eq!(
+            badge.to_json(),
+            "{\"pending\":4,\"failed\":1,\"updated_at\":120,\"updated\":\"1970-01-01 00:02 +00:00\"}\n"
+        );
+        assert!(badge.to_svg().contains(">1 failed</text>"));
+    }
+
+    #[test]
+    fn test_write_picks_format_from_extension() {
+        let dir = tempdir().unwrap();
+        let badge = Badge::from_status(&[status("brew", Some(2), false, 0)], Timezone::Utc);
+
+        let svg = dir.path().join("badge.svg");
+        badge.write(&svg).unwrap();
+        let content = fs::read_to_string(&svg).unwrap();
+        assert!(content.starts_with("<svg"));
+        assert!(content.contains(">2</text>"));
+
+        let json = dir.path().join("nested").join("updog.json");
+        badge.write(&json).unwrap();
+        assert!(fs::read_to_string(&json).unwrap().contains("\"pending\":2"));
+    }
+
+    #[test]
+    fn test_waybar_module() {
+        let statuses = [
+            status("brew", Some(3), false, 60),
+            status("npm", None, true, 120),
+        ];
+        assert_eq!(
+            waybar(&statuses, Timezone::Utc),
+            "{\"text\":\"3\",\"alt\":\"failed\",\"tooltip\":\"brew: 3 pending\\nnpm: failed\\nLast run: 1970-01-01 00:02 +00:00\",\"class\":\"failed\"}\n"
+        );
+        assert_eq!(polybar(&statuses, Timezone::Utc), "3 (1 failed)\n");
+
+        let idle = waybar(&[], Timezone::Utc);
+        assert!(idle.contains("\"class\":\"ok\""));
+        assert!(idle.contains("No runs recorded yet"));
+    }
+}
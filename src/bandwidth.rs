@@ -0,0 +1,202 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::cron::CronSchedule;
+use crate::disk::ByteSize;
+use crate::lint;
+use crate::shell::Shell;
+use crate::step::shell_quote;
+use crate::timezone::Timezone;
+
+// Download rate a manager's commands are held to, in bytes per second:
+//
+//   commands:
+//     - id: apt
+//       update: sudo apt-get upgrade -y
+//       bandwidth_limit: 2M
+//     - id: steam
+//       update: steamcmd +app_update 740 +quit
+//       bandwidth_limit: { rate: 500K, during: "* 9-17 * * MON-FRI" }
+//
+// Tools with a rate limit option of their own get it added; other commands
+// run under trickle, when it's installed. `during` is a cron expression
+// (evaluated in the daemon's timezone) for the minutes the limit applies in.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BandwidthLimit {
+    pub rate: ByteSize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub during: Option<CronSchedule>,
+}
+
+impl<'de> Deserialize<'de> for BandwidthLimit {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Limit {
+            Rate(ByteSize),
+            Full {
+                rate: ByteSize,
+                #[serde(default)]
+                during: Option<CronSchedule>,
+            },
+        }
+
+        Ok(match Limit::deserialize(deserializer)? {
+            Limit::Rate(rate) => BandwidthLimit { rate, during: None },
+            Limit::Full { rate, during } => BandwidthLimit { rate, during },
+        })
+    }
+}
+
+// The rate limit option of the tool `name`, if it has one, added after
+// the program name
+fn native_option(name: &str, kib: u64) -> Option<String> {
+    Some(match name {
+        "apt" | "apt-get" => format!("-o Acquire::http::Dl-Limit={}", kib),
+        "dnf" | "yum" => format!("--setopt=throttle={}k", kib),
+        "curl" => format!("--limit-rate {}K", kib),
+        "wget" => format!("--limit-rate={}k", kib),
+        "rsync" => format!("--bwlimit={}", kib),
+        "aria2c" => format!("--max-overall-download-limit={}K", kib),
+        _ => return None,
+    })
+}
+
+impl BandwidthLimit {
+    pub fn validate(&self) -> Result<(), String> {
+        match self.rate.0 < 1024 {
+            true => Err(format!(
+                "bandwidth_limit {} is below the smallest limit (1K)",
+                self.rate
+            )),
+            false => Ok(()),
+        }
+    }
+
+    // Whether the limit is in force at `time`
+    pub fn applies_at(&self, time: SystemTime, timezone: Timezone) -> bool {
+        self.during
+            .as_ref()
+            .is_none_or(|during| during.matches(time, timezone))
+    }
+
+    // `command` held to the rate, or why it can't be. A single command of a
+    // tool with a rate limit option gets it; anything else runs under
+    // trickle through `trickle_shell` (None when trickle can't be used).
+    // trickle works by preloading a library, which sudo drops.
+    pub fn apply(&self, command: &str, trickle_shell: Option<Shell>) -> Result<String, String> {
+        let kib = self.rate.0 / 1024;
+        let program = lint::program(command);
+        let native = program.and_then(|program| {
+            let name = Path::new(program).file_name()?.to_str()?;
+            native_option(name, kib)
+        });
+        if let (Some(program), Some(option), false) = (program, native, is_compound(command)) {
+            let end = program_end(command, program);
+            return Ok(format!("{} {}{}", &command[..end], option, &command[end..]));
+        }
+        let Some(shell) = trickle_shell else {
+            return Err(format!(
+                "can't limit the bandwidth of '{}' (no rate limit option, and trickle isn't available)",
+                command
+            ));
+        };
+        if lint::uses_sudo(command) {
+            return Err(format!(
+                "can't limit the bandwidth of '{}' (trickle doesn't work through sudo)",
+                command
+            ));
+        }
+        Ok(format!(
+            "trickle -s -d {} {} -c {}",
+            kib,
+            shell.program(),
+            shell_quote(command)
+        ))
+    }
+}
+
+// Whether `command` is more than one simple command, so that an option
+// added to its first program wouldn't cover the rest
+fn is_compound(command: &str) -> bool {
+    command.contains(['|', ';', '&', '\n', '`', '(']) || command.contains("$(")
+}
+
+// Byte offset just past the word `program` of `command`
+fn program_end(command: &str, program: &str) -> usize {
+    let mut offset = 0;
+    for word in command.split_whitespace() {
+        let start = offset + command[offset..].find(word).unwrap_or(0);
+        offset = start + word.len();
+        if word == program {
+            break;
+        }
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(yaml: &str) -> BandwidthLimit {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_native_options() {
+        let limit = limit("2M");
+        assert_eq!(
+            limit.apply("sudo apt-get upgrade -y", None).unwrap(),
+            "sudo apt-get -o Acquire::http::Dl-Limit=2048 upgrade -y"
+        );
+        assert_eq!(
+            limit.apply("DNF_VAR=1 /usr/bin/dnf upgrade", None).unwrap(),
+            "DNF_VAR=1 /usr/bin/dnf --setopt=throttle=2048k upgrade"
+        );
+        assert_eq!(
+            limit
+                .apply("curl -fsSLO https://example.com/x", None)
+                .unwrap(),
+            "curl --limit-rate 2048K -fsSLO https://example.com/x"
+        );
+    }
+
+    #[test]
+    fn test_trickle() {
+        let limit = limit("{ rate: 512K }");
+        assert_eq!(
+            limit.apply("brew upgrade", Some(Shell::Bash)).unwrap(),
+            "trickle -s -d 512 bash -c 'brew upgrade'"
+        );
+        // Only the first tool would get the option
+        assert_eq!(
+            limit
+                .apply("apt-get update && apt-get upgrade -y", Some(Shell::Sh))
+                .unwrap(),
+            "trickle -s -d 512 sh -c 'apt-get update && apt-get upgrade -y'"
+        );
+        assert!(limit.apply("brew upgrade", None).is_err());
+        let err = limit
+            .apply("sudo snap refresh", Some(Shell::Bash))
+            .unwrap_err();
+        assert!(err.contains("sudo"), "{}", err);
+    }
+
+    #[test]
+    fn test_during() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        // 2024-01-01T10:00:00Z was a Monday morning
+        let monday = UNIX_EPOCH + Duration::from_secs(1_704_103_200);
+        let office = limit(r#"{ rate: 1M, during: "* 9-17 * * MON-FRI" }"#);
+        assert!(office.applies_at(monday, Timezone::Utc));
+        assert!(!office.applies_at(monday + Duration::from_secs(5 * 86_400), Timezone::Utc));
+        assert!(limit("1M").applies_at(monday, Timezone::Utc));
+        assert!(limit("512B").validate().is_err());
+    }
+}
@@ -0,0 +1,409 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+use crate::config::{Config, Verbosity};
+use crate::hash::{to_hex, Sha256};
+use crate::history::unix_seconds;
+use crate::output::summary_columns;
+use crate::report::{ExecutionReport, ExecutionResult, ExecutionStatus, Operation};
+use crate::state_file::{write_atomic, StateLock};
+use crate::stats::format_duration;
+
+#[derive(Debug, Error)]
+pub enum CacheError {
+    #[error("Failed to access check cache: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Failed to parse check cache: {0}")]
+    ParseError(#[from] serde_yaml::Error),
+}
+
+// Latest successful check of every manager with a `cooldown`, reused by
+// `updog check` until the cooldown has passed so that prompts and status bars
+// can call it often without hitting the package servers each time:
+//
+//   commands:
+//     - id: homebrew
+//       check: brew outdated
+//       cooldown: 1h
+//
+// Entries are keyed by the manager and the hash of its config entry and
+// check command, so editing the manager invalidates its entry. An update of
+// the manager drops it too; `updog check --refresh` ignores the cache.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CheckCache {
+    // By display name ("manager" or "manager:subcommand")
+    #[serde(default)]
+    pub managers: BTreeMap<String, CachedCheck>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedCheck {
+    // Seconds since the Unix epoch
+    pub checked_at: u64,
+    // `command_hash` of the check; a cache written before it was recorded
+    // never matches
+    #[serde(default)]
+    pub command_hash: String,
+    pub pending: usize,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub output: String,
+}
+
+impl CheckCache {
+    // Default location: <data dir>/updog/check_cache.yaml
+    pub fn default_path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("updog")
+            .join("check_cache.yaml")
+    }
+
+    // A missing file is an empty cache
+    pub fn load(path: &Path) -> Result<Self, CacheError> {
+        match fs::read_to_string(path) {
+            Ok(content) if content.trim().is_empty() => Ok(Self::default()),
+            Ok(content) => Ok(serde_yaml::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), CacheError> {
+        write_atomic(path, serde_yaml::to_string(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    // Load, change and save the cache at `path` while holding its lock, so
+    // concurrent runs don't drop each other's entries. `change` returns
+    // whether there is anything to save.
+    pub fn update(path: &Path, change: impl FnOnce(&mut Self) -> bool) -> Result<(), CacheError> {
+        let _lock = StateLock::exclusive(path)?;
+        let mut cache = Self::load(path)?;
+        if change(&mut cache) {
+            cache.save(path)?;
+        }
+        Ok(())
+    }
+
+    // The cached check of the manager, if it's younger than the manager's
+    // `cooldown` and ran with the command the config resolves to now
+    pub fn fresh(
+        &self,
+        config: &Config,
+        manager: &str,
+        subcommand: Option<&str>,
+        verbosity: Verbosity,
+        now: SystemTime,
+    ) -> Option<ExecutionResult> {
+        let cooldown = cooldown(config, manager)?;
+        let cached = self.managers.get(&display_name(manager, subcommand))?;
+        if command_hash(config, manager, subcommand, verbosity)? != cached.command_hash {
+            return None;
+        }
+        let age = Duration::from_secs(unix_seconds(now).checked_sub(cached.checked_at)?);
+        if age >= cooldown {
+            return None;
+        }
+        let (status, message) = match cached.pending {
+            0 => (
+                ExecutionStatus::Success,
+                "Successfully checked for updates".to_string(),
+            ),
+            count => (
+                ExecutionStatus::UpdatesAvailable { count },
+                format!("{} updates available", count),
+            ),
+        };
+        Some(ExecutionResult {
+            manager: manager.to_string(),
+            subcommand: subcommand.map(str::to_string),
+            status,
+            message: format!("{} (cached, checked {} ago)", message, format_duration(age)),
+            duration: Duration::ZERO,
+            output: cached.output.clone(),
+            steps: Vec::new(),
+            exit_code: None,
+            upgraded: None,
+            cached: true,
+            reboot_required: false,
+            cleaned: Vec::new(),
+            warnings: Vec::new(),
+            columns: config
+                .find_package_manager(manager)
+                .map(|m| summary_columns(&m.summary_columns, &cached.output))
+                .unwrap_or_default(),
+        })
+    }
+
+    // Remember a check that ran at `now` with `verbosity`; failed checks
+    // aren't cached. Returns whether it was remembered.
+    pub fn record(
+        &mut self,
+        config: &Config,
+        result: &ExecutionResult,
+        verbosity: Verbosity,
+        now: SystemTime,
+    ) -> bool {
+        let Some(pending) = result.pending().filter(|_| !result.cached) else {
+            return false;
+        };
+        let Some(command_hash) = command_hash(
+            config,
+            &result.manager,
+            result.subcommand.as_deref(),
+            verbosity,
+        ) else {
+            return false;
+        };
+        self.managers.insert(
+            result.name(),
+            CachedCheck {
+                checked_at: unix_seconds(now),
+                command_hash,
+                pending,
+                output: result.output.clone(),
+            },
+        );
+        true
+    }
+
+    // Remember the checks in `report` of managers with a `cooldown`, and
+    // forget the ones whose managers it updated. Returns whether anything
+    // changed.
+    pub fn apply(
+        &mut self,
+        config: &Config,
+        report: &ExecutionReport,
+        verbosity: Verbosity,
+        now: SystemTime,
+    ) -> bool {
+        let mut changed = false;
+        for result in &report.results {
+            if result.cached || cooldown(config, &result.manager).is_none() {
+                continue;
+            }
+            match report.operation {
+                Operation::Check => changed |= self.record(config, result, verbosity, now),
+                Operation::Update => changed |= self.forget(&result.manager),
+            }
+        }
+        changed
+    }
+
+    // Drop the entries of `manager` and its subcommands (e.g. after an update).
+    // Returns whether anything was dropped.
+    pub fn forget(&mut self, manager: &str) -> bool {
+        let before = self.managers.len();
+        let prefix = format!("{}:", manager);
+        self.managers
+            .retain(|name, _| name != manager && !name.starts_with(&prefix));
+        self.managers.len() != before
+    }
+}
+
+// How long checks of `manager` are reused, if they are
+pub fn cooldown(config: &Config, manager: &str) -> Option<Duration> {
+    config
+        .find_package_manager(manager)
+        .and_then(|m| m.cooldown)
+        .map(|interval| interval.duration())
+}
+
+// Identifies what a check ran: the manager's whole config entry (so editing
+// its env, parser, filter or cooldown invalidates the cache too) and the check
+// command resolved for the subcommand and verbosity
+pub fn command_hash(
+    config: &Config,
+    manager: &str,
+    subcommand: Option<&str>,
+    verbosity: Verbosity,
+) -> Option<String> {
+    let entry = config.find_package_manager(manager)?;
+    let command = entry.find_subcommand_command(subcommand)?;
+    let mut hasher = Sha256::new();
+    hasher.update(serde_yaml::to_string(entry).ok()?.as_bytes());
+    hasher.update(b"\0");
+    hasher.update(
+        serde_yaml::to_string(command.check_for(verbosity)?)
+            .ok()?
+            .as_bytes(),
+    );
+    Some(to_hex(&hasher.finalize()))
+}
+
+fn display_name(manager: &str, subcommand: Option<&str>) -> String {
+    match subcommand {
+        Some(sc) => format!("{}:{}", manager, sc),
+        None => manager.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+    use tempfile::tempdir;
+
+    const CONFIG: &str = r#"
+        commands:
+          - id: brew
+            cooldown: 1h
+            subcommands:
+              - id: default
+                check: brew outdated
+                check_verbose: brew outdated --verbose
+              - id: cask
+                check: brew outdated --cask
+          - id: brewery
+            check: brewery outdated
+            cooldown: 1h
+          - id: npm
+            check: npm outdated
+        "#;
+
+    fn config() -> Config {
+        serde_yaml::from_str(CONFIG).unwrap()
+    }
+
+    fn checked(manager: &str, subcommand: Option<&str>, count: usize) -> ExecutionResult {
+        ExecutionResult {
+            manager: manager.to_string(),
+            subcommand: subcommand.map(str::to_string),
+            status: ExecutionStatus::UpdatesAvailable { count },
+            message: format!("{} updates available", count),
+            duration: Duration::from_secs(3),
+            output: "git 2.40 -> 2.41\n".to_string(),
+            steps: Vec::new(),
+            exit_code: None,
+            upgraded: None,
+            cached: false,
+            reboot_required: false,
+            cleaned: Vec::new(),
+            warnings: Vec::new(),
+            columns: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_fresh_within_cooldown() {
+        let config = config();
+        let normal = Verbosity::Normal;
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let hour = Duration::from_secs(3600);
+        let mut cache = CheckCache::default();
+        assert!(cache.record(&config, &checked("brew", None, 1), normal, now));
+
+        let later = now + Duration::from_secs(600);
+        let result = cache.fresh(&config, "brew", None, normal, later).unwrap();
+        assert!(result.cached);
+        assert_eq!(
+            result.status,
+            ExecutionStatus::UpdatesAvailable { count: 1 }
+        );
+        assert_eq!(
+            result.message,
+            "1 updates available (cached, checked 10m00s ago)"
+        );
+        assert_eq!(result.output, "git 2.40 -> 2.41\n");
+
+        assert!(cache
+            .fresh(&config, "brew", None, normal, now + hour)
+            .is_none());
+        assert!(cache
+            .fresh(&config, "brew", Some("cask"), normal, later)
+            .is_none());
+        // A clock that went backwards doesn't make old results fresh forever
+        assert!(cache
+            .fresh(&config, "brew", None, normal, UNIX_EPOCH)
+            .is_none());
+
+        // Reused results aren't recorded again, failures aren't recorded at all
+        assert!(!cache.record(&config, &result, normal, later));
+        let mut failed = checked("brewery", None, 0);
+        failed.status = ExecutionStatus::Failed;
+        assert!(!cache.record(&config, &failed, normal, later));
+        assert_eq!(cache.managers.len(), 1);
+        assert_eq!(cache.managers["brew"].checked_at, 1_000_000);
+    }
+
+    #[test]
+    fn test_changed_command_invalidates() {
+        let normal = Verbosity::Normal;
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let mut cache = CheckCache::default();
+        cache.record(&config(), &checked("brew", None, 1), normal, now);
+        cache.record(&config(), &checked("brewery", None, 1), normal, now);
+        assert!(cache.fresh(&config(), "brew", None, normal, now).is_some());
+
+        // A different verbosity resolves to a different command
+        assert!(cache
+            .fresh(&config(), "brew", None, Verbosity::Verbose, now)
+            .is_none());
+
+        // Editing an entry only invalidates that entry
+        let edited: Config =
+            serde_yaml::from_str(&CONFIG.replace("brew outdated\n", "brew outdated --greedy\n"))
+                .unwrap();
+        assert!(cache.fresh(&edited, "brew", None, normal, now).is_none());
+        assert!(cache.fresh(&edited, "brewery", None, normal, now).is_some());
+        let cooler: Config =
+            serde_yaml::from_str(&CONFIG.replacen("cooldown: 1h", "cooldown: 2h", 1)).unwrap();
+        assert!(cache.fresh(&cooler, "brew", None, normal, now).is_none());
+
+        // Caches written before hashes were recorded are stale
+        let mut old = cache.clone();
+        old.managers
+            .get_mut("brewery")
+            .unwrap()
+            .command_hash
+            .clear();
+        assert!(old.fresh(&config(), "brewery", None, normal, now).is_none());
+    }
+
+    #[test]
+    fn test_apply_report() {
+        let config = config();
+        let normal = Verbosity::Normal;
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let mut cache = CheckCache::default();
+        let mut report = ExecutionReport::new(Operation::Check, now);
+        report.results = vec![checked("brew", None, 1), checked("npm", None, 2)];
+        assert!(cache.apply(&config, &report, normal, now));
+        let names: Vec<&String> = cache.managers.keys().collect();
+        assert_eq!(names, ["brew"]);
+
+        // Reusing the cached check changes nothing, updating the manager drops it
+        report.results = vec![cache.fresh(&config, "brew", None, normal, now).unwrap()];
+        assert!(!cache.apply(&config, &report, normal, now));
+        let mut update = ExecutionReport::new(Operation::Update, now);
+        update.results = vec![checked("brew", None, 0)];
+        assert!(cache.apply(&config, &update, normal, now));
+        assert!(cache.managers.is_empty());
+    }
+
+    #[test]
+    fn test_forget_and_roundtrip() {
+        let config = config();
+        let normal = Verbosity::Normal;
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let mut cache = CheckCache::default();
+        cache.record(&config, &checked("brew", None, 1), normal, now);
+        cache.record(&config, &checked("brew", Some("cask"), 2), normal, now);
+        cache.record(&config, &checked("brewery", None, 3), normal, now);
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("updog/check_cache.yaml");
+        assert_eq!(CheckCache::load(&path).unwrap(), CheckCache::default());
+        cache.save(&path).unwrap();
+        assert_eq!(CheckCache::load(&path).unwrap(), cache);
+
+        assert!(cache.forget("brew"));
+        assert!(!cache.forget("brew"));
+        let names: Vec<&String> = cache.managers.keys().collect();
+        assert_eq!(names, ["brewery"]);
+    }
+}
@@ -1,5 +1,12 @@
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use clap::{Args, Parser, Subcommand};
+use std::path::{Path, PathBuf};
+
+use crate::config::Verbosity;
+use crate::export::ExportFormat;
+use crate::progress::ProgressFormat;
+use crate::report::OutputFormat;
+use crate::schedule::Interval;
+use crate::selector::{ExecutionOrder, Selection};
 
 #[derive(Parser)]
 #[command(author, version, about)]
@@ -8,10 +15,23 @@ pub struct Cli {
     #[arg(short, long, value_name = "FILE")]
     pub config: Option<PathBuf>,
 
-    /// Enable verbose logging
+    /// Enable verbose logging (and the managers' verbose command variants)
     #[arg(short, long)]
     pub verbose: bool,
 
+    /// Only log warnings and errors (and use the managers' quiet command variants)
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Don't offer to create a config interactively when none exists
+    #[arg(long, global = true)]
+    pub no_wizard: bool,
+
+    /// Run with the default configuration when the config file doesn't load,
+    /// instead of stopping
+    #[arg(long, global = true)]
+    pub ignore_config_errors: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -20,66 +40,724 @@ pub struct Cli {
 pub enum Commands {
     /// Check for available updates
     Check {
-        /// Specific package manager to check (format: manager[:subcommand])
+        /// Package manager, group, @tag or glob to check (format: manager[:subcommand])
         package_manager: Option<String>,
 
+        #[command(flatten)]
+        selection: SelectionArgs,
+
+        /// Order to run managers in: config, alphabetical, or shortest or
+        /// longest typical duration (from history) first
+        #[arg(long, value_name = "ORDER", default_value = "config")]
+        order: ExecutionOrder,
+
         /// Show what would be done without actually doing it
         #[arg(long)]
         dry_run: bool,
+
+        /// Check N managers at once (default: `concurrency.checks`)
+        #[arg(long, short = 'j', value_name = "N")]
+        jobs: Option<usize>,
+
+        /// Only print the number of pending updates
+        #[arg(long)]
+        count: bool,
+
+        /// With --count, print one count per package manager
+        #[arg(long, requires = "count")]
+        by_manager: bool,
+
+        /// Run the managers an interrupted run didn't finish (see `updog status`)
+        #[arg(long, conflicts_with_all = ["package_manager", "tags", "except"])]
+        resume: bool,
+
+        /// Run every check, even for managers whose `cooldown` hasn't passed
+        #[arg(long)]
+        refresh: bool,
+
+        /// Write pending update totals to this file (JSON, or an SVG badge for *.svg)
+        #[arg(long, value_name = "PATH")]
+        write_badge: Option<PathBuf>,
+
+        /// Skip the run while `updog pause` is in effect (for timers and cron jobs)
+        #[arg(long)]
+        scheduled: bool,
+
+        /// Refuse to run if the config changed since `updog config approve`
+        #[arg(long, requires = "scheduled")]
+        require_approval: bool,
+
+        /// Annotate the run in history (e.g. "before upgrading to macOS 15")
+        #[arg(long, value_name = "TEXT")]
+        note: Option<String>,
+
+        /// Print progress lines on stderr: "plain" text or "json", one object per line
+        #[arg(long, value_name = "FORMAT")]
+        progress: Option<ProgressFormat>,
+
+        /// Also write the summary to this file as JSON, for wrapper scripts
+        #[arg(long, value_name = "PATH")]
+        summary_file: Option<PathBuf>,
+
+        /// Print the summary as "text", or as "json" or "yaml" for scripts
+        /// (commands' output then only appears in it)
+        #[arg(
+            long,
+            value_name = "FORMAT",
+            default_value = "text",
+            conflicts_with = "count"
+        )]
+        output: OutputFormat,
     },
 
     /// Perform updates
     Update {
-        /// Specific package manager to update (format: manager[:subcommand])
+        /// Package manager, group, @tag or glob to update (format: manager[:subcommand])
+        package_manager: Option<String>,
+
+        #[command(flatten)]
+        selection: SelectionArgs,
+
+        /// Order to run managers in: config, alphabetical, or shortest or
+        /// longest typical duration (from history) first
+        #[arg(long, value_name = "ORDER", default_value = "config")]
+        order: ExecutionOrder,
+
+        /// Show what would be done without actually doing it
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Update N managers at once. Subcommands of a manager, and managers
+        /// sharing a lock file, still take turns. Commands can't prompt, and
+        /// their output is shown once all of them are done.
+        #[arg(long, short = 'j', value_name = "N", conflicts_with = "keys")]
+        jobs: Option<usize>,
+
+        /// Check every manager first, N at a time (default: `concurrency.checks`,
+        /// or all at once), then update only the ones that aren't up to date
+        #[arg(
+            long,
+            value_name = "N",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "0",
+            conflicts_with = "resume"
+        )]
+        check_parallel: Option<usize>,
+
+        /// Check every manager first, show what would be updated, and ask
+        /// which managers to update before updating any
+        #[arg(long, conflicts_with_all = ["resume", "scheduled"])]
+        plan: bool,
+
+        /// Read keys while updating: s skips the running manager, p pauses
+        /// before the next one, q stops after the running one. Commands get
+        /// no input, so they can't prompt.
+        #[arg(long, conflicts_with = "scheduled")]
+        keys: bool,
+
+        /// Run the managers an interrupted run didn't finish (see `updog status`)
+        #[arg(long, conflicts_with_all = ["package_manager", "tags", "except"])]
+        resume: bool,
+
+        /// Skip the run while `updog pause` is in effect (for timers and cron jobs)
+        #[arg(long)]
+        scheduled: bool,
+
+        /// Refuse to run if the config changed since `updog config approve`
+        #[arg(long, requires = "scheduled")]
+        require_approval: bool,
+
+        /// Annotate the run in history (e.g. "before upgrading to macOS 15")
+        #[arg(long, value_name = "TEXT")]
+        note: Option<String>,
+
+        /// Print progress lines on stderr: "plain" text or "json", one object per line
+        #[arg(long, value_name = "FORMAT")]
+        progress: Option<ProgressFormat>,
+
+        /// Also write the summary to this file as JSON, for wrapper scripts
+        #[arg(long, value_name = "PATH")]
+        summary_file: Option<PathBuf>,
+
+        /// Print the summary as "text", or as "json" or "yaml" for scripts
+        /// (commands' output then only appears in it)
+        #[arg(
+            long,
+            value_name = "FORMAT",
+            default_value = "text",
+            conflicts_with = "plan"
+        )]
+        output: OutputFormat,
+    },
+
+    /// Show per-manager statistics aggregated from run history
+    Stats {
+        /// Only show statistics for this package manager
+        package_manager: Option<String>,
+
+        /// Only include runs of this operation
+        #[arg(long, value_parser = ["check", "update"])]
+        operation: Option<String>,
+    },
+
+    /// Show the latest result of each package manager and flag persistently failing ones
+    Status {
+        /// Output format: a table, or a module for waybar or polybar
+        #[arg(long, value_parser = ["table", "waybar", "polybar"], default_value = "table")]
+        format: String,
+    },
+
+    /// List the configured package managers, their subcommands and groups
+    /// with their descriptions
+    List,
+
+    /// Write the update commands of the selected package managers as a
+    /// standalone shell script, for machines without updog
+    ExportScript {
+        /// Package manager, group, @tag or glob to export (format: manager[:subcommand])
         package_manager: Option<String>,
 
+        #[command(flatten)]
+        selection: SelectionArgs,
+
+        /// Write the script to this file (made executable) instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+
+    /// Show which package managers have listed a package, whether it's outdated
+    /// and when it was last upgraded, from the recorded runs
+    Why {
+        /// Package name as the package managers print it
+        package: String,
+    },
+
+    /// List recent runs with their notes, or export, import or prune the run history
+    History {
+        /// Number of most recent runs to list
+        #[arg(long, value_name = "N", default_value_t = 20)]
+        limit: usize,
+
+        #[command(subcommand)]
+        command: Option<HistoryCommand>,
+    },
+
+    /// Inspect the audit log of executed commands
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommand,
+    },
+
+    /// Manage updog-<name> plugins found on PATH
+    Plugin {
+        #[command(subcommand)]
+        command: PluginCommand,
+    },
+
+    /// Run in the background, checking (or updating) each package manager on its schedule
+    Daemon {
+        /// Show what would be done without actually doing it
+        #[arg(long)]
+        dry_run: bool,
+
+        #[command(flatten)]
+        selection: SelectionArgs,
+
+        /// Refresh this badge file (JSON, or SVG for *.svg) after every run
+        #[arg(long, value_name = "PATH")]
+        write_badge: Option<PathBuf>,
+
+        /// Refuse to start if the config changed since `updog config approve`
+        #[arg(long)]
+        require_approval: bool,
+    },
+
+    /// Check one package manager repeatedly and print whenever the result changes
+    Watch {
+        /// Package manager to watch (format: manager[:subcommand])
+        package_manager: String,
+
+        /// How often to check
+        #[arg(long, value_name = "DURATION", default_value = "5m")]
+        interval: Interval,
+
+        /// Exit after the first change (e.g. `updog watch apt --exit-on-change && notify-send ...`)
+        #[arg(long)]
+        exit_on_change: bool,
+    },
+
+    /// Run one of a package manager's `actions` (e.g. `updog run brew doctor`)
+    Run {
+        /// Package manager to run the action of (format: manager[:subcommand])
+        package_manager: String,
+
+        /// Name of the action
+        action: String,
+
         /// Show what would be done without actually doing it
         #[arg(long)]
         dry_run: bool,
     },
 
-    /// Launch TUI mode
+    /// Pause scheduled and daemon runs until `updog resume`
+    Pause {
+        /// Resume automatically after this long (e.g. 2h, 1d)
+        #[arg(long = "for", value_name = "DURATION")]
+        duration: Option<Interval>,
+    },
+
+    /// Resume scheduled and daemon runs after `updog pause`
+    Resume,
+
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Inspect the daemon schedule
+    Schedule {
+        #[command(subcommand)]
+        command: ScheduleCommand,
+    },
+
+    /// Print a shell completion script; manager names are completed from
+    /// the config at tab time (e.g. `updog completions bash > ~/.local/share/bash-completion/completions/updog`)
+    Completions {
+        /// Shell to complete in
+        #[arg(value_parser = crate::completions::SHELLS.to_vec())]
+        shell: String,
+    },
+
+    /// Generate snippets for other tools to load
+    Generate {
+        #[command(subcommand)]
+        command: GenerateCommand,
+    },
+
+    /// Print completion candidates for the completion scripts
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        /// What to complete
+        #[arg(value_parser = ["managers"])]
+        kind: String,
+
+        /// Follow each candidate with a tab and its description (for fish)
+        #[arg(long)]
+        descriptions: bool,
+    },
+
+    /// Check and update managers from a terminal UI, watching their output
     Tui,
 }
 
+#[derive(Subcommand)]
+pub enum GenerateCommand {
+    /// Print shell functions to eval from your shell's rc file: `up` runs
+    /// `updog update` with completion (e.g. `eval "$(updog generate shell-hook zsh --precmd)"`)
+    ShellHook {
+        /// Shell to generate for
+        #[arg(value_parser = crate::shell_hook::SHELLS.to_vec())]
+        shell: String,
+
+        /// Name of the function running `updog update`
+        #[arg(long, value_name = "NAME", default_value = "up", value_parser = crate::shell_hook::parse_name)]
+        name: String,
+
+        /// Show the pending update count from recorded runs at the first
+        /// prompt of each day
+        #[arg(long)]
+        precmd: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ScheduleCommand {
+    /// Print when each package manager will next run
+    Next,
+
+    /// Generate systemd service and timer units for each package manager
+    Systemd {
+        /// Write the units into this directory instead of printing them
+        /// (e.g. ~/.config/systemd/user)
+        #[arg(long, value_name = "DIR")]
+        dir: Option<PathBuf>,
+    },
+}
+
+// Selection flags shared by the commands that run several package managers
+#[derive(Args, Debug, Clone, Default, PartialEq)]
+pub struct SelectionArgs {
+    /// Only package managers with this tag (repeatable)
+    #[arg(long = "tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+
+    /// Leave out these package managers, groups, @tags or globs (repeatable)
+    #[arg(long, value_name = "SELECTOR")]
+    pub except: Vec<String>,
+
+    /// Don't fail when nothing is left to run (unmatched tags or globs, or
+    /// everything left out by --except)
+    #[arg(long)]
+    pub allow_empty: bool,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Warn about risky commands (rm -rf, unquoted variables, curl | sh, ...)
+    /// and programs that aren't installed; exits with 1 if anything is found
+    Lint {
+        /// Also run every command through shellcheck (must be installed)
+        #[arg(long)]
+        shellcheck: bool,
+    },
+
+    /// Approve the current config for runs started with --require-approval
+    Approve,
+
+    /// Translate another updater's config into an updog config, reporting
+    /// whatever couldn't be mapped
+    Import {
+        /// Format of the config to import
+        #[arg(long, value_parser = ["topgrade"])]
+        from: String,
+
+        /// Config file to import (e.g. ~/.config/topgrade.toml)
+        path: PathBuf,
+
+        /// Write the config to this file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+
+    /// Rewrite the config for the current config version, replacing
+    /// deprecated constructs (comments aren't kept)
+    Migrate {
+        /// Write the config to this file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+
+    /// Print the config in effect: yours, merged over the machine-wide
+    /// config (/etc/updog/updog.yaml or the platform's equivalent) if any
+    Show {
+        /// List the system or user layer each entry comes from instead
+        #[arg(long)]
+        origin: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AuditCommand {
+    /// Check that the audit log parses and its hash chain is intact;
+    /// exits with 1 if it doesn't
+    Verify,
+}
+
+#[derive(Subcommand)]
+pub enum PluginCommand {
+    /// List plugins on PATH, what they provide and whether the config enables them
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum HistoryCommand {
+    /// Show a recorded run in full, by its run id (or the start of it)
+    Show {
+        /// Id from `updog history`, the logs or an alert's UPDOG_RUN_ID
+        run_id: String,
+    },
+
+    /// Write all recorded runs to stdout or a file
+    Export {
+        /// Output format
+        #[arg(long, value_name = "FORMAT", default_value = "json")]
+        format: ExportFormat,
+
+        /// Write to this file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// Merge runs from an exported file into the local history
+    Import {
+        /// File produced by `updog history export`
+        file: PathBuf,
+
+        /// Input format (guessed from the file extension by default, else json)
+        #[arg(long, value_name = "FORMAT")]
+        format: Option<ExportFormat>,
+    },
+
+    /// Remove runs outside the retention policy (`history.keep_runs` / `history.keep_days`)
+    Prune {
+        /// Keep only this many most recent runs (overrides the config)
+        #[arg(long, value_name = "N")]
+        keep_runs: Option<usize>,
+
+        /// Drop runs older than this many days (overrides the config)
+        #[arg(long, value_name = "DAYS")]
+        keep_days: Option<u64>,
+    },
+}
+
 impl Commands {
     // Returns whether the command is in dry run mode
     pub fn is_dry_run(&self) -> bool {
         match self {
             Commands::Check { dry_run, .. } => *dry_run,
             Commands::Update { dry_run, .. } => *dry_run,
-            Commands::Tui => false,
+            Commands::Daemon { dry_run, .. } => *dry_run,
+            Commands::Run { dry_run, .. } => *dry_run,
+            Commands::Stats { .. }
+            | Commands::Status { .. }
+            | Commands::List
+            | Commands::History { .. }
+            | Commands::Why { .. }
+            | Commands::ExportScript { .. }
+            | Commands::Audit { .. }
+            | Commands::Plugin { .. }
+            | Commands::Config { .. }
+            | Commands::Watch { .. }
+            | Commands::Pause { .. }
+            | Commands::Resume
+            | Commands::Schedule { .. }
+            | Commands::Completions { .. }
+            | Commands::Generate { .. }
+            | Commands::Complete { .. }
+            | Commands::Tui => false,
+        }
+    }
+
+    // Returns whether the run was started by a timer and should honor `updog pause`
+    pub fn is_scheduled(&self) -> bool {
+        matches!(
+            self,
+            Commands::Check {
+                scheduled: true,
+                ..
+            } | Commands::Update {
+                scheduled: true,
+                ..
+            }
+        )
+    }
+
+    // Returns whether the config must match the approved revision before running
+    pub fn requires_approval(&self) -> bool {
+        matches!(
+            self,
+            Commands::Check {
+                require_approval: true,
+                ..
+            } | Commands::Update {
+                require_approval: true,
+                ..
+            } | Commands::Daemon {
+                require_approval: true,
+                ..
+            }
+        )
+    }
+
+    // Returns the package managers selected on the command line
+    pub fn selection(&self) -> Selection {
+        let (package_manager, args) = match self {
+            Commands::Check {
+                package_manager,
+                selection,
+                ..
+            }
+            | Commands::Update {
+                package_manager,
+                selection,
+                ..
+            } => (package_manager.as_deref(), selection),
+            Commands::ExportScript {
+                package_manager,
+                selection,
+                ..
+            } => (package_manager.as_deref(), selection),
+            Commands::Daemon { selection, .. } => (None, selection),
+            _ => return Selection::default(),
+        };
+        Selection {
+            include: package_manager
+                .into_iter()
+                .map(str::to_string)
+                .chain(args.tags.iter().map(|tag| format!("@{}", tag)))
+                .collect(),
+            except: args.except.clone(),
+            allow_empty: args.allow_empty,
+        }
+    }
+
+    // Returns the note to store with the run in history
+    pub fn note(&self) -> Option<&str> {
+        match self {
+            Commands::Check { note, .. } | Commands::Update { note, .. } => note.as_deref(),
+            _ => None,
+        }
+    }
+
+    // Returns whether to run what the journal of an interrupted run left
+    pub fn resume(&self) -> bool {
+        matches!(
+            self,
+            Commands::Check { resume: true, .. } | Commands::Update { resume: true, .. }
+        )
+    }
+
+    // Returns the --check-parallel setting of an update run (0 if given
+    // without a number)
+    pub fn check_parallel(&self) -> Option<usize> {
+        match self {
+            Commands::Update { check_parallel, .. } => *check_parallel,
+            _ => None,
+        }
+    }
+
+    // Returns the --jobs setting of a check or update run
+    pub fn jobs(&self) -> Option<usize> {
+        match self {
+            Commands::Check { jobs, .. } | Commands::Update { jobs, .. } => *jobs,
+            _ => None,
+        }
+    }
+
+    // Returns whether an update run asks for confirmation after checking
+    pub fn plan(&self) -> bool {
+        matches!(self, Commands::Update { plan: true, .. })
+    }
+
+    // Returns whether an update run takes keyboard controls
+    pub fn keys(&self) -> bool {
+        matches!(self, Commands::Update { keys: true, .. })
+    }
+
+    // Returns whether cached checks must not be reused
+    pub fn refresh(&self) -> bool {
+        matches!(self, Commands::Check { refresh: true, .. })
+    }
+
+    // Returns the order to run the selected managers in
+    pub fn order(&self) -> ExecutionOrder {
+        match self {
+            Commands::Check { order, .. } | Commands::Update { order, .. } => *order,
+            _ => ExecutionOrder::Config,
+        }
+    }
+
+    // Returns the format of progress lines on stderr, if requested
+    pub fn progress(&self) -> Option<ProgressFormat> {
+        match self {
+            Commands::Check { progress, .. } | Commands::Update { progress, .. } => *progress,
+            _ => None,
+        }
+    }
+
+    pub fn summary_file(&self) -> Option<&Path> {
+        match self {
+            Commands::Check { summary_file, .. } | Commands::Update { summary_file, .. } => {
+                summary_file.as_deref()
+            }
+            _ => None,
+        }
+    }
+
+    // Returns the format of the summary
+    pub fn output(&self) -> OutputFormat {
+        match self {
+            Commands::Check { output, .. } | Commands::Update { output, .. } => *output,
+            _ => OutputFormat::Text,
         }
     }
 
+    // Returns whether stdout is for scripts: counts or a structured summary
+    pub fn is_scripted(&self) -> bool {
+        self.is_count_mode() || self.output() != OutputFormat::Text
+    }
+
+    // Returns whether only update counts should be printed
+    pub fn is_count_mode(&self) -> bool {
+        matches!(self, Commands::Check { count: true, .. })
+    }
+
     // Parse package manager string to extract package manager and subcommand
     // Format: manager[:subcommand]
     pub fn parse_package_manager(&self) -> Option<(String, Option<String>)> {
         let package_manager = match self {
-            Commands::Check { package_manager, .. } => package_manager,
-            Commands::Update { package_manager, .. } => package_manager,
-            Commands::Tui => return None,
+            Commands::Check {
+                package_manager, ..
+            } => package_manager,
+            Commands::Update {
+                package_manager, ..
+            } => package_manager,
+            Commands::Watch {
+                package_manager, ..
+            }
+            | Commands::Run {
+                package_manager, ..
+            } => return Some(split_package_manager(package_manager)),
+            Commands::Stats { .. }
+            | Commands::Status { .. }
+            | Commands::List
+            | Commands::History { .. }
+            | Commands::Why { .. }
+            | Commands::ExportScript { .. }
+            | Commands::Audit { .. }
+            | Commands::Plugin { .. }
+            | Commands::Config { .. }
+            | Commands::Daemon { .. }
+            | Commands::Pause { .. }
+            | Commands::Resume
+            | Commands::Schedule { .. }
+            | Commands::Completions { .. }
+            | Commands::Generate { .. }
+            | Commands::Complete { .. }
+            | Commands::Tui => return None,
         };
 
-        package_manager.as_ref().map(|pm_str| {
-            // Split by ':' to get package manager and subcommand
-            let parts: Vec<&str> = pm_str.split(':').collect();
-            match parts.len() {
-                1 => (parts[0].to_string(), None),
-                2 => (parts[0].to_string(), Some(parts[1].to_string())),
-                _ => {
-                    // If there are more than one ':', take the first part as package manager
-                    // and the rest joined by ':' as subcommand
-                    let manager = parts[0].to_string();
-                    let subcommand = parts[1..].join(":").to_string();
-                    (manager, Some(subcommand))
-                }
-            }
-        })
+        package_manager.as_deref().map(split_package_manager)
     }
 }
 
 impl Cli {
+    pub fn verbosity(&self) -> Verbosity {
+        if self.verbose {
+            Verbosity::Verbose
+        } else if self.quiet {
+            Verbosity::Quiet
+        } else {
+            Verbosity::Normal
+        }
+    }
+
+    // Whether a missing config may be created with the first-run wizard
+    // (the caller still checks for a terminal)
+    pub fn offers_wizard(&self) -> bool {
+        !self.no_wizard
+            && !self.command.is_scripted()
+            && matches!(
+                self.command,
+                Commands::Check { .. } | Commands::Update { .. } | Commands::Watch { .. }
+            )
+    }
+
+    // Whether a config that doesn't load is reported and ignored rather than
+    // fatal: with --ignore-config-errors, and for shell completion, which
+    // mustn't print errors into the command line being completed
+    pub fn ignores_config_errors(&self) -> bool {
+        self.ignore_config_errors
+            || matches!(
+                self.command,
+                Commands::Completions { .. }
+                    | Commands::Generate { .. }
+                    | Commands::Complete { .. }
+            )
+    }
+
     pub fn get_config_path(&self) -> PathBuf {
         if let Some(config_path) = &self.config {
             config_path.clone()
@@ -110,6 +788,23 @@ impl Cli {
     }
 }
 
+// Split "manager[:subcommand]" into its parts
+fn split_package_manager(pm_str: &str) -> (String, Option<String>) {
+    // Split by ':' to get package manager and subcommand
+    let parts: Vec<&str> = pm_str.split(':').collect();
+    match parts.len() {
+        1 => (parts[0].to_string(), None),
+        2 => (parts[0].to_string(), Some(parts[1].to_string())),
+        _ => {
+            // If there are more than one ':', take the first part as package manager
+            // and the rest joined by ':' as subcommand
+            let manager = parts[0].to_string();
+            let subcommand = parts[1..].join(":").to_string();
+            (manager, Some(subcommand))
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -125,6 +820,9 @@ mod tests {
         let cli = Cli {
             config: Some(PathBuf::from("custom.yaml")),
             verbose: false,
+            quiet: false,
+            no_wizard: false,
+            ignore_config_errors: false,
             command: Commands::Tui,
         };
         assert_eq!(cli.get_config_path(), PathBuf::from("custom.yaml"));
@@ -137,6 +835,9 @@ mod tests {
         let cli = Cli {
             config: None,
             verbose: false,
+            quiet: false,
+            no_wizard: false,
+            ignore_config_errors: false,
             command: Commands::Tui,
         };
         let _path = cli.get_config_path();
@@ -146,32 +847,90 @@ mod tests {
     fn test_package_manager_parsing() {
         // Test case 1: Just package manager name
         let cmd = Commands::Check {
+            selection: SelectionArgs::default(),
+            order: ExecutionOrder::Config,
             package_manager: Some("brew".to_string()),
             dry_run: false,
+            jobs: None,
+            count: false,
+            by_manager: false,
+            resume: false,
+            refresh: false,
+            write_badge: None,
+            scheduled: false,
+            require_approval: false,
+            note: None,
+            progress: None,
+            summary_file: None,
+            output: OutputFormat::Text,
         };
         let result = cmd.parse_package_manager();
         assert_eq!(result, Some(("brew".to_string(), None)));
 
         // Test case 2: Package manager with subcommand
         let cmd = Commands::Update {
+            selection: SelectionArgs::default(),
+            order: ExecutionOrder::Config,
             package_manager: Some("brew:cask".to_string()),
             dry_run: false,
+            jobs: None,
+            check_parallel: None,
+            plan: false,
+            keys: false,
+            resume: false,
+            scheduled: false,
+            require_approval: false,
+            note: None,
+            progress: None,
+            summary_file: None,
+            output: OutputFormat::Text,
         };
         let result = cmd.parse_package_manager();
         assert_eq!(result, Some(("brew".to_string(), Some("cask".to_string()))));
 
         // Test case 3: Package manager with complex subcommand (containing ':')
         let cmd = Commands::Check {
+            selection: SelectionArgs::default(),
+            order: ExecutionOrder::Config,
             package_manager: Some("custom:with:colons".to_string()),
             dry_run: false,
+            jobs: None,
+            count: false,
+            by_manager: false,
+            resume: false,
+            refresh: false,
+            write_badge: None,
+            scheduled: false,
+            require_approval: false,
+            note: None,
+            progress: None,
+            summary_file: None,
+            output: OutputFormat::Text,
         };
         let result = cmd.parse_package_manager();
-        assert_eq!(result, Some(("custom".to_string(), Some("with:colons".to_string()))));
+        assert_eq!(
+            result,
+            Some(("custom".to_string(), Some("with:colons".to_string())))
+        );
 
         // Test case 4: No package manager specified
         let cmd = Commands::Check {
+            selection: SelectionArgs::default(),
+            order: ExecutionOrder::Config,
             package_manager: None,
             dry_run: false,
+            jobs: None,
+            count: false,
+            by_manager: false,
+            resume: false,
+            refresh: false,
+            write_badge: None,
+            scheduled: false,
+            require_approval: false,
+            note: None,
+            progress: None,
+            summary_file: None,
+            output: OutputFormat::Text,
         };
         let result = cmd.parse_package_manager();
         assert_eq!(result, None);
@@ -181,4 +940,170 @@ mod tests {
         let result = cmd.parse_package_manager();
         assert_eq!(result, None);
     }
+
+    #[test]
+    fn test_verbosity_flags() {
+        let cli = Cli::parse_from(["updog", "-v", "check"]);
+        assert_eq!(cli.verbosity(), Verbosity::Verbose);
+
+        let cli = Cli::parse_from(["updog", "--quiet", "update"]);
+        assert_eq!(cli.verbosity(), Verbosity::Quiet);
+
+        let cli = Cli::parse_from(["updog", "update"]);
+        assert_eq!(cli.verbosity(), Verbosity::Normal);
+
+        assert!(Cli::try_parse_from(["updog", "-v", "-q", "update"]).is_err());
+    }
+
+    #[test]
+    fn test_resume_flag() {
+        let cli = Cli::parse_from(["updog", "update", "--resume"]);
+        assert!(cli.command.resume());
+        assert!(!Cli::parse_from(["updog", "check"]).command.resume());
+        assert!(Cli::try_parse_from(["updog", "update", "--resume", "brew"]).is_err());
+        assert!(Cli::try_parse_from(["updog", "update", "--resume", "--tag", "dev"]).is_err());
+    }
+
+    #[test]
+    fn test_check_parallel_flag() {
+        let cli = Cli::parse_from(["updog", "update", "--check-parallel"]);
+        assert_eq!(cli.command.check_parallel(), Some(0));
+        let cli = Cli::parse_from(["updog", "update", "--check-parallel=4", "brew"]);
+        assert_eq!(cli.command.check_parallel(), Some(4));
+        assert_eq!(
+            cli.command.parse_package_manager(),
+            Some(("brew".to_string(), None))
+        );
+        let cli = Cli::parse_from(["updog", "update", "--check-parallel", "brew"]);
+        assert_eq!(cli.command.check_parallel(), Some(0));
+        assert_eq!(
+            Cli::parse_from(["updog", "update"])
+                .command
+                .check_parallel(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_plan_flag() {
+        assert!(Cli::parse_from(["updog", "update", "--plan"])
+            .command
+            .plan());
+        assert!(!Cli::parse_from(["updog", "update"]).command.plan());
+        assert!(Cli::try_parse_from(["updog", "update", "--plan", "--resume"]).is_err());
+        assert!(Cli::try_parse_from(["updog", "update", "--plan", "--scheduled"]).is_err());
+    }
+
+    #[test]
+    fn test_jobs_flag() {
+        assert_eq!(
+            Cli::parse_from(["updog", "update", "--jobs", "3"])
+                .command
+                .jobs(),
+            Some(3)
+        );
+        assert_eq!(
+            Cli::parse_from(["updog", "check", "-j", "4"])
+                .command
+                .jobs(),
+            Some(4)
+        );
+        assert_eq!(Cli::parse_from(["updog", "update"]).command.jobs(), None);
+        assert!(Cli::try_parse_from(["updog", "update", "-j", "2", "--keys"]).is_err());
+    }
+
+    #[test]
+    fn test_keys_flag() {
+        assert!(Cli::parse_from(["updog", "update", "--keys"])
+            .command
+            .keys());
+        assert!(!Cli::parse_from(["updog", "update"]).command.keys());
+        assert!(Cli::try_parse_from(["updog", "check", "--keys"]).is_err());
+        assert!(Cli::try_parse_from(["updog", "update", "--keys", "--scheduled"]).is_err());
+    }
+
+    #[test]
+    fn test_ignore_config_errors() {
+        let cli = Cli::parse_from(["updog", "check", "--ignore-config-errors"]);
+        assert!(cli.ignores_config_errors());
+        assert!(!Cli::parse_from(["updog", "update"]).ignores_config_errors());
+        // Completion never stops on a broken config
+        assert!(Cli::parse_from(["updog", "completions", "bash"]).ignores_config_errors());
+    }
+
+    #[test]
+    fn test_count_flags() {
+        let cli = Cli::parse_from(["updog", "check", "--count", "--by-manager"]);
+        assert!(cli.command.is_count_mode());
+
+        let cli = Cli::parse_from(["updog", "check"]);
+        assert!(!cli.command.is_count_mode());
+
+        // --by-manager only makes sense together with --count
+        assert!(Cli::try_parse_from(["updog", "check", "--by-manager"]).is_err());
+    }
+
+    #[test]
+    fn test_offers_wizard() {
+        assert!(Cli::parse_from(["updog", "check"]).offers_wizard());
+        assert!(!Cli::parse_from(["updog", "check", "--no-wizard"]).offers_wizard());
+        assert!(!Cli::parse_from(["updog", "--no-wizard", "update"]).offers_wizard());
+        assert!(!Cli::parse_from(["updog", "check", "--count"]).offers_wizard());
+        assert!(!Cli::parse_from(["updog", "stats"]).offers_wizard());
+    }
+
+    #[test]
+    fn test_output_flag() {
+        let cli = Cli::parse_from(["updog", "check", "--output", "json"]);
+        assert_eq!(cli.command.output(), OutputFormat::Json);
+        assert!(cli.command.is_scripted());
+        assert!(!cli.offers_wizard());
+        let cli = Cli::parse_from(["updog", "update"]);
+        assert_eq!(cli.command.output(), OutputFormat::Text);
+        assert!(!cli.command.is_scripted());
+        assert!(Cli::try_parse_from(["updog", "check", "--count", "--output", "yaml"]).is_err());
+        assert!(Cli::try_parse_from(["updog", "update", "--plan", "--output", "json"]).is_err());
+        assert!(Cli::try_parse_from(["updog", "check", "--output", "xml"]).is_err());
+    }
+
+    #[test]
+    fn test_progress_flag() {
+        let cli = Cli::parse_from(["updog", "update", "--progress", "json"]);
+        assert_eq!(cli.command.progress(), Some(ProgressFormat::Json));
+        assert_eq!(Cli::parse_from(["updog", "check"]).command.progress(), None);
+        assert!(Cli::try_parse_from(["updog", "check", "--progress", "bar"]).is_err());
+    }
+
+    #[test]
+    fn test_summary_file_flag() {
+        let cli = Cli::parse_from(["updog", "check", "--count", "--summary-file", "out.json"]);
+        assert_eq!(cli.command.summary_file(), Some(Path::new("out.json")));
+        assert_eq!(
+            Cli::parse_from(["updog", "update"]).command.summary_file(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_selection_flags() {
+        let cli = Cli::parse_from([
+            "updog", "update", "@system", "--tag", "dev", "--except", "apt", "--except", "docker*",
+        ]);
+        assert_eq!(
+            cli.command.selection(),
+            Selection {
+                include: vec!["@system".to_string(), "@dev".to_string()],
+                except: vec!["apt".to_string(), "docker*".to_string()],
+                allow_empty: false,
+            }
+        );
+
+        let cli = Cli::parse_from(["updog", "daemon", "--except", "brew", "--allow-empty"]);
+        assert_eq!(cli.command.selection().except, vec!["brew".to_string()]);
+        assert!(cli.command.selection().allow_empty);
+        assert!(Cli::parse_from(["updog", "check"])
+            .command
+            .selection()
+            .is_everything());
+    }
 }
@@ -0,0 +1,493 @@
+use clap::Command;
+
+use crate::config::Config;
+
+// `updog completions <shell>` prints a completion script for bash, zsh or
+// fish. Subcommands, flags and their fixed values come from the CLI
+// definition; manager arguments are completed at tab time by the hidden
+// `updog __complete managers`, so they follow the config actually in use
+// (including one passed with --config).
+
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+// Completion candidates for manager arguments, with their descriptions
+// ("" if none): every manager and group id, and `manager:subcommand` for
+// managers with subcommands
+pub fn managers(config: &Config) -> Vec<(String, String)> {
+    let mut candidates = Vec::new();
+    for manager in &config.commands {
+        candidates.push((
+            manager.id.clone(),
+            manager.description.clone().unwrap_or_default(),
+        ));
+        for subcommand in &manager.subcommands {
+            candidates.push((
+                format!("{}:{}", manager.id, subcommand.id),
+                subcommand.description.clone().unwrap_or_default(),
+            ));
+        }
+    }
+    candidates
+}
+
+// A (sub)command of the CLI and what can follow it
+struct Node {
+    // Subcommand names from the root, like ["config", "lint"]
+    path: Vec<String>,
+    // Subcommands, with their descriptions
+    children: Vec<(String, String)>,
+    options: Vec<Opt>,
+    // Fixed values of positional arguments
+    values: Vec<String>,
+    takes_manager: bool,
+}
+
+struct Opt {
+    long: Option<String>,
+    short: Option<char>,
+    help: String,
+    takes_value: bool,
+    values: Vec<String>,
+}
+
+impl Opt {
+    fn names(&self) -> Vec<String> {
+        self.long
+            .iter()
+            .map(|long| format!("--{}", long))
+            .chain(self.short.iter().map(|short| format!("-{}", short)))
+            .collect()
+    }
+}
+
+fn first_line(text: Option<String>) -> String {
+    text.unwrap_or_default()
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('.')
+        .to_string()
+}
+
+fn nodes(command: &Command, path: Vec<String>, out: &mut Vec<Node>) {
+    let visible = || {
+        command
+            .get_subcommands()
+            .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+    };
+    let args = || command.get_arguments().filter(|arg| !arg.is_hide_set());
+    out.push(Node {
+        path: path.clone(),
+        children: visible()
+            .map(|sub| {
+                let about = first_line(sub.get_about().map(ToString::to_string));
+                (sub.get_name().to_string(), about)
+            })
+            .collect(),
+        options: args()
+            .filter(|arg| !arg.is_positional())
+            .map(|arg| Opt {
+                long: arg.get_long().map(str::to_string),
+                short: arg.get_short(),
+                help: first_line(arg.get_help().map(ToString::to_string)),
+                takes_value: arg.get_action().takes_values(),
+                values: possible_values(arg),
+            })
+            .collect(),
+        values: args()
+            .filter(|arg| arg.is_positional())
+            .flat_map(possible_values)
+            .collect(),
+        takes_manager: args().any(|arg| arg.is_positional() && arg.get_id() == "package_manager"),
+    });
+    for sub in visible() {
+        let mut sub_path = path.clone();
+        sub_path.push(sub.get_name().to_string());
+        nodes(sub, sub_path, out);
+    }
+}
+
+fn possible_values(arg: &clap::Arg) -> Vec<String> {
+    arg.get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect()
+}
+
+// The completion script for `shell` (one of SHELLS)
+pub fn script(shell: &str, command: &Command) -> String {
+    let mut command = command.clone();
+    command.build();
+    let name = command.get_name().to_string();
+    let mut all = Vec::new();
+    nodes(&command, Vec::new(), &mut all);
+    match shell {
+        "fish" => fish(&name, &all),
+        "zsh" => zsh(&name, &all),
+        _ => bash(&name, &all),
+    }
+}
+
+// Shell functions shared by the bash and zsh scripts, which both parse them:
+// the subcommands, options and fixed values of every node ("" for the root,
+// "config lint" for nested ones), the options taking a value, and the nodes
+// completing manager names
+fn helpers(name: &str, nodes: &[Node]) -> String {
+    let prefix = format!("_{}", name.replace('-', "_"));
+    let case = |function: &str, arms: Vec<(String, String)>| {
+        let mut out = format!("{}_{}() {{\n    case \"$1\" in\n", prefix, function);
+        for (pattern, words) in arms.iter().filter(|(_, words)| !words.is_empty()) {
+            out.push_str(&format!("        \"{}\") echo \"{}\" ;;\n", pattern, words));
+        }
+        out.push_str("    esac\n}\n\n");
+        out
+    };
+
+    let mut out = case(
+        "children",
+        nodes
+            .iter()
+            .map(|node| {
+                let names: Vec<&str> = node.children.iter().map(|(n, _)| n.as_str()).collect();
+                (node.path.join(" "), names.join(" "))
+            })
+            .collect(),
+    );
+    out.push_str(&case(
+        "options",
+        nodes
+            .iter()
+            .map(|node| {
+                let names: Vec<String> = node.options.iter().flat_map(Opt::names).collect();
+                (node.path.join(" "), names.join(" "))
+            })
+            .collect(),
+    ));
+    // Keyed by the node, then the option for option values
+    let mut values = Vec::new();
+    for node in nodes {
+        let path = node.path.join(" ");
+        values.push((path.clone(), node.values.join(" ")));
+        for opt in &node.options {
+            for option in opt.names() {
+                let key = format!("{} {}", path, option).trim_start().to_string();
+                values.push((key, opt.values.join(" ")));
+            }
+        }
+    }
+    out.push_str(&case("values", values));
+
+    let mut takes_value: Vec<String> = nodes
+        .iter()
+        .flat_map(|node| &node.options)
+        .filter(|opt| opt.takes_value)
+        .flat_map(Opt::names)
+        .collect();
+    takes_value.sort();
+    takes_value.dedup();
+    out.push_str(&format!(
+        "{}_takes_value() {{\n    case \"$1\" in\n        {}) return 0 ;;\n    esac\n    return 1\n}}\n\n",
+        prefix,
+        takes_value.join("|")
+    ));
+
+    let managers: Vec<String> = nodes
+        .iter()
+        .filter(|node| node.takes_manager)
+        .map(|node| format!("\"{}\"", node.path.join(" ")))
+        .collect();
+    out.push_str(&format!(
+        "{}_takes_manager() {{\n    case \"$1\" in\n        {}) return 0 ;;\n    esac\n    return 1\n}}\n\n",
+        prefix,
+        managers.join("|")
+    ));
+    out
+}
+
+fn bash(name: &str, nodes: &[Node]) -> String {
+    let prefix = format!("_{}", name.replace('-', "_"));
+    let mut out =
+        format!("# bash completion for {name}, generated by `{name} completions bash`\n\n");
+    out.push_str(&helpers(name, nodes));
+    out.push_str(
+        &r#"_PREFIX() {
+    local cur prev words cword node="" word i
+    local -a config=()
+    if declare -F _get_comp_words_by_ref >/dev/null; then
+        # Keep "manager:subcommand" in one word
+        _get_comp_words_by_ref -n : cur prev words cword
+    else
+        words=("${COMP_WORDS[@]}")
+        cword=$COMP_CWORD
+        cur=${words[cword]}
+        prev=${words[cword - 1]}
+    fi
+    for ((i = 1; i < cword; i++)); do
+        word=${words[i]}
+        if [[ $word == -c || $word == --config ]]; then
+            config=(--config "${words[i + 1]}")
+        fi
+        if _PREFIX_takes_value "$word"; then
+            ((i++))
+        elif [[ $word != -* && " $(_PREFIX_children "$node") " == *" $word "* ]]; then
+            node=${node:+$node }$word
+        fi
+    done
+    if _PREFIX_takes_value "$prev"; then
+        local values
+        values=$(_PREFIX_values "${node:+$node }$prev")
+        [[ -n $values ]] && COMPREPLY=($(compgen -W "$values" -- "$cur"))
+        return
+    fi
+    if [[ $cur == -* ]]; then
+        COMPREPLY=($(compgen -W "$(_PREFIX_options "$node")" -- "$cur"))
+        return
+    fi
+    COMPREPLY=($(compgen -W "$(_PREFIX_children "$node") $(_PREFIX_values "$node")" -- "$cur"))
+    if _PREFIX_takes_manager "$node"; then
+        COMPREPLY+=($(compgen -W "$(NAME "${config[@]}" __complete managers 2>/dev/null)" -- "$cur"))
+        declare -F __ltrim_colon_completions >/dev/null && __ltrim_colon_completions "$cur"
+    fi
+}
+
+complete -o default -F _PREFIX NAME
+"#
+        .replace("_PREFIX", &prefix)
+        .replace("NAME", name),
+    );
+    out
+}
+
+fn zsh(name: &str, nodes: &[Node]) -> String {
+    let prefix = format!("_{}", name.replace('-', "_"));
+    let mut out = format!(
+        "#compdef {name}\n# zsh completion for {name}, generated by `{name} completions zsh`\n\n"
+    );
+    out.push_str(&helpers(name, nodes));
+    out.push_str(
+        &r#"_PREFIX() {
+    local node="" word prev=${words[CURRENT - 1]} i values
+    local -a config
+    for ((i = 2; i < CURRENT; i++)); do
+        word=${words[i]}
+        if [[ $word == -c || $word == --config ]]; then
+            config=(--config "${words[i + 1]}")
+        fi
+        if _PREFIX_takes_value "$word"; then
+            ((i++))
+        elif [[ $word != -* && " $(_PREFIX_children "$node") " == *" $word "* ]]; then
+            node=${node:+$node }$word
+        fi
+    done
+    if _PREFIX_takes_value "$prev"; then
+        values=$(_PREFIX_values "${node:+$node }$prev")
+        if [[ -n $values ]]; then
+            compadd -- ${=values}
+        else
+            _files
+        fi
+        return
+    fi
+    if [[ $PREFIX == -* ]]; then
+        compadd -- ${=$(_PREFIX_options "$node")}
+        return
+    fi
+    compadd -- ${=$(_PREFIX_children "$node")} ${=$(_PREFIX_values "$node")}
+    if _PREFIX_takes_manager "$node"; then
+        compadd -- ${(f)"$(NAME $config __complete managers 2>/dev/null)"}
+    fi
+}
+
+if [[ $funcstack[1] == _PREFIX ]]; then
+    _PREFIX "$@"
+else
+    compdef _PREFIX NAME
+fi
+"#
+        .replace("_PREFIX", &prefix)
+        .replace("NAME", name),
+    );
+    out
+}
+
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn fish(name: &str, nodes: &[Node]) -> String {
+    let managers_function = format!("__fish_{}_managers", name.replace('-', "_"));
+    let mut out = format!(
+        "# fish completion for {name}, generated by `{name} completions fish`\n\n\
+         function {managers_function}\n\
+         \x20   set -l tokens (commandline -opc)\n\
+         \x20   set -l i (contains -i -- --config $tokens; or contains -i -- -c $tokens)\n\
+         \x20   if test -n \"$i\"\n\
+         \x20       {name} --config $tokens[(math $i + 1)] __complete managers --descriptions 2>/dev/null\n\
+         \x20   else\n\
+         \x20       {name} __complete managers --descriptions 2>/dev/null\n\
+         \x20   end\n\
+         end\n\n"
+    );
+    // The condition for completing right after a node's subcommands
+    let condition = |node: &Node| -> String {
+        let children: Vec<&str> = node.children.iter().map(|(n, _)| n.as_str()).collect();
+        let mut parts: Vec<String> = node
+            .path
+            .iter()
+            .map(|sub| format!("__fish_seen_subcommand_from {}", sub))
+            .collect();
+        if node.path.is_empty() {
+            parts.push("__fish_use_subcommand".to_string());
+        } else if !children.is_empty() {
+            parts.push(format!(
+                "not __fish_seen_subcommand_from {}",
+                children.join(" ")
+            ));
+        }
+        parts.join("; and ")
+    };
+    for node in nodes {
+        let here = condition(node);
+        for (child, about) in &node.children {
+            out.push_str(&format!(
+                "complete -c {} -f -n {} -a {} -d {}\n",
+                name,
+                fish_quote(&here),
+                child,
+                fish_quote(about)
+            ));
+        }
+        if !node.values.is_empty() {
+            out.push_str(&format!(
+                "complete -c {} -f -n {} -a {}\n",
+                name,
+                fish_quote(&here),
+                fish_quote(&node.values.join(" "))
+            ));
+        }
+        if node.takes_manager {
+            out.push_str(&format!(
+                "complete -c {} -f -n {} -a '({})'\n",
+                name,
+                fish_quote(&here),
+                managers_function
+            ));
+        }
+        // Options apply anywhere below their subcommand
+        let seen: Vec<String> = node
+            .path
+            .iter()
+            .map(|sub| format!("__fish_seen_subcommand_from {}", sub))
+            .collect();
+        for opt in &node.options {
+            let mut line = format!("complete -c {}", name);
+            if !seen.is_empty() {
+                line.push_str(&format!(" -n {}", fish_quote(&seen.join("; and "))));
+            }
+            if let Some(long) = &opt.long {
+                line.push_str(&format!(" -l {}", long));
+            }
+            if let Some(short) = opt.short {
+                line.push_str(&format!(" -s {}", short));
+            }
+            if !opt.values.is_empty() {
+                line.push_str(&format!(" -x -a {}", fish_quote(&opt.values.join(" "))));
+            } else if opt.takes_value {
+                line.push_str(" -r");
+            }
+            if !opt.help.is_empty() {
+                line.push_str(&format!(" -d {}", fish_quote(&opt.help)));
+            }
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Cli;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_managers() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+            commands:
+              - id: brew
+                subcommands:
+                  - id: formula
+                    check: brew outdated
+                  - id: cask
+                    description: Casks, including auto-updating ones
+                    check: brew outdated --cask
+              - id: npm
+                description: Global npm packages
+                check: npm outdated -g
+              - id: dev
+                type: group
+                members: [npm]
+            "#,
+        )
+        .unwrap();
+        let candidates = managers(&config);
+        let names: Vec<&str> = candidates.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["brew", "brew:formula", "brew:cask", "npm", "dev"]);
+        assert_eq!(candidates[1].1, "");
+        assert_eq!(candidates[2].1, "Casks, including auto-updating ones");
+        assert_eq!(candidates[3].1, "Global npm packages");
+    }
+
+    #[test]
+    fn test_bash_script() {
+        let script = script("bash", &Cli::command());
+        assert!(script.contains("complete -o default -F _updog updog\n"));
+        assert!(
+            script.contains("        \"config\") echo \"lint approve import migrate show\" ;;\n")
+        );
+        assert!(script.contains("        \"status --format\") echo \"table waybar polybar\" ;;\n"));
+        assert!(script.contains("updog \"${config[@]}\" __complete managers"));
+        // The hidden helper isn't offered
+        let root = script
+            .lines()
+            .find(|line| line.starts_with("        \"\") echo \"check "))
+            .unwrap();
+        assert!(root.contains(" completions"));
+        assert!(!root.contains("__complete "));
+    }
+
+    #[test]
+    fn test_takes_manager() {
+        let mut nodes_found = Vec::new();
+        let mut command = Cli::command();
+        command.build();
+        nodes(&command, Vec::new(), &mut nodes_found);
+        let takes: Vec<String> = nodes_found
+            .iter()
+            .filter(|node| node.takes_manager)
+            .map(|node| node.path.join(" "))
+            .collect();
+        assert_eq!(
+            takes,
+            ["check", "update", "stats", "export-script", "watch", "run"]
+        );
+    }
+
+    #[test]
+    fn test_fish_script() {
+        let script = script("fish", &Cli::command());
+        assert!(script.contains(
+            "complete -c updog -f -n '__fish_use_subcommand' -a check -d 'Check for available updates'\n"
+        ));
+        assert!(script.contains(
+            "complete -c updog -f -n '__fish_seen_subcommand_from check' -a '(__fish_updog_managers)'\n"
+        ));
+        assert!(script.contains(
+            "complete -c updog -n '__fish_seen_subcommand_from status' -l format -x -a 'table waybar polybar'"
+        ));
+        assert!(script.contains("-n '__fish_seen_subcommand_from config; and not __fish_seen_subcommand_from lint approve import migrate show' -a lint"));
+    }
+}
@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+
+// How many managers run at once. Checks only read, so they can run side by
+// side; updates run one after another unless `--jobs` says otherwise:
+//
+//   concurrency:
+//     checks: 4                   # checks at once (0 or 1: one at a time)
+//     check_before_update: true   # `updog update` checks first, see below
+//
+// With `check_before_update` (or `updog update --check-parallel`), an update
+// run first checks every selected manager, concurrently, and then updates
+// only the ones that didn't come back up to date.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConcurrencyConfig {
+    #[serde(default, skip_serializing_if = "is_serial")]
+    pub checks: usize,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub check_before_update: bool,
+}
+
+fn is_serial(jobs: &usize) -> bool {
+    *jobs <= 1
+}
+
+impl ConcurrencyConfig {
+    // Checks to run at once
+    pub fn check_jobs(&self) -> usize {
+        self.checks.max(1)
+    }
+}
+
+// Call `run` for every index below `count`, up to `jobs` at a time, starting
+// them in order. Results are returned by index.
+pub fn run_parallel<T: Send>(jobs: usize, count: usize, run: impl Fn(usize) -> T + Sync) -> Vec<T> {
+    if jobs <= 1 || count <= 1 {
+        return (0..count).map(run).collect();
+    }
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<T>>> = Mutex::new((0..count).map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.min(count) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                if index >= count {
+                    break;
+                }
+                let result = run(index);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every index runs"))
+        .collect()
+}
+
+// What jobs running side by side mustn't share (a manager, a lock file):
+// a job claims all it needs at once, waiting while another holds any of it.
+// Jobs sharing a key take their turns in job order, so the subcommands of
+// a manager selected one after another still run in that order.
+#[derive(Debug)]
+pub struct Claims {
+    // The keys of each job, by index
+    keys: Vec<Vec<String>>,
+    state: Mutex<ClaimState>,
+    released: Condvar,
+}
+
+#[derive(Debug)]
+struct ClaimState {
+    held: HashSet<String>,
+    done: Vec<bool>,
+}
+
+// Released when dropped
+pub struct Claim<'a> {
+    claims: &'a Claims,
+    index: usize,
+}
+
+impl Claims {
+    pub fn new(keys: Vec<Vec<String>>) -> Self {
+        let done = vec![false; keys.len()];
+        Self {
+            keys,
+            state: Mutex::new(ClaimState {
+                held: HashSet::new(),
+                done,
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    // Wait for job `index`'s turn on its keys: none of them held, and every
+    // earlier job sharing one of them done
+    pub fn claim(&self, index: usize) -> Claim<'_> {
+        let keys = &self.keys[index];
+        let shares = |other: usize| self.keys[other].iter().any(|key| keys.contains(key));
+        let mut state = self.state.lock().unwrap();
+        while keys.iter().any(|key| state.held.contains(key))
+            || (0..index).any(|earlier| !state.done[earlier] && shares(earlier))
+        {
+            state = self.released.wait(state).unwrap();
+        }
+        state.held.extend(keys.iter().cloned());
+        Claim {
+            claims: self,
+            index,
+        }
+    }
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        let mut state = self.claims.state.lock().unwrap();
+        for key in &self.claims.keys[self.index] {
+            state.held.remove(key);
+        }
+        state.done[self.index] = true;
+        self.claims.released.notify_all();
+    }
+}
+
+// Indexes of `items` grouped into batches that run one after another: each
+// run of consecutive subcommands of a manager that `fans_out` is one batch,
+// whose items run side by side; every other item is a batch of its own
+pub fn batches(
+    items: &[(String, Option<String>)],
+    fans_out: impl Fn(&str) -> bool,
+) -> Vec<Vec<usize>> {
+    let mut batches: Vec<Vec<usize>> = Vec::new();
+    for (index, (manager, subcommand)) in items.iter().enumerate() {
+        let joins = subcommand.is_some() && fans_out(manager);
+        match batches.last_mut() {
+            Some(batch)
+                if joins && items[batch[0]].0 == *manager && items[batch[0]].1.is_some() =>
+            {
+                batch.push(index)
+            }
+            _ => batches.push(vec![index]),
+        }
+    }
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_config() {
+        let config: ConcurrencyConfig = serde_yaml::from_str("checks: 4").unwrap();
+        assert_eq!(config.check_jobs(), 4);
+        assert!(!config.check_before_update);
+        assert_eq!(ConcurrencyConfig::default().check_jobs(), 1);
+    }
+
+    #[test]
+    fn test_run_parallel_keeps_order() {
+        let results = run_parallel(3, 10, |index| {
+            // Later indexes finish first
+            std::thread::sleep(Duration::from_millis(10 * (10 - index as u64)));
+            index * 2
+        });
+        assert_eq!(results, (0..10).map(|i| i * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_claims_take_turns() {
+        // Jobs 0 and 2 share a lock file, so 2 waits for 0 while 1 runs
+        let claims = Claims::new(vec![
+            vec!["apt".to_string(), "/var/lib/dpkg/lock".to_string()],
+            vec!["npm".to_string()],
+            vec!["apt-get".to_string(), "/var/lib/dpkg/lock".to_string()],
+        ]);
+        let running = Mutex::new(Vec::new());
+        let overlapped = run_parallel(3, 3, |index| {
+            let _claim = claims.claim(index);
+            running.lock().unwrap().push(index);
+            std::thread::sleep(Duration::from_millis(50));
+            let others = running.lock().unwrap().clone();
+            running.lock().unwrap().retain(|&i| i != index);
+            others
+                .into_iter()
+                .filter(|&i| i != index)
+                .collect::<Vec<_>>()
+        });
+        assert!(!overlapped[0].contains(&2) && !overlapped[2].contains(&0));
+        let state = claims.state.lock().unwrap();
+        assert!(state.held.is_empty() && state.done.iter().all(|&done| done));
+    }
+
+    #[test]
+    fn test_claims_keep_job_order() {
+        // Job 1 asks first but waits for job 0, which shares its manager;
+        // job 2 shares nothing and doesn't wait
+        let claims = Claims::new(vec![
+            vec!["manager brew".to_string()],
+            vec!["manager brew".to_string()],
+            vec!["manager npm".to_string()],
+        ]);
+        let order = Mutex::new(Vec::new());
+        let (claims, order) = (&claims, &order);
+        std::thread::scope(|scope| {
+            let (asked, asking) = std::sync::mpsc::channel();
+            scope.spawn(move || {
+                let _claim = claims.claim(1);
+                order.lock().unwrap().push(1);
+            });
+            scope.spawn(move || {
+                let _claim = claims.claim(2);
+                asked.send(()).unwrap();
+            });
+            asking.recv().unwrap();
+            let _claim = claims.claim(0);
+            order.lock().unwrap().push(0);
+        });
+        assert_eq!(*order.lock().unwrap(), [0, 1]);
+    }
+
+    #[test]
+    fn test_run_parallel_overlaps() {
+        // Each job waits to see the other one started
+        let started = AtomicUsize::new(0);
+        let results = run_parallel(2, 2, |_| {
+            started.fetch_add(1, Ordering::SeqCst);
+            (0..500).any(|_| {
+                std::thread::sleep(Duration::from_millis(10));
+                started.load(Ordering::SeqCst) == 2
+            })
+        });
+        assert_eq!(results, [true, true]);
+    }
+
+    #[test]
+    fn test_batches() {
+        let items: Vec<(String, Option<String>)> = [
+            ("apt", None),
+            ("brew", Some("formulae")),
+            ("brew", Some("casks")),
+            ("brew", None),
+            ("npm", Some("global")),
+            ("npm", Some("local")),
+            ("brew", Some("casks")),
+        ]
+        .iter()
+        .map(|(m, sc)| (m.to_string(), sc.map(str::to_string)))
+        .collect();
+        assert_eq!(
+            batches(&items, |manager| manager == "brew"),
+            [vec![0], vec![1, 2], vec![3], vec![4], vec![5], vec![6]]
+        );
+        assert_eq!(batches(&items, |_| false).len(), items.len());
+    }
+}
@@ -1,109 +1,576 @@
 use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
+use crate::alert::AlertConfig;
+use crate::audit::AuditConfig;
+use crate::bandwidth::BandwidthLimit;
+use crate::concurrency::ConcurrencyConfig;
+use crate::cron::CronSchedule;
+use crate::daemon::DaemonConfig;
+use crate::disk::FreeSpace;
+use crate::docker_images::DockerImages;
+use crate::git_repo::GitRepo;
+use crate::github_release::GithubRelease;
+use crate::history::HistoryConfig;
+use crate::layer::{self, Origin};
+use crate::locale::Locale;
+use crate::migrate::{self, CONFIG_VERSION};
+use crate::output::{OutputFilter, OutputLimit, StatusOverride};
+use crate::plugin::LoadedPlugin;
+use crate::preset::Preset;
+use crate::proxy::ProxyConfig;
+use crate::redact::RedactRule;
+use crate::schedule::{Cadence, Interval};
+use crate::script::{self, ScriptingConfig};
+use crate::secret::SecretRef;
+use crate::shell::Shell;
+use crate::ssh::SshTarget;
+use crate::step::Step;
+use crate::wasm::is_wasm_parser;
+
 #[derive(Debug, Error)]
 pub enum ConfigError {
     #[error("Failed to read config file: {0}")]
     FileReadError(#[from] std::io::Error),
     #[error("Failed to parse config file: {0}")]
     ParseError(#[from] serde_yaml::Error),
+    #[error("Invalid config: {0}")]
+    ValidationError(String),
+    // A problem with one of several config layers (see `layer`)
+    #[error("{}: {source}", path.display())]
+    Layer {
+        path: PathBuf,
+        source: Box<ConfigError>,
+    },
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+// Kind of a `commands` entry
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryType {
+    #[default]
+    Manager,
+    // Named, ordered set of other entries (`members`)
+    Group,
+    // Single binary installed from GitHub release assets
+    #[serde(rename = "github-release")]
+    GithubRelease,
+    // Git working tree pulled from its upstream (dotfiles, plugins, sources)
+    #[serde(rename = "git-repo")]
+    GitRepo,
+    // Container images pulled when their tag moves to a newer digest
+    #[serde(rename = "docker-images")]
+    DockerImages,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct PackageManagerConfig {
     pub id: String,
+    #[serde(
+        default,
+        rename = "type",
+        skip_serializing_if = "EntryType::is_manager"
+    )]
+    pub entry_type: EntryType,
+    // Entries a group expands to, in order ("manager[:subcommand]" or other groups)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<String>,
+    // What the entry is for, shown by `updog list` and shell completion
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    // Heading the run summary groups the manager under (system, language,
+    // editor, apps, ...)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
     #[serde(default)]
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub subcommands: Vec<SubcommandConfig>,
+    // Subcommand run when none is named (`brew` rather than `brew:greedy`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_subcommand: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub check: Option<CommandSequence>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub update: Option<CommandSequence>,
+    // Alternative commands selected by -v/-q (fall back to check/update)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub check_verbose: Option<CommandSequence>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub check_quiet: Option<CommandSequence>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_verbose: Option<CommandSequence>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_quiet: Option<CommandSequence>,
+    // Commands beyond check/update (doctor, list, rollback, ...), run with
+    // `updog run <manager> <action>`; also available to the subcommands
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub actions: BTreeMap<String, CommandSequence>,
+    // Values for the run summary captured from the output of the manager and
+    // its subcommands, by column name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub summary_columns: BTreeMap<String, String>,
+    // Filter for captured output, also used by subcommands without their own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_filter: Option<OutputFilter>,
+    // success_regex and failure_regex, also used by subcommands without their own
+    #[serde(flatten)]
+    pub status_override: StatusOverride,
+    // Ask tools to emit color even though their output is captured
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub force_color: bool,
+    // Extra environment variables for this manager's commands. Values may be
+    // `secret://` references, resolved only when the commands actually run.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    // Daemon cadence for this manager (defaults to `daemon.every`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub every: Option<Interval>,
+    // Cron schedule for this manager (alternative to `every`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<CronSchedule>,
+    // Maximum random delay added to scheduled runs (defaults to `daemon.jitter`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jitter: Option<Interval>,
+    // Warn (and alert) when a run takes longer than this, without stopping it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warn_after: Option<Interval>,
+    // Reuse the last successful check for this long (see `cache`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cooldown: Option<Interval>,
+    // Lock files to wait for before running (inferred for apt, dnf and
+    // pacman commands; an empty list turns the check off)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locks: Option<Vec<String>>,
+    // How long to wait for a held lock before skipping the manager as busy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_wait: Option<Interval>,
+    // Commands run after a successful update to check that the manager (or
+    // what it installed) still works
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verify: Option<CommandSequence>,
+    // Free space required before updating (overrides the global setting)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_free_space: Option<FreeSpace>,
+    // Proxies for this manager's commands and downloads (replaces the
+    // global `proxy` section)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<ProxyConfig>,
+    // Locale for this manager's commands, or `native` to keep the user's
+    // (overrides the global `locale`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<Locale>,
+    // Start the commands from a clean environment (overrides the global
+    // `clean_env`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clean_env: Option<bool>,
+    // Variables a clean environment still takes from updog's, besides the
+    // global `pass_env`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pass_env: Vec<String>,
+    // Download rate the manager's commands are held to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bandwidth_limit: Option<BandwidthLimit>,
+    // Labels for selecting managers with `@tag` or `--tag`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    // Only updated when named on the command line (OS upgrades, firmware):
+    // runs of everything, tags, globs and groups leave it out
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub manual: bool,
+    // Acknowledges that the commands use sudo (silences the lint warning)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sudo: bool,
+    // Subcommands selected together (`brew:*`) run side by side
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub parallel_subcommands: bool,
+    // Rhai expression deciding whether the entry runs on this machine
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when_script: Option<String>,
+    // Rhai script rewriting captured `output`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform_output: Option<String>,
+    // Plugin turning check output into the list of pending updates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parser: Option<String>,
+    // repo, asset_pattern, ... of a `github-release` entry
+    #[serde(flatten)]
+    pub release: GithubRelease,
+    // path and build of a `git-repo` entry
+    #[serde(flatten)]
+    pub git: GitRepo,
+    // images of a `docker-images` entry
+    #[serde(flatten)]
+    pub docker: DockerImages,
+    // Built-in firmware, OS or app store commands (fwupd, flatpak, ...)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<Preset>,
+    // Remove unused flatpak runtimes or old snap revisions after updating
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cleanup: bool,
+    // Host the commands run on, over ssh
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh: Option<SshTarget>,
+}
+
+impl EntryType {
+    fn is_manager(&self) -> bool {
+        *self == EntryType::Manager
+    }
 }
 
 impl PackageManagerConfig {
+    pub fn is_group(&self) -> bool {
+        self.entry_type == EntryType::Group
+    }
+
     // Find a subcommand with the specified ID
     pub fn find_subcommand(&self, id: &str) -> Option<&SubcommandConfig> {
         self.subcommands.iter().find(|sc| sc.id == id)
     }
-    
-    // Get the default subcommand (either "default" or the first one)
+
+    // Get the default subcommand: the one `default_subcommand` names, else
+    // "default" or the first one
     pub fn default_subcommand(&self) -> Option<&SubcommandConfig> {
+        if let Some(id) = &self.default_subcommand {
+            return self.find_subcommand(id);
+        }
         let default_sc = self.find_subcommand("default");
         if default_sc.is_some() {
             return default_sc;
         }
-        
+
         if !self.subcommands.is_empty() {
             return Some(&self.subcommands[0]);
         }
-        
-        if self.check.is_some() || self.update.is_some() {
-            return None;
-        }
-        
+
         None
     }
-    
+
+    // The commands of `sc`: for a chain, the steps of the subcommands it
+    // runs, one after another, keeping its own filter, status patterns and
+    // actions. A variant (check_verbose, ...) is joined when one of them
+    // has it, from the plain command of the others.
+    pub fn chained_command(&self, sc: &SubcommandConfig) -> UpdateCommand {
+        if sc.runs.is_empty() {
+            return sc.command.clone();
+        }
+        let chained: Vec<UpdateCommand> = sc
+            .runs
+            .iter()
+            .filter_map(|id| self.find_subcommand(id))
+            .map(|member| self.chained_command(member))
+            .collect();
+        let join = |pick: fn(&UpdateCommand) -> Option<&CommandSequence>| {
+            let steps: Vec<Step> = chained
+                .iter()
+                .filter_map(pick)
+                .flat_map(CommandSequence::steps)
+                .collect();
+            (!steps.is_empty()).then_some(CommandSequence::Multiple(steps))
+        };
+        let has = |variant: fn(&UpdateCommand) -> bool| chained.iter().any(variant);
+        UpdateCommand {
+            check: join(|c| c.check.as_ref()),
+            update: join(|c| c.update.as_ref()),
+            check_verbose: has(|c| c.check_verbose.is_some())
+                .then(|| join(|c| c.check_for(Verbosity::Verbose)))
+                .flatten(),
+            check_quiet: has(|c| c.check_quiet.is_some())
+                .then(|| join(|c| c.check_for(Verbosity::Quiet)))
+                .flatten(),
+            update_verbose: has(|c| c.update_verbose.is_some())
+                .then(|| join(|c| c.update_for(Verbosity::Verbose)))
+                .flatten(),
+            update_quiet: has(|c| c.update_quiet.is_some())
+                .then(|| join(|c| c.update_for(Verbosity::Quiet)))
+                .flatten(),
+            ..sc.command.clone()
+        }
+    }
+
+    // Fail on a chain naming a subcommand the manager doesn't have, running
+    // itself, or running one with its own filter or status patterns (only
+    // the chain's apply to its steps); `path` is the chains being followed
+    fn check_chain<'a>(
+        &'a self,
+        sc: &'a SubcommandConfig,
+        path: &mut Vec<&'a str>,
+    ) -> Result<(), ConfigError> {
+        if path.contains(&sc.id.as_str()) {
+            path.push(&sc.id);
+            return Err(ConfigError::ValidationError(format!(
+                "{}: subcommand cycle: {}",
+                self.id,
+                path.join(" -> ")
+            )));
+        }
+        path.push(&sc.id);
+        for id in &sc.runs {
+            let Some(member) = self.find_subcommand(id) else {
+                return Err(ConfigError::ValidationError(format!(
+                    "{}:{} runs unknown subcommand {}",
+                    self.id, sc.id, id
+                )));
+            };
+            self.check_chain(member, path)?;
+            if member.command.output_filter.is_some() || !member.command.status_override.is_empty()
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "{}:{} runs {}, whose output_filter, success_regex and failure_regex \
+                     would be ignored; set them on {} instead",
+                    self.id, sc.id, id, sc.id
+                )));
+            }
+        }
+        path.pop();
+        Ok(())
+    }
+
+    // Whether check/update are defined on the entry itself rather than in subcommands
+    pub fn has_direct_command(&self) -> bool {
+        self.check.is_some()
+            || self.update.is_some()
+            || self.preset.is_some()
+            || matches!(
+                self.entry_type,
+                EntryType::GithubRelease | EntryType::GitRepo | EntryType::DockerImages
+            )
+    }
+
     // Find UpdateCommand including subcommand or direct commands (for backward compatibility)
     pub fn find_subcommand_command(&self, subcommand_id: Option<&str>) -> Option<UpdateCommand> {
         match subcommand_id {
-            Some(sc_id) => self.find_subcommand(sc_id).map(|sc| sc.command.clone()),
-            
+            Some(sc_id) => self
+                .find_subcommand(sc_id)
+                .map(|sc| self.chained_command(sc)),
+
             None => {
                 if let Some(sc) = self.default_subcommand() {
-                    return Some(sc.command.clone());
+                    return Some(self.chained_command(sc));
                 }
-                
-                if self.check.is_some() || self.update.is_some() {
-                    return Some(UpdateCommand {
-                        check: self.check.clone(),
-                        update: self.update.clone(),
-                    });
+
+                if self.has_direct_command() {
+                    return Some(self.direct_command());
                 }
-                
+
                 None
             }
         }
     }
+
+    // Commands defined directly on the package manager (simple format)
+    pub fn direct_command(&self) -> UpdateCommand {
+        if self.entry_type == EntryType::GithubRelease {
+            let step = |step: Step| Some(CommandSequence::Multiple(vec![step]));
+            return UpdateCommand {
+                check: step(Step::ReleaseCheck(self.release.clone())),
+                update: step(Step::ReleaseInstall(self.release.clone())),
+                actions: self.actions.clone(),
+                output_filter: self.output_filter.clone(),
+                ..Default::default()
+            };
+        }
+        if let Some(preset) = self.preset {
+            let mut update = vec![Step::PresetUpdate(preset)];
+            if self.cleanup {
+                update.push(Step::PresetCleanup(preset));
+            }
+            return UpdateCommand {
+                check: preset
+                    .has_check()
+                    .then(|| CommandSequence::Multiple(vec![Step::PresetCheck(preset)])),
+                update: preset
+                    .has_update()
+                    .then_some(CommandSequence::Multiple(update)),
+                actions: self.actions.clone(),
+                output_filter: self.output_filter.clone(),
+                ..Default::default()
+            };
+        }
+        if self.entry_type == EntryType::DockerImages {
+            return UpdateCommand {
+                check: Some(CommandSequence::Multiple(self.docker.check_steps())),
+                update: Some(CommandSequence::Multiple(self.docker.update_steps())),
+                actions: self.actions.clone(),
+                output_filter: self.output_filter.clone(),
+                ..Default::default()
+            };
+        }
+        if self.entry_type == EntryType::GitRepo {
+            return UpdateCommand {
+                check: Some(CommandSequence::Multiple(self.git.check_steps())),
+                update: Some(CommandSequence::Multiple(self.git.update_steps())),
+                actions: self.actions.clone(),
+                output_filter: self.output_filter.clone(),
+                ..Default::default()
+            };
+        }
+        UpdateCommand {
+            check: self.check.clone(),
+            update: self.update.clone(),
+            check_verbose: self.check_verbose.clone(),
+            check_quiet: self.check_quiet.clone(),
+            update_verbose: self.update_verbose.clone(),
+            update_quiet: self.update_quiet.clone(),
+            actions: self.actions.clone(),
+            output_filter: self.output_filter.clone(),
+            status_override: self.status_override.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SubcommandConfig {
     pub id: String,
+    // What sets the subcommand apart (e.g. "casks, including auto-updating ones")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    // Other subcommands of the manager to run in order instead of commands
+    // of its own (`runs: [formulae, casks, cleanup]`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub runs: Vec<String>,
     #[serde(flatten)]
     pub command: UpdateCommand,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Clone)]
 #[serde(untagged)]
 pub enum CommandSequence {
     Single(String),
-    Multiple(Vec<String>),
+    Multiple(Vec<Step>),
+}
+
+impl<'de> Deserialize<'de> for CommandSequence {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_yaml::Value::deserialize(deserializer)?;
+        Self::from_yaml(&value).map_err(serde::de::Error::custom)
+    }
 }
 
 impl CommandSequence {
+    // A command string, a lone built-in step, or a list of both
+    fn from_yaml(value: &serde_yaml::Value) -> Result<Self, String> {
+        match value {
+            serde_yaml::Value::String(s) => Ok(CommandSequence::Single(s.clone())),
+            serde_yaml::Value::Mapping(_) => {
+                Ok(CommandSequence::Multiple(vec![Step::from_yaml(value)?]))
+            }
+            serde_yaml::Value::Sequence(steps) => steps
+                .iter()
+                .map(Step::from_yaml)
+                .collect::<Result<_, _>>()
+                .map(CommandSequence::Multiple),
+            _ => Err("expected a command, a step or a list of them".to_string()),
+        }
+    }
+
     pub fn as_single_str(&self) -> Option<&str> {
         match self {
             CommandSequence::Single(s) => Some(s),
             _ => None,
         }
     }
-    
-    pub fn as_multiple(&self) -> Option<&Vec<String>> {
+
+    pub fn as_multiple(&self) -> Option<&Vec<Step>> {
         match self {
             CommandSequence::Multiple(v) => Some(v),
             _ => None,
         }
     }
+
+    // Individual steps, in execution order
+    pub fn steps(&self) -> Vec<Step> {
+        match self {
+            CommandSequence::Single(s) => vec![Step::Command(s.clone())],
+            CommandSequence::Multiple(v) => v.clone(),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Clone)]
+// Output verbosity requested on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    Quiet,
+    #[default]
+    Normal,
+    Verbose,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
 pub struct UpdateCommand {
     pub check: Option<CommandSequence>,
     pub update: Option<CommandSequence>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check_verbose: Option<CommandSequence>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check_quiet: Option<CommandSequence>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update_verbose: Option<CommandSequence>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update_quiet: Option<CommandSequence>,
+    // Named commands beyond check/update (`updog run`)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub actions: BTreeMap<String, CommandSequence>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_filter: Option<OutputFilter>,
+    #[serde(flatten)]
+    pub status_override: StatusOverride,
+}
+
+impl UpdateCommand {
+    // Check command for the given verbosity, falling back to the plain one
+    pub fn check_for(&self, verbosity: Verbosity) -> Option<&CommandSequence> {
+        let variant = match verbosity {
+            Verbosity::Quiet => self.check_quiet.as_ref(),
+            Verbosity::Normal => None,
+            Verbosity::Verbose => self.check_verbose.as_ref(),
+        };
+        variant.or(self.check.as_ref())
+    }
+
+    // Update command for the given verbosity, falling back to the plain one
+    pub fn update_for(&self, verbosity: Verbosity) -> Option<&CommandSequence> {
+        let variant = match verbosity {
+            Verbosity::Quiet => self.update_quiet.as_ref(),
+            Verbosity::Normal => None,
+            Verbosity::Verbose => self.update_verbose.as_ref(),
+        };
+        variant.or(self.update.as_ref())
+    }
+
+    // Every command sequence that is set, with its config key
+    pub fn sequences(&self) -> Vec<(&'static str, &CommandSequence)> {
+        [
+            ("check", &self.check),
+            ("check_verbose", &self.check_verbose),
+            ("check_quiet", &self.check_quiet),
+            ("update", &self.update),
+            ("update_verbose", &self.update_verbose),
+            ("update_quiet", &self.update_quiet),
+        ]
+        .into_iter()
+        .filter_map(|(key, sequence)| sequence.as_ref().map(|s| (key, s)))
+        .collect()
+    }
+}
+
+// Parse a command sequence stored under `key`
+fn sequence_from_map<E: serde::de::Error>(
+    map: &serde_yaml::Mapping,
+    key: &str,
+) -> Result<Option<CommandSequence>, E> {
+    match map.get(serde_yaml::Value::String(key.to_string())) {
+        Some(serde_yaml::Value::Null) | None => Ok(None),
+        Some(value) => CommandSequence::from_yaml(value)
+            .map(Some)
+            .map_err(|e| E::custom(format!("{}: {}", key, e))),
+    }
 }
 
 impl<'de> Deserialize<'de> for UpdateCommand {
@@ -115,53 +582,41 @@ impl<'de> Deserialize<'de> for UpdateCommand {
 
         match value {
             serde_yaml::Value::Mapping(map) => {
-                let mut check = None;
-                let mut update = None;
-
-                if let Some(check_val) = map.get(&serde_yaml::Value::String("check".to_string())) {
-                    if let serde_yaml::Value::String(s) = check_val {
-                        check = Some(CommandSequence::Single(s.clone()));
-                    } else if let serde_yaml::Value::Sequence(seq) = check_val {
-                        let commands: Result<Vec<String>, _> = seq
-                            .iter()
-                            .map(|val| {
-                                if let serde_yaml::Value::String(s) = val {
-                                    Ok(s.clone())
-                                } else {
-                                    Err(serde::de::Error::custom(
-                                        "Expected string in command sequence",
-                                    ))
-                                }
-                            })
-                            .collect();
-                        
-                        check = Some(CommandSequence::Multiple(commands?));
-                    }
-                }
+                let check = sequence_from_map(&map, "check")?;
+                let update = sequence_from_map(&map, "update")?;
+                let check_verbose = sequence_from_map(&map, "check_verbose")?;
+                let check_quiet = sequence_from_map(&map, "check_quiet")?;
+                let update_verbose = sequence_from_map(&map, "update_verbose")?;
+                let update_quiet = sequence_from_map(&map, "update_quiet")?;
+                let actions = match map.get(serde_yaml::Value::String("actions".to_string())) {
+                    Some(actions) => serde_yaml::from_value(actions.clone())
+                        .map_err(|e| serde::de::Error::custom(format!("actions: {}", e)))?,
+                    None => BTreeMap::new(),
+                };
 
-                if let Some(update_val) = map.get(&serde_yaml::Value::String("update".to_string()))
-                {
-                    if let serde_yaml::Value::String(s) = update_val {
-                        update = Some(CommandSequence::Single(s.clone()));
-                    } else if let serde_yaml::Value::Sequence(seq) = update_val {
-                        let commands: Result<Vec<String>, _> = seq
-                            .iter()
-                            .map(|val| {
-                                if let serde_yaml::Value::String(s) = val {
-                                    Ok(s.clone())
-                                } else {
-                                    Err(serde::de::Error::custom(
-                                        "Expected string in command sequence",
-                                    ))
-                                }
-                            })
-                            .collect();
-                        
-                        update = Some(CommandSequence::Multiple(commands?));
-                    }
-                }
+                let output_filter =
+                    match map.get(serde_yaml::Value::String("output_filter".to_string())) {
+                        Some(filter_val) => Some(
+                            serde_yaml::from_value(filter_val.clone())
+                                .map_err(serde::de::Error::custom)?,
+                        ),
+                        None => None,
+                    };
+                // Other keys are ignored, as when deserializing the manager
+                let status_override = serde_yaml::from_value(serde_yaml::Value::Mapping(map))
+                    .map_err(serde::de::Error::custom)?;
 
-                Ok(UpdateCommand { check, update })
+                Ok(UpdateCommand {
+                    check,
+                    update,
+                    check_verbose,
+                    check_quiet,
+                    update_verbose,
+                    update_quiet,
+                    actions,
+                    output_filter,
+                    status_override,
+                })
             }
 
             _ => Err(serde::de::Error::custom(
@@ -171,68 +626,669 @@ impl<'de> Deserialize<'de> for UpdateCommand {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    // Config version the file is written for (see `migrate`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    // Shell the commands run in (bash, or PowerShell on Windows, by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<Shell>,
     pub commands: Vec<PackageManagerConfig>,
+    // Maximum captured output retained per command
+    #[serde(default)]
+    pub output_limit: OutputLimit,
+    // Values masked in every output sink (terminal, logs, history)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redact: Vec<RedactRule>,
+    #[serde(default)]
+    pub daemon: DaemonConfig,
+    #[serde(default)]
+    pub history: HistoryConfig,
+    #[serde(default)]
+    pub alerts: AlertConfig,
+    #[serde(default)]
+    pub audit: AuditConfig,
+    #[serde(default)]
+    pub scripting: ScriptingConfig,
+    // Free space required before any manager updates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_free_space: Option<FreeSpace>,
+    // Battery charge (percent) below which an update run on battery power
+    // starts no more managers (see `guard`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_battery: Option<u8>,
+    // Proxies for every manager's commands, alert commands and downloads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<ProxyConfig>,
+    // Locale every manager's commands run in (C unless set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<Locale>,
+    // Start every manager's commands from PATH, `pass_env` and the variables
+    // updog sets, instead of updog's whole environment
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub clean_env: bool,
+    // Variables a clean environment still takes from updog's (e.g. HOME)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pass_env: Vec<String>,
+    // Checks run at once, and whether updates check first
+    #[serde(default)]
+    pub concurrency: ConcurrencyConfig,
+    // Names of the updog-<name> plugins to load
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub plugins: Vec<String>,
+    // Filled in by `plugin::load`
+    #[serde(skip)]
+    pub loaded_plugins: Vec<LoadedPlugin>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: None,
+            shell: None,
+            commands: vec![PackageManagerConfig {
+                id: "homebrew".to_string(),
+                entry_type: EntryType::Manager,
+                members: Vec::new(),
+                description: None,
+                category: None,
+                default_subcommand: None,
+                subcommands: vec![SubcommandConfig {
+                    id: "default".to_string(),
+                    description: None,
+                    runs: Vec::new(),
+                    command: UpdateCommand {
+                        check: Some(CommandSequence::Single("brew outdated".to_string())),
+                        update: Some(CommandSequence::Single("brew upgrade".to_string())),
+                        ..Default::default()
+                    },
+                }],
+                check: None,
+                update: None,
+                check_verbose: None,
+                check_quiet: None,
+                update_verbose: None,
+                update_quiet: None,
+                actions: BTreeMap::new(),
+                summary_columns: BTreeMap::new(),
+                output_filter: None,
+                status_override: StatusOverride::default(),
+                force_color: false,
+                env: BTreeMap::new(),
+                every: None,
+                schedule: None,
+                jitter: None,
+                warn_after: None,
+                cooldown: None,
+                locks: None,
+                lock_wait: None,
+                verify: None,
+                min_free_space: None,
+                proxy: None,
+                locale: None,
+                clean_env: None,
+                pass_env: Vec::new(),
+                bandwidth_limit: None,
+                tags: Vec::new(),
+                manual: false,
+                sudo: false,
+                parallel_subcommands: false,
+                when_script: None,
+                transform_output: None,
+                parser: None,
+                release: GithubRelease::default(),
+                git: GitRepo::default(),
+                docker: DockerImages::default(),
+                preset: None,
+                cleanup: false,
+                ssh: None,
+            }],
+            output_limit: OutputLimit::default(),
+            redact: Vec::new(),
+            daemon: DaemonConfig::default(),
+            history: HistoryConfig::default(),
+            alerts: AlertConfig::default(),
+            audit: AuditConfig::default(),
+            scripting: ScriptingConfig::default(),
+            min_free_space: None,
+            min_battery: None,
+            proxy: None,
+            locale: None,
+            clean_env: false,
+            pass_env: Vec::new(),
+            concurrency: ConcurrencyConfig::default(),
+            plugins: Vec::new(),
+            loaded_plugins: Vec::new(),
+        }
+    }
 }
 
 impl Config {
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
-        let content = fs::read_to_string(path)?;
-        let config = serde_yaml::from_str(&content)?;
+        let content = fs::read_to_string(&path)?;
+        let mut config: Config = serde_yaml::from_str(&content)?;
+        config.validate()?;
+
+        // WASM parsers are found relative to the config file, not the working directory
+        let dir = path.as_ref().parent().unwrap_or(Path::new(""));
+        for manager in &mut config.commands {
+            if let Some(parser) = &mut manager.parser {
+                if is_wasm_parser(parser)
+                    && Path::new(parser).is_relative()
+                    && !parser.starts_with("~/")
+                {
+                    *parser = dir.join(&*parser).display().to_string();
+                }
+            }
+        }
         Ok(config)
     }
 
-    pub fn default() -> Self {
-        Self {
-            commands: vec![
-                PackageManagerConfig {
-                    id: "homebrew".to_string(),
-                    subcommands: vec![
-                        SubcommandConfig {
-                            id: "default".to_string(),
-                            command: UpdateCommand {
-                                check: Some(CommandSequence::Single("brew outdated".to_string())),
-                                update: Some(CommandSequence::Single("brew upgrade".to_string())),
-                            },
-                        },
-                    ],
-                    check: None,
-                    update: None,
-                },
-            ],
+    // The config merged from `layers`, the system one first (see `layer`).
+    // A single layer is read as `from_file` does.
+    pub fn from_layers(layers: &[(Origin, &Path)]) -> Result<Self, ConfigError> {
+        if let [(_, path)] = layers {
+            return Self::from_file(path);
         }
+        let layers = layers
+            .iter()
+            .map(|(origin, path)| Ok((*origin, layer::read(path)?)))
+            .collect::<Result<Vec<_>, ConfigError>>()?;
+        let (mut merged, _) = layer::merge(layers);
+        // Either layer may leave the managers to the other
+        if let serde_yaml::Value::Mapping(mapping) = &mut merged {
+            mapping
+                .entry("commands".into())
+                .or_insert_with(|| serde_yaml::Value::Sequence(Vec::new()));
+        }
+        let config: Config = serde_yaml::from_value(merged)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Config version the file is written for; 1 without `version:`
+    pub fn version(&self) -> u32 {
+        self.version.unwrap_or(1)
+    }
+
+    // Check values that can't be expressed in the YAML schema (e.g. regexes)
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.version() > CONFIG_VERSION {
+            return Err(ConfigError::ValidationError(format!(
+                "config version {} is newer than this updog supports ({}); update updog",
+                self.version(),
+                CONFIG_VERSION
+            )));
+        }
+        let version = self.version();
+        if let Some(dropped) = migrate::deprecations(self)
+            .into_iter()
+            .find(|d| d.since <= version)
+        {
+            return Err(ConfigError::ValidationError(format!(
+                "{}: {}",
+                dropped.location, dropped.message
+            )));
+        }
+
+        for group in self.commands.iter().filter(|m| m.is_group()) {
+            if group.members.is_empty() {
+                return Err(ConfigError::ValidationError(format!(
+                    "group {} has no members",
+                    group.id
+                )));
+            }
+            if !group.subcommands.is_empty()
+                || !group.direct_command().sequences().is_empty()
+                || !group.actions.is_empty()
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "group {} can't define commands of its own",
+                    group.id
+                )));
+            }
+            self.expand(&group.id)?;
+        }
+
+        // check and update (and their variants) aren't actions
+        for entry in &self.commands {
+            let actions = entry
+                .actions
+                .keys()
+                .map(|action| (entry.id.clone(), action));
+            let sc_actions = entry.subcommands.iter().flat_map(|sc| {
+                let name = format!("{}:{}", entry.id, sc.id);
+                sc.command
+                    .actions
+                    .keys()
+                    .map(move |action| (name.clone(), action))
+            });
+            for (id, action) in actions.chain(sc_actions) {
+                let reserved = [
+                    "check",
+                    "update",
+                    "check_verbose",
+                    "check_quiet",
+                    "update_verbose",
+                    "update_quiet",
+                ];
+                if reserved.contains(&action.as_str()) || action.trim().is_empty() {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}: '{}' can't be an action (check and update are set on their own)",
+                        id, action
+                    )));
+                }
+            }
+        }
+
+        for entry in &self.commands {
+            if entry.cleanup && !entry.preset.is_some_and(|p| p.has_cleanup()) {
+                return Err(ConfigError::ValidationError(format!(
+                    "{}: cleanup needs the flatpak or snap preset",
+                    entry.id
+                )));
+            }
+            let name = match (entry.entry_type, entry.preset) {
+                (EntryType::Manager, Some(preset)) => format!("{} (preset {})", entry.id, preset),
+                (_, Some(_)) => {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}: preset can't be combined with type",
+                        entry.id
+                    )))
+                }
+                (EntryType::GithubRelease, None) => format!("github-release {}", entry.id),
+                (EntryType::GitRepo, None) => format!("git-repo {}", entry.id),
+                (EntryType::DockerImages, None) => format!("docker-images {}", entry.id),
+                _ => continue,
+            };
+            let own_commands = !entry.subcommands.is_empty()
+                || [
+                    &entry.check,
+                    &entry.update,
+                    &entry.check_verbose,
+                    &entry.check_quiet,
+                ]
+                .into_iter()
+                .chain([&entry.update_verbose, &entry.update_quiet])
+                .any(Option::is_some);
+            if own_commands {
+                return Err(ConfigError::ValidationError(format!(
+                    "{} can't define commands of its own",
+                    name
+                )));
+            }
+        }
+
+        for (_, name, sequence) in self.sequences() {
+            for step in sequence.steps() {
+                step.validate()
+                    .map_err(|e| ConfigError::ValidationError(format!("{}: {}", name, e)))?;
+            }
+        }
+
+        for entry in &self.commands {
+            if let Some(limit) = &entry.bandwidth_limit {
+                limit
+                    .validate()
+                    .map_err(|e| ConfigError::ValidationError(format!("{}: {}", entry.id, e)))?;
+            }
+        }
+
+        // Remote commands go through `ssh ... -- host 'command'`, quoted for
+        // a POSIX shell, and only plain commands can be sent that way
+        for entry in &self.commands {
+            let Some(target) = &entry.ssh else {
+                continue;
+            };
+            target
+                .validate()
+                .map_err(|e| ConfigError::ValidationError(format!("{}: {}", entry.id, e)))?;
+            if entry.entry_type != EntryType::Manager || entry.preset.is_some() {
+                return Err(ConfigError::ValidationError(format!(
+                    "{}: ssh can't be combined with type or preset",
+                    entry.id
+                )));
+            }
+            let shell = self.shell.unwrap_or_else(Shell::platform_default);
+            if !matches!(shell, Shell::Bash | Shell::Sh) {
+                return Err(ConfigError::ValidationError(format!(
+                    "{}: ssh needs bash or sh as the shell, not {}",
+                    entry.id, shell
+                )));
+            }
+        }
+        for (manager, name, sequence) in self.sequences() {
+            if manager.ssh.is_some() && sequence.steps().iter().any(|s| s.as_command().is_none()) {
+                return Err(ConfigError::ValidationError(format!(
+                    "{}: only commands can run over ssh (no verify, download or check_http steps)",
+                    name
+                )));
+            }
+        }
+
+        for manager in &self.commands {
+            if let Some(id) = &manager.default_subcommand {
+                if manager.find_subcommand(id).is_none() {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}: default_subcommand {} isn't one of its subcommands",
+                        manager.id, id
+                    )));
+                }
+            }
+            for sc in manager.subcommands.iter().filter(|sc| !sc.runs.is_empty()) {
+                if !sc.command.sequences().is_empty() {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}:{}: runs can't be combined with commands of its own",
+                        manager.id, sc.id
+                    )));
+                }
+                manager.check_chain(sc, &mut Vec::new())?;
+            }
+        }
+
+        for manager in &self.commands {
+            let filters = manager
+                .subcommands
+                .iter()
+                .map(|sc| {
+                    (
+                        format!("{}:{}", manager.id, sc.id),
+                        &sc.command.output_filter,
+                    )
+                })
+                .chain(std::iter::once((
+                    manager.id.clone(),
+                    &manager.output_filter,
+                )));
+
+            for (name, filter) in filters {
+                if let Some(filter) = filter {
+                    filter.validate().map_err(|e| {
+                        ConfigError::ValidationError(format!("output_filter of {}: {}", name, e))
+                    })?;
+                }
+            }
+
+            let overrides = manager
+                .subcommands
+                .iter()
+                .map(|sc| {
+                    (
+                        format!("{}:{}", manager.id, sc.id),
+                        &sc.command.status_override,
+                    )
+                })
+                .chain(std::iter::once((
+                    manager.id.clone(),
+                    &manager.status_override,
+                )));
+            for (name, status_override) in overrides {
+                status_override.validate().map_err(|(key, e)| {
+                    ConfigError::ValidationError(format!("{} of {}: {}", key, name, e))
+                })?;
+            }
+
+            for (column, pattern) in &manager.summary_columns {
+                regex::Regex::new(pattern).map_err(|e| {
+                    ConfigError::ValidationError(format!(
+                        "summary_columns.{} of {}: {}",
+                        column, manager.id, e
+                    ))
+                })?;
+            }
+
+            for (key, script) in [
+                ("when_script", &manager.when_script),
+                ("transform_output", &manager.transform_output),
+            ] {
+                if let Some(script) = script {
+                    script::compile(script).map_err(|e| {
+                        ConfigError::ValidationError(format!("{} of {}: {}", key, manager.id, e))
+                    })?;
+                }
+            }
+
+            if let Some(parser) = &manager.parser {
+                if !is_wasm_parser(parser) && !self.plugins.contains(parser) {
+                    return Err(ConfigError::ValidationError(format!(
+                        "parser {} of {} isn't listed in `plugins`",
+                        parser, manager.id
+                    )));
+                }
+            }
+
+            if manager.every.is_some() && manager.schedule.is_some() {
+                return Err(ConfigError::ValidationError(format!(
+                    "{} sets both `every` and `schedule`",
+                    manager.id
+                )));
+            }
+
+            for (name, value) in &manager.env {
+                if let Some(Err(e)) = SecretRef::parse(value) {
+                    return Err(ConfigError::ValidationError(format!(
+                        "env {} of {}: {}",
+                        name, manager.id, e
+                    )));
+                }
+            }
+        }
+
+        for (index, rule) in self.redact.iter().enumerate() {
+            rule.validate().map_err(|e| {
+                ConfigError::ValidationError(format!("redact entry {}: {}", index + 1, e))
+            })?;
+        }
+        Ok(())
+    }
+
+    // Every command sequence of every manager, named "manager[:subcommand] key"
+    pub fn sequences(&self) -> Vec<(&PackageManagerConfig, String, CommandSequence)> {
+        let mut sequences = Vec::new();
+        for manager in &self.commands {
+            let commands = manager
+                .subcommands
+                .iter()
+                .map(|sc| (format!("{}:{}", manager.id, sc.id), sc.command.clone()))
+                .chain(std::iter::once((
+                    manager.id.clone(),
+                    manager.direct_command(),
+                )));
+            for (name, command) in commands {
+                for (key, sequence) in command.sequences() {
+                    sequences.push((manager, format!("{} {}", name, key), sequence.clone()));
+                }
+                for (action, sequence) in &command.actions {
+                    sequences.push((manager, format!("{} {}", name, action), sequence.clone()));
+                }
+            }
+            if let Some(verify) = &manager.verify {
+                sequences.push((manager, format!("{} verify", manager.id), verify.clone()));
+            }
+        }
+        sequences
     }
 
     pub fn find_package_manager(&self, id: &str) -> Option<&PackageManagerConfig> {
         self.commands.iter().find(|pm| pm.id == id)
     }
-    
+
+    // Proxy settings for a manager's commands: its own, or the global ones
+    pub fn proxy_for<'a>(
+        &'a self,
+        manager: Option<&'a PackageManagerConfig>,
+    ) -> Option<&'a ProxyConfig> {
+        manager
+            .and_then(|m| m.proxy.as_ref())
+            .or(self.proxy.as_ref())
+    }
+
+    // Locale a manager's commands run in
+    pub fn locale_for(&self, manager: Option<&PackageManagerConfig>) -> Locale {
+        manager
+            .and_then(|m| m.locale.clone())
+            .or_else(|| self.locale.clone())
+            .unwrap_or_default()
+    }
+
+    // Variables a manager's commands take from updog's environment when
+    // they start from a clean one, or None when they inherit all of it
+    pub fn clean_env_for(&self, manager: Option<&PackageManagerConfig>) -> Option<Vec<String>> {
+        let clean = manager.and_then(|m| m.clean_env).unwrap_or(self.clean_env);
+        clean.then(|| {
+            self.pass_env
+                .iter()
+                .chain(manager.into_iter().flat_map(|m| &m.pass_env))
+                .cloned()
+                .collect()
+        })
+    }
+
+    // Package managers, i.e. every entry that isn't a group, in config order
+    pub fn managers(&self) -> impl Iterator<Item = &PackageManagerConfig> {
+        self.commands.iter().filter(|m| !m.is_group())
+    }
+
+    // `category` of every manager that has one, by id
+    pub fn categories(&self) -> BTreeMap<String, String> {
+        self.managers()
+            .filter_map(|m| Some((m.id.clone(), m.category.clone()?)))
+            .collect()
+    }
+
+    // Resolve "manager[:subcommand]" or a group id into the (manager,
+    // subcommand) pairs to run, in order. Nested groups are expanded in
+    // place and members listed more than once only run the first time.
+    pub fn expand(&self, selector: &str) -> Result<Vec<(String, Option<String>)>, ConfigError> {
+        let mut expanded = Vec::new();
+        self.expand_into(selector, &mut Vec::new(), &mut expanded)?;
+        Ok(expanded)
+    }
+
+    fn expand_into(
+        &self,
+        selector: &str,
+        path: &mut Vec<String>,
+        expanded: &mut Vec<(String, Option<String>)>,
+    ) -> Result<(), ConfigError> {
+        let (id, subcommand) = match selector.split_once(':') {
+            Some((id, subcommand)) => (id, Some(subcommand.to_string())),
+            None => (selector, None),
+        };
+        let entry = self.find_package_manager(id);
+
+        match entry {
+            Some(group) if group.is_group() && subcommand.is_none() => {
+                if path.iter().any(|p| p == id) {
+                    path.push(id.to_string());
+                    return Err(ConfigError::ValidationError(format!(
+                        "group cycle: {}",
+                        path.join(" -> ")
+                    )));
+                }
+                path.push(id.to_string());
+                for member in &group.members {
+                    self.expand_into(member, path, expanded)?;
+                }
+                path.pop();
+            }
+            Some(group) if group.is_group() => {
+                return Err(ConfigError::ValidationError(format!(
+                    "{} is a group and has no subcommands",
+                    id
+                )));
+            }
+            // Unknown top-level selectors are reported when they are run
+            None if !path.is_empty() => {
+                return Err(ConfigError::ValidationError(format!(
+                    "group {} has unknown member {}",
+                    path.last().unwrap(),
+                    selector
+                )));
+            }
+            _ => {
+                let item = (id.to_string(), subcommand);
+                if !expanded.contains(&item) {
+                    expanded.push(item);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Scheduled cadence of a manager: its own schedule or interval,
+    // otherwise the daemon's default schedule or interval
+    pub fn cadence(&self, manager_id: &str) -> Cadence {
+        let daemon = &self.daemon;
+        let cron = |schedule: &CronSchedule| Cadence::Cron(schedule.clone(), daemon.timezone);
+        let manager = self.find_package_manager(manager_id);
+
+        if let Some(schedule) = manager.and_then(|m| m.schedule.as_ref()) {
+            return cron(schedule);
+        }
+        if let Some(every) = manager.and_then(|m| m.every) {
+            return Cadence::Every(every);
+        }
+        match &daemon.schedule {
+            Some(schedule) => cron(schedule),
+            None => Cadence::Every(daemon.every),
+        }
+    }
+
+    // Maximum random delay for a manager's scheduled runs
+    pub fn jitter(&self, manager_id: &str) -> Option<Interval> {
+        self.find_package_manager(manager_id)
+            .and_then(|m| m.jitter)
+            .or(self.daemon.jitter)
+    }
+
     // Find a subcommand for a specific package manager
-    pub fn find_subcommand(&self, manager_id: &str, subcommand_id: Option<&str>) -> Option<SubcommandConfig> {
+    pub fn find_subcommand(
+        &self,
+        manager_id: &str,
+        subcommand_id: Option<&str>,
+    ) -> Option<SubcommandConfig> {
         let manager = self.find_package_manager(manager_id)?;
-        
+
+        // Subcommands without their own output filter, status patterns or
+        // actions inherit the manager's
+        let inherit_filter = |sc: &SubcommandConfig| {
+            let mut sc = sc.clone();
+            sc.command = manager.chained_command(&sc);
+            if sc.command.output_filter.is_none() {
+                sc.command.output_filter = manager.output_filter.clone();
+            }
+            sc.command.status_override = sc.command.status_override.or(&manager.status_override);
+            for (action, command) in &manager.actions {
+                sc.command
+                    .actions
+                    .entry(action.clone())
+                    .or_insert_with(|| command.clone());
+            }
+            sc
+        };
+
         if let Some(sc_id) = subcommand_id {
             if let Some(sc) = manager.find_subcommand(sc_id) {
-                return Some(sc.clone());
+                return Some(inherit_filter(sc));
             }
             return None;
         }
-        
+
         if let Some(sc) = manager.default_subcommand() {
-            return Some(sc.clone());
+            return Some(inherit_filter(sc));
         }
-        
-        if manager.check.is_some() || manager.update.is_some() {
+
+        if manager.has_direct_command() {
             return Some(SubcommandConfig {
                 id: "default".to_string(),
-                command: UpdateCommand {
-                    check: manager.check.clone(),
-                    update: manager.update.clone(),
-                },
+                description: None,
+                runs: Vec::new(),
+                command: manager.direct_command(),
             });
         }
-        
+
         None
     }
 }
@@ -247,12 +1303,12 @@ mod tests {
         assert_eq!(config.commands.len(), 1);
         let homebrew = &config.commands[0];
         assert_eq!(homebrew.id, "homebrew");
-        
+
         // Test subcommands
         assert_eq!(homebrew.subcommands.len(), 1);
         let default_sc = homebrew.default_subcommand().unwrap();
         assert_eq!(default_sc.id, "default");
-        
+
         let cmd = &default_sc.command;
         assert!(cmd.check.is_some());
         assert!(cmd.update.is_some());
@@ -261,11 +1317,11 @@ mod tests {
     #[test]
     fn test_find_package_manager() {
         let config = Config::default();
-        
+
         let homebrew = config.find_package_manager("homebrew");
         assert!(homebrew.is_some());
         assert_eq!(homebrew.unwrap().id, "homebrew");
-        
+
         let unknown = config.find_package_manager("unknown");
         assert!(unknown.is_none());
     }
@@ -287,17 +1343,137 @@ mod tests {
 
         let config: Config = serde_yaml::from_str(yaml).unwrap();
         assert_eq!(config.commands.len(), 2);
-        
+
         let homebrew = config.find_package_manager("homebrew").unwrap();
         let homebrew_default = homebrew.default_subcommand().unwrap();
         assert!(homebrew_default.command.check.is_some());
-        
+
         let npm = config.find_package_manager("npm").unwrap();
         let npm_default = npm.default_subcommand().unwrap();
         assert!(npm_default.command.update.is_some());
         assert!(npm_default.command.check.is_none());
     }
 
+    #[test]
+    fn test_default_subcommand_override() {
+        let yaml = r#"
+        commands:
+          - id: homebrew
+            default_subcommand: greedy
+            subcommands:
+              - id: default
+                update: brew upgrade
+              - id: greedy
+                update: brew upgrade --greedy
+        "#;
+
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        let homebrew = config.find_package_manager("homebrew").unwrap();
+        assert_eq!(homebrew.default_subcommand().unwrap().id, "greedy");
+        let command = homebrew.find_subcommand_command(None).unwrap();
+        assert_eq!(
+            command.update.unwrap().as_single_str(),
+            Some("brew upgrade --greedy")
+        );
+
+        let config: Config = serde_yaml::from_str(
+            &yaml.replace("default_subcommand: greedy", "default_subcommand: cask"),
+        )
+        .unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(
+            err.contains("homebrew: default_subcommand cask isn't one of its subcommands"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_chained_subcommands() {
+        let yaml = r#"
+        commands:
+          - id: homebrew
+            subcommands:
+              - id: formulae
+                check: brew outdated --formula
+                update: brew upgrade --formula
+                update_verbose: brew upgrade --formula --verbose
+              - id: casks
+                check: brew outdated --cask
+                update: [brew upgrade --cask, brew cleanup --prune=all]
+              - id: cleanup
+                update: brew autoremove
+              - id: all
+                runs: [formulae, casks, cleanup]
+              - id: everything
+                runs: [all]
+                output_filter:
+                  exclude: ["^==> "]
+        "#;
+
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        let all = config
+            .find_subcommand("homebrew", Some("everything"))
+            .unwrap();
+        let steps = |sequence: Option<&CommandSequence>| -> Vec<String> {
+            sequence
+                .unwrap()
+                .steps()
+                .iter()
+                .map(|step| step.as_command().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(
+            steps(all.command.check.as_ref()),
+            ["brew outdated --formula", "brew outdated --cask"]
+        );
+        assert_eq!(
+            steps(all.command.update_for(Verbosity::Verbose)),
+            [
+                "brew upgrade --formula --verbose",
+                "brew upgrade --cask",
+                "brew cleanup --prune=all",
+                "brew autoremove"
+            ]
+        );
+        assert!(all.command.check_verbose.is_none());
+        assert!(all.command.output_filter.is_some());
+
+        for (from, to, error) in [
+            (
+                "runs: [formulae, casks, cleanup]",
+                "runs: [formulae, cask]",
+                "homebrew:all runs unknown subcommand cask",
+            ),
+            (
+                "runs: [formulae, casks, cleanup]",
+                "runs: [formulae, everything]",
+                "homebrew: subcommand cycle: all -> everything -> all",
+            ),
+            (
+                "runs: [all]",
+                "runs: [all]\n                check: brew outdated",
+                "homebrew:everything: runs can't be combined with commands of its own",
+            ),
+            (
+                "update: brew autoremove",
+                "update: brew autoremove\n                success_regex: Removing",
+                "homebrew:all runs cleanup, whose output_filter",
+            ),
+            (
+                "runs: [formulae, casks, cleanup]",
+                "runs: [formulae, casks, cleanup]\n                output_filter:\n                  drop: [\"^==> \"]",
+                "homebrew:everything runs all, whose output_filter",
+            ),
+        ] {
+            let config: Config = serde_yaml::from_str(&yaml.replace(from, to)).unwrap();
+            let err = config.validate().unwrap_err().to_string();
+            assert!(err.contains(error), "{}", err);
+        }
+    }
+
     #[test]
     fn test_parse_command_sequence() {
         let yaml = r#"
@@ -316,22 +1492,25 @@ mod tests {
 
         let config: Config = serde_yaml::from_str(yaml).unwrap();
         assert_eq!(config.commands.len(), 2);
-        
+
         let npm = config.find_package_manager("npm").unwrap();
         let npm_default = npm.default_subcommand().unwrap();
         let npm_update = &npm_default.command.update;
         assert!(npm_update.is_some());
-        
+
         let commands = npm_update.as_ref().unwrap().as_multiple().unwrap();
         assert_eq!(commands.len(), 2);
         assert_eq!(commands[0], "npm cache clean -f");
         assert_eq!(commands[1], "npm update -g");
-        
+
         let rust = config.find_package_manager("rust").unwrap();
         let rust_default = rust.default_subcommand().unwrap();
         let rust_update = &rust_default.command.update;
         assert!(rust_update.is_some());
-        assert_eq!(rust_update.as_ref().unwrap().as_single_str().unwrap(), "rustup update");
+        assert_eq!(
+            rust_update.as_ref().unwrap().as_single_str().unwrap(),
+            "rustup update"
+        );
     }
 
     #[test]
@@ -352,7 +1531,7 @@ mod tests {
         assert_eq!(config.commands[0].id, "brew");
         assert_eq!(config.commands[1].id, "npm");
     }
-    
+
     #[test]
     fn test_parse_subcommands() {
         let yaml = r#"
@@ -368,22 +1547,22 @@ mod tests {
 
         let config: Config = serde_yaml::from_str(yaml).unwrap();
         let rustup = config.find_package_manager("rustup").unwrap();
-        
+
         // Test default subcommand
         let default_sc = rustup.default_subcommand().unwrap();
         assert_eq!(default_sc.id, "default");
         assert!(default_sc.command.check.is_some());
-        
+
         // Test specific subcommand
         let self_sc = rustup.find_subcommand("self").unwrap();
         assert_eq!(self_sc.id, "self");
         assert!(self_sc.command.update.is_some());
         assert!(self_sc.command.check.is_none());
-        
+
         // Test find_subcommand method
         let self_sc2 = config.find_subcommand("rustup", Some("self")).unwrap();
         assert_eq!(self_sc2.id, "self");
-        
+
         // Test default subcommand
         let default_sc2 = config.find_subcommand("rustup", None).unwrap();
         assert_eq!(default_sc2.id, "default");
@@ -408,10 +1587,10 @@ mod tests {
 
         let config: Config = serde_yaml::from_str(yaml).unwrap();
         assert_eq!(config.commands.len(), 2);
-        
+
         let npm = config.find_package_manager("npm").unwrap();
         assert_eq!(npm.subcommands.len(), 2);
-        
+
         let npm_default = npm.default_subcommand().unwrap();
         assert_eq!(npm_default.id, "default");
         assert!(npm_default.command.update.is_some());
@@ -436,22 +1615,25 @@ mod tests {
 
         let config: Config = serde_yaml::from_str(yaml).unwrap();
         assert_eq!(config.commands.len(), 2);
-        
+
         let npm = config.find_package_manager("npm").unwrap();
         let npm_default = npm.default_subcommand().unwrap();
         let npm_update = &npm_default.command.update;
         assert!(npm_update.is_some());
-        
+
         let commands = npm_update.as_ref().unwrap().as_multiple().unwrap();
         assert_eq!(commands.len(), 2);
         assert_eq!(commands[0], "npm cache clean -f");
         assert_eq!(commands[1], "npm update -g");
-        
+
         let rust = config.find_package_manager("rust").unwrap();
         let rust_default = rust.default_subcommand().unwrap();
         let rust_update = &rust_default.command.update;
         assert!(rust_update.is_some());
-        assert_eq!(rust_update.as_ref().unwrap().as_single_str().unwrap(), "rustup update");
+        assert_eq!(
+            rust_update.as_ref().unwrap().as_single_str().unwrap(),
+            "rustup update"
+        );
     }
 
     #[test]
@@ -472,45 +1654,647 @@ mod tests {
 
         let config: Config = serde_yaml::from_str(yaml).unwrap();
         assert_eq!(config.commands.len(), 2);
-        
+
         // Test package manager with direct fields only
         let brew = config.find_package_manager("brew").unwrap();
         assert_eq!(brew.subcommands.len(), 0);
         assert!(brew.check.is_some());
         assert!(brew.update.is_some());
-        
+
         // Test if find_subcommand properly handles direct fields
         let brew_subcommand = config.find_subcommand("brew", None).unwrap();
         assert_eq!(brew_subcommand.id, "default");
         assert_eq!(
-            brew_subcommand.command.check.as_ref().unwrap().as_single_str().unwrap(),
+            brew_subcommand
+                .command
+                .check
+                .as_ref()
+                .unwrap()
+                .as_single_str()
+                .unwrap(),
             "brew outdated"
         );
         assert_eq!(
-            brew_subcommand.command.update.as_ref().unwrap().as_single_str().unwrap(),
+            brew_subcommand
+                .command
+                .update
+                .as_ref()
+                .unwrap()
+                .as_single_str()
+                .unwrap(),
             "brew upgrade"
         );
-        
+
         // Test package manager with both direct fields and subcommands
         let mixed = config.find_package_manager("mixed").unwrap();
         assert_eq!(mixed.subcommands.len(), 1);
         assert!(mixed.check.is_some());
         assert!(mixed.update.is_some());
-        
+
         // Default subcommand (subcommands have priority)
         let mixed_default = config.find_subcommand("mixed", None).unwrap();
         assert_eq!(mixed_default.id, "sub1");
         assert_eq!(
-            mixed_default.command.check.as_ref().unwrap().as_single_str().unwrap(),
+            mixed_default
+                .command
+                .check
+                .as_ref()
+                .unwrap()
+                .as_single_str()
+                .unwrap(),
             "sub1 check"
         );
-        
+
         // Specific subcommand request
         let mixed_sub1 = config.find_subcommand("mixed", Some("sub1")).unwrap();
         assert_eq!(mixed_sub1.id, "sub1");
-        
+
         // Direct fields should not be returned when requesting a nonexistent subcommand
         let nonexistent = config.find_subcommand("mixed", Some("nonexistent"));
         assert!(nonexistent.is_none());
     }
+
+    #[test]
+    fn test_parse_output_filter() {
+        let yaml = r#"
+        commands:
+          - id: npm
+            output_filter:
+              drop: funding
+            subcommands:
+              - id: default
+                update: npm update -g
+              - id: quiet
+                update: npm update -g
+                output_filter:
+                  keep: ["^added"]
+        "#;
+
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+
+        // Subcommand without its own filter inherits the manager's
+        let default_sc = config.find_subcommand("npm", None).unwrap();
+        assert_eq!(
+            default_sc.command.output_filter.unwrap().drop,
+            vec!["funding"]
+        );
+
+        let quiet = config.find_subcommand("npm", Some("quiet")).unwrap();
+        let filter = quiet.command.output_filter.unwrap();
+        assert_eq!(filter.keep, vec!["^added"]);
+        assert!(filter.drop.is_empty());
+    }
+
+    #[test]
+    fn test_validate_invalid_output_filter() {
+        let yaml = r#"
+        commands:
+          - id: brew
+            check: brew outdated
+            output_filter:
+              drop: "(unclosed"
+        "#;
+
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("output_filter of brew"));
+    }
+
+    #[test]
+    fn test_validate_invalid_summary_column() {
+        let yaml = r#"
+        commands:
+          - id: composer
+            check: composer outdated --direct
+            summary_columns:
+              outdated: "(\\d+ outdated"
+        "#;
+
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("summary_columns.outdated of composer"));
+    }
+
+    #[test]
+    fn test_validate_invalid_status_regex() {
+        let yaml = r#"
+        commands:
+          - id: brew
+            subcommands:
+              - id: casks
+                update: brew upgrade --cask
+                success_regex: "(unclosed"
+        "#;
+
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("success_regex of brew:casks"));
+    }
+
+    #[test]
+    fn test_parse_output_limit() {
+        let yaml = r#"
+        commands: []
+        output_limit:
+          head_kb: 8
+        "#;
+
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.output_limit.head_kb, 8);
+        assert_eq!(config.output_limit.tail_kb, 64);
+
+        let config: Config = serde_yaml::from_str("commands: []").unwrap();
+        assert_eq!(config.output_limit, OutputLimit::default());
+    }
+
+    #[test]
+    fn test_actions() {
+        let yaml = r#"
+        commands:
+          - id: brew
+            actions:
+              doctor: brew doctor
+              list: brew list
+            subcommands:
+              - id: cask
+                update: brew upgrade --cask
+                actions:
+                  list: brew list --cask
+                  rollback:
+                    - brew uninstall --cask foo
+                    - brew install --cask foo@1
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        let cask = config
+            .find_subcommand("brew", Some("cask"))
+            .unwrap()
+            .command;
+        let actions: Vec<&str> = cask.actions.keys().map(String::as_str).collect();
+        assert_eq!(actions, ["doctor", "list", "rollback"]);
+        assert_eq!(
+            cask.actions["list"].as_single_str(),
+            Some("brew list --cask")
+        );
+        assert_eq!(cask.actions["rollback"].steps().len(), 2);
+        // Linted like the other commands
+        assert!(config
+            .sequences()
+            .iter()
+            .any(|(_, name, _)| name == "brew:cask rollback"));
+
+        let yaml = r#"
+        commands:
+          - id: brew
+            subcommands:
+              - id: cask
+                actions:
+                  update_quiet: brew upgrade --cask --quiet
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(
+            err.contains("brew:cask: 'update_quiet' can't be an action"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_verbosity_variants() {
+        let yaml = r#"
+        commands:
+          - id: brew
+            subcommands:
+              - id: default
+                check: brew outdated
+                update: brew upgrade
+                update_verbose: brew upgrade --verbose
+                update_quiet:
+                  - brew upgrade --quiet
+                  - brew cleanup --quiet
+          - id: npm
+            update: npm update -g
+            update_verbose: npm update -g --loglevel verbose
+        "#;
+
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let brew = config.find_subcommand("brew", None).unwrap().command;
+
+        let update = |v| brew.update_for(v).unwrap().clone();
+        assert_eq!(
+            update(Verbosity::Normal).as_single_str(),
+            Some("brew upgrade")
+        );
+        assert_eq!(
+            update(Verbosity::Verbose).as_single_str(),
+            Some("brew upgrade --verbose")
+        );
+        assert_eq!(update(Verbosity::Quiet).as_multiple().unwrap().len(), 2);
+
+        // No variant defined: fall back to the plain command
+        assert_eq!(
+            brew.check_for(Verbosity::Verbose).unwrap().as_single_str(),
+            Some("brew outdated")
+        );
+
+        // Simple format carries the variants too
+        let npm = config.find_subcommand("npm", None).unwrap().command;
+        assert_eq!(
+            npm.update_for(Verbosity::Verbose).unwrap().as_single_str(),
+            Some("npm update -g --loglevel verbose")
+        );
+        assert!(npm.check_for(Verbosity::Verbose).is_none());
+    }
+
+    #[test]
+    fn test_validate_secret_references() {
+        let yaml = r#"
+        commands:
+          - id: npm
+            update: npm update -g
+            env:
+              NPM_TOKEN: secret://env/CI_NPM_TOKEN
+              NPM_CONFIG_REGISTRY: https://registry.example.com
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.commands[0].env.len(), 2);
+
+        let yaml = r#"
+        commands:
+          - id: npm
+            update: npm update -g
+            env:
+              NPM_TOKEN: secret://vault/npm
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("env NPM_TOKEN of npm"));
+    }
+
+    #[test]
+    fn test_validate_redact_rules() {
+        let yaml = r#"
+        commands: []
+        redact:
+          - env: GITHUB_TOKEN
+          - pattern: "("
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.redact.len(), 2);
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("redact entry 2"));
+    }
+
+    #[test]
+    fn test_parse_daemon_schedule() {
+        let yaml = r#"
+        daemon:
+          every: 12h
+          operation: update
+        commands:
+          - id: brew
+            update: brew upgrade
+            every: weekly
+          - id: npm
+            update: npm update -g
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.daemon.every.to_string(), "12h");
+        assert_eq!(config.daemon.operation, crate::report::Operation::Update);
+        assert_eq!(config.commands[0].every, Some(Interval::WEEK));
+        assert!(config.commands[1].every.is_none());
+
+        let config: Config = serde_yaml::from_str("commands: []").unwrap();
+        assert_eq!(config.daemon, DaemonConfig::default());
+
+        let invalid = "commands:\n  - id: brew\n    every: often\n";
+        assert!(serde_yaml::from_str::<Config>(invalid).is_err());
+    }
+
+    #[test]
+    fn test_parse_cron_schedule() {
+        let yaml = r#"
+        daemon:
+          schedule: "0 9 * * MON"
+          timezone: "+09:00"
+        commands:
+          - id: brew
+            update: brew upgrade
+            schedule: "@daily"
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.daemon.schedule.unwrap().to_string(), "0 9 * * MON");
+        assert_eq!(config.daemon.timezone.to_string(), "+09:00");
+        assert_eq!(
+            config.commands[0].schedule.as_ref().unwrap().to_string(),
+            "@daily"
+        );
+
+        let both = "commands:\n  - id: brew\n    every: 6h\n    schedule: '@daily'\n";
+        let config: Config = serde_yaml::from_str(both).unwrap();
+        assert!(config.validate().is_err());
+
+        let invalid = "commands:\n  - id: brew\n    schedule: '0 9 * *'\n";
+        assert!(serde_yaml::from_str::<Config>(invalid).is_err());
+    }
+
+    #[test]
+    fn test_parse_lone_builtin_step() {
+        let yaml = r#"
+        commands:
+          - id: fzf
+            check:
+              check_http:
+                url: https://api.github.com/repos/junegunn/fzf/releases/latest
+                json: tag_name
+                version_command: fzf --version
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        let fzf = config.find_subcommand("fzf", None).unwrap().command;
+        let steps = fzf.check.unwrap().steps();
+        assert!(matches!(steps[..], [Step::CheckHttp { .. }]));
+
+        let yaml = "commands:\n  - id: x\n    check:\n      fetch: https://example.com\n";
+        assert!(serde_yaml::from_str::<Config>(yaml).is_err());
+    }
+
+    #[test]
+    fn test_parse_git_repo() {
+        let yaml = r#"
+        commands:
+          - id: dwm
+            type: git-repo
+            path: ~/src/dwm
+            build: make && sudo make install
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        let dwm = config.find_subcommand("dwm", None).unwrap().command;
+        assert!(matches!(
+            dwm.check.unwrap().steps()[..],
+            [Step::GitCheck(_)]
+        ));
+        assert_eq!(
+            dwm.update.unwrap().steps(),
+            [
+                "git -C ~/'src/dwm' pull --ff-only",
+                "cd ~/'src/dwm' && make && sudo make install"
+            ]
+        );
+        let saved = serde_yaml::to_string(&config).unwrap();
+        assert!(saved.contains("type: git-repo"));
+        assert!(saved.contains("path: ~/src/dwm"));
+
+        let config: Config = serde_yaml::from_str(&yaml.replace("path:", "# path:")).unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("dwm check: missing path"), "{}", err);
+        let config: Config =
+            serde_yaml::from_str(&format!("{}    update: make install\n", yaml)).unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("git-repo dwm can't define commands of its own"));
+    }
+
+    #[test]
+    fn test_parse_docker_images() {
+        let yaml = r#"
+        commands:
+          - id: containers
+            type: docker-images
+            images: [nginx:stable, postgres:16]
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        let containers = config.find_subcommand("containers", None).unwrap().command;
+        assert!(matches!(
+            &containers.check.unwrap().steps()[..],
+            [Step::ImageCheck(docker)] if docker.images == ["nginx:stable", "postgres:16"]
+        ));
+        assert!(matches!(
+            containers.update.unwrap().steps()[..],
+            [Step::ImagePull(_)]
+        ));
+        let saved = serde_yaml::to_string(&config).unwrap();
+        assert!(saved.contains("type: docker-images"));
+
+        let config: Config =
+            serde_yaml::from_str(&format!("{}    update: docker compose pull\n", yaml)).unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("docker-images containers can't define commands of its own"));
+    }
+
+    #[test]
+    fn test_parse_preset() {
+        let yaml = r#"
+        commands:
+          - id: firmware
+            preset: fwupd
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        let firmware = config.find_subcommand("firmware", None).unwrap().command;
+        assert!(matches!(
+            firmware.check.unwrap().steps()[..],
+            [Step::PresetCheck(Preset::Fwupd)]
+        ));
+        assert!(matches!(
+            firmware.update.unwrap().steps()[..],
+            [Step::PresetUpdate(Preset::Fwupd)]
+        ));
+        assert!(serde_yaml::to_string(&config)
+            .unwrap()
+            .contains("preset: fwupd"));
+
+        let config: Config =
+            serde_yaml::from_str(&format!("{}    check: fwupdmgr get-updates\n", yaml)).unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("firmware (preset fwupd) can't define commands of its own"));
+        assert!(serde_yaml::from_str::<Config>(&yaml.replace("fwupd", "bios")).is_err());
+
+        let config: Config = serde_yaml::from_str(&format!("{}    cleanup: true\n", yaml)).unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("firmware: cleanup needs the flatpak or snap preset"));
+        let config: Config = serde_yaml::from_str(&format!(
+            "{}    cleanup: true\n",
+            yaml.replace("fwupd", "flatpak")
+        ))
+        .unwrap();
+        config.validate().unwrap();
+        let update = config
+            .find_subcommand("firmware", None)
+            .unwrap()
+            .command
+            .update;
+        assert!(matches!(
+            update.unwrap().steps()[..],
+            [
+                Step::PresetUpdate(Preset::Flatpak),
+                Step::PresetCleanup(Preset::Flatpak)
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_parse_github_release() {
+        let yaml = r#"
+        commands:
+          - id: lazygit
+            type: github-release
+            repo: jesseduffield/lazygit
+            asset_pattern: "lazygit_*_Linux_x86_64.tar.gz"
+            install_dir: ~/.local/bin
+            version_command: lazygit --version
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        let lazygit = config.find_subcommand("lazygit", None).unwrap().command;
+        assert!(matches!(
+            lazygit.check.unwrap().steps()[..],
+            [Step::ReleaseCheck(_)]
+        ));
+        assert!(matches!(
+            lazygit.update.unwrap().steps()[..],
+            [Step::ReleaseInstall(_)]
+        ));
+        let saved = serde_yaml::to_string(&config).unwrap();
+        assert!(saved.contains("type: github-release"));
+        assert!(saved.contains("repo: jesseduffield/lazygit"));
+
+        let config: Config = serde_yaml::from_str(&yaml.replace("repo:", "# repo:")).unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("lazygit check: missing repo"));
+        let config: Config =
+            serde_yaml::from_str(&format!("{}    update: make install\n", yaml)).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_wasm_parser_relative_to_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        fs::write(
+            &path,
+            "commands:\n  - id: brew\n    check: brew outdated\n    parser: ./parsers/brew.wasm\n  - id: npm\n    check: npm outdated\n    parser: npm-json\n",
+        )
+        .unwrap();
+        // A non-WASM parser must be a plugin enabled in `plugins`
+        assert!(Config::from_file(&path).is_err());
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::write(&path, format!("plugins: [npm-json]\n{}", content)).unwrap();
+        let config = Config::from_file(&path).unwrap();
+        assert_eq!(
+            config.commands[0].parser.as_deref().map(Path::new),
+            Some(dir.path().join("./parsers/brew.wasm").as_path())
+        );
+        assert_eq!(config.commands[1].parser.as_deref(), Some("npm-json"));
+    }
+
+    #[test]
+    fn test_from_layers() {
+        let dir = tempfile::tempdir().unwrap();
+        let system = dir.path().join("system.yaml");
+        let user = dir.path().join("user.yaml");
+        fs::write(
+            &system,
+            "commands:\n  - id: apt\n    update: sudo apt upgrade -y\nmin_free_space:\n  size: 2G\n",
+        )
+        .unwrap();
+        // The user layer only adds a setting, keeping the system's managers
+        fs::write(&user, "min_battery: 30\n").unwrap();
+        let config =
+            Config::from_layers(&[(Origin::System, &system), (Origin::User, &user)]).unwrap();
+        assert_eq!(config.commands.len(), 1);
+        assert!(config.min_free_space.is_some());
+        assert_eq!(config.min_battery, Some(30));
+
+        // Merged configs are validated as a whole
+        fs::write(
+            &user,
+            "commands:\n  - id: all\n    type: group\n    members: [gone]\n",
+        )
+        .unwrap();
+        assert!(Config::from_layers(&[(Origin::System, &system), (Origin::User, &user)]).is_err());
+        fs::write(&system, "commands: [").unwrap();
+        let err = Config::from_layers(&[(Origin::System, &system), (Origin::User, &user)])
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with(&system.display().to_string()), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_history_retention() {
+        let yaml = "history:\n  keep_runs: 500\n  keep_days: 90\ncommands: []\n";
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.history.keep_runs, Some(500));
+        assert_eq!(config.history.keep_days, Some(90));
+
+        let config: Config = serde_yaml::from_str("commands: []").unwrap();
+        assert!(config.history.is_unlimited());
+    }
+
+    #[test]
+    fn test_group_expansion() {
+        let yaml = r#"
+        commands:
+          - id: brew
+            update: brew upgrade
+          - id: npm
+            update: npm update -g
+          - id: rust
+            subcommands:
+              - id: toolchain
+                update: rustup update
+          - id: dev
+            type: group
+            members: [npm, "rust:toolchain"]
+          - id: essentials
+            type: group
+            members: [brew, dev, npm]
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+
+        let pair = |id: &str, sc: Option<&str>| (id.to_string(), sc.map(str::to_string));
+        assert_eq!(
+            config.expand("essentials").unwrap(),
+            vec![
+                pair("brew", None),
+                pair("npm", None),
+                pair("rust", Some("toolchain"))
+            ]
+        );
+        assert_eq!(config.expand("brew").unwrap(), vec![pair("brew", None)]);
+        let managers: Vec<&str> = config.managers().map(|m| m.id.as_str()).collect();
+        assert_eq!(managers, vec!["brew", "npm", "rust"]);
+    }
+
+    #[test]
+    fn test_validate_groups() {
+        let invalid = |yaml: &str| {
+            let config: Config = serde_yaml::from_str(yaml).unwrap();
+            config.validate().unwrap_err().to_string()
+        };
+
+        let cycle = "commands:\n  - {id: a, type: group, members: [b]}\n  - {id: b, type: group, members: [a]}\n";
+        assert_eq!(invalid(cycle), "Invalid config: group cycle: a -> b -> a");
+
+        let unknown = "commands:\n  - {id: a, type: group, members: [nope]}\n";
+        assert_eq!(
+            invalid(unknown),
+            "Invalid config: group a has unknown member nope"
+        );
+
+        let with_command = "commands:\n  - {id: a, type: group, members: [b], update: x}\n  - {id: b, update: y}\n";
+        assert!(invalid(with_command).contains("can't define commands"));
+
+        let empty = "commands:\n  - {id: a, type: group}\n";
+        assert!(invalid(empty).contains("no members"));
+    }
 }
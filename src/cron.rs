@@ -0,0 +1,321 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::timezone::Timezone;
+
+const MONTH_NAMES: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+const WEEKDAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+const SYSTEMD_WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+// Upper bound on search steps when looking for the next matching time
+// (a few years' worth of day/hour skips)
+const MAX_STEPS: usize = 100_000;
+
+// Standard five-field cron expression: minute hour day-of-month month day-of-week
+//
+//   schedule: "0 9 * * MON"       # 09:00 every Monday
+//   schedule: "*/30 8-18 * * 1-5" # every 30 minutes during office hours
+//   schedule: "@daily"
+//
+// Fields accept `*`, lists, ranges, steps and month/weekday names. As in
+// Vixie cron, when both day fields are restricted a day matching either one
+// is a match.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronSchedule {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl TryFrom<String> for CronSchedule {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<CronSchedule> for String {
+    fn from(schedule: CronSchedule) -> Self {
+        schedule.source
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+// Parse one field into a bitset of allowed values in min..=max
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |text: &str| -> Result<u32, String> {
+        let upper = text.to_ascii_uppercase();
+        if let Some(index) = names.iter().position(|name| *name == upper) {
+            // Month names start at 1, weekday names at 0
+            return Ok(index as u32 + min);
+        }
+        text.parse::<u32>()
+            .map_err(|_| format!("invalid value '{}'", text))
+    };
+
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| format!("invalid step '{}'", step))?;
+                if step == 0 {
+                    return Err("step must be positive".to_string());
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (value(start)?, value(end)?)
+        } else {
+            let start = value(range)?;
+            // "5/15" means every 15 starting at 5
+            (start, if part.contains('/') { max } else { start })
+        };
+
+        if start < min || end > max || start > end {
+            return Err(format!("'{}' is out of range {}-{}", part, min, max));
+        }
+        for v in (start..=end).step_by(step as usize) {
+            bits |= 1 << v;
+        }
+    }
+    Ok(bits)
+}
+
+impl std::str::FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let source = value.trim();
+        let expression = match source {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!(
+                "invalid cron expression '{}' (expected 5 fields: minute hour day month weekday)",
+                source
+            ));
+        }
+        fn context(name: &'static str) -> impl Fn(String) -> String {
+            move |e| format!("cron {} field: {}", name, e)
+        }
+
+        let mut weekdays =
+            parse_field(fields[4], 0, 7, &WEEKDAY_NAMES).map_err(context("weekday"))?;
+        // Both 0 and 7 mean Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            source: source.to_string(),
+            minutes: parse_field(fields[0], 0, 59, &[]).map_err(context("minute"))?,
+            hours: parse_field(fields[1], 0, 23, &[]).map_err(context("hour"))?,
+            days: parse_field(fields[2], 1, 31, &[]).map_err(context("day"))?,
+            months: parse_field(fields[3], 1, 12, &MONTH_NAMES).map_err(context("month"))?,
+            weekdays,
+            days_restricted: fields[2] != "*",
+            weekdays_restricted: fields[4] != "*",
+        })
+    }
+}
+
+impl CronSchedule {
+    fn day_matches(&self, day: u32, weekday: u32) -> bool {
+        let day_ok = self.days & (1 << day) != 0;
+        let weekday_ok = self.weekdays & (1 << weekday) != 0;
+        if self.days_restricted && self.weekdays_restricted {
+            day_ok || weekday_ok
+        } else {
+            day_ok && weekday_ok
+        }
+    }
+
+    // Equivalent systemd OnCalendar= expressions (without a time zone). Two
+    // are needed when both day fields are restricted, since cron ORs them.
+    pub fn on_calendar(&self) -> Vec<String> {
+        let list = |bits: u64, min: u32, max: u32| -> String {
+            if (min..=max).all(|v| bits & (1 << v) != 0) {
+                return "*".to_string();
+            }
+            (min..=max)
+                .filter(|v| bits & (1 << v) != 0)
+                .map(|v| format!("{:02}", v))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let weekdays = (0..7)
+            .filter(|d| self.weekdays & (1 << d) != 0)
+            .map(|d| SYSTEMD_WEEKDAYS[d])
+            .collect::<Vec<_>>()
+            .join(",");
+        let time = format!(
+            "{}:{}:00",
+            list(self.hours, 0, 23),
+            list(self.minutes, 0, 59)
+        );
+        let months = list(self.months, 1, 12);
+        let days = list(self.days, 1, 31);
+
+        let by_weekday = format!("{} *-{}-* {}", weekdays, months, time);
+        let by_day = format!("*-{}-{} {}", months, days, time);
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => vec![by_weekday, by_day],
+            (false, true) => vec![by_weekday],
+            _ => vec![by_day],
+        }
+    }
+
+    // Whether the minute `time` is in matches, evaluated in `timezone`
+    pub fn matches(&self, time: SystemTime, timezone: Timezone) -> bool {
+        let civil = timezone.civil(time);
+        self.months & (1 << civil.month) != 0
+            && self.day_matches(civil.day, civil.weekday)
+            && self.hours & (1 << civil.hour) != 0
+            && self.minutes & (1 << civil.minute) != 0
+    }
+
+    // First matching minute strictly after `time`, evaluated in `timezone`
+    pub fn next_after(&self, time: SystemTime, timezone: Timezone) -> Option<SystemTime> {
+        let secs = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        let mut candidate = UNIX_EPOCH + Duration::from_secs((secs / 60 + 1) * 60);
+
+        for _ in 0..MAX_STEPS {
+            let civil = timezone.civil(candidate);
+            let skip_to = if self.months & (1 << civil.month) == 0 {
+                Some(civil.next_month_start_wall())
+            } else if !self.day_matches(civil.day, civil.weekday) {
+                Some(civil.day_start_wall() + 86_400)
+            } else if self.hours & (1 << civil.hour) == 0 {
+                Some(civil.wall_seconds() - (civil.minute * 60 + civil.second) as i64 + 3600)
+            } else if self.minutes & (1 << civil.minute) == 0 {
+                None
+            } else {
+                return Some(candidate);
+            };
+
+            let next = skip_to.map(|wall| timezone.instant_from_wall(wall));
+            // Always make progress, even across DST transitions
+            candidate = match next {
+                Some(next) if next > candidate => next,
+                _ => candidate + Duration::from_secs(60),
+            };
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-01-01T00:00:00Z was a Monday
+    fn at(days: u64, hour: u64, minute: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_704_067_200 + days * 86_400 + hour * 3600 + minute * 60)
+    }
+
+    fn next(expression: &str, from: SystemTime) -> SystemTime {
+        expression
+            .parse::<CronSchedule>()
+            .unwrap()
+            .next_after(from, Timezone::Utc)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_weekly_schedule() {
+        assert_eq!(next("0 9 * * MON", at(0, 8, 0)), at(0, 9, 0));
+        // Strictly after: at 09:00 the next run is a week later
+        assert_eq!(next("0 9 * * MON", at(0, 9, 0)), at(7, 9, 0));
+        assert_eq!(next("0 9 * * mon", at(2, 12, 0)), at(7, 9, 0));
+    }
+
+    #[test]
+    fn test_steps_ranges_and_lists() {
+        assert_eq!(next("*/15 * * * *", at(0, 10, 1)), at(0, 10, 15));
+        assert_eq!(next("30 8-18/2 * * *", at(0, 9, 0)), at(0, 10, 30));
+        assert_eq!(next("0 0 1,15 * *", at(3, 0, 0)), at(14, 0, 0));
+        assert_eq!(next("@monthly", at(3, 0, 0)), at(31, 0, 0));
+        // 7 is Sunday too
+        assert_eq!(next("0 0 * * 7", at(0, 0, 0)), at(6, 0, 0));
+    }
+
+    #[test]
+    fn test_matches() {
+        let office: CronSchedule = "* 9-17 * * MON-FRI".parse().unwrap();
+        assert!(office.matches(at(0, 9, 0), Timezone::Utc));
+        assert!(office.matches(at(4, 17, 59), Timezone::Utc));
+        assert!(!office.matches(at(0, 18, 0), Timezone::Utc));
+        // 2024-01-06 was a Saturday
+        assert!(!office.matches(at(5, 12, 0), Timezone::Utc));
+    }
+
+    #[test]
+    fn test_day_fields_are_ored_when_both_restricted() {
+        // The 10th, or any Friday (2024-01-05)
+        assert_eq!(next("0 0 10 * FRI", at(0, 1, 0)), at(4, 0, 0));
+    }
+
+    #[test]
+    fn test_timezone_is_honored() {
+        let schedule: CronSchedule = "0 9 * * *".parse().unwrap();
+        let tokyo = Timezone::Fixed(9 * 3600);
+        // 09:00 in Tokyo is 00:00 UTC
+        assert_eq!(schedule.next_after(at(0, 1, 0), tokyo), Some(at(1, 0, 0)));
+    }
+
+    #[test]
+    fn test_impossible_date_has_no_next_run() {
+        let schedule: CronSchedule = "0 0 30 2 *".parse().unwrap();
+        assert_eq!(schedule.next_after(at(0, 0, 0), Timezone::Utc), None);
+    }
+
+    #[test]
+    fn test_on_calendar() {
+        let calendar = |expression: &str| expression.parse::<CronSchedule>().unwrap().on_calendar();
+        assert_eq!(calendar("0 9 * * MON"), vec!["Mon *-*-* 09:00:00"]);
+        assert_eq!(calendar("*/30 8-10 * * *"), vec!["*-*-* 08,09,10:00,30:00"]);
+        assert_eq!(calendar("@monthly"), vec!["*-*-01 00:00:00"]);
+        assert_eq!(
+            calendar("0 0 10 * FRI"),
+            vec!["Fri *-*-* 00:00:00", "*-*-10 00:00:00"]
+        );
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        assert!("0 9 * *".parse::<CronSchedule>().is_err());
+        assert!("60 * * * *".parse::<CronSchedule>().is_err());
+        assert!("* * * * FUNDAY".parse::<CronSchedule>().is_err());
+        assert!("*/0 * * * *".parse::<CronSchedule>().is_err());
+        assert!("5-1 * * * *".parse::<CronSchedule>().is_err());
+    }
+}
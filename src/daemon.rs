@@ -0,0 +1,871 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{info, info_span, warn};
+
+use crate::config::PackageManagerConfig;
+use crate::cron::CronSchedule;
+use crate::guard::RunGuard;
+use crate::history::unix_seconds;
+use crate::metadata::{self, RunMetadata};
+use crate::package_manager::PackageManager;
+use crate::report::{ExecutionReport, ExecutionResult, ExecutionStatus, Operation};
+use crate::schedule::{jitter_delay, Cadence, Interval, Pause, ScheduleError, ScheduleState};
+use crate::sd_notify::SystemdNotifier;
+use crate::stats::format_duration;
+use crate::terminal;
+use crate::timezone::Timezone;
+
+// Longest uninterrupted sleep, so that shutdown requests are noticed quickly
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// How far the wall clock may run ahead of a sleep before we assume the
+// machine was suspended (or the clock was changed)
+const CLOCK_JUMP_THRESHOLD: Duration = Duration::from_secs(30);
+
+// Settings for `updog daemon`
+//
+//   daemon:
+//     every: daily              # default cadence; managers may set their own `every`
+//     schedule: "0 9 * * MON"   # or a default cron schedule (takes precedence over `every`)
+//     timezone: local           # zone cron schedules are evaluated in
+//     jitter: 15m               # random delay added to each scheduled run
+//     resume_delay: 2m          # wait after a suspend/resume before running
+//     operation: check          # or update
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DaemonConfig {
+    #[serde(default = "DaemonConfig::default_every")]
+    pub every: Interval,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<CronSchedule>,
+    #[serde(default)]
+    pub timezone: Timezone,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jitter: Option<Interval>,
+    #[serde(default = "DaemonConfig::default_resume_delay")]
+    pub resume_delay: Interval,
+    #[serde(default)]
+    pub operation: Operation,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            every: Self::default_every(),
+            schedule: None,
+            timezone: Timezone::default(),
+            jitter: None,
+            resume_delay: Self::default_resume_delay(),
+            operation: Operation::default(),
+        }
+    }
+}
+
+// Next due time of one manager
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledManager {
+    pub name: String,
+    pub due_at: SystemTime,
+    pub cadence: Cadence,
+}
+
+impl DaemonConfig {
+    fn default_every() -> Interval {
+        Interval::DAY
+    }
+
+    fn default_resume_delay() -> Interval {
+        "2m".parse().expect("valid interval")
+    }
+}
+
+// Whether the wall clock moved from `before` to `after` in a way a sleep of
+// `slept` can't explain: a suspend/resume or a manual clock change
+pub fn clock_jumped(before: SystemTime, after: SystemTime, slept: Duration) -> bool {
+    match after.duration_since(before) {
+        Ok(elapsed) => elapsed > slept + CLOCK_JUMP_THRESHOLD,
+        // The clock went backwards
+        Err(e) => e.duration() > CLOCK_JUMP_THRESHOLD,
+    }
+}
+
+// Runs each package manager whenever its cadence says it's due
+pub struct Daemon<'a> {
+    pm: &'a PackageManager,
+    state: ScheduleState,
+    state_path: PathBuf,
+    // Attached to every report
+    pub metadata: Option<RunMetadata>,
+    // Seeds the jitter so that hosts sharing a config are spread out
+    jitter_seed: String,
+    // No runs before this time (set after a resume)
+    hold_until: Option<SystemTime>,
+    // Written by `updog pause`, next to the state file
+    pause_path: PathBuf,
+    paused: bool,
+    // Managers to schedule (all of them when None)
+    pub selected: Option<Vec<String>>,
+    // Readiness, status and watchdog pings for systemd
+    pub notifier: Option<SystemdNotifier>,
+    last_watchdog: Option<Instant>,
+}
+
+impl<'a> Daemon<'a> {
+    pub fn new(pm: &'a PackageManager, state_path: PathBuf) -> Result<Self, ScheduleError> {
+        let state = ScheduleState::load(&state_path)?;
+        Ok(Self {
+            pm,
+            state,
+            pause_path: state_path.with_file_name("pause.yaml"),
+            paused: false,
+            state_path,
+            metadata: None,
+            jitter_seed: metadata::hostname().unwrap_or_default(),
+            hold_until: None,
+            selected: None,
+            notifier: None,
+            last_watchdog: None,
+        })
+    }
+
+    pub fn state(&self) -> &ScheduleState {
+        &self.state
+    }
+
+    // Configured managers the daemon is responsible for; `manual` ones
+    // aren't scheduled for updates unless selected by name
+    fn managers(&self) -> impl Iterator<Item = &'a PackageManagerConfig> + '_ {
+        let updates = self.pm.config.daemon.operation == Operation::Update;
+        self.pm
+            .config
+            .managers()
+            .filter(move |m| match &self.selected {
+                Some(ids) => ids.contains(&m.id),
+                None => !(updates && m.manual),
+            })
+    }
+
+    pub fn cadence(&self, manager: &str) -> Cadence {
+        self.pm.config.cadence(manager)
+    }
+
+    // Hold off all runs for `daemon.resume_delay` after a resume at `now`,
+    // so the network has a chance to come back first
+    pub fn resumed(&mut self, now: SystemTime) {
+        let delay = self.pm.config.daemon.resume_delay.duration();
+        info!(
+            "Clock jump detected (suspend/resume?), waiting {} before running",
+            format_duration(delay)
+        );
+        self.hold_until = Some(now + delay);
+    }
+
+    // Random delay for a manager's run scheduled from `now`
+    fn jitter(&self, manager: &str, now: SystemTime) -> Duration {
+        let max = self
+            .pm
+            .config
+            .jitter(manager)
+            .map_or(Duration::ZERO, |jitter| jitter.duration());
+        let seed = format!("{}:{}:{}", self.jitter_seed, manager, unix_seconds(now));
+        jitter_delay(&seed, max)
+    }
+
+    // Next due time of every configured manager, in config order
+    pub fn schedule(&self, now: SystemTime) -> Vec<ScheduledManager> {
+        self.managers()
+            .map(|m| {
+                let cadence = self.cadence(&m.id);
+                ScheduledManager {
+                    name: m.id.clone(),
+                    due_at: self.state.due_at(&m.id, &cadence, now),
+                    cadence,
+                }
+            })
+            .collect()
+    }
+
+    // Run all managers that are due. Returns None when nothing was due.
+    pub fn tick(&mut self) -> Option<ExecutionReport> {
+        let now = self.pm.clock().now();
+        if self.hold_until.is_some_and(|until| now < until) || self.check_paused(now) {
+            return None;
+        }
+
+        let mut registered = false;
+        let managers: Vec<&PackageManagerConfig> = self.managers().collect();
+        for manager in managers {
+            let cadence = self.cadence(&manager.id);
+            let jitter = self.jitter(&manager.id, now);
+            registered |= self.state.register(&manager.id, &cadence, now, jitter);
+        }
+
+        let due: Vec<String> = self
+            .schedule(now)
+            .into_iter()
+            .filter(|scheduled| scheduled.due_at <= now)
+            .map(|scheduled| scheduled.name)
+            .collect();
+        if due.is_empty() {
+            if registered {
+                self.save_state();
+            }
+            return None;
+        }
+
+        let operation = self.pm.config.daemon.operation;
+        let mut report = ExecutionReport::new(operation, now);
+        let pm = self.pm.for_run(&report.run_id);
+        let _span = info_span!("run", id = %report.run_id).entered();
+        report.metadata = self.metadata.clone();
+        report.categories = self.pm.config.categories();
+        let total = due.len();
+        // Updates stop when the battery or disk runs low
+        let mut guard = (operation == Operation::Update && !self.pm.dry_run)
+            .then(|| RunGuard::new(&self.pm.config));
+        for (index, manager) in due.into_iter().enumerate() {
+            // Left due, so the next tick tries again
+            if let Some(reason) = guard.as_mut().and_then(RunGuard::check) {
+                report.results.push(ExecutionResult::skipped(
+                    manager,
+                    None,
+                    "Run stopped",
+                    format!("Not started: {}", reason),
+                ));
+                continue;
+            }
+            info!("Scheduled {} for {}", operation, manager);
+            self.notify_status(&format!(
+                "Running {} for {} ({}/{})",
+                operation,
+                manager,
+                index + 1,
+                total
+            ));
+            let result = self.keep_alive(|| pm.execute(operation, &manager, None));
+
+            // An interrupted run is retried on the next start
+            if result.status != ExecutionStatus::Cancelled {
+                let cadence = self.cadence(&manager);
+                let jitter = self.jitter(&manager, now);
+                self.state.record_run(&manager, &cadence, now, jitter);
+            }
+            report.results.push(result);
+        }
+
+        self.save_state();
+        let failed = report
+            .results
+            .iter()
+            .filter(|r| r.status.is_failure())
+            .count();
+        self.notify_status(&format!(
+            "Idle; last {} ran {} managers, {} failed",
+            operation,
+            report.results.len(),
+            failed
+        ));
+        Some(report)
+    }
+
+    fn notify_status(&self, status: &str) {
+        if let Some(notifier) = &self.notifier {
+            if let Err(e) = notifier.status(status) {
+                warn!("Failed to notify systemd: {}", e);
+            }
+        }
+    }
+
+    // Ping the systemd watchdog if it's time to
+    fn ping_watchdog(&mut self) {
+        let Some(interval) = self.notifier.as_ref().and_then(|n| n.watchdog_interval()) else {
+            return;
+        };
+        if self
+            .last_watchdog
+            .is_some_and(|last| last.elapsed() < interval)
+        {
+            return;
+        }
+        if let Some(notifier) = &self.notifier {
+            let _ = notifier.watchdog();
+        }
+        self.last_watchdog = Some(Instant::now());
+    }
+
+    // Run `run` while pinging the watchdog in the background: a manager that
+    // takes a while is expected, a daemon loop that stops is what the
+    // watchdog is for (see `warn_after` for slow managers)
+    fn keep_alive<T>(&self, run: impl FnOnce() -> T) -> T {
+        let Some((notifier, interval)) = self
+            .notifier
+            .as_ref()
+            .and_then(|n| n.watchdog_interval().map(|interval| (n, interval)))
+        else {
+            return run();
+        };
+        let (done, finished) = mpsc::channel::<()>();
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                while finished.recv_timeout(interval) == Err(RecvTimeoutError::Timeout) {
+                    let _ = notifier.watchdog();
+                }
+            });
+            let result = run();
+            drop(done);
+            result
+        })
+    }
+
+    // Whether `updog pause` is in effect, logging when that changes
+    fn check_paused(&mut self, now: SystemTime) -> bool {
+        let paused = match Pause::load_active(&self.pause_path, now) {
+            Ok(pause) => pause.is_some(),
+            Err(e) => {
+                warn!("Ignoring pause file {:?}: {}", self.pause_path, e);
+                false
+            }
+        };
+        if paused != self.paused {
+            info!(
+                "Scheduled runs {}",
+                if paused { "paused" } else { "resumed" }
+            );
+            self.paused = paused;
+        }
+        paused
+    }
+
+    fn save_state(&self) {
+        if let Err(e) = self.state.save(&self.state_path) {
+            warn!(
+                "Failed to save schedule state to {:?}: {}",
+                self.state_path, e
+            );
+        }
+    }
+
+    // Start of the digest that's due, if one is: the runs since then go in
+    // it, and the next one starts now. The first digest covers the runs from
+    // the daemon's first start on.
+    pub fn digest_due(&mut self, now: SystemTime) -> Option<SystemTime> {
+        let digest = self.pm.config.alerts.digest.as_ref()?;
+        let Some(since) = self.state.digest_since else {
+            self.state.digest_since = Some(unix_seconds(now));
+            self.save_state();
+            return None;
+        };
+        let since = UNIX_EPOCH + Duration::from_secs(since);
+        let cadence = digest.cadence(self.pm.config.daemon.timezone);
+        if cadence.next_after(since).is_none_or(|due| now < due) {
+            return None;
+        }
+        self.state.digest_since = Some(unix_seconds(now));
+        self.save_state();
+        Some(since)
+    }
+
+    // Earliest time any manager is due
+    pub fn next_wake(&self, now: SystemTime) -> Option<SystemTime> {
+        let wake = self
+            .schedule(now)
+            .into_iter()
+            .map(|scheduled| scheduled.due_at)
+            .min()?;
+        Some(match self.hold_until {
+            Some(until) => wake.max(until),
+            None => wake,
+        })
+    }
+
+    // Run until shutdown is requested, handing each report to `on_report`
+    // and the start of each digest that's due to `on_digest`
+    pub fn run(
+        &mut self,
+        mut on_report: impl FnMut(&ExecutionReport),
+        mut on_digest: impl FnMut(SystemTime),
+    ) {
+        info!("Daemon started");
+        if let Some(notifier) = &self.notifier {
+            if let Err(e) = notifier.ready("Idle") {
+                warn!("Failed to notify systemd: {}", e);
+            }
+        }
+        while !self.pm.shutdown_requested() {
+            self.ping_watchdog();
+            if let Some(report) = self.tick() {
+                on_report(&report);
+            }
+            if let Some(since) = self.digest_due(self.pm.clock().now()) {
+                on_digest(since);
+            }
+
+            let clock = self.pm.clock();
+            let now = clock.now();
+            let wait = self
+                .next_wake(now)
+                .and_then(|wake| wake.duration_since(now).ok())
+                .unwrap_or(POLL_INTERVAL);
+            let wait = wait.clamp(Duration::from_millis(10), POLL_INTERVAL);
+            clock.sleep(wait);
+
+            let after = clock.now();
+            if clock_jumped(now, after, wait) {
+                self.resumed(after);
+            }
+        }
+        if let Some(notifier) = &self.notifier {
+            let _ = notifier.stopping();
+        }
+        info!("Daemon stopped");
+    }
+}
+
+// Render the next run of each manager as a plain text table. With a
+// terminal `width`, long manager names and schedules are cut to fit.
+pub fn render_schedule(
+    schedule: &[ScheduledManager],
+    now: SystemTime,
+    timezone: Timezone,
+    width: Option<usize>,
+) -> String {
+    if schedule.is_empty() {
+        return "No package managers configured.\n".to_string();
+    }
+
+    let rows: Vec<(String, String, String, String)> = schedule
+        .iter()
+        .map(|s| {
+            let (when, wait) = match s.due_at.duration_since(now) {
+                Ok(wait) if !wait.is_zero() => (
+                    timezone.civil(s.due_at).to_string(),
+                    format!("in {}", format_duration(wait)),
+                ),
+                _ => ("due now".to_string(), "-".to_string()),
+            };
+            (s.name.clone(), when, wait, s.cadence.to_string())
+        })
+        .collect();
+
+    let column_width = |column: fn(&(String, String, String, String)) -> &String, header: &str| {
+        rows.iter()
+            .map(|row| terminal::display_width(column(row)))
+            .max()
+            .unwrap_or(0)
+            .max(header.len())
+    };
+    let mut name_width = column_width(|r| &r.0, "Manager");
+    let when_width = column_width(|r| &r.1, "Next run");
+    let wait_width = column_width(|r| &r.2, "In");
+    // Names and schedules share what the fixed-width columns leave over
+    let mut cadence_width = usize::MAX;
+    if let Some(width) = width {
+        let rest = width.saturating_sub(when_width + wait_width + 6);
+        name_width = name_width.min((rest / 2).max("Manager".len()));
+        cadence_width = rest.saturating_sub(name_width).max("Schedule".len());
+    }
+
+    let mut out = format!(
+        "{}  {:<ww$}  {:<iw$}  Schedule\n",
+        terminal::pad("Manager", name_width),
+        "Next run",
+        "In",
+        ww = when_width,
+        iw = wait_width
+    );
+    for (name, when, wait, cadence) in rows {
+        out.push_str(&format!(
+            "{}  {:<ww$}  {:<iw$}  {}\n",
+            terminal::pad(&terminal::truncate(&name, name_width), name_width),
+            when,
+            wait,
+            terminal::truncate(&cadence, cadence_width),
+            ww = when_width,
+            iw = wait_width
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::executor::Clock;
+    use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+    use std::sync::Arc;
+    use std::time::UNIX_EPOCH;
+    use tempfile::tempdir;
+
+    fn config() -> Config {
+        serde_yaml::from_str(
+            r#"
+            daemon:
+              every: daily
+            commands:
+              - id: brew
+                check: brew outdated
+                every: 6h
+              - id: npm
+                check: npm outdated
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn setup() -> (Arc<FakeClock>, Arc<ScriptedExecutor>, PackageManager) {
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor
+            .script("brew outdated", ScriptedCommand::success())
+            .script("npm outdated", ScriptedCommand::success());
+        let mut pm = PackageManager::with_executor(config(), executor.clone(), clock.clone());
+        pm.echo_output = false;
+        (clock, executor, pm)
+    }
+
+    #[test]
+    fn test_managers_follow_their_own_cadence() {
+        let dir = tempdir().unwrap();
+        let (clock, executor, pm) = setup();
+        let mut daemon = Daemon::new(&pm, dir.path().join("schedule.yaml")).unwrap();
+
+        // Everything is due on the first tick
+        assert_eq!(daemon.tick().unwrap().results.len(), 2);
+        assert!(daemon.tick().is_none());
+
+        clock.advance(Duration::from_secs(6 * 3600));
+        let report = daemon.tick().unwrap();
+        assert_eq!(report.results.len(), 1);
+        assert_eq!(report.results[0].manager, "brew");
+
+        clock.advance(Duration::from_secs(18 * 3600));
+        assert_eq!(daemon.tick().unwrap().results.len(), 2);
+        assert_eq!(executor.commands().len(), 5);
+    }
+
+    #[test]
+    fn test_only_selected_managers_are_scheduled() {
+        let dir = tempdir().unwrap();
+        let (_clock, executor, pm) = setup();
+        let mut daemon = Daemon::new(&pm, dir.path().join("schedule.yaml")).unwrap();
+        daemon.selected = Some(vec!["npm".to_string()]);
+
+        assert_eq!(daemon.tick().unwrap().results.len(), 1);
+        assert_eq!(executor.commands(), vec!["npm outdated"]);
+        assert!(!daemon.state().managers.contains_key("brew"));
+    }
+
+    #[test]
+    fn test_manual_managers_are_not_scheduled_for_updates() {
+        let dir = tempdir().unwrap();
+        let (_clock, _executor, mut pm) = setup();
+        pm.config.commands[0].manual = true;
+        let daemon = Daemon::new(&pm, dir.path().join("schedule.yaml")).unwrap();
+        assert_eq!(daemon.managers().count(), 2);
+
+        pm.config.daemon.operation = Operation::Update;
+        let mut daemon = Daemon::new(&pm, dir.path().join("schedule.yaml")).unwrap();
+        let names: Vec<&str> = daemon.managers().map(|m| m.id.as_str()).collect();
+        assert_eq!(names, ["npm"]);
+        daemon.selected = Some(vec!["brew".to_string()]);
+        assert_eq!(daemon.managers().count(), 1);
+    }
+
+    #[test]
+    fn test_next_due_survives_restart() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("schedule.yaml");
+        let (clock, executor, pm) = setup();
+
+        Daemon::new(&pm, path.clone()).unwrap().tick().unwrap();
+
+        // A new daemon picks up the persisted schedule instead of running again
+        clock.advance(Duration::from_secs(3600));
+        let mut restarted = Daemon::new(&pm, path).unwrap();
+        assert!(restarted.tick().is_none());
+        assert_eq!(
+            restarted.next_wake(clock.now()),
+            Some(clock.now() + Duration::from_secs(5 * 3600))
+        );
+        assert_eq!(executor.commands().len(), 2);
+    }
+
+    #[test]
+    fn test_cancelled_runs_stay_due() {
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor
+            .script("brew outdated", ScriptedCommand::success())
+            .script("npm outdated", ScriptedCommand::cancelled());
+        let mut pm = PackageManager::with_executor(config(), executor, clock);
+        pm.echo_output = false;
+
+        let mut daemon = Daemon::new(&pm, dir.path().join("schedule.yaml")).unwrap();
+        daemon.tick().unwrap();
+        assert!(daemon.state().managers["brew"].last_run.is_some());
+        assert!(daemon.state().managers["npm"].last_run.is_none());
+        assert_eq!(daemon.tick().unwrap().results[0].manager, "npm");
+    }
+
+    #[test]
+    fn test_run_stops_on_shutdown() {
+        let dir = tempdir().unwrap();
+        let (_clock, executor, pm) = setup();
+        let mut daemon = Daemon::new(&pm, dir.path().join("schedule.yaml")).unwrap();
+
+        let mut reports = 0;
+        daemon.run(
+            |_| {
+                reports += 1;
+                executor.request_shutdown();
+            },
+            |_| {},
+        );
+        assert_eq!(reports, 1);
+    }
+
+    #[test]
+    fn test_digest_due() {
+        let dir = tempdir().unwrap();
+        let (clock, _executor, mut pm) = setup();
+        pm.config.alerts = serde_yaml::from_str("digest: { every: 12h }").unwrap();
+        let path = dir.path().join("schedule.yaml");
+        let mut daemon = Daemon::new(&pm, path.clone()).unwrap();
+
+        // The first digest starts when the daemon first does
+        let started = clock.now();
+        assert_eq!(daemon.digest_due(started), None);
+        clock.advance(Duration::from_secs(11 * 3600));
+        assert_eq!(daemon.digest_due(clock.now()), None);
+        clock.advance(Duration::from_secs(3600));
+        assert_eq!(daemon.digest_due(clock.now()), Some(started));
+        assert_eq!(daemon.digest_due(clock.now()), None);
+
+        // A restart carries on from the last digest
+        let mut daemon = Daemon::new(&pm, path).unwrap();
+        clock.advance(Duration::from_secs(12 * 3600));
+        assert_eq!(
+            daemon.digest_due(clock.now()),
+            Some(started + Duration::from_secs(12 * 3600))
+        );
+
+        pm.config.alerts.digest = None;
+        let mut daemon = Daemon::new(&pm, dir.path().join("other.yaml")).unwrap();
+        assert_eq!(daemon.digest_due(clock.now()), None);
+        assert_eq!(daemon.state().digest_since, None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_notifies_systemd() {
+        use std::os::unix::net::UnixDatagram;
+
+        let dir = tempdir().unwrap();
+        let socket = dir.path().join("notify.sock");
+        let receiver = UnixDatagram::bind(&socket).unwrap();
+        receiver.set_nonblocking(true).unwrap();
+        let (_clock, executor, pm) = setup();
+        let mut daemon = Daemon::new(&pm, dir.path().join("schedule.yaml")).unwrap();
+        daemon.notifier = SystemdNotifier::from_vars(Some(socket.into()), Some("60000000"), None);
+
+        daemon.run(|_| executor.request_shutdown(), |_| {});
+
+        let mut messages = Vec::new();
+        let mut buffer = [0u8; 256];
+        while let Ok(n) = receiver.recv(&mut buffer) {
+            messages.push(String::from_utf8_lossy(&buffer[..n]).into_owned());
+        }
+        assert_eq!(
+            messages,
+            [
+                "READY=1\nSTATUS=Idle",
+                "WATCHDOG=1",
+                "STATUS=Running check for brew (1/2)",
+                "STATUS=Running check for npm (2/2)",
+                "STATUS=Idle; last check ran 2 managers, 0 failed",
+                "STOPPING=1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cadence_precedence() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+            daemon:
+              schedule: "0 9 * * MON"
+              timezone: UTC
+            commands:
+              - id: brew
+                check: brew outdated
+                every: 6h
+              - id: npm
+                check: npm outdated
+                schedule: "@daily"
+              - id: pip
+                check: pip list --outdated
+            "#,
+        )
+        .unwrap();
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        let pm = PackageManager::with_executor(config, executor, clock);
+        let dir = tempdir().unwrap();
+        let daemon = Daemon::new(&pm, dir.path().join("schedule.yaml")).unwrap();
+
+        assert_eq!(daemon.cadence("brew").to_string(), "every 6h");
+        assert_eq!(daemon.cadence("npm").to_string(), "cron @daily");
+        assert_eq!(daemon.cadence("pip").to_string(), "cron 0 9 * * MON");
+    }
+
+    #[test]
+    fn test_cron_manager_waits_for_first_match() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+            daemon:
+              timezone: UTC
+            commands:
+              - id: brew
+                check: brew outdated
+                schedule: "0 9 * * *"
+            "#,
+        )
+        .unwrap();
+        // FakeClock starts at 2024-01-01T00:00:00Z
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor.script("brew outdated", ScriptedCommand::success());
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock.clone());
+        pm.echo_output = false;
+        let dir = tempdir().unwrap();
+        let mut daemon = Daemon::new(&pm, dir.path().join("schedule.yaml")).unwrap();
+
+        assert!(daemon.tick().is_none());
+        clock.advance(Duration::from_secs(9 * 3600));
+        assert_eq!(daemon.tick().unwrap().results.len(), 1);
+        assert!(daemon.tick().is_none());
+
+        let schedule = daemon.schedule(clock.now());
+        let table = render_schedule(&schedule, clock.now(), Timezone::Utc, None);
+        assert!(table.contains("2024-01-02 09:00 +00:00"));
+        assert!(table.contains("in 24h00m"));
+        assert!(table.contains("cron 0 9 * * *"));
+
+        // Cut to fit a narrow terminal
+        let table = render_schedule(&schedule, clock.now(), Timezone::Utc, Some(56));
+        assert!(table.contains("cron 0 9 *…"), "{}", table);
+        assert!(table
+            .lines()
+            .all(|line| terminal::display_width(line) <= 56));
+    }
+
+    #[test]
+    fn test_jitter_delays_next_run() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+            daemon:
+              every: 1h
+              jitter: 15m
+            commands:
+              - id: brew
+                check: brew outdated
+            "#,
+        )
+        .unwrap();
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor.script("brew outdated", ScriptedCommand::success());
+        let mut pm = PackageManager::with_executor(config, executor, clock.clone());
+        pm.echo_output = false;
+        let dir = tempdir().unwrap();
+        let mut daemon = Daemon::new(&pm, dir.path().join("schedule.yaml")).unwrap();
+        daemon.jitter_seed = "host".to_string();
+
+        // Even the first run waits for its share of the jitter
+        assert!(daemon.tick().is_none());
+        let first = daemon.next_wake(clock.now()).unwrap();
+        let delay = first.duration_since(clock.now()).unwrap();
+        assert!(!delay.is_zero() && delay <= Duration::from_secs(900));
+
+        clock.set(first);
+        assert!(daemon.tick().is_some());
+
+        let entry = &daemon.state().managers["brew"];
+        let last = UNIX_EPOCH + Duration::from_secs(entry.last_run.unwrap());
+        let wait = Duration::from_secs(entry.next_due) - last.duration_since(UNIX_EPOCH).unwrap();
+        assert!(wait >= Duration::from_secs(3600));
+        assert!(wait <= Duration::from_secs(3600 + 900));
+    }
+
+    #[test]
+    fn test_clock_jump_detection() {
+        let before = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let slept = Duration::from_secs(1);
+        assert!(!clock_jumped(before, before + slept, slept));
+        assert!(!clock_jumped(
+            before,
+            before + Duration::from_secs(5),
+            slept
+        ));
+        // Lid closed for an hour
+        assert!(clock_jumped(
+            before,
+            before + Duration::from_secs(3600),
+            slept
+        ));
+        // Clock set back
+        assert!(clock_jumped(
+            before,
+            before - Duration::from_secs(3600),
+            slept
+        ));
+    }
+
+    #[test]
+    fn test_resume_delays_due_runs() {
+        let (clock, _executor, pm) = setup();
+        let dir = tempdir().unwrap();
+        let mut daemon = Daemon::new(&pm, dir.path().join("schedule.yaml")).unwrap();
+        assert!(daemon.tick().is_some());
+
+        // Everything is overdue after a long suspend, but nothing runs yet
+        clock.advance(Duration::from_secs(2 * 86_400));
+        daemon.resumed(clock.now());
+        assert!(daemon.tick().is_none());
+        assert_eq!(
+            daemon.next_wake(clock.now()),
+            Some(clock.now() + Duration::from_secs(120))
+        );
+
+        clock.advance(Duration::from_secs(120));
+        let report = daemon.tick().unwrap();
+        assert_eq!(report.results.len(), 2);
+    }
+
+    #[test]
+    fn test_pause_skips_runs_until_resumed() {
+        let (clock, _executor, pm) = setup();
+        let dir = tempdir().unwrap();
+        let mut daemon = Daemon::new(&pm, dir.path().join("schedule.yaml")).unwrap();
+        let pause_path = dir.path().join("pause.yaml");
+
+        Pause::new(clock.now(), Some(Duration::from_secs(3600)))
+            .save(&pause_path)
+            .unwrap();
+        assert!(daemon.tick().is_none());
+
+        // The pause expires on its own
+        clock.advance(Duration::from_secs(3600));
+        assert!(daemon.tick().is_some());
+
+        // An indefinite pause lasts until cleared
+        Pause::new(clock.now(), None).save(&pause_path).unwrap();
+        clock.advance(Duration::from_secs(2 * 86_400));
+        assert!(daemon.tick().is_none());
+        Pause::clear(&pause_path).unwrap();
+        assert!(daemon.tick().is_some());
+    }
+}
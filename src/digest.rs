@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::history::{ManagerRecord, RunRecord};
+use crate::stats::display_name;
+use crate::timezone::Timezone;
+
+// What the recorded runs since the last digest came to, sent by the daemon
+// on the `alerts.digest` cadence instead of a notification per run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Digest {
+    pub runs: usize,
+    // Managers whose latest run in the digest failed
+    pub failing: usize,
+    // First line of `text`
+    pub headline: String,
+    pub text: String,
+}
+
+// One manager's runs within the digest
+#[derive(Default)]
+struct Entry<'a> {
+    runs: usize,
+    failures: usize,
+    upgraded: usize,
+    // Pending updates by the latest check, none after a later update
+    pending: Option<usize>,
+    latest: Option<&'a ManagerRecord>,
+    // Whether the manager's latest run before the digest succeeded
+    before: Option<bool>,
+}
+
+impl Entry<'_> {
+    fn status(&self) -> &str {
+        let latest = self.latest.expect("entries have a run");
+        match (&latest.status, latest.success) {
+            (Some(status), _) => status,
+            (None, true) => "success",
+            (None, false) => "failed",
+        }
+    }
+
+    // "3 runs, 12 upgraded, 1 failed (newly failing)"
+    fn details(&self) -> String {
+        let mut parts = vec![match self.runs {
+            1 => "1 run".to_string(),
+            n => format!("{} runs", n),
+        }];
+        if self.upgraded > 0 {
+            parts.push(format!("{} upgraded", self.upgraded));
+        }
+        if let Some(pending) = self.pending.filter(|&p| p > 0) {
+            parts.push(format!("{} pending", pending));
+        }
+        if self.failures > 0 {
+            parts.push(format!("{} failed", self.failures));
+        }
+        let success = self.latest.is_some_and(|latest| latest.success);
+        let change = match (self.before, success) {
+            (Some(false), true) => " (recovered)",
+            (Some(true), false) => " (newly failing)",
+            _ => "",
+        };
+        format!("{}{}", parts.join(", "), change)
+    }
+}
+
+// The digest of `runs` started at or after `since` (seconds since the
+// epoch), or None when there are none. How a manager did before `since`
+// shows which ones recovered or started failing.
+pub fn build(runs: &[RunRecord], since: u64, timezone: Timezone) -> Option<Digest> {
+    let mut entries: BTreeMap<String, Entry> = BTreeMap::new();
+    let mut count = 0;
+    for run in runs {
+        let within = run.started_at >= since;
+        count += usize::from(within);
+        for record in run.results.iter().filter(|r| r.was_executed()) {
+            let entry = entries.entry(display_name(record)).or_default();
+            if !within {
+                entry.before = Some(record.success);
+                continue;
+            }
+            entry.runs += 1;
+            entry.failures += usize::from(!record.success);
+            match (run.operation.as_str(), record.packages) {
+                ("check", Some(pending)) => entry.pending = Some(pending),
+                ("update", upgraded) if record.success => {
+                    entry.upgraded += upgraded.unwrap_or(0);
+                    entry.pending = None;
+                }
+                _ => {}
+            }
+            entry.latest = Some(record);
+        }
+    }
+    if count == 0 {
+        return None;
+    }
+    entries.retain(|_, entry| entry.runs > 0);
+
+    let upgraded: usize = entries.values().map(|e| e.upgraded).sum();
+    let pending: usize = entries.values().filter_map(|e| e.pending).sum();
+    let failing = entries
+        .values()
+        .filter(|e| e.latest.is_some_and(|latest| !latest.success))
+        .count();
+    let started = timezone.civil(UNIX_EPOCH + Duration::from_secs(since));
+    let headline = format!(
+        "{} {} since {}: {} upgraded, {} pending, {} failing",
+        count,
+        if count == 1 { "run" } else { "runs" },
+        started,
+        upgraded,
+        pending,
+        failing
+    );
+
+    let name_width = entries.keys().map(|n| n.chars().count()).max().unwrap_or(0);
+    let status_width = entries
+        .values()
+        .map(|e| e.status().len())
+        .max()
+        .unwrap_or(0);
+    let mut text = format!("{}\n\n", headline);
+    for (name, entry) in &entries {
+        text.push_str(&format!(
+            "{:<name_width$}  {:<status_width$}  {}\n",
+            name,
+            entry.status(),
+            entry.details(),
+        ));
+        // Why the latest run failed
+        if let Some(latest) = entry.latest.filter(|latest| !latest.success) {
+            if !latest.message.is_empty() {
+                text.push_str(&format!("{:name_width$}  {}\n", "", latest.message));
+            }
+        }
+    }
+    Some(Digest {
+        runs: count,
+        failing,
+        headline,
+        text,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(started_at: u64, operation: &str, results: &[(&str, bool, Option<usize>)]) -> RunRecord {
+        let mut run = RunRecord::new(operation, UNIX_EPOCH + Duration::from_secs(started_at));
+        run.results = results
+            .iter()
+            .map(|&(manager, success, packages)| ManagerRecord {
+                manager: manager.to_string(),
+                subcommand: None,
+                success,
+                status: None,
+                duration_secs: 1.0,
+                packages,
+                message: match success {
+                    true => String::new(),
+                    false => "Error: boom".to_string(),
+                },
+                steps: Vec::new(),
+                output: String::new(),
+                warnings: Vec::new(),
+                columns: BTreeMap::new(),
+            })
+            .collect();
+        run
+    }
+
+    #[test]
+    fn test_build() {
+        let runs = vec![
+            // Before the digest
+            run(0, "update", &[("apt", false, None), ("npm", true, None)]),
+            run(
+                100,
+                "check",
+                &[("apt", true, Some(4)), ("npm", true, Some(2))],
+            ),
+            run(
+                200,
+                "update",
+                &[("apt", true, Some(4)), ("npm", false, None)],
+            ),
+            run(300, "check", &[("cargo", true, Some(1))]),
+        ];
+        let digest = build(&runs, 100, Timezone::Utc).unwrap();
+        assert_eq!(digest.runs, 3);
+        assert_eq!(digest.failing, 1);
+        assert_eq!(
+            digest.text,
+            "3 runs since 1970-01-01 00:01 +00:00: 4 upgraded, 3 pending, 1 failing\n\
+             \n\
+             apt    success  2 runs, 4 upgraded (recovered)\n\
+             cargo  success  1 run, 1 pending\n\
+             npm    failed   2 runs, 2 pending, 1 failed (newly failing)\n\
+             \x20      Error: boom\n"
+        );
+        assert!(digest.text.starts_with(&digest.headline));
+        assert_eq!(build(&runs, 400, Timezone::Utc), None);
+    }
+}
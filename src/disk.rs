@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+// Free space an update needs before it's started, so that a full disk skips
+// the update instead of leaving it half applied:
+//
+//   min_free_space:          # for every manager's updates
+//     size: 2G
+//     path: /                # filesystem to check (default /)
+//   commands:
+//     - id: homebrew
+//       update: brew upgrade
+//       min_free_space: { size: 5G, path: /opt/homebrew }
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FreeSpace {
+    pub size: ByteSize,
+    #[serde(default = "FreeSpace::default_path")]
+    pub path: String,
+}
+
+impl FreeSpace {
+    fn default_path() -> String {
+        "/".to_string()
+    }
+
+    // Why there isn't enough room on `path`, if there isn't. The space is
+    // looked up at the nearest existing ancestor, so a path the update creates
+    // can be named.
+    pub fn shortfall(&self) -> io::Result<Option<String>> {
+        let path = crate::step::expand_home(&self.path);
+        let existing = path
+            .ancestors()
+            .find(|p| p.exists())
+            .unwrap_or(Path::new("/"));
+        let available = available_space(existing)?;
+        Ok((available < self.size.0).then(|| {
+            format!(
+                "Not enough free space: {} available on {}, {} required",
+                ByteSize(available),
+                self.path,
+                self.size
+            )
+        }))
+    }
+}
+
+// A size in bytes, written "500M", "2G", "1.5GB" or "1024" (binary units)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ByteSize(pub u64);
+
+const UNITS: &[(&str, u64)] = &[
+    ("T", 1 << 40),
+    ("G", 1 << 30),
+    ("M", 1 << 20),
+    ("K", 1 << 10),
+    ("B", 1),
+];
+
+impl std::str::FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid size '{}' (expected e.g. 500M or 2G)", value);
+        let trimmed = value.trim().to_ascii_uppercase();
+        let without_suffix = trimmed
+            .strip_suffix("IB")
+            .or_else(|| trimmed.strip_suffix('B').filter(|s| !s.is_empty()))
+            .unwrap_or(&trimmed);
+        let split = without_suffix
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(without_suffix.len());
+        let (number, unit) = without_suffix.split_at(split);
+        let multiplier = match unit.trim() {
+            "" => 1,
+            unit => UNITS
+                .iter()
+                .find(|(name, _)| *name == unit)
+                .map(|(_, multiplier)| *multiplier)
+                .ok_or_else(invalid)?,
+        };
+        let number: f64 = number.parse().map_err(|_| invalid())?;
+        if !number.is_finite() || number < 0.0 {
+            return Err(invalid());
+        }
+        Ok(ByteSize((number * multiplier as f64) as u64))
+    }
+}
+
+impl TryFrom<String> for ByteSize {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ByteSize> for String {
+    fn from(size: ByteSize) -> Self {
+        size.to_string()
+    }
+}
+
+impl fmt::Display for ByteSize {
+    // The largest unit that keeps the number at least 1, with one decimal
+    // unless it's whole: "512M", "1.5G"
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (unit, multiplier) = UNITS
+            .iter()
+            .find(|(_, multiplier)| self.0 >= *multiplier)
+            .copied()
+            .unwrap_or(("B", 1));
+        let unit = if unit == "B" { "" } else { unit };
+        let value = self.0 as f64 / multiplier as f64;
+        if self.0.is_multiple_of(multiplier) {
+            write!(f, "{}{}", self.0 / multiplier, unit)
+        } else {
+            write!(f, "{:.1}{}", value, unit)
+        }
+    }
+}
+
+// Bytes available to unprivileged users on the filesystem holding `path`
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> io::Result<u64> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "free space checks are only supported on Unix",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_size() {
+        assert_eq!("500M".parse(), Ok(ByteSize(500 << 20)));
+        assert_eq!("2 GB".parse(), Ok(ByteSize(2 << 30)));
+        assert_eq!("1.5GiB".parse(), Ok(ByteSize(3 << 29)));
+        assert_eq!("4096".parse(), Ok(ByteSize(4096)));
+        assert_eq!("10b".parse(), Ok(ByteSize(10)));
+        assert!("lots".parse::<ByteSize>().is_err());
+        assert!("5X".parse::<ByteSize>().is_err());
+
+        assert_eq!(ByteSize(2 << 30).to_string(), "2G");
+        assert_eq!(ByteSize(3 << 29).to_string(), "1.5G");
+        assert_eq!(ByteSize(100).to_string(), "100");
+    }
+
+    #[test]
+    fn test_shortfall() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("not/created/yet").display().to_string();
+        let roomy = FreeSpace {
+            size: ByteSize(1),
+            path: path.clone(),
+        };
+        assert_eq!(roomy.shortfall().unwrap(), None);
+
+        let full = FreeSpace {
+            size: ByteSize(1 << 60),
+            path: path.clone(),
+        };
+        let reason = full.shortfall().unwrap().unwrap();
+        assert!(reason.starts_with("Not enough free space: "), "{}", reason);
+        assert!(reason.ends_with(&format!("on {}, 1048576T required", path)));
+
+        let config: FreeSpace = serde_yaml::from_str("size: 2G").unwrap();
+        assert_eq!(config.path, "/");
+        assert_eq!(config.size, ByteSize(2 << 30));
+    }
+}
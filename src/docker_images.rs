@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::executor::{CommandRequest, Executor};
+use crate::package_manager::UpdateError;
+use crate::step::{shell_quote, Step};
+use crate::warnings;
+
+// Container images kept at the newest digest of their tag, for self-hosted
+// compose stacks
+//
+//   - id: containers
+//     type: docker-images
+//     images: [nginx:stable, postgres:16]   # default: every pulled local image
+//
+// The check asks the registry for each tag's current digest and lists the
+// images whose local copy is older, one line each; the update pulls them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DockerImages {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<String>,
+}
+
+// An image whose tag points at a newer digest in the registry
+#[derive(Debug, PartialEq)]
+struct Outdated {
+    image: String,
+    local: String,
+    remote: String,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    digest: String,
+}
+
+// `sha256:0123456789ab...` shortened like docker does
+fn short(digest: &str) -> &str {
+    let hex = digest.strip_prefix("sha256:").unwrap_or(digest);
+    &hex[..hex.len().min(12)]
+}
+
+// `repo:tag` lines of `docker image ls`, without untagged images
+fn parse_image_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.contains("<none>"))
+        .map(str::to_string)
+        .collect()
+}
+
+impl DockerImages {
+    // Output of a docker command that has to succeed
+    fn run(
+        executor: &dyn Executor,
+        env: &[(String, String)],
+        command: &str,
+    ) -> Result<String, UpdateError> {
+        let mut request = CommandRequest::captured(command);
+        request.env = env.to_vec();
+        let output = executor.run(&request)?;
+        if !output.success() {
+            let reason = match output.stderr.trim() {
+                "" => output.status_description(),
+                stderr => stderr.lines().last().unwrap_or(stderr).to_string(),
+            };
+            return Err(UpdateError::new(format!("{} failed: {}", command, reason)));
+        }
+        Ok(output.stdout)
+    }
+
+    fn images(
+        &self,
+        executor: &dyn Executor,
+        env: &[(String, String)],
+    ) -> Result<Vec<String>, UpdateError> {
+        if !self.images.is_empty() {
+            return Ok(self.images.clone());
+        }
+        let output = Self::run(
+            executor,
+            env,
+            "docker image ls --format '{{.Repository}}:{{.Tag}}'",
+        )?;
+        Ok(parse_image_list(&output))
+    }
+
+    // None for images that weren't pulled from a registry (built locally)
+    fn compare(
+        executor: &dyn Executor,
+        env: &[(String, String)],
+        image: &str,
+    ) -> Result<Option<Outdated>, UpdateError> {
+        let local = Self::run(
+            executor,
+            env,
+            &format!(
+                "docker image inspect --format '{{{{join .RepoDigests \" \"}}}}' {}",
+                shell_quote(image)
+            ),
+        )?;
+        let local: Vec<&str> = local
+            .split_whitespace()
+            .filter_map(|reference| reference.split_once('@').map(|(_, digest)| digest))
+            .collect();
+        let Some(current) = local.first() else {
+            info!("{} has no registry digest, not checking it", image);
+            return Ok(None);
+        };
+        let remote = Self::run(
+            executor,
+            env,
+            &format!(
+                "docker buildx imagetools inspect --format '{{{{json .Manifest}}}}' {}",
+                shell_quote(image)
+            ),
+        )?;
+        let remote: Manifest = serde_json::from_str(remote.trim())
+            .map_err(|e| UpdateError::new(format!("Unexpected manifest of {}: {}", image, e)))?;
+        if local.contains(&remote.digest.as_str()) {
+            return Ok(None);
+        }
+        Ok(Some(Outdated {
+            image: image.to_string(),
+            local: current.to_string(),
+            remote: remote.digest,
+        }))
+    }
+
+    // Images with a newer digest; one image failing doesn't stop the others
+    fn outdated(
+        &self,
+        executor: &dyn Executor,
+        env: &[(String, String)],
+    ) -> Result<Vec<Outdated>, UpdateError> {
+        let images = self.images(executor, env)?;
+        let mut outdated = Vec::new();
+        let mut failed = Vec::new();
+        for image in &images {
+            match Self::compare(executor, env, image) {
+                Ok(Some(image)) => outdated.push(image),
+                Ok(None) => {}
+                Err(e) => {
+                    warnings::warn(format_args!("Couldn't check {}: {}", image, e.message));
+                    failed.push(image.as_str());
+                }
+            }
+        }
+        if !failed.is_empty() && failed.len() == images.len() {
+            return Err(UpdateError::new(format!(
+                "Couldn't check any image: {}",
+                failed.join(", ")
+            )));
+        }
+        Ok(outdated)
+    }
+
+    // `<image> <local digest> -> <registry digest>` per outdated image
+    pub fn check(
+        &self,
+        executor: &dyn Executor,
+        env: &[(String, String)],
+    ) -> Result<String, UpdateError> {
+        Ok(self
+            .outdated(executor, env)?
+            .iter()
+            .map(|o| format!("{} {} -> {}\n", o.image, short(&o.local), short(&o.remote)))
+            .collect())
+    }
+
+    // Pull every outdated image, reporting each; fails after trying them all
+    // if any pull failed
+    pub fn pull(
+        &self,
+        executor: &dyn Executor,
+        env: &[(String, String)],
+    ) -> Result<String, UpdateError> {
+        let mut output = String::new();
+        let mut failed = Vec::new();
+        for outdated in self.outdated(executor, env)? {
+            let command = format!("docker pull --quiet {}", shell_quote(&outdated.image));
+            match Self::run(executor, env, &command) {
+                Ok(_) => output.push_str(&format!(
+                    "Pulled {} ({} -> {})\n",
+                    outdated.image,
+                    short(&outdated.local),
+                    short(&outdated.remote)
+                )),
+                Err(e) => {
+                    warnings::warn(&e.message);
+                    failed.push(outdated.image);
+                }
+            }
+        }
+        if !failed.is_empty() {
+            return Err(UpdateError::new(format!(
+                "Failed to pull {}",
+                failed.join(", ")
+            )));
+        }
+        Ok(output)
+    }
+
+    pub fn check_steps(&self) -> Vec<Step> {
+        vec![Step::ImageCheck(self.clone())]
+    }
+
+    pub fn update_steps(&self) -> Vec<Step> {
+        vec![Step::ImagePull(self.clone())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+    use std::sync::Arc;
+
+    const NGINX_OLD: &str =
+        "sha256:1111111111111111111111111111111111111111111111111111111111111111";
+    const NGINX_NEW: &str =
+        "sha256:2222222222222222222222222222222222222222222222222222222222222222";
+
+    fn inspect(image: &str) -> String {
+        format!(
+            "docker image inspect --format '{{{{join .RepoDigests \" \"}}}}' '{}'",
+            image
+        )
+    }
+
+    fn manifest(image: &str) -> String {
+        format!(
+            "docker buildx imagetools inspect --format '{{{{json .Manifest}}}}' '{}'",
+            image
+        )
+    }
+
+    fn digest(digest: &str) -> ScriptedCommand {
+        ScriptedCommand::success().stdout(&format!(
+            r#"{{"mediaType":"application/vnd.oci.image.index.v1+json","digest":"{}","size":10229}}"#,
+            digest
+        ))
+    }
+
+    #[test]
+    fn test_parse_image_list() {
+        assert_eq!(
+            parse_image_list("nginx:stable\n<none>:<none>\nghcr.io/foo/bar:1.2\n"),
+            ["nginx:stable", "ghcr.io/foo/bar:1.2"]
+        );
+    }
+
+    #[test]
+    fn test_check_and_pull() {
+        let executor = ScriptedExecutor::new(Arc::new(FakeClock::new()));
+        executor
+            .script(
+                "docker image ls --format '{{.Repository}}:{{.Tag}}'",
+                ScriptedCommand::success().stdout("nginx:stable\nmyapp:dev\nredis:7\n"),
+            )
+            .script(
+                &inspect("nginx:stable"),
+                ScriptedCommand::success().stdout(&format!("nginx@{}\n", NGINX_OLD)),
+            )
+            .script(&manifest("nginx:stable"), digest(NGINX_NEW))
+            // Built locally, never pushed
+            .script(
+                &inspect("myapp:dev"),
+                ScriptedCommand::success().stdout("\n"),
+            )
+            .script(
+                &inspect("redis:7"),
+                ScriptedCommand::success().stdout(&format!("redis@{}\n", NGINX_NEW)),
+            )
+            .script(
+                &manifest("redis:7"),
+                ScriptedCommand::exit(1).stderr("ERROR: toomanyrequests\n"),
+            )
+            .script(
+                "docker pull --quiet 'nginx:stable'",
+                ScriptedCommand::success(),
+            );
+        let images = DockerImages::default();
+        assert_eq!(
+            images.check(&executor, &[]).unwrap(),
+            "nginx:stable 111111111111 -> 222222222222\n"
+        );
+        assert_eq!(
+            images.pull(&executor, &[]).unwrap(),
+            "Pulled nginx:stable (111111111111 -> 222222222222)\n"
+        );
+    }
+
+    #[test]
+    fn test_configured_images_all_failing() {
+        let executor = ScriptedExecutor::new(Arc::new(FakeClock::new()));
+        executor.script(
+            &inspect("postgres:16"),
+            ScriptedCommand::exit(1).stderr("Error: No such image: postgres:16\n"),
+        );
+        let images = DockerImages {
+            images: vec!["postgres:16".to_string()],
+        };
+        let err = images.check(&executor, &[]).unwrap_err();
+        assert_eq!(err.message, "Couldn't check any image: postgres:16");
+    }
+}
@@ -0,0 +1,400 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::executor::Clock;
+use crate::package_manager::{UpdateError, UpdateErrorKind};
+use crate::step::expand_home;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// Without a Content-Length, report progress every this many bytes
+const PROGRESS_BYTES: u64 = 10 * 1024 * 1024;
+
+// Fetch a URL to a file, resuming partial downloads and retrying failures
+//
+//   - download:
+//       url: https://github.com/ryanoasis/nerd-fonts/releases/latest/download/FiraCode.zip
+//       path: ~/Downloads/FiraCode.zip
+//       retries: 3                      # default
+//       proxy: http://proxy.lan:3128    # default: the `proxy` settings or HTTPS_PROXY
+//
+// `$VAR` and `${VAR}` in `url` and `path` are expanded from the manager's
+// `env` and then the process environment.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DownloadStep {
+    pub url: String,
+    pub path: String,
+    #[serde(default = "DownloadStep::default_retries")]
+    pub retries: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+}
+
+// Why an attempt stopped, and whether trying again could help
+enum Failure {
+    Retry(String),
+    Fatal(UpdateError),
+}
+
+impl DownloadStep {
+    fn default_retries() -> u32 {
+        3
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.url.starts_with("https://") || self.url.starts_with("http://")) {
+            return Err(format!(
+                "download {}: url must start with https:// or http://",
+                self.url
+            ));
+        }
+        if self.path.is_empty() {
+            return Err(format!("download {}: path is empty", self.url));
+        }
+        Ok(())
+    }
+
+    // Download to `<path>.part`, renamed to `path` once complete. `env` is the
+    // manager's environment; `cancelled` is polled between chunks.
+    pub fn run(
+        &self,
+        env: &[(String, String)],
+        clock: &dyn Clock,
+        cancelled: &dyn Fn() -> bool,
+    ) -> Result<PathBuf, UpdateError> {
+        let url = expand_vars(&self.url, env);
+        let path = expand_home(&expand_vars(&self.path, env));
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(|e| {
+                UpdateError::new(format!("Failed to create {}: {}", parent.display(), e))
+            })?;
+        }
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+        let agent = http_agent(self.proxy.as_deref(), env, &url)?;
+
+        let mut attempt = 0;
+        loop {
+            match fetch(&agent, &url, &partial, cancelled) {
+                Ok(()) => break,
+                Err(Failure::Fatal(e)) => return Err(e),
+                Err(Failure::Retry(message)) if attempt < self.retries => {
+                    attempt += 1;
+                    let delay = Duration::from_secs(1 << attempt.min(6));
+                    warn!(
+                        "Download of {} failed ({}), retrying in {}s ({}/{})",
+                        url,
+                        message,
+                        delay.as_secs(),
+                        attempt,
+                        self.retries
+                    );
+                    clock.sleep(delay);
+                }
+                Err(Failure::Retry(message)) => {
+                    return Err(UpdateError::new(format!(
+                        "Download of {} failed: {}",
+                        url, message
+                    )))
+                }
+            }
+        }
+
+        fs::rename(&partial, &path).map_err(|e| {
+            UpdateError::new(format!(
+                "Failed to move download to {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        info!("Downloaded {} to {}", url, path.display());
+        Ok(path)
+    }
+}
+
+// HTTP client for built-in steps fetching `url`. An explicit `proxy` wins
+// over the proxy variables in the manager's environment (see
+// `proxy::for_url`), which win over updog's own environment.
+pub(crate) fn http_agent(
+    proxy: Option<&str>,
+    env: &[(String, String)],
+    url: &str,
+) -> Result<ureq::Agent, UpdateError> {
+    let builder = ureq::AgentBuilder::new()
+        .user_agent(concat!("updog/", env!("CARGO_PKG_VERSION")))
+        .timeout_connect(Duration::from_secs(30))
+        .timeout_read(Duration::from_secs(60));
+    let builder = match proxy
+        .map(str::to_string)
+        .or_else(|| crate::proxy::for_url(url, env))
+    {
+        Some(proxy) => builder.proxy(
+            ureq::Proxy::new(&proxy)
+                .map_err(|e| UpdateError::new(format!("Invalid proxy {}: {}", proxy, e)))?,
+        ),
+        None => builder,
+    };
+    Ok(builder.build())
+}
+
+// One attempt, continuing from whatever `partial` already holds
+fn fetch(
+    agent: &ureq::Agent,
+    url: &str,
+    partial: &Path,
+    cancelled: &dyn Fn() -> bool,
+) -> Result<(), Failure> {
+    let offset = fs::metadata(partial).map(|m| m.len()).unwrap_or(0);
+    let mut request = agent.get(url);
+    if offset > 0 {
+        request = request.set("Range", &format!("bytes={}-", offset));
+    }
+
+    let response = match request.call() {
+        Ok(response) => response,
+        // The partial file is already complete
+        Err(ureq::Error::Status(416, _)) if offset > 0 => return Ok(()),
+        Err(ureq::Error::Status(code, _)) if code == 429 || code >= 500 => {
+            return Err(Failure::Retry(format!("HTTP {}", code)))
+        }
+        Err(ureq::Error::Status(code, _)) => {
+            return Err(Failure::Fatal(UpdateError::new(format!(
+                "Download of {} failed: HTTP {}",
+                url, code
+            ))))
+        }
+        Err(e) => return Err(Failure::Retry(e.to_string())),
+    };
+
+    // Servers that ignore Range send the whole file again
+    let resumed = offset > 0 && response.status() == 206;
+    let start = if resumed { offset } else { 0 };
+    let total = response
+        .header("Content-Length")
+        .and_then(|len| len.parse::<u64>().ok())
+        .map(|len| len + start);
+    if resumed {
+        info!("Resuming download of {} at {} bytes", url, offset);
+    }
+
+    let io_error = |e: io::Error| {
+        Failure::Fatal(UpdateError::new(format!(
+            "Failed to write {}: {}",
+            partial.display(),
+            e
+        )))
+    };
+    let mut file: File = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(partial)
+        .map_err(io_error)?;
+
+    let mut reader = response.into_reader();
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut received = start;
+    let mut reported = received;
+    loop {
+        if cancelled() {
+            return Err(Failure::Fatal(UpdateError::with_kind(
+                UpdateErrorKind::Cancelled,
+                "Operation was cancelled",
+            )));
+        }
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => return Err(Failure::Retry(e.to_string())),
+        };
+        file.write_all(&buffer[..n]).map_err(io_error)?;
+        received += n as u64;
+
+        match total {
+            Some(total) if total > 0 => {
+                let percent = |bytes: u64| bytes * 100 / total;
+                if percent(received) / 10 > percent(reported) / 10 {
+                    info!("Downloading {}: {}%", url, percent(received));
+                    reported = received;
+                }
+            }
+            _ if received - reported >= PROGRESS_BYTES => {
+                info!("Downloading {}: {} MiB", url, received / (1024 * 1024));
+                reported = received;
+            }
+            _ => {}
+        }
+    }
+
+    if total.is_some_and(|total| received < total) {
+        return Err(Failure::Retry(format!(
+            "connection closed after {} of {} bytes",
+            received,
+            total.unwrap_or(0)
+        )));
+    }
+    Ok(())
+}
+
+// Expand `$VAR` and `${VAR}`, looking in `env` first. Unknown variables
+// expand to nothing, as in the shell.
+pub(crate) fn expand_vars(text: &str, env: &[(String, String)]) -> String {
+    let lookup = |name: &str| {
+        env.iter()
+            .rev()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.clone())
+            .or_else(|| std::env::var(name).ok())
+            .unwrap_or_default()
+    };
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find('$') {
+        expanded.push_str(&rest[..index]);
+        let after = &rest[index + 1..];
+        if let Some(braced) = after.strip_prefix('{') {
+            if let Some(end) = braced.find('}') {
+                expanded.push_str(&lookup(&braced[..end]));
+                rest = &braced[end + 1..];
+                continue;
+            }
+        }
+        let end = after.find(|c| !is_name_char(c)).unwrap_or(after.len());
+        if end == 0 {
+            expanded.push('$');
+        } else {
+            expanded.push_str(&lookup(&after[..end]));
+        }
+        rest = &after[end..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::FakeClock;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::thread;
+    use tempfile::tempdir;
+
+    const BODY: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    // Serve BODY once per scripted response, honoring Range requests.
+    // A response of `None` cuts the body off halfway.
+    fn serve(responses: Vec<Option<u16>>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut ranges = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut start = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(range) = line.strip_prefix("Range: bytes=") {
+                        ranges.push(range.trim().to_string());
+                        start = range.trim().trim_end_matches('-').parse().unwrap();
+                    }
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                }
+                let (status, body) = match response {
+                    Some(206) => ("206 Partial Content", &BODY[start..]),
+                    Some(503) => ("503 Service Unavailable", &b""[..]),
+                    Some(404) => ("404 Not Found", &b""[..]),
+                    Some(_) => ("200 OK", BODY),
+                    None => ("200 OK", BODY),
+                };
+                let sent = match response {
+                    None => &body[..body.len() / 2],
+                    _ => body,
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                )
+                .unwrap();
+                stream.write_all(sent).unwrap();
+            }
+            ranges
+        });
+        (url, handle)
+    }
+
+    fn step(url: &str, path: &Path) -> DownloadStep {
+        DownloadStep {
+            url: url.to_string(),
+            path: path.display().to_string(),
+            retries: 3,
+            proxy: None,
+        }
+    }
+
+    #[test]
+    fn test_download_resumes_after_interruption() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("nested").join("file.bin");
+        let (url, server) = serve(vec![None, Some(503), Some(206)]);
+        let clock = FakeClock::new();
+
+        let downloaded = step(&url, &path).run(&[], &clock, &|| false).unwrap();
+        assert_eq!(downloaded, path);
+        assert_eq!(fs::read(&path).unwrap(), BODY);
+        assert!(!dir.path().join("nested").join("file.bin.part").exists());
+        assert_eq!(server.join().unwrap(), vec!["18-", "18-"]);
+    }
+
+    #[test]
+    fn test_download_gives_up() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.bin");
+        let clock = FakeClock::new();
+
+        let (url, server) = serve(vec![Some(404)]);
+        let err = step(&url, &path).run(&[], &clock, &|| false).unwrap_err();
+        assert!(err.message.ends_with("HTTP 404"));
+        server.join().unwrap();
+
+        let (url, server) = serve(vec![Some(503), Some(503)]);
+        let mut once = step(&url, &path);
+        once.retries = 1;
+        let err = once.run(&[], &clock, &|| false).unwrap_err();
+        assert!(err.message.ends_with("HTTP 503"));
+        server.join().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_expand_vars() {
+        let env = vec![("TAG".to_string(), "v1.2".to_string())];
+        assert_eq!(
+            expand_vars("https://x/${TAG}/tool-$TAG.tgz", &env),
+            "https://x/v1.2/tool-v1.2.tgz"
+        );
+        assert_eq!(expand_vars("$UPDOG_SURELY_UNSET/a $ b", &env), "/a $ b");
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut download = step("ftp://example.com/x", Path::new("x"));
+        assert!(download.validate().is_err());
+        download.url = "https://example.com/x".to_string();
+        assert!(download.validate().is_ok());
+    }
+}
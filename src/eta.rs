@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+use crate::stats::format_duration;
+
+// Time left of a run, from how long each manager typically takes (its
+// median duration in history). Managers are assumed to run one at a time,
+// so with concurrent checks it's on the long side.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Estimate {
+    // Typical duration of the manager that's starting
+    pub manager: Option<Duration>,
+    // Left for the whole run, counting the managers with history
+    pub run: Duration,
+    // Managers left without history, making `run` a lower bound
+    pub unknown: usize,
+}
+
+impl Estimate {
+    // "about 3m20s left", "at least 40s left", or None when there's nothing
+    // to go by (or less than a second left)
+    pub fn describe(&self) -> Option<String> {
+        if self.run.as_secs() == 0 {
+            return None;
+        }
+        let qualifier = match self.unknown {
+            0 => "about",
+            _ => "at least",
+        };
+        Some(format!("{} {} left", qualifier, short(self.run)))
+    }
+}
+
+// Whole seconds under a minute, as format_duration above that
+pub fn short(duration: Duration) -> String {
+    match duration.as_secs() {
+        0 => "<1s".to_string(),
+        secs @ 1..60 => format!("{}s", secs),
+        _ => format_duration(duration),
+    }
+}
+
+// Tracks the managers of a run as they start and finish
+#[derive(Debug)]
+pub struct Eta {
+    typical: Vec<Option<Duration>>,
+    started: Vec<Option<SystemTime>>,
+    finished: Vec<bool>,
+}
+
+impl Eta {
+    // `durations` maps "manager[:subcommand]" to a typical duration
+    pub fn new(items: &[(String, Option<String>)], durations: &BTreeMap<String, Duration>) -> Self {
+        let typical = items
+            .iter()
+            .map(|(manager, subcommand)| {
+                let name = match subcommand {
+                    Some(sc) => format!("{}:{}", manager, sc),
+                    None => manager.clone(),
+                };
+                durations.get(&name).copied()
+            })
+            .collect();
+        Self {
+            typical,
+            started: vec![None; items.len()],
+            finished: vec![false; items.len()],
+        }
+    }
+
+    pub fn start(&mut self, index: usize, now: SystemTime) -> Estimate {
+        self.started[index] = Some(now);
+        Estimate {
+            manager: self.typical[index],
+            ..self.remaining(now)
+        }
+    }
+
+    pub fn finish(&mut self, index: usize, now: SystemTime) -> Estimate {
+        self.finished[index] = true;
+        self.remaining(now)
+    }
+
+    // Managers that are running count with what's left of their typical
+    // duration (nothing once they take longer)
+    pub fn remaining(&self, now: SystemTime) -> Estimate {
+        let mut estimate = Estimate::default();
+        for index in (0..self.typical.len()).filter(|&i| !self.finished[i]) {
+            let Some(typical) = self.typical[index] else {
+                estimate.unknown += 1;
+                continue;
+            };
+            let elapsed = self.started[index]
+                .and_then(|started| now.duration_since(started).ok())
+                .unwrap_or_default();
+            estimate.run += typical.saturating_sub(elapsed);
+        }
+        estimate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_remaining_as_managers_run() {
+        let items = vec![
+            ("apt".to_string(), None),
+            ("npm".to_string(), Some("global".to_string())),
+            ("new".to_string(), None),
+        ];
+        let durations: BTreeMap<String, Duration> = [
+            ("apt".to_string(), Duration::from_secs(60)),
+            ("npm:global".to_string(), Duration::from_secs(20)),
+        ]
+        .into_iter()
+        .collect();
+        let mut eta = Eta::new(&items, &durations);
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+
+        let estimate = eta.start(0, at(0));
+        assert_eq!(estimate.manager, Some(Duration::from_secs(60)));
+        assert_eq!(estimate.run, Duration::from_secs(80));
+        assert_eq!(estimate.describe().unwrap(), "at least 1m20s left");
+        assert_eq!(eta.remaining(at(45)).run, Duration::from_secs(35));
+        // Running late
+        assert_eq!(eta.remaining(at(90)).run, Duration::from_secs(20));
+
+        eta.finish(0, at(90));
+        assert_eq!(eta.start(1, at(90)).run, Duration::from_secs(20));
+        let estimate = eta.finish(1, at(100));
+        assert_eq!(estimate.run, Duration::ZERO);
+        assert_eq!(estimate.unknown, 1);
+        assert_eq!(estimate.describe(), None);
+        assert_eq!(eta.start(2, at(100)).manager, None);
+        assert_eq!(eta.finish(2, at(110)), Estimate::default());
+    }
+
+    #[test]
+    fn test_describe() {
+        let estimate = Estimate {
+            manager: None,
+            run: Duration::from_secs(42),
+            unknown: 0,
+        };
+        assert_eq!(estimate.describe().unwrap(), "about 42s left");
+        assert_eq!(short(Duration::from_millis(59_900)), "59s");
+        assert_eq!(short(Duration::from_millis(300)), "<1s");
+        let estimate = Estimate {
+            run: Duration::from_millis(300),
+            ..estimate
+        };
+        assert_eq!(estimate.describe(), None);
+        assert_eq!(short(Duration::from_secs(3700)), "1h01m");
+    }
+}
@@ -0,0 +1,695 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{IsTerminal, Read, Write};
+use std::process::{Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::info;
+
+use crate::lint::binary_exists;
+use crate::output::decode_output;
+use crate::package_manager::{UpdateError, UpdateErrorKind};
+use crate::shell::Shell;
+
+// Result of running a single shell command
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandOutput {
+    // Exit code of the process (None if it was terminated by a signal)
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration: Duration,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    // Human readable exit status, used in error messages
+    pub fn status_description(&self) -> String {
+        match self.exit_code {
+            Some(code) => format!("exit status: {}", code),
+            None => "terminated by signal".to_string(),
+        }
+    }
+}
+
+// A command to run, plus how to run it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandRequest {
+    pub command: String,
+    // Capture stdout/stderr instead of passing them through to the terminal
+    pub capture: bool,
+    // With `capture`, also show the output on the terminal while it runs
+    pub tee: bool,
+    // Extra environment variables for the command
+    pub env: Vec<(String, String)>,
+    // Written to the command's stdin (the terminal's stdin is inherited otherwise)
+    pub stdin: Option<String>,
+    // Start from an empty environment, with only `env` and the variables
+    // in CLEAN_ENV_KEPT, instead of updog's own
+    pub clean_env: bool,
+}
+
+// Environment variable with the id of the run a command belongs to
+pub const RUN_ID_ENV: &str = "UPDOG_RUN_ID";
+
+// Kept in a clean environment, since hardly any command runs without them
+#[cfg(not(windows))]
+pub const CLEAN_ENV_KEPT: &[&str] = &["PATH"];
+#[cfg(windows)]
+pub const CLEAN_ENV_KEPT: &[&str] = &["PATH", "SystemRoot"];
+
+impl CommandRequest {
+    pub fn new(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn captured(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            capture: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn run_id(&self) -> Option<&str> {
+        self.env
+            .iter()
+            .find(|(name, _)| name == RUN_ID_ENV)
+            .map(|(_, id)| id.as_str())
+    }
+}
+
+// Runs shell commands on behalf of a PackageManager
+pub trait Executor: Send + Sync {
+    fn run(&self, request: &CommandRequest) -> Result<CommandOutput, UpdateError>;
+
+    // Terminate anything still running (called on exit)
+    fn cleanup(&self) {}
+
+    // Terminate the commands that are running, leaving the run going
+    fn interrupt(&self) {}
+
+    // Whether an interrupt/termination signal has been received
+    fn shutdown_requested(&self) -> bool {
+        false
+    }
+
+    // Whether `program` would be found, for dry runs to tell without running it
+    fn finds(&self, program: &str) -> bool {
+        binary_exists(program)
+    }
+}
+
+// Source of time, so that durations and schedules can be controlled in tests
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+    fn sleep(&self, duration: Duration);
+}
+
+// Clock backed by the operating system
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+// Structure to track running processes
+pub(crate) struct ProcessTracker {
+    active_processes: HashSet<u32>, // Set of active process IDs
+    shutdown_requested: Arc<AtomicBool>,
+}
+
+impl ProcessTracker {
+    fn new() -> Self {
+        Self {
+            active_processes: HashSet::new(),
+            shutdown_requested: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // Register a process
+    fn register_process(&mut self, pid: u32) {
+        self.active_processes.insert(pid);
+    }
+
+    // Unregister a process
+    fn unregister_process(&mut self, pid: u32) {
+        self.active_processes.remove(&pid);
+    }
+
+    // Mark shutdown as requested
+    fn request_shutdown(&self) {
+        self.shutdown_requested.store(true, Ordering::SeqCst);
+    }
+
+    // Check if shutdown has been requested
+    fn is_shutdown_requested(&self) -> bool {
+        self.shutdown_requested.load(Ordering::SeqCst)
+    }
+
+    // Terminate all active processes
+    fn terminate_all_processes(&self) {
+        for &pid in &self.active_processes {
+            // Attempt to send SIGTERM signal
+            #[cfg(unix)]
+            unsafe {
+                libc::kill(pid as i32, libc::SIGTERM);
+                info!("Sent SIGTERM to process {}", pid);
+            }
+
+            #[cfg(windows)]
+            {
+                // On Windows, a different termination mechanism is needed
+                // Here, we just perform simple logging
+                info!(
+                    "Process termination on Windows not implemented for PID: {}",
+                    pid
+                );
+            }
+        }
+    }
+}
+
+// Our end of a teed stdout or stderr: the master of a pseudo-terminal, so
+// the command still sees a terminal (colors, progress bars), or a pipe if
+// ours isn't one or no pseudo-terminal can be opened
+enum TeeStream {
+    Pty(File),
+    Pipe,
+}
+
+impl TeeStream {
+    // The stream for our `terminal` and the child's end of it
+    fn open(terminal: &impl IsTerminal) -> (Self, Stdio) {
+        if !terminal.is_terminal() {
+            return (TeeStream::Pipe, Stdio::piped());
+        }
+        match open_pty() {
+            Ok((master, slave)) => (TeeStream::Pty(master), Stdio::from(slave)),
+            Err(e) => {
+                info!(
+                    "Couldn't open a pseudo-terminal, teeing through a pipe: {}",
+                    e
+                );
+                (TeeStream::Pipe, Stdio::piped())
+            }
+        }
+    }
+
+    // Copy what the command writes to `to` and keep it, until it's done
+    fn copy(
+        self,
+        pipe: Option<impl Read + Send + 'static>,
+        mut to: impl Write + Send + 'static,
+    ) -> JoinHandle<Vec<u8>> {
+        let mut from: Box<dyn Read + Send> = match (self, pipe) {
+            (TeeStream::Pty(master), _) => Box::new(master),
+            (TeeStream::Pipe, Some(pipe)) => Box::new(pipe),
+            (TeeStream::Pipe, None) => Box::new(std::io::empty()),
+        };
+        thread::spawn(move || {
+            let mut captured = Vec::new();
+            let mut buffer = [0; 4096];
+            loop {
+                match from.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        let _ = to.write_all(&buffer[..n]);
+                        let _ = to.flush();
+                        captured.extend_from_slice(&buffer[..n]);
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                    // A pseudo-terminal reports EIO once nothing has it open
+                    Err(_) => break,
+                }
+            }
+            captured
+        })
+    }
+}
+
+// A pseudo-terminal with the window size of our stdout, returning the master
+// and the slave. Output isn't post-processed, so captured lines end in "\n"
+// rather than "\r\n".
+#[cfg(unix)]
+fn open_pty() -> std::io::Result<(File, std::os::fd::OwnedFd)> {
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let sized = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    let (mut master, mut slave) = (0, 0);
+    let result = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            if sized { &size } else { std::ptr::null() },
+        )
+    };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let (master, slave) = unsafe { (File::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+    for fd in [master.as_raw_fd(), slave.as_raw_fd()] {
+        unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    }
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(slave.as_raw_fd(), &mut termios) } == 0 {
+        termios.c_oflag &= !libc::OPOST;
+        unsafe { libc::tcsetattr(slave.as_raw_fd(), libc::TCSANOW, &termios) };
+    }
+    Ok((master, slave))
+}
+
+#[cfg(not(unix))]
+fn open_pty() -> std::io::Result<(File, File)> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "pseudo-terminals need unix",
+    ))
+}
+
+// Receives the output of commands as it comes, a chunk at a time
+pub type OutputSink = Arc<dyn Fn(&[u8]) + Send + Sync>;
+
+// Writes to an OutputSink
+struct SinkWriter(OutputSink);
+
+impl Write for SinkWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        (self.0)(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// Process trackers of the executors alive, for the signal handler
+#[cfg(unix)]
+static TRACKERS: Mutex<Vec<std::sync::Weak<Mutex<ProcessTracker>>>> = Mutex::new(Vec::new());
+
+// Executor that spawns real shell processes, with inherited, captured or
+// teed stdio
+pub struct ShellExecutor {
+    process_tracker: Arc<Mutex<ProcessTracker>>,
+    shell: Shell,
+    // Where captured output is teed to instead of the terminal (`updog tui`)
+    output: Option<OutputSink>,
+}
+
+impl Default for ShellExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShellExecutor {
+    pub fn new() -> Self {
+        Self::with_shell(Shell::platform_default())
+    }
+
+    pub fn with_shell(shell: Shell) -> Self {
+        let process_tracker = Arc::new(Mutex::new(ProcessTracker::new()));
+        Self::setup_signal_handlers(process_tracker.clone());
+        Self {
+            process_tracker,
+            shell,
+            output: None,
+        }
+    }
+
+    // Hand the output of captured commands to `sink` as they run. Teeing
+    // goes there too, rather than to our stdout and stderr.
+    pub fn with_output(mut self, sink: OutputSink) -> Self {
+        self.output = Some(sink);
+        self
+    }
+
+    #[cfg(unix)]
+    // Set up signal handlers (Unix platforms only). One thread handles the
+    // signals for every executor, however many are built (`updog tui` builds
+    // one per entry), and stops the commands each of them is running.
+    fn setup_signal_handlers(process_tracker: Arc<Mutex<ProcessTracker>>) {
+        use signal_hook::{
+            consts::{SIGINT, SIGTERM},
+            iterator::Signals,
+        };
+        use std::sync::Once;
+        use std::thread;
+
+        static HANDLER: Once = Once::new();
+
+        let mut trackers = TRACKERS.lock().unwrap();
+        trackers.retain(|tracker| tracker.strong_count() > 0);
+        trackers.push(Arc::downgrade(&process_tracker));
+        drop(trackers);
+
+        HANDLER.call_once(|| {
+            let mut signals = Signals::new([SIGINT, SIGTERM]).unwrap();
+            thread::spawn(move || {
+                for sig in signals.forever() {
+                    info!("Received signal: {}", sig);
+                    let trackers = TRACKERS.lock().unwrap();
+                    for tracker in trackers.iter().filter_map(std::sync::Weak::upgrade) {
+                        let tracker = tracker.lock().unwrap();
+                        tracker.request_shutdown();
+                        tracker.terminate_all_processes();
+                    }
+                }
+            });
+        });
+    }
+
+    #[cfg(not(unix))]
+    // Simple implementation on Windows
+    fn setup_signal_handlers(_process_tracker: Arc<Mutex<ProcessTracker>>) {
+        // Windows signal handling requires different mechanisms
+        // For simplicity, we'll just log that it's not fully implemented
+        // but we'll keep the process_tracker so the rest of the code is consistent
+        info!("Signal handling on this platform is limited");
+
+        // In a real application, we'd implement proper Ctrl+C handling for Windows
+        // using the ctrlc crate or Windows-specific APIs
+    }
+}
+
+impl Executor for ShellExecutor {
+    fn shutdown_requested(&self) -> bool {
+        self.process_tracker.lock().unwrap().is_shutdown_requested()
+    }
+
+    // cmd and PowerShell commands are often builtins or cmdlets, which
+    // aren't found on PATH
+    fn finds(&self, program: &str) -> bool {
+        match self.shell {
+            Shell::Bash | Shell::Sh => binary_exists(program),
+            Shell::Cmd | Shell::Powershell | Shell::Pwsh => true,
+        }
+    }
+
+    fn run(&self, request: &CommandRequest) -> Result<CommandOutput, UpdateError> {
+        let command = request.command.as_str();
+        info!("Executing command: {}", command);
+        let started = Instant::now();
+
+        let stdio = || {
+            if request.capture {
+                Stdio::piped()
+            } else {
+                Stdio::inherit()
+            }
+        };
+        let (tee, stdout, stderr) = if request.capture && self.output.is_some() {
+            let pipe = || (TeeStream::Pipe, Stdio::piped());
+            let ((stdout, child_stdout), (stderr, child_stderr)) = (pipe(), pipe());
+            (Some((stdout, stderr)), child_stdout, child_stderr)
+        } else if request.capture && request.tee {
+            let (stdout, child_stdout) = TeeStream::open(&std::io::stdout());
+            let (stderr, child_stderr) = TeeStream::open(&std::io::stderr());
+            (Some((stdout, stderr)), child_stdout, child_stderr)
+        } else {
+            (None, stdio(), stdio())
+        };
+
+        // Launch the command
+        let mut process = self.shell.command(command);
+        if request.clean_env {
+            process.env_clear().envs(
+                CLEAN_ENV_KEPT
+                    .iter()
+                    .filter_map(|name| Some((name, std::env::var_os(name)?))),
+            );
+        }
+        let mut process = match process
+            .envs(request.env.iter().map(|(k, v)| (k, v)))
+            .stdin(if request.stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::inherit()
+            })
+            .stderr(stderr)
+            .stdout(stdout)
+            .spawn()
+        {
+            Ok(p) => p,
+            Err(e) => {
+                return Err(UpdateError::new(format!(
+                    "Failed to execute command: {}",
+                    e
+                )));
+            }
+        };
+
+        // Feed stdin from a thread so a command filling its output pipe
+        // can't block on us
+        if let (Some(input), Some(mut pipe)) = (request.stdin.clone(), process.stdin.take()) {
+            std::thread::spawn(move || {
+                use std::io::Write;
+                let _ = pipe.write_all(input.as_bytes());
+            });
+        }
+
+        // Get the process ID for tracking
+        let pid = process.id();
+
+        // Register the process with the tracker
+        {
+            let mut tracker = self.process_tracker.lock().unwrap();
+            tracker.register_process(pid);
+
+            // Check if shutdown was requested before we even started
+            if tracker.is_shutdown_requested() {
+                drop(tracker); // Release the lock before terminating
+
+                // If so, terminate immediately
+                #[cfg(unix)]
+                unsafe {
+                    libc::kill(pid as i32, libc::SIGTERM);
+                    info!("Terminated process {} due to shutdown request", pid);
+                }
+
+                return Err(UpdateError::with_kind(
+                    UpdateErrorKind::Cancelled,
+                    "Operation was cancelled",
+                ));
+            }
+        }
+
+        // Wait for the process to complete (collecting output if piped or teed)
+        let output = match tee {
+            Some((stdout, stderr)) => {
+                let (to_stdout, to_stderr): (Box<dyn Write + Send>, Box<dyn Write + Send>) =
+                    match &self.output {
+                        Some(sink) => (
+                            Box::new(SinkWriter(sink.clone())),
+                            Box::new(SinkWriter(sink.clone())),
+                        ),
+                        None => (Box::new(std::io::stdout()), Box::new(std::io::stderr())),
+                    };
+                let stdout = stdout.copy(process.stdout.take(), to_stdout);
+                let stderr = stderr.copy(process.stderr.take(), to_stderr);
+                process.wait().map(|status| Output {
+                    status,
+                    stdout: stdout.join().unwrap_or_default(),
+                    stderr: stderr.join().unwrap_or_default(),
+                })
+            }
+            None => process.wait_with_output(),
+        };
+        let output = match output {
+            Ok(output) => output,
+            Err(e) => {
+                return Err(UpdateError::new(format!(
+                    "Failed to wait for command: {}",
+                    e
+                )));
+            }
+        };
+
+        // Unregister the process when it's done
+        {
+            let mut tracker = self.process_tracker.lock().unwrap();
+            tracker.unregister_process(pid);
+        }
+
+        Ok(CommandOutput {
+            exit_code: output.status.code(),
+            stdout: decode_output(&output.stdout),
+            stderr: decode_output(&output.stderr),
+            duration: started.elapsed(),
+        })
+    }
+
+    fn cleanup(&self) {
+        let tracker = self.process_tracker.lock().unwrap();
+        tracker.request_shutdown();
+        tracker.terminate_all_processes();
+    }
+
+    fn interrupt(&self) {
+        self.process_tracker
+            .lock()
+            .unwrap()
+            .terminate_all_processes();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_output_success() {
+        let output = CommandOutput {
+            exit_code: Some(0),
+            ..Default::default()
+        };
+        assert!(output.success());
+        assert_eq!(output.status_description(), "exit status: 0");
+
+        let output = CommandOutput {
+            exit_code: None,
+            ..Default::default()
+        };
+        assert!(!output.success());
+        assert_eq!(output.status_description(), "terminated by signal");
+    }
+
+    #[test]
+    fn test_shell_executor_exit_code() {
+        let executor = ShellExecutor::new();
+        assert!(executor
+            .run(&CommandRequest::new("exit 0"))
+            .unwrap()
+            .success());
+        assert_eq!(
+            executor
+                .run(&CommandRequest::new("exit 3"))
+                .unwrap()
+                .exit_code,
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn test_shell_executor_capture() {
+        let executor = ShellExecutor::new();
+        let output = executor
+            .run(&CommandRequest::captured("echo hello"))
+            .unwrap();
+        assert_eq!(output.stdout.trim(), "hello");
+
+        let output = executor
+            .run(&CommandRequest::new("echo passthrough"))
+            .unwrap();
+        assert!(output.stdout.is_empty());
+    }
+
+    #[test]
+    fn test_shell_executor_tee() {
+        let executor = ShellExecutor::new();
+        let mut request = CommandRequest::captured("echo out; echo err >&2; exit 2");
+        request.tee = true;
+        let output = executor.run(&request).unwrap();
+        assert_eq!(output.stdout, "out\n");
+        assert_eq!(output.stderr, "err\n");
+        assert_eq!(output.exit_code, Some(2));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shell_executor_output_sink() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let executor = ShellExecutor::new().with_output(Arc::new(move |chunk: &[u8]| {
+            sink.lock().unwrap().extend_from_slice(chunk)
+        }));
+        let output = executor
+            .run(&CommandRequest::captured("echo out; echo err >&2"))
+            .unwrap();
+        assert_eq!(output.stdout, "out\n");
+        assert_eq!(output.stderr, "err\n");
+        // The streams are copied on threads of their own, in either order
+        let seen = String::from_utf8(seen.lock().unwrap().clone()).unwrap();
+        let mut lines: Vec<&str> = seen.lines().collect();
+        lines.sort();
+        assert_eq!(lines, ["err", "out"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_executors_share_signal_handler() {
+        let registered = |executor: &ShellExecutor| {
+            TRACKERS
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|tracker| tracker.as_ptr() == Arc::as_ptr(&executor.process_tracker))
+        };
+        let first = ShellExecutor::new();
+        let second = ShellExecutor::new();
+        assert!(registered(&first) && registered(&second));
+        let dropped = Arc::downgrade(&first.process_tracker);
+        drop(first);
+        let _third = ShellExecutor::new();
+        assert!(!TRACKERS
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|tracker| tracker.ptr_eq(&dropped)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shell_executor_env() {
+        let executor = ShellExecutor::new();
+        let mut request = CommandRequest::captured("echo $UPDOG_TEST_VAR");
+        request
+            .env
+            .push(("UPDOG_TEST_VAR".to_string(), "set".to_string()));
+        assert_eq!(executor.run(&request).unwrap().stdout.trim(), "set");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shell_executor_clean_env() {
+        let executor = ShellExecutor::new();
+        let mut request =
+            CommandRequest::captured("echo ${HOME-unset} ${PATH:+path} $UPDOG_TEST_VAR");
+        request
+            .env
+            .push(("UPDOG_TEST_VAR".to_string(), "set".to_string()));
+        request.clean_env = true;
+        assert_eq!(
+            executor.run(&request).unwrap().stdout.trim(),
+            "unset path set"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shell_executor_stdin() {
+        let executor = ShellExecutor::new();
+        let mut request = CommandRequest::captured("tr a-z A-Z");
+        request.stdin = Some("updog\n".to_string());
+        assert_eq!(executor.run(&request).unwrap().stdout, "UPDOG\n");
+    }
+}
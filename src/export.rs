@@ -0,0 +1,370 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::history::{HistoryError, ManagerRecord, RunRecord, HISTORY_VERSION};
+use crate::metadata::RunMetadata;
+
+// File format for `updog history export` / `import`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    // Complete run records, suitable for backups and migration
+    Json,
+    // One row per manager result, for spreadsheets and other external tools.
+    // Only the hostname survives of the run metadata.
+    Csv,
+}
+
+impl ExportFormat {
+    // Guess the format from a file extension
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "json" => Ok(ExportFormat::Json),
+            "csv" => Ok(ExportFormat::Csv),
+            _ => Err(format!("unknown format '{}' (expected json or csv)", value)),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+        })
+    }
+}
+
+// Top-level JSON document
+#[derive(Serialize, Deserialize)]
+struct JsonExport {
+    version: u32,
+    runs: Vec<RunRecord>,
+}
+
+// Exports from before runs had ids lack the last column
+const CSV_HEADER: [&str; 13] = [
+    "version",
+    "started_at",
+    "operation",
+    "hostname",
+    "manager",
+    "subcommand",
+    "status",
+    "success",
+    "duration_secs",
+    "packages",
+    "message",
+    "note",
+    "run_id",
+];
+
+pub fn export(runs: &[RunRecord], format: ExportFormat) -> Result<String, HistoryError> {
+    match format {
+        ExportFormat::Json => {
+            let document = JsonExport {
+                version: HISTORY_VERSION,
+                runs: runs.to_vec(),
+            };
+            let mut content = serde_json::to_string_pretty(&document)
+                .map_err(|e| HistoryError::InvalidData(e.to_string()))?;
+            content.push('\n');
+            Ok(content)
+        }
+        ExportFormat::Csv => Ok(export_csv(runs)),
+    }
+}
+
+pub fn import(content: &str, format: ExportFormat) -> Result<Vec<RunRecord>, HistoryError> {
+    match format {
+        ExportFormat::Json => {
+            let document: JsonExport = serde_json::from_str(content)
+                .map_err(|e| HistoryError::InvalidData(e.to_string()))?;
+            if document.version > HISTORY_VERSION {
+                return Err(HistoryError::UnsupportedVersion(document.version));
+            }
+            Ok(document.runs)
+        }
+        ExportFormat::Csv => import_csv(content),
+    }
+}
+
+fn export_csv(runs: &[RunRecord]) -> String {
+    let mut content = CSV_HEADER.join(",");
+    content.push('\n');
+    for run in runs {
+        for record in &run.results {
+            let fields = [
+                run.version.to_string(),
+                run.started_at.to_string(),
+                run.operation.clone(),
+                run.hostname().unwrap_or_default().to_string(),
+                record.manager.clone(),
+                record.subcommand.clone().unwrap_or_default(),
+                record.status.clone().unwrap_or_default(),
+                record.success.to_string(),
+                record.duration_secs.to_string(),
+                record.packages.map(|p| p.to_string()).unwrap_or_default(),
+                record.message.clone(),
+                run.note.clone().unwrap_or_default(),
+                run.run_id.clone().unwrap_or_default(),
+            ];
+            let row: Vec<String> = fields.iter().map(|f| csv_escape(f)).collect();
+            content.push_str(&row.join(","));
+            content.push('\n');
+        }
+    }
+    content
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// Rebuild runs from CSV rows; consecutive rows of the same run are grouped
+fn import_csv(content: &str) -> Result<Vec<RunRecord>, HistoryError> {
+    let mut rows = parse_csv(content)?.into_iter().enumerate();
+    match rows.next() {
+        Some((_, header)) if header.first().map(String::as_str) == Some(CSV_HEADER[0]) => {}
+        _ => return Err(HistoryError::InvalidData("missing CSV header".to_string())),
+    }
+
+    let mut runs: Vec<RunRecord> = Vec::new();
+    // Data rows are numbered from 1, after the header
+    for (line, row) in rows {
+        if row.len() != CSV_HEADER.len() && row.len() != CSV_HEADER.len() - 1 {
+            return Err(HistoryError::InvalidData(format!(
+                "CSV row {} has {} fields, expected {}",
+                line,
+                row.len(),
+                CSV_HEADER.len()
+            )));
+        }
+        let invalid = |column: &str| {
+            HistoryError::InvalidData(format!("invalid {} in CSV row {}", column, line))
+        };
+        let optional = |value: &str| (!value.is_empty()).then(|| value.to_string());
+
+        let version: u32 = row[0].parse().map_err(|_| invalid("version"))?;
+        let started_at: u64 = row[1].parse().map_err(|_| invalid("started_at"))?;
+        let record = ManagerRecord {
+            manager: row[4].clone(),
+            subcommand: optional(&row[5]),
+            status: optional(&row[6]),
+            success: row[7].parse().map_err(|_| invalid("success"))?,
+            duration_secs: row[8].parse().map_err(|_| invalid("duration_secs"))?,
+            packages: match row[9].as_str() {
+                "" => None,
+                value => Some(value.parse().map_err(|_| invalid("packages"))?),
+            },
+            message: row[10].clone(),
+            steps: Vec::new(),
+            output: String::new(),
+            warnings: Vec::new(),
+            columns: BTreeMap::new(),
+        };
+
+        let hostname = optional(&row[3]);
+        let note = optional(&row[11]);
+        let run_id = row.get(12).and_then(|id| optional(id));
+        match runs.last_mut() {
+            Some(run)
+                if run.version == version
+                    && run.run_id == run_id
+                    && run.started_at == started_at
+                    && run.operation == row[2]
+                    && run.hostname() == hostname.as_deref()
+                    && run.note == note =>
+            {
+                run.results.push(record)
+            }
+            _ => runs.push(RunRecord {
+                version,
+                run_id,
+                started_at,
+                operation: row[2].clone(),
+                results: vec![record],
+                metadata: hostname.map(|hostname| RunMetadata {
+                    hostname: Some(hostname),
+                    ..RunMetadata::default()
+                }),
+                note,
+                warnings: Vec::new(),
+            }),
+        }
+    }
+    Ok(runs)
+}
+
+// Split CSV content into rows of fields (RFC 4180 quoting)
+fn parse_csv(content: &str) -> Result<Vec<Vec<String>>, HistoryError> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(HistoryError::InvalidData(
+            "unterminated quoted CSV field".to_string(),
+        ));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn sample_runs() -> Vec<RunRecord> {
+        let mut first = RunRecord::new("check", UNIX_EPOCH + Duration::from_secs(100));
+        first.run_id = Some("3f2a9c1e-8b4d-4e6f-a1b2-c3d4e5f60718".to_string());
+        first.metadata = Some(RunMetadata {
+            hostname: Some("laptop".to_string()),
+            ..RunMetadata::default()
+        });
+        first.results.push(ManagerRecord {
+            manager: "apt".to_string(),
+            subcommand: None,
+            success: true,
+            status: Some("success".to_string()),
+            duration_secs: 1.5,
+            packages: Some(3),
+            message: String::new(),
+            steps: Vec::new(),
+            output: String::new(),
+            warnings: Vec::new(),
+            columns: BTreeMap::new(),
+        });
+        first.results.push(ManagerRecord {
+            manager: "brew".to_string(),
+            subcommand: Some("cask".to_string()),
+            success: false,
+            status: Some("failed".to_string()),
+            duration_secs: 0.25,
+            packages: None,
+            message: "error: \"network\", retry\nlater".to_string(),
+            steps: Vec::new(),
+            output: String::new(),
+            warnings: Vec::new(),
+            columns: BTreeMap::new(),
+        });
+
+        let mut second = RunRecord::new("update", UNIX_EPOCH + Duration::from_secs(200));
+        second.note = Some("after reinstalling, with \"quotes\"".to_string());
+        second.results.push(ManagerRecord {
+            manager: "apt".to_string(),
+            subcommand: None,
+            success: true,
+            status: None,
+            duration_secs: 12.0,
+            packages: None,
+            message: String::new(),
+            steps: Vec::new(),
+            output: String::new(),
+            warnings: Vec::new(),
+            columns: BTreeMap::new(),
+        });
+        vec![first, second]
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let runs = sample_runs();
+        let content = export(&runs, ExportFormat::Json).unwrap();
+        assert_eq!(import(&content, ExportFormat::Json).unwrap(), runs);
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let runs = sample_runs();
+        let content = export(&runs, ExportFormat::Csv).unwrap();
+        assert!(content.starts_with(
+            "version,started_at,operation,hostname,manager,subcommand,status,success,duration_secs,packages,message,note,run_id\n"
+        ));
+        assert!(content.contains(
+            "1,100,check,laptop,apt,,success,true,1.5,3,,,3f2a9c1e-8b4d-4e6f-a1b2-c3d4e5f60718\n"
+        ));
+        // CSV keeps only the hostname of the metadata, which is all the samples have
+        assert_eq!(import(&content, ExportFormat::Csv).unwrap(), runs);
+    }
+
+    #[test]
+    fn test_csv_import_without_run_ids() {
+        let content = "version,started_at,operation,hostname,manager,subcommand,status,success,duration_secs,packages,message,note\n1,100,check,,apt,,success,true,1.5,3,,\n1,100,check,,brew,,success,true,2,0,,\n";
+        let runs = import(content, ExportFormat::Csv).unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].run_id, None);
+        assert_eq!(runs[0].results.len(), 2);
+    }
+
+    #[test]
+    fn test_import_rejects_newer_version() {
+        let content = format!("{{\"version\": {}, \"runs\": []}}", HISTORY_VERSION + 1);
+        assert!(matches!(
+            import(&content, ExportFormat::Json),
+            Err(HistoryError::UnsupportedVersion(v)) if v == HISTORY_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn test_csv_import_reports_bad_rows() {
+        let content = "version,started_at,operation,hostname,manager,subcommand,status,success,duration_secs,packages,message,note\n1,soon,check,,apt,,,true,1,,,\n";
+        let err = import(content, ExportFormat::Csv).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid history data: invalid started_at in CSV row 1"
+        );
+        assert!(import("apt,1\n", ExportFormat::Csv).is_err());
+    }
+
+    #[test]
+    fn test_format_parsing() {
+        assert_eq!("JSON".parse(), Ok(ExportFormat::Json));
+        assert_eq!(
+            ExportFormat::from_path(std::path::Path::new("backup.csv")),
+            Some(ExportFormat::Csv)
+        );
+        assert!("xml".parse::<ExportFormat>().is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use crate::executor::{CommandRequest, Executor};
+use crate::package_manager::UpdateError;
+use crate::step::{shell_quote, Step};
+use crate::warnings;
+use serde::{Deserialize, Serialize};
+
+// A git working tree kept in step with its upstream branch: dotfiles, vim
+// plugin directories, suckless-style source trees
+//
+//   - id: dwm
+//     type: git-repo
+//     path: ~/src/dwm
+//     build: make && sudo make install   # run in `path` after pulling
+//
+// The check fetches and lists the commits the branch is behind its upstream,
+// one line each; the update pulls (fast-forward only) and runs `build`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct GitRepo {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<String>,
+}
+
+// The branch line and changed files of `git status -b --porcelain`
+#[derive(Debug, PartialEq)]
+struct Status {
+    branch: String,
+    upstream: Option<String>,
+    ahead: usize,
+    behind: usize,
+    gone: bool,
+    changes: usize,
+}
+
+// `## main...origin/main [ahead 1, behind 2]`, then a line per changed file
+fn parse_status(output: &str) -> Option<Status> {
+    let mut lines = output.lines();
+    let header = lines.next()?.strip_prefix("## ")?;
+    let header = header.strip_prefix("No commits yet on ").unwrap_or(header);
+    let (refs, counts) = match header.split_once(" [") {
+        Some((refs, counts)) => (refs, counts.trim_end_matches(']')),
+        None => (header, ""),
+    };
+    let (branch, upstream) = match refs.split_once("...") {
+        Some((branch, upstream)) => (branch, Some(upstream.to_string())),
+        None => (refs, None),
+    };
+    let count = |name: &str| {
+        counts
+            .split(", ")
+            .find_map(|part| part.strip_prefix(name)?.parse().ok())
+            .unwrap_or(0)
+    };
+    Some(Status {
+        branch: branch.to_string(),
+        upstream,
+        ahead: count("ahead "),
+        behind: count("behind "),
+        gone: counts == "gone",
+        changes: lines.filter(|line| !line.trim().is_empty()).count(),
+    })
+}
+
+impl GitRepo {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.path.is_empty() {
+            return Err("missing path".to_string());
+        }
+        Ok(())
+    }
+
+    fn git(&self, args: &str) -> String {
+        format!("git -C {} {}", shell_quote(&self.path), args)
+    }
+
+    // Output of a git command that has to succeed
+    fn run(
+        &self,
+        executor: &dyn Executor,
+        env: &[(String, String)],
+        args: &str,
+    ) -> Result<String, UpdateError> {
+        let command = self.git(args);
+        let mut request = CommandRequest::captured(&command);
+        request.env = env.to_vec();
+        let output = executor.run(&request)?;
+        if !output.success() {
+            let reason = match output.stderr.trim() {
+                "" => output.status_description(),
+                stderr => stderr.to_string(),
+            };
+            return Err(UpdateError::new(format!("{} failed: {}", command, reason)));
+        }
+        Ok(output.stdout)
+    }
+
+    // Fetch, then list the commits the branch is behind its upstream
+    pub fn check(
+        &self,
+        executor: &dyn Executor,
+        env: &[(String, String)],
+    ) -> Result<String, UpdateError> {
+        self.run(executor, env, "fetch --quiet")?;
+        let output = self.run(executor, env, "status -b --porcelain")?;
+        let status = parse_status(&output).ok_or_else(|| {
+            UpdateError::new(format!("Unexpected git status output in {}", self.path))
+        })?;
+        let upstream = match &status.upstream {
+            Some(upstream) if !status.gone => upstream,
+            Some(upstream) => {
+                return Err(UpdateError::new(format!(
+                    "The upstream {} of {} in {} is gone",
+                    upstream, status.branch, self.path
+                )))
+            }
+            None => {
+                return Err(UpdateError::new(format!(
+                    "{} in {} has no upstream branch",
+                    status.branch, self.path
+                )))
+            }
+        };
+        if status.ahead > 0 {
+            warnings::warn(format_args!(
+                "{}: {} is {} commits ahead of {}",
+                self.path, status.branch, status.ahead, upstream
+            ));
+        }
+        if status.changes > 0 {
+            warnings::warn(format_args!(
+                "{} has {} uncommitted changes, which may keep it from updating",
+                self.path, status.changes
+            ));
+        }
+        if status.behind == 0 {
+            return Ok(String::new());
+        }
+        self.run(executor, env, "log --oneline 'HEAD..@{u}'")
+    }
+
+    pub fn check_steps(&self) -> Vec<Step> {
+        vec![Step::GitCheck(self.clone())]
+    }
+
+    // Plain shell commands, so the output shows like any other update's
+    pub fn update_steps(&self) -> Vec<Step> {
+        let mut steps = vec![Step::Command(self.git("pull --ff-only"))];
+        if let Some(build) = &self.build {
+            steps.push(Step::Command(format!(
+                "cd {} && {}",
+                shell_quote(&self.path),
+                build
+            )));
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+    use std::sync::Arc;
+
+    fn dotfiles() -> GitRepo {
+        GitRepo {
+            path: "~/.dotfiles".to_string(),
+            build: Some("./install".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_parse_status() {
+        assert_eq!(
+            parse_status("## main...origin/main [ahead 1, behind 12]\n M vimrc\n?? notes\n"),
+            Some(Status {
+                branch: "main".to_string(),
+                upstream: Some("origin/main".to_string()),
+                ahead: 1,
+                behind: 12,
+                gone: false,
+                changes: 2,
+            })
+        );
+        let status = parse_status("## main...origin/main\n").unwrap();
+        assert_eq!((status.ahead, status.behind, status.changes), (0, 0, 0));
+        assert!(parse_status("## wip...origin/wip [gone]\n").unwrap().gone);
+        let status = parse_status("## No commits yet on main...origin/main [gone]\n").unwrap();
+        assert_eq!(status.branch, "main");
+        assert_eq!(
+            parse_status("## HEAD (no branch)\n").unwrap().upstream,
+            None
+        );
+        assert_eq!(parse_status(""), None);
+    }
+
+    #[test]
+    fn test_check() {
+        // Scripted responses are used in turn, the last one repeating
+        let fetch = "git -C ~/'.dotfiles' fetch --quiet";
+        let status = "git -C ~/'.dotfiles' status -b --porcelain";
+        let executor = ScriptedExecutor::new(Arc::new(FakeClock::new()));
+        executor
+            .script(fetch, ScriptedCommand::success())
+            .script(fetch, ScriptedCommand::success())
+            .script(
+                fetch,
+                ScriptedCommand::exit(128).stderr("fatal: unable to access remote\n"),
+            )
+            .script(
+                status,
+                ScriptedCommand::success().stdout("## main...origin/main [behind 2]\n"),
+            )
+            .script(status, ScriptedCommand::success().stdout("## main\n"))
+            .script(
+                "git -C ~/'.dotfiles' log --oneline 'HEAD..@{u}'",
+                ScriptedCommand::success().stdout("abc1234 Add tmux config\ndef5678 Fix zshrc\n"),
+            );
+        assert_eq!(
+            dotfiles().check(&executor, &[]).unwrap(),
+            "abc1234 Add tmux config\ndef5678 Fix zshrc\n"
+        );
+
+        let err = dotfiles().check(&executor, &[]).unwrap_err();
+        assert_eq!(err.message, "main in ~/.dotfiles has no upstream branch");
+
+        let err = dotfiles().check(&executor, &[]).unwrap_err();
+        assert_eq!(
+            err.message,
+            "git -C ~/'.dotfiles' fetch --quiet failed: fatal: unable to access remote"
+        );
+    }
+
+    #[test]
+    fn test_update_steps() {
+        assert_eq!(
+            dotfiles().update_steps(),
+            [
+                "git -C ~/'.dotfiles' pull --ff-only",
+                "cd ~/'.dotfiles' && ./install"
+            ]
+        );
+    }
+}
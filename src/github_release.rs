@@ -0,0 +1,464 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::download::{expand_vars, http_agent, DownloadStep};
+use crate::executor::{Clock, CommandRequest, Executor};
+use crate::http_check::{compare_versions, extract_version, DEFAULT_VERSION_REGEX};
+use crate::package_manager::UpdateError;
+use crate::selector::glob_match;
+use crate::step::{expand_home, shell_quote};
+
+const GITHUB_API: &str = "https://api.github.com";
+
+// A single-binary tool installed from the assets of its GitHub releases
+//
+//   - id: lazygit
+//     type: github-release
+//     repo: jesseduffield/lazygit
+//     asset_pattern: "lazygit_*_Linux_x86_64.tar.gz"
+//     install_dir: ~/.local/bin
+//     version_command: lazygit --version
+//     binary: lazygit          # file to install from an archive, the repo name by default
+//
+// GITHUB_TOKEN (from `env` or updog's environment) is sent when set, to
+// avoid the API's low anonymous rate limit.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct GithubRelease {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub repo: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub asset_pattern: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub install_dir: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub version_command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binary: Option<String>,
+    // API base for GitHub Enterprise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl GithubRelease {
+    pub fn validate(&self) -> Result<(), String> {
+        let missing: Vec<&str> = [
+            ("repo", &self.repo),
+            ("asset_pattern", &self.asset_pattern),
+            ("install_dir", &self.install_dir),
+            ("version_command", &self.version_command),
+        ]
+        .into_iter()
+        .filter(|(_, value)| value.trim().is_empty())
+        .map(|(name, _)| name)
+        .collect();
+        if !missing.is_empty() {
+            return Err(format!("missing {}", missing.join(", ")));
+        }
+        match self.repo.split_once('/') {
+            Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/') => {
+                Ok(())
+            }
+            _ => Err(format!("repo must be owner/name, not '{}'", self.repo)),
+        }
+    }
+
+    fn binary(&self) -> &str {
+        self.binary
+            .as_deref()
+            .unwrap_or_else(|| self.repo.rsplit('/').next().unwrap_or_default())
+    }
+
+    fn fetch_latest(&self, env: &[(String, String)]) -> Result<Release, UpdateError> {
+        let url = format!(
+            "{}/repos/{}/releases/latest",
+            self.api_url
+                .as_deref()
+                .unwrap_or(GITHUB_API)
+                .trim_end_matches('/'),
+            self.repo
+        );
+        let mut request = http_agent(None, env, &url)?
+            .get(&url)
+            .set("Accept", "application/vnd.github+json");
+        let token = expand_vars("${GITHUB_TOKEN}", env);
+        if !token.is_empty() {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+        let body = request
+            .call()
+            .map_err(|e| UpdateError::new(format!("Failed to fetch {}: {}", url, e)))?
+            .into_string()
+            .map_err(|e| UpdateError::new(format!("Failed to read {}: {}", url, e)))?;
+        serde_json::from_str(&body)
+            .map_err(|e| UpdateError::new(format!("Invalid release data from {}: {}", url, e)))
+    }
+
+    // The installed version, or None when the tool isn't installed (yet)
+    fn installed_version(
+        &self,
+        executor: &dyn Executor,
+        env: &[(String, String)],
+    ) -> Result<Option<String>, UpdateError> {
+        let mut request = CommandRequest::captured(&self.version_command);
+        request.env = env.to_vec();
+        let output = executor.run(&request)?;
+        if !output.success() {
+            return Ok(None);
+        }
+        let regex = regex::Regex::new(DEFAULT_VERSION_REGEX).expect("valid regex");
+        extract_version(&regex, &format!("{}\n{}", output.stdout, output.stderr))
+            .map(Some)
+            .ok_or_else(|| {
+                UpdateError::new(format!(
+                    "No version found in the output of {}",
+                    self.version_command
+                ))
+            })
+    }
+
+    // Latest release version, if it's newer than the installed one
+    fn newer_release(
+        &self,
+        executor: &dyn Executor,
+        env: &[(String, String)],
+    ) -> Result<(Release, Option<String>, Option<String>), UpdateError> {
+        let release = self.fetch_latest(env)?;
+        let latest = release.tag_name.trim_start_matches(['v', 'V']).to_string();
+        let installed = self.installed_version(executor, env)?;
+        let newer = match &installed {
+            Some(installed) if compare_versions(&latest, installed).is_le() => None,
+            _ => Some(latest),
+        };
+        Ok((release, installed, newer))
+    }
+
+    // One line when a newer release is available, like `check_http`
+    pub fn check(
+        &self,
+        executor: &dyn Executor,
+        env: &[(String, String)],
+    ) -> Result<String, UpdateError> {
+        let (_, installed, newer) = self.newer_release(executor, env)?;
+        Ok(match newer {
+            Some(latest) => format!(
+                "{} {} -> {}\n",
+                self.binary(),
+                installed.as_deref().unwrap_or("(not installed)"),
+                latest
+            ),
+            None => String::new(),
+        })
+    }
+
+    // Download the matching asset of the latest release and install the
+    // binary, unless the installed version is already current
+    pub fn install(
+        &self,
+        executor: &dyn Executor,
+        env: &[(String, String)],
+        clock: &dyn Clock,
+        cancelled: &dyn Fn() -> bool,
+    ) -> Result<String, UpdateError> {
+        let (release, _, newer) = self.newer_release(executor, env)?;
+        let Some(latest) = newer else {
+            info!("{} is up to date", self.binary());
+            return Ok(String::new());
+        };
+        let asset = release
+            .assets
+            .iter()
+            .find(|asset| glob_match(&self.asset_pattern, &asset.name))
+            .ok_or_else(|| {
+                let names: Vec<&str> = release.assets.iter().map(|a| a.name.as_str()).collect();
+                UpdateError::new(format!(
+                    "No asset of {} {} matches {} (assets: {})",
+                    self.repo,
+                    release.tag_name,
+                    self.asset_pattern,
+                    names.join(", ")
+                ))
+            })?;
+
+        let work_dir = std::env::temp_dir().join(format!(
+            "updog-{}-{}",
+            self.repo.replace('/', "-"),
+            std::process::id()
+        ));
+        let result = self.install_asset(asset, &work_dir, executor, env, clock, cancelled);
+        let _ = fs::remove_dir_all(&work_dir);
+        let target = result?;
+        Ok(format!(
+            "Installed {} {} to {}\n",
+            self.binary(),
+            latest,
+            target.display()
+        ))
+    }
+
+    fn install_asset(
+        &self,
+        asset: &Asset,
+        work_dir: &Path,
+        executor: &dyn Executor,
+        env: &[(String, String)],
+        clock: &dyn Clock,
+        cancelled: &dyn Fn() -> bool,
+    ) -> Result<PathBuf, UpdateError> {
+        let download = DownloadStep {
+            url: asset.browser_download_url.clone(),
+            path: work_dir.join(&asset.name).display().to_string(),
+            retries: 3,
+            proxy: None,
+        };
+        let archive = download.run(env, clock, cancelled)?;
+
+        let binary = match extract_command(&asset.name, &archive, work_dir) {
+            Some(command) => {
+                let output = executor.run(&CommandRequest::captured(&command))?;
+                if !output.success() {
+                    return Err(UpdateError::new(format!(
+                        "Failed to extract {}: {}",
+                        asset.name,
+                        output.stderr.trim()
+                    )));
+                }
+                find_file(work_dir, self.binary()).ok_or_else(|| {
+                    UpdateError::new(format!("{} not found in {}", self.binary(), asset.name))
+                })?
+            }
+            None => archive,
+        };
+
+        let install_dir = expand_home(&self.install_dir);
+        let target = install_dir.join(self.binary());
+        let io_error = |e: std::io::Error| {
+            UpdateError::new(format!("Failed to install {}: {}", target.display(), e))
+        };
+        fs::create_dir_all(&install_dir).map_err(io_error)?;
+        // Replace the binary in one step, even while it's running
+        let staged = install_dir.join(format!(".{}.updog-tmp", self.binary()));
+        fs::copy(&binary, &staged).map_err(io_error)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&staged, fs::Permissions::from_mode(0o755)).map_err(io_error)?;
+        }
+        fs::rename(&staged, &target).map_err(io_error)?;
+        Ok(target)
+    }
+}
+
+// Shell command unpacking an archive asset into `dir`, or None for a bare binary
+fn extract_command(name: &str, archive: &Path, dir: &Path) -> Option<String> {
+    let archive = shell_quote(&archive.display().to_string());
+    let dir = shell_quote(&dir.display().to_string());
+    const TARBALLS: &[&str] = &[
+        ".tar", ".tar.gz", ".tgz", ".tar.xz", ".txz", ".tar.bz2", ".tbz", ".tar.zst",
+    ];
+    if TARBALLS.iter().any(|suffix| name.ends_with(suffix)) {
+        Some(format!("tar -xf {} -C {}", archive, dir))
+    } else if name.ends_with(".zip") {
+        Some(format!("unzip -o -q {} -d {}", archive, dir))
+    } else {
+        None
+    }
+}
+
+// Depth-first search for a file named `name` (or `name`.exe)
+fn find_file(dir: &Path, name: &str) -> Option<PathBuf> {
+    let mut entries: Vec<_> = fs::read_dir(dir).ok()?.flatten().collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in &entries {
+        let path = entry.path();
+        let file_name = entry.file_name();
+        if path.is_file() && (file_name == name || file_name == format!("{}.exe", name).as_str()) {
+            return Some(path);
+        }
+    }
+    entries
+        .iter()
+        .filter(|entry| entry.path().is_dir())
+        .find_map(|entry| find_file(&entry.path(), name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::thread;
+    use tempfile::tempdir;
+
+    const SCRIPT: &str = "#!/bin/sh\necho k9s\n";
+
+    // Serve `count` requests for a k9s release with a bare binary asset
+    fn serve(count: usize) -> (String, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let release = format!(
+            r#"{{"tag_name": "v0.44.1", "assets": [
+                {{"name": "k9s_Darwin_arm64", "browser_download_url": "{base}/dl/darwin"}},
+                {{"name": "k9s_Linux_amd64", "browser_download_url": "{base}/dl/linux"}}
+            ]}}"#
+        );
+        let handle = thread::spawn(move || {
+            for _ in 0..count {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                }
+                let (status, body) = match request_line.split_whitespace().nth(1) {
+                    Some("/repos/derailed/k9s/releases/latest") => ("200 OK", release.as_str()),
+                    Some("/dl/linux") => ("200 OK", SCRIPT),
+                    _ => ("404 Not Found", ""),
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        (base, handle)
+    }
+
+    fn k9s(base: &str, install_dir: &Path) -> GithubRelease {
+        GithubRelease {
+            repo: "derailed/k9s".to_string(),
+            asset_pattern: "k9s_Linux_*".to_string(),
+            install_dir: install_dir.display().to_string(),
+            version_command: "k9s version --short".to_string(),
+            binary: None,
+            api_url: Some(base.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_check_and_install() {
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(FakeClock::new());
+        let executor = ScriptedExecutor::new(clock.clone());
+        executor.script(
+            "k9s version --short",
+            ScriptedCommand::success().stdout("Version v0.40.10\n"),
+        );
+        let (base, server) = serve(3);
+        let release = k9s(&base, dir.path());
+
+        assert_eq!(
+            release.check(&executor, &[]).unwrap(),
+            "k9s 0.40.10 -> 0.44.1\n"
+        );
+        let output = release
+            .install(&executor, &[], clock.as_ref(), &|| false)
+            .unwrap();
+        assert!(output.starts_with("Installed k9s 0.44.1"));
+        server.join().unwrap();
+
+        let installed = dir.path().join("k9s");
+        assert_eq!(fs::read_to_string(&installed).unwrap(), SCRIPT);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&installed).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+    }
+
+    #[test]
+    fn test_up_to_date_installs_nothing() {
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(FakeClock::new());
+        let executor = ScriptedExecutor::new(clock.clone());
+        executor.script(
+            "k9s version --short",
+            ScriptedCommand::success().stdout("v0.44.1\n"),
+        );
+        let (base, server) = serve(2);
+        let release = k9s(&base, dir.path());
+
+        assert_eq!(release.check(&executor, &[]).unwrap(), "");
+        assert_eq!(
+            release
+                .install(&executor, &[], clock.as_ref(), &|| false)
+                .unwrap(),
+            ""
+        );
+        server.join().unwrap();
+        assert!(!dir.path().join("k9s").exists());
+    }
+
+    #[test]
+    fn test_not_installed_and_no_matching_asset() {
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(FakeClock::new());
+        let executor = ScriptedExecutor::new(clock.clone());
+        executor.script("k9s version --short", ScriptedCommand::exit(127));
+        let (base, server) = serve(2);
+        let mut release = k9s(&base, dir.path());
+
+        assert_eq!(
+            release.check(&executor, &[]).unwrap(),
+            "k9s (not installed) -> 0.44.1\n"
+        );
+        release.asset_pattern = "k9s_Windows_*".to_string();
+        let err = release
+            .install(&executor, &[], clock.as_ref(), &|| false)
+            .unwrap_err();
+        assert!(err.message.contains("k9s_Darwin_arm64, k9s_Linux_amd64"));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_validate_and_helpers() {
+        let mut release = GithubRelease {
+            repo: "jesseduffield/lazygit".to_string(),
+            asset_pattern: "lazygit_*_Linux_x86_64.tar.gz".to_string(),
+            install_dir: "~/.local/bin".to_string(),
+            version_command: "lazygit --version".to_string(),
+            ..Default::default()
+        };
+        assert!(release.validate().is_ok());
+        assert_eq!(release.binary(), "lazygit");
+        release.repo = "lazygit".to_string();
+        assert!(release.validate().is_err());
+        assert_eq!(
+            GithubRelease::default().validate().unwrap_err(),
+            "missing repo, asset_pattern, install_dir, version_command"
+        );
+
+        let dir = Path::new("/tmp/x");
+        assert_eq!(
+            extract_command("a.tar.gz", &dir.join("a.tar.gz"), dir).unwrap(),
+            "tar -xf '/tmp/x/a.tar.gz' -C '/tmp/x'"
+        );
+        assert!(extract_command("k9s_Linux_amd64", dir, dir).is_none());
+    }
+}
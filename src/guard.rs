@@ -0,0 +1,149 @@
+use std::io;
+use tracing::warn;
+
+use crate::config::Config;
+use crate::disk::FreeSpace;
+use crate::power::{self, PowerStatus};
+
+type PowerSource = Box<dyn Fn() -> io::Result<Option<PowerStatus>> + Send + Sync>;
+
+// Conditions an update run rechecks before starting each manager, so that a
+// run started on AC power doesn't drain the battery (or fill the disk) one
+// manager at a time. Once a condition fails, the run starts no more managers:
+//
+//   min_battery: 20   # percent; only while running on battery
+//
+// The global `min_free_space` stops the run too, when the filesystem fills up
+// after the run started. If it was already too full at the start, managers
+// are skipped one by one instead, so that their own `min_free_space` still
+// applies.
+pub struct RunGuard {
+    min_battery: Option<u8>,
+    free_space: Option<FreeSpace>,
+    power: PowerSource,
+    stopped: Option<String>,
+}
+
+impl RunGuard {
+    pub fn new(config: &Config) -> Self {
+        Self::with_power(config, power::status)
+    }
+
+    pub fn with_power(
+        config: &Config,
+        power: impl Fn() -> io::Result<Option<PowerStatus>> + Send + Sync + 'static,
+    ) -> Self {
+        // Only a threshold that is met now can be crossed
+        let free_space = config
+            .min_free_space
+            .clone()
+            .filter(|required| matches!(required.shortfall(), Ok(None)));
+        Self {
+            min_battery: config.min_battery,
+            free_space,
+            power: Box::new(power),
+            stopped: None,
+        }
+    }
+
+    // Why the next manager shouldn't start, if it shouldn't. A stopped run
+    // stays stopped, even if the charger is plugged back in.
+    pub fn check(&mut self) -> Option<String> {
+        if self.stopped.is_none() {
+            self.stopped = self.battery_low().or_else(|| self.disk_full());
+            if let Some(reason) = &self.stopped {
+                warn!("Stopping the run: {}", reason);
+            }
+        }
+        self.stopped.clone()
+    }
+
+    fn battery_low(&self) -> Option<String> {
+        let min = self.min_battery?;
+        match (self.power)() {
+            Ok(Some(status)) if status.on_battery && status.charge < min => Some(format!(
+                "Battery at {}%, below min_battery {}%",
+                status.charge, min
+            )),
+            Ok(_) => None,
+            // Not knowing shouldn't stop the run
+            Err(e) => {
+                warn!("Could not read the battery status: {}", e);
+                None
+            }
+        }
+    }
+
+    fn disk_full(&self) -> Option<String> {
+        self.free_space.as_ref()?.shortfall().ok().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::ByteSize;
+    use std::sync::Mutex;
+
+    fn config(yaml: &str) -> Config {
+        serde_yaml::from_str(&format!("commands: []\n{}", yaml)).unwrap()
+    }
+
+    // A power source reporting `readings` in turn, then the last one
+    fn readings(readings: &[(bool, u8)]) -> impl Fn() -> io::Result<Option<PowerStatus>> {
+        let readings = Mutex::new(readings.to_vec());
+        move || {
+            let mut readings = readings.lock().unwrap();
+            let (on_battery, charge) = if readings.len() > 1 {
+                readings.remove(0)
+            } else {
+                readings[0]
+            };
+            Ok(Some(PowerStatus { on_battery, charge }))
+        }
+    }
+
+    #[test]
+    fn test_battery_stops_the_run() {
+        let config = config("min_battery: 20");
+        let mut guard = RunGuard::with_power(
+            &config,
+            readings(&[(false, 10), (true, 25), (true, 19), (false, 80)]),
+        );
+        // Charging at 10% is fine, and so is 25% on battery
+        assert_eq!(guard.check(), None);
+        assert_eq!(guard.check(), None);
+        let reason = Some("Battery at 19%, below min_battery 20%".to_string());
+        assert_eq!(guard.check(), reason);
+        // Plugging the charger back in doesn't restart the run
+        assert_eq!(guard.check(), reason);
+    }
+
+    #[test]
+    fn test_without_thresholds() {
+        let mut guard = RunGuard::with_power(&config(""), || panic!("battery read"));
+        assert_eq!(guard.check(), None);
+
+        let mut guard = RunGuard::with_power(&config("min_battery: 20"), || {
+            Err(io::Error::other("no battery"))
+        });
+        assert_eq!(guard.check(), None);
+    }
+
+    #[test]
+    fn test_free_space_only_when_met_at_start() {
+        let mut config = config("");
+        config.min_free_space = Some(FreeSpace {
+            size: ByteSize(1 << 60),
+            path: "/".to_string(),
+        });
+        // Already too full: managers are skipped one by one instead
+        let guard = RunGuard::with_power(&config, || Ok(None));
+        assert!(guard.free_space.is_none());
+
+        config.min_free_space.as_mut().unwrap().size = ByteSize(1);
+        let mut guard = RunGuard::with_power(&config, || Ok(None));
+        assert!(guard.free_space.is_some());
+        assert_eq!(guard.check(), None);
+    }
+}
@@ -0,0 +1,122 @@
+use serde::Deserialize;
+use std::cmp::Ordering;
+use tracing::info;
+
+use crate::http_check::compare_versions;
+
+// A release of `helm list --all-namespaces -o json`
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub name: String,
+    pub namespace: String,
+    // `<chart name>-<chart version>`
+    pub chart: String,
+    #[serde(default)]
+    pub app_version: String,
+}
+
+// A chart of `helm search repo -o json`, newest version per repo
+#[derive(Debug, Deserialize)]
+pub struct Chart {
+    // `<repo>/<chart name>`
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub app_version: String,
+}
+
+// Split `ingress-nginx-4.8.3` at the dash before the version; chart names
+// have dashes too, versions start with a digit (or `v` and a digit)
+fn split_chart(chart: &str) -> Option<(&str, &str)> {
+    chart.match_indices('-').find_map(|(index, _)| {
+        let version = &chart[index + 1..];
+        let digits = version.strip_prefix('v').unwrap_or(version);
+        digits
+            .starts_with(|c: char| c.is_ascii_digit())
+            .then(|| (&chart[..index], version))
+    })
+}
+
+// `<namespace>/<release> <chart> <installed> -> <newest>` per release whose
+// chart has a newer version in the added repos. Releases from OCI registries
+// or local charts aren't in any repo and are left out. When several repos
+// have the chart, the newest version counts.
+pub fn outdated(releases: &str, charts: &str) -> Result<String, serde_json::Error> {
+    let releases: Vec<Release> = serde_json::from_str(releases)?;
+    let charts: Vec<Chart> = serde_json::from_str(charts)?;
+    let mut out = String::new();
+    for release in &releases {
+        let Some((chart, installed)) = split_chart(&release.chart) else {
+            info!("Can't tell the chart version of {}", release.chart);
+            continue;
+        };
+        let newest = charts
+            .iter()
+            .filter(|c| c.name.rsplit('/').next() == Some(chart))
+            .max_by(|a, b| compare_versions(&a.version, &b.version));
+        let Some(newest) = newest else {
+            info!(
+                "Chart {} of {} isn't in any added repo, not checking it",
+                chart, release.name
+            );
+            continue;
+        };
+        if compare_versions(&newest.version, installed) != Ordering::Greater {
+            continue;
+        }
+        out.push_str(&format!(
+            "{}/{} {} {} -> {}",
+            release.namespace, release.name, chart, installed, newest.version
+        ));
+        if !newest.app_version.is_empty() && newest.app_version != release.app_version {
+            out.push_str(&format!(
+                " (app {} -> {})",
+                release.app_version, newest.app_version
+            ));
+        }
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_chart() {
+        assert_eq!(
+            split_chart("ingress-nginx-4.8.3"),
+            Some(("ingress-nginx", "4.8.3"))
+        );
+        assert_eq!(
+            split_chart("cert-manager-v1.13.2"),
+            Some(("cert-manager", "v1.13.2"))
+        );
+        assert_eq!(
+            split_chart("k8s-at-home-1.0.0-rc.1"),
+            Some(("k8s-at-home", "1.0.0-rc.1"))
+        );
+        assert_eq!(split_chart("nochart"), None);
+    }
+
+    #[test]
+    fn test_outdated() {
+        let releases = r#"[
+            {"name":"traefik","namespace":"kube-system","revision":"3","status":"deployed","chart":"traefik-25.0.0","app_version":"v2.10.5"},
+            {"name":"cache","namespace":"apps","revision":"1","status":"deployed","chart":"redis-18.6.1","app_version":"7.2.3"},
+            {"name":"blog","namespace":"apps","revision":"7","status":"deployed","chart":"ghost-0.1.0","app_version":""}
+        ]"#;
+        let charts = r#"[
+            {"name":"traefik/traefik","version":"26.1.0","app_version":"v2.11.0","description":"A Traefik based Kubernetes ingress controller"},
+            {"name":"old-mirror/traefik","version":"24.0.0","app_version":"v2.10.4","description":""},
+            {"name":"bitnami/redis","version":"18.6.1","app_version":"7.2.3","description":""}
+        ]"#;
+        assert_eq!(
+            outdated(releases, charts).unwrap(),
+            "kube-system/traefik traefik 25.0.0 -> 26.1.0 (app v2.10.5 -> v2.11.0)\n"
+        );
+        assert_eq!(outdated("[]", "[]").unwrap(), "");
+        assert!(outdated("not json", "[]").is_err());
+    }
+}
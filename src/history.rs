@@ -0,0 +1,553 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::metadata::RunMetadata;
+use crate::state_file::{write_atomic, StateLock};
+use crate::timezone::Timezone;
+
+// Version of the run record schema. Bump when a change would make older
+// versions of updog misread new records; records without a version predate
+// versioning and count as version 0.
+pub const HISTORY_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum HistoryError {
+    #[error("Failed to access history file: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Failed to parse history file: {0}")]
+    ParseError(#[from] serde_yaml::Error),
+    #[error("History schema version {0} is newer than this updog supports ({HISTORY_VERSION})")]
+    UnsupportedVersion(u32),
+    #[error("Invalid history data: {0}")]
+    InvalidData(String),
+}
+
+// Retention of the history store, applied by `updog history prune` and
+// after every daemon run
+//
+//   history:
+//     keep_runs: 500   # keep only the most recent runs
+//     keep_days: 90    # drop runs that started longer ago
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct HistoryConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_runs: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_days: Option<u64>,
+}
+
+impl HistoryConfig {
+    // Whether history grows without bounds
+    pub fn is_unlimited(&self) -> bool {
+        self.keep_runs.is_none() && self.keep_days.is_none()
+    }
+
+    // Split chronologically ordered runs into the ones to keep and the
+    // number to drop
+    pub fn retain(&self, mut runs: Vec<RunRecord>, now: SystemTime) -> (Vec<RunRecord>, usize) {
+        let before = runs.len();
+        if let Some(days) = self.keep_days {
+            let cutoff = unix_seconds(now).saturating_sub(days * 24 * 60 * 60);
+            runs.retain(|run| run.started_at >= cutoff);
+        }
+        if let Some(keep) = self.keep_runs {
+            let excess = runs.len().saturating_sub(keep);
+            runs.drain(..excess);
+        }
+        let dropped = before - runs.len();
+        (runs, dropped)
+    }
+}
+
+// Result of a single manager within a recorded run
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ManagerRecord {
+    pub manager: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subcommand: Option<String>,
+    pub success: bool,
+    // Detailed status label (success, skipped, cancelled, ...)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    // Wall clock duration in seconds
+    pub duration_secs: f64,
+    // Number of packages reported by the manager, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub packages: Option<usize>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub message: String,
+    // Per-step outcome of multi-step command sequences
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<StepRecord>,
+    // Captured output: what the commands printed, or the pending updates or
+    // upgraded packages a parser listed
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub output: String,
+    // Warnings raised while the manager ran
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    // Values of the manager's `summary_columns`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub columns: BTreeMap<String, String>,
+}
+
+// One step of a recorded command sequence
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StepRecord {
+    pub step: String,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    pub duration_secs: f64,
+}
+
+impl ManagerRecord {
+    // Whether a command actually ran (skipped and cancelled entries didn't)
+    pub fn was_executed(&self) -> bool {
+        !matches!(self.status.as_deref(), Some("skipped") | Some("cancelled"))
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.duration_secs.max(0.0))
+    }
+}
+
+// A single check/update invocation
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RunRecord {
+    #[serde(default)]
+    pub version: u32,
+    // UUID of the run (none for runs recorded before there were ids)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    // Seconds since the Unix epoch
+    pub started_at: u64,
+    pub operation: String,
+    pub results: Vec<ManagerRecord>,
+    // Host, user and config the run was produced with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<RunMetadata>,
+    // Free-form annotation given with --note
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    // Warnings about the run as a whole (config, selection)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl RunRecord {
+    pub fn new(operation: &str, started_at: SystemTime) -> Self {
+        Self {
+            version: HISTORY_VERSION,
+            run_id: None,
+            started_at: unix_seconds(started_at),
+            operation: operation.to_string(),
+            results: Vec::new(),
+            metadata: None,
+            note: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn hostname(&self) -> Option<&str> {
+        self.metadata.as_ref()?.hostname.as_deref()
+    }
+
+    // Whether both records describe the same run (e.g. an import of a run
+    // that is already in the store)
+    pub fn same_run(&self, other: &RunRecord) -> bool {
+        if let (Some(id), Some(other)) = (&self.run_id, &other.run_id) {
+            return id == other;
+        }
+        self.started_at == other.started_at
+            && self.operation == other.operation
+            && self.hostname() == other.hostname()
+    }
+}
+
+pub fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Append-only history store: one YAML document per run
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    // Default location: <data dir>/updog/history.yaml
+    pub fn default_path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("updog")
+            .join("history.yaml")
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, run: &RunRecord) -> Result<(), HistoryError> {
+        let document = format!("---\n{}", serde_yaml::to_string(run)?);
+        let _lock = StateLock::exclusive(&self.path)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(document.as_bytes())?;
+        Ok(())
+    }
+
+    // Rewrite the store with exactly `runs`
+    pub fn replace(&self, runs: &[RunRecord]) -> Result<(), HistoryError> {
+        let _lock = StateLock::exclusive(&self.path)?;
+        self.write_runs(runs)
+    }
+
+    // Rewrite the store; the caller holds the lock
+    fn write_runs(&self, runs: &[RunRecord]) -> Result<(), HistoryError> {
+        let mut content = String::new();
+        for run in runs {
+            content.push_str("---\n");
+            content.push_str(&serde_yaml::to_string(run)?);
+        }
+        write_atomic(&self.path, content.as_bytes())?;
+        Ok(())
+    }
+
+    // Add runs from elsewhere, skipping ones already present, and keep the
+    // store ordered by start time. Returns the number of runs added.
+    pub fn merge(&self, runs: Vec<RunRecord>) -> Result<usize, HistoryError> {
+        let _lock = StateLock::exclusive(&self.path)?;
+        let mut existing = self.read_runs()?;
+        let before = existing.len();
+        for run in runs {
+            check_version(&run)?;
+            if !existing.iter().any(|r| r.same_run(&run)) {
+                existing.push(run);
+            }
+        }
+        let added = existing.len() - before;
+        if added > 0 {
+            existing.sort_by_key(|run| run.started_at);
+            self.write_runs(&existing)?;
+        }
+        Ok(added)
+    }
+
+    // Drop runs outside the retention policy. Returns the number of runs removed.
+    pub fn prune(&self, retention: &HistoryConfig, now: SystemTime) -> Result<usize, HistoryError> {
+        if retention.is_unlimited() {
+            return Ok(0);
+        }
+        let _lock = StateLock::exclusive(&self.path)?;
+        let (kept, dropped) = retention.retain(self.read_runs()?, now);
+        if dropped > 0 {
+            self.write_runs(&kept)?;
+        }
+        Ok(dropped)
+    }
+
+    // Load all runs, oldest first. A missing file is an empty history.
+    pub fn load(&self) -> Result<Vec<RunRecord>, HistoryError> {
+        // Waits for an append in progress, which writes the file in place.
+        // Best effort, so that a store in a read-only directory can be read.
+        let _lock = StateLock::shared(&self.path).ok();
+        self.read_runs()
+    }
+
+    fn read_runs(&self) -> Result<Vec<RunRecord>, HistoryError> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut runs = Vec::new();
+        for document in serde_yaml::Deserializer::from_str(&content) {
+            let run = RunRecord::deserialize(document)?;
+            check_version(&run)?;
+            runs.push(run);
+        }
+        Ok(runs)
+    }
+}
+
+// Render runs as one line each, newest first, with times in `timezone`
+pub fn render_runs(runs: &[RunRecord], timezone: Timezone) -> String {
+    if runs.is_empty() {
+        return "No history recorded yet.\n".to_string();
+    }
+
+    let mut out = String::new();
+    for run in runs.iter().rev() {
+        let time = UNIX_EPOCH + Duration::from_secs(run.started_at);
+        let failed = run.results.iter().filter(|r| !r.success).count();
+        let mut line = format!(
+            "{}  {:<6}  {} managers, {} failed",
+            timezone.civil(time),
+            run.operation,
+            run.results.len(),
+            failed
+        );
+        let upgraded: usize = match run.operation.as_str() {
+            "update" => run.results.iter().filter_map(|r| r.packages).sum(),
+            _ => 0,
+        };
+        if upgraded > 0 {
+            line.push_str(&format!(", {} packages upgraded", upgraded));
+        }
+        if let Some(hostname) = run.hostname() {
+            line.push_str(&format!("  on {}", hostname));
+        }
+        if let Some(id) = &run.run_id {
+            line.push_str(&format!("  [{}]", &id[..id.len().min(8)]));
+        }
+        if let Some(note) = &run.note {
+            line.push_str(&format!("  # {}", note));
+        }
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+// The run whose id is or starts with `id` (as listed by `render_runs`), for
+// `updog history show`
+pub fn find_run<'a>(runs: &'a [RunRecord], id: &str) -> Result<&'a RunRecord, String> {
+    let id = id.trim().to_ascii_lowercase();
+    let matching: Vec<&RunRecord> = runs
+        .iter()
+        .filter(|run| !id.is_empty() && run.run_id.as_ref().is_some_and(|r| r.starts_with(&id)))
+        .collect();
+    match matching[..] {
+        [run] => Ok(run),
+        [] => Err(format!("No recorded run has id {}", id)),
+        _ => Err(format!(
+            "Run id {} is ambiguous ({} runs start with it)",
+            id,
+            matching.len()
+        )),
+    }
+}
+
+pub fn check_version(run: &RunRecord) -> Result<(), HistoryError> {
+    if run.version > HISTORY_VERSION {
+        return Err(HistoryError::UnsupportedVersion(run.version));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn record(manager: &str, success: bool, secs: f64) -> ManagerRecord {
+        ManagerRecord {
+            manager: manager.to_string(),
+            subcommand: None,
+            success,
+            status: None,
+            duration_secs: secs,
+            packages: None,
+            message: String::new(),
+            steps: Vec::new(),
+            output: String::new(),
+            warnings: Vec::new(),
+            columns: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_missing_history_is_empty() {
+        let dir = tempdir().unwrap();
+        let history = History::new(dir.path().join("history.yaml"));
+        assert!(history.load().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_append_and_load_round_trip() {
+        let dir = tempdir().unwrap();
+        let history = History::new(dir.path().join("nested").join("history.yaml"));
+
+        let mut first = RunRecord::new("check", UNIX_EPOCH + Duration::from_secs(100));
+        first.results.push(record("brew", true, 1.5));
+        first.metadata = Some(RunMetadata {
+            hostname: Some("laptop".to_string()),
+            os: "macos-aarch64".to_string(),
+            config_hash: Some("abc123".to_string()),
+            invocation: vec!["updog".to_string(), "check".to_string()],
+            ..Default::default()
+        });
+        let mut second = RunRecord::new("update", UNIX_EPOCH + Duration::from_secs(200));
+        second.results.push(record("npm", false, 3.0));
+
+        history.append(&first).unwrap();
+        history.append(&second).unwrap();
+
+        let runs = history.load().unwrap();
+        assert_eq!(runs, vec![first, second]);
+        assert_eq!(runs[0].results[0].duration(), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn test_concurrent_appends_are_kept() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("history.yaml");
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let history = History::new(&path);
+                std::thread::spawn(move || {
+                    let mut run = RunRecord::new("check", UNIX_EPOCH + Duration::from_secs(i));
+                    run.results = (0..50).map(|_| record("brew", true, 1.0)).collect();
+                    history.append(&run).unwrap();
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let mut runs = History::new(&path).load().unwrap();
+        runs.sort_by_key(|run| run.started_at);
+        let started: Vec<u64> = runs.iter().map(|run| run.started_at).collect();
+        assert_eq!(started, (0..8).collect::<Vec<_>>());
+        assert!(runs.iter().all(|run| run.results.len() == 50));
+    }
+
+    #[test]
+    fn test_newer_schema_version_is_rejected() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("history.yaml");
+        fs::write(
+            &path,
+            "---\nstarted_at: 1\noperation: check\nresults: []\n---\nversion: 99\nstarted_at: 2\noperation: check\nresults: []\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            History::new(&path).load(),
+            Err(HistoryError::UnsupportedVersion(99))
+        ));
+    }
+
+    #[test]
+    fn test_merge_skips_duplicates_and_sorts() {
+        let dir = tempdir().unwrap();
+        let history = History::new(dir.path().join("history.yaml"));
+        let run = |secs| RunRecord::new("check", UNIX_EPOCH + Duration::from_secs(secs));
+        history.append(&run(300)).unwrap();
+
+        let added = history.merge(vec![run(100), run(300), run(200)]).unwrap();
+        assert_eq!(added, 2);
+        let started: Vec<u64> = history
+            .load()
+            .unwrap()
+            .iter()
+            .map(|r| r.started_at)
+            .collect();
+        assert_eq!(started, vec![100, 200, 300]);
+        assert_eq!(history.merge(vec![run(100)]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_runs_with_ids() {
+        let run = |secs, id: &str| {
+            let mut run = RunRecord::new("check", UNIX_EPOCH + Duration::from_secs(secs));
+            run.run_id = Some(id.to_string());
+            run
+        };
+        let runs = vec![
+            run(100, "3f2a9c1e-8b4d-4e6f-a1b2-c3d4e5f60718"),
+            run(100, "3f7b0d2a-1c4e-4a5b-9d8c-7e6f5a4b3c2d"),
+        ];
+        // Same second, but not the same run
+        assert!(!runs[0].same_run(&runs[1]));
+        assert!(runs[0].same_run(&runs[0].clone()));
+
+        assert_eq!(find_run(&runs, "3F7B").unwrap(), &runs[1]);
+        assert_eq!(
+            find_run(&runs, "3f").unwrap_err(),
+            "Run id 3f is ambiguous (2 runs start with it)"
+        );
+        assert_eq!(
+            find_run(&runs, "abc").unwrap_err(),
+            "No recorded run has id abc"
+        );
+        assert!(find_run(&runs, "").is_err());
+    }
+
+    #[test]
+    fn test_prune_by_count_and_age() {
+        let dir = tempdir().unwrap();
+        let history = History::new(dir.path().join("history.yaml"));
+        let day = 24 * 60 * 60;
+        for days in 0..5 {
+            history
+                .append(&RunRecord::new(
+                    "check",
+                    UNIX_EPOCH + Duration::from_secs(days * day),
+                ))
+                .unwrap();
+        }
+        let now = UNIX_EPOCH + Duration::from_secs(5 * day);
+        let started = |history: &History| -> Vec<u64> {
+            history
+                .load()
+                .unwrap()
+                .iter()
+                .map(|r| r.started_at / day)
+                .collect()
+        };
+
+        assert_eq!(history.prune(&HistoryConfig::default(), now).unwrap(), 0);
+
+        let by_age = HistoryConfig {
+            keep_runs: None,
+            keep_days: Some(3),
+        };
+        assert_eq!(history.prune(&by_age, now).unwrap(), 2);
+        assert_eq!(started(&history), vec![2, 3, 4]);
+
+        let by_count = HistoryConfig {
+            keep_runs: Some(1),
+            keep_days: Some(3),
+        };
+        assert_eq!(history.prune(&by_count, now).unwrap(), 2);
+        assert_eq!(started(&history), vec![4]);
+        assert_eq!(history.prune(&by_count, now).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_render_runs_shows_notes_newest_first() {
+        let mut first = RunRecord::new("update", UNIX_EPOCH + Duration::from_secs(3600));
+        first.results.push(record("brew", true, 1.0));
+        first.results.push(record("npm", false, 2.0));
+        first.results[0].packages = Some(7);
+        first.note = Some("before upgrading to macOS 15".to_string());
+        let mut second = RunRecord::new("check", UNIX_EPOCH + Duration::from_secs(7200));
+        second.run_id = Some("3f2a9c1e-8b4d-4e6f-a1b2-c3d4e5f60718".to_string());
+
+        assert_eq!(
+            render_runs(&[first, second], Timezone::Utc),
+            "1970-01-01 02:00 +00:00  check   0 managers, 0 failed  [3f2a9c1e]\n\
+             1970-01-01 01:00 +00:00  update  2 managers, 1 failed, 7 packages upgraded  # before upgrading to macOS 15\n"
+        );
+        assert_eq!(
+            render_runs(&[], Timezone::Utc),
+            "No history recorded yet.\n"
+        );
+    }
+}
@@ -0,0 +1,284 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use crate::download::{expand_vars, http_agent};
+use crate::executor::{CommandRequest, Executor};
+use crate::package_manager::UpdateError;
+
+// Versions look like 1.2, 1.2.3 or 2024.01.05, optionally with a suffix
+pub(crate) const DEFAULT_VERSION_REGEX: &str = r"\d+(?:\.\d+)+(?:[-+][0-9A-Za-z.]+)?";
+
+// Compare the latest version published at a URL with the installed one, for
+// tools installed outside any package manager
+//
+//   check:
+//     - check_http:
+//         url: https://api.github.com/repos/junegunn/fzf/releases/latest
+//         json: tag_name                 # dotted path into a JSON response
+//         version_command: fzf --version
+//         headers:
+//           Authorization: Bearer ${GITHUB_TOKEN}
+//
+// Both the remote value and the command's output go through `regex` (first
+// capture group, or the whole match) to extract the version. When the remote
+// version is newer, the step prints "<name> <installed> -> <latest>", which
+// counts as one pending update.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HttpCheckStep {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json: Option<String>,
+    pub version_command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+    // Shown in the output, the version command's program by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+}
+
+impl HttpCheckStep {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.url.starts_with("https://") || self.url.starts_with("http://")) {
+            return Err(format!(
+                "check_http {}: url must start with https:// or http://",
+                self.url
+            ));
+        }
+        if self.version_command.trim().is_empty() {
+            return Err(format!("check_http {}: version_command is empty", self.url));
+        }
+        self.version_regex()
+            .map(|_| ())
+            .map_err(|e| format!("check_http {}: invalid regex: {}", self.url, e))
+    }
+
+    fn version_regex(&self) -> Result<Regex, regex::Error> {
+        Regex::new(self.regex.as_deref().unwrap_or(DEFAULT_VERSION_REGEX))
+    }
+
+    fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or_else(|| {
+            self.version_command
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+        })
+    }
+
+    // Returns a line describing the update, or nothing when up to date
+    pub fn run(
+        &self,
+        executor: &dyn Executor,
+        env: &[(String, String)],
+    ) -> Result<String, UpdateError> {
+        let regex = self
+            .version_regex()
+            .map_err(|e| UpdateError::new(format!("Invalid regex: {}", e)))?;
+
+        let mut request = CommandRequest::captured(&self.version_command);
+        request.env = env.to_vec();
+        let output = executor.run(&request)?;
+        if !output.success() {
+            return Err(UpdateError::new(format!(
+                "Version command failed with {}",
+                output.status_description()
+            )));
+        }
+        let installed = extract_version(&regex, &output.stdout).ok_or_else(|| {
+            UpdateError::new(format!(
+                "No version found in the output of {}",
+                self.version_command
+            ))
+        })?;
+
+        let latest_text = self.fetch(env)?;
+        let latest = extract_version(&regex, &latest_text).ok_or_else(|| {
+            UpdateError::new(format!("No version found in {}", latest_text.trim()))
+        })?;
+
+        Ok(match compare_versions(&latest, &installed) {
+            Ordering::Greater => format!("{} {} -> {}\n", self.name(), installed, latest),
+            _ => String::new(),
+        })
+    }
+
+    // The remote value: the whole body, or the value at the `json` path
+    fn fetch(&self, env: &[(String, String)]) -> Result<String, UpdateError> {
+        let url = expand_vars(&self.url, env);
+        let mut request = http_agent(self.proxy.as_deref(), env, &url)?.get(&url);
+        for (name, value) in &self.headers {
+            request = request.set(name, &expand_vars(value, env));
+        }
+        let body = request
+            .call()
+            .map_err(|e| UpdateError::new(format!("Failed to fetch {}: {}", url, e)))?
+            .into_string()
+            .map_err(|e| UpdateError::new(format!("Failed to read {}: {}", url, e)))?;
+
+        let Some(path) = &self.json else {
+            return Ok(body);
+        };
+        let value: serde_json::Value = serde_json::from_str(&body)
+            .map_err(|e| UpdateError::new(format!("Invalid JSON from {}: {}", url, e)))?;
+        match json_path(&value, path) {
+            Some(serde_json::Value::String(s)) => Ok(s.clone()),
+            Some(other) => Ok(other.to_string()),
+            None => Err(UpdateError::new(format!("{} not found in {}", path, url))),
+        }
+    }
+}
+
+// Follow a dotted path such as `assets.0.name` (numbers index arrays)
+fn json_path<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('.').try_fold(value, |value, key| match value {
+        serde_json::Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+        _ => value.get(key),
+    })
+}
+
+pub(crate) fn extract_version(regex: &Regex, text: &str) -> Option<String> {
+    let captures = regex.captures(text)?;
+    let version = captures.get(1).or_else(|| captures.get(0))?.as_str();
+    Some(version.trim_start_matches(['v', 'V']).to_string())
+}
+
+// Compare numerically component by component ("1.10" > "1.9"); a version
+// with a suffix sorts before the same version without one ("2.0-rc1" < "2.0")
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (a_numbers, a_suffix) = split_version(a);
+    let (b_numbers, b_suffix) = split_version(b);
+    let length = a_numbers.len().max(b_numbers.len());
+    let component = |numbers: &[u64], i: usize| numbers.get(i).copied().unwrap_or(0);
+    (0..length)
+        .map(|i| component(&a_numbers, i).cmp(&component(&b_numbers, i)))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| match (a_suffix, b_suffix) {
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (a, b) => a.cmp(&b),
+        })
+}
+
+// Numeric components and the suffix starting at `-` or `+`, if any
+fn split_version(version: &str) -> (Vec<u64>, Option<&str>) {
+    let (numbers, suffix) = match version.find(['-', '+']) {
+        Some(index) => (&version[..index], Some(&version[index..])),
+        None => (version, None),
+    };
+    let numbers = numbers
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect();
+    (numbers, suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::thread;
+
+    // Answer one request with `body`, returning the request's header lines
+    fn serve(body: &'static str) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/releases/latest", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut headers = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                headers.push(line.trim().to_string());
+            }
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+            headers
+        });
+        (url, handle)
+    }
+
+    fn step(url: &str) -> HttpCheckStep {
+        HttpCheckStep {
+            url: url.to_string(),
+            json: Some("tag_name".to_string()),
+            version_command: "fzf --version".to_string(),
+            regex: None,
+            name: None,
+            headers: BTreeMap::from([("Authorization".to_string(), "Bearer ${TOKEN}".to_string())]),
+            proxy: None,
+        }
+    }
+
+    #[test]
+    fn test_reports_newer_release() {
+        let clock = Arc::new(FakeClock::new());
+        let executor = ScriptedExecutor::new(clock);
+        executor.script(
+            "fzf --version",
+            ScriptedCommand::success().stdout("0.54.3 (brew)\n"),
+        );
+        let env = vec![("TOKEN".to_string(), "secret".to_string())];
+
+        let (url, server) = serve(r#"{"tag_name": "v0.55.0", "draft": false}"#);
+        let output = step(&url).run(&executor, &env).unwrap();
+        assert_eq!(output, "fzf 0.54.3 -> 0.55.0\n");
+        assert!(server
+            .join()
+            .unwrap()
+            .contains(&"Authorization: Bearer secret".to_string()));
+
+        let (url, server) = serve(r#"{"tag_name": "v0.54.3"}"#);
+        assert_eq!(step(&url).run(&executor, &env).unwrap(), "");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_missing_version() {
+        let clock = Arc::new(FakeClock::new());
+        let executor = ScriptedExecutor::new(clock);
+        executor.script(
+            "fzf --version",
+            ScriptedCommand::success().stdout("0.54.3\n"),
+        );
+
+        let (url, server) = serve(r#"{"name": "latest"}"#);
+        let err = step(&url).run(&executor, &[]).unwrap_err();
+        assert!(err.message.starts_with("tag_name not found"));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_json_path() {
+        let value: serde_json::Value =
+            serde_json::from_str(r#"[{"tag_name": "v2", "assets": [{"name": "a"}]}]"#).unwrap();
+        assert_eq!(json_path(&value, "0.tag_name").unwrap(), "v2");
+        assert_eq!(json_path(&value, "0.assets.0.name").unwrap(), "a");
+        assert!(json_path(&value, "1.tag_name").is_none());
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.10.0", "1.9.9"), Ordering::Greater);
+        assert_eq!(compare_versions("1.2", "1.2.0"), Ordering::Equal);
+        assert_eq!(compare_versions("2.0-rc1", "2.0"), Ordering::Less);
+        assert_eq!(compare_versions("0.54.3", "0.55.0"), Ordering::Less);
+    }
+}
@@ -0,0 +1,292 @@
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::migrate::CONFIG_VERSION;
+use crate::wizard::{render_entry, KnownManager, KNOWN_MANAGERS};
+
+// `updog config import --from topgrade`: turn a topgrade.toml into an updog
+// config. Topgrade runs every step whose tool is installed unless the step is
+// disabled, so the steps updog has an equivalent for are imported for the
+// tools found on this machine, like the first-run wizard does. Custom
+// commands become update-only entries; everything else is reported.
+
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error("Failed to read {0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("Invalid topgrade config: {0}")]
+    Parse(#[from] toml::de::Error),
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Imported {
+    pub managers: Vec<KnownManager>,
+    // Custom commands by updog id, with their command line
+    pub commands: Vec<(String, String)>,
+    // Settings and steps updog has nothing to map to
+    pub unmapped: Vec<String>,
+}
+
+// Topgrade steps and the known managers they run
+const TOPGRADE_STEPS: &[(&str, &[&str])] = &[
+    ("system", &["apt", "dnf", "pacman"]),
+    ("brew", &["homebrew"]),
+    ("brew_formula", &["homebrew"]),
+    ("brew_cask", &["homebrew"]),
+    ("mas", &["mas"]),
+    ("flatpak", &["flatpak"]),
+    ("snap", &["snap"]),
+    ("node", &["npm"]),
+    ("rustup", &["rustup"]),
+    ("cargo", &["cargo"]),
+    ("pipx", &["pipx"]),
+];
+
+// Topgrade's step for the `[commands]` table
+const CUSTOM_COMMANDS_STEP: &str = "custom_commands";
+
+fn string_list(value: Option<&toml::Value>) -> Vec<String> {
+    value
+        .and_then(toml::Value::as_array)
+        .map(|values| {
+            values
+                .iter()
+                .filter_map(|v| v.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// An updog id for a custom command name: "Python Environment" → python-environment
+fn slug(name: &str) -> String {
+    let words: Vec<String> = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect();
+    if words.is_empty() {
+        "command".to_string()
+    } else {
+        words.join("-")
+    }
+}
+
+// Translate topgrade.toml contents. `binary_exists` decides which of the
+// enabled steps' tools are installed.
+pub fn from_topgrade(
+    text: &str,
+    binary_exists: &dyn Fn(&str) -> bool,
+) -> Result<Imported, ImportError> {
+    let table: toml::Table = text.parse()?;
+    let mut unmapped = Vec::new();
+
+    // Topgrade 10 moved these under [misc]; older configs have them at the top
+    let misc = table.get("misc").and_then(toml::Value::as_table);
+    let setting = |key: &str| misc.and_then(|m| m.get(key)).or_else(|| table.get(key));
+    let disabled = string_list(setting("disable"));
+    let only = string_list(setting("only"));
+    let enabled = |step: &str| {
+        !disabled.iter().any(|s| s == step) && (only.is_empty() || only.iter().any(|s| s == step))
+    };
+
+    for step in &only {
+        if step != CUSTOM_COMMANDS_STEP && !TOPGRADE_STEPS.iter().any(|(name, _)| name == step) {
+            unmapped.push(format!("step '{}': updog has no equivalent", step));
+        }
+    }
+
+    let ids: Vec<&str> = TOPGRADE_STEPS
+        .iter()
+        .filter(|(step, _)| enabled(step))
+        .flat_map(|(_, ids)| ids.iter().copied())
+        .collect();
+    let managers: Vec<KnownManager> = KNOWN_MANAGERS
+        .iter()
+        .filter(|m| ids.contains(&m.id) && binary_exists(m.program))
+        .copied()
+        .collect();
+
+    let mut commands = Vec::new();
+    if let Some(custom) = table.get("commands").and_then(toml::Value::as_table) {
+        for (name, command) in custom {
+            if !enabled(CUSTOM_COMMANDS_STEP) {
+                break;
+            }
+            let Some(command) = command.as_str() else {
+                unmapped.push(format!("commands.{}: not a command line", name));
+                continue;
+            };
+            let base = slug(name);
+            let taken = |id: &str| {
+                managers.iter().any(|m| m.id == id)
+                    || commands.iter().any(|(c, _): &(String, String)| c == id)
+            };
+            let mut id = base.clone();
+            let mut n = 2;
+            while taken(&id) {
+                id = format!("{}-{}", base, n);
+                n += 1;
+            }
+            commands.push((id, command.to_string()));
+        }
+    }
+
+    for (key, value) in &table {
+        match key.as_str() {
+            "commands" | "disable" | "only" => {}
+            "misc" => {
+                for misc_key in value.as_table().into_iter().flat_map(|t| t.keys()) {
+                    if misc_key != "disable" && misc_key != "only" {
+                        unmapped.push(format!("misc.{}", misc_key));
+                    }
+                }
+            }
+            "pre_commands" | "post_commands" => {
+                for name in value.as_table().into_iter().flat_map(|t| t.keys()) {
+                    unmapped.push(format!("{}.{}: updog has no run-wide hooks", key, name));
+                }
+            }
+            _ if value.is_table() => unmapped.push(format!("[{}] step options", key)),
+            _ => unmapped.push(key.clone()),
+        }
+    }
+
+    Ok(Imported {
+        managers,
+        commands,
+        unmapped,
+    })
+}
+
+pub fn from_topgrade_file(
+    path: &Path,
+    binary_exists: &dyn Fn(&str) -> bool,
+) -> Result<Imported, ImportError> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| ImportError::Io(path.display().to_string(), e))?;
+    from_topgrade(&text, binary_exists)
+}
+
+// Config file contents for an import, with what wasn't imported as comments
+pub fn render_config(imported: &Imported, source: &Path) -> String {
+    let mut out = format!(
+        "# Imported by updog from {}. See the README for everything\n\
+         # else that can go in here (schedules, tags, groups, alerts, ...).\n",
+        source.display()
+    );
+    if !imported.unmapped.is_empty() {
+        out.push_str("#\n# Not imported:\n");
+        for item in &imported.unmapped {
+            out.push_str(&format!("#   {}\n", item));
+        }
+    }
+    out.push_str(&format!("version: {}\n", CONFIG_VERSION));
+    if imported.managers.is_empty() && imported.commands.is_empty() {
+        out.push_str("commands: []\n");
+        return out;
+    }
+    out.push_str("commands:\n");
+    for manager in &imported.managers {
+        out.push_str(&render_entry(manager));
+    }
+    for (id, command) in &imported.commands {
+        out.push_str(&format!("  - id: {}\n    update: {:?}\n", id, command));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    const TOPGRADE: &str = r#"
+        [misc]
+        disable = ["snap", "cargo"]
+        assume_yes = true
+
+        [pre_commands]
+        "Emacs Snapshot" = "cp -rl ~/.emacs.d ~/.emacs._backup"
+
+        [commands]
+        "Python Environment" = "~/dev/.env/bin/pip install -U pip"
+        "npm" = "npm i -g corepack"
+
+        [brew]
+        greedy_cask = true
+    "#;
+
+    fn installed(program: &str) -> bool {
+        ["apt-get", "brew", "npm", "snap", "cargo-install-update"].contains(&program)
+    }
+
+    #[test]
+    fn test_from_topgrade() {
+        let imported = from_topgrade(TOPGRADE, &installed).unwrap();
+        let ids: Vec<&str> = imported.managers.iter().map(|m| m.id).collect();
+        assert_eq!(ids, ["homebrew", "apt", "npm"]);
+        assert_eq!(
+            imported.commands,
+            [
+                (
+                    "python-environment".to_string(),
+                    "~/dev/.env/bin/pip install -U pip".to_string()
+                ),
+                ("npm-2".to_string(), "npm i -g corepack".to_string()),
+            ]
+        );
+        assert_eq!(
+            imported.unmapped,
+            [
+                "[brew] step options",
+                "misc.assume_yes",
+                "pre_commands.Emacs Snapshot: updog has no run-wide hooks",
+            ]
+        );
+
+        let yaml = render_config(&imported, Path::new("topgrade.toml"));
+        assert!(yaml.contains("#   misc.assume_yes\n"), "{}", yaml);
+        let config: Config = serde_yaml::from_str(&yaml).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.commands.len(), 5);
+        let python = config.find_package_manager("python-environment").unwrap();
+        assert!(python.check.is_none());
+        assert_eq!(
+            python.update.as_ref().unwrap().as_single_str().unwrap(),
+            "~/dev/.env/bin/pip install -U pip"
+        );
+    }
+
+    #[test]
+    fn test_only_and_old_format() {
+        let imported = from_topgrade(
+            r#"
+            only = ["system", "vim", "custom_commands"]
+            [commands]
+            "Update" = "true"
+            "#,
+            &installed,
+        )
+        .unwrap();
+        let ids: Vec<&str> = imported.managers.iter().map(|m| m.id).collect();
+        assert_eq!(ids, ["apt"]);
+        assert_eq!(imported.commands.len(), 1);
+        assert_eq!(imported.unmapped, ["step 'vim': updog has no equivalent"]);
+
+        let none = from_topgrade(
+            "[misc]\ndisable = [\"custom_commands\"]\n[commands]\nx = \"true\"",
+            &|_| false,
+        )
+        .unwrap();
+        assert_eq!(none, Imported::default());
+        let yaml = render_config(&none, Path::new("t.toml"));
+        let config: Config = serde_yaml::from_str(&yaml).unwrap();
+        assert!(config.commands.is_empty());
+
+        assert!(matches!(
+            from_topgrade("[misc", &installed),
+            Err(ImportError::Parse(_))
+        ));
+    }
+}
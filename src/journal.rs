@@ -0,0 +1,283 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::history::unix_seconds;
+use crate::lock::process_running;
+use crate::report::{ExecutionStatus, Operation};
+use crate::state_file::write_atomic;
+use crate::timezone::Timezone;
+
+#[derive(Debug, Error)]
+pub enum JournalError {
+    #[error("Failed to access run journal: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Failed to parse run journal: {0}")]
+    ParseError(#[from] serde_yaml::Error),
+}
+
+// What a check or update run set out to do and how far it got, written
+// before the first manager starts and after each one changes state. A run
+// that finishes removes it, so a journal left behind by a process that is no
+// longer running means the run crashed or the machine lost power: `updog
+// status` reports it, and `updog update --resume` runs the managers it
+// didn't finish.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Journal {
+    pub operation: Operation,
+    pub pid: u32,
+    // Seconds since the Unix epoch
+    pub started_at: u64,
+    pub items: Vec<JournalItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalItem {
+    pub manager: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subcommand: Option<String>,
+    pub state: ItemState,
+    // Result label of a finished item
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemState {
+    Pending,
+    Running,
+    Done,
+}
+
+impl JournalItem {
+    pub fn name(&self) -> String {
+        match &self.subcommand {
+            Some(sc) => format!("{}:{}", self.manager, sc),
+            None => self.manager.clone(),
+        }
+    }
+}
+
+impl Journal {
+    // Default location: <data dir>/updog/journal.yaml
+    pub fn default_path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("updog")
+            .join("journal.yaml")
+    }
+
+    // The journal of a run of this process about to run `items`
+    pub fn start(
+        operation: Operation,
+        items: &[(String, Option<String>)],
+        started_at: SystemTime,
+    ) -> Self {
+        Self {
+            operation,
+            pid: std::process::id(),
+            started_at: unix_seconds(started_at),
+            items: items
+                .iter()
+                .map(|(manager, subcommand)| JournalItem {
+                    manager: manager.clone(),
+                    subcommand: subcommand.clone(),
+                    state: ItemState::Pending,
+                    status: None,
+                })
+                .collect(),
+        }
+    }
+
+    // The journal at `path`, if a run left one
+    pub fn load(path: &Path) -> Result<Option<Self>, JournalError> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(Some(serde_yaml::from_str(&content)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), JournalError> {
+        write_atomic(path, serde_yaml::to_string(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn remove(path: &Path) -> Result<(), JournalError> {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn begin(&mut self, index: usize) {
+        if let Some(item) = self.items.get_mut(index) {
+            item.state = ItemState::Running;
+        }
+    }
+
+    // Record the result of an item. A cancelled item is left to resume.
+    pub fn finish(&mut self, index: usize, status: &ExecutionStatus) {
+        if let Some(item) = self.items.get_mut(index) {
+            if *status == ExecutionStatus::Cancelled {
+                item.state = ItemState::Pending;
+                item.status = None;
+            } else {
+                item.state = ItemState::Done;
+                item.status = Some(status.label().to_string());
+            }
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.items.iter().all(|item| item.state == ItemState::Done)
+    }
+
+    // Whether the process that wrote the journal is still running it
+    pub fn in_progress(&self) -> bool {
+        process_running(self.pid)
+    }
+
+    // Managers left to run by a resumed run: the one that was running, then
+    // the ones that hadn't started
+    pub fn unfinished(&self) -> Vec<(String, Option<String>)> {
+        self.items
+            .iter()
+            .filter(|item| item.state != ItemState::Done)
+            .map(|item| (item.manager.clone(), item.subcommand.clone()))
+            .collect()
+    }
+
+    fn names(&self, state: ItemState) -> Vec<String> {
+        self.items
+            .iter()
+            .filter(|item| item.state == state)
+            .map(JournalItem::name)
+            .collect()
+    }
+
+    // A few lines for `updog status` about a run in progress or one that
+    // didn't complete
+    pub fn render(&self, in_progress: bool, timezone: Timezone) -> String {
+        let started = timezone.civil(UNIX_EPOCH + Duration::from_secs(self.started_at));
+        let running = self.names(ItemState::Running);
+        let pending = self.names(ItemState::Pending);
+        let mut out = if in_progress {
+            let article = match self.operation {
+                Operation::Check => "A",
+                Operation::Update => "An",
+            };
+            format!(
+                "{} {} run is in progress (pid {}, started {})",
+                article, self.operation, self.pid, started
+            )
+        } else {
+            format!(
+                "The last {} run (started {}) did not complete",
+                self.operation, started
+            )
+        };
+        match (in_progress, running.as_slice()) {
+            (_, []) => {}
+            (true, names) => out.push_str(&format!("; {} running", names.join(", "))),
+            (false, names) => {
+                let verb = match self.operation {
+                    Operation::Check => "mid-check",
+                    Operation::Update => "mid-update",
+                };
+                let was = if names.len() == 1 { "was" } else { "were" };
+                out.push_str(&format!("; {} {} {}", names.join(", "), was, verb));
+            }
+        }
+        out.push('\n');
+        if !pending.is_empty() {
+            out.push_str(&format!("Not started: {}\n", pending.join(", ")));
+        }
+        if !in_progress {
+            out.push_str(&format!(
+                "Run `updog {} --resume` to finish it\n",
+                self.operation
+            ));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn items() -> Vec<(String, Option<String>)> {
+        vec![
+            ("homebrew".to_string(), Some("cask".to_string())),
+            ("npm".to_string(), None),
+            ("pipx".to_string(), None),
+        ]
+    }
+
+    #[test]
+    fn test_progress_and_unfinished() {
+        let mut journal = Journal::start(Operation::Update, &items(), UNIX_EPOCH);
+        assert_eq!(journal.unfinished(), items());
+
+        journal.begin(0);
+        journal.finish(0, &ExecutionStatus::Success);
+        journal.begin(1);
+        assert!(!journal.is_complete());
+        assert_eq!(journal.unfinished(), items()[1..]);
+        assert_eq!(journal.items[0].status.as_deref(), Some("success"));
+
+        // A cancelled manager is left to resume
+        journal.finish(1, &ExecutionStatus::Cancelled);
+        assert_eq!(journal.items[1].state, ItemState::Pending);
+        journal.begin(1);
+        journal.finish(1, &ExecutionStatus::Failed);
+        journal.begin(2);
+        journal.finish(2, &ExecutionStatus::Skipped { reason: "x".into() });
+        assert!(journal.is_complete());
+        assert!(journal.unfinished().is_empty());
+    }
+
+    #[test]
+    fn test_save_load_remove() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("updog/journal.yaml");
+        assert_eq!(Journal::load(&path).unwrap(), None);
+
+        let mut journal = Journal::start(Operation::Update, &items(), UNIX_EPOCH);
+        journal.begin(0);
+        journal.save(&path).unwrap();
+        assert_eq!(Journal::load(&path).unwrap(), Some(journal));
+
+        Journal::remove(&path).unwrap();
+        Journal::remove(&path).unwrap();
+        assert_eq!(Journal::load(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn test_render() {
+        let mut journal = Journal::start(Operation::Update, &items(), UNIX_EPOCH);
+        journal.begin(0);
+        journal.finish(0, &ExecutionStatus::Success);
+        journal.begin(1);
+
+        assert_eq!(
+            journal.render(false, Timezone::Utc),
+            "The last update run (started 1970-01-01 00:00 +00:00) did not complete; \
+             npm was mid-update\n\
+             Not started: pipx\n\
+             Run `updog update --resume` to finish it\n"
+        );
+        let running = journal.render(true, Timezone::Utc);
+        assert!(
+            running.starts_with("An update run is in progress (pid "),
+            "{}",
+            running
+        );
+        assert!(running.ends_with("; npm running\nNot started: pipx\n"));
+    }
+}
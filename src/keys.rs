@@ -0,0 +1,179 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+
+// Keyboard controls of `updog update --keys`:
+//
+//   s  skip the manager that's running (its commands are terminated)
+//   p  pause before the next manager, p again to resume
+//   q  stop once the running manager is done (--resume runs the rest)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Skip,
+    Pause,
+    Stop,
+}
+
+impl Action {
+    pub fn from_key(key: u8) -> Option<Self> {
+        match key.to_ascii_lowercase() {
+            b's' => Some(Action::Skip),
+            b'p' => Some(Action::Pause),
+            b'q' => Some(Action::Stop),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    skip: bool,
+    paused: bool,
+    stopped: bool,
+}
+
+// What the keys pressed so far ask of the run, shared between the thread
+// reading them and the ones running managers
+#[derive(Debug, Default)]
+pub struct Controls {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl Controls {
+    // Apply `action`, returning the line telling the user what happens
+    pub fn press(&self, action: Action) -> &'static str {
+        let mut state = self.state.lock().unwrap();
+        let line = match action {
+            Action::Skip => {
+                state.skip = true;
+                "⏭  Skipping the running manager"
+            }
+            Action::Pause if state.paused => {
+                state.paused = false;
+                "▶  Resuming"
+            }
+            Action::Pause => {
+                state.paused = true;
+                "⏸  Pausing before the next manager (p to resume)"
+            }
+            Action::Stop => {
+                state.stopped = true;
+                "⏹  Stopping after the running manager"
+            }
+        };
+        self.changed.notify_all();
+        line
+    }
+
+    // Wait while the run is paused before starting a manager, returning
+    // false if it was stopped instead
+    pub fn proceed(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        let mut state = self
+            .changed
+            .wait_while(state, |state| state.paused && !state.stopped)
+            .unwrap();
+        state.skip = false;
+        !state.stopped
+    }
+
+    // Whether the manager that just finished was skipped
+    pub fn skipped(&self) -> bool {
+        self.state.lock().unwrap().skip
+    }
+}
+
+// Hand the keys typed on the terminal on stdin to `on_key` until `done` is
+// set. Meanwhile the terminal doesn't echo them or wait for Enter; Ctrl+C
+// still works.
+#[cfg(unix)]
+pub fn read_keys(done: &AtomicBool, mut on_key: impl FnMut(u8)) {
+    let fd = libc::STDIN_FILENO;
+    let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+        tracing::warn!("Can't read keys: {}", std::io::Error::last_os_error());
+        return;
+    }
+    let mut raw = saved;
+    raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+    raw.c_cc[libc::VMIN] = 1;
+    raw.c_cc[libc::VTIME] = 0;
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) };
+
+    // Read unbuffered, so keys aren't left in a buffer poll doesn't see
+    let mut key = 0u8;
+    while !done.load(Ordering::SeqCst) {
+        let mut poll = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // Wake up now and then to notice the run is over
+        if unsafe { libc::poll(&mut poll, 1, 100) } <= 0 {
+            continue;
+        }
+        match unsafe { libc::read(fd, (&mut key as *mut u8).cast(), 1) } {
+            1 => on_key(key),
+            // End of input
+            0 => break,
+            _ => {}
+        }
+    }
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &saved) };
+}
+
+#[cfg(not(unix))]
+pub fn read_keys(_done: &AtomicBool, _on_key: impl FnMut(u8)) {
+    tracing::warn!("Keyboard controls aren't supported on this platform");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_from_key() {
+        assert_eq!(Action::from_key(b's'), Some(Action::Skip));
+        assert_eq!(Action::from_key(b'Q'), Some(Action::Stop));
+        assert_eq!(Action::from_key(b'x'), None);
+    }
+
+    #[test]
+    fn test_skip_applies_to_the_running_manager() {
+        let controls = Controls::default();
+        assert!(controls.proceed());
+        controls.press(Action::Skip);
+        assert!(controls.skipped());
+        assert!(controls.proceed());
+        assert!(!controls.skipped());
+    }
+
+    #[test]
+    fn test_pause_waits_until_resumed_or_stopped() {
+        let controls = Arc::new(Controls::default());
+        assert_eq!(
+            controls.press(Action::Pause),
+            "⏸  Pausing before the next manager (p to resume)"
+        );
+        let waiting = thread::spawn({
+            let controls = controls.clone();
+            move || controls.proceed()
+        });
+        thread::sleep(Duration::from_millis(50));
+        assert!(!waiting.is_finished());
+        assert_eq!(controls.press(Action::Pause), "▶  Resuming");
+        assert!(waiting.join().unwrap());
+
+        controls.press(Action::Pause);
+        let waiting = thread::spawn({
+            let controls = controls.clone();
+            move || controls.proceed()
+        });
+        controls.press(Action::Stop);
+        assert!(!waiting.join().unwrap());
+        assert!(!controls.proceed());
+    }
+}
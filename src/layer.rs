@@ -0,0 +1,356 @@
+use serde_yaml::{Mapping, Value};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::ConfigError;
+use crate::wasm::is_wasm_parser;
+
+// A machine-wide config, shipped by an admin, is merged under the user's:
+//
+//   commands        entries replace the system entry with the same id, or
+//                   are added after the system ones
+//   redact, plugins the user's are added to the system ones
+//   mappings        merged key by key (daemon, alerts, history, ...)
+//   anything else   the user's value wins
+//
+// UPDOG_SYSTEM_CONFIG points elsewhere; set to nothing, there's no system layer.
+pub const SYSTEM_CONFIG_ENV: &str = "UPDOG_SYSTEM_CONFIG";
+
+// Top-level lists the user adds to instead of replacing
+const APPENDED: &[&str] = &["redact", "plugins"];
+
+// /etc/updog/updog.yaml, or the platform's equivalent
+pub fn system_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(SYSTEM_CONFIG_ENV) {
+        return (!path.is_empty()).then(|| PathBuf::from(path));
+    }
+    if cfg!(windows) {
+        let data = std::env::var_os("ProgramData")?;
+        Some(PathBuf::from(data).join("updog").join("updog.yaml"))
+    } else if cfg!(target_os = "macos") {
+        Some(PathBuf::from(
+            "/Library/Application Support/updog/updog.yaml",
+        ))
+    } else {
+        Some(PathBuf::from("/etc/updog/updog.yaml"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    System,
+    User,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            Origin::System => "system",
+            Origin::User => "user",
+        })
+    }
+}
+
+// Layer a merged entry (`commands.apt`, `daemon.schedule`, `redact[1]`)
+// was taken from, and the layer it replaced, if any
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    pub key: String,
+    pub origin: Origin,
+    pub overrides: Option<Origin>,
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.origin)?;
+        if let Some(overridden) = self.overrides {
+            write!(f, " (overrides {})", overridden)?;
+        }
+        Ok(())
+    }
+}
+
+// A layer as YAML, with relative WASM parser paths made relative to its
+// directory (as `Config::from_file` does), so they stay right once merged
+pub fn read(path: &Path) -> Result<Value, ConfigError> {
+    let in_layer = |e: ConfigError| ConfigError::Layer {
+        path: path.to_path_buf(),
+        source: Box::new(e),
+    };
+    let content = fs::read_to_string(path).map_err(|e| in_layer(e.into()))?;
+    let mut value: Value = serde_yaml::from_str(&content).map_err(|e| in_layer(e.into()))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let commands = value.get_mut("commands").and_then(Value::as_sequence_mut);
+    for entry in commands.into_iter().flatten() {
+        if let Some(Value::String(parser)) = entry.get_mut("parser") {
+            if is_wasm_parser(parser)
+                && Path::new(parser).is_relative()
+                && !parser.starts_with("~/")
+            {
+                *parser = dir.join(&*parser).display().to_string();
+            }
+        }
+    }
+    Ok(value)
+}
+
+// Merge `layers`, lowest first, with where each entry came from
+pub fn merge(layers: Vec<(Origin, Value)>) -> (Value, Vec<Provenance>) {
+    let mut merged = Mapping::new();
+    let mut provenance = Vec::new();
+    for (origin, layer) in layers {
+        if let Value::Mapping(mapping) = layer {
+            Layer {
+                origin,
+                provenance: &mut provenance,
+            }
+            .merge_mapping(&mut merged, mapping, "", true);
+        }
+    }
+    (Value::Mapping(merged), provenance)
+}
+
+struct Layer<'a> {
+    origin: Origin,
+    provenance: &'a mut Vec<Provenance>,
+}
+
+impl Layer<'_> {
+    // Note `key` as coming from this layer, replacing what was noted for it
+    // and anything under it. What it replaced may have been noted for the
+    // mapping holding it.
+    fn record(&mut self, key: String, replaced: bool) {
+        let overrides = self
+            .provenance
+            .iter()
+            .find(|p| p.key == key || is_under(&p.key, &key) || is_under(&key, &p.key))
+            .map(|p| p.origin)
+            .filter(|_| replaced);
+        self.provenance
+            .retain(|p| p.key != key && !is_under(&p.key, &key));
+        self.provenance.push(Provenance {
+            key,
+            origin: self.origin,
+            overrides,
+        });
+    }
+
+    fn merge_mapping(&mut self, base: &mut Mapping, over: Mapping, prefix: &str, top: bool) {
+        for (key, value) in over {
+            let name = match &key {
+                Value::String(name) => name.clone(),
+                other => serde_yaml::to_string(other)
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string(),
+            };
+            let path = match prefix {
+                "" => name.clone(),
+                prefix => format!("{}.{}", prefix, name),
+            };
+            match (base.get_mut(&key), value) {
+                (Some(Value::Sequence(entries)), Value::Sequence(added))
+                    if top && name == "commands" =>
+                {
+                    self.merge_commands(entries, added);
+                }
+                (Some(Value::Sequence(items)), Value::Sequence(added))
+                    if top && APPENDED.contains(&name.as_str()) =>
+                {
+                    for item in added {
+                        self.record(format!("{}[{}]", path, items.len()), false);
+                        items.push(item);
+                    }
+                }
+                (Some(Value::Mapping(base)), Value::Mapping(over)) => {
+                    self.merge_mapping(base, over, &path, false);
+                }
+                (Some(existing), value) => {
+                    *existing = value;
+                    self.record(path, true);
+                }
+                (None, Value::Sequence(entries)) if top && name == "commands" => {
+                    let mut merged = Vec::new();
+                    self.merge_commands(&mut merged, entries);
+                    base.insert(key, Value::Sequence(merged));
+                }
+                (None, Value::Sequence(items)) if top && APPENDED.contains(&name.as_str()) => {
+                    for index in 0..items.len() {
+                        self.record(format!("{}[{}]", path, index), false);
+                    }
+                    base.insert(key, Value::Sequence(items));
+                }
+                (None, value) => {
+                    base.insert(key, value);
+                    self.record(path, false);
+                }
+            }
+        }
+    }
+
+    // An entry replaces the one with its id in place, or is added at the end
+    fn merge_commands(&mut self, entries: &mut Vec<Value>, added: Vec<Value>) {
+        for entry in added {
+            let id = entry
+                .get("id")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            let key = format!("commands.{}", id);
+            match entries
+                .iter_mut()
+                .find(|e| e.get("id").and_then(Value::as_str) == Some(id.as_str()))
+            {
+                Some(existing) => {
+                    *existing = entry;
+                    self.record(key, true);
+                }
+                None => {
+                    entries.push(entry);
+                    self.record(key, false);
+                }
+            }
+        }
+    }
+}
+
+// Whether `key` names something inside `parent` (`daemon.schedule` in `daemon`)
+fn is_under(key: &str, parent: &str) -> bool {
+    key.strip_prefix(parent)
+        .is_some_and(|rest| rest.starts_with(['.', '[']))
+}
+
+// The `--origin` listing: the layers, then each merged entry and its layer
+pub fn describe(layers: &[(Origin, &Path)], provenance: &[Provenance]) -> String {
+    let mut out = String::new();
+    for (origin, path) in layers {
+        out.push_str(&format!("{:<6}  {}\n", origin, path.display()));
+    }
+    out.push('\n');
+    let width = provenance.iter().map(|p| p.key.len()).max().unwrap_or(0);
+    for entry in provenance {
+        out.push_str(&format!(
+            "{:<width$}  {}\n",
+            entry.key,
+            entry,
+            width = width
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYSTEM: &str = r#"
+commands:
+  - id: apt
+    update: sudo apt upgrade -y
+  - id: npm
+    update: npm update -g
+daemon:
+  interval: 1d
+  max_retries: 2
+redact:
+  - pattern: "corp-[0-9]+"
+plugins: [inventory]
+"#;
+
+    const USER: &str = r#"
+commands:
+  - id: npm
+    update: npm update -g --omit=dev
+  - id: cargo
+    update: cargo install-update -a
+daemon:
+  interval: 6h
+redact:
+  - pattern: "ghp_[A-Za-z0-9]+"
+output_limit: 1M
+"#;
+
+    fn layers() -> Vec<(Origin, Value)> {
+        vec![
+            (Origin::System, serde_yaml::from_str(SYSTEM).unwrap()),
+            (Origin::User, serde_yaml::from_str(USER).unwrap()),
+        ]
+    }
+
+    #[test]
+    fn test_merge() {
+        let (merged, _) = merge(layers());
+        let ids: Vec<&str> = merged["commands"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, ["apt", "npm", "cargo"]);
+        assert_eq!(
+            merged["commands"][1]["update"].as_str(),
+            Some("npm update -g --omit=dev")
+        );
+        assert_eq!(merged["daemon"]["interval"].as_str(), Some("6h"));
+        assert_eq!(merged["daemon"]["max_retries"].as_u64(), Some(2));
+        assert_eq!(merged["redact"].as_sequence().unwrap().len(), 2);
+        assert_eq!(merged["plugins"][0].as_str(), Some("inventory"));
+        assert_eq!(merged["output_limit"].as_str(), Some("1M"));
+    }
+
+    #[test]
+    fn test_provenance() {
+        let (_, provenance) = merge(layers());
+        let rows: Vec<(&str, String)> = provenance
+            .iter()
+            .map(|p| (p.key.as_str(), p.to_string()))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("commands.apt", "system".to_string()),
+                ("daemon", "system".to_string()),
+                ("redact[0]", "system".to_string()),
+                ("plugins[0]", "system".to_string()),
+                ("commands.npm", "user (overrides system)".to_string()),
+                ("commands.cargo", "user".to_string()),
+                ("daemon.interval", "user (overrides system)".to_string()),
+                ("redact[1]", "user".to_string()),
+                ("output_limit", "user".to_string()),
+            ]
+        );
+
+        let listing = describe(
+            &[
+                (Origin::System, Path::new("/etc/updog/updog.yaml")),
+                (Origin::User, Path::new("/home/me/.config/updog/updog.yaml")),
+            ],
+            &provenance,
+        );
+        assert!(listing.starts_with(
+            "system  /etc/updog/updog.yaml\nuser    /home/me/.config/updog/updog.yaml\n\n"
+        ));
+        assert!(listing.contains("\ncommands.npm     user (overrides system)\n"));
+    }
+
+    #[test]
+    fn test_read_keeps_wasm_parsers_next_to_the_layer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("updog.yaml");
+        fs::write(
+            &path,
+            "commands:\n  - id: apt\n    check: apt list --upgradable\n    parser: parsers/apt.wasm\n",
+        )
+        .unwrap();
+        let value = read(&path).unwrap();
+        assert_eq!(
+            value["commands"][0]["parser"].as_str(),
+            Some(dir.path().join("parsers/apt.wasm").to_str().unwrap())
+        );
+
+        fs::write(&path, "commands: [").unwrap();
+        let err = read(&path).unwrap_err().to_string();
+        assert!(err.starts_with(&format!("{}: ", path.display())), "{}", err);
+    }
+}
@@ -1,6 +1,72 @@
+pub mod alert;
+pub mod approval;
+pub mod audit;
+pub mod badge;
+pub mod bandwidth;
+pub mod cache;
 pub mod cli;
+pub mod completions;
+pub mod concurrency;
 pub mod config;
+pub mod cron;
+pub mod daemon;
+pub mod digest;
+pub mod disk;
+pub mod docker_images;
+pub mod download;
+pub mod eta;
+pub mod executor;
+pub mod export;
+pub mod git_repo;
+pub mod github_release;
+pub mod guard;
+pub mod hash;
+pub mod helm;
+pub mod history;
+pub mod http_check;
+pub mod import;
+pub mod journal;
+pub mod keys;
+pub mod layer;
+pub mod lint;
+pub mod list;
+pub mod locale;
+pub mod lock;
+pub mod metadata;
+pub mod migrate;
+pub mod output;
 pub mod package_manager;
+pub mod plan;
+pub mod plugin;
+pub mod power;
+pub mod preset;
+pub mod progress;
+pub mod proxy;
+pub mod redact;
+pub mod report;
+pub mod schedule;
+pub mod script;
+pub mod sd_notify;
+pub mod secret;
+pub mod selector;
+pub mod shell;
+pub mod shell_hook;
+pub mod shell_script;
+pub mod ssh;
+pub mod state_file;
+pub mod stats;
+pub mod step;
+pub mod systemd;
+pub mod terminal;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+pub mod timezone;
+pub mod tui;
+pub mod warnings;
+pub mod wasm;
+pub mod watch;
+pub mod why;
+pub mod wizard;
 
 pub use config::{Config, SubcommandConfig, UpdateCommand};
 pub use package_manager::PackageManager;
@@ -0,0 +1,418 @@
+use regex::Regex;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use crate::config::{CommandSequence, Config, PackageManagerConfig};
+use crate::shell::Shell;
+
+// Kind of risky pattern found in a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintRule {
+    // `rm -rf` and friends
+    RecursiveDelete,
+    // `$VAR` outside double quotes is subject to word splitting and globbing
+    UnquotedVariable,
+    // `curl ... | sh` runs whatever the server sends
+    PipeToShell,
+    // sudo in a multi-step sequence of a manager without `sudo: true`
+    UndeclaredSudo,
+    // The command's program isn't installed on this machine
+    MissingBinary,
+    // Reported by shellcheck (`updog config lint --shellcheck`)
+    ShellCheck,
+}
+
+impl LintRule {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LintRule::RecursiveDelete => "recursive-delete",
+            LintRule::UnquotedVariable => "unquoted-variable",
+            LintRule::PipeToShell => "pipe-to-shell",
+            LintRule::UndeclaredSudo => "undeclared-sudo",
+            LintRule::MissingBinary => "missing-binary",
+            LintRule::ShellCheck => "shellcheck",
+        }
+    }
+}
+
+// A finding of the lint pass
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintWarning {
+    // "manager[:subcommand] key", plus the step for multi-step sequences
+    pub location: String,
+    pub rule: LintRule,
+    pub message: String,
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} [{}]",
+            self.location,
+            self.message,
+            self.rule.as_str()
+        )
+    }
+}
+
+// Shell words that aren't programs on the PATH
+const SHELL_BUILTINS: &[&str] = &[
+    ".", ":", "[", "[[", "alias", "break", "cd", "command", "continue", "echo", "eval", "exec",
+    "exit", "export", "false", "for", "if", "printf", "read", "return", "set", "shift", "source",
+    "test", "then", "true", "type", "ulimit", "umask", "unset", "wait", "while", "{", "(",
+];
+
+fn pipe_to_shell_regex() -> &'static Regex {
+    static PIPE: OnceLock<Regex> = OnceLock::new();
+    PIPE.get_or_init(|| {
+        Regex::new(r"\b(curl|wget)\b[^|]*\|\s*(sudo\s+(-\S+\s+)*)?(ba|z|da|k)?sh\b").unwrap()
+    })
+}
+
+// A single command string of the config and where it comes from
+struct Step<'a> {
+    manager: &'a PackageManagerConfig,
+    location: String,
+    command: String,
+    multi_step: bool,
+}
+
+fn steps(config: &Config) -> Vec<Step<'_>> {
+    let mut steps = Vec::new();
+    for (manager, name, sequence) in config.sequences() {
+        let multi_step = matches!(sequence, CommandSequence::Multiple(_));
+        for (index, step) in sequence.steps().into_iter().enumerate() {
+            // Built-in steps don't go through the shell
+            let Some(command) = step.as_command() else {
+                continue;
+            };
+            steps.push(Step {
+                manager,
+                location: if multi_step {
+                    format!("{} step {}", name, index + 1)
+                } else {
+                    name.clone()
+                },
+                command: command.to_string(),
+                multi_step,
+            });
+        }
+    }
+    steps
+}
+
+// Lint every command in the config. `binary_exists` decides whether a
+// program name resolves on this machine; pass `None` to skip that rule.
+pub fn lint(config: &Config, binary_exists: Option<&dyn Fn(&str) -> bool>) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+    for step in steps(config) {
+        let command = step.command.as_str();
+        let mut warn = |rule, message: String| {
+            warnings.push(LintWarning {
+                location: step.location.clone(),
+                rule,
+                message,
+            })
+        };
+
+        if has_recursive_delete(command) {
+            warn(
+                LintRule::RecursiveDelete,
+                "recursive forced delete (rm -rf)".to_string(),
+            );
+        }
+        for variable in unquoted_variables(command) {
+            warn(
+                LintRule::UnquotedVariable,
+                format!("unquoted variable ${} (wrap it in double quotes)", variable),
+            );
+        }
+        if pipe_to_shell_regex().is_match(command) {
+            warn(
+                LintRule::PipeToShell,
+                "downloaded script piped into a shell".to_string(),
+            );
+        }
+        if step.multi_step && !step.manager.sudo && uses_sudo(command) {
+            warn(
+                LintRule::UndeclaredSudo,
+                format!("uses sudo but {} doesn't set `sudo: true`", step.manager.id),
+            );
+        }
+        // Remote commands run programs installed on their host
+        if let Some(exists) = binary_exists.filter(|_| step.manager.ssh.is_none()) {
+            if let Some(program) = program(command).filter(|p| !exists(p)) {
+                warn(
+                    LintRule::MissingBinary,
+                    format!("'{}' is not installed on this machine", program),
+                );
+            }
+        }
+    }
+    warnings
+}
+
+// Run every command through shellcheck, as the `sh -c` snippet it is
+// executed as. Fails when shellcheck isn't installed.
+pub fn shellcheck(config: &Config) -> Result<Vec<LintWarning>, String> {
+    if let Some(shell @ (Shell::Cmd | Shell::Powershell | Shell::Pwsh)) = config.shell {
+        return Err(format!(
+            "shellcheck only checks sh and bash commands, not {}",
+            shell
+        ));
+    }
+    let mut warnings = Vec::new();
+    for step in steps(config) {
+        let output = run_shellcheck(&step.command).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => "shellcheck is not installed".to_string(),
+            _ => format!("Failed to run shellcheck: {}", e),
+        })?;
+        warnings.extend(parse_shellcheck(&step.location, &output));
+    }
+    Ok(warnings)
+}
+
+fn run_shellcheck(command: &str) -> std::io::Result<String> {
+    let mut child = Command::new("shellcheck")
+        .args(["--shell=sh", "--format=gcc", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(command.as_bytes())?;
+        stdin.write_all(b"\n")?;
+    }
+    // A non-zero exit status just means there were findings
+    let output = child.wait_with_output()?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Parse shellcheck's gcc format: "-:1:6: warning: Double quote ... [SC2086]"
+fn parse_shellcheck(location: &str, output: &str) -> Vec<LintWarning> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(4, ':');
+            let (_file, _line) = (parts.next()?, parts.next()?);
+            let column = parts.next()?.trim();
+            let rest = parts.next()?.trim();
+            let (level, message) = rest.split_once(':')?;
+            let message = message.trim();
+            let (message, code) = match message.rsplit_once(" [") {
+                Some((message, code)) => (message, code.trim_end_matches(']')),
+                None => (message, ""),
+            };
+            Some(LintWarning {
+                location: location.to_string(),
+                rule: LintRule::ShellCheck,
+                message: format!("{} {} at column {}: {}", code, level, column, message)
+                    .trim_start()
+                    .to_string(),
+            })
+        })
+        .collect()
+}
+
+// Whether `program` resolves to an executable via PATH (or is an existing path)
+pub fn binary_exists(program: &str) -> bool {
+    if program.contains('/') {
+        return Path::new(program).is_file();
+    }
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&path).any(|dir| {
+        let candidate = dir.join(program);
+        candidate.is_file() || (cfg!(windows) && candidate.with_extension("exe").is_file())
+    })
+}
+
+// Whether any command in `command` runs through sudo
+pub fn uses_sudo(command: &str) -> bool {
+    words(command).any(|w| w == "sudo")
+}
+
+fn words(command: &str) -> impl Iterator<Item = &str> {
+    command
+        .split(|c: char| c.is_whitespace() || matches!(c, ';' | '&' | '|'))
+        .filter(|w| !w.is_empty())
+}
+
+fn has_recursive_delete(command: &str) -> bool {
+    command.split([';', '&', '|']).any(|segment| {
+        let mut words = segment.split_whitespace();
+        if !words.any(|w| w == "rm" || w.ends_with("/rm")) {
+            return false;
+        }
+        let (mut recursive, mut force) = (false, false);
+        for word in words {
+            match word {
+                "--recursive" => recursive = true,
+                "--force" => force = true,
+                flag if flag.starts_with('-') && !flag.starts_with("--") => {
+                    recursive |= flag.contains(['r', 'R']);
+                    force |= flag.contains('f');
+                }
+                _ => {}
+            }
+        }
+        recursive && force
+    })
+}
+
+// Names of variables expanded outside double (or single) quotes
+fn unquoted_variables(command: &str) -> Vec<String> {
+    let mut variables = Vec::new();
+    let (mut single, mut double) = (false, false);
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if !single => {
+                chars.next();
+            }
+            '\'' if !double => single = !single,
+            '"' if !single => double = !double,
+            '$' if !single && !double => {
+                let braced = chars.peek() == Some(&'{');
+                if braced {
+                    chars.next();
+                }
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_alphanumeric() || c == '_' {
+                        name.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                // `$1` and friends are positional, `$(...)` is a substitution
+                if name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+                    variables.push(name);
+                }
+            }
+            _ => {}
+        }
+    }
+    variables
+}
+
+// Program a command starts with, skipping environment assignments and sudo
+pub fn program(command: &str) -> Option<&str> {
+    command
+        .split_whitespace()
+        .find(|word| !word.contains('=') && *word != "sudo" && !word.starts_with('-'))
+        .filter(|word| !SHELL_BUILTINS.contains(word) && !word.starts_with(['$', '"', '\'']))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint_yaml(yaml: &str) -> Vec<LintWarning> {
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        lint(&config, Some(&|program: &str| program != "missing-tool"))
+    }
+
+    fn rules(warnings: &[LintWarning]) -> Vec<LintRule> {
+        warnings.iter().map(|w| w.rule).collect()
+    }
+
+    #[test]
+    fn test_clean_config_has_no_warnings() {
+        let warnings = lint_yaml(
+            r#"
+            commands:
+              - id: brew
+                check: brew outdated
+                update: 'brew upgrade && rm -f "$HOME/.cache/brew.lock"'
+            "#,
+        );
+        assert!(warnings.is_empty(), "{:?}", warnings);
+    }
+
+    #[test]
+    fn test_risky_patterns() {
+        let warnings = lint_yaml(
+            r#"
+            commands:
+              - id: tools
+                check: ls $HOME/tools
+                update:
+                  - curl -fsSL https://example.com/install.sh | sudo bash
+                  - rm -r -f /tmp/tools
+                  - missing-tool --upgrade
+            "#,
+        );
+        assert_eq!(
+            rules(&warnings),
+            vec![
+                LintRule::UnquotedVariable,
+                LintRule::PipeToShell,
+                LintRule::UndeclaredSudo,
+                LintRule::RecursiveDelete,
+                LintRule::MissingBinary,
+            ]
+        );
+        assert_eq!(warnings[0].location, "tools check");
+        assert_eq!(
+            warnings[1].to_string(),
+            "tools update step 1: downloaded script piped into a shell [pipe-to-shell]"
+        );
+    }
+
+    #[test]
+    fn test_declared_sudo_and_single_commands_are_accepted() {
+        let yaml = r#"
+            commands:
+              - id: apt
+                sudo: true
+                update: [sudo apt update, sudo apt upgrade -y]
+              - id: dnf
+                update: sudo dnf upgrade -y
+            "#;
+        assert!(lint_yaml(yaml).is_empty());
+    }
+
+    #[test]
+    fn test_recursive_delete() {
+        assert!(has_recursive_delete("sudo rm -rf /opt/old"));
+        assert!(has_recursive_delete("/bin/rm --force -R dir"));
+        assert!(!has_recursive_delete("rm -r dir && git clean -f"));
+    }
+
+    #[test]
+    fn test_parse_shellcheck_output() {
+        let output =
+            "-:1:6: warning: Double quote to prevent globbing and word splitting. [SC2086]\n\
+                      -:1:20: note: Use find instead of ls. [SC2012]\n";
+        let warnings = parse_shellcheck("brew update step 2", output);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(
+            warnings[0].to_string(),
+            "brew update step 2: SC2086 warning at column 6: Double quote to prevent globbing and word splitting. [shellcheck]"
+        );
+        assert!(parse_shellcheck("brew check", "").is_empty());
+    }
+
+    #[test]
+    fn test_unquoted_variables() {
+        assert_eq!(
+            unquoted_variables("echo $A ${B} \"$C\" '$D' \\$E $1 $(date)"),
+            vec!["A", "B"]
+        );
+    }
+
+    #[test]
+    fn test_program_detection() {
+        assert_eq!(program("FOO=1 sudo -E apt upgrade"), Some("apt"));
+        assert_eq!(program("cd ~/src && make"), None);
+        assert!(binary_exists("sh"));
+        assert!(!binary_exists("updog-definitely-not-installed"));
+    }
+}
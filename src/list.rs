@@ -0,0 +1,108 @@
+use crate::config::Config;
+
+// `updog list`: every entry of the config, with its subcommands and their
+// `description`s, so that ids chosen long ago still explain themselves:
+//
+//   homebrew                Homebrew formulae
+//     homebrew:greedy       Casks, including ones that update themselves
+//     homebrew:all          runs: default, greedy
+//   npm                     actions: doctor
+//   dev                     group: rustup, npm
+pub fn render(config: &Config) -> String {
+    let mut rows: Vec<(String, String)> = Vec::new();
+    for entry in &config.commands {
+        let description = entry.description.clone().unwrap_or_default();
+        let text = if entry.is_group() {
+            let members = format!("group: {}", entry.members.join(", "));
+            match description.as_str() {
+                "" => members,
+                _ => format!("{} ({})", description, members),
+            }
+        } else {
+            with_actions(description, entry.actions.keys())
+        };
+        rows.push((entry.id.clone(), text));
+        for subcommand in &entry.subcommands {
+            let mut description = subcommand.description.clone().unwrap_or_default();
+            if !subcommand.runs.is_empty() {
+                let runs = format!("runs: {}", subcommand.runs.join(", "));
+                description = match description.as_str() {
+                    "" => runs,
+                    _ => format!("{} ({})", description, runs),
+                };
+            }
+            rows.push((
+                format!("  {}:{}", entry.id, subcommand.id),
+                with_actions(description, subcommand.command.actions.keys()),
+            ));
+        }
+    }
+
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    rows.iter()
+        .map(|(name, text)| {
+            let line = format!("{:<width$}  {}", name, text, width = width);
+            format!("{}\n", line.trim_end())
+        })
+        .collect()
+}
+
+// `description`, followed by the `actions` defined there, if any
+fn with_actions<'a>(description: String, actions: impl Iterator<Item = &'a String>) -> String {
+    let actions: Vec<&str> = actions.map(String::as_str).collect();
+    match (description.as_str(), actions.is_empty()) {
+        (_, true) => description,
+        ("", false) => format!("actions: {}", actions.join(", ")),
+        (_, false) => format!("{} (actions: {})", description, actions.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+            commands:
+              - id: homebrew
+                description: Homebrew formulae and casks
+                subcommands:
+                  - id: default
+                    check: brew outdated
+                  - id: greedy
+                    description: Casks, including ones that update themselves
+                    check: brew outdated --cask --greedy
+                  - id: all
+                    runs: [default, greedy]
+              - id: npm
+                check: npm outdated -g
+                actions:
+                  doctor: npm doctor
+              - id: dev
+                type: group
+                members: [npm]
+              - id: all
+                type: group
+                description: Everything but the casks
+                members: [homebrew:default, dev]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            render(&config),
+            "homebrew            Homebrew formulae and casks\n\
+             \x20 homebrew:default\n\
+             \x20 homebrew:greedy   Casks, including ones that update themselves\n\
+             \x20 homebrew:all      runs: default, greedy\n\
+             npm                 actions: doctor\n\
+             dev                 group: npm\n\
+             all                 Everything but the casks (group: homebrew:default, dev)\n"
+        );
+    }
+}
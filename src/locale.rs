@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Locale the managers' commands run in, so that output parsers, status
+// patterns and `failure_regex` see the same untranslated output whatever
+// the user's locale is:
+//
+//   locale: C.UTF-8          # every manager (C by default)
+//   commands:
+//     - id: apt
+//       update: sudo apt-get upgrade -y
+//       locale: native       # keep the user's locale for this one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Locale {
+    // Whatever updog was started with
+    Native,
+    Name(String),
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::Name("C".to_string())
+    }
+}
+
+impl TryFrom<String> for Locale {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        if name == "native" {
+            return Ok(Locale::Native);
+        }
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "._-@".contains(c));
+        match valid {
+            true => Ok(Locale::Name(name)),
+            false => Err(format!(
+                "invalid locale '{}' (a name like C or en_US.UTF-8, or native)",
+                name
+            )),
+        }
+    }
+}
+
+impl From<Locale> for String {
+    fn from(locale: Locale) -> Self {
+        locale.to_string()
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Locale::Native => f.write_str("native"),
+            Locale::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+impl Locale {
+    // Variables for the commands. LANGUAGE is set too, since gettext
+    // prefers it to LC_ALL for messages.
+    pub fn env(&self) -> Vec<(String, String)> {
+        match self {
+            Locale::Native => Vec::new(),
+            Locale::Name(name) => ["LC_ALL", "LANG", "LANGUAGE"]
+                .iter()
+                .map(|key| (key.to_string(), name.clone()))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_env() {
+        let locale: Locale = serde_yaml::from_str("C.UTF-8").unwrap();
+        assert_eq!(
+            locale.env(),
+            [
+                ("LC_ALL".to_string(), "C.UTF-8".to_string()),
+                ("LANG".to_string(), "C.UTF-8".to_string()),
+                ("LANGUAGE".to_string(), "C.UTF-8".to_string()),
+            ]
+        );
+        let native: Locale = serde_yaml::from_str("native").unwrap();
+        assert!(native.env().is_empty());
+        assert_eq!(serde_yaml::to_string(&native).unwrap(), "native\n");
+        assert!(serde_yaml::from_str::<Locale>("'de_DE; rm -rf'").is_err());
+        assert_eq!(Locale::default().to_string(), "C");
+    }
+}
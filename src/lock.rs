@@ -0,0 +1,208 @@
+use std::path::{Path, PathBuf};
+
+// Lock files of system package managers, so that a run can wait for (or skip)
+// a manager another process is using instead of failing with its confusing
+// "could not get lock" error
+//
+//   commands:
+//     - id: apt
+//       update: "sudo apt-get upgrade -y"
+//       lock_wait: 5m         # wait up to this long, then skip as busy
+//       locks: [/var/lib/dpkg/lock-frontend]   # defaults from the commands
+//
+// A lock file counts as held when another process has a lock on it, when it
+// contains the PID of a running process (dnf), or, for *.lck files, when it
+// exists at all (pacman).
+
+// Programs and the lock files they take
+const KNOWN_LOCKS: &[(&[&str], &[&str])] = &[
+    (
+        &["apt", "apt-get", "aptitude", "dpkg", "unattended-upgrade"],
+        &["/var/lib/dpkg/lock-frontend", "/var/lib/dpkg/lock"],
+    ),
+    (&["pacman", "yay", "paru"], &["/var/lib/pacman/db.lck"]),
+    (
+        &["dnf", "yum"],
+        &[
+            "/var/lib/dnf/rpmdb_lock.pid",
+            "/var/cache/dnf/metadata_lock.pid",
+        ],
+    ),
+];
+
+// Lock files used by the programs in `commands`
+pub fn default_locks<'a>(commands: impl IntoIterator<Item = &'a str>) -> Vec<PathBuf> {
+    let mut locks: Vec<PathBuf> = Vec::new();
+    for command in commands {
+        let words = command
+            .split(|c: char| c.is_whitespace() || matches!(c, ';' | '&' | '|' | '('))
+            .map(|word| word.rsplit('/').next().unwrap_or(word));
+        for word in words {
+            let found = KNOWN_LOCKS
+                .iter()
+                .filter(|(programs, _)| programs.contains(&word))
+                .flat_map(|(_, paths)| paths.iter().map(PathBuf::from));
+            for path in found {
+                if !locks.contains(&path) {
+                    locks.push(path);
+                }
+            }
+        }
+    }
+    locks
+}
+
+// The first of `paths` that another process holds
+pub fn find_held(paths: &[PathBuf]) -> Option<&PathBuf> {
+    paths.iter().find(|path| is_held(path))
+}
+
+pub fn is_held(path: &Path) -> bool {
+    if !path.exists() {
+        return false;
+    }
+    if path.extension().is_some_and(|ext| ext == "lck") {
+        return true;
+    }
+    if let Some(pid) = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| content.trim().parse::<u32>().ok())
+    {
+        return pid != std::process::id() && process_running(pid);
+    }
+    locked_by_other(path)
+}
+
+#[cfg(unix)]
+pub fn process_running(pid: u32) -> bool {
+    // Signal 0 only checks that the process exists; EPERM means it does but
+    // belongs to someone else
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+pub fn process_running(_pid: u32) -> bool {
+    false
+}
+
+// Whether another process has a lock on the file. /proc/locks works without
+// read access to the file, which root-owned lock files don't grant.
+#[cfg(target_os = "linux")]
+fn locked_by_other(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    let (Ok(metadata), Ok(locks)) = (
+        std::fs::metadata(path),
+        std::fs::read_to_string("/proc/locks"),
+    ) else {
+        return false;
+    };
+    let dev = metadata.dev();
+    let (major, minor) = unsafe { (libc::major(dev) as u64, libc::minor(dev) as u64) };
+    proc_locks_holds(&locks, (major, minor, metadata.ino()), std::process::id())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn locked_by_other(path: &Path) -> bool {
+    use std::os::unix::io::AsRawFd;
+    let Ok(file) = std::fs::File::open(path) else {
+        return false;
+    };
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = libc::F_WRLCK as _;
+    lock.l_whence = libc::SEEK_SET as _;
+    let result = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETLK, &mut lock) };
+    result == 0 && lock.l_type != libc::F_UNLCK as _
+}
+
+#[cfg(not(unix))]
+fn locked_by_other(_path: &Path) -> bool {
+    false
+}
+
+// Whether a /proc/locks listing has a lock on the file identified by
+// (device major, minor, inode) held by a process other than `own_pid`.
+// Lines look like "1: POSIX  ADVISORY  WRITE 1234 08:02:131090 0 EOF", with
+// the device numbers in hex.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn proc_locks_holds(locks: &str, file: (u64, u64, u64), own_pid: u32) -> bool {
+    locks.lines().any(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        // Waiting locks are listed with "->" after the number
+        let fields = match fields.get(1) {
+            Some(&"->") => &fields[1..],
+            _ => &fields[..],
+        };
+        let (Some(pid), Some(id)) = (fields.get(4), fields.get(5)) else {
+            return false;
+        };
+        let mut parts = id.split(':');
+        let parsed = (
+            parts.next().and_then(|p| u64::from_str_radix(p, 16).ok()),
+            parts.next().and_then(|p| u64::from_str_radix(p, 16).ok()),
+            parts.next().and_then(|p| p.parse::<u64>().ok()),
+        );
+        parsed == (Some(file.0), Some(file.1), Some(file.2))
+            && pid.parse::<u32>().is_ok_and(|pid| pid != own_pid)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_default_locks() {
+        assert_eq!(
+            default_locks(["sudo apt-get update && sudo /usr/bin/apt-get upgrade -y"]),
+            [
+                PathBuf::from("/var/lib/dpkg/lock-frontend"),
+                PathBuf::from("/var/lib/dpkg/lock")
+            ]
+        );
+        assert_eq!(
+            default_locks(["sudo pacman -Syu", "paru -Sua"]),
+            [PathBuf::from("/var/lib/pacman/db.lck")]
+        );
+        assert!(default_locks(["brew upgrade", "apt-cache policy"]).is_empty());
+    }
+
+    #[test]
+    fn test_lock_files() {
+        let dir = tempdir().unwrap();
+        let missing = dir.path().join("db.lck");
+        assert!(!is_held(&missing));
+        std::fs::write(&missing, "").unwrap();
+        assert!(is_held(&missing));
+
+        let pid_file = dir.path().join("rpmdb_lock.pid");
+        std::fs::write(&pid_file, std::process::id().to_string()).unwrap();
+        assert!(!is_held(&pid_file));
+        let mut child = std::process::Command::new("sleep")
+            .arg("5")
+            .spawn()
+            .unwrap();
+        std::fs::write(&pid_file, child.id().to_string()).unwrap();
+        assert!(is_held(&pid_file));
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(!is_held(&pid_file));
+
+        let plain = dir.path().join("lock-frontend");
+        std::fs::write(&plain, "").unwrap();
+        assert!(!is_held(&plain));
+        assert_eq!(find_held(&[plain, missing.clone()]), Some(&missing));
+    }
+
+    #[test]
+    fn test_proc_locks() {
+        let locks = "1: POSIX  ADVISORY  WRITE 4242 08:02:131090 0 EOF\n\
+                     1: -> POSIX  ADVISORY  WRITE 5151 fd:01:77 0 EOF\n\
+                     2: FLOCK  ADVISORY  WRITE 99 08:02:500 0 EOF\n";
+        assert!(proc_locks_holds(locks, (8, 2, 131090), 1));
+        assert!(!proc_locks_holds(locks, (8, 2, 131090), 4242));
+        assert!(proc_locks_holds(locks, (0xfd, 1, 77), 1));
+        assert!(!proc_locks_holds(locks, (8, 2, 501), 1));
+    }
+}
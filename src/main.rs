@@ -1,35 +1,148 @@
-use clap::Parser;
-use std::collections::HashMap;
+use clap::{CommandFactory, Parser};
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::process;
-use tracing::{error, info};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tracing::{error, info, warn};
 use updog::{
-    cli::{Cli, Commands},
-    Config, PackageManager,
+    alert,
+    approval::{self, Approvals, Verdict},
+    audit,
+    badge::{self, Badge},
+    cache::{self, CheckCache},
+    cli::{
+        AuditCommand, Cli, Commands, ConfigCommand, GenerateCommand, HistoryCommand, PluginCommand,
+        ScheduleCommand,
+    },
+    completions,
+    config::{ConfigError, Verbosity},
+    daemon::{self, Daemon},
+    digest,
+    eta::Eta,
+    executor::{CommandRequest, Executor, ShellExecutor},
+    export::{self, ExportFormat},
+    history::{self, History, HistoryConfig},
+    import,
+    journal::Journal,
+    keys::{self, Action, Controls},
+    layer::{self, Origin},
+    lint, list,
+    metadata::RunMetadata,
+    migrate,
+    package_manager::{RunTracker, SequenceResult},
+    plan, plugin,
+    progress::ProgressFormat,
+    redact::{self, RedactingWriter, Redactor},
+    report::{ExecutionReport, ExecutionResult, ExecutionStatus, Operation},
+    schedule::{Pause, ScheduleState},
+    sd_notify::SystemdNotifier,
+    shell_hook, shell_script,
+    state_file::write_atomic,
+    stats,
+    step::shell_quote,
+    systemd, terminal, tui, warnings,
+    watch::Watcher,
+    why, wizard, Config, PackageManager,
 };
 
-// Execution result tracking struct
-struct ExecutionResult {
-    success: bool,
-    message: String,
-}
-
 fn main() {
     let cli = Cli::parse();
 
     // Initialize logging
-    let level = if cli.verbose { "debug" } else { "info" };
-    tracing_subscriber::fmt().with_env_filter(level).init();
+    let level = match cli.verbosity() {
+        Verbosity::Verbose => "debug",
+        Verbosity::Normal => "info",
+        Verbosity::Quiet => "warn",
+    };
+    // Counts and structured summaries are meant for scripts, so keep the
+    // log quiet; shell completion doesn't need the config warnings at all,
+    // and the TUI has the terminal to itself
+    let level = if matches!(
+        cli.command,
+        Commands::Completions { .. } | Commands::Complete { .. } | Commands::Tui
+    ) {
+        "off"
+    } else if cli.command.is_scripted() {
+        "warn"
+    } else {
+        level
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(level)
+        .with_writer(|| RedactingWriter::new(std::io::stderr()))
+        .init();
 
-    // Load configuration
+    // Shell hooks run at every shell start and don't depend on the config
+    if let Commands::Generate {
+        command:
+            GenerateCommand::ShellHook {
+                shell,
+                name,
+                precmd,
+            },
+    } = &cli.command
+    {
+        let config = cli.config.as_deref();
+        print!("{}", shell_hook::script(shell, name, *precmd, config));
+        return;
+    }
+
+    // Load configuration, offering to create it on the first run
+    // A system config serves users without a config of their own
     let config_path = cli.get_config_path();
-    let config = match Config::from_file(&config_path) {
-        Ok(config) => config,
-        Err(e) => {
-            error!("Failed to load config from {:?}: {}", config_path, e);
-            info!("Using default configuration");
-            Config::default()
+    if !config_path.exists()
+        && !layer::system_path().is_some_and(|system| system.is_file())
+        && cli.offers_wizard()
+        && std::io::stdin().is_terminal()
+        && std::io::stdout().is_terminal()
+    {
+        if let Err(e) = wizard::run(&config_path) {
+            warn!("Failed to create config at {:?}: {}", config_path, e);
+        }
+    }
+    let ((config, layers), config_warnings) = warnings::collect(|| {
+        let (mut config, layers) = match load_config(&cli, &config_path) {
+            Some((config, layers)) => {
+                // Missing programs are left to `updog config lint`: configs
+                // shared between machines routinely name tools some don't have
+                if !matches!(cli.command, Commands::Config { .. }) {
+                    for warning in lint::lint(&config, None) {
+                        warnings::warn(warning);
+                    }
+                }
+                for deprecated in migrate::warnings(&config) {
+                    warnings::warn(deprecated);
+                }
+                (config, layers)
+            }
+            None => (Config::default(), Vec::new()),
+        };
+        // `plugin list` runs the handshakes itself
+        if !matches!(cli.command, Commands::Plugin { .. }) {
+            let path_var = std::env::var_os("PATH").unwrap_or_default();
+            for e in plugin::load(&mut config, &path_var) {
+                warnings::warn(e);
+            }
         }
+        (config, layers)
+    });
+    let redactor = Redactor::from_config(&config);
+    let layer_path = |wanted| {
+        layers
+            .iter()
+            .find(|(origin, _)| *origin == wanted)
+            .map(|(_, path)| path.as_path())
     };
+    let mut metadata = RunMetadata::collect(layer_path(Origin::User), layer_path(Origin::System));
+    metadata.invocation = metadata
+        .invocation
+        .iter()
+        .map(|arg| redactor.apply(arg))
+        .collect();
+    redact::set_log_redactor(redactor);
 
     // Check if dry run mode is enabled
     let is_dry_run = cli.command.is_dry_run();
@@ -37,13 +150,18 @@ fn main() {
         info!("Dry run mode - no changes will be made");
     }
 
-    let pm = PackageManager::with_dry_run(config, is_dry_run);
+    let mut pm = PackageManager::with_dry_run(config, is_dry_run);
+    pm.verbosity = cli.verbosity();
+    pm.keyboard_controls = cli.command.keys();
+    pm.config_warnings = config_warnings;
+    // Watch mode prints only what changed, and scripts only get the summary
+    pm.echo_output = !cli.command.is_scripted() && !matches!(cli.command, Commands::Watch { .. });
 
     // Set up cleanup on panic
-    let pm_clone = std::panic::catch_unwind(|| {
-        execute_command(&cli.command, &pm);
+    let pm_clone = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        execute_command(&cli.command, &pm, &metadata);
         pm // Return PackageManager instance on normal exit
-    });
+    }));
 
     // Perform cleanup before exiting
     if let Ok(pm) = pm_clone {
@@ -58,209 +176,1435 @@ fn main() {
     }
 }
 
-fn execute_command(command: &Commands, pm: &PackageManager) {
+// The config at `path`, merged over the system config when there is one
+// (see `layer`), with the layers it was loaded from, or None to use the
+// default one. Without a config file that's what happens; a config that
+// doesn't load stops updog, unless it's fixed in the editor when asked on a
+// terminal, or ignored with --ignore-config-errors. Runs never quietly fall
+// back to the default.
+fn load_config(cli: &Cli, path: &Path) -> Option<(Config, Vec<(Origin, PathBuf)>)> {
+    let system = layer::system_path().filter(|system| system.is_file());
+    loop {
+        // Without a config of their own, users get the system one
+        let user = (cli.config.is_some() || path.exists()).then_some(path);
+        let layers: Vec<(Origin, &Path)> = system
+            .iter()
+            .map(|system| (Origin::System, system.as_path()))
+            .chain(user.map(|user| (Origin::User, user)))
+            .collect();
+        if layers.is_empty() {
+            info!("No config file found, using the default configuration");
+            return None;
+        }
+        let e = match Config::from_layers(&layers) {
+            Ok(config) => {
+                let layers = layers
+                    .iter()
+                    .map(|(origin, path)| (*origin, path.to_path_buf()))
+                    .collect();
+                return Some((config, layers));
+            }
+            Err(e) => e,
+        };
+        // Users can't fix the system config, only an admin can
+        let (failed, e) = match e {
+            ConfigError::Layer { path, source } => (path, *source),
+            e => (
+                layers.last().map_or(path, |(_, path)| path).to_path_buf(),
+                e,
+            ),
+        };
+        let in_system = system.as_ref() == Some(&failed);
+        if cli.ignores_config_errors() {
+            warnings::warn(format_args!(
+                "Ignoring the config at {}, using the default configuration: {}",
+                failed.display(),
+                e
+            ));
+            return None;
+        }
+        error!("Failed to load config from {:?}: {}", failed, e);
+        if !in_system && std::io::stdin().is_terminal() && std::io::stdout().is_terminal() {
+            let editor = wizard::editor();
+            match wizard::ask_to_edit(
+                path,
+                &editor,
+                &mut std::io::stdin().lock(),
+                &mut std::io::stdout(),
+            ) {
+                Ok(true) => {
+                    let command =
+                        format!("{} {}", editor, shell_quote(&path.display().to_string()));
+                    match ShellExecutor::new().run(&CommandRequest::new(&command)) {
+                        Ok(output) if output.success() => continue,
+                        Ok(output) => {
+                            error!("{} exited with {}", editor, output.status_description())
+                        }
+                        Err(e) => error!("Failed to run {}: {}", editor, e),
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => error!("{}", e),
+            }
+        }
+        match in_system {
+            true => eprintln!(
+                "Ask an admin to fix the system config, or run with --ignore-config-errors \
+                 to use the default configuration"
+            ),
+            false => eprintln!(
+                "Fix the config, or run with --ignore-config-errors to use the default configuration"
+            ),
+        }
+        process::exit(1);
+    }
+}
+
+fn execute_command(command: &Commands, pm: &PackageManager, metadata: &RunMetadata) {
+    if command.is_scheduled() {
+        match Pause::load_active(&Pause::default_path(), pm.clock().now()) {
+            Ok(Some(_)) => {
+                info!("Scheduled runs are paused, skipping");
+                return;
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Ignoring pause file: {}", e),
+        }
+    }
+    if command.requires_approval() {
+        require_approved_config(pm, metadata);
+    }
+
     match command {
         Commands::Check {
-            package_manager, ..
+            count,
+            by_manager,
+            write_badge,
+            ..
+        } => {
+            let report = run_operation(command, pm, Operation::Check, metadata);
+
+            // Print summary of execution result (or just the counts)
+            if *count {
+                print_counts(&report, *by_manager);
+            } else {
+                print!("{}", report.render(command.output(), terminal::width()));
+            }
+            write_summary_file(command, &report);
+            record_history(pm, &report);
+            refresh_check_cache(pm, &report);
+            if let Some(path) = write_badge {
+                refresh_badge(pm, path);
+            }
+            exit_with(&report);
+        }
+
+        Commands::Update { .. } => {
+            let report = run_operation(command, pm, Operation::Update, metadata);
+
+            // Print summary of execution result
+            print!("{}", report.render(command.output(), terminal::width()));
+            write_summary_file(command, &report);
+            record_history(pm, &report);
+            refresh_check_cache(pm, &report);
+            exit_with(&report);
+        }
+
+        Commands::Stats {
+            package_manager,
+            operation,
         } => {
-            // If specific package manager is provided, use it. Otherwise, use all available
-            let execution_items = match package_manager {
-                Some(_) => {
-                    // Parse package manager to get manager name and subcommand
-                    let parsed = command.parse_package_manager().unwrap();
-                    vec![parsed]
+            let runs = match History::new(History::default_path()).load() {
+                Ok(runs) => runs,
+                Err(e) => {
+                    error!("{}", e);
+                    process::exit(1);
                 }
-                None => {
-                    // Use all package managers with their default subcommands
-                    pm.config
-                        .commands
-                        .iter()
-                        .map(|pm_config| (pm_config.id.clone(), None))
-                        .collect()
+            };
+
+            let mut manager_stats = stats::compute(&runs, operation.as_deref());
+            if let Some(name) = package_manager {
+                // Match both "manager" and "manager:subcommand" entries
+                manager_stats
+                    .retain(|s| s.name == *name || s.name.starts_with(&format!("{}:", name)));
+            }
+            print!("{}", stats::render(&manager_stats));
+        }
+
+        Commands::Status { format } => {
+            let runs = match History::new(History::default_path()).load() {
+                Ok(runs) => runs,
+                Err(e) => {
+                    error!("{}", e);
+                    process::exit(1);
                 }
             };
+            let names: Vec<String> = pm.config.managers().map(|m| m.id.clone()).collect();
+            let statuses = stats::status(&runs, &names);
+            let timezone = pm.config.daemon.timezone;
+            let output = match format.as_str() {
+                "waybar" => badge::waybar(&statuses, timezone),
+                "polybar" => badge::polybar(&statuses, timezone),
+                _ => {
+                    let mut out =
+                        stats::render_status(&statuses, pm.config.alerts.escalate_after, timezone);
+                    // A run in progress, or one that crashed
+                    match Journal::load(&Journal::default_path()) {
+                        Ok(Some(journal)) => {
+                            out.push('\n');
+                            out.push_str(&journal.render(journal.in_progress(), timezone));
+                        }
+                        Ok(None) => {}
+                        Err(e) => warn!("{}", e),
+                    }
+                    out
+                }
+            };
+            print!("{}", output);
+        }
 
-            let mut has_error = false;
-            let mut results = HashMap::new();
+        Commands::List => print!("{}", list::render(&pm.config)),
 
-            for (manager_name, subcommand) in &execution_items {
-                // Display name for logs and results
-                let display_name = match &subcommand {
-                    Some(sc) => format!("{}:{}", manager_name, sc),
-                    None => manager_name.clone(),
-                };
+        Commands::ExportScript { out, .. } => {
+            let items = match command.selection().resolve(&pm.config) {
+                Ok(items) => items,
+                Err(e) => {
+                    error!("{}", e);
+                    process::exit(1);
+                }
+            };
+            let script = shell_script::render(&pm.config, &items, pm.verbosity);
+            for warning in &script.warnings {
+                warn!("{}", warning);
+            }
+            match out {
+                Some(path) => {
+                    if let Err(e) = shell_script::write(path, &script.text) {
+                        error!("Failed to write {}: {}", path.display(), e);
+                        process::exit(1);
+                    }
+                    info!("Wrote {}", path.display());
+                }
+                None => print!("{}", script.text),
+            }
+        }
+
+        Commands::Why { package } => {
+            let runs = match History::new(History::default_path()).load() {
+                Ok(runs) => runs,
+                Err(e) => {
+                    error!("{}", e);
+                    process::exit(1);
+                }
+            };
+            let infos = why::lookup(&runs, package);
+            print!(
+                "{}",
+                why::render(package, &infos, pm.config.daemon.timezone)
+            );
+            if infos.is_empty() {
+                process::exit(1);
+            }
+        }
 
-                info!("Checking updates for {}", display_name);
+        Commands::History {
+            limit,
+            command: None,
+        } => {
+            let runs = match History::new(History::default_path()).load() {
+                Ok(runs) => runs,
+                Err(e) => {
+                    error!("{}", e);
+                    process::exit(1);
+                }
+            };
+            let recent = &runs[runs.len().saturating_sub(*limit)..];
+            print!(
+                "{}",
+                history::render_runs(recent, pm.config.daemon.timezone)
+            );
+        }
 
-                // Execute check command with the appropriate subcommand
-                let result = match &subcommand {
-                    Some(sc) => pm.check_with_subcommand(&manager_name, Some(sc)),
-                    None => pm.check(&manager_name),
-                };
+        Commands::History {
+            command: Some(HistoryCommand::Show { run_id }),
+            ..
+        } => {
+            let runs = match History::new(History::default_path()).load() {
+                Ok(runs) => runs,
+                Err(e) => {
+                    error!("{}", e);
+                    process::exit(1);
+                }
+            };
+            let run = match history::find_run(&runs, run_id) {
+                Ok(run) => run,
+                Err(e) => {
+                    error!("{}", e);
+                    process::exit(1);
+                }
+            };
+            match serde_yaml::to_string(run) {
+                Ok(yaml) => print!("{}", yaml),
+                Err(e) => {
+                    error!("Failed to show run {}: {}", run_id, e);
+                    process::exit(1);
+                }
+            }
+        }
 
-                match result {
-                    Ok(_) => {
-                        results.insert(
-                            display_name,
-                            ExecutionResult {
-                                success: true,
-                                message: "Successfully checked for updates".to_string(),
-                            },
-                        );
+        Commands::History {
+            command: Some(HistoryCommand::Export { format, output }),
+            ..
+        } => {
+            let content = History::new(History::default_path())
+                .load()
+                .and_then(|runs| export::export(&runs, *format));
+            let content = match content {
+                Ok(content) => content,
+                Err(e) => {
+                    error!("{}", e);
+                    process::exit(1);
+                }
+            };
+            match output {
+                Some(path) => {
+                    if let Err(e) = std::fs::write(path, content) {
+                        error!("Failed to write {:?}: {}", path, e);
+                        process::exit(1);
+                    }
+                }
+                None => print!("{}", content),
+            }
+        }
+
+        Commands::History {
+            command: Some(HistoryCommand::Import { file, format }),
+            ..
+        } => {
+            let format = format
+                .or_else(|| ExportFormat::from_path(file))
+                .unwrap_or(ExportFormat::Json);
+            let content = match std::fs::read_to_string(file) {
+                Ok(content) => content,
+                Err(e) => {
+                    error!("Failed to read {:?}: {}", file, e);
+                    process::exit(1);
+                }
+            };
+            let history = History::new(History::default_path());
+            match export::import(&content, format).and_then(|runs| {
+                let total = runs.len();
+                history.merge(runs).map(|added| (added, total))
+            }) {
+                Ok((added, total)) => println!(
+                    "Imported {} of {} runs ({} already present)",
+                    added,
+                    total,
+                    total - added
+                ),
+                Err(e) => {
+                    error!("Failed to import {:?}: {}", file, e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        Commands::History {
+            command:
+                Some(HistoryCommand::Prune {
+                    keep_runs,
+                    keep_days,
+                }),
+            ..
+        } => {
+            let retention = HistoryConfig {
+                keep_runs: keep_runs.or(pm.config.history.keep_runs),
+                keep_days: keep_days.or(pm.config.history.keep_days),
+            };
+            if retention.is_unlimited() {
+                println!("No retention configured (set history.keep_runs or history.keep_days)");
+                return;
+            }
+            match History::new(History::default_path()).prune(&retention, pm.clock().now()) {
+                Ok(removed) => println!("Removed {} runs from history", removed),
+                Err(e) => {
+                    error!("{}", e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        Commands::Daemon { write_badge, .. } => {
+            let mut daemon = match Daemon::new(pm, ScheduleState::default_path()) {
+                Ok(daemon) => daemon,
+                Err(e) => {
+                    error!("{}", e);
+                    process::exit(1);
+                }
+            };
+            daemon.metadata = Some(metadata.clone());
+            daemon.notifier = SystemdNotifier::from_env();
+            let selection = command.selection();
+            if !selection.is_everything() {
+                match selection.resolve(&pm.config) {
+                    Ok(mut items) => {
+                        if pm.config.daemon.operation == Operation::Update {
+                            selection.leave_out_manual(&pm.config, &mut items);
+                        }
+                        daemon.selected = Some(items.into_iter().map(|(id, _)| id).collect())
                     }
                     Err(e) => {
-                        error!("{}: {}", manager_name, e);
-                        results.insert(
-                            display_name,
-                            ExecutionResult {
-                                success: false,
-                                message: format!("Error: {}", e),
-                            },
-                        );
-                        has_error = true;
+                        error!("{}", e);
+                        process::exit(1);
                     }
                 }
+            }
+            daemon.run(
+                |report| {
+                    print!("{}", report.render_summary(terminal::width()));
+                    record_history(pm, report);
+                    refresh_check_cache(pm, report);
+                    prune_history(pm);
+                    if let Some(path) = write_badge {
+                        refresh_badge(pm, path);
+                    }
+                },
+                |since| send_digest(pm, since),
+            );
+        }
 
-                // Add visual separator between package managers
-                if execution_items.len() > 1 {
-                    println!("\n----------------------------------------------\n");
-                }
+        Commands::Run { action, .. } => {
+            let Some((manager, subcommand)) = command.parse_package_manager() else {
+                return;
+            };
+            let result = pm
+                .run_action(&manager, subcommand.as_deref(), action)
+                .and_then(SequenceResult::into_output);
+            if let Err(e) = result {
+                error!("{}", e.message);
+                process::exit(1);
             }
+        }
 
-            // Print summary of execution result
-            print_summary("Check", &results);
+        Commands::Watch {
+            interval,
+            exit_on_change,
+            ..
+        } => {
+            let Some((manager, subcommand)) = command.parse_package_manager() else {
+                return;
+            };
+            let timezone = pm.config.daemon.timezone;
+            info!("Watching {} every {}", manager, interval);
+            Watcher::new(pm, manager, subcommand).run(interval.duration(), |event| {
+                let time = timezone.civil(pm.clock().now()).to_string();
+                print!("{}", event.render(&time));
+                !(*exit_on_change && event.is_change())
+            });
+        }
 
-            if has_error {
+        Commands::Pause { duration } => {
+            let now = pm.clock().now();
+            let pause = Pause::new(now, duration.map(|d| d.duration()));
+            if let Err(e) = pause.save(&Pause::default_path()) {
+                error!("{}", e);
                 process::exit(1);
             }
+            match duration {
+                Some(duration) => println!(
+                    "Scheduled runs paused for {} (until {})",
+                    duration,
+                    pm.config.daemon.timezone.civil(now + duration.duration())
+                ),
+                None => println!("Scheduled runs paused until `updog resume`"),
+            }
         }
 
-        Commands::Update {
-            package_manager, ..
+        Commands::Resume => match Pause::clear(&Pause::default_path()) {
+            Ok(true) => println!("Scheduled runs resumed"),
+            Ok(false) => println!("Scheduled runs were not paused"),
+            Err(e) => {
+                error!("{}", e);
+                process::exit(1);
+            }
+        },
+
+        Commands::Config {
+            command: ConfigCommand::Lint { shellcheck },
         } => {
-            // If specific package manager is provided, use it. Otherwise, use all available
-            let execution_items = match package_manager {
-                Some(_) => {
-                    // Parse package manager to get manager name and subcommand
-                    let parsed = command.parse_package_manager().unwrap();
-                    vec![parsed]
+            let mut warnings = lint::lint(&pm.config, Some(&lint::binary_exists));
+            if *shellcheck {
+                match lint::shellcheck(&pm.config) {
+                    Ok(found) => warnings.extend(found),
+                    Err(e) => {
+                        error!("{}", e);
+                        process::exit(1);
+                    }
                 }
-                None => {
-                    // Use all package managers with their default subcommands
-                    pm.config
-                        .commands
-                        .iter()
-                        .map(|pm_config| (pm_config.id.clone(), None))
-                        .collect()
+            }
+            for warning in &warnings {
+                println!("{}", warning);
+            }
+            if !warnings.is_empty() {
+                process::exit(1);
+            }
+            println!("No problems found");
+        }
+
+        Commands::Audit {
+            command: AuditCommand::Verify,
+        } => {
+            let path = pm.config.audit.path();
+            match audit::verify(&path) {
+                Ok(count) => println!("{}: {} entries, no problems found", path.display(), count),
+                Err(e) => {
+                    error!("{}: {}", path.display(), e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        Commands::Plugin {
+            command: PluginCommand::List,
+        } => {
+            let path_var = std::env::var_os("PATH").unwrap_or_default();
+            let plugins = plugin::discover(&path_var);
+            for plugin in &plugins {
+                let state = if pm.config.plugins.contains(&plugin.name) {
+                    "enabled"
+                } else {
+                    "disabled"
+                };
+                match plugin.handshake() {
+                    Ok(handshake) => {
+                        let version = handshake
+                            .version
+                            .as_deref()
+                            .map_or(String::new(), |v| format!(" {}", v));
+                        println!("{}{} ({})", plugin.name, version, state);
+                        if let Some(description) = &handshake.description {
+                            println!("  {}", description);
+                        }
+                        println!("  provides: {}", handshake.provides().join(", "));
+                    }
+                    Err(e) => println!("{} ({})\n  {}", plugin.name, state, e),
+                }
+                println!("  path: {}", plugin.path.display());
+            }
+            for name in &pm.config.plugins {
+                if !plugins.iter().any(|p| &p.name == name) {
+                    println!("{} (enabled)\n  not found on PATH", name);
+                }
+            }
+            if plugins.is_empty() && pm.config.plugins.is_empty() {
+                println!("No plugins found on PATH");
+            }
+        }
+
+        Commands::Config {
+            command: ConfigCommand::Show { origin },
+        } => {
+            let layers: Vec<(Origin, &Path)> = [
+                (Origin::System, &metadata.system_config),
+                (Origin::User, &metadata.config_path),
+            ]
+            .into_iter()
+            .filter_map(|(origin, path)| Some((origin, Path::new(path.as_deref()?))))
+            .collect();
+            if layers.is_empty() {
+                eprintln!("No config file was loaded; this is the default configuration");
+                print!("{}", serde_yaml::to_string(&pm.config).unwrap_or_default());
+                return;
+            }
+            let read = layers
+                .iter()
+                .map(|(origin, path)| Ok((*origin, layer::read(path)?)))
+                .collect::<Result<Vec<_>, ConfigError>>();
+            let (merged, provenance) = match read {
+                Ok(read) => layer::merge(read),
+                Err(e) => {
+                    error!("{}", e);
+                    process::exit(1);
                 }
             };
+            match origin {
+                true => print!("{}", layer::describe(&layers, &provenance)),
+                false => print!("{}", serde_yaml::to_string(&merged).unwrap_or_default()),
+            }
+        }
 
-            let mut has_error = false;
-            let mut results = HashMap::new();
+        Commands::Config {
+            command: ConfigCommand::Approve,
+        } => {
+            let (Some(config_path), Some(hash)) = (&metadata.config_path, &metadata.config_hash)
+            else {
+                error!("No config file was loaded, so there is nothing to approve");
+                process::exit(1);
+            };
+            let path = Approvals::default_path();
+            let result = Approvals::load(&path).and_then(|mut approvals| {
+                approvals.approve(config_path, hash, pm.clock().now());
+                approvals.confirm_commands(config_path, &approval::command_hashes(&pm.config));
+                approvals.save(&path)
+            });
+            if let Err(e) = result {
+                error!("{}", e);
+                process::exit(1);
+            }
+            println!("Approved {} ({})", config_path, &hash[..12]);
+        }
 
-            for (manager_name, subcommand) in &execution_items {
-                // Display name for logs and results
-                let display_name = match &subcommand {
-                    Some(sc) => format!("{}:{}", manager_name, sc),
-                    None => manager_name.clone(),
-                };
+        Commands::Config {
+            command: ConfigCommand::Import { path, out, .. },
+        } => {
+            let imported = match import::from_topgrade_file(path, &lint::binary_exists) {
+                Ok(imported) => imported,
+                Err(e) => {
+                    error!("{}", e);
+                    process::exit(1);
+                }
+            };
+            for item in &imported.unmapped {
+                warn!("Not imported: {}", item);
+            }
+            let config = import::render_config(&imported, path);
+            print_or_write(&config, out.as_deref());
+        }
 
-                info!("Updating {}", display_name);
+        Commands::Config {
+            command: ConfigCommand::Migrate { out },
+        } => {
+            let Some(config_path) = &metadata.config_path else {
+                error!("No config file was loaded, so there is nothing to migrate");
+                process::exit(1);
+            };
+            if pm.config.version() == migrate::CONFIG_VERSION {
+                println!(
+                    "{} already uses config version {}",
+                    config_path,
+                    migrate::CONFIG_VERSION
+                );
+                return;
+            }
+            let migrated = std::fs::read_to_string(config_path)
+                .map_err(ConfigError::from)
+                .and_then(|content| migrate::migrate(&content, Path::new(config_path)));
+            match migrated {
+                Ok(config) => print_or_write(&config, out.as_deref()),
+                Err(e) => {
+                    error!("{}", e);
+                    process::exit(1);
+                }
+            }
+        }
 
-                // Execute update command with the appropriate subcommand
-                let result = match &subcommand {
-                    Some(sc) => pm.update_with_subcommand(&manager_name, Some(sc)),
-                    None => pm.update(&manager_name),
-                };
+        Commands::Schedule {
+            command: ScheduleCommand::Next,
+        } => {
+            let daemon = match Daemon::new(pm, ScheduleState::default_path()) {
+                Ok(daemon) => daemon,
+                Err(e) => {
+                    error!("{}", e);
+                    process::exit(1);
+                }
+            };
+            let now = pm.clock().now();
+            print!(
+                "{}",
+                daemon::render_schedule(
+                    &daemon.schedule(now),
+                    now,
+                    pm.config.daemon.timezone,
+                    terminal::width()
+                )
+            );
+        }
 
-                match result {
-                    Ok(_) => {
-                        results.insert(
-                            display_name,
-                            ExecutionResult {
-                                success: true,
-                                message: "Successfully updated".to_string(),
-                            },
-                        );
+        Commands::Schedule {
+            command: ScheduleCommand::Systemd { dir },
+        } => {
+            let exe = std::env::current_exe().unwrap_or_else(|_| "updog".into());
+            let config_path = metadata.config_path.as_ref().map(std::path::Path::new);
+            let units = systemd::generate(&pm.config, &exe, config_path);
+            match dir {
+                Some(dir) => match systemd::write_units(&units, dir) {
+                    Ok(paths) => {
+                        for path in paths {
+                            println!("Wrote {}", path.display());
+                        }
                     }
                     Err(e) => {
-                        error!("{}: {}", manager_name, e);
-                        results.insert(
-                            display_name,
-                            ExecutionResult {
-                                success: false,
-                                message: format!("Error: {}", e),
-                            },
-                        );
-                        has_error = true;
+                        error!("Failed to write systemd units to {:?}: {}", dir, e);
+                        process::exit(1);
+                    }
+                },
+                None => {
+                    for unit in units {
+                        println!("# {}\n{}", unit.name, unit.contents);
                     }
                 }
+            }
+        }
+
+        Commands::Completions { shell } => {
+            print!("{}", completions::script(shell, &Cli::command()));
+        }
 
-                // Add visual separator between package managers
-                if execution_items.len() > 1 {
-                    println!("\n----------------------------------------------\n");
+        Commands::Generate { .. } => unreachable!("generated before loading the config"),
+
+        Commands::Complete { descriptions, .. } => {
+            for (candidate, description) in completions::managers(&pm.config) {
+                if *descriptions && !description.is_empty() {
+                    println!("{}\t{}", candidate, description);
+                } else {
+                    println!("{}", candidate);
                 }
             }
+        }
 
-            // Print summary of execution result
-            print_summary("Update", &results);
+        Commands::Tui => {
+            if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+                eprintln!("updog tui needs a terminal");
+                process::exit(1);
+            }
+            let on_report = |report: &ExecutionReport| {
+                record_history(pm, report);
+                refresh_check_cache(pm, report);
+            };
+            if let Err(e) = tui::run(pm, &on_report) {
+                eprintln!("updog tui: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+// Print a generated config, or write it to `out` (a new file)
+fn print_or_write(config: &str, out: Option<&Path>) {
+    let Some(out) = out else {
+        print!("{}", config);
+        return;
+    };
+    if out.exists() {
+        error!("{} already exists, not overwriting it", out.display());
+        process::exit(1);
+    }
+    if let Err(e) = std::fs::write(out, config) {
+        error!("Failed to write {}: {}", out.display(), e);
+        process::exit(1);
+    }
+    info!("Wrote {}", out.display());
+}
 
-            if has_error {
+// Run check or update for the selected package managers
+fn run_operation(
+    command: &Commands,
+    pm: &PackageManager,
+    operation: Operation,
+    metadata: &RunMetadata,
+) -> ExecutionReport {
+    // Selected package managers, or all of them by default (but for updates,
+    // `manual` ones only when named); with --resume, the ones an interrupted
+    // run didn't finish, in their original order
+    let mut manual = Vec::new();
+    let (mut execution_items, selection_warnings) = warnings::collect(|| {
+        if command.resume() {
+            return resume_items(pm, operation);
+        }
+        let selection = command.selection();
+        let mut items = match selection.resolve(&pm.config) {
+            Ok(items) => items,
+            Err(e) => {
+                error!("{}", e);
                 process::exit(1);
             }
+        };
+        if operation == Operation::Update {
+            manual = selection.leave_out_manual(&pm.config, &mut items);
         }
+        let order = command.order();
+        let durations = if order.uses_durations() {
+            typical_durations(operation)
+        } else {
+            Default::default()
+        };
+        order.sort(&mut items, &durations);
+        items
+    });
 
-        Commands::Tui => {
-            info!("TUI mode not implemented yet");
+    if command.keys() && !std::io::stdin().is_terminal() {
+        error!("--keys needs a terminal to read keys from");
+        process::exit(1);
+    }
+
+    let mut report = ExecutionReport::new(operation, pm.clock().now());
+    let pm = &pm.for_run(&report.run_id);
+    info!("Starting {} run {}", operation, report.run_id);
+    report.metadata = Some(metadata.clone());
+    report.note = command.note().map(str::to_string);
+    report.categories = pm.config.categories();
+    report.warnings = [pm.config_warnings.clone(), selection_warnings].concat();
+    report.manual = manual;
+    confirm_changed_commands(command, pm, metadata, &mut execution_items, &mut report);
+
+    // With a check phase, managers whose check found nothing aren't updated,
+    // nor, with --plan, the ones the user leaves out
+    if let Some(jobs) = check_phase_jobs(command, pm, operation) {
+        if command.plan() && !std::io::stdin().is_terminal() {
+            error!(
+                "--plan needs a terminal to ask on; use --check-parallel to update without asking"
+            );
+            process::exit(1);
         }
+        let checks = check_before_update(command, pm, metadata, &execution_items, jobs);
+        report.checks = checks.clone();
+        // In dry run mode no check really ran, so none is up to date
+        let mut skips: Vec<Option<(&str, &str)>> = checks
+            .iter()
+            .map(|check| {
+                (!pm.dry_run && check.pending() == Some(0))
+                    .then_some(("Up to date", "Up to date (checked before updating)"))
+            })
+            .collect();
+        if command.plan() {
+            confirm_plan(&checks, &mut skips);
+        }
+        let (skipped, remaining) = execution_items
+            .into_iter()
+            .zip(skips)
+            .partition::<Vec<_>, _>(|(_, skip)| skip.is_some());
+        execution_items = remaining.into_iter().map(|(item, _)| item).collect();
+        report
+            .results
+            .extend(skipped.into_iter().map(|((manager, subcommand), skip)| {
+                let (reason, message) = skip.unwrap_or_default();
+                ExecutionResult::skipped(manager, subcommand, reason, message.to_string())
+            }));
     }
+
+    // Managers with a `cooldown` reuse recent checks
+    let cache = (matches!(operation, Operation::Check))
+        .then(|| check_cache(command, pm, &execution_items))
+        .flatten();
+
+    // Journal the run so that a crash leaves a trace (skipped in dry run mode)
+    let journal =
+        (!pm.dry_run).then(|| Journal::start(operation, &execution_items, pm.clock().now()));
+    let tracker = Mutex::new(Tracker::new(
+        operation,
+        &report.run_id,
+        &execution_items,
+        command.progress(),
+        journal,
+    ));
+    let jobs = match (operation, command.jobs()) {
+        (_, Some(jobs)) => jobs.max(1),
+        (Operation::Check, None) => pm.config.concurrency.check_jobs(),
+        (Operation::Update, None) => 1,
+    };
+    let controls = command.keys().then(Controls::default);
+    let done = AtomicBool::new(false);
+    let results = std::thread::scope(|scope| {
+        if let Some(controls) = &controls {
+            eprintln!("Keys: s skips the running manager, p pauses, q stops");
+            scope.spawn(|| {
+                keys::read_keys(&done, |key| {
+                    let Some(action) = Action::from_key(key) else {
+                        return;
+                    };
+                    eprintln!("{}", controls.press(action));
+                    if action == Action::Skip {
+                        pm.interrupt();
+                    }
+                })
+            });
+        }
+        let results = pm.run_all(
+            operation,
+            &execution_items,
+            jobs,
+            cache.as_ref(),
+            &tracker,
+            controls.as_ref(),
+        );
+        done.store(true, Ordering::SeqCst);
+        results
+    });
+    report.results.splice(0..0, results);
+    tracker.into_inner().unwrap().close();
+    report
 }
 
-// Print summary of execution result
-fn print_summary(operation: &str, results: &HashMap<String, ExecutionResult>) {
-    println!("\n{} Summary:", operation);
-    println!("==============================================");
+// Progress lines, log messages and the journal of the managers a run goes
+// through, which may finish in any order when they run concurrently
+struct Tracker<'a> {
+    operation: Operation,
+    items: &'a [(String, Option<String>)],
+    progress: Option<ProgressFormat>,
+    done: usize,
+    // Time left, from history, when progress lines are printed
+    eta: Option<Eta>,
+    journal: Option<Journal>,
+    journal_path: PathBuf,
+}
 
-    let mut success_count = 0;
-    let mut failure_count = 0;
+impl<'a> Tracker<'a> {
+    fn new(
+        operation: Operation,
+        run_id: &str,
+        items: &'a [(String, Option<String>)],
+        progress: Option<ProgressFormat>,
+        journal: Option<Journal>,
+    ) -> Self {
+        if let Some(format) = progress {
+            eprintln!("{}", format.begin(operation, run_id, items.len()));
+        }
+        let journal_path = Journal::default_path();
+        if let Some(journal) = &journal {
+            write_journal(journal, &journal_path);
+        }
+        let eta = progress.map(|_| Eta::new(items, &typical_durations(operation)));
+        Self {
+            operation,
+            items,
+            progress,
+            done: 0,
+            eta,
+            journal,
+            journal_path,
+        }
+    }
 
-    // Calculate successful items
-    for (_, result) in results.iter() {
-        if result.success {
-            success_count += 1;
-        } else {
-            failure_count += 1;
+    fn report(&mut self, index: usize, result: &ExecutionResult) {
+        match &result.status {
+            ExecutionStatus::Failed
+            | ExecutionStatus::TimedOut
+            | ExecutionStatus::VerifyFailed
+            | ExecutionStatus::Unreachable => error!("{}: {}", result.name(), result.message),
+            ExecutionStatus::Skipped { reason } => info!("{}: {}", result.name(), reason),
+            _ => {}
+        }
+        self.done += 1;
+        if let (Some(format), Some(eta)) = (self.progress, self.eta.as_mut()) {
+            let estimate = eta.finish(index, SystemTime::now());
+            eprintln!(
+                "{}",
+                format.done(self.done, self.items.len(), result, &estimate)
+            );
         }
     }
 
-    // Print successful items if there are any
-    if success_count > 0 {
-        println!("✅ Successful:");
-        for (name, result) in results.iter() {
-            if result.success {
-                println!("  - {}: {}", name, result.message);
+    // A run that got through everything leaves no journal; cancelled
+    // managers keep it around for --resume
+    fn close(self) {
+        if self.journal.as_ref().is_some_and(Journal::is_complete) {
+            if let Err(e) = Journal::remove(&self.journal_path) {
+                warn!("Failed to remove run journal: {}", e);
             }
         }
     }
+}
+
+impl RunTracker for Tracker<'_> {
+    fn start(&mut self, index: usize) {
+        let (manager_name, subcommand) = &self.items[index];
+        if let (Some(format), Some(eta)) = (self.progress, self.eta.as_mut()) {
+            let name = match subcommand {
+                Some(sc) => format!("{}:{}", manager_name, sc),
+                None => manager_name.clone(),
+            };
+            let estimate = eta.start(index, SystemTime::now());
+            eprintln!(
+                "{}",
+                format.start(
+                    self.operation,
+                    index + 1,
+                    self.items.len(),
+                    &name,
+                    &estimate
+                )
+            );
+        }
+        match self.operation {
+            Operation::Check => info!("Checking updates for {}", manager_name),
+            Operation::Update => info!("Updating {}", manager_name),
+        }
+        if let Some(journal) = self.journal.as_mut() {
+            journal.begin(index);
+            write_journal(journal, &self.journal_path);
+        }
+    }
+
+    fn finish(&mut self, index: usize, result: &ExecutionResult) {
+        self.report(index, result);
+        if let Some(journal) = self.journal.as_mut() {
+            journal.finish(index, &result.status);
+            write_journal(journal, &self.journal_path);
+        }
+    }
+
+    // A manager the run stopped before; the journal keeps it for --resume
+    fn leave(&mut self, index: usize, result: &ExecutionResult) {
+        self.report(index, result);
+    }
+}
 
-    // Print failed items if there are any
-    if failure_count > 0 {
-        if success_count > 0 {
-            println!(); // Add empty line between success and failure items
+// Checks to run at once before an update run's updates, if it checks first
+// (`--check-parallel`, `--plan` or `concurrency.check_before_update`)
+fn check_phase_jobs(
+    command: &Commands,
+    pm: &PackageManager,
+    operation: Operation,
+) -> Option<usize> {
+    if operation != Operation::Update || command.resume() {
+        return None;
+    }
+    let concurrency = &pm.config.concurrency;
+    match command.check_parallel() {
+        // No number given: `concurrency.checks`, or all at once
+        Some(0) if concurrency.checks > 1 => Some(concurrency.checks),
+        Some(0) => Some(usize::MAX),
+        Some(jobs) => Some(jobs),
+        None => {
+            (command.plan() || concurrency.check_before_update).then(|| concurrency.check_jobs())
+        }
+    }
+}
+
+// The check phase of an update run: check every manager, `jobs` at a time,
+// and record the checks like `updog check` does. With --plan, the checks'
+// output and summary are left to the plan.
+fn check_before_update(
+    command: &Commands,
+    pm: &PackageManager,
+    metadata: &RunMetadata,
+    items: &[(String, Option<String>)],
+    jobs: usize,
+) -> Vec<ExecutionResult> {
+    let mut report = ExecutionReport::new(Operation::Check, pm.clock().now());
+    let pm = &pm.for_run(&report.run_id);
+    info!("Starting check run {} before updating", report.run_id);
+    report.metadata = Some(metadata.clone());
+    report.categories = pm.config.categories();
+    let cache = check_cache(command, pm, items);
+    let tracker = Mutex::new(Tracker::new(
+        Operation::Check,
+        &report.run_id,
+        items,
+        command.progress(),
+        None,
+    ));
+    let quiet = command.plan().then(|| {
+        let mut quiet = pm.clone();
+        quiet.echo_output = false;
+        quiet
+    });
+    let runner = quiet.as_ref().unwrap_or(pm);
+    report.results = runner.run_all(
+        Operation::Check,
+        items,
+        jobs,
+        cache.as_ref(),
+        &tracker,
+        None,
+    );
+    // A structured summary carries the checks in the update run's document
+    if !command.plan() && !command.is_scripted() {
+        print!("{}", report.render_summary(terminal::width()));
+    }
+    record_history(pm, &report);
+    refresh_check_cache(pm, &report);
+    report.results
+}
+
+// `updog update --plan`: show what the check phase found for the managers
+// left to update and ask which of them to update. Cancelling ends the run.
+fn confirm_plan(checks: &[ExecutionResult], skips: &mut [Option<(&str, &str)>]) {
+    let planned: Vec<usize> = (0..checks.len()).filter(|&i| skips[i].is_none()).collect();
+    if planned.is_empty() {
+        println!("Everything is up to date.");
+        return;
+    }
+    let shown: Vec<&ExecutionResult> = planned.iter().map(|&i| &checks[i]).collect();
+    let stdin = std::io::stdin();
+    match plan::confirm(&shown, &mut stdin.lock(), &mut std::io::stdout()) {
+        Ok(Some(chosen)) => {
+            for (&index, chosen) in planned.iter().zip(chosen) {
+                if !chosen {
+                    skips[index] = Some(("Not in the plan", "Left out of the plan"));
+                }
+            }
+        }
+        Ok(None) => {
+            println!("\nCancelled; nothing was updated.");
+            process::exit(0);
+        }
+        Err(e) => {
+            error!("Failed to read the plan confirmation: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+// The check cache, if any of `items` has a `cooldown` and it may be used
+fn check_cache(
+    command: &Commands,
+    pm: &PackageManager,
+    items: &[(String, Option<String>)],
+) -> Option<CheckCache> {
+    let wanted = !pm.dry_run
+        && !command.refresh()
+        && items
+            .iter()
+            .any(|(m, _)| cache::cooldown(&pm.config, m).is_some());
+    wanted.then(load_check_cache)
+}
+
+fn write_journal(journal: &Journal, path: &std::path::Path) {
+    if let Err(e) = journal.save(path) {
+        warn!("Failed to write run journal: {}", e);
+    }
+}
+
+// Managers the journal of an interrupted `operation` run didn't finish
+fn resume_items(pm: &PackageManager, operation: Operation) -> Vec<(String, Option<String>)> {
+    let journal = match Journal::load(&Journal::default_path()) {
+        Ok(Some(journal)) => journal,
+        Ok(None) => {
+            error!("There is no interrupted run to resume");
+            process::exit(1);
+        }
+        Err(e) => {
+            error!("{}", e);
+            process::exit(1);
         }
-        println!("❌ Failed:");
-        for (name, result) in results.iter() {
-            if !result.success {
-                println!("  - {}: {}", name, result.message);
+    };
+    if journal.operation != operation {
+        error!(
+            "The interrupted run was `updog {0}`; resume it with `updog {0} --resume`",
+            journal.operation
+        );
+        process::exit(1);
+    }
+    if journal.in_progress() {
+        error!(
+            "The {} run being resumed is still in progress (pid {})",
+            journal.operation, journal.pid
+        );
+        process::exit(1);
+    }
+    let items: Vec<_> = journal
+        .unfinished()
+        .into_iter()
+        .filter(|(manager, _)| {
+            let known = pm.config.find_package_manager(manager).is_some();
+            if !known {
+                warnings::warn(format_args!(
+                    "Not resuming {}: it is no longer in the config",
+                    manager
+                ));
             }
+            known
+        })
+        .collect();
+    if items.is_empty() {
+        error!("The interrupted run left nothing to resume");
+        process::exit(1);
+    }
+    info!("Resuming the interrupted run: {} left", items.len());
+    items
+}
+
+// Median duration of each manager's past runs of `operation`
+fn typical_durations(operation: Operation) -> BTreeMap<String, Duration> {
+    match History::new(History::default_path()).load() {
+        Ok(runs) => stats::compute(&runs, Some(operation.as_str()))
+            .into_iter()
+            .map(|s| (s.name, s.median))
+            .collect(),
+        Err(e) => {
+            warn!("Failed to read run history for typical durations: {}", e);
+            BTreeMap::new()
         }
     }
+}
+
+fn exit_with(report: &ExecutionReport) {
+    let code = report.exit_code();
+    if code != 0 {
+        process::exit(code);
+    }
+}
+
+fn load_check_cache() -> CheckCache {
+    CheckCache::load(&CheckCache::default_path()).unwrap_or_else(|e| {
+        warn!("Ignoring check cache: {}", e);
+        CheckCache::default()
+    })
+}
+
+// Cache the checks of managers with a `cooldown` and drop the ones the
+// report's updates made stale (skipped in dry run mode)
+fn refresh_check_cache(pm: &PackageManager, report: &ExecutionReport) {
+    if pm.dry_run || pm.config.managers().all(|m| m.cooldown.is_none()) {
+        return;
+    }
+    let path = CheckCache::default_path();
+    let now = pm.clock().now();
+    if let Err(e) = CheckCache::update(&path, |cache| {
+        cache.apply(&pm.config, report, pm.verbosity, now)
+    }) {
+        warn!("Failed to update check cache at {:?}: {}", path, e);
+    }
+}
+
+// Append the run to the history store and alert about its failures
+// (skipped in dry run mode)
+fn record_history(pm: &PackageManager, report: &ExecutionReport) {
+    // Results reused from the check cache were recorded when they ran
+    if pm.dry_run || report.results.iter().all(|r| r.cached) {
+        return;
+    }
 
-    // Print statistics
-    println!(
-        "\nTotal: {}, Successful: {}, Failed: {}",
-        success_count + failure_count,
-        success_count,
-        failure_count
+    let history = History::new(History::default_path());
+    if let Err(e) = history.append(&report.to_run_record()) {
+        warn!(
+            "Failed to record run history in {:?}: {}",
+            history.path(),
+            e
+        );
+    }
+
+    if !report.has_failures() || !alert::has_targets(&pm.config) {
+        return;
+    }
+    // Streaks come from history, which now includes this run
+    let streaks = match history.load() {
+        Ok(runs) => stats::failure_streaks(&runs),
+        Err(e) => {
+            warn!("Failed to read run history for alerts: {}", e);
+            Default::default()
+        }
+    };
+    alert::send(
+        &pm.for_run(&report.run_id),
+        &alert::alerts(report, &streaks, &pm.config.alerts),
     );
 }
+
+// Managers whose commands are new or changed since they were confirmed: on
+// a terminal, ask before running them and leave them out if the answer is
+// no; otherwise run them, but flag them in the summary and alerts
+fn confirm_changed_commands(
+    command: &Commands,
+    pm: &PackageManager,
+    metadata: &RunMetadata,
+    items: &mut Vec<(String, Option<String>)>,
+    report: &mut ExecutionReport,
+) {
+    let Some(config_path) = &metadata.config_path else {
+        return;
+    };
+    let path = Approvals::default_path();
+    let mut approvals = match Approvals::load(&path) {
+        Ok(approvals) => approvals,
+        Err(e) => {
+            warn!("Can't tell whether commands changed: {}", e);
+            return;
+        }
+    };
+    let before = approvals.clone();
+    let changes: Vec<_> = approvals
+        .unconfirmed_commands(config_path, &approval::command_hashes(&pm.config))
+        .into_iter()
+        .filter(|change| items.iter().any(|(manager, _)| *manager == change.manager))
+        .collect();
+    // Dry runs say what a real run would ask about, asking and recording nothing
+    if pm.dry_run {
+        report.warnings.extend(changes.iter().map(|change| {
+            format!(
+                "{}: commands are new or changed; a real run would ask before running them",
+                change
+            )
+        }));
+        return;
+    }
+
+    let interactive = std::io::stdin().is_terminal()
+        && std::io::stdout().is_terminal()
+        && !command.is_scheduled();
+    match (changes.is_empty(), interactive) {
+        (true, _) => {}
+        (false, true) => {
+            let stdin = std::io::stdin();
+            match approval::confirm(
+                &pm.config,
+                &changes,
+                &mut stdin.lock(),
+                &mut std::io::stdout(),
+            ) {
+                Ok(true) => {
+                    let confirmed = changes
+                        .iter()
+                        .map(|change| (change.manager.clone(), change.hash.clone()))
+                        .collect();
+                    approvals.confirm_commands(config_path, &confirmed);
+                }
+                answer => {
+                    if let Err(e) = answer {
+                        error!("Failed to read the confirmation: {}", e);
+                    }
+                    let (left_out, kept) = std::mem::take(items)
+                        .into_iter()
+                        .partition(|(m, _)| changes.iter().any(|change| change.manager == *m));
+                    *items = kept;
+                    report
+                        .results
+                        .extend(left_out.into_iter().map(|(manager, subcommand)| {
+                            ExecutionResult::skipped(
+                                manager,
+                                subcommand,
+                                "Not confirmed",
+                                "Commands are new or changed and weren't confirmed".to_string(),
+                            )
+                        }));
+                }
+            }
+        }
+        (false, false) => {
+            for change in &changes {
+                report.warnings.push(format!(
+                    "{}: commands are new or changed since they were last confirmed; \
+                     run updog on a terminal to review them",
+                    change
+                ));
+            }
+            if alert::has_targets(&pm.config) {
+                let alerts: Vec<_> = changes
+                    .iter()
+                    .map(|change| alert::Alert::commands_changed(&change.manager, change.new))
+                    .collect();
+                alert::send(pm, &alerts);
+            }
+        }
+    }
+    if approvals != before {
+        if let Err(e) = approvals.save(&path) {
+            warn!("{}", e);
+        }
+    }
+}
+
+// Refuse to run unattended with a config that changed since it was approved.
+// A config seen for the first time is trusted and recorded.
+fn require_approved_config(pm: &PackageManager, metadata: &RunMetadata) {
+    let (Some(config_path), Some(hash)) = (&metadata.config_path, &metadata.config_hash) else {
+        return;
+    };
+    let path = Approvals::default_path();
+    let mut approvals = match Approvals::load(&path) {
+        Ok(approvals) => approvals,
+        Err(e) => {
+            error!("{}", e);
+            process::exit(1);
+        }
+    };
+    match approvals.verify(config_path, hash, pm.clock().now()) {
+        Verdict::Approved => {}
+        Verdict::FirstUse => {
+            info!("Trusting {} on first use", config_path);
+            if let Err(e) = approvals.save(&path) {
+                error!("{}", e);
+                process::exit(1);
+            }
+        }
+        Verdict::Changed { approved_hash } => {
+            error!(
+                "{} changed since it was approved ({} -> {}); review it and run `updog config approve`",
+                config_path,
+                &approved_hash[..12.min(approved_hash.len())],
+                &hash[..12]
+            );
+            process::exit(1);
+        }
+    }
+}
+
+// `--summary-file`: the summary as JSON, written whatever the run's outcome
+fn write_summary_file(command: &Commands, report: &ExecutionReport) {
+    let Some(path) = command.summary_file() else {
+        return;
+    };
+    let mut json = serde_json::to_string_pretty(&report.to_summary_json())
+        .expect("the summary serializes to JSON");
+    json.push('\n');
+    if let Err(e) = write_atomic(path, json.as_bytes()) {
+        error!("Failed to write the summary to {}: {}", path.display(), e);
+    }
+}
+
+// Summarize the latest results from history into a badge file
+fn refresh_badge(pm: &PackageManager, path: &std::path::Path) {
+    let runs = match History::new(History::default_path()).load() {
+        Ok(runs) => runs,
+        Err(e) => {
+            warn!("Failed to read run history for the badge: {}", e);
+            return;
+        }
+    };
+    let names: Vec<String> = pm.config.managers().map(|m| m.id.clone()).collect();
+    let badge = Badge::from_status(&stats::status(&runs, &names), pm.config.daemon.timezone);
+    if let Err(e) = badge.write(path) {
+        warn!("Failed to write badge to {:?}: {}", path, e);
+    }
+}
+
+// Send the digest of the runs recorded since `since`, if there were any
+fn send_digest(pm: &PackageManager, since: SystemTime) {
+    if pm.dry_run {
+        return;
+    }
+    let history = History::new(History::default_path());
+    let runs = match history.load() {
+        Ok(runs) => runs,
+        Err(e) => {
+            warn!("Failed to read run history for the digest: {}", e);
+            return;
+        }
+    };
+    let timezone = pm.config.daemon.timezone;
+    match digest::build(&runs, history::unix_seconds(since), timezone) {
+        Some(digest) => alert::send_digest(pm, &digest),
+        None => info!("No runs since {}; no digest sent", timezone.civil(since)),
+    }
+}
+
+// Apply the configured retention policy to the history store
+fn prune_history(pm: &PackageManager) {
+    if pm.dry_run {
+        return;
+    }
+
+    let history = History::new(History::default_path());
+    match history.prune(&pm.config.history, pm.clock().now()) {
+        Ok(0) => {}
+        Ok(removed) => info!("Pruned {} runs from history", removed),
+        Err(e) => warn!("Failed to prune history in {:?}: {}", history.path(), e),
+    }
+}
+
+// Print pending update counts for scripting: the total, or one line per manager
+fn print_counts(report: &ExecutionReport, by_manager: bool) {
+    if by_manager {
+        for result in &report.results {
+            match (&result.status, result.pending()) {
+                (_, Some(count)) => println!("{}: {}", result.name(), count),
+                (ExecutionStatus::Skipped { .. }, None) => println!("{}: skipped", result.name()),
+                (_, None) => println!("{}: error", result.name()),
+            }
+        }
+    } else {
+        let total: usize = report.results.iter().filter_map(|r| r.pending()).sum();
+        println!("{}", total);
+    }
+}
@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::hash::sha256_hex;
+
+// Where and how a run was produced, recorded alongside its results so
+// history from several machines (or config revisions) can be told apart
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct RunMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    // Operating system and architecture, e.g. "linux-x86_64"
+    pub os: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    pub updog_version: String,
+    // Config file the run used (None when the built-in default was used)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_path: Option<String>,
+    // Machine-wide config merged under it (see `layer`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_config: Option<String>,
+    // SHA-256 of the config file contents, the system config's first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_hash: Option<String>,
+    // Command line arguments as given
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub invocation: Vec<String>,
+}
+
+impl RunMetadata {
+    // Gather metadata for the current process. `config_path` is the config
+    // file that was actually loaded, if any, and `system_config` the system
+    // config merged under it.
+    pub fn collect(config_path: Option<&Path>, system_config: Option<&Path>) -> Self {
+        let layers: Vec<&Path> = system_config.into_iter().chain(config_path).collect();
+        let config_hash = layers
+            .iter()
+            .map(|path| fs::read(path).ok())
+            .collect::<Option<Vec<_>>>()
+            .filter(|contents| !contents.is_empty())
+            .map(|contents| sha256_hex(&contents.concat()));
+
+        Self {
+            hostname: hostname(),
+            os: format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH),
+            user: username(),
+            updog_version: env!("CARGO_PKG_VERSION").to_string(),
+            config_path: config_path.map(|p| p.display().to_string()),
+            system_config: system_config.map(|p| p.display().to_string()),
+            config_hash,
+            invocation: std::env::args().collect(),
+        }
+    }
+}
+
+#[cfg(unix)]
+pub fn hostname() -> Option<String> {
+    let mut buffer = [0u8; 256];
+    let result =
+        unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) };
+    if result != 0 {
+        return None;
+    }
+    let end = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    let name = String::from_utf8_lossy(&buffer[..end]).into_owned();
+    (!name.is_empty()).then_some(name)
+}
+
+#[cfg(not(unix))]
+pub fn hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME")
+        .ok()
+        .filter(|name| !name.is_empty())
+}
+
+pub fn username() -> Option<String> {
+    ["USER", "USERNAME", "LOGNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|name| !name.is_empty()))
+}
+
+// Random (version 4) UUID identifying a run in history, logs, alerts and the
+// audit log, e.g. "3f2a9c1e-8b4d-4e6f-a1b2-c3d4e5f60718"
+pub fn new_run_id() -> String {
+    let mut bytes = [0u8; 16];
+    if let Err(e) = getrandom::getrandom(&mut bytes) {
+        // Unique enough to tell runs apart, which is all the id is for
+        tracing::warn!("No random source for the run id: {}", e);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        bytes[..12].copy_from_slice(&nanos.to_le_bytes()[..12]);
+        bytes[12..].copy_from_slice(&std::process::id().to_le_bytes());
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_collect_hashes_config_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        fs::write(&path, "abc").unwrap();
+
+        let metadata = RunMetadata::collect(Some(&path), None);
+        assert_eq!(
+            metadata.config_hash.as_deref(),
+            Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        assert_eq!(metadata.config_path, Some(path.display().to_string()));
+        assert_eq!(metadata.updog_version, env!("CARGO_PKG_VERSION"));
+        assert!(metadata.os.starts_with(std::env::consts::OS));
+
+        // A change to the system config changes the hash too
+        let system = dir.path().join("system.yaml");
+        fs::write(&system, "def").unwrap();
+        let layered = RunMetadata::collect(Some(&path), Some(&system));
+        assert_ne!(layered.config_hash, metadata.config_hash);
+        assert_eq!(layered.system_config, Some(system.display().to_string()));
+    }
+
+    #[test]
+    fn test_collect_without_config() {
+        let metadata = RunMetadata::collect(None, None);
+        assert!(metadata.config_path.is_none());
+        assert!(metadata.config_hash.is_none());
+        assert!(!metadata.invocation.is_empty());
+    }
+
+    #[test]
+    fn test_new_run_id() {
+        let id = new_run_id();
+        let groups: Vec<usize> = id.split('-').map(str::len).collect();
+        assert_eq!(groups, [8, 4, 4, 4, 12]);
+        assert!(id.chars().all(|c| c == '-' || c.is_ascii_hexdigit()));
+        assert_eq!(&id[14..15], "4");
+        assert!("89ab".contains(&id[19..20]));
+        assert_ne!(new_run_id(), id);
+    }
+}
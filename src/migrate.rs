@@ -0,0 +1,256 @@
+use serde_yaml::{Mapping, Value};
+use std::fmt;
+use std::path::Path;
+
+use crate::config::{Config, ConfigError, EntryType};
+
+// Version of the config schema this updog writes. Configs without
+// `version:` are version 1; constructs each later version dropped are
+// warned about in older configs and rejected in newer ones.
+//
+//   2  check/update next to `subcommands` (they were never run)
+pub const CONFIG_VERSION: u32 = 2;
+
+// A construct of an older config version found in a config
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deprecated {
+    // Config version that dropped it
+    pub since: u32,
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for Deprecated {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} (deprecated in config version {}; `updog config migrate` fixes it)",
+            self.location, self.message, self.since
+        )
+    }
+}
+
+// Every deprecated construct in `config`, whatever its version
+pub fn deprecations(config: &Config) -> Vec<Deprecated> {
+    let mut found = Vec::new();
+    for entry in &config.commands {
+        if entry.entry_type != EntryType::Manager
+            || entry.preset.is_some()
+            || entry.subcommands.is_empty()
+        {
+            continue;
+        }
+        let direct = entry.direct_command();
+        let keys: Vec<&str> = direct.sequences().iter().map(|(key, _)| *key).collect();
+        if !keys.is_empty() {
+            found.push(Deprecated {
+                since: 2,
+                location: entry.id.clone(),
+                message: format!(
+                    "{} next to subcommands {} never run; move {} into a subcommand",
+                    keys.join(", "),
+                    if keys.len() == 1 { "is" } else { "are" },
+                    if keys.len() == 1 { "it" } else { "them" },
+                ),
+            });
+        }
+    }
+    found
+}
+
+// Deprecations to warn about: those a config still written for an older
+// version may contain (`Config::validate` rejects the rest)
+pub fn warnings(config: &Config) -> Vec<Deprecated> {
+    deprecations(config)
+        .into_iter()
+        .filter(|d| d.since > config.version())
+        .collect()
+}
+
+fn key(name: &str) -> Value {
+    Value::String(name.to_string())
+}
+
+// Commands defined on the entry itself, moved by version 2
+const DIRECT_KEYS: &[&str] = &[
+    "check",
+    "update",
+    "check_verbose",
+    "check_quiet",
+    "update_verbose",
+    "update_quiet",
+];
+
+// Move check/update next to subcommands into a subcommand of their own,
+// appended so the default subcommand stays the same
+fn to_version_2(entry: &mut Mapping) -> Option<String> {
+    let id = entry.get(key("id"))?.as_str()?.to_string();
+    let Some(Value::Sequence(subcommands)) = entry.get(key("subcommands")) else {
+        return None;
+    };
+    if subcommands.is_empty() {
+        return None;
+    }
+    let taken: Vec<&str> = subcommands
+        .iter()
+        .filter_map(|sc| sc.get("id").and_then(Value::as_str))
+        .collect();
+    let name = (1..)
+        .map(|n| match n {
+            1 => "direct".to_string(),
+            n => format!("direct{}", n),
+        })
+        .find(|name| !taken.contains(&name.as_str()))?;
+
+    let mut moved = Mapping::new();
+    moved.insert(key("id"), key(&name));
+    for direct in DIRECT_KEYS {
+        if let Some(value) = entry.remove(key(direct)) {
+            moved.insert(key(direct), value);
+        }
+    }
+    if moved.len() == 1 {
+        return None;
+    }
+    let keys: Vec<&str> = moved.keys().skip(1).filter_map(Value::as_str).collect();
+    let change = format!("{}: moved {} into subcommand {}", id, keys.join(", "), name);
+    if let Some(Value::Sequence(subcommands)) = entry.get_mut(key("subcommands")) {
+        subcommands.push(Value::Mapping(moved));
+    }
+    Some(change)
+}
+
+// Rewrite the config in `content` (read from `source`) for the current
+// config version, headed by a comment listing what changed. Comments of
+// the original config aren't kept.
+pub fn migrate(content: &str, source: &Path) -> Result<String, ConfigError> {
+    let Value::Mapping(mut config) = serde_yaml::from_str(content)? else {
+        return Err(ConfigError::ValidationError(
+            "the config isn't a mapping".to_string(),
+        ));
+    };
+    let version = match config.get(key("version")) {
+        None => 1,
+        Some(value) => value
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| ConfigError::ValidationError(format!("version: {:?}", value)))?,
+    };
+    if version > CONFIG_VERSION {
+        return Err(ConfigError::ValidationError(format!(
+            "config version {} is newer than this updog supports ({})",
+            version, CONFIG_VERSION
+        )));
+    }
+
+    let mut changes = Vec::new();
+    if version < 2 {
+        if let Some(Value::Sequence(commands)) = config.get_mut(key("commands")) {
+            changes.extend(
+                commands
+                    .iter_mut()
+                    .filter_map(Value::as_mapping_mut)
+                    .filter_map(to_version_2),
+            );
+        }
+    }
+
+    // `version` goes first, where it's easy to spot
+    config.remove(key("version"));
+    let mut migrated = Mapping::new();
+    migrated.insert(key("version"), Value::from(CONFIG_VERSION));
+    migrated.extend(config);
+    let yaml = serde_yaml::to_string(&migrated)?;
+    serde_yaml::from_str::<Config>(&yaml)?.validate()?;
+
+    let mut out = format!(
+        "# Migrated by updog from {} (config version {}) to config version {}\n",
+        source.display(),
+        version,
+        CONFIG_VERSION
+    );
+    if !changes.is_empty() {
+        out.push_str("#\n# Changed:\n");
+        for change in &changes {
+            out.push_str(&format!("#   {}\n", change));
+        }
+    }
+    out.push_str(&yaml);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIXED: &str = r#"
+commands:
+  - id: homebrew
+    check: "brew outdated"
+    update: "brew upgrade"
+    subcommands:
+      - id: formulae
+        update: "brew upgrade --formula"
+      - id: direct
+        update: "true"
+  - id: npm
+    check: "npm outdated -g"
+    update: "npm update -g"
+"#;
+
+    #[test]
+    fn test_deprecations_warn_in_older_configs() {
+        let config: Config = serde_yaml::from_str(MIXED).unwrap();
+        config.validate().unwrap();
+        let found = warnings(&config);
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            found[0].to_string(),
+            "homebrew: check, update next to subcommands are never run; move them into a \
+             subcommand (deprecated in config version 2; `updog config migrate` fixes it)"
+        );
+
+        // A config written for version 2 can't have them
+        let config: Config = serde_yaml::from_str(&format!("version: 2\n{}", MIXED)).unwrap();
+        assert!(warnings(&config).is_empty());
+        let err = config.validate().unwrap_err().to_string();
+        assert!(
+            err.contains("homebrew: check, update next to subcommands"),
+            "{}",
+            err
+        );
+
+        let config: Config = serde_yaml::from_str("version: 3\ncommands: []\n").unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("config version 3 is newer"), "{}", err);
+    }
+
+    #[test]
+    fn test_migrate() {
+        let migrated = migrate(MIXED, Path::new("updog.yaml")).unwrap();
+        assert!(migrated.starts_with(
+            "# Migrated by updog from updog.yaml (config version 1) to config version 2\n\
+             #\n\
+             # Changed:\n\
+             #   homebrew: moved check, update into subcommand direct2\n\
+             version: 2\n"
+        ));
+        let config: Config = serde_yaml::from_str(&migrated).unwrap();
+        assert_eq!(config.version, Some(2));
+        let homebrew = config.find_package_manager("homebrew").unwrap();
+        assert!(homebrew.check.is_none() && homebrew.update.is_none());
+        // The default subcommand is still the first one
+        assert_eq!(homebrew.default_subcommand().unwrap().id, "formulae");
+        let moved = homebrew.find_subcommand("direct2").unwrap();
+        assert!(moved.command.check.is_some() && moved.command.update.is_some());
+        // The simple format stays as it is
+        let npm = config.find_package_manager("npm").unwrap();
+        assert!(npm.check.is_some());
+
+        let again = migrate(&migrated, Path::new("updog.yaml")).unwrap();
+        assert!(again.starts_with(
+            "# Migrated by updog from updog.yaml (config version 2) to config version 2\nversion: 2\n"
+        ));
+        assert!(migrate("version: 9\ncommands: []\n", Path::new("updog.yaml")).is_err());
+    }
+}
@@ -0,0 +1,563 @@
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use crate::executor::CommandOutput;
+
+// Environment variables understood by common tools as "emit color even when piped"
+pub const FORCE_COLOR_ENV: &[(&str, &str)] = &[
+    ("CLICOLOR_FORCE", "1"),
+    ("FORCE_COLOR", "1"),
+    ("HOMEBREW_COLOR", "1"),
+    ("CARGO_TERM_COLOR", "always"),
+    ("npm_config_color", "always"),
+];
+
+fn ansi_regex() -> &'static Regex {
+    static ANSI: OnceLock<Regex> = OnceLock::new();
+    ANSI.get_or_init(|| {
+        // CSI sequences (colors, cursor movement), OSC sequences (titles, links)
+        // and the remaining two-byte escapes
+        Regex::new(r"\x1b\[[0-9;?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)|\x1b[@-Z\\-_]")
+            .unwrap()
+    })
+}
+
+// Remove ANSI escape sequences, for sinks that store or transmit plain text
+// (history, JSON, notifications)
+pub fn strip_ansi(text: &str) -> String {
+    ansi_regex().replace_all(text, "").into_owned()
+}
+
+// Text of a command's captured stdout or stderr. Besides UTF-8, some
+// Windows tools write UTF-16 (wmic, winget when piped) or text in the
+// console's legacy code page (cp1252, cp866, Shift-JIS, ...).
+pub fn decode_output(bytes: &[u8]) -> String {
+    decode_with_codepage(bytes, console_codepage())
+}
+
+fn decode_with_codepage(bytes: &[u8], codepage: Option<u16>) -> String {
+    let utf16 = match bytes {
+        [0xFF, 0xFE, rest @ ..] => Some((encoding_rs::UTF_16LE, rest)),
+        [0xFE, 0xFF, rest @ ..] => Some((encoding_rs::UTF_16BE, rest)),
+        _ if looks_like_utf16le(bytes) => Some((encoding_rs::UTF_16LE, bytes)),
+        _ => None,
+    };
+    if let Some((encoding, text)) = utf16 {
+        return encoding.decode_without_bom_handling(text).0.into_owned();
+    }
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return text.to_string();
+    }
+    match codepage.and_then(codepage::to_encoding) {
+        Some(encoding) => encoding.decode_without_bom_handling(bytes).0.into_owned(),
+        None => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+// UTF-16LE without a byte order mark: mostly ASCII text, so every other
+// byte is zero
+fn looks_like_utf16le(bytes: &[u8]) -> bool {
+    if bytes.len() < 2 || !bytes.len().is_multiple_of(2) {
+        return false;
+    }
+    let pairs = bytes.len() / 2;
+    let high_zero = bytes.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+    let low_zero = bytes.iter().step_by(2).filter(|&&b| b == 0).count();
+    high_zero * 2 >= pairs && low_zero == 0
+}
+
+// Code page console programs write in: the console's, or the OEM code page
+// without a console
+#[cfg(windows)]
+fn console_codepage() -> Option<u16> {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetConsoleOutputCP() -> u32;
+        fn GetOEMCP() -> u32;
+    }
+    let codepage = match unsafe { GetConsoleOutputCP() } {
+        0 => unsafe { GetOEMCP() },
+        codepage => codepage,
+    };
+    u16::try_from(codepage).ok()
+}
+
+#[cfg(not(windows))]
+fn console_codepage() -> Option<u16> {
+    None
+}
+
+// Accept either a single string or a list of strings
+pub(crate) fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(s) => vec![s],
+        OneOrMany::Many(v) => v,
+    })
+}
+
+// Number of pending updates reported by a check command: one per non-empty
+// output line (use `output_filter` to drop headers and chatter)
+pub fn count_pending(output: &str) -> usize {
+    strip_ansi(output)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .count()
+}
+
+// Values shown next to a manager in the run summary, picked out of its
+// captured output by regex (for tools updog has no parser for)
+//
+//   summary_columns:
+//     outdated: '(\d+) outdated'   # first capture group, else the whole match
+//
+// Tools print their totals last, so the last matching line counts. Columns
+// that match nothing are left out.
+pub fn summary_columns(
+    columns: &BTreeMap<String, String>,
+    output: &str,
+) -> BTreeMap<String, String> {
+    let lines: Vec<String> = strip_ansi(output).lines().map(str::to_string).collect();
+    columns
+        .iter()
+        .filter_map(|(name, pattern)| {
+            let regex = Regex::new(pattern).ok()?;
+            let value = lines.iter().rev().find_map(|line| {
+                let captures = regex.captures(line)?;
+                let value = captures.get(1).or_else(|| captures.get(0))?;
+                Some(value.as_str().trim().to_string())
+            })?;
+            Some((name.clone(), value))
+        })
+        .collect()
+}
+
+// Line filter applied to captured command output
+//
+//   output_filter:
+//     keep: "^==> Upgrading"         # only keep matching lines
+//     drop: ["funding", "^npm notice"] # remove matching lines
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct OutputFilter {
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub keep: Vec<String>,
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub drop: Vec<String>,
+}
+
+impl OutputFilter {
+    // Check that all patterns are valid regular expressions
+    pub fn validate(&self) -> Result<(), regex::Error> {
+        for pattern in self.keep.iter().chain(self.drop.iter()) {
+            Regex::new(pattern)?;
+        }
+        Ok(())
+    }
+
+    // Apply the filter line by line. Invalid patterns are ignored
+    // (they are rejected when the config is loaded).
+    pub fn apply(&self, text: &str) -> String {
+        let compile = |patterns: &[String]| -> Vec<Regex> {
+            patterns.iter().filter_map(|p| Regex::new(p).ok()).collect()
+        };
+        let keep = compile(&self.keep);
+        let drop = compile(&self.drop);
+
+        // Patterns match against the uncolored line, but the line is kept as-is
+        let mut filtered: String = text
+            .lines()
+            .filter(|line| {
+                let plain = strip_ansi(line);
+                (keep.is_empty() || keep.iter().any(|re| re.is_match(&plain)))
+                    && !drop.iter().any(|re| re.is_match(&plain))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if !filtered.is_empty() && text.ends_with('\n') {
+            filtered.push('\n');
+        }
+        filtered
+    }
+}
+
+// Patterns deciding whether a command worked from its captured output, for
+// tools whose exit status can't be trusted
+//
+//   success_regex: "^Nothing to upgrade"   # worked despite a nonzero exit
+//   failure_regex: "(?i)^error:"           # failed despite exiting 0
+//
+// Lines are matched without colors. When both match, the command failed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct StatusOverride {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success_regex: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_regex: Option<String>,
+}
+
+impl StatusOverride {
+    pub fn is_empty(&self) -> bool {
+        self.success_regex.is_none() && self.failure_regex.is_none()
+    }
+
+    // Check that both patterns are valid regular expressions, returning the
+    // key of the first one that isn't
+    pub fn validate(&self) -> Result<(), (&'static str, regex::Error)> {
+        for (key, pattern) in [
+            ("success_regex", &self.success_regex),
+            ("failure_regex", &self.failure_regex),
+        ] {
+            if let Some(pattern) = pattern {
+                Regex::new(pattern).map_err(|e| (key, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Patterns not set here are taken from `parent`
+    pub fn or(&self, parent: &StatusOverride) -> StatusOverride {
+        StatusOverride {
+            success_regex: self.success_regex.clone().or(parent.success_regex.clone()),
+            failure_regex: self.failure_regex.clone().or(parent.failure_regex.clone()),
+        }
+    }
+
+    // Whether `output` is a success: by the patterns if one matches, by the
+    // exit status otherwise. The error describes the failure as in "failed
+    // with ...". Invalid patterns are ignored (they are rejected when the
+    // config is loaded).
+    pub fn judge(&self, output: &CommandOutput) -> Result<(), String> {
+        let matches = |pattern: &Option<String>| {
+            let Some(re) = pattern.as_deref().and_then(|p| Regex::new(p).ok()) else {
+                return false;
+            };
+            [&output.stdout, &output.stderr]
+                .iter()
+                .flat_map(|text| text.lines())
+                .any(|line| re.is_match(&strip_ansi(line)))
+        };
+        if matches(&self.failure_regex) {
+            return Err(format!(
+                "output matching failure_regex '{}'",
+                self.failure_regex.as_deref().unwrap_or_default()
+            ));
+        }
+        if output.success() || matches(&self.success_regex) {
+            return Ok(());
+        }
+        Err(output.status_description())
+    }
+}
+
+// Cap on retained captured output: the first `head_kb` and last `tail_kb`
+// kilobytes are kept, with a marker replacing everything in between
+//
+//   output_limit:
+//     head_kb: 32
+//     tail_kb: 32
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct OutputLimit {
+    #[serde(default = "OutputLimit::default_kb")]
+    pub head_kb: usize,
+    #[serde(default = "OutputLimit::default_kb")]
+    pub tail_kb: usize,
+}
+
+impl Default for OutputLimit {
+    fn default() -> Self {
+        Self {
+            head_kb: Self::default_kb(),
+            tail_kb: Self::default_kb(),
+        }
+    }
+}
+
+impl OutputLimit {
+    fn default_kb() -> usize {
+        64
+    }
+
+    // Largest char boundary at or below `index`
+    fn floor_boundary(text: &str, mut index: usize) -> usize {
+        while !text.is_char_boundary(index) {
+            index -= 1;
+        }
+        index
+    }
+
+    // Smallest char boundary at or above `index`
+    fn ceil_boundary(text: &str, mut index: usize) -> usize {
+        while !text.is_char_boundary(index) {
+            index += 1;
+        }
+        index
+    }
+
+    // Truncate the middle of `text` if it exceeds the limit.
+    // Returns the (possibly) truncated text and the number of bytes removed.
+    pub fn apply(&self, text: String) -> (String, usize) {
+        let head = self.head_kb * 1024;
+        let tail = self.tail_kb * 1024;
+        if text.len() <= head + tail {
+            return (text, 0);
+        }
+
+        let head_end = Self::floor_boundary(&text, head);
+        let tail_start = Self::ceil_boundary(&text, text.len() - tail);
+        let removed = tail_start - head_end;
+
+        let mut truncated = String::with_capacity(head + tail + 64);
+        truncated.push_str(&text[..head_end]);
+        if !truncated.is_empty() && !truncated.ends_with('\n') {
+            truncated.push('\n');
+        }
+        truncated.push_str(&format!("[... {} bytes truncated ...]\n", removed));
+        truncated.push_str(&text[tail_start..]);
+        (truncated, removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_lines() {
+        let filter = OutputFilter {
+            keep: vec![],
+            drop: vec!["fund".to_string(), "^npm notice".to_string()],
+        };
+        let output =
+            "added 3 packages\n5 packages are looking for funding\nnpm notice New version\n";
+        assert_eq!(filter.apply(output), "added 3 packages\n");
+    }
+
+    #[test]
+    fn test_keep_then_drop() {
+        let filter = OutputFilter {
+            keep: vec!["^==>".to_string()],
+            drop: vec!["Cleaning".to_string()],
+        };
+        let output = "==> Upgrading git\nsome chatter\n==> Cleaning up\n==> Upgrading curl";
+        assert_eq!(
+            filter.apply(output),
+            "==> Upgrading git\n==> Upgrading curl"
+        );
+    }
+
+    #[test]
+    fn test_empty_filter_is_identity() {
+        let filter = OutputFilter::default();
+        assert_eq!(filter.apply("a\nb\n"), "a\nb\n");
+    }
+
+    #[test]
+    fn test_deserialize_single_or_list() {
+        let filter: OutputFilter = serde_yaml::from_str("drop: funding").unwrap();
+        assert_eq!(filter.drop, vec!["funding"]);
+
+        let filter: OutputFilter = serde_yaml::from_str("keep: [a, b]").unwrap();
+        assert_eq!(filter.keep, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_validate_rejects_invalid_regex() {
+        let filter = OutputFilter {
+            keep: vec!["(unclosed".to_string()],
+            drop: vec![],
+        };
+        assert!(filter.validate().is_err());
+    }
+
+    #[test]
+    fn test_status_override() {
+        let output = |exit_code, stdout: &str| CommandOutput {
+            exit_code: Some(exit_code),
+            stdout: stdout.to_string(),
+            ..Default::default()
+        };
+        let status = StatusOverride {
+            success_regex: Some("^Nothing to do".to_string()),
+            failure_regex: Some("(?i)^error:".to_string()),
+        };
+        assert_eq!(status.judge(&output(0, "Upgraded 2 packages\n")), Ok(()));
+        assert_eq!(status.judge(&output(1, "Nothing to do.\n")), Ok(()));
+        assert_eq!(
+            status.judge(&output(0, "\x1b[31mERROR:\x1b[0m mirror unreachable\n")),
+            Err("output matching failure_regex '(?i)^error:'".to_string())
+        );
+        assert_eq!(
+            status.judge(&output(1, "Nothing to do\nerror: lock held\n")),
+            Err("output matching failure_regex '(?i)^error:'".to_string())
+        );
+        assert_eq!(
+            StatusOverride::default().judge(&output(2, "Nothing to do\n")),
+            Err("exit status: 2".to_string())
+        );
+
+        let parent = StatusOverride {
+            success_regex: Some("ok".to_string()),
+            failure_regex: Some("(unclosed".to_string()),
+        };
+        let inherited = StatusOverride {
+            success_regex: Some("done".to_string()),
+            failure_regex: None,
+        }
+        .or(&parent);
+        assert_eq!(inherited.success_regex.as_deref(), Some("done"));
+        assert!(inherited
+            .validate()
+            .is_err_and(|(key, _)| key == "failure_regex"));
+    }
+
+    #[test]
+    fn test_output_limit_keeps_head_and_tail() {
+        let limit = OutputLimit {
+            head_kb: 1,
+            tail_kb: 1,
+        };
+        let text = format!(
+            "{}{}{}",
+            "h".repeat(1024),
+            "m".repeat(5000),
+            "t".repeat(1024)
+        );
+        let (truncated, removed) = limit.apply(text);
+
+        assert_eq!(removed, 5000);
+        assert!(truncated.starts_with(&"h".repeat(1024)));
+        assert!(truncated.ends_with(&"t".repeat(1024)));
+        assert!(truncated.contains("[... 5000 bytes truncated ...]"));
+        assert!(!truncated.contains('m'));
+    }
+
+    #[test]
+    fn test_output_limit_small_output_untouched() {
+        let limit = OutputLimit::default();
+        assert_eq!(limit.apply("short".to_string()), ("short".to_string(), 0));
+    }
+
+    #[test]
+    fn test_output_limit_respects_char_boundaries() {
+        let limit = OutputLimit {
+            head_kb: 1,
+            tail_kb: 1,
+        };
+        // Multi-byte characters straddling the cut points must not panic
+        let text = "é".repeat(3000);
+        let (truncated, removed) = limit.apply(text);
+        assert!(removed > 0);
+        assert!(truncated.contains("bytes truncated"));
+    }
+
+    #[test]
+    fn test_decode_output() {
+        let utf16le =
+            |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+        // wmic writes UTF-16LE with a byte order mark, winget without
+        let wmic = [
+            &[0xFF, 0xFE][..],
+            &utf16le("Caption\r\nMicrosoft Windows 11 Pro\r\n"),
+        ]
+        .concat();
+        assert_eq!(
+            decode_with_codepage(&wmic, Some(437)),
+            "Caption\r\nMicrosoft Windows 11 Pro\r\n"
+        );
+        assert_eq!(
+            decode_with_codepage(
+                &utf16le("Name  Id  Version\nGit   Git.Git  2.45 → 2.46\n"),
+                None
+            ),
+            "Name  Id  Version\nGit   Git.Git  2.45 → 2.46\n"
+        );
+        let be: Vec<u8> = [0xFE, 0xFF]
+            .into_iter()
+            .chain("ok".encode_utf16().flat_map(u16::to_be_bytes))
+            .collect();
+        assert_eq!(decode_with_codepage(&be, None), "ok");
+
+        // UTF-8 wins over the code page, with or without a BOM
+        assert_eq!(
+            decode_with_codepage("café\n".as_bytes(), Some(1252)),
+            "café\n"
+        );
+        assert_eq!(decode_with_codepage(b"\xEF\xBB\xBFok", Some(1252)), "ok");
+        // Legacy code pages
+        assert_eq!(decode_with_codepage(b"caf\xE9", Some(1252)), "café");
+        assert_eq!(
+            decode_with_codepage(b"\x8F\xE0\xA8\xA2\xA5\xE2", Some(866)),
+            "Привет"
+        );
+        assert_eq!(decode_with_codepage(b"caf\xE9", None), "caf\u{FFFD}");
+        // Odd-length or NUL-free output isn't UTF-16
+        assert_eq!(decode_with_codepage(b"a\0b", None), "a\0b");
+        assert_eq!(decode_with_codepage(b"", None), "");
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(
+            strip_ansi("\x1b[1;32m==>\x1b[0m Upgrading"),
+            "==> Upgrading"
+        );
+        assert_eq!(strip_ansi("\x1b]0;title\x07text"), "text");
+        assert_eq!(strip_ansi("\x1b[2K\x1b[1Gprogress"), "progress");
+        assert_eq!(strip_ansi("plain"), "plain");
+    }
+
+    #[test]
+    fn test_filter_matches_uncolored_text() {
+        let filter = OutputFilter {
+            keep: vec!["^==> Upgrading".to_string()],
+            drop: vec![],
+        };
+        let output = "\x1b[34m==>\x1b[0m Upgrading git\nother\n";
+        assert_eq!(filter.apply(output), "\x1b[34m==>\x1b[0m Upgrading git\n");
+    }
+
+    #[test]
+    fn test_count_pending() {
+        assert_eq!(count_pending(""), 0);
+        assert_eq!(count_pending("git (2.40) < 2.41\n\ncurl (8.0) < 8.1\n"), 2);
+        assert_eq!(count_pending("\x1b[1mgit\x1b[0m\n   \n"), 1);
+    }
+
+    #[test]
+    fn test_summary_columns() {
+        let columns: BTreeMap<String, String> = [
+            ("outdated", r"(\d+) outdated"),
+            ("status", r"^Status: .*"),
+            ("vulnerable", r"(\d+) vulnerab"),
+        ]
+        .into_iter()
+        .map(|(name, pattern)| (name.to_string(), pattern.to_string()))
+        .collect();
+        let output = "3 outdated (direct)\nfoo 1.0 -> 1.1\n\x1b[1m12 outdated\x1b[0m in total\nStatus: ok \n";
+        let found = summary_columns(&columns, output);
+        assert_eq!(found["outdated"], "12");
+        assert_eq!(found["status"], "Status: ok");
+        assert!(!found.contains_key("vulnerable"));
+    }
+}
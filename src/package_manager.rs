@@ -1,13 +1,84 @@
-use std::process::{Command, ExitStatus, Stdio};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
-use tracing::{error, info};
-
-use crate::config::{CommandSequence, Config};
+use std::time::Duration;
+use tracing::{error, info, info_span, warn};
+
+use crate::alert::{self, Alert};
+use crate::audit::{AuditLog, AuditingExecutor};
+use crate::bandwidth::BandwidthLimit;
+use crate::cache::CheckCache;
+use crate::concurrency::{self, Claims};
+use crate::config::{CommandSequence, Config, PackageManagerConfig, Verbosity};
+use crate::executor::{
+    Clock, CommandOutput, CommandRequest, Executor, OutputSink, ShellExecutor, SystemClock,
+    RUN_ID_ENV,
+};
+use crate::guard::RunGuard;
+use crate::keys::Controls;
+use crate::lint;
+use crate::lock;
+use crate::output::{
+    count_pending, strip_ansi, summary_columns, OutputFilter, StatusOverride, FORCE_COLOR_ENV,
+};
+use crate::plugin;
+use crate::proxy::ProxyConfig;
+use crate::redact::Redactor;
+use crate::report::{ExecutionResult, ExecutionStatus, Operation};
+use crate::script;
+use crate::secret::SecretRef;
+use crate::shell::Shell;
+use crate::ssh::SshTarget;
+use crate::stats::format_duration;
+use crate::step::{expand_home, Step, StepResult};
+use crate::warnings;
+use crate::wasm;
+
+// How often to look at a held lock again while waiting for it
+const LOCK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// What kind of problem an UpdateError describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UpdateErrorKind {
+    // A command ran and failed, or couldn't be started
+    #[default]
+    Failed,
+    // The package manager or subcommand isn't configured
+    NotFound,
+    // The subcommand has no command for the requested operation
+    MissingCommand,
+    // The entry's `when_script` ruled it out on this machine
+    Excluded,
+    // Shutdown was requested before or while the command ran
+    Cancelled,
+    // The command exceeded its time limit
+    TimedOut,
+    // Another process holds the package manager's lock
+    Busy,
+    // The filesystem is fuller than `min_free_space` allows
+    LowSpace,
+    // ssh couldn't connect to or log in to the manager's host
+    Unreachable,
+}
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UpdateError {
     pub message: String,
+    pub kind: UpdateErrorKind,
+}
+
+impl UpdateError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self::with_kind(UpdateErrorKind::Failed, message)
+    }
+
+    pub fn with_kind(kind: UpdateErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            kind,
+        }
+    }
 }
 
 impl std::fmt::Display for UpdateError {
@@ -18,276 +89,691 @@ impl std::fmt::Display for UpdateError {
 
 impl std::error::Error for UpdateError {}
 
-pub struct PackageManager {
-    pub config: Config,
-    pub dry_run: bool,
-    process_tracker: Arc<Mutex<ProcessTracker>>,
-}
-
-// Structure to track running processes
-struct ProcessTracker {
-    active_processes: std::collections::HashSet<u32>, // Set of active process IDs
-    shutdown_requested: Arc<AtomicBool>,
+// Step-by-step outcome of a command sequence. Steps after a failed one don't run.
+#[derive(Debug, Clone, Default)]
+pub struct SequenceResult {
+    pub steps: Vec<StepResult>,
+    // Number of steps in the sequence, including those that didn't run
+    pub total: usize,
+    // Captured output of the whole sequence after output limits, transforms and parsers
+    pub output: String,
+    // Why the sequence failed, if it did
+    pub error: Option<UpdateError>,
+    // Number of packages an update upgraded, when its parser could tell
+    pub upgraded: Option<usize>,
+    // Whether an update said the machine has to reboot to finish
+    pub reboot_required: bool,
+    // What a cleanup step removed (unused runtimes, old revisions)
+    pub cleaned: Vec<String>,
 }
 
-impl ProcessTracker {
-    fn new() -> Self {
-        Self {
-            active_processes: std::collections::HashSet::new(),
-            shutdown_requested: Arc::new(AtomicBool::new(false)),
-        }
+impl SequenceResult {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
     }
 
-    // Register a process
-    fn register_process(&mut self, pid: u32) {
-        self.active_processes.insert(pid);
+    // The step that failed, numbered from 1
+    pub fn failed_step(&self) -> Option<(usize, &StepResult)> {
+        self.steps
+            .iter()
+            .enumerate()
+            .find(|(_, step)| !step.success)
+            .map(|(index, step)| (index + 1, step))
     }
 
-    // Unregister a process
-    fn unregister_process(&mut self, pid: u32) {
-        self.active_processes.remove(&pid);
+    // The combined output, or the error
+    pub fn into_output(self) -> Result<String, UpdateError> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.output),
+        }
     }
+}
 
-    // Mark shutdown as requested
-    fn request_shutdown(&self) {
-        self.shutdown_requested.store(true, Ordering::SeqCst);
-    }
+// What happens to a command's output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Capture {
+    // Passed through to the terminal
+    Passthrough,
+    // Captured, and echoed once the command is done
+    Captured,
+    // Shown on the terminal as it comes and captured as well
+    Teed,
+}
 
-    // Check if shutdown has been requested
-    fn is_shutdown_requested(&self) -> bool {
-        self.shutdown_requested.load(Ordering::SeqCst)
+impl Capture {
+    fn is_captured(self) -> bool {
+        self != Capture::Passthrough
     }
+}
 
-    // Terminate all active processes
-    fn terminate_all_processes(&self) {
-        for &pid in &self.active_processes {
-            // Attempt to send SIGTERM signal
-            #[cfg(unix)]
-            unsafe {
-                libc::kill(pid as i32, libc::SIGTERM);
-                info!("Sent SIGTERM to process {}", pid);
-            }
+// How a sequence's commands are run: on the manager's ssh host, if it has
+// one, passed the names of the variables set for them, and held to its
+// bandwidth limit if that's in force (under trickle through `trickle`,
+// when it's installed locally)
+struct Route<'a> {
+    ssh: Option<&'a SshTarget>,
+    send_env: Vec<&'a str>,
+    // The manager's `pass_env` when it has `clean_env`
+    clean_env: Option<&'a [String]>,
+    bandwidth: Option<&'a BandwidthLimit>,
+    trickle: Option<Shell>,
+}
 
-            #[cfg(windows)]
-            {
-                // On Windows, a different termination mechanism is needed
-                // Here, we just perform simple logging
-                info!(
-                    "Process termination on Windows not implemented for PID: {}",
-                    pid
-                );
-            }
+#[derive(Clone)]
+pub struct PackageManager {
+    pub config: Config,
+    pub dry_run: bool,
+    // Print captured (filtered) output to the terminal as commands finish
+    pub echo_output: bool,
+    // Selects the quiet/verbose command variants
+    pub verbosity: Verbosity,
+    // The terminal's keys control the run (`update --keys`), so commands
+    // get an empty stdin instead of it
+    pub keyboard_controls: bool,
+    // Found when loading the config (lint findings, plugins that didn't
+    // load), repeated in the summary of every run
+    pub config_warnings: Vec<String>,
+    // Id of the run in progress, passed to commands and hooks as
+    // UPDOG_RUN_ID (and so recorded in the audit log)
+    pub run_id: Option<String>,
+    executor: Arc<dyn Executor>,
+    clock: Arc<dyn Clock>,
+    // Built from the config's `redact:` rules
+    redactor: Redactor,
+}
+
+// Wrap `executor` to record every command in the audit log, if enabled
+fn audited(
+    config: &Config,
+    executor: Arc<dyn Executor>,
+    clock: Arc<dyn Clock>,
+) -> Arc<dyn Executor> {
+    match AuditLog::from_config(&config.audit) {
+        Some(log) => Arc::new(AuditingExecutor::new(executor, clock, log)),
+        None => executor,
+    }
+}
+
+// The `locks` of `manager`, or those of the programs `command` runs
+fn manager_locks(manager: &PackageManagerConfig, command: &CommandSequence) -> Vec<PathBuf> {
+    match &manager.locks {
+        Some(paths) => paths.iter().map(|path| expand_home(path)).collect(),
+        None => {
+            let steps = command.steps();
+            lock::default_locks(steps.iter().filter_map(Step::as_command))
         }
     }
 }
 
 impl PackageManager {
-    // Execute a command sequence (single or multiple commands)
+    // Execute a command sequence (single or multiple commands), stopping at
+    // the first failed step. Errors are returned for problems that keep the
+    // sequence from starting (e.g. an unresolvable secret).
     fn execute_command(
         &self,
-        _manager_name: &str,
+        manager_name: &str,
         command: &CommandSequence,
-    ) -> Result<(), UpdateError> {
-        match command {
-            CommandSequence::Single(cmd) => {
-                // Execute a single command
-                let status = self.run_single_command(cmd)?;
-                if !status.success() {
-                    return Err(UpdateError {
-                        message: format!("Command failed with exit code: {}", status),
-                    });
+        capture: Capture,
+        filter: Option<&OutputFilter>,
+        status: &StatusOverride,
+    ) -> Result<SequenceResult, UpdateError> {
+        let manager = self.config.find_package_manager(manager_name);
+        let mut env: Vec<(String, String)> = self.run_env();
+        let ssh = manager.and_then(|m| m.ssh.as_ref());
+        let clean_env = self.config.clean_env_for(manager);
+        // On a remote host, the pass_env variables are the host's own
+        if ssh.is_none() {
+            env.extend(
+                clean_env
+                    .iter()
+                    .flatten()
+                    .filter_map(|name| Some((name.clone(), std::env::var(name).ok()?))),
+            );
+        }
+        env.extend(self.config.locale_for(manager).env());
+        if capture.is_captured() && manager.is_some_and(|m| m.force_color) {
+            env.extend(
+                FORCE_COLOR_ENV
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string())),
+            );
+        }
+        env.extend(
+            self.config
+                .proxy_for(manager)
+                .into_iter()
+                .flat_map(ProxyConfig::env),
+        );
+
+        // Secrets are resolved only for real runs and are redacted from captured output
+        let mut secrets = Vec::new();
+        for (name, value) in manager.into_iter().flat_map(|m| m.env.iter()) {
+            match SecretRef::parse(value) {
+                None => env.push((name.clone(), value.clone())),
+                // Dry runs only make sure the reference is well-formed
+                Some(reference) if self.dry_run => {
+                    reference.map_err(UpdateError::new)?;
                 }
-                Ok(())
+                Some(reference) => {
+                    let secret = reference
+                        .map_err(UpdateError::new)?
+                        .resolve(&self.executor)
+                        .map_err(|e| {
+                            UpdateError::new(format!(
+                                "Failed to resolve secret {}: {}",
+                                name, e.message
+                            ))
+                        })?;
+                    env.push((name.clone(), secret.clone()));
+                    secrets.push(secret);
+                }
+            }
+        }
+
+        let redactor = self.redactor.clone().with_values(secrets);
+        let bandwidth = manager
+            .and_then(|m| m.bandwidth_limit.as_ref())
+            .filter(|limit| limit.applies_at(self.clock.now(), self.config.daemon.timezone));
+        let shell = self.config.shell.unwrap_or_else(Shell::platform_default);
+        let route = Route {
+            ssh,
+            send_env: env.iter().map(|(name, _)| name.as_str()).collect(),
+            clean_env: clean_env.as_deref(),
+            bandwidth,
+            trickle: (bandwidth.is_some()
+                && ssh.is_none()
+                && matches!(shell, Shell::Bash | Shell::Sh)
+                && self.executor.finds("trickle"))
+            .then_some(shell),
+        };
+
+        let steps = command.steps();
+        let single = matches!(command, CommandSequence::Single(_));
+        let mut result = SequenceResult {
+            total: steps.len(),
+            ..Default::default()
+        };
+        let mut stdout = String::new();
+        // Outputs of `register` steps, for `{{name}}` in later steps
+        let mut registered: Vec<(String, String)> = Vec::new();
+        let mut sudo_probed = false;
+        // Shell commands show their own output; that of steps updog runs
+        // itself is redacted and shown here
+        let builtin = |output: Result<String, UpdateError>| match output {
+            Ok(output) => {
+                let output = redactor.apply(&output);
+                if self.echo_output {
+                    print!("{}", output);
+                }
+                (None, output, None)
             }
-            CommandSequence::Multiple(cmds) => {
-                // Execute multiple commands in sequence
-                for (index, cmd) in cmds.iter().enumerate() {
-                    info!("Executing step {} of {}", index + 1, cmds.len());
-                    let status = self.run_single_command(cmd)?;
-                    if !status.success() {
-                        // Stop on first failure and return error
-                        return Err(UpdateError {
-                            message: format!("Command failed with exit code: {}", status),
+            Err(e) => (None, String::new(), Some(e)),
+        };
+        for (index, step) in steps.iter().enumerate() {
+            if !single {
+                info!("Executing step {} of {}", index + 1, steps.len());
+            }
+            let step = &step.interpolate(&registered);
+            let started = self.clock.now();
+            let (exit_code, output, error) = match step {
+                Step::Register { run, register } => {
+                    // Captured even for interactive updates, to have the value
+                    let ran = self
+                        .preflight(run, &route, &mut sudo_probed)
+                        .and_then(|()| {
+                            self.run_single_command(
+                                run,
+                                &route,
+                                Capture::Captured,
+                                &env,
+                                &redactor,
+                                filter,
+                                status,
+                            )
                         });
+                    match ran {
+                        Ok((output, Ok(()))) => {
+                            // Dry runs leave the placeholder in the commands they log
+                            if !self.dry_run {
+                                let value = output.stdout.trim_end().to_string();
+                                registered.retain(|(name, _)| name != register);
+                                registered.push((register.clone(), value));
+                            }
+                            (output.exit_code, output.stdout, None)
+                        }
+                        Ok((output, Err(failure))) => (
+                            output.exit_code,
+                            output.stdout,
+                            Some(UpdateError::new(failure)),
+                        ),
+                        Err(e) => (None, String::new(), Some(e)),
                     }
                 }
-                Ok(())
+                Step::Command(cmd) => {
+                    let ran = self
+                        .preflight(cmd, &route, &mut sudo_probed)
+                        .and_then(|()| {
+                            self.run_single_command(
+                                cmd, &route, capture, &env, &redactor, filter, status,
+                            )
+                        });
+                    match ran {
+                        Ok((output, Ok(()))) => (output.exit_code, output.stdout, None),
+                        Ok((output, Err(failure))) => {
+                            let status = match single {
+                                true => format!("Command failed with {}", failure),
+                                false => failure,
+                            };
+                            (
+                                output.exit_code,
+                                output.stdout,
+                                Some(UpdateError::new(status)),
+                            )
+                        }
+                        Err(e) => (None, String::new(), Some(e)),
+                    }
+                }
+                // Updates through a preset may leave a reboot pending
+                Step::PresetUpdate(preset) if !self.dry_run => {
+                    builtin(preset.update(self.executor.as_ref(), &env).map(|update| {
+                        result.reboot_required |= update.reboot_required;
+                        update.output
+                    }))
+                }
+                Step::PresetCleanup(preset) if !self.dry_run => {
+                    builtin(preset.cleanup(self.executor.as_ref(), &env).map(|removed| {
+                        let output = removed
+                            .iter()
+                            .map(|item| format!("Removed {}\n", item))
+                            .collect();
+                        result
+                            .cleaned
+                            .extend(removed.iter().map(|item| redactor.apply(item)));
+                        output
+                    }))
+                }
+                step => builtin(self.run_builtin_step(step, &env)),
+            };
+            let duration = self.clock.now().duration_since(started).unwrap_or_default();
+            stdout.push_str(&output);
+            let step = redactor.apply(&step.to_string());
+
+            if let Some(e) = &error {
+                // Name the step, so the failure can be found in a long sequence
+                result.error = Some(match single {
+                    true => e.clone(),
+                    false => UpdateError::with_kind(
+                        e.kind,
+                        format!(
+                            "Failed at step {}/{}: {} ({})",
+                            index + 1,
+                            steps.len(),
+                            step,
+                            e.message
+                        ),
+                    ),
+                });
+            }
+            result.steps.push(StepResult {
+                step,
+                success: error.is_none(),
+                exit_code,
+                duration,
+                output,
+            });
+            if error.is_some() {
+                break;
             }
         }
+        result.output = self.limit_output(stdout);
+        Ok(result)
     }
 
-    // Execute a single command
-    fn run_single_command(&self, command: &str) -> Result<ExitStatus, UpdateError> {
+    // Run a step that updog implements itself, returning its output as is
+    // (the sequence redacts and shows it). A failure stops the sequence like
+    // a failed command.
+    fn run_builtin_step(
+        &self,
+        step: &Step,
+        env: &[(String, String)],
+    ) -> Result<String, UpdateError> {
         if self.dry_run {
-            info!("Dry run: would execute command: {}", command);
-            return Ok(ExitStatus::default()); // Simulate success in dry run mode
+            info!("Dry run: would run step: {}", step);
+            return Ok(String::new());
         }
-
-        let shell = if cfg!(target_os = "windows") {
-            "cmd"
-        } else {
-            "bash"
-        };
-
-        let shell_arg = if cfg!(target_os = "windows") {
-            "/C"
-        } else {
-            "-c"
-        };
-
-        info!("Executing command: {}", command);
-
-        // Launch the command
-        let mut process = match Command::new(shell)
-            .arg(shell_arg)
-            .arg(command)
-            .stderr(Stdio::inherit())
-            .stdout(Stdio::inherit())
-            .spawn()
-        {
-            Ok(p) => p,
-            Err(e) => {
-                return Err(UpdateError {
-                    message: format!("Failed to execute command: {}", e),
-                });
+        match step {
+            Step::Command(_) | Step::Register { .. } => {
+                unreachable!("commands are run by the shell")
             }
-        };
+            Step::PresetUpdate(_) | Step::PresetCleanup(_) => {
+                unreachable!("preset updates and cleanups report to the sequence")
+            }
+            Step::Verify { verify } => {
+                verify.run(self.executor.as_ref())?;
+                info!("Verified {}", verify.file);
+                Ok(String::new())
+            }
+            Step::Download { download } => {
+                download.run(env, self.clock.as_ref(), &|| self.shutdown_requested())?;
+                Ok(String::new())
+            }
+            Step::CheckHttp { check_http } => check_http.run(self.executor.as_ref(), env),
+            Step::ReleaseCheck(release) => release.check(self.executor.as_ref(), env),
+            Step::PresetCheck(preset) => preset.check(self.executor.as_ref(), env),
+            Step::GitCheck(repo) => repo.check(self.executor.as_ref(), env),
+            Step::ImageCheck(images) => images.check(self.executor.as_ref(), env),
+            Step::ImagePull(images) => images.pull(self.executor.as_ref(), env),
+            Step::ReleaseInstall(release) => {
+                release.install(self.executor.as_ref(), env, self.clock.as_ref(), &|| {
+                    self.shutdown_requested()
+                })
+            }
+        }
+    }
 
-        // Get the process ID for tracking
-        let pid = process.id();
+    // What a dry run can tell about a command without running it: a
+    // program that isn't installed fails it, and sudo that would ask for a
+    // password (probed with `sudo -n true`, once per sequence) is warned about.
+    // Remote commands need only ssh here; the host isn't contacted.
+    fn preflight(
+        &self,
+        command: &str,
+        route: &Route,
+        sudo_probed: &mut bool,
+    ) -> Result<(), UpdateError> {
+        if !self.dry_run {
+            return Ok(());
+        }
+        if route.ssh.is_some() {
+            return match self.executor.finds("ssh") {
+                true => Ok(()),
+                false => Err(UpdateError::new("ssh is not installed (not found on PATH)")),
+            };
+        }
+        // A placeholder is filled in from an earlier step's output
+        let programs = lint::program(command)
+            .filter(|program| !program.contains("{{"))
+            .into_iter()
+            .chain(lint::uses_sudo(command).then_some("sudo"));
+        for program in programs {
+            if !self.executor.finds(program) {
+                return Err(UpdateError::new(format!(
+                    "{} is not installed (not found on PATH)",
+                    program
+                )));
+            }
+        }
+        if lint::uses_sudo(command) && !std::mem::replace(sudo_probed, true) {
+            let probe = CommandRequest {
+                command: "sudo -n true".to_string(),
+                capture: true,
+                tee: false,
+                env: Vec::new(),
+                stdin: Some(String::new()),
+                clean_env: false,
+            };
+            if !self
+                .executor
+                .run(&probe)
+                .is_ok_and(|output| output.success())
+            {
+                warnings::warn("sudo would ask for a password");
+            }
+        }
+        Ok(())
+    }
 
-        // Register the process with the tracker
-        {
-            let mut tracker = self.process_tracker.lock().unwrap();
-            tracker.register_process(pid);
-
-            // Check if shutdown was requested before we even started
-            if tracker.is_shutdown_requested() {
-                drop(tracker); // Release the lock before terminating
-
-                // If so, terminate immediately
-                #[cfg(unix)]
-                unsafe {
-                    libc::kill(pid as i32, libc::SIGTERM);
-                    info!("Terminated process {} due to shutdown request", pid);
-                }
+    // Apply the configured head/tail retention to captured output
+    fn limit_output(&self, output: String) -> String {
+        let (output, removed) = self.config.output_limit.apply(output);
+        if removed > 0 {
+            warnings::warn(format_args!(
+                "Captured output truncated by {} bytes (output_limit)",
+                removed
+            ));
+        }
+        output
+    }
 
-                return Err(UpdateError {
-                    message: String::from("Operation was cancelled"),
-                });
+    // Execute a single command, returning its output and whether it worked
+    // (by `status` and the exit status, see `StatusOverride::judge`). A
+    // remote command runs through ssh, and fails as unreachable when ssh
+    // itself does.
+    #[allow(clippy::too_many_arguments)]
+    fn run_single_command(
+        &self,
+        command: &str,
+        route: &Route,
+        capture: Capture,
+        env: &[(String, String)],
+        redactor: &Redactor,
+        filter: Option<&OutputFilter>,
+        status: &StatusOverride,
+    ) -> Result<(CommandOutput, Result<(), String>), UpdateError> {
+        let mut command = command.to_string();
+        if let Some(limit) = route.bandwidth {
+            match limit.apply(&command, route.trickle) {
+                Ok(limited) => command = limited,
+                Err(reason) => warnings::warn(format_args!("bandwidth_limit: {}", reason)),
             }
         }
-
-        // Wait for the process to complete
-        let exit_status = match process.wait() {
-            Ok(status) => status,
-            Err(e) => {
-                return Err(UpdateError {
-                    message: format!("Failed to wait for command: {}", e),
-                });
+        if let Some(target) = route.ssh {
+            // A clean environment is for the command on the host; ssh itself
+            // needs updog's to find its keys and agent
+            if let Some(pass_env) = route.clean_env {
+                let keep: Vec<&str> = route
+                    .send_env
+                    .iter()
+                    .copied()
+                    .chain(pass_env.iter().map(String::as_str))
+                    .collect();
+                command = SshTarget::clean_env(&command, &keep);
             }
+            command = target.wrap(&command, &route.send_env);
+        }
+        let command = command.as_str();
+        if self.dry_run {
+            info!("Dry run: would execute command: {}", command);
+            // Simulate success in dry run mode
+            let output = CommandOutput {
+                exit_code: Some(0),
+                ..Default::default()
+            };
+            return Ok((output, Ok(())));
+        }
+
+        let request = CommandRequest {
+            command: command.to_string(),
+            capture: capture.is_captured(),
+            tee: capture == Capture::Teed,
+            env: env.to_vec(),
+            stdin: self.keyboard_controls.then(String::new),
+            clean_env: route.clean_env.is_some() && route.ssh.is_none(),
         };
+        let mut output = self.executor.run(&request)?;
+        if !redactor.is_empty() {
+            output.stdout = redactor.apply(&output.stdout);
+            output.stderr = redactor.apply(&output.stderr);
+        }
+        if let Some(reason) = route.ssh.and_then(|t| t.unreachable(&output)) {
+            error!("{}", reason);
+            return Err(UpdateError::with_kind(UpdateErrorKind::Unreachable, reason));
+        }
+        // Judged before filtering, so dropped lines still count
+        let outcome = status.judge(&output);
 
-        // Unregister the process when it's done
-        {
-            let mut tracker = self.process_tracker.lock().unwrap();
-            tracker.unregister_process(pid);
+        if let Some(filter) = filter {
+            output.stdout = filter.apply(&output.stdout);
+            output.stderr = filter.apply(&output.stderr);
+        }
+
+        // Teed output has been shown already
+        if capture == Capture::Captured && self.echo_output {
+            // Colors are kept for re-display on a terminal, stripped otherwise
+            let for_stream = |text: &str, is_terminal: bool| {
+                if is_terminal {
+                    text.to_string()
+                } else {
+                    strip_ansi(text)
+                }
+            };
+            print!(
+                "{}",
+                for_stream(&output.stdout, std::io::stdout().is_terminal())
+            );
+            let _ = std::io::stdout().flush();
+            eprint!(
+                "{}",
+                for_stream(&output.stderr, std::io::stderr().is_terminal())
+            );
         }
 
         // Check the exit status
-        if exit_status.success() {
-            info!("Command completed successfully");
-        } else {
-            error!("Command failed with exit code: {}", exit_status);
+        match &outcome {
+            Ok(()) if output.success() => info!("Command completed successfully"),
+            Ok(()) => info!(
+                "Command completed successfully (output matched success_regex, {})",
+                output.status_description()
+            ),
+            Err(failure) => error!("Command failed with {}", failure),
         }
 
-        Ok(exit_status)
+        Ok((output, outcome))
     }
 
     pub fn new(config: Config) -> Self {
-        let process_tracker = Arc::new(Mutex::new(ProcessTracker::new()));
-        let pm = Self {
-            config,
-            dry_run: false,
-            process_tracker: process_tracker.clone(),
-        };
-
-        Self::setup_signal_handlers(process_tracker);
-        pm
+        Self::with_dry_run(config, false)
     }
 
     pub fn with_dry_run(config: Config, dry_run: bool) -> Self {
-        let process_tracker = Arc::new(Mutex::new(ProcessTracker::new()));
-        let pm = Self {
+        let executor = audited(
+            &config,
+            Arc::new(ShellExecutor::with_shell(
+                config.shell.unwrap_or_else(Shell::platform_default),
+            )),
+            Arc::new(SystemClock),
+        );
+        Self {
+            redactor: Redactor::from_config(&config),
             config,
             dry_run,
-            process_tracker: process_tracker.clone(),
-        };
-
-        Self::setup_signal_handlers(process_tracker);
-        pm
+            echo_output: true,
+            verbosity: Verbosity::Normal,
+            keyboard_controls: false,
+            config_warnings: Vec::new(),
+            run_id: None,
+            executor,
+            clock: Arc::new(SystemClock),
+        }
     }
 
     pub fn with_default_config() -> Self {
-        let process_tracker = Arc::new(Mutex::new(ProcessTracker::new()));
-        let pm = Self {
-            config: Config::default(),
+        Self::new(Config::default())
+    }
+
+    // Use a custom executor and clock (e.g. the scripted ones from `test_util`)
+    pub fn with_executor(
+        config: Config,
+        executor: Arc<dyn Executor>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        let executor = audited(&config, executor, clock.clone());
+        Self {
+            redactor: Redactor::from_config(&config),
+            config,
             dry_run: false,
-            process_tracker: process_tracker.clone(),
-        };
+            echo_output: true,
+            verbosity: Verbosity::Normal,
+            keyboard_controls: false,
+            config_warnings: Vec::new(),
+            run_id: None,
+            executor,
+            clock,
+        }
+    }
 
-        Self::setup_signal_handlers(process_tracker);
-        pm
+    // Update commands keep the terminal's stdin so that interactive prompts
+    // keep working, and show their output as it comes while capturing it
+    // (passed through when stdout isn't a terminal). Without a terminal, or
+    // when output isn't echoed (updates running side by side), it's captured.
+    fn capture_updates(&self) -> Capture {
+        if !self.echo_output || !std::io::stdin().is_terminal() {
+            Capture::Captured
+        } else if std::io::stdout().is_terminal() {
+            Capture::Teed
+        } else {
+            Capture::Passthrough
+        }
     }
 
-    #[cfg(unix)]
-    // Set up signal handlers (Unix platforms only)
-    fn setup_signal_handlers(process_tracker: Arc<Mutex<ProcessTracker>>) {
-        use signal_hook::{
-            consts::{SIGINT, SIGTERM},
-            iterator::Signals,
-        };
-        use std::thread;
+    // The same package manager running the run with `run_id`
+    pub fn for_run(&self, run_id: &str) -> Self {
+        Self {
+            run_id: Some(run_id.to_string()),
+            ..self.clone()
+        }
+    }
 
-        let mut signals = Signals::new(&[SIGINT, SIGTERM]).unwrap();
-        let process_tracker_clone = process_tracker.clone();
+    // The same package manager with its commands' output captured and handed
+    // to `sink` as they run, instead of shown on the terminal, and an empty
+    // stdin for them (`updog tui`, which has the terminal)
+    pub fn streaming_to(&self, sink: OutputSink) -> Self {
+        let shell = self.config.shell.unwrap_or_else(Shell::platform_default);
+        let executor = ShellExecutor::with_shell(shell).with_output(sink);
+        Self {
+            executor: audited(&self.config, Arc::new(executor), self.clock.clone()),
+            echo_output: false,
+            keyboard_controls: true,
+            ..self.clone()
+        }
+    }
 
-        thread::spawn(move || {
-            for sig in signals.forever() {
-                info!("Received signal: {}", sig);
-                let tracker = process_tracker_clone.lock().unwrap();
-                tracker.request_shutdown();
-                tracker.terminate_all_processes();
-                break;
-            }
-        });
+    fn run_env(&self) -> Vec<(String, String)> {
+        self.run_id
+            .iter()
+            .map(|id| (RUN_ID_ENV.to_string(), id.clone()))
+            .collect()
     }
 
-    #[cfg(not(unix))]
-    // Simple implementation on Windows
-    fn setup_signal_handlers(process_tracker: Arc<Mutex<ProcessTracker>>) {
-        // Windows signal handling requires different mechanisms
-        // For simplicity, we'll just log that it's not fully implemented
-        // but we'll keep the process_tracker so the rest of the code is consistent
-        info!("Signal handling on this platform is limited");
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
 
-        // In a real application, we'd implement proper Ctrl+C handling for Windows
-        // using the ctrlc crate or Windows-specific APIs
+    pub fn redactor(&self) -> &Redactor {
+        &self.redactor
     }
 
     // Clean up on exit
     pub fn cleanup(&self) {
-        let tracker = self.process_tracker.lock().unwrap();
-        tracker.request_shutdown();
-        tracker.terminate_all_processes();
+        self.executor.cleanup();
     }
 
-    // Execute the check command for a subcommand of a package manager
-    pub fn check_with_subcommand(
+    // Terminate the commands that are running (to skip a manager), without
+    // stopping the run
+    pub fn interrupt(&self) {
+        self.executor.interrupt();
+    }
+
+    // Whether the user asked updog to stop (SIGINT/SIGTERM)
+    pub fn shutdown_requested(&self) -> bool {
+        self.executor.shutdown_requested()
+    }
+
+    // Run the check or update command of a package manager's subcommand,
+    // with the outcome of each step
+    pub fn run_operation(
         &self,
+        operation: Operation,
         manager_name: &str,
         subcommand_name: Option<&str>,
-    ) -> Result<(), UpdateError> {
+    ) -> Result<SequenceResult, UpdateError> {
+        let display_name = format!(
+            "{}{}",
+            manager_name,
+            subcommand_name.map_or("".to_string(), |s| format!(":{}", s))
+        );
+
         // Find the package manager and subcommand
         let subcommand = self
             .config
@@ -303,84 +789,728 @@ impl PackageManager {
                         manager_name
                     ),
                 };
-                UpdateError { message }
+                UpdateError::with_kind(UpdateErrorKind::NotFound, message)
             })?;
 
-        // Check if the subcommand has a check command
-        if let Some(check_cmd) = &subcommand.command.check {
-            info!(
-                "Checking updates for {}{}...",
-                manager_name,
-                subcommand_name.map_or("".to_string(), |s| format!(":{}", s))
-            );
+        let command = match operation {
+            Operation::Check => subcommand.command.check_for(self.verbosity),
+            Operation::Update => subcommand.command.update_for(self.verbosity),
+        };
 
-            // Execute the check command
-            self.execute_command(manager_name, check_cmd)
-        } else {
-            // No check command specified for this subcommand
-            let message = format!(
-                "No check command specified for {}{}",
-                manager_name,
-                subcommand_name.map_or("".to_string(), |s| format!(":{}", s))
-            );
-            Err(UpdateError { message })
+        // Check if the subcommand has a command for this operation
+        let Some(command) = command else {
+            return Err(UpdateError::with_kind(
+                UpdateErrorKind::MissingCommand,
+                format!("No {} command specified for {}", operation, display_name),
+            ));
+        };
+
+        let manager = self.config.find_package_manager(manager_name);
+        let scripting = &self.config.scripting;
+        if let Some(script) = manager.and_then(|m| m.when_script.as_deref()) {
+            let run = script::when(scripting, manager_name, script)
+                .map_err(|e| UpdateError::new(format!("when_script of {}: {}", manager_name, e)))?;
+            if !run {
+                return Err(UpdateError::with_kind(
+                    UpdateErrorKind::Excluded,
+                    format!("Excluded by when_script: {}", display_name),
+                ));
+            }
+        }
+        if let Some(preset) = manager.and_then(|m| m.preset) {
+            preset.ensure_supported()?;
         }
+
+        // Dry runs look at locks and free space too, without waiting
+        if let Some(manager) = manager {
+            self.wait_for_locks(manager, command, &display_name)?;
+        }
+        if operation == Operation::Update {
+            self.check_free_space(manager)?;
+        }
+
+        match operation {
+            Operation::Check => info!("Checking updates for {}...", display_name),
+            Operation::Update => info!("Updating packages for {}...", display_name),
+        }
+
+        // Checks are read-only and always captured
+        let capture = match operation {
+            Operation::Check => Capture::Captured,
+            Operation::Update => self.capture_updates(),
+        };
+        let mut result = self.execute_command(
+            manager_name,
+            command,
+            capture,
+            subcommand.command.output_filter.as_ref(),
+            &subcommand.command.status_override,
+        )?;
+        if !result.is_success() || self.dry_run {
+            return Ok(result);
+        }
+
+        let output = std::mem::take(&mut result.output);
+        let output = match manager.and_then(|m| m.transform_output.as_deref()) {
+            Some(script) => {
+                script::transform(scripting, manager_name, script, &output).map_err(|e| {
+                    UpdateError::new(format!("transform_output of {}: {}", manager_name, e))
+                })
+            }
+            None => Ok(output),
+        };
+        let parser = manager.and_then(|m| m.parser.as_deref());
+        let output = match (operation, parser) {
+            (Operation::Check, Some(parser)) => {
+                output.and_then(|output| self.parse_output(parser, operation, manager_name, output))
+            }
+            // Listing what was upgraded is a bonus; the update itself worked
+            (Operation::Update, Some(parser)) if capture.is_captured() => output.map(|output| {
+                match self.parse_output(parser, operation, manager_name, output.clone()) {
+                    Ok(upgraded) => {
+                        result.upgraded = Some(count_pending(&upgraded));
+                        upgraded
+                    }
+                    Err(e) => {
+                        warnings::warn(format_args!(
+                            "Couldn't tell which packages {} upgraded: {}",
+                            manager_name, e.message
+                        ));
+                        output
+                    }
+                }
+            }),
+            _ => output,
+        };
+        match output {
+            Ok(output) => result.output = output,
+            Err(e) => result.error = Some(e),
+        }
+        Ok(result)
     }
 
-    // Execute the update command for a subcommand of a package manager
-    pub fn update_with_subcommand(
+    // Run a named action of a package manager's subcommand (`updog run`),
+    // with the outcome of each step. Its output is handled like an update's.
+    pub fn run_action(
         &self,
         manager_name: &str,
         subcommand_name: Option<&str>,
-    ) -> Result<(), UpdateError> {
-        // Find the package manager and subcommand
+        action: &str,
+    ) -> Result<SequenceResult, UpdateError> {
+        let display_name = match subcommand_name {
+            Some(sc) => format!("{}:{}", manager_name, sc),
+            None => manager_name.to_string(),
+        };
+        let manager = self
+            .config
+            .find_package_manager(manager_name)
+            .filter(|m| !m.is_group())
+            .ok_or_else(|| {
+                UpdateError::with_kind(
+                    UpdateErrorKind::NotFound,
+                    format!("Unknown package manager: {}", manager_name),
+                )
+            })?;
         let subcommand = self
             .config
             .find_subcommand(manager_name, subcommand_name)
             .ok_or_else(|| {
-                let message = match subcommand_name {
-                    Some(sc) => format!(
-                        "Unknown subcommand '{}' for package manager '{}'",
-                        sc, manager_name
-                    ),
-                    None => format!(
-                        "Unknown package manager or default subcommand: {}",
-                        manager_name
+                UpdateError::with_kind(
+                    UpdateErrorKind::NotFound,
+                    format!("Unknown subcommand: {}", display_name),
+                )
+            })?;
+        let Some(command) = subcommand.command.actions.get(action) else {
+            let defined: Vec<&str> = subcommand
+                .command
+                .actions
+                .keys()
+                .map(String::as_str)
+                .collect();
+            let defined = match defined.is_empty() {
+                true => "it has none".to_string(),
+                false => format!("it has {}", defined.join(", ")),
+            };
+            return Err(UpdateError::with_kind(
+                UpdateErrorKind::MissingCommand,
+                format!("No {} action for {} ({})", action, display_name, defined),
+            ));
+        };
+
+        self.wait_for_locks(manager, command, &display_name)?;
+        info!("Running {} for {}...", action, display_name);
+        self.execute_command(
+            manager_name,
+            command,
+            self.capture_updates(),
+            subcommand.command.output_filter.as_ref(),
+            &subcommand.command.status_override,
+        )
+    }
+
+    // Turn check output into one line per pending update, or update output
+    // into one line per upgraded package, with a WASM parser or a parser plugin
+    fn parse_output(
+        &self,
+        parser: &str,
+        operation: Operation,
+        manager_name: &str,
+        output: String,
+    ) -> Result<String, UpdateError> {
+        if wasm::is_wasm_parser(parser) {
+            let parsed = wasm::run_parser(&expand_home(parser), manager_name, operation, &output)
+                .map_err(|e| UpdateError::new(e.to_string()))?;
+            return plugin::parse_updates(parser, &parsed).map_err(UpdateError::new);
+        }
+
+        let plugin = self
+            .config
+            .loaded_plugins
+            .iter()
+            .find(|p| p.plugin.name == parser)
+            .ok_or_else(|| UpdateError::new(format!("Parser plugin {} isn't loaded", parser)))?;
+        if !plugin.handshake.parser {
+            return Err(UpdateError::new(format!(
+                "Plugin {} doesn't provide a parser",
+                parser
+            )));
+        }
+        // Checks are parsed with `parse <id>`, as before updates were
+        let args = match operation {
+            Operation::Check => vec!["parse", manager_name],
+            Operation::Update => vec!["parse", manager_name, "update"],
+        };
+        let result = self.executor.run(&CommandRequest {
+            stdin: Some(output),
+            ..CommandRequest::captured(&plugin.plugin.command(&args))
+        })?;
+        if !result.success() {
+            return Err(UpdateError::new(format!(
+                "Parser {} failed with {}",
+                parser,
+                result.status_description()
+            )));
+        }
+        plugin::parse_updates(parser, &result.stdout).map_err(UpdateError::new)
+    }
+
+    // Run a command outside any package manager (e.g. an alert), with its
+    // output captured and not echoed and the global proxy settings in its
+    // environment. Skipped in dry run mode.
+    pub fn run_hook(
+        &self,
+        command: &str,
+        env: Vec<(String, String)>,
+        stdin: Option<String>,
+    ) -> Result<CommandOutput, UpdateError> {
+        if self.dry_run {
+            info!("Dry run: would execute command: {}", command);
+            return Ok(CommandOutput {
+                exit_code: Some(0),
+                ..Default::default()
+            });
+        }
+
+        let mut env = env;
+        env.splice(0..0, self.config.proxy.iter().flat_map(ProxyConfig::env));
+        env.splice(0..0, self.run_env());
+        self.executor.run(&CommandRequest {
+            command: command.to_string(),
+            capture: true,
+            tee: false,
+            env,
+            stdin,
+            clean_env: false,
+        })
+    }
+
+    // Run the manager's `verify` commands after a successful update, turning
+    // the result into VerifyFailed if they fail
+    fn verify(
+        &self,
+        manager_name: &str,
+        message: String,
+        mut output: String,
+    ) -> (ExecutionStatus, String, String) {
+        let Some(verify) = self
+            .config
+            .find_package_manager(manager_name)
+            .and_then(|m| m.verify.as_ref())
+        else {
+            return (ExecutionStatus::Success, message, output);
+        };
+        info!("Verifying {}...", manager_name);
+        let result = self.execute_command(
+            manager_name,
+            verify,
+            Capture::Captured,
+            None,
+            &StatusOverride::default(),
+        );
+        let error = match result {
+            Ok(sequence) => {
+                output.push_str(&sequence.output);
+                sequence.error
+            }
+            Err(e) => Some(e),
+        };
+        match error {
+            None => (ExecutionStatus::Success, message, output),
+            Some(e) => (
+                ExecutionStatus::VerifyFailed,
+                format!("Verification failed: {}", e.message),
+                output,
+            ),
+        }
+    }
+
+    // Lock files the update of a manager (or subcommand) takes: the ones to
+    // wait for before running it, and that managers updated side by side
+    // can't share
+    pub fn update_locks(&self, manager_name: &str, subcommand_name: Option<&str>) -> Vec<PathBuf> {
+        let Some(manager) = self.config.find_package_manager(manager_name) else {
+            return Vec::new();
+        };
+        let subcommand = self.config.find_subcommand(manager_name, subcommand_name);
+        match subcommand
+            .as_ref()
+            .and_then(|sc| sc.command.update_for(self.verbosity))
+        {
+            Some(command) if manager.ssh.is_none() => manager_locks(manager, command),
+            _ => Vec::new(),
+        }
+    }
+
+    // Wait until no other process holds the manager's lock files, up to its
+    // `lock_wait`, or fail as busy. Dry runs warn instead of waiting.
+    fn wait_for_locks(
+        &self,
+        manager: &PackageManagerConfig,
+        command: &CommandSequence,
+        display_name: &str,
+    ) -> Result<(), UpdateError> {
+        // The locks of a remote manager are on its host
+        if manager.ssh.is_some() {
+            return Ok(());
+        }
+        let locks = manager_locks(manager, command);
+        let wait = manager.lock_wait.map_or(Duration::ZERO, |i| i.duration());
+        let started = self.clock.now();
+        let mut announced = false;
+        while let Some(held) = lock::find_held(&locks) {
+            let waited = self.clock.now().duration_since(started).unwrap_or_default();
+            if waited >= wait || self.shutdown_requested() {
+                return Err(UpdateError::with_kind(
+                    UpdateErrorKind::Busy,
+                    format!(
+                        "Package manager busy: {} is locked by another process",
+                        held.display()
                     ),
+                ));
+            }
+            if self.dry_run {
+                warnings::warn(format_args!(
+                    "{} is locked by another process; a real run would wait up to {} for it",
+                    held.display(),
+                    format_duration(wait)
+                ));
+                return Ok(());
+            }
+            if !announced {
+                info!(
+                    "Waiting up to {} for {} to be unlocked before running {}...",
+                    format_duration(wait),
+                    held.display(),
+                    display_name
+                );
+                announced = true;
+            }
+            self.clock.sleep(LOCK_POLL_INTERVAL.min(wait - waited));
+        }
+        Ok(())
+    }
+
+    // Fail when the filesystem an update writes to is fuller than the
+    // manager's (or the global) `min_free_space` allows
+    fn check_free_space(&self, manager: Option<&PackageManagerConfig>) -> Result<(), UpdateError> {
+        // Nor is there a way to look at a remote host's filesystems
+        if manager.is_some_and(|m| m.ssh.is_some()) {
+            return Ok(());
+        }
+        let Some(required) = manager
+            .and_then(|m| m.min_free_space.as_ref())
+            .or(self.config.min_free_space.as_ref())
+        else {
+            return Ok(());
+        };
+        match required.shortfall() {
+            Ok(None) => Ok(()),
+            Ok(Some(reason)) => Err(UpdateError::with_kind(UpdateErrorKind::LowSpace, reason)),
+            // Not knowing shouldn't stop the update
+            Err(e) => {
+                warnings::warn(format_args!(
+                    "Could not check free space on {}: {}",
+                    required.path, e
+                ));
+                Ok(())
+            }
+        }
+    }
+
+    // Run `run`, warning and alerting once if it's still going after
+    // `warn_after`. Slow runs are left alone; only hung ones need a timeout.
+    fn watched<T>(&self, name: &str, warn_after: Option<Duration>, run: impl FnOnce() -> T) -> T {
+        let Some(warn_after) = warn_after else {
+            return run();
+        };
+        let (done, finished) = mpsc::channel::<()>();
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                if finished.recv_timeout(warn_after) == Err(RecvTimeoutError::Timeout) {
+                    warn!("{} has been running {}", name, format_duration(warn_after));
+                    alert::send(self, &[Alert::slow(name, warn_after)]);
+                }
+            });
+            let result = run();
+            drop(done);
+            result
+        })
+    }
+
+    // Run an operation and classify the outcome for reporting
+    pub fn execute(
+        &self,
+        operation: Operation,
+        manager_name: &str,
+        subcommand_name: Option<&str>,
+    ) -> ExecutionResult {
+        let started = self.clock.now();
+        let name = match subcommand_name {
+            Some(sc) => format!("{}:{}", manager_name, sc),
+            None => manager_name.to_string(),
+        };
+        let warn_after = self
+            .config
+            .find_package_manager(manager_name)
+            .and_then(|m| m.warn_after)
+            .filter(|_| !self.dry_run)
+            .map(|interval| interval.duration());
+        let (sequence, mut warnings) = warnings::collect(|| {
+            self.watched(&name, warn_after, || {
+                self.run_operation(operation, manager_name, subcommand_name)
+            })
+        });
+        let upgraded = sequence.as_ref().ok().and_then(|s| s.upgraded);
+        let exit_code = sequence
+            .as_ref()
+            .ok()
+            .and_then(|s| s.steps.last())
+            .and_then(|step| step.exit_code);
+        let reboot_required = sequence.as_ref().is_ok_and(|s| s.reboot_required);
+        let cleaned = match &sequence {
+            Ok(sequence) => sequence.cleaned.clone(),
+            Err(_) => Vec::new(),
+        };
+        let preset = self
+            .config
+            .find_package_manager(manager_name)
+            .and_then(|m| m.preset);
+        let (steps, result) = match sequence {
+            // A single command's step would only repeat the result itself
+            Ok(sequence) if sequence.total > 1 => {
+                let steps = sequence
+                    .steps
+                    .iter()
+                    .map(|step| StepResult {
+                        step: self.redactor.apply(&step.step),
+                        ..step.clone()
+                    })
+                    .collect();
+                (steps, sequence.into_output())
+            }
+            Ok(sequence) => (Vec::new(), sequence.into_output()),
+            Err(e) => (Vec::new(), Err(e)),
+        };
+        let (status, message, output) = match result {
+            Ok(output) => match operation {
+                Operation::Check if !self.dry_run && count_pending(&output) > 0 => {
+                    let count = count_pending(&output);
+                    let mut message = format!("{} updates available", count);
+                    if let Some(breakdown) = preset.and_then(|p| p.breakdown(&output)) {
+                        message = format!("{} ({})", message, breakdown);
+                    }
+                    (ExecutionStatus::UpdatesAvailable { count }, message, output)
+                }
+                Operation::Check => (
+                    ExecutionStatus::Success,
+                    "Successfully checked for updates".to_string(),
+                    output,
+                ),
+                Operation::Update => (
+                    ExecutionStatus::Success,
+                    match upgraded {
+                        Some(1) => "1 package upgraded".to_string(),
+                        Some(count) => format!("{} packages upgraded", count),
+                        None => "Successfully updated".to_string(),
+                    },
+                    output,
+                ),
+            },
+            Err(e) => {
+                let status = match e.kind {
+                    UpdateErrorKind::MissingCommand
+                    | UpdateErrorKind::Excluded
+                    | UpdateErrorKind::Busy
+                    | UpdateErrorKind::LowSpace => ExecutionStatus::Skipped {
+                        reason: e.message.clone(),
+                    },
+                    UpdateErrorKind::Cancelled => ExecutionStatus::Cancelled,
+                    UpdateErrorKind::TimedOut => ExecutionStatus::TimedOut,
+                    UpdateErrorKind::Unreachable => ExecutionStatus::Unreachable,
+                    UpdateErrorKind::Failed | UpdateErrorKind::NotFound => ExecutionStatus::Failed,
                 };
-                UpdateError { message }
-            })?;
+                let message = match status {
+                    ExecutionStatus::Skipped { .. } => e.message.clone(),
+                    _ => format!("Error: {}", e),
+                };
+                (status, message, String::new())
+            }
+        };
+        let (status, message, output) = match status {
+            ExecutionStatus::Success if operation == Operation::Update && !self.dry_run => {
+                self.verify(manager_name, message, output)
+            }
+            _ => (status, message, output),
+        };
+        let message = match status {
+            ExecutionStatus::Success if reboot_required => {
+                format!("{} (reboot required)", message)
+            }
+            _ => message,
+        };
+        let duration = self.clock.now().duration_since(started).unwrap_or_default();
+        // The log warned while it ran; the summary says how long it took
+        if let Some(warn_after) = warn_after.filter(|&warn_after| duration > warn_after) {
+            warnings.push(format!(
+                "Took {}, longer than warn_after {}",
+                format_duration(duration),
+                format_duration(warn_after)
+            ));
+        }
 
-        // Check if the subcommand has an update command
-        if let Some(update_cmd) = &subcommand.command.update {
-            info!(
-                "Updating packages for {}{}...",
-                manager_name,
-                subcommand_name.map_or("".to_string(), |s| format!(":{}", s))
-            );
+        let columns = self
+            .config
+            .find_package_manager(manager_name)
+            .map(|m| summary_columns(&m.summary_columns, &output))
+            .unwrap_or_default();
+
+        ExecutionResult {
+            manager: manager_name.to_string(),
+            subcommand: subcommand_name.map(|s| s.to_string()),
+            status,
+            message: self.redactor.apply(&message),
+            duration,
+            output,
+            steps,
+            exit_code,
+            upgraded,
+            cached: false,
+            reboot_required,
+            cleaned,
+            warnings: warnings.iter().map(|w| self.redactor.apply(w)).collect(),
+            columns,
+        }
+    }
+
+    // Execute the check command for a subcommand of a package manager
+    pub fn check_with_subcommand(
+        &self,
+        manager_name: &str,
+        subcommand_name: Option<&str>,
+    ) -> Result<String, UpdateError> {
+        self.run_operation(Operation::Check, manager_name, subcommand_name)
+            .and_then(SequenceResult::into_output)
+    }
 
-            // Execute the update command
-            self.execute_command(manager_name, update_cmd)
-        } else {
-            // No update command specified for this subcommand
-            let message = format!(
-                "No update command specified for {}{}",
-                manager_name,
-                subcommand_name.map_or("".to_string(), |s| format!(":{}", s))
-            );
-            Err(UpdateError { message })
-        }
+    // Execute the update command for a subcommand of a package manager
+    pub fn update_with_subcommand(
+        &self,
+        manager_name: &str,
+        subcommand_name: Option<&str>,
+    ) -> Result<String, UpdateError> {
+        self.run_operation(Operation::Update, manager_name, subcommand_name)
+            .and_then(SequenceResult::into_output)
     }
 
     // Check for updates (uses default subcommand)
-    pub fn check(&self, manager_name: &str) -> Result<(), UpdateError> {
+    pub fn check(&self, manager_name: &str) -> Result<String, UpdateError> {
         self.check_with_subcommand(manager_name, None)
     }
 
     // Update packages (uses default subcommand)
-    pub fn update(&self, manager_name: &str) -> Result<(), UpdateError> {
+    pub fn update(&self, manager_name: &str) -> Result<String, UpdateError> {
         self.update_with_subcommand(manager_name, None)
     }
+
+    // Run `operation` for `items`, up to `jobs` at a time, with their results
+    // in the order of `items`. Subcommands of a manager with
+    // `parallel_subcommands` that are selected together run side by side.
+    // `controls` are the keys pressed during the run, with `--keys`.
+    pub fn run_all(
+        &self,
+        operation: Operation,
+        items: &[(String, Option<String>)],
+        jobs: usize,
+        cache: Option<&CheckCache>,
+        tracker: &Mutex<impl RunTracker>,
+        controls: Option<&Controls>,
+    ) -> Vec<ExecutionResult> {
+        let batches = concurrency::batches(items, |manager| {
+            self.config
+                .find_package_manager(manager)
+                .is_some_and(|m| m.parallel_subcommands)
+        });
+        // Output of concurrent commands would interleave, so it's printed once
+        // they have all finished
+        let parallel = jobs > 1 && batches.len() > 1;
+        let quiet = (parallel || batches.iter().any(|batch| batch.len() > 1)).then(|| {
+            let mut quiet = self.clone();
+            quiet.echo_output = false;
+            quiet
+        });
+        let quiet = quiet.as_ref().unwrap_or(self);
+        // Updates stop when the battery or disk runs low
+        let guard = (operation == Operation::Update && !self.dry_run)
+            .then(|| Mutex::new(RunGuard::new(&self.config)));
+        // Updates side by side take turns on a manager and on lock files, in
+        // the order they were selected
+        let claims = (operation == Operation::Update && parallel).then(|| {
+            Claims::new(
+                batches
+                    .iter()
+                    .map(|batch| {
+                        let mut keys = Vec::new();
+                        for &item in batch {
+                            let (manager, subcommand) = &items[item];
+                            keys.push(format!("manager {}", manager));
+                            keys.extend(
+                                self.update_locks(manager, subcommand.as_deref())
+                                    .iter()
+                                    .map(|lock| format!("lock {}", lock.display())),
+                            );
+                        }
+                        keys.sort();
+                        keys.dedup();
+                        keys
+                    })
+                    .collect(),
+            )
+        });
+
+        let run = |index: usize, runner: &PackageManager| {
+            // Log lines of the run carry its id, also on the threads of a
+            // concurrent check
+            let _span = self
+                .run_id
+                .as_deref()
+                .map(|id| info_span!("run", id = %id).entered());
+            let (manager_name, subcommand) = &items[index];
+            if controls.is_some_and(|controls| !controls.proceed()) {
+                let result = ExecutionResult::skipped(
+                    manager_name.clone(),
+                    subcommand.clone(),
+                    "Run stopped",
+                    "Not started: stopped from the keyboard".to_string(),
+                );
+                tracker.lock().unwrap().leave(index, &result);
+                return result;
+            }
+            if let Some(reason) = guard.as_ref().and_then(|g| g.lock().unwrap().check()) {
+                let result = ExecutionResult::skipped(
+                    manager_name.clone(),
+                    subcommand.clone(),
+                    "Run stopped",
+                    format!("Not started: {}", reason),
+                );
+                tracker.lock().unwrap().leave(index, &result);
+                return result;
+            }
+            tracker.lock().unwrap().start(index);
+
+            // Execute the command with the appropriate subcommand
+            let cached = cache.and_then(|cache| {
+                cache.fresh(
+                    &self.config,
+                    manager_name,
+                    subcommand.as_deref(),
+                    self.verbosity,
+                    self.clock().now(),
+                )
+            });
+            let mut result = match cached {
+                Some(result) => result,
+                None => runner.execute(operation, manager_name, subcommand.as_deref()),
+            };
+            if controls.is_some_and(Controls::skipped) {
+                result = ExecutionResult::skipped(
+                    manager_name.clone(),
+                    subcommand.clone(),
+                    "Skipped",
+                    "Skipped from the keyboard".to_string(),
+                );
+            }
+            tracker.lock().unwrap().finish(index, &result);
+            result
+        };
+
+        let results = concurrency::run_parallel(jobs, batches.len(), |index| {
+            let batch = &batches[index];
+            let _claim = claims.as_ref().map(|claims| claims.claim(index));
+            if let [index] = batch[..] {
+                let result = run(index, if parallel { quiet } else { self });
+                // Add visual separator between package managers
+                if !parallel && items.len() > 1 && self.echo_output {
+                    println!("\n----------------------------------------------\n");
+                }
+                return vec![result];
+            }
+            let results =
+                concurrency::run_parallel(batch.len(), batch.len(), |i| run(batch[i], quiet));
+            if !parallel && self.echo_output {
+                print_outputs(&results);
+            }
+            results
+        });
+        let results: Vec<ExecutionResult> = results.into_iter().flatten().collect();
+
+        if parallel && self.echo_output {
+            print_outputs(&results);
+        }
+        results
+    }
+}
+
+// Follows a run of `PackageManager::run_all` through its items, by index:
+// they may start and finish in any order when they run concurrently
+pub trait RunTracker: Send {
+    fn start(&mut self, index: usize);
+    fn finish(&mut self, index: usize, result: &ExecutionResult);
+    // An item the run stopped before starting it
+    fn leave(&mut self, index: usize, result: &ExecutionResult);
+}
+
+// The output of managers that ran side by side, one after another
+fn print_outputs(results: &[ExecutionResult]) {
+    let terminal = std::io::stdout().is_terminal();
+    for result in results.iter().filter(|r| !r.output.trim().is_empty()) {
+        let output = if terminal {
+            result.output.clone()
+        } else {
+            strip_ansi(&result.output)
+        };
+        println!("{}:\n{}", result.name(), output.trim_end());
+        println!("\n----------------------------------------------\n");
+    }
 }
 
 #[cfg(test)]
@@ -510,9 +1640,7 @@ mod tests {
 
     #[test]
     fn test_update_error_display() {
-        let error = UpdateError {
-            message: "Test error".to_string(),
-        };
+        let error = UpdateError::new("Test error");
         assert_eq!(error.to_string(), "Update error: Test error");
     }
 
@@ -566,32 +1694,1414 @@ mod tests {
     fn test_simple_format_compatibility() {
         let config = create_test_config_with_simple_format();
         let pm = PackageManager::new(config);
-        
+
         // Test package manager with direct check/update fields only
         let result = pm.check("simple");
         assert!(result.is_ok());
-        
+
         let result = pm.update("simple");
         assert!(result.is_ok());
-        
+
         // Test error handling for failing direct check/update fields
         let result = pm.check("simple_fail");
         assert!(result.is_err());
-        
+
         let result = pm.update("simple_fail");
         assert!(result.is_err());
-        
+
         // Test behavior when both direct fields and subcommands are present
         // Default behavior: subcommands take priority
         let result = pm.check("mixed");
         assert!(result.is_ok());
-        
+
         // Test with specific subcommand specified
         let result = pm.check_with_subcommand("mixed", Some("sub"));
         assert!(result.is_ok());
-        
+
         // Test with nonexistent subcommand
         let result = pm.check_with_subcommand("mixed", Some("nonexistent"));
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_scripted_sequence_stops_at_first_failure() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor
+            .script("echo checking step 1", ScriptedCommand::exit(2))
+            .script("echo updating step 1", ScriptedCommand::success());
+
+        let pm = PackageManager::with_executor(
+            create_test_config_with_subcommands(),
+            executor.clone(),
+            clock,
+        );
+
+        let result = pm.check_with_subcommand("test", Some("multi"));
+        assert_eq!(
+            result.unwrap_err().message,
+            "Failed at step 1/2: echo checking step 1 (exit status: 2)"
+        );
+        assert_eq!(executor.commands(), vec!["echo checking step 1"]);
+
+        let sequence = pm
+            .run_operation(Operation::Check, "test", Some("multi"))
+            .unwrap();
+        assert_eq!(sequence.total, 2);
+        assert_eq!(sequence.steps.len(), 1);
+        let (number, step) = sequence.failed_step().unwrap();
+        assert_eq!((number, step.exit_code), (1, Some(2)));
+        let result = pm.execute(Operation::Check, "test", Some("multi"));
+        assert_eq!(result.steps, sequence.steps);
+
+        let result = pm.update("test");
+        assert!(result.is_ok());
+        // A single command's step would only repeat the result
+        let result = pm.execute(Operation::Update, "test", None);
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert!(result.steps.is_empty());
+    }
+
+    #[test]
+    fn test_registered_output_in_later_steps() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let config: Config = serde_yaml::from_str(
+            r#"
+            commands:
+              - id: tool
+                update:
+                  - run: latest-version tool
+                    register: version
+                  - install tool@{{version}} {{other}}
+            "#,
+        )
+        .unwrap();
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor
+            .script(
+                "latest-version tool",
+                ScriptedCommand::success().stdout("1.4.2\n"),
+            )
+            .script("install tool@1.4.2 {{other}}", ScriptedCommand::success());
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock);
+        pm.echo_output = false;
+
+        let sequence = pm.run_operation(Operation::Update, "tool", None).unwrap();
+        assert!(sequence.is_success(), "{:?}", sequence.error);
+        assert_eq!(
+            executor.commands(),
+            vec!["latest-version tool", "install tool@1.4.2 {{other}}"]
+        );
+        assert_eq!(
+            sequence.steps[0].step,
+            "latest-version tool (register version)"
+        );
+        assert_eq!(sequence.steps[1].step, "install tool@1.4.2 {{other}}");
+    }
+
+    #[test]
+    fn test_failed_verify_step_stops_sequence() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("tool.tar.gz");
+        std::fs::write(&file, "tampered").unwrap();
+        let yaml = format!(
+            r#"
+            commands:
+              - id: tool
+                update:
+                  - curl -LO https://example.com/tool.tar.gz
+                  - verify:
+                      file: {}
+                      sha256: "{}"
+                  - tar xzf tool.tar.gz
+            "#,
+            file.display(),
+            "0".repeat(64)
+        );
+        let config: Config = serde_yaml::from_str(&yaml).unwrap();
+        config.validate().unwrap();
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor
+            .script(
+                "curl -LO https://example.com/tool.tar.gz",
+                ScriptedCommand::success(),
+            )
+            .script("tar xzf tool.tar.gz", ScriptedCommand::success());
+        let pm = PackageManager::with_executor(config, executor.clone(), clock);
+
+        let err = pm.update("tool").unwrap_err();
+        assert!(err.message.starts_with("Failed at step 2/3: verify "));
+        assert!(err.message.contains("SHA-256 mismatch"));
+        assert_eq!(
+            executor.commands(),
+            vec!["curl -LO https://example.com/tool.tar.gz"]
+        );
+    }
+
+    #[test]
+    fn test_parser_plugin_turns_check_output_into_updates() {
+        use crate::plugin::{Handshake, LoadedPlugin, Plugin};
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let yaml = r#"
+        plugins: [flatpak]
+        commands:
+          - id: flatpak
+            check: "flatpak remote-ls --updates"
+            parser: flatpak
+        "#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        config.loaded_plugins.push(LoadedPlugin {
+            plugin: Plugin {
+                name: "flatpak".to_string(),
+                path: "/bin/updog-flatpak".into(),
+            },
+            handshake: Handshake {
+                protocol: 1,
+                parser: true,
+                ..Default::default()
+            },
+        });
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor
+            .script(
+                "flatpak remote-ls --updates",
+                ScriptedCommand::success().stdout("GIMP\torg.gimp.GIMP\t2.10.38\n"),
+            )
+            .script(
+                "'/bin/updog-flatpak' 'parse' 'flatpak'",
+                ScriptedCommand::success()
+                    .stdout(r#"{"updates": [{"name": "org.gimp.GIMP", "latest": "2.10.38"}]}"#),
+            );
+        let pm = PackageManager::with_executor(config, executor.clone(), clock);
+
+        assert_eq!(pm.check("flatpak").unwrap(), "org.gimp.GIMP -> 2.10.38\n");
+        assert_eq!(
+            executor.calls()[1].request.stdin.as_deref(),
+            Some("GIMP\torg.gimp.GIMP\t2.10.38\n")
+        );
+    }
+
+    #[test]
+    fn test_parser_lists_upgraded_packages() {
+        use crate::plugin::{Handshake, LoadedPlugin, Plugin};
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let yaml = r#"
+        plugins: [flatpak]
+        commands:
+          - id: flatpak
+            update: "flatpak update -y"
+            parser: flatpak
+        "#;
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.loaded_plugins.push(LoadedPlugin {
+            plugin: Plugin {
+                name: "flatpak".to_string(),
+                path: "/bin/updog-flatpak".into(),
+            },
+            handshake: Handshake {
+                protocol: 1,
+                parser: true,
+                ..Default::default()
+            },
+        });
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor
+            .script(
+                "flatpak update -y",
+                ScriptedCommand::success().stdout("Updating org.gimp.GIMP\nUpdating org.kde.krita\n"),
+            )
+            .script(
+                "'/bin/updog-flatpak' 'parse' 'flatpak' 'update'",
+                ScriptedCommand::success().stdout(
+                    r#"{"updates": [{"name": "org.gimp.GIMP", "current": "2.10.36", "latest": "2.10.38"},
+                                    {"name": "org.kde.krita"}]}"#,
+                ),
+            )
+            .script(
+                "'/bin/updog-flatpak' 'parse' 'flatpak' 'update'",
+                ScriptedCommand::exit(1),
+            );
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock);
+        pm.echo_output = false;
+
+        let result = pm.execute(Operation::Update, "flatpak", None);
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(result.message, "2 packages upgraded");
+        assert_eq!(result.upgraded, Some(2));
+        assert_eq!(
+            result.output,
+            "org.gimp.GIMP 2.10.36 -> 2.10.38\norg.kde.krita\n"
+        );
+
+        // A parser that can't make sense of the output doesn't fail the update
+        let result = pm.execute(Operation::Update, "flatpak", None);
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(result.message, "Successfully updated");
+        assert_eq!(result.upgraded, None);
+        assert!(result.output.starts_with("Updating org.gimp.GIMP"));
+
+        let mut report =
+            crate::report::ExecutionReport::new(Operation::Update, std::time::UNIX_EPOCH);
+        report.results.push(result);
+        let record = report.to_run_record();
+        assert_eq!(
+            record.results[0].output,
+            "Updating org.gimp.GIMP\nUpdating org.kde.krita\n"
+        );
+    }
+
+    #[test]
+    fn test_when_script_and_transform_output() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let yaml = r#"
+        commands:
+          - id: apt
+            check: "apt list --upgradable"
+            transform_output: 'output.split("\n").filter(|l| l != "" && !l.starts_with("Listing"))'
+          - id: winget
+            check: "winget upgrade"
+            when_script: 'os == "none"'
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor.script(
+            "apt list --upgradable",
+            ScriptedCommand::success().stdout("Listing... Done\ngit/stable 2.41\n"),
+        );
+        let pm = PackageManager::with_executor(config, executor.clone(), clock);
+
+        let result = pm.execute(Operation::Check, "apt", None);
+        assert_eq!(
+            result.status,
+            ExecutionStatus::UpdatesAvailable { count: 1 }
+        );
+        assert_eq!(result.output, "git/stable 2.41\n");
+
+        let result = pm.execute(Operation::Check, "winget", None);
+        assert!(matches!(result.status, ExecutionStatus::Skipped { .. }));
+        assert_eq!(executor.commands(), vec!["apt list --upgradable"]);
+    }
+
+    #[test]
+    fn test_output_filter_applied_to_captured_output() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let yaml = r#"
+        commands:
+          - id: npm
+            check: "npm outdated -g"
+            output_filter:
+              drop: funding
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor.script(
+            "npm outdated -g",
+            ScriptedCommand::success()
+                .stdout("typescript 5.0 5.1\n3 packages are looking for funding\n"),
+        );
+
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock);
+        pm.echo_output = false;
+
+        let output = pm.check("npm").unwrap();
+        assert_eq!(output, "typescript 5.0 5.1\n");
+        assert!(executor.calls()[0].request.capture);
+    }
+
+    #[test]
+    fn test_status_regexes_override_exit_status() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let yaml = r#"
+        commands:
+          - id: mas
+            failure_regex: "^Error:"
+            subcommands:
+              - id: apps
+                update: "mas upgrade"
+              - id: lenient
+                update: "mas lenient"
+                success_regex: "^No updates"
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor
+            .script(
+                "mas upgrade",
+                ScriptedCommand::success().stdout("Error: not signed in\n"),
+            )
+            .script(
+                "mas lenient",
+                ScriptedCommand::exit(1).stdout("No updates found\n"),
+            );
+        let mut pm = PackageManager::with_executor(config, executor, clock);
+        pm.echo_output = false;
+
+        let err = pm.update_with_subcommand("mas", Some("apps")).unwrap_err();
+        assert_eq!(
+            err.message,
+            "Command failed with output matching failure_regex '^Error:'"
+        );
+        assert_eq!(
+            pm.update_with_subcommand("mas", Some("lenient")).unwrap(),
+            "No updates found\n"
+        );
+    }
+
+    #[test]
+    fn test_captured_output_is_limited() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let yaml = r#"
+        commands:
+          - id: noisy
+            check: "noisy check"
+        output_limit:
+          head_kb: 1
+          tail_kb: 1
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor.script(
+            "noisy check",
+            ScriptedCommand::success().stdout(&"x".repeat(10_000)),
+        );
+
+        let mut pm = PackageManager::with_executor(config, executor, clock);
+        pm.echo_output = false;
+
+        let output = pm.check("noisy").unwrap();
+        assert!(output.len() < 3000);
+        assert!(output.contains("bytes truncated"));
+    }
+
+    #[test]
+    fn test_force_color_sets_environment() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let yaml = r#"
+        commands:
+          - id: brew
+            check: "brew outdated"
+            force_color: true
+          - id: npm
+            check: "npm outdated"
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor
+            .script("brew outdated", ScriptedCommand::success())
+            .script("npm outdated", ScriptedCommand::success());
+
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock);
+        pm.echo_output = false;
+        pm.check("brew").unwrap();
+        pm.check("npm").unwrap();
+
+        let calls = executor.calls();
+        assert!(calls[0]
+            .request
+            .env
+            .contains(&("CLICOLOR_FORCE".to_string(), "1".to_string())));
+        assert_eq!(calls[1].request.env, crate::locale::Locale::default().env());
+    }
+
+    #[test]
+    fn test_proxy_settings_reach_commands() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let yaml = r#"
+        proxy:
+          https: http://proxy.lan:3128
+        commands:
+          - id: brew
+            check: "brew outdated"
+          - id: npm
+            check: "npm outdated"
+            proxy: { https: "http://egress.lan:8080", no_proxy: [registry.lan] }
+            env: { NO_PROXY: "*" }
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor
+            .script("brew outdated", ScriptedCommand::success())
+            .script("npm outdated", ScriptedCommand::success())
+            .script("notify", ScriptedCommand::success());
+
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock);
+        pm.echo_output = false;
+        pm.check("brew").unwrap();
+        pm.check("npm").unwrap();
+        pm.run_hook(
+            "notify",
+            vec![("UPDOG_EVENT".to_string(), "failed".to_string())],
+            None,
+        )
+        .unwrap();
+
+        let pairs = |env: &[(String, String)]| -> Vec<String> {
+            env.iter().map(|(k, v)| format!("{}={}", k, v)).collect()
+        };
+        let calls = executor.calls();
+        assert_eq!(
+            pairs(&calls[0].request.env),
+            [
+                "LC_ALL=C",
+                "LANG=C",
+                "LANGUAGE=C",
+                "https_proxy=http://proxy.lan:3128",
+                "HTTPS_PROXY=http://proxy.lan:3128"
+            ]
+        );
+        // The manager's section replaces the global one, and its env comes last
+        assert_eq!(
+            pairs(&calls[1].request.env),
+            [
+                "LC_ALL=C",
+                "LANG=C",
+                "LANGUAGE=C",
+                "https_proxy=http://egress.lan:8080",
+                "HTTPS_PROXY=http://egress.lan:8080",
+                "no_proxy=registry.lan",
+                "NO_PROXY=registry.lan",
+                "NO_PROXY=*",
+            ]
+        );
+        assert_eq!(
+            pairs(&calls[2].request.env),
+            [
+                "https_proxy=http://proxy.lan:3128",
+                "HTTPS_PROXY=http://proxy.lan:3128",
+                "UPDOG_EVENT=failed",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_locale_reaches_commands() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let yaml = r#"
+        locale: C.UTF-8
+        commands:
+          - id: brew
+            check: "brew outdated"
+          - id: apt
+            check: "apt list --upgradable"
+            locale: native
+          - id: npm
+            check: "npm outdated"
+            env: { LANG: en_US.UTF-8 }
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor
+            .script("brew outdated", ScriptedCommand::success())
+            .script("apt list --upgradable", ScriptedCommand::success())
+            .script("npm outdated", ScriptedCommand::success());
+
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock);
+        pm.echo_output = false;
+        pm.check("brew").unwrap();
+        pm.check("apt").unwrap();
+        pm.check("npm").unwrap();
+
+        let pairs = |env: &[(String, String)]| -> Vec<String> {
+            env.iter().map(|(k, v)| format!("{}={}", k, v)).collect()
+        };
+        let calls = executor.calls();
+        assert_eq!(
+            pairs(&calls[0].request.env),
+            ["LC_ALL=C.UTF-8", "LANG=C.UTF-8", "LANGUAGE=C.UTF-8"]
+        );
+        assert!(calls[1].request.env.is_empty());
+        // The manager's own env comes last, so it wins
+        assert_eq!(
+            pairs(&calls[2].request.env),
+            [
+                "LC_ALL=C.UTF-8",
+                "LANG=C.UTF-8",
+                "LANGUAGE=C.UTF-8",
+                "LANG=en_US.UTF-8"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clean_env() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let yaml = r#"
+        clean_env: true
+        pass_env: [PATH]
+        locale: native
+        commands:
+          - id: brew
+            check: "brew outdated"
+            pass_env: [UPDOG_TEST_UNSET]
+            env: { HOMEBREW_NO_ANALYTICS: "1" }
+          - id: npm
+            check: "npm outdated"
+            clean_env: false
+          - id: web1
+            ssh: { host: web1 }
+            check: "apt list --upgradable"
+            env: { DEBIAN_FRONTEND: noninteractive }
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor
+            .script("brew outdated", ScriptedCommand::success())
+            .script("npm outdated", ScriptedCommand::success());
+
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock);
+        pm.echo_output = false;
+        pm.check("brew").unwrap();
+        pm.check("npm").unwrap();
+
+        let calls = executor.calls();
+        assert!(calls[0].request.clean_env);
+        // Variables updog doesn't have are left out
+        assert_eq!(
+            calls[0].request.env,
+            [
+                ("PATH".to_string(), std::env::var("PATH").unwrap()),
+                ("HOMEBREW_NO_ANALYTICS".to_string(), "1".to_string()),
+            ]
+        );
+        assert!(!calls[1].request.clean_env);
+        assert!(calls[1].request.env.is_empty());
+
+        // ssh keeps updog's environment (SSH_AUTH_SOCK); the command on the
+        // host starts from a clean one, with the host's PATH
+        let _ = pm.check("web1");
+        let remote = &executor.calls()[2].request;
+        assert!(!remote.clean_env);
+        assert_eq!(
+            remote.env,
+            [("DEBIAN_FRONTEND".to_string(), "noninteractive".to_string())]
+        );
+        assert!(remote.command.contains("SendEnv=DEBIAN_FRONTEND"));
+        assert!(!remote.command.contains("SendEnv=PATH"));
+        assert!(remote.command.contains(
+            r#"env -i ${PATH+"PATH=$PATH"} ${DEBIAN_FRONTEND+"DEBIAN_FRONTEND=$DEBIAN_FRONTEND"} sh -c"#
+        ));
+    }
+
+    #[test]
+    fn test_run_action() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let yaml = r#"
+        commands:
+          - id: brew
+            actions:
+              doctor: "brew doctor"
+            subcommands:
+              - id: cask
+                update: "brew upgrade --cask"
+                actions:
+                  list: "brew list --cask"
+          - id: npm
+            update: "npm update -g"
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor
+            .script("brew doctor", ScriptedCommand::exit(1))
+            .script("brew list --cask", ScriptedCommand::success());
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock);
+        pm.echo_output = false;
+
+        pm.run_action("brew", Some("cask"), "list").unwrap();
+        // Subcommands have the manager's actions too
+        let result = pm.run_action("brew", Some("cask"), "doctor").unwrap();
+        assert!(!result.is_success());
+
+        // Without a subcommand, the default one's (cask's) actions
+        pm.run_action("brew", None, "list").unwrap();
+        let err = pm.run_action("brew", None, "cleanup").unwrap_err();
+        assert_eq!(
+            err.message,
+            "No cleanup action for brew (it has doctor, list)"
+        );
+        let err = pm.run_action("npm", None, "doctor").unwrap_err();
+        assert_eq!(err.message, "No doctor action for npm (it has none)");
+        let err = pm.run_action("pip", None, "doctor").unwrap_err();
+        assert_eq!(err.kind, UpdateErrorKind::NotFound);
+        assert_eq!(
+            executor.commands(),
+            vec!["brew list --cask", "brew doctor", "brew list --cask"]
+        );
+    }
+
+    #[test]
+    fn test_verbosity_selects_command_variant() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let yaml = r#"
+        commands:
+          - id: brew
+            update: "brew upgrade"
+            update_verbose: "brew upgrade --verbose"
+            update_quiet: "brew upgrade --quiet"
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        for cmd in [
+            "brew upgrade",
+            "brew upgrade --verbose",
+            "brew upgrade --quiet",
+        ] {
+            executor.script(cmd, ScriptedCommand::success());
+        }
+
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock);
+        pm.echo_output = false;
+        for verbosity in [Verbosity::Normal, Verbosity::Verbose, Verbosity::Quiet] {
+            pm.verbosity = verbosity;
+            pm.update("brew").unwrap();
+        }
+
+        assert_eq!(
+            executor.commands(),
+            vec![
+                "brew upgrade",
+                "brew upgrade --verbose",
+                "brew upgrade --quiet"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_warn_after_alerts_without_stopping() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let config: Config = serde_yaml::from_str(
+            r#"
+            alerts:
+              command: notify
+            commands:
+              - id: brew
+                update: brew upgrade
+                warn_after: 10m
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.find_package_manager("brew").unwrap().warn_after,
+            Some("10m".parse().unwrap())
+        );
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor
+            .script("notify", ScriptedCommand::success())
+            .script("brew upgrade", ScriptedCommand::success());
+        let pm = PackageManager::with_executor(config, executor.clone(), clock)
+            .for_run("3f2a9c1e-8b4d-4e6f-a1b2-c3d4e5f60718");
+
+        let result = pm.watched("brew", Some(Duration::from_millis(20)), || {
+            std::thread::sleep(Duration::from_millis(200));
+            42
+        });
+        assert_eq!(result, 42);
+        assert_eq!(executor.commands(), vec!["notify"]);
+        let env = &executor.calls()[0].request.env;
+        assert!(env.contains(&("UPDOG_EVENT".to_string(), "slow".to_string())));
+        assert!(env.contains(&(
+            "UPDOG_MESSAGE".to_string(),
+            "brew has been running 0.0s".to_string()
+        )));
+        let run_id = (
+            "UPDOG_RUN_ID".to_string(),
+            "3f2a9c1e-8b4d-4e6f-a1b2-c3d4e5f60718".to_string(),
+        );
+        assert!(env.contains(&run_id));
+
+        // Quick runs don't wait for the threshold
+        pm.watched("brew", Some(Duration::from_secs(600)), || ());
+        assert_eq!(executor.calls().len(), 1);
+
+        // Update commands get the run id too
+        pm.execute(Operation::Update, "brew", None);
+        assert!(executor.calls()[1].request.env.contains(&run_id));
+    }
+
+    #[test]
+    fn test_held_lock_waits_then_skips() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let dir = tempfile::tempdir().unwrap();
+        let lock = dir.path().join("db.lck");
+        std::fs::write(&lock, "").unwrap();
+        let config: Config = serde_yaml::from_str(&format!(
+            r#"
+            commands:
+              - id: pacman
+                update: sudo pacman -Syu
+                locks: [{:?}]
+                lock_wait: 1m
+            "#,
+            lock.display().to_string()
+        ))
+        .unwrap();
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor.script("sudo pacman -Syu", ScriptedCommand::success());
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock.clone());
+        pm.echo_output = false;
+
+        let started = clock.now();
+        let result = pm.execute(Operation::Update, "pacman", None);
+        assert_eq!(
+            result.status,
+            ExecutionStatus::Skipped {
+                reason: format!(
+                    "Package manager busy: {} is locked by another process",
+                    lock.display()
+                )
+            }
+        );
+        assert_eq!(
+            clock.now().duration_since(started).unwrap(),
+            Duration::from_secs(60)
+        );
+        assert!(executor.commands().is_empty());
+
+        std::fs::remove_file(&lock).unwrap();
+        let result = pm.execute(Operation::Update, "pacman", None);
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(executor.commands(), vec!["sudo pacman -Syu"]);
+    }
+
+    #[test]
+    fn test_min_free_space_skips_updates() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let config: Config = serde_yaml::from_str(
+            r#"
+            min_free_space:
+              size: 1048576T
+            commands:
+              - id: brew
+                check: brew outdated
+                update: brew upgrade
+              - id: npm
+                update: npm update -g
+                min_free_space: { size: 1K, path: /tmp }
+            "#,
+        )
+        .unwrap();
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor.script("brew outdated", ScriptedCommand::success());
+        executor.script("npm update -g", ScriptedCommand::success());
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock);
+        pm.echo_output = false;
+
+        let result = pm.execute(Operation::Update, "brew", None);
+        let ExecutionStatus::Skipped { reason } = result.status else {
+            panic!("expected a skip, got {:?}", result.status);
+        };
+        assert!(reason.starts_with("Not enough free space: "), "{}", reason);
+        assert!(reason.ends_with(" on /, 1048576T required"), "{}", reason);
+
+        // Checks don't need room, and a manager's own setting wins
+        let result = pm.execute(Operation::Check, "brew", None);
+        assert_eq!(result.status, ExecutionStatus::Success);
+        let result = pm.execute(Operation::Update, "npm", None);
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(executor.commands(), vec!["brew outdated", "npm update -g"]);
+    }
+
+    #[test]
+    fn test_dry_run_preflight() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let dir = tempfile::tempdir().unwrap();
+        let lock = dir.path().join("db.lck");
+        std::fs::write(&lock, "").unwrap();
+        let config: Config = serde_yaml::from_str(&format!(
+            r#"
+            commands:
+              - id: pacman
+                update:
+                  - sudo pacman -Sy
+                  - sudo pacman -Su
+                locks: [{:?}]
+                lock_wait: 1m
+              - id: tool
+                check:
+                  - run: "tool-lookup --bin"
+                    register: bin
+                  - "{{{{bin}}}} --version"
+                  - "gone --outdated"
+              - id: brew
+                update: brew upgrade
+                min_free_space: {{ size: 1048576T }}
+              - id: npm
+                check: npm outdated
+                env:
+                  NPM_TOKEN: secret://vault/npm
+            "#,
+            lock.display().to_string()
+        ))
+        .unwrap();
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor.missing("gone");
+        executor.script("sudo -n true", ScriptedCommand::exit(1));
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock.clone());
+        pm.dry_run = true;
+        pm.echo_output = false;
+
+        // The held lock is reported instead of waited for, and sudo probed once
+        let started = clock.now();
+        let result = pm.execute(Operation::Update, "pacman", None);
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(clock.now(), started);
+        assert_eq!(
+            result.warnings,
+            vec![
+                format!(
+                    "{} is locked by another process; a real run would wait up to 1m00s for it",
+                    lock.display()
+                ),
+                "sudo would ask for a password".to_string(),
+            ]
+        );
+        assert_eq!(executor.commands(), vec!["sudo -n true"]);
+
+        let result = pm.execute(Operation::Check, "tool", None);
+        assert_eq!(result.status, ExecutionStatus::Failed);
+        assert!(
+            result
+                .message
+                .contains("Failed at step 3/3: gone --outdated (gone is not installed"),
+            "{}",
+            result.message
+        );
+
+        let result = pm.execute(Operation::Update, "brew", None);
+        assert!(
+            matches!(&result.status, ExecutionStatus::Skipped { reason } if reason.starts_with("Not enough free space")),
+            "{:?}",
+            result.status
+        );
+        let result = pm.execute(Operation::Check, "npm", None);
+        assert!(
+            result.message.contains("unknown secret source 'vault'"),
+            "{}",
+            result.message
+        );
+        assert_eq!(executor.commands(), vec!["sudo -n true"]);
+    }
+
+    #[test]
+    fn test_remote_manager_runs_over_ssh() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let config: Config = serde_yaml::from_str(
+            r#"
+            commands:
+              - id: web1
+                ssh:
+                  host: web1
+                  user: admin
+                check: apt list --upgradable
+                update:
+                  - sudo apt-get update
+                  - sudo apt-get upgrade -y
+                env:
+                  DEBIAN_FRONTEND: noninteractive
+                locks: [/var/lib/dpkg/lock-frontend]
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        let ssh = "ssh -o BatchMode=yes -o ConnectTimeout=10 -o StrictHostKeyChecking=yes \
+                   -o SendEnv=LC_ALL -o SendEnv=LANG -o SendEnv=LANGUAGE \
+                   -o SendEnv=DEBIAN_FRONTEND -l admin -- web1";
+        executor.script(
+            &format!("{} 'apt list --upgradable'", ssh),
+            ScriptedCommand::success().stdout("curl/stable 8.5.0 amd64 [upgradable from: 8.4.0]\n"),
+        );
+        executor.script(
+            &format!("{} 'sudo apt-get update'", ssh),
+            ScriptedCommand::exit(255)
+                .stderr("ssh: connect to host web1 port 22: Connection timed out\n"),
+        );
+        let pm = PackageManager::with_executor(config, executor.clone(), clock);
+
+        let result = pm.execute(Operation::Check, "web1", None);
+        assert_eq!(
+            result.status,
+            ExecutionStatus::UpdatesAvailable { count: 1 }
+        );
+        assert_eq!(result.exit_code, Some(0));
+
+        // ssh failing is told apart from the command failing, and stops there
+        let result = pm.execute(Operation::Update, "web1", None);
+        assert_eq!(result.status, ExecutionStatus::Unreachable);
+        // 255 is ssh's, not the command's
+        assert_eq!(result.exit_code, None);
+        assert!(
+            result.message.contains(
+                "admin@web1 unreachable: ssh: connect to host web1 port 22: Connection timed out"
+            ),
+            "{}",
+            result.message
+        );
+        assert_eq!(executor.commands().len(), 2);
+
+        // Steps updog runs itself can't run on the host
+        let config: Config = serde_yaml::from_str(
+            r#"
+            commands:
+              - id: web1
+                ssh: { host: web1 }
+                update:
+                  - download: { url: "https://example.com/x", path: x }
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_bandwidth_limit() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let config: Config = serde_yaml::from_str(
+            r#"
+            shell: bash
+            commands:
+              - id: apt
+                update: sudo apt-get upgrade -y
+                bandwidth_limit: 1M
+              - id: brew
+                update: brew upgrade
+                bandwidth_limit: { rate: 256K }
+            "#,
+        )
+        .unwrap();
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        for command in [
+            "sudo apt-get -o Acquire::http::Dl-Limit=1024 upgrade -y",
+            "trickle -s -d 256 bash -c 'brew upgrade'",
+            "brew upgrade",
+        ] {
+            executor.script(command, ScriptedCommand::success());
+        }
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock);
+        pm.echo_output = false;
+
+        pm.execute(Operation::Update, "apt", None);
+        pm.execute(Operation::Update, "brew", None);
+        executor.missing("trickle");
+        let result = pm.execute(Operation::Update, "brew", None);
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(
+            result.warnings,
+            vec![
+                "bandwidth_limit: can't limit the bandwidth of 'brew upgrade' \
+                  (no rate limit option, and trickle isn't available)"
+                    .to_string()
+            ]
+        );
+        assert_eq!(
+            executor.commands(),
+            vec![
+                "sudo apt-get -o Acquire::http::Dl-Limit=1024 upgrade -y",
+                "trickle -s -d 256 bash -c 'brew upgrade'",
+                "brew upgrade",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_summary_columns_from_output() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let config: Config = serde_yaml::from_str(
+            r#"
+            commands:
+              - id: composer
+                check: composer outdated --direct
+                summary_columns:
+                  outdated: '(\d+) outdated'
+                  abandoned: '(\d+) abandoned'
+            "#,
+        )
+        .unwrap();
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor.script(
+            "composer outdated --direct",
+            ScriptedCommand::success()
+                .stdout("monolog/monolog 2.9 3.5\n1 outdated direct dependency\n"),
+        );
+        let mut pm = PackageManager::with_executor(config, executor, clock);
+        pm.echo_output = false;
+
+        let result = pm.execute(Operation::Check, "composer", None);
+        assert_eq!(
+            result.columns,
+            std::collections::BTreeMap::from([("outdated".to_string(), "1".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_verify_after_update() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let config: Config = serde_yaml::from_str(
+            r#"
+            commands:
+              - id: brew
+                check: brew outdated
+                update: brew upgrade
+                verify: [brew doctor, nvim --version]
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor
+            .script("brew outdated", ScriptedCommand::success())
+            .script(
+                "brew upgrade",
+                ScriptedCommand::success().stdout("upgraded\n"),
+            )
+            .script("brew doctor", ScriptedCommand::success())
+            .script("nvim --version", ScriptedCommand::exit(127));
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock);
+        pm.echo_output = false;
+
+        let result = pm.execute(Operation::Update, "brew", None);
+        assert_eq!(result.status, ExecutionStatus::VerifyFailed);
+        assert!(result.status.is_failure());
+        assert_eq!(
+            result.message,
+            "Verification failed: Failed at step 2/2: nvim --version (exit status: 127)"
+        );
+        assert!(result.output.starts_with("upgraded"));
+        assert_eq!(
+            executor.commands(),
+            vec!["brew upgrade", "brew doctor", "nvim --version"]
+        );
+
+        // Only updates are verified
+        let result = pm.execute(Operation::Check, "brew", None);
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(executor.commands().len(), 4);
+    }
+
+    #[test]
+    fn test_execute_classifies_results() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+        use std::time::Duration;
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor
+            .script(
+                "echo checking",
+                ScriptedCommand::success().delay(Duration::from_secs(5)),
+            )
+            .script("echo updating", ScriptedCommand::cancelled());
+
+        let mut pm = PackageManager::with_executor(create_test_config(), executor.clone(), clock);
+        pm.echo_output = false;
+
+        let result = pm.execute(Operation::Check, "test", None);
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(result.duration, Duration::from_secs(5));
+
+        // Missing commands are skipped rather than failed
+        let result = pm.execute(Operation::Check, "nocheck", None);
+        assert!(matches!(result.status, ExecutionStatus::Skipped { .. }));
+
+        let result = pm.execute(Operation::Check, "fail", None);
+        assert_eq!(result.status, ExecutionStatus::Failed);
+
+        let result = pm.execute(Operation::Check, "unknown", None);
+        assert_eq!(result.status, ExecutionStatus::Failed);
+
+        let result = pm.execute(Operation::Update, "test", None);
+        assert_eq!(result.status, ExecutionStatus::Cancelled);
+    }
+
+    #[test]
+    fn test_execute_reports_updates_available() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor.script(
+            "echo checking",
+            ScriptedCommand::success().stdout("git\ncurl\n"),
+        );
+
+        let mut pm = PackageManager::with_executor(create_test_config(), executor, clock);
+        pm.echo_output = false;
+
+        let result = pm.execute(Operation::Check, "test", None);
+        assert_eq!(
+            result.status,
+            ExecutionStatus::UpdatesAvailable { count: 2 }
+        );
+        assert_eq!(result.pending(), Some(2));
+    }
+
+    #[test]
+    fn test_secret_env_is_resolved_and_redacted() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let yaml = r#"
+        commands:
+          - id: npm
+            check: "npm outdated"
+            env:
+              NPM_TOKEN: secret://cmd/pass show npm
+              NPM_CONFIG_COLOR: "false"
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor
+            .script(
+                "pass show npm",
+                ScriptedCommand::success().stdout("t0ken\n"),
+            )
+            .script(
+                "npm outdated",
+                ScriptedCommand::success().stdout("using t0ken\nleft-pad\n"),
+            );
+
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock);
+        pm.echo_output = false;
+        let output = pm.check("npm").unwrap();
+        assert_eq!(output, "using ********\nleft-pad\n");
+
+        let calls = executor.calls();
+        let env = &calls[1].request.env;
+        assert!(env.contains(&("NPM_TOKEN".to_string(), "t0ken".to_string())));
+        assert!(env.contains(&("NPM_CONFIG_COLOR".to_string(), "false".to_string())));
+    }
+
+    #[test]
+    fn test_secrets_not_resolved_in_dry_run() {
+        use crate::test_util::{FakeClock, ScriptedExecutor};
+
+        let yaml = r#"
+        commands:
+          - id: npm
+            check: "npm outdated"
+            env:
+              NPM_TOKEN: secret://cmd/pass show npm
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock);
+        pm.dry_run = true;
+        pm.echo_output = false;
+
+        assert!(pm.check("npm").is_ok());
+        assert!(executor.calls().is_empty());
+    }
+
+    #[test]
+    fn test_secret_resolution_failure_fails_command() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let yaml = r#"
+        commands:
+          - id: npm
+            check: "npm outdated"
+            env:
+              NPM_TOKEN: secret://cmd/pass show npm
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor.script("pass show npm", ScriptedCommand::exit(1));
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock);
+        pm.echo_output = false;
+
+        let err = pm.check("npm").unwrap_err();
+        assert!(err.message.contains("Failed to resolve secret NPM_TOKEN"));
+        assert_eq!(executor.commands(), vec!["pass show npm"]);
+    }
+
+    #[test]
+    fn test_redact_rules_apply_to_captured_output() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let yaml = r#"
+        commands:
+          - id: gh
+            check: "gh extension upgrade --dry-run"
+        redact:
+          - pattern: "ghp_[A-Za-z0-9]+"
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor.script(
+            "gh extension upgrade --dry-run",
+            ScriptedCommand::success().stdout("auth ghp_abc123\n"),
+        );
+        let mut pm = PackageManager::with_executor(config, executor, clock);
+        pm.echo_output = false;
+
+        let result = pm.execute(Operation::Check, "gh", None);
+        assert_eq!(result.output, "auth ********\n");
+    }
+
+    #[test]
+    fn test_redact_rules_apply_to_builtin_step_output() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let yaml = r#"
+        commands:
+          - id: snap
+            preset: snap
+        redact:
+          - pattern: "ghp_[A-Za-z0-9]+"
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        executor.script(
+            "snap refresh --list",
+            ScriptedCommand::success()
+                .stdout("Name Version Rev Size Publisher Notes\ntool ghp_abc123 12 1MB me -\n"),
+        );
+        let mut pm = PackageManager::with_executor(config, executor, clock);
+        pm.echo_output = false;
+
+        let result = pm.execute(Operation::Check, "snap", None);
+        assert_eq!(result.output, "tool ********\n");
+    }
+
+    struct NoTracker;
+
+    impl RunTracker for NoTracker {
+        fn start(&mut self, _index: usize) {}
+        fn finish(&mut self, _index: usize, _result: &ExecutionResult) {}
+        fn leave(&mut self, _index: usize, _result: &ExecutionResult) {}
+    }
+
+    #[test]
+    fn test_run_all_keeps_manager_order() {
+        use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+
+        let config: Config = serde_yaml::from_str(
+            r#"
+            commands:
+              - id: brew
+                subcommands:
+                  - id: upgrade
+                    update: brew upgrade
+                  - id: cleanup
+                    update: brew cleanup
+                  - id: doctor
+                    update: brew doctor
+              - id: npm
+                update: npm update -g
+              - id: pip
+                update: pip upgrade
+            "#,
+        )
+        .unwrap();
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        for command in [
+            "brew upgrade",
+            "brew cleanup",
+            "brew doctor",
+            "npm update -g",
+            "pip upgrade",
+        ] {
+            executor.script(command, ScriptedCommand::success());
+        }
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock);
+        pm.echo_output = false;
+        let items: Vec<(String, Option<String>)> = [
+            ("brew", Some("upgrade")),
+            ("npm", None),
+            ("brew", Some("cleanup")),
+            ("pip", None),
+            ("brew", Some("doctor")),
+        ]
+        .iter()
+        .map(|(m, sc)| (m.to_string(), sc.map(str::to_string)))
+        .collect();
+
+        // Whichever thread gets to a manager first mustn't run it out of
+        // turn, however the threads are scheduled
+        for _ in 0..20 {
+            let tracker = Mutex::new(NoTracker);
+            let before = executor.commands().len();
+            let results = pm.run_all(Operation::Update, &items, 4, None, &tracker, None);
+            assert!(results.iter().all(|r| r.status == ExecutionStatus::Success));
+            let brew: Vec<String> = executor.commands()[before..]
+                .iter()
+                .filter(|command| command.starts_with("brew"))
+                .cloned()
+                .collect();
+            assert_eq!(brew, ["brew upgrade", "brew cleanup", "brew doctor"]);
+        }
+    }
 }
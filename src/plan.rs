@@ -0,0 +1,203 @@
+use std::io::{self, BufRead, Write};
+
+use crate::output::strip_ansi;
+use crate::report::{ExecutionResult, ExecutionStatus};
+
+// `updog update --plan`: after the check phase, list what each manager would
+// update and ask once before updating anything. Enter updates everything
+// listed, numbers leave managers out (or back in), q cancels.
+
+// Pending packages listed per manager before the rest are summed up
+const SHOWN_PACKAGES: usize = 5;
+
+// What the check found for a manager in the plan
+fn describe(check: &ExecutionResult) -> String {
+    match &check.status {
+        ExecutionStatus::UpdatesAvailable { count: 1 } => "1 update".to_string(),
+        ExecutionStatus::UpdatesAvailable { count } => format!("{} updates", count),
+        ExecutionStatus::Skipped { reason } => reason.clone(),
+        status if status.is_failure() => format!("check failed: {}", check.message),
+        _ => check.message.clone(),
+    }
+}
+
+// The plan with the managers' marks: what their checks found and the first
+// few pending packages
+pub fn render(checks: &[&ExecutionResult], included: &[bool]) -> String {
+    let name_width = checks
+        .iter()
+        .map(|check| check.name().chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut out = String::from("\nUpdate plan:\n");
+    for (i, (check, included)) in checks.iter().zip(included).enumerate() {
+        let mark = if *included { 'x' } else { ' ' };
+        out.push_str(&format!(
+            "  {:>2}. [{}] {:<width$}  {}\n",
+            i + 1,
+            mark,
+            check.name(),
+            describe(check),
+            width = name_width
+        ));
+        if check.pending().is_none() {
+            continue;
+        }
+        let output = strip_ansi(&check.output);
+        let packages: Vec<&str> = output
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        for package in packages.iter().take(SHOWN_PACKAGES) {
+            out.push_str(&format!("          {}\n", package));
+        }
+        if packages.len() > SHOWN_PACKAGES {
+            out.push_str(&format!(
+                "          ... and {} more\n",
+                packages.len() - SHOWN_PACKAGES
+            ));
+        }
+    }
+    let pending: usize = checks
+        .iter()
+        .zip(included)
+        .filter(|(_, included)| **included)
+        .filter_map(|(check, _)| check.pending())
+        .sum();
+    let managers = included.iter().filter(|included| **included).count();
+    let updates = if pending == 1 { "update" } else { "updates" };
+    out.push_str(&format!(
+        "{} pending {} in {} of {} managers\n",
+        pending,
+        updates,
+        managers,
+        checks.len()
+    ));
+    out
+}
+
+// Show the plan and ask which managers to update. Returns None when the
+// user cancels or input ends.
+pub fn confirm<R: BufRead, W: Write>(
+    checks: &[&ExecutionResult],
+    input: &mut R,
+    output: &mut W,
+) -> io::Result<Option<Vec<bool>>> {
+    let mut included = vec![true; checks.len()];
+    loop {
+        write!(output, "{}", render(checks, &included))?;
+        write!(
+            output,
+            "Enter to update the checked managers, numbers to toggle (e.g. \"1 3\"), q to cancel: "
+        )?;
+        output.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim();
+        if line.eq_ignore_ascii_case("q") {
+            return Ok(None);
+        }
+        if line.is_empty() {
+            return Ok(Some(included));
+        }
+        for word in line.split([' ', ',']).filter(|w| !w.is_empty()) {
+            match word.parse::<usize>() {
+                Ok(n) if (1..=checks.len()).contains(&n) => included[n - 1] = !included[n - 1],
+                _ => writeln!(output, "Ignoring '{}': not a number from the list.", word)?,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    fn check(manager: &str, status: ExecutionStatus, output: &str) -> ExecutionResult {
+        ExecutionResult {
+            manager: manager.to_string(),
+            subcommand: None,
+            status,
+            message: "exit status: 1".to_string(),
+            duration: Duration::from_secs(1),
+            output: output.to_string(),
+            steps: Vec::new(),
+            exit_code: None,
+            upgraded: None,
+            cached: false,
+            reboot_required: false,
+            cleaned: Vec::new(),
+            warnings: Vec::new(),
+            columns: BTreeMap::new(),
+        }
+    }
+
+    fn checks() -> Vec<ExecutionResult> {
+        vec![
+            check(
+                "homebrew",
+                ExecutionStatus::UpdatesAvailable { count: 7 },
+                "a\nb\nc\nd\ne\nf\ng\n",
+            ),
+            check(
+                "npm",
+                ExecutionStatus::UpdatesAvailable { count: 1 },
+                "npm\n",
+            ),
+            check("pip", ExecutionStatus::Failed, ""),
+        ]
+    }
+
+    #[test]
+    fn test_render() {
+        let checks = checks();
+        let checks: Vec<&ExecutionResult> = checks.iter().collect();
+        assert_eq!(
+            render(&checks, &[true, false, true]),
+            "\nUpdate plan:\n\
+             \x20  1. [x] homebrew  7 updates\n\
+             \x20         a\n\
+             \x20         b\n\
+             \x20         c\n\
+             \x20         d\n\
+             \x20         e\n\
+             \x20         ... and 2 more\n\
+             \x20  2. [ ] npm       1 update\n\
+             \x20         npm\n\
+             \x20  3. [x] pip       check failed: exit status: 1\n\
+             7 pending updates in 2 of 3 managers\n"
+        );
+    }
+
+    #[test]
+    fn test_confirm() {
+        let checks = checks();
+        let checks: Vec<&ExecutionResult> = checks.iter().collect();
+
+        let chosen = confirm(&checks, &mut Cursor::new("\n"), &mut Vec::new()).unwrap();
+        assert_eq!(chosen, Some(vec![true, true, true]));
+
+        let mut output = Vec::new();
+        let chosen = confirm(&checks, &mut Cursor::new("1 x,3\n\n"), &mut output).unwrap();
+        assert_eq!(chosen, Some(vec![false, true, false]));
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Ignoring 'x'"));
+        assert!(output.contains("1 pending update in 1 of 3 managers"));
+
+        assert_eq!(
+            confirm(&checks, &mut Cursor::new("q\n"), &mut Vec::new()).unwrap(),
+            None
+        );
+        assert_eq!(
+            confirm(&checks, &mut Cursor::new(""), &mut Vec::new()).unwrap(),
+            None
+        );
+    }
+}
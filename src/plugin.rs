@@ -0,0 +1,344 @@
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+use crate::config::{CommandSequence, Config, PackageManagerConfig};
+use crate::step::shell_quote;
+
+// Executables named updog-<name> on PATH
+pub const PREFIX: &str = "updog-";
+pub const PROTOCOL_VERSION: u32 = 1;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Error)]
+pub enum PluginError {
+    #[error("Plugin {0} not found on PATH")]
+    NotFound(String),
+    #[error("Failed to run plugin {name}: {source}")]
+    IoError {
+        name: String,
+        source: std::io::Error,
+    },
+    #[error("Plugin {name} handshake failed: {message}")]
+    HandshakeFailed { name: String, message: String },
+    #[error(
+        "Plugin {name} speaks protocol {protocol}, updog supports {}",
+        PROTOCOL_VERSION
+    )]
+    UnsupportedProtocol { name: String, protocol: u32 },
+}
+
+// What a plugin prints for `updog-<name> handshake`
+//
+//   {"protocol": 1, "description": "Flatpak apps", "managers": ["flatpak"],
+//    "parser": false, "notifier": false}
+//
+// For each of its managers, updog runs `updog-<name> check <id>` and
+// `updog-<name> update <id>`. A parser gets a check's output on stdin from
+// `updog-<name> parse <id>` and prints {"updates": [{"name", "current",
+// "latest"}]}, and an update's output from `updog-<name> parse <id> update`
+// to list what was upgraded; a notifier gets each failure alert as JSON on
+// stdin from `updog-<name> notify`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Handshake {
+    pub protocol: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub managers: Vec<String>,
+    #[serde(default)]
+    pub parser: bool,
+    #[serde(default)]
+    pub notifier: bool,
+}
+
+impl Handshake {
+    // Capabilities, as shown by `updog plugin list`
+    pub fn provides(&self) -> Vec<String> {
+        let mut provides: Vec<String> = self
+            .managers
+            .iter()
+            .map(|id| format!("manager {}", id))
+            .collect();
+        if self.parser {
+            provides.push("parser".to_string());
+        }
+        if self.notifier {
+            provides.push("notifier".to_string());
+        }
+        provides
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plugin {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+// A plugin enabled in the config, after a successful handshake
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadedPlugin {
+    pub plugin: Plugin,
+    pub handshake: Handshake,
+}
+
+// An update reported by a parser plugin
+#[derive(Debug, Clone, Deserialize)]
+struct ParsedUpdate {
+    name: String,
+    #[serde(default)]
+    current: Option<String>,
+    #[serde(default)]
+    latest: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ParseResult {
+    updates: Vec<ParsedUpdate>,
+}
+
+impl Plugin {
+    // Shell command running the plugin with `args`
+    pub fn command(&self, args: &[&str]) -> String {
+        std::iter::once(self.path.display().to_string())
+            .chain(args.iter().map(|arg| arg.to_string()))
+            .map(|arg| shell_quote(&arg))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // Ask the plugin what it provides. Plugins that don't answer within a few
+    // seconds are killed.
+    pub fn handshake(&self) -> Result<Handshake, PluginError> {
+        let io_error = |source| PluginError::IoError {
+            name: self.name.clone(),
+            source,
+        };
+        let failed = |message: String| PluginError::HandshakeFailed {
+            name: self.name.clone(),
+            message,
+        };
+
+        let mut child = Command::new(&self.path)
+            .arg("handshake")
+            .env("UPDOG_PLUGIN_PROTOCOL", PROTOCOL_VERSION.to_string())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(io_error)?;
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait().map_err(io_error)? {
+                break status;
+            }
+            if started.elapsed() > HANDSHAKE_TIMEOUT {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(failed("timed out".to_string()));
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        let mut stdout = String::new();
+        if let Some(mut pipe) = child.stdout.take() {
+            pipe.read_to_string(&mut stdout).map_err(io_error)?;
+        }
+        if !status.success() {
+            return Err(failed(format!("exited with {}", status)));
+        }
+
+        let handshake: Handshake =
+            serde_json::from_str(stdout.trim()).map_err(|e| failed(e.to_string()))?;
+        if handshake.protocol != PROTOCOL_VERSION {
+            return Err(PluginError::UnsupportedProtocol {
+                name: self.name.clone(),
+                protocol: handshake.protocol,
+            });
+        }
+        Ok(handshake)
+    }
+}
+
+// Plugins in the directories of `path_var` (a PATH value), by name. The first
+// of several executables with the same name wins, like the shell's lookup.
+pub fn discover(path_var: &OsStr) -> Vec<Plugin> {
+    let mut plugins: Vec<Plugin> = Vec::new();
+    for dir in std::env::split_paths(path_var) {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut found: Vec<Plugin> = entries
+            .flatten()
+            .filter(|entry| is_executable(&entry.path()))
+            .filter_map(|entry| {
+                let file_name = entry.file_name().to_string_lossy().into_owned();
+                let name = file_name.strip_prefix(PREFIX)?;
+                let name = name.strip_suffix(".exe").unwrap_or(name);
+                (!name.is_empty()).then(|| Plugin {
+                    name: name.to_string(),
+                    path: entry.path(),
+                })
+            })
+            .collect();
+        found.sort_by(|a, b| a.name.cmp(&b.name));
+        for plugin in found {
+            if !plugins.iter().any(|p| p.name == plugin.name) {
+                plugins.push(plugin);
+            }
+        }
+    }
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+    plugins
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|ext| ext == "exe")
+}
+
+// Handshake with the plugins enabled in `config.plugins` and register their
+// managers, unless the config already has an entry with the same id.
+// Returns one error per plugin that couldn't be loaded.
+pub fn load(config: &mut Config, path_var: &OsStr) -> Vec<PluginError> {
+    let available = discover(path_var);
+    let mut errors = Vec::new();
+    for name in config.plugins.clone() {
+        let Some(plugin) = available.iter().find(|p| p.name == name) else {
+            errors.push(PluginError::NotFound(name));
+            continue;
+        };
+        let handshake = match plugin.handshake() {
+            Ok(handshake) => handshake,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+        for id in &handshake.managers {
+            if config.find_package_manager(id).is_some() {
+                continue;
+            }
+            let command =
+                |operation: &str| Some(CommandSequence::Single(plugin.command(&[operation, id])));
+            config.commands.push(PackageManagerConfig {
+                id: id.clone(),
+                check: command("check"),
+                update: command("update"),
+                ..Default::default()
+            });
+        }
+        config.loaded_plugins.push(LoadedPlugin {
+            plugin: plugin.clone(),
+            handshake,
+        });
+    }
+    errors
+}
+
+// One "name current -> latest" line per update in a parser's JSON output,
+// so that each counts as a pending update
+pub fn parse_updates(name: &str, output: &str) -> Result<String, String> {
+    let result: ParseResult = serde_json::from_str(output.trim())
+        .map_err(|e| format!("invalid output from parser {}: {}", name, e))?;
+    Ok(result
+        .updates
+        .iter()
+        .map(|update| match (&update.current, &update.latest) {
+            (Some(current), Some(latest)) => {
+                format!("{} {} -> {}\n", update.name, current, latest)
+            }
+            (None, Some(latest)) => format!("{} -> {}\n", update.name, latest),
+            _ => format!("{}\n", update.name),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[cfg(unix)]
+    fn write_script(dir: &Path, name: &str, body: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_discover_and_load() {
+        let first = tempdir().unwrap();
+        let second = tempdir().unwrap();
+        write_script(
+            first.path(),
+            "updog-flatpak",
+            r#"echo '{"protocol": 1, "description": "Flatpak apps", "managers": ["flatpak", "homebrew"], "parser": true}'"#,
+        );
+        write_script(second.path(), "updog-flatpak", "exit 1");
+        write_script(second.path(), "updog-broken", "echo not json");
+        fs::write(second.path().join("updog-notes.txt"), "").unwrap();
+        let path_var = std::env::join_paths([first.path(), second.path()]).unwrap();
+
+        let plugins = discover(&path_var);
+        let names: Vec<&str> = plugins.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["broken", "flatpak"]);
+        assert!(plugins[1].path.starts_with(first.path()));
+        assert!(matches!(
+            plugins[0].handshake(),
+            Err(PluginError::HandshakeFailed { .. })
+        ));
+
+        let mut config = Config {
+            plugins: vec!["flatpak".to_string(), "missing".to_string()],
+            ..Default::default()
+        };
+        let errors = load(&mut config, &path_var);
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], PluginError::NotFound(name) if name == "missing"));
+
+        // The config's own homebrew entry wins over the plugin's
+        let ids: Vec<&str> = config.commands.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["homebrew", "flatpak"]);
+        let flatpak = config.find_subcommand("flatpak", None).unwrap().command;
+        assert_eq!(
+            flatpak.check.unwrap().as_single_str().unwrap(),
+            format!(
+                "'{}' 'check' 'flatpak'",
+                first.path().join("updog-flatpak").display()
+            )
+        );
+        assert!(config.loaded_plugins[0].handshake.parser);
+    }
+
+    #[test]
+    fn test_parse_updates() {
+        let output = r#"{"updates": [
+            {"name": "org.gimp.GIMP", "current": "2.10.36", "latest": "2.10.38"},
+            {"name": "org.videolan.VLC", "latest": "3.0.21"},
+            {"name": "runtime"}
+        ]}"#;
+        assert_eq!(
+            parse_updates("flatpak", output).unwrap(),
+            "org.gimp.GIMP 2.10.36 -> 2.10.38\norg.videolan.VLC -> 3.0.21\nruntime\n"
+        );
+        assert_eq!(parse_updates("flatpak", r#"{"updates": []}"#).unwrap(), "");
+        assert!(parse_updates("flatpak", "oops").is_err());
+    }
+}
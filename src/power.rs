@@ -0,0 +1,155 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Whether the machine runs on its battery, and how charged the battery is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerStatus {
+    pub on_battery: bool,
+    // Charge in percent
+    pub charge: u8,
+}
+
+// The power status of this machine, or None if it has no battery (or the
+// platform isn't supported)
+pub fn status() -> io::Result<Option<PowerStatus>> {
+    if cfg!(target_os = "linux") {
+        from_sysfs(Path::new("/sys/class/power_supply"))
+    } else if cfg!(target_os = "macos") {
+        let output = std::process::Command::new("pmset")
+            .args(["-g", "batt"])
+            .output()?;
+        Ok(parse_pmset(&String::from_utf8_lossy(&output.stdout)))
+    } else {
+        Ok(None)
+    }
+}
+
+// Linux: every supply under /sys/class/power_supply has a `type`. The machine
+// is on battery when no mains supply is online and a battery is discharging;
+// the charge is the batteries' average `capacity`.
+fn from_sysfs(root: &Path) -> io::Result<Option<PowerStatus>> {
+    let read = |supply: &Path, file: &str| {
+        fs::read_to_string(supply.join(file))
+            .map(|value| value.trim().to_string())
+            .unwrap_or_default()
+    };
+    let mut mains_online = false;
+    let mut discharging = false;
+    let mut charges = Vec::new();
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let supply = entry?.path();
+        match read(&supply, "type").as_str() {
+            "Mains" => mains_online |= read(&supply, "online") == "1",
+            "Battery" => {
+                discharging |= read(&supply, "status") == "Discharging";
+                if let Ok(charge) = read(&supply, "capacity").parse::<u32>() {
+                    charges.push(charge);
+                }
+            }
+            _ => {}
+        }
+    }
+    if charges.is_empty() {
+        return Ok(None);
+    }
+    let charge = charges.iter().sum::<u32>() / charges.len() as u32;
+    Ok(Some(PowerStatus {
+        on_battery: discharging && !mains_online,
+        charge: charge.min(100) as u8,
+    }))
+}
+
+// macOS: `pmset -g batt` prints the power source, then a line per battery:
+//
+//   Now drawing from 'Battery Power'
+//    -InternalBattery-0 (id=4653155)	85%; discharging; 4:12 remaining present: true
+fn parse_pmset(output: &str) -> Option<PowerStatus> {
+    let charge = output
+        .split(|c: char| c.is_whitespace() || c == ';')
+        .find_map(|word| word.strip_suffix('%')?.parse::<u8>().ok())?;
+    Some(PowerStatus {
+        on_battery: output.contains("'Battery Power'"),
+        charge,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supply(root: &Path, name: &str, files: &[(&str, &str)]) {
+        let dir = root.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        for (file, value) in files {
+            fs::write(dir.join(file), format!("{}\n", value)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_from_sysfs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        assert_eq!(from_sysfs(&root.join("missing")).unwrap(), None);
+
+        supply(root, "AC", &[("type", "Mains"), ("online", "0")]);
+        assert_eq!(from_sysfs(root).unwrap(), None);
+
+        supply(
+            root,
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Discharging"),
+                ("capacity", "40"),
+            ],
+        );
+        supply(
+            root,
+            "BAT1",
+            &[
+                ("type", "Battery"),
+                ("status", "Unknown"),
+                ("capacity", "21"),
+            ],
+        );
+        assert_eq!(
+            from_sysfs(root).unwrap(),
+            Some(PowerStatus {
+                on_battery: true,
+                charge: 30
+            })
+        );
+
+        supply(root, "AC", &[("online", "1")]);
+        assert!(!from_sysfs(root).unwrap().unwrap().on_battery);
+    }
+
+    #[test]
+    fn test_parse_pmset() {
+        let output = "Now drawing from 'Battery Power'\n \
+                      -InternalBattery-0 (id=4653155)\t85%; discharging; 4:12 remaining present: true\n";
+        assert_eq!(
+            parse_pmset(output),
+            Some(PowerStatus {
+                on_battery: true,
+                charge: 85
+            })
+        );
+        let output = "Now drawing from 'AC Power'\n \
+                      -InternalBattery-0 (id=4653155)\t100%; charged; 0:00 remaining present: true\n";
+        assert_eq!(
+            parse_pmset(output),
+            Some(PowerStatus {
+                on_battery: false,
+                charge: 100
+            })
+        );
+        assert_eq!(parse_pmset("Now drawing from 'AC Power'\n"), None);
+    }
+}
@@ -0,0 +1,1139 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::executor::{CommandOutput, CommandRequest, Executor};
+use crate::helm;
+use crate::package_manager::{UpdateError, UpdateErrorKind};
+use crate::step::shell_quote;
+
+// Built-in entries for tools that report "nothing to do", "reboot needed"
+// or runtime updates in their own ways. Opt in per entry:
+//
+//   - id: firmware
+//     preset: fwupd
+//
+// fwupd, flatpak and snap (Linux), softwareupdate and
+// softwareupdate-recommended (macOS), windows-update
+// (the PSWindowsUpdate PowerShell module) and winget (Windows). On other
+// platforms the entry is skipped. helm runs anywhere, but only checks;
+// homebrew reads `brew outdated`'s JSON; lazy-nvim, packer and vscode
+// update editor plugins anywhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Preset {
+    Fwupd,
+    Softwareupdate,
+    // Only the updates Apple recommends, leaving out OS upgrades
+    SoftwareupdateRecommended,
+    WindowsUpdate,
+    Winget,
+    Flatpak,
+    Snap,
+    Helm,
+    Homebrew,
+    LazyNvim,
+    Packer,
+    Vscode,
+}
+
+// Result of a preset's update
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresetUpdate {
+    pub output: String,
+    pub reboot_required: bool,
+}
+
+// winget's HRESULT exit codes
+const WINGET_NO_APPLICABLE_UPDATE: i32 = 0x8A15002B_u32 as i32;
+const WINGET_NO_PACKAGES_FOUND: i32 = 0x8A150014_u32 as i32;
+const WINGET_REBOOT_REQUIRED: &[i32] = &[
+    0x8A150109_u32 as i32, // reboot required to finish
+    0x8A15010A_u32 as i32, // reboot required for install
+    0x8A15010B_u32 as i32, // reboot initiated
+];
+
+// fwupdmgr exits with 2 when there's nothing to do
+const FWUPD_NOTHING_TO_DO: i32 = 2;
+
+// Printed by the windows-update update command when Windows wants a reboot
+const WINDOWS_REBOOT_MARKER: &str = "updog: reboot required";
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Preset::Fwupd => "fwupd",
+            Preset::Softwareupdate => "softwareupdate",
+            Preset::SoftwareupdateRecommended => "softwareupdate-recommended",
+            Preset::WindowsUpdate => "windows-update",
+            Preset::Winget => "winget",
+            Preset::Flatpak => "flatpak",
+            Preset::Snap => "snap",
+            Preset::Helm => "helm",
+            Preset::Homebrew => "homebrew",
+            Preset::LazyNvim => "lazy-nvim",
+            Preset::Packer => "packer",
+            Preset::Vscode => "vscode",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+fn run(
+    executor: &dyn Executor,
+    env: &[(String, String)],
+    command: &str,
+) -> Result<CommandOutput, UpdateError> {
+    let mut request = CommandRequest::captured(command);
+    request.env = env.to_vec();
+    executor.run(&request)
+}
+
+fn failed(command: &str, output: &CommandOutput) -> UpdateError {
+    let reason = match output.stderr.trim() {
+        "" => output.status_description(),
+        stderr => stderr.lines().last().unwrap_or(stderr).to_string(),
+    };
+    UpdateError::new(format!("{} failed: {}", command, reason))
+}
+
+// Output of a command that has to succeed
+fn run_ok(
+    executor: &dyn Executor,
+    env: &[(String, String)],
+    command: &str,
+) -> Result<String, UpdateError> {
+    let output = run(executor, env, command)?;
+    if !output.success() {
+        return Err(failed(command, &output));
+    }
+    Ok(output.stdout)
+}
+
+// Marks runtime lines in flatpak and snap check output
+const RUNTIME_SUFFIX: &str = " (runtime)";
+
+// lazy.nvim records what a check found in each plugin's `_.updates`
+const LAZY_CHECK: &str = "nvim --headless \
+    -c 'lua require(\"lazy\").check({ wait = true, show = false })' \
+    -c 'lua for name, p in pairs(require(\"lazy.core.config\").plugins) do \
+    if p._.updates then io.stdout:write(name .. \" \" .. p._.updates.from.commit:sub(1, 7) \
+    .. \" -> \" .. p._.updates.to.commit:sub(1, 7) .. \"\\n\") end end' \
+    -c 'qa'";
+const LAZY_SYNC: &str = "nvim --headless '+Lazy! sync' +qa";
+// PackerSync returns at once and works in the background; quit when it's
+// done, or with exit status 3 right away when packer isn't loaded (nvim
+// would otherwise wait forever)
+const PACKER_SYNC: &str = "nvim --headless \
+    -c 'if exists(\":PackerSync\") != 2 | cquit 3 | endif' \
+    -c 'autocmd User PackerComplete quitall' \
+    -c 'PackerSync'";
+const VSCODE_UPDATE: &str = "code --update-extensions";
+
+// nvim exits with 0 after errors in headless commands; they show on stderr
+fn nvim_error(command: &str, stderr: &str) -> Option<UpdateError> {
+    let missing = stderr
+        .lines()
+        .find(|line| line.contains("E492") || line.contains("module 'lazy"));
+    if let Some(line) = missing {
+        return Some(UpdateError::new(format!(
+            "The plugin manager isn't loaded by nvim: {}",
+            line.trim()
+        )));
+    }
+    let error = stderr
+        .lines()
+        .find(|line| line.contains("Error") || line.starts_with("E5108"))?;
+    Some(UpdateError::new(format!(
+        "{} failed: {}",
+        command,
+        error.trim()
+    )))
+}
+
+impl Preset {
+    fn platform(&self) -> Option<&'static str> {
+        match self {
+            Preset::Fwupd | Preset::Flatpak | Preset::Snap => Some("linux"),
+            Preset::Softwareupdate | Preset::SoftwareupdateRecommended => Some("macos"),
+            Preset::WindowsUpdate | Preset::Winget => Some("windows"),
+            Preset::Helm
+            | Preset::Homebrew
+            | Preset::LazyNvim
+            | Preset::Packer
+            | Preset::Vscode => None,
+        }
+    }
+
+    // Skip the entry on platforms the tool doesn't exist on
+    pub fn ensure_supported(&self) -> Result<(), UpdateError> {
+        match self.platform() {
+            Some(platform) if platform != std::env::consts::OS => Err(UpdateError::with_kind(
+                UpdateErrorKind::Excluded,
+                format!("{} only runs on {}", self, platform),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // helm upgrades depend on each release's values, so helm only checks
+    pub fn has_update(&self) -> bool {
+        *self != Preset::Helm
+    }
+
+    // packer and VS Code can't list pending updates without applying them
+    pub fn has_check(&self) -> bool {
+        !matches!(self, Preset::Packer | Preset::Vscode)
+    }
+
+    // Whether `cleanup` can remove what updates leave behind
+    pub fn has_cleanup(&self) -> bool {
+        matches!(self, Preset::Flatpak | Preset::Snap)
+    }
+
+    // "3 apps, 2 runtimes" for checks that tell runtimes apart
+    pub fn breakdown(&self, output: &str) -> Option<String> {
+        if !self.has_cleanup() {
+            return None;
+        }
+        let lines: Vec<&str> = output.lines().filter(|l| !l.trim().is_empty()).collect();
+        let runtimes = lines.iter().filter(|l| l.ends_with(RUNTIME_SUFFIX)).count();
+        let plural = |count: usize, noun: &str| match count {
+            1 => format!("1 {}", noun),
+            count => format!("{} {}s", count, noun),
+        };
+        Some(format!(
+            "{}, {}",
+            plural(lines.len() - runtimes, "app"),
+            plural(runtimes, "runtime")
+        ))
+    }
+
+    // One line per pending update
+    pub fn check(
+        &self,
+        executor: &dyn Executor,
+        env: &[(String, String)],
+    ) -> Result<String, UpdateError> {
+        match self {
+            Preset::Fwupd => {
+                // Refreshing metadata that is already current "has nothing to do"
+                let refresh = "fwupdmgr refresh";
+                let output = run(executor, env, refresh)?;
+                if !matches!(output.exit_code, Some(0 | FWUPD_NOTHING_TO_DO)) {
+                    return Err(failed(refresh, &output));
+                }
+                let command = "fwupdmgr get-updates --json";
+                let output = run(executor, env, command)?;
+                match output.exit_code {
+                    Some(0) => parse_fwupd(&output.stdout).ok_or_else(|| {
+                        UpdateError::new(format!("Unexpected output of {}", command))
+                    }),
+                    Some(FWUPD_NOTHING_TO_DO) => Ok(String::new()),
+                    _ => Err(failed(command, &output)),
+                }
+            }
+            Preset::Softwareupdate | Preset::SoftwareupdateRecommended => {
+                // softwareupdate exits with 0 even when it can't reach the server
+                let command = "softwareupdate --list";
+                let output = run(executor, env, command)?;
+                if !output.success()
+                    || output.stderr.contains("Error")
+                    || output.stderr.contains("connect to")
+                {
+                    return Err(failed(command, &output));
+                }
+                let recommended_only = *self == Preset::SoftwareupdateRecommended;
+                Ok(parse_softwareupdate(&output.stdout, recommended_only))
+            }
+            Preset::WindowsUpdate => {
+                let command = "powershell -NoProfile -NonInteractive -Command \
+                               \"Get-WindowsUpdate | ForEach-Object { $_.KB + ' ' + $_.Title }\"";
+                let output = run(executor, env, command)?;
+                if output.stderr.contains("is not recognized") {
+                    return Err(UpdateError::new(
+                        "PSWindowsUpdate isn't installed (Install-Module PSWindowsUpdate)",
+                    ));
+                }
+                if !output.success() {
+                    return Err(failed(command, &output));
+                }
+                Ok(output.stdout)
+            }
+            Preset::Winget => {
+                let command = "winget upgrade --include-unknown --disable-interactivity";
+                let output = run(executor, env, command)?;
+                match output.exit_code {
+                    Some(0) => Ok(parse_winget(&output.stdout)),
+                    Some(WINGET_NO_APPLICABLE_UPDATE | WINGET_NO_PACKAGES_FOUND) => {
+                        Ok(String::new())
+                    }
+                    _ => Err(failed(command, &output)),
+                }
+            }
+            Preset::Flatpak => {
+                let apps = run_ok(
+                    executor,
+                    env,
+                    "flatpak remote-ls --updates --app --columns=application,version",
+                )?;
+                let runtimes = run_ok(
+                    executor,
+                    env,
+                    "flatpak remote-ls --updates --runtime --columns=application,branch",
+                )?;
+                Ok(format!(
+                    "{}{}",
+                    parse_flatpak(&apps, false),
+                    parse_flatpak(&runtimes, true)
+                ))
+            }
+            Preset::Snap => {
+                // "All snaps up to date." goes to stderr
+                let output = run_ok(executor, env, "snap refresh --list")?;
+                Ok(parse_snap_refresh(&output))
+            }
+            Preset::Helm => {
+                run_ok(executor, env, "helm repo update")?;
+                let releases = run_ok(executor, env, "helm list --all-namespaces -o json")?;
+                // Without a term, the newest version of every chart in every repo
+                let charts = run_ok(executor, env, "helm search repo -o json")?;
+                helm::outdated(&releases, &charts)
+                    .map_err(|e| UpdateError::new(format!("Unexpected helm output: {}", e)))
+            }
+            Preset::Homebrew => {
+                let command = "brew outdated --json=v2";
+                let output = run_ok(executor, env, command)?;
+                parse_brew_outdated(&output).map_err(|e| {
+                    UpdateError::new(format!("Unexpected output of {}: {}", command, e))
+                })
+            }
+            Preset::LazyNvim => {
+                let output = run(executor, env, LAZY_CHECK)?;
+                if let Some(e) = nvim_error("lazy.nvim check", &output.stderr) {
+                    return Err(e);
+                }
+                if !output.success() {
+                    return Err(failed("lazy.nvim check", &output));
+                }
+                Ok(output.stdout)
+            }
+            Preset::Packer | Preset::Vscode => Err(UpdateError::with_kind(
+                UpdateErrorKind::MissingCommand,
+                format!("{} can't check without updating", self),
+            )),
+        }
+    }
+
+    // Remove unused flatpak runtimes or disabled snap revisions, returning
+    // what was removed
+    pub fn cleanup(
+        &self,
+        executor: &dyn Executor,
+        env: &[(String, String)],
+    ) -> Result<Vec<String>, UpdateError> {
+        match self {
+            Preset::Flatpak => {
+                // The uninstall output differs between versions; compare the lists
+                let list = "flatpak list --runtime --columns=application,branch";
+                let before = parse_flatpak_list(&run_ok(executor, env, list)?);
+                run_ok(
+                    executor,
+                    env,
+                    "flatpak uninstall --unused --assumeyes --noninteractive",
+                )?;
+                let after = parse_flatpak_list(&run_ok(executor, env, list)?);
+                Ok(before
+                    .into_iter()
+                    .filter(|runtime| !after.contains(runtime))
+                    .collect())
+            }
+            Preset::Snap => {
+                let list = run_ok(executor, env, "snap list --all")?;
+                let mut removed = Vec::new();
+                for (name, revision) in parse_disabled_snaps(&list) {
+                    run_ok(
+                        executor,
+                        env,
+                        &format!(
+                            "sudo snap remove {} --revision={}",
+                            shell_quote(&name),
+                            shell_quote(&revision)
+                        ),
+                    )?;
+                    removed.push(format!("{} (revision {})", name, revision));
+                }
+                Ok(removed)
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    pub fn update(
+        &self,
+        executor: &dyn Executor,
+        env: &[(String, String)],
+    ) -> Result<PresetUpdate, UpdateError> {
+        let done = |output: String, reboot_required: bool| {
+            Ok(PresetUpdate {
+                output,
+                reboot_required,
+            })
+        };
+        match self {
+            Preset::Fwupd => {
+                // No reboot prompt; staged capsule updates are reported instead
+                let command = "fwupdmgr update --assume-yes --no-reboot-check";
+                let output = run(executor, env, command)?;
+                match output.exit_code {
+                    Some(0) => {
+                        let text = format!("{}\n{}", output.stdout, output.stderr).to_lowercase();
+                        done(
+                            output.stdout,
+                            text.contains("reboot") || text.contains("restart"),
+                        )
+                    }
+                    Some(FWUPD_NOTHING_TO_DO) => done(output.stdout, false),
+                    _ => Err(failed(command, &output)),
+                }
+            }
+            Preset::Softwareupdate | Preset::SoftwareupdateRecommended => {
+                let command = match self {
+                    Preset::Softwareupdate => {
+                        "sudo softwareupdate --install --all --agree-to-license"
+                    }
+                    _ => "sudo softwareupdate --install --recommended --agree-to-license",
+                };
+                let output = run(executor, env, command)?;
+                let text = format!("{}\n{}", output.stdout, output.stderr);
+                if !output.success() || text.contains("Failed to") || text.contains("Error") {
+                    return Err(failed(command, &output));
+                }
+                done(output.stdout, softwareupdate_restart_required(&text))
+            }
+            Preset::WindowsUpdate => {
+                let command = format!(
+                    "powershell -NoProfile -NonInteractive -Command \
+                     \"Install-WindowsUpdate -AcceptAll -IgnoreReboot | Out-String; \
+                     if (Get-WURebootStatus -Silent) {{ '{}' }}\"",
+                    WINDOWS_REBOOT_MARKER
+                );
+                let output = run(executor, env, &command)?;
+                if !output.success() {
+                    return Err(failed(&command, &output));
+                }
+                let reboot_required = output
+                    .stdout
+                    .lines()
+                    .any(|line| line.trim() == WINDOWS_REBOOT_MARKER);
+                let text: String = output
+                    .stdout
+                    .lines()
+                    .filter(|line| line.trim() != WINDOWS_REBOOT_MARKER)
+                    .map(|line| format!("{}\n", line))
+                    .collect();
+                done(text, reboot_required)
+            }
+            Preset::Winget => {
+                let command = "winget upgrade --all --include-unknown --silent \
+                               --accept-package-agreements --accept-source-agreements \
+                               --disable-interactivity";
+                let output = run(executor, env, command)?;
+                match output.exit_code {
+                    Some(0) => done(output.stdout, false),
+                    Some(WINGET_NO_APPLICABLE_UPDATE | WINGET_NO_PACKAGES_FOUND) => {
+                        done(output.stdout, false)
+                    }
+                    Some(code) if WINGET_REBOOT_REQUIRED.contains(&code) => {
+                        done(output.stdout, true)
+                    }
+                    _ => Err(failed(command, &output)),
+                }
+            }
+            Preset::Flatpak => {
+                let command = "flatpak update --assumeyes --noninteractive";
+                done(run_ok(executor, env, command)?, false)
+            }
+            Preset::Snap => done(run_ok(executor, env, "sudo snap refresh")?, false),
+            Preset::Helm => Err(UpdateError::with_kind(
+                UpdateErrorKind::MissingCommand,
+                "helm releases are upgraded with their own values",
+            )),
+            Preset::Homebrew => done(run_ok(executor, env, "brew upgrade")?, false),
+            Preset::LazyNvim => {
+                let output = run(executor, env, LAZY_SYNC)?;
+                if let Some(e) = nvim_error(LAZY_SYNC, &output.stderr) {
+                    return Err(e);
+                }
+                if !output.success() {
+                    return Err(failed(LAZY_SYNC, &output));
+                }
+                done(output.stdout, false)
+            }
+            Preset::Packer => {
+                let output = run(executor, env, PACKER_SYNC)?;
+                match output.exit_code {
+                    Some(3) => Err(UpdateError::new("packer.nvim isn't loaded by nvim")),
+                    _ => match nvim_error("PackerSync", &output.stderr) {
+                        Some(e) => Err(e),
+                        None if output.success() => done(output.stdout, false),
+                        None => Err(failed("PackerSync", &output)),
+                    },
+                }
+            }
+            Preset::Vscode => {
+                // VS Code refuses to run as root without a separate profile
+                let output = run(executor, env, VSCODE_UPDATE)?;
+                if output.stderr.contains("as a super user") {
+                    return Err(UpdateError::new(
+                        "VS Code won't update extensions as root; run updog as your user",
+                    ));
+                }
+                if !output.success() {
+                    return Err(failed(VSCODE_UPDATE, &output));
+                }
+                done(output.stdout, false)
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct FwupdUpdates {
+    #[serde(default)]
+    devices: Vec<FwupdDevice>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct FwupdDevice {
+    name: String,
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    releases: Vec<FwupdRelease>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct FwupdRelease {
+    version: String,
+}
+
+#[derive(Deserialize)]
+struct BrewOutdated {
+    #[serde(default)]
+    formulae: Vec<BrewPackage>,
+    #[serde(default)]
+    casks: Vec<BrewPackage>,
+}
+
+#[derive(Deserialize)]
+struct BrewPackage {
+    name: String,
+    installed_versions: BrewVersions,
+    current_version: String,
+    #[serde(default)]
+    pinned: bool,
+}
+
+// Older brew versions give casks a single installed version
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BrewVersions {
+    Many(Vec<String>),
+    One(String),
+}
+
+// `<name> <installed> -> <current>` per outdated formula and cask. Pinned
+// formulae are left out, as `brew upgrade` leaves them alone.
+fn parse_brew_outdated(json: &str) -> Result<String, serde_json::Error> {
+    let outdated: BrewOutdated = serde_json::from_str(json)?;
+    Ok(outdated
+        .formulae
+        .iter()
+        .chain(&outdated.casks)
+        .filter(|package| !package.pinned)
+        .map(|package| {
+            let installed = match &package.installed_versions {
+                BrewVersions::Many(versions) => versions.last().map_or("?", String::as_str),
+                BrewVersions::One(version) => version,
+            };
+            format!(
+                "{} {} -> {}\n",
+                package.name, installed, package.current_version
+            )
+        })
+        .collect())
+}
+
+// `<device> <version> -> <newest release>` per device with a release
+fn parse_fwupd(json: &str) -> Option<String> {
+    let updates: FwupdUpdates = serde_json::from_str(json).ok()?;
+    Some(
+        updates
+            .devices
+            .iter()
+            .filter_map(|device| {
+                let newest = device.releases.first()?;
+                Some(format!(
+                    "{} {} -> {}\n",
+                    device.name,
+                    device.version.as_deref().unwrap_or("?"),
+                    newest.version
+                ))
+            })
+            .collect(),
+    )
+}
+
+// Whether an update's details line has `field` (`Recommended: YES` since
+// macOS 10.15) or `tag` (`[recommended]` before)
+fn details_say(details: &str, field: &str, tag: &str) -> bool {
+    details.contains(field) || details.contains(tag)
+}
+
+// `* Label: <name>` per update, each followed by a details line that says
+// whether Apple recommends it and whether it needs a restart (or a shut
+// down, for some firmware)
+fn parse_softwareupdate(output: &str, recommended_only: bool) -> String {
+    let mut updates = String::new();
+    let mut lines = output.lines().peekable();
+    while let Some(line) = lines.next() {
+        let Some(label) = line.trim().strip_prefix("* Label: ") else {
+            continue;
+        };
+        let details = lines.peek().copied().unwrap_or_default();
+        if recommended_only && !details_say(details, "Recommended: YES", "[recommended]") {
+            continue;
+        }
+        updates.push_str(label.trim());
+        if details_say(details, "Action: restart", "[restart]")
+            || details_say(details, "Action: shut down", "[shut down]")
+        {
+            updates.push_str(" (restart)");
+        }
+        updates.push('\n');
+    }
+    updates
+}
+
+// What `softwareupdate --install` says when installed updates wait for a
+// restart (it never restarts without --restart)
+const SOFTWAREUPDATE_RESTART_NOTES: &[&str] = &[
+    "restart immediately",
+    "requires that you restart",
+    "restart required",
+    "restart is required",
+    "shut down immediately",
+    "requires that you shut down",
+];
+
+fn softwareupdate_restart_required(output: &str) -> bool {
+    let output = output.to_lowercase();
+    SOFTWAREUPDATE_RESTART_NOTES
+        .iter()
+        .any(|note| output.contains(note))
+}
+
+// The rows of winget's table, between the dashed line under the header and
+// the "N upgrades available." footer (rows end with the source name, footer
+// sentences with a period or colon). Progress spinners end with '\r'.
+fn parse_winget(output: &str) -> String {
+    output
+        .lines()
+        .map(|line| line.rsplit('\r').next().unwrap_or(line))
+        .skip_while(|line| !line.starts_with("---"))
+        .skip(1)
+        .take_while(|line| {
+            let line = line.trim();
+            !line.is_empty() && !line.ends_with('.') && !line.ends_with(':')
+        })
+        .map(|line| format!("{}\n", line.trim_end()))
+        .collect()
+}
+
+// Tab-separated `application<TAB>version` (or branch) rows
+fn parse_flatpak(output: &str, runtimes: bool) -> String {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split('\t').map(str::trim);
+            let application = columns.next().filter(|a| !a.is_empty())?;
+            let mut entry = application.to_string();
+            if let Some(version) = columns.next().filter(|v| !v.is_empty()) {
+                entry = format!("{} {}", entry, version);
+            }
+            Some(format!(
+                "{}{}\n",
+                entry,
+                if runtimes { RUNTIME_SUFFIX } else { "" }
+            ))
+        })
+        .collect()
+}
+
+// Installed runtimes as `application//branch`
+fn parse_flatpak_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (application, branch) = line.split_once('\t')?;
+            Some(format!("{}//{}", application.trim(), branch.trim()))
+        })
+        .collect()
+}
+
+// Snaps other apps build on: bases, snapd itself and the shared content
+// snaps (desktop platforms, themes, graphics drivers)
+fn is_snap_runtime(name: &str) -> bool {
+    name == "snapd"
+        || name == "bare"
+        || name.starts_with("core")
+        || name.ends_with("-themes")
+        || ["gnome-", "kde-", "kf5-", "kf6-", "mesa-", "gpu-"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+}
+
+// `Name Version Rev Size Publisher Notes` table of `snap refresh --list`
+fn parse_snap_refresh(output: &str) -> String {
+    output
+        .lines()
+        .skip_while(|line| !line.starts_with("Name "))
+        .skip(1)
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let name = columns.next()?;
+            let version = columns.next().unwrap_or("");
+            let suffix = if is_snap_runtime(name) {
+                RUNTIME_SUFFIX
+            } else {
+                ""
+            };
+            Some(format!("{} {}{}\n", name, version, suffix))
+        })
+        .collect()
+}
+
+// (name, revision) of the revisions `snap list --all` marks as disabled,
+// which snap keeps around for rollbacks
+fn parse_disabled_snaps(output: &str) -> Vec<(String, String)> {
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            let notes = columns.last()?;
+            if columns.len() < 4 || !notes.split(',').any(|note| note == "disabled") {
+                return None;
+            }
+            Some((columns[0].to_string(), columns[2].to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+    use std::sync::Arc;
+
+    fn executor() -> ScriptedExecutor {
+        ScriptedExecutor::new(Arc::new(FakeClock::new()))
+    }
+
+    #[test]
+    fn test_fwupd() {
+        let executor = executor();
+        executor
+            .script("fwupdmgr refresh", ScriptedCommand::exit(2))
+            .script(
+                "fwupdmgr get-updates --json",
+                ScriptedCommand::success().stdout(
+                    r#"{"Devices": [
+                        {"Name": "System Firmware", "Version": "1.2", "Releases": [{"Version": "1.4"}, {"Version": "1.3"}]},
+                        {"Name": "Webcam", "Version": "0.1", "Releases": []}
+                    ]}"#,
+                ),
+            )
+            .script("fwupdmgr get-updates --json", ScriptedCommand::exit(2))
+            .script(
+                "fwupdmgr update --assume-yes --no-reboot-check",
+                ScriptedCommand::success()
+                    .stdout("Successfully installed firmware\nAn update requires a reboot to complete.\n"),
+            );
+        assert_eq!(
+            Preset::Fwupd.check(&executor, &[]).unwrap(),
+            "System Firmware 1.2 -> 1.4\n"
+        );
+        // Nothing to do isn't a failure
+        assert_eq!(Preset::Fwupd.check(&executor, &[]).unwrap(), "");
+        assert!(
+            Preset::Fwupd
+                .update(&executor, &[])
+                .unwrap()
+                .reboot_required
+        );
+
+        let executor = self::executor();
+        executor.script(
+            "fwupdmgr refresh",
+            ScriptedCommand::exit(1).stderr("No network\n"),
+        );
+        let err = Preset::Fwupd.check(&executor, &[]).unwrap_err();
+        assert_eq!(err.message, "fwupdmgr refresh failed: No network");
+    }
+
+    #[test]
+    fn test_softwareupdate() {
+        let executor = executor();
+        executor
+            .script(
+                "softwareupdate --list",
+                ScriptedCommand::success().stdout(
+                    "Software Update Tool\n\nFinding available software\nSoftware Update found the following new or updated software:\n\
+                     * Label: macOS Sonoma 14.4.1-23E224\n\
+                     \tTitle: macOS Sonoma 14.4.1, Version: 14.4.1, Size: 1256383KiB, Recommended: YES, Action: restart,\n\
+                     * Label: Safari17.4.1SonomaAuto-17.4.1\n\
+                     \tTitle: Safari, Version: 17.4.1, Size: 153605KiB, Recommended: YES,\n",
+                ),
+            )
+            .script(
+                "softwareupdate --list",
+                ScriptedCommand::success().stderr("Can’t connect to the Apple Software Update server.\n"),
+            );
+        assert_eq!(
+            Preset::Softwareupdate.check(&executor, &[]).unwrap(),
+            "macOS Sonoma 14.4.1-23E224 (restart)\nSafari17.4.1SonomaAuto-17.4.1\n"
+        );
+        // Exit status 0, but the check didn't happen
+        assert!(Preset::Softwareupdate.check(&executor, &[]).is_err());
+    }
+
+    #[test]
+    fn test_softwareupdate_recommended() {
+        let list = "* Label: macOS Sequoia 15.1-24B83\n\
+                    \tTitle: macOS Sequoia 15.1, Version: 15.1, Size: 6123456KiB, Recommended: NO, Action: restart,\n\
+                    * Label: Command Line Tools for Xcode-16.1\n\
+                    \tTitle: Command Line Tools for Xcode, Version: 16.1, Size: 751234KiB, Recommended: YES,\n\
+                    * Label: BridgeOSUpdateCustomer\n\
+                    \tBridgeOS (1.0), 412345K [recommended] [shut down]\n";
+        assert_eq!(
+            parse_softwareupdate(list, false),
+            "macOS Sequoia 15.1-24B83 (restart)\n\
+             Command Line Tools for Xcode-16.1\n\
+             BridgeOSUpdateCustomer (restart)\n"
+        );
+        assert_eq!(
+            parse_softwareupdate(list, true),
+            "Command Line Tools for Xcode-16.1\nBridgeOSUpdateCustomer (restart)\n"
+        );
+
+        let executor = executor();
+        executor
+            .script(
+                "sudo softwareupdate --install --recommended --agree-to-license",
+                ScriptedCommand::success().stdout(
+                    "Software Update Tool\n\nFinding available software\n\
+                     Downloading Command Line Tools for Xcode\n\
+                     Done with Command Line Tools for Xcode\nDone.\n",
+                ),
+            )
+            .script(
+                "sudo softwareupdate --install --all --agree-to-license",
+                ScriptedCommand::success().stdout(
+                    "Downloading macOS Sequoia 15.1\nDone with macOS Sequoia 15.1\n\
+                     You have installed one or more updates that requires that you restart your\n\
+                     computer.  Please restart immediately.\n",
+                ),
+            )
+            .script(
+                "sudo softwareupdate --install --all --agree-to-license",
+                ScriptedCommand::success().stderr("Failed to download & prepare update\n"),
+            );
+        let update = Preset::SoftwareupdateRecommended
+            .update(&executor, &[])
+            .unwrap();
+        assert!(!update.reboot_required);
+        assert!(
+            Preset::Softwareupdate
+                .update(&executor, &[])
+                .unwrap()
+                .reboot_required
+        );
+        assert!(Preset::Softwareupdate.update(&executor, &[]).is_err());
+        assert!(!softwareupdate_restart_required(
+            "Restarting the download of Safari"
+        ));
+        assert!(softwareupdate_restart_required(
+            "Restart is required to finish"
+        ));
+    }
+
+    #[test]
+    fn test_winget() {
+        let executor = executor();
+        let list = "winget upgrade --include-unknown --disable-interactivity";
+        let upgrade = "winget upgrade --all --include-unknown --silent \
+                       --accept-package-agreements --accept-source-agreements \
+                       --disable-interactivity";
+        executor
+            .script(
+                list,
+                ScriptedCommand::success().stdout(
+                    "   - \r   \\ \rName      Id             Version Available Source\n\
+                     ------------------------------------------------------\n\
+                     Git       Git.Git        2.44.0  2.45.0    winget\n\
+                     PowerToys Microsoft.PowerToys 0.79 0.80   winget\n\
+                     2 upgrades available.\n",
+                ),
+            )
+            .script(list, ScriptedCommand::exit(WINGET_NO_APPLICABLE_UPDATE))
+            .script(upgrade, ScriptedCommand::exit(WINGET_REBOOT_REQUIRED[0]))
+            .script(upgrade, ScriptedCommand::exit(1));
+        assert_eq!(
+            Preset::Winget.check(&executor, &[]).unwrap(),
+            "Git       Git.Git        2.44.0  2.45.0    winget\n\
+             PowerToys Microsoft.PowerToys 0.79 0.80   winget\n"
+        );
+        assert_eq!(Preset::Winget.check(&executor, &[]).unwrap(), "");
+        assert!(
+            Preset::Winget
+                .update(&executor, &[])
+                .unwrap()
+                .reboot_required
+        );
+        assert!(Preset::Winget.update(&executor, &[]).is_err());
+    }
+
+    #[test]
+    fn test_flatpak() {
+        let executor = executor();
+        let list = "flatpak list --runtime --columns=application,branch";
+        executor
+            .script(
+                "flatpak remote-ls --updates --app --columns=application,version",
+                ScriptedCommand::success().stdout("org.gimp.GIMP\t2.10.38\n"),
+            )
+            .script(
+                "flatpak remote-ls --updates --runtime --columns=application,branch",
+                ScriptedCommand::success()
+                    .stdout("org.gnome.Platform\t46\norg.freedesktop.Platform.GL.default\t23.08\n"),
+            )
+            .script(
+                list,
+                ScriptedCommand::success()
+                    .stdout("org.gnome.Platform\t45\norg.gnome.Platform\t46\n"),
+            )
+            .script(
+                list,
+                ScriptedCommand::success().stdout("org.gnome.Platform\t46\n"),
+            )
+            .script(
+                "flatpak uninstall --unused --assumeyes --noninteractive",
+                ScriptedCommand::success(),
+            );
+        let output = Preset::Flatpak.check(&executor, &[]).unwrap();
+        assert_eq!(
+            output,
+            "org.gimp.GIMP 2.10.38\n\
+             org.gnome.Platform 46 (runtime)\n\
+             org.freedesktop.Platform.GL.default 23.08 (runtime)\n"
+        );
+        assert_eq!(
+            Preset::Flatpak.breakdown(&output).as_deref(),
+            Some("1 app, 2 runtimes")
+        );
+        assert_eq!(
+            Preset::Flatpak.cleanup(&executor, &[]).unwrap(),
+            ["org.gnome.Platform//45"]
+        );
+        assert_eq!(Preset::Fwupd.breakdown(&output), None);
+    }
+
+    #[test]
+    fn test_snap() {
+        let output = parse_snap_refresh(
+            "Name     Version   Rev   Size   Publisher   Notes\n\
+             core22   20240408  1380  77MB   canonical✓  base\n\
+             firefox  125.0.2-1 4173  275MB  mozilla✓    -\n",
+        );
+        assert_eq!(output, "core22 20240408 (runtime)\nfirefox 125.0.2-1\n");
+        assert_eq!(parse_snap_refresh(""), "");
+
+        let executor = executor();
+        executor
+            .script(
+                "snap list --all",
+                ScriptedCommand::success().stdout(
+                    "Name     Version   Rev   Tracking       Publisher   Notes\n\
+                     core22   20240111  1122  latest/stable  canonical✓  base,disabled\n\
+                     core22   20240408  1380  latest/stable  canonical✓  base\n\
+                     firefox  124.0.2-1 4090  latest/stable  mozilla✓    disabled\n",
+                ),
+            )
+            .script(
+                "sudo snap remove 'core22' --revision='1122'",
+                ScriptedCommand::success(),
+            )
+            .script(
+                "sudo snap remove 'firefox' --revision='4090'",
+                ScriptedCommand::success(),
+            );
+        assert_eq!(
+            Preset::Snap.cleanup(&executor, &[]).unwrap(),
+            ["core22 (revision 1122)", "firefox (revision 4090)"]
+        );
+    }
+
+    #[test]
+    fn test_windows_update_reboot_marker() {
+        let executor = executor();
+        let command = format!(
+            "powershell -NoProfile -NonInteractive -Command \
+             \"Install-WindowsUpdate -AcceptAll -IgnoreReboot | Out-String; \
+             if (Get-WURebootStatus -Silent) {{ '{}' }}\"",
+            WINDOWS_REBOOT_MARKER
+        );
+        executor.script(
+            &command,
+            ScriptedCommand::success().stdout("KB5036893 Installed\nupdog: reboot required\n"),
+        );
+        assert_eq!(
+            Preset::WindowsUpdate.update(&executor, &[]).unwrap(),
+            PresetUpdate {
+                output: "KB5036893 Installed\n".to_string(),
+                reboot_required: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_ensure_supported() {
+        let err = Preset::Softwareupdate.ensure_supported();
+        if cfg!(target_os = "macos") {
+            assert!(err.is_ok());
+        } else {
+            let err = err.unwrap_err();
+            assert_eq!(err.kind, UpdateErrorKind::Excluded);
+            assert_eq!(err.message, "softwareupdate only runs on macos");
+        }
+        let name: Preset = serde_yaml::from_str("windows-update").unwrap();
+        assert_eq!(name.to_string(), "windows-update");
+        assert!(Preset::Helm.ensure_supported().is_ok());
+    }
+
+    #[test]
+    fn test_homebrew() {
+        let executor = executor();
+        executor
+            .script(
+                "brew outdated --json=v2",
+                ScriptedCommand::success().stdout(
+                    r#"{"formulae": [
+                        {"name": "git", "installed_versions": ["2.40.0", "2.40.1"],
+                         "current_version": "2.41.0", "pinned": false, "pinned_version": null},
+                        {"name": "postgresql@16", "installed_versions": ["16.2"],
+                         "current_version": "16.3", "pinned": true, "pinned_version": "16.2"}
+                    ],
+                    "casks": [
+                        {"name": "firefox", "installed_versions": ["125.0"], "current_version": "126.0"},
+                        {"name": "iterm2", "installed_versions": "3.4.23", "current_version": "3.5.0"}
+                    ]}"#,
+                ),
+            )
+            .script("brew outdated --json=v2", ScriptedCommand::success().stdout("git (2.40.0) < 2.41.0\n"));
+        let output = Preset::Homebrew.check(&executor, &[]).unwrap();
+        assert_eq!(
+            output,
+            "git 2.40.1 -> 2.41.0\nfirefox 125.0 -> 126.0\niterm2 3.4.23 -> 3.5.0\n"
+        );
+        let err = Preset::Homebrew.check(&executor, &[]).unwrap_err();
+        assert!(err
+            .message
+            .starts_with("Unexpected output of brew outdated --json=v2"));
+        assert_eq!(
+            parse_brew_outdated(r#"{"formulae": [], "casks": []}"#).unwrap(),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_editor_plugins() {
+        let executor = executor();
+        executor
+            .script(
+                LAZY_CHECK,
+                ScriptedCommand::success().stdout("telescope.nvim 1a2b3c4 -> 5d6e7f8\n"),
+            )
+            .script(
+                LAZY_CHECK,
+                ScriptedCommand::success().stderr(
+                    "Error detected while processing command line:\n\
+                     E5108: Error executing lua [string \":lua\"]:1: module 'lazy' not found:\n",
+                ),
+            )
+            .script(PACKER_SYNC, ScriptedCommand::exit(3))
+            .script(
+                VSCODE_UPDATE,
+                ScriptedCommand::success().stderr(
+                    "You are trying to start Visual Studio Code as a super user which isn't recommended.\n",
+                ),
+            );
+        assert_eq!(
+            Preset::LazyNvim.check(&executor, &[]).unwrap(),
+            "telescope.nvim 1a2b3c4 -> 5d6e7f8\n"
+        );
+        // nvim exits with 0 here
+        let err = Preset::LazyNvim.check(&executor, &[]).unwrap_err();
+        assert_eq!(
+            err.message,
+            "The plugin manager isn't loaded by nvim: \
+             E5108: Error executing lua [string \":lua\"]:1: module 'lazy' not found:"
+        );
+        let err = Preset::Packer.update(&executor, &[]).unwrap_err();
+        assert_eq!(err.message, "packer.nvim isn't loaded by nvim");
+        assert!(Preset::Vscode.update(&executor, &[]).is_err());
+        assert!(!Preset::Vscode.has_check());
+        assert!(Preset::LazyNvim.has_check());
+    }
+
+    #[test]
+    fn test_helm() {
+        let executor = executor();
+        executor
+            .script(
+                "helm repo update",
+                ScriptedCommand::success().stdout("Update Complete. ⎈Happy Helming!⎈\n"),
+            )
+            .script(
+                "helm list --all-namespaces -o json",
+                ScriptedCommand::success().stdout(
+                    r#"[{"name":"loki","namespace":"monitoring","chart":"loki-5.41.0","app_version":"2.9.3"}]"#,
+                ),
+            )
+            .script(
+                "helm search repo -o json",
+                ScriptedCommand::success()
+                    .stdout(r#"[{"name":"grafana/loki","version":"5.43.0","app_version":"2.9.4"}]"#),
+            );
+        assert_eq!(
+            Preset::Helm.check(&executor, &[]).unwrap(),
+            "monitoring/loki loki 5.41.0 -> 5.43.0 (app 2.9.3 -> 2.9.4)\n"
+        );
+        assert!(!Preset::Helm.has_update());
+
+        let executor = self::executor();
+        executor.script(
+            "helm repo update",
+            ScriptedCommand::exit(1)
+                .stderr("Error: no repositories found. You must add one before updating\n"),
+        );
+        let err = Preset::Helm.check(&executor, &[]).unwrap_err();
+        assert_eq!(
+            err.message,
+            "helm repo update failed: Error: no repositories found. You must add one before updating"
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use serde_json::json;
+use std::fmt;
+
+use crate::eta::{self, Estimate};
+use crate::report::{ExecutionResult, Operation};
+
+// Progress lines written to stderr with `--progress`, for wrapper scripts and
+// GUIs that want a progress bar without parsing the human-readable output:
+//
+//   plain: "run 3f2a9c1e-...: checking 3 managers"
+//          "1/3 managers: checking apt (usually 40s; about 1m10s left)"
+//          "1/3 managers done (apt: success; about 30s left)"
+//   json:  {"event":"run",...}
+//          {"event":"start",...}
+//          {"event":"done",...}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressFormat {
+    Plain,
+    // One JSON object per line
+    Json,
+}
+
+impl std::str::FromStr for ProgressFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "plain" => Ok(ProgressFormat::Plain),
+            "json" => Ok(ProgressFormat::Json),
+            _ => Err(format!(
+                "unknown progress format '{}' (expected plain or json)",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ProgressFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ProgressFormat::Plain => "plain",
+            ProgressFormat::Json => "json",
+        })
+    }
+}
+
+impl ProgressFormat {
+    // First line of a run, with its id to find it again in history
+    pub fn begin(&self, operation: Operation, run_id: &str, total: usize) -> String {
+        match self {
+            ProgressFormat::Plain => {
+                format!("run {}: {} {} managers", run_id, verb(operation), total)
+            }
+            ProgressFormat::Json => json!({
+                "event": "run",
+                "run_id": run_id,
+                "operation": operation,
+                "total": total,
+            })
+            .to_string(),
+        }
+    }
+
+    // Line announcing the `index`th (1-based) of `total` managers, with the
+    // time it and the run are estimated to take
+    pub fn start(
+        &self,
+        operation: Operation,
+        index: usize,
+        total: usize,
+        name: &str,
+        estimate: &Estimate,
+    ) -> String {
+        match self {
+            ProgressFormat::Plain => {
+                let notes: Vec<String> = estimate
+                    .manager
+                    .map(|typical| format!("usually {}", eta::short(typical)))
+                    .into_iter()
+                    .chain(estimate.describe())
+                    .collect();
+                let mut line =
+                    format!("{}/{} managers: {} {}", index, total, verb(operation), name);
+                if !notes.is_empty() {
+                    line.push_str(&format!(" ({})", notes.join("; ")));
+                }
+                line
+            }
+            ProgressFormat::Json => json!({
+                "event": "start",
+                "operation": operation,
+                "manager": name,
+                "index": index,
+                "total": total,
+                "manager_eta_secs": estimate.manager.map(|typical| typical.as_secs_f64()),
+                "eta_secs": estimate.run.as_secs_f64(),
+                "eta_unknown": estimate.unknown,
+            })
+            .to_string(),
+        }
+    }
+
+    // Line reporting that `done` of `total` managers have finished, the last
+    // one with `result`, and the time the rest are estimated to take
+    pub fn done(
+        &self,
+        done: usize,
+        total: usize,
+        result: &ExecutionResult,
+        estimate: &Estimate,
+    ) -> String {
+        match self {
+            ProgressFormat::Plain => {
+                let mut status = result.status.label().to_string();
+                if let Some(left) = estimate.describe() {
+                    status.push_str(&format!("; {}", left));
+                }
+                format!(
+                    "{}/{} managers done ({}: {})",
+                    done,
+                    total,
+                    result.name(),
+                    status
+                )
+            }
+            ProgressFormat::Json => json!({
+                "event": "done",
+                "manager": result.name(),
+                "status": result.status.label(),
+                "duration_secs": result.duration.as_secs_f64(),
+                "warnings": result.warnings,
+                "columns": result.columns,
+                "done": done,
+                "total": total,
+                "eta_secs": estimate.run.as_secs_f64(),
+                "eta_unknown": estimate.unknown,
+            })
+            .to_string(),
+        }
+    }
+}
+
+fn verb(operation: Operation) -> &'static str {
+    match operation {
+        Operation::Check => "checking",
+        Operation::Update => "updating",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::ExecutionStatus;
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    #[test]
+    fn test_progress_lines() {
+        let result = ExecutionResult {
+            manager: "npm".to_string(),
+            subcommand: Some("global".to_string()),
+            status: ExecutionStatus::Failed,
+            message: "Error: Command failed".to_string(),
+            duration: Duration::from_millis(1500),
+            output: String::new(),
+            steps: Vec::new(),
+            exit_code: None,
+            upgraded: None,
+            cached: false,
+            reboot_required: false,
+            cleaned: Vec::new(),
+            warnings: vec!["Captured output truncated by 12 bytes (output_limit)".to_string()],
+            columns: BTreeMap::from([("outdated".to_string(), "5".to_string())]),
+        };
+
+        let unknown = Estimate::default();
+        let estimate = Estimate {
+            manager: Some(Duration::from_secs(20)),
+            run: Duration::from_secs(200),
+            unknown: 0,
+        };
+
+        let plain = ProgressFormat::Plain;
+        assert_eq!(
+            plain.begin(Operation::Update, "3f2a9c1e-8b4d-4e6f-a1b2-c3d4e5f60718", 7),
+            "run 3f2a9c1e-8b4d-4e6f-a1b2-c3d4e5f60718: updating 7 managers"
+        );
+        assert_eq!(
+            plain.start(Operation::Update, 2, 7, "npm:global", &unknown),
+            "2/7 managers: updating npm:global"
+        );
+        assert_eq!(
+            plain.start(Operation::Update, 2, 7, "npm:global", &estimate),
+            "2/7 managers: updating npm:global (usually 20s; about 3m20s left)"
+        );
+        assert_eq!(
+            plain.done(3, 7, &result, &unknown),
+            "3/7 managers done (npm:global: failed)"
+        );
+        let estimate = Estimate {
+            manager: None,
+            run: Duration::from_secs(30),
+            unknown: 1,
+        };
+        assert_eq!(
+            plain.done(3, 7, &result, &estimate),
+            "3/7 managers done (npm:global: failed; at least 30s left)"
+        );
+
+        let begin: serde_json::Value = serde_json::from_str(&ProgressFormat::Json.begin(
+            Operation::Check,
+            "3f2a9c1e-8b4d-4e6f-a1b2-c3d4e5f60718",
+            2,
+        ))
+        .unwrap();
+        assert_eq!(begin["event"], "run");
+        assert_eq!(begin["run_id"], "3f2a9c1e-8b4d-4e6f-a1b2-c3d4e5f60718");
+        assert_eq!(begin["total"], 2);
+        let start: serde_json::Value = serde_json::from_str(&ProgressFormat::Json.start(
+            Operation::Check,
+            1,
+            2,
+            "apt",
+            &unknown,
+        ))
+        .unwrap();
+        assert_eq!(start["event"], "start");
+        assert_eq!(start["operation"], "check");
+        assert_eq!(start["total"], 2);
+        assert!(start["manager_eta_secs"].is_null());
+        let line = ProgressFormat::Json.done(2, 2, &result, &estimate);
+        assert!(!line.contains('\n'));
+        let done: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(done["status"], "failed");
+        assert_eq!(done["duration_secs"], 1.5);
+        assert_eq!(done["done"], 2);
+        assert_eq!(done["eta_secs"], 30.0);
+        assert_eq!(done["eta_unknown"], 1);
+        assert_eq!(done["columns"]["outdated"], "5");
+        assert_eq!(
+            done["warnings"][0],
+            "Captured output truncated by 12 bytes (output_limit)"
+        );
+
+        assert_eq!("JSON".parse(), Ok(ProgressFormat::Json));
+        assert!("bar".parse::<ProgressFormat>().is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+
+// Proxies for the commands updog runs and for its own HTTP requests
+// (downloads, http checks, GitHub releases):
+//
+//   proxy:
+//     http: http://proxy.lan:3128
+//     https: http://proxy.lan:3128
+//     no_proxy: [localhost, .lan]
+//   commands:
+//     - id: npm
+//       update: npm update -g
+//       proxy: { https: http://egress.lan:8080 }   # replaces the global section
+//
+// Commands get them as http_proxy/HTTP_PROXY, https_proxy/HTTPS_PROXY and
+// no_proxy/NO_PROXY ahead of the manager's `env`, which can still override
+// them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub https: Option<String>,
+    // Hosts reached directly: exact names, or domains with a leading dot
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub no_proxy: Vec<String>,
+}
+
+impl ProxyConfig {
+    // Environment variables for spawned commands, lower and upper case
+    pub fn env(&self) -> Vec<(String, String)> {
+        let no_proxy = (!self.no_proxy.is_empty()).then(|| self.no_proxy.join(","));
+        [
+            ("http_proxy", self.http.clone()),
+            ("https_proxy", self.https.clone()),
+            ("no_proxy", no_proxy),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|v| (name, v)))
+        .flat_map(|(name, value)| {
+            [
+                (name.to_string(), value.clone()),
+                (name.to_ascii_uppercase(), value),
+            ]
+        })
+        .collect()
+    }
+}
+
+// The first of `names` set in `env`, then in updog's own environment
+fn lookup(names: &[&str], env: &[(String, String)]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|name| env.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone()))
+        .or_else(|| names.iter().find_map(|name| std::env::var(name).ok()))
+        .filter(|value| !value.is_empty())
+}
+
+fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host_port = authority.rsplit('@').next().unwrap_or_default();
+    match host_port.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => host_port.split(':').next().unwrap_or_default(),
+    }
+}
+
+// Whether a no_proxy list ("localhost,.lan,example.com") covers `host`
+pub fn bypasses(host: &str, no_proxy: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    no_proxy
+        .split(',')
+        .map(|entry| entry.trim().to_ascii_lowercase())
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| {
+            let domain = entry.trim_start_matches("*.").trim_start_matches('.');
+            entry == "*" || host == domain || host.ends_with(&format!(".{}", domain))
+        })
+}
+
+// The proxy to fetch `url` through, from the variables a manager's commands
+// get and then updog's own environment, unless no_proxy covers the host
+pub fn for_url(url: &str, env: &[(String, String)]) -> Option<String> {
+    let no_proxy = lookup(&["NO_PROXY", "no_proxy"], env).unwrap_or_default();
+    if bypasses(host(url), &no_proxy) {
+        return None;
+    }
+    if url.starts_with("http://") {
+        lookup(&["HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"], env)
+    } else {
+        lookup(
+            &["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"],
+            env,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_env() {
+        let config: ProxyConfig =
+            serde_yaml::from_str("{ https: 'http://proxy.lan:3128', no_proxy: [localhost, .lan] }")
+                .unwrap();
+        assert_eq!(
+            config.env(),
+            vars(&[
+                ("https_proxy", "http://proxy.lan:3128"),
+                ("HTTPS_PROXY", "http://proxy.lan:3128"),
+                ("no_proxy", "localhost,.lan"),
+                ("NO_PROXY", "localhost,.lan"),
+            ])
+        );
+        assert!(ProxyConfig::default().env().is_empty());
+    }
+
+    #[test]
+    fn test_bypasses() {
+        assert!(bypasses("localhost", "localhost, .lan"));
+        assert!(bypasses("nas.lan", "localhost,.lan"));
+        assert!(bypasses("api.example.com", "example.com"));
+        assert!(bypasses("anything", "*"));
+        assert!(!bypasses("example.com.evil", "example.com"));
+        assert!(!bypasses("notexample.com", "example.com"));
+        assert!(!bypasses("github.com", ""));
+    }
+
+    #[test]
+    fn test_for_url() {
+        let env = vars(&[
+            ("HTTPS_PROXY", "http://secure.lan:3128"),
+            ("http_proxy", "http://plain.lan:3128"),
+            ("NO_PROXY", ".lan"),
+        ]);
+        assert_eq!(
+            for_url("https://api.github.com/repos/x/y", &env).as_deref(),
+            Some("http://secure.lan:3128")
+        );
+        assert_eq!(
+            for_url("http://user:pw@example.com:8080/x", &env).as_deref(),
+            Some("http://plain.lan:3128")
+        );
+        assert_eq!(for_url("https://nas.lan/file", &env), None);
+        assert_eq!(host("http://[::1]:8080/x"), "::1");
+    }
+}
@@ -0,0 +1,198 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::sync::OnceLock;
+
+use crate::config::Config;
+
+// Placeholder substituted for sensitive values
+pub const REDACTED: &str = "********";
+
+// One entry of the top-level `redact:` list: either a regular expression
+// or the name of an environment variable whose value is masked
+//
+//   redact:
+//     - env: GITHUB_TOKEN
+//     - pattern: "ghp_[A-Za-z0-9]{36}"
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct RedactRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<String>,
+}
+
+impl RedactRule {
+    pub fn validate(&self) -> Result<(), String> {
+        match (&self.pattern, &self.env) {
+            (Some(pattern), None) => Regex::new(pattern).map(|_| ()).map_err(|e| e.to_string()),
+            (None, Some(_)) => Ok(()),
+            _ => Err("each entry needs exactly one of `pattern` or `env`".to_string()),
+        }
+    }
+}
+
+// Masks sensitive values in text before it reaches any output sink
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    patterns: Vec<Regex>,
+    values: Vec<String>,
+}
+
+impl Redactor {
+    // Build from the config's `redact:` rules. Env rules mask the variable's
+    // value from the process environment and from package manager `env` maps.
+    pub fn from_config(config: &Config) -> Self {
+        let mut redactor = Self::default();
+        for rule in &config.redact {
+            if let Some(pattern) = &rule.pattern {
+                // Invalid patterns are rejected when the config is loaded
+                if let Ok(re) = Regex::new(pattern) {
+                    redactor.patterns.push(re);
+                }
+            }
+            if let Some(name) = &rule.env {
+                let configured = config
+                    .commands
+                    .iter()
+                    .filter_map(|m| m.env.get(name))
+                    .filter(|value| !value.starts_with(crate::secret::SECRET_PREFIX))
+                    .cloned();
+                redactor
+                    .values
+                    .extend(std::env::var(name).ok().into_iter().chain(configured));
+            }
+        }
+        redactor.normalize();
+        redactor
+    }
+
+    // Also mask the given literal values (e.g. resolved secrets)
+    pub fn with_values<I: IntoIterator<Item = String>>(mut self, values: I) -> Self {
+        self.values.extend(values);
+        self.normalize();
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty() && self.values.is_empty()
+    }
+
+    // Longest values first, so a value containing another is masked whole
+    fn normalize(&mut self) {
+        self.values.retain(|value| !value.is_empty());
+        self.values
+            .sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        self.values.dedup();
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        if self.is_empty() {
+            return text.to_string();
+        }
+        let text = self.values.iter().fold(text.to_string(), |text, value| {
+            text.replace(value.as_str(), REDACTED)
+        });
+        self.patterns.iter().fold(text, |text, re| {
+            re.replace_all(&text, REDACTED).into_owned()
+        })
+    }
+}
+
+static LOG_REDACTOR: OnceLock<Redactor> = OnceLock::new();
+
+// Install the redactor used for log output. Only the first call has an effect.
+pub fn set_log_redactor(redactor: Redactor) {
+    let _ = LOG_REDACTOR.set(redactor);
+}
+
+// Log writer that masks sensitive values before they reach the inner writer
+pub struct RedactingWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> RedactingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+}
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match LOG_REDACTOR.get() {
+            Some(redactor) if !redactor.is_empty() => {
+                let text = String::from_utf8_lossy(buf);
+                self.inner.write_all(redactor.apply(&text).as_bytes())?;
+                Ok(buf.len())
+            }
+            _ => self.inner.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(yaml: &str) -> Config {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_pattern_and_config_env_rules() {
+        let config = config(
+            r#"
+            commands:
+              - id: npm
+                update: npm update -g
+                env:
+                  NPM_TOKEN: plain-token-value
+            redact:
+              - pattern: "ghp_[A-Za-z0-9]+"
+              - env: NPM_TOKEN
+            "#,
+        );
+        let redactor = Redactor::from_config(&config);
+        assert_eq!(
+            redactor.apply("gh ghp_abc123 npm plain-token-value"),
+            "gh ******** npm ********"
+        );
+    }
+
+    #[test]
+    fn test_with_values_masks_longest_first() {
+        let redactor = Redactor::default().with_values(vec![
+            "abc".to_string(),
+            "abcdef".to_string(),
+            String::new(),
+        ]);
+        assert_eq!(redactor.apply("x abcdef y abc"), "x ******** y ********");
+        assert_eq!(Redactor::default().apply("plain"), "plain");
+    }
+
+    #[test]
+    fn test_rule_validation() {
+        let rule = |pattern: Option<&str>, env: Option<&str>| RedactRule {
+            pattern: pattern.map(String::from),
+            env: env.map(String::from),
+        };
+        assert!(rule(Some("tok_[0-9]+"), None).validate().is_ok());
+        assert!(rule(None, Some("TOKEN")).validate().is_ok());
+        assert!(rule(Some("(unclosed"), None).validate().is_err());
+        assert!(rule(None, None).validate().is_err());
+        assert!(rule(Some("a"), Some("B")).validate().is_err());
+    }
+
+    #[test]
+    fn test_redacting_writer_passes_through_without_redactor() {
+        let mut buffer = Vec::new();
+        RedactingWriter::new(&mut buffer)
+            .write_all(b"log line\n")
+            .unwrap();
+        assert_eq!(buffer, b"log line\n");
+    }
+}
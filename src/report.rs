@@ -0,0 +1,949 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use crate::history::{unix_seconds, ManagerRecord, RunRecord, StepRecord};
+use crate::metadata::{new_run_id, RunMetadata};
+use crate::output::strip_ansi;
+use crate::step::StepResult;
+use crate::terminal;
+
+// Which command of a package manager is run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    #[default]
+    Check,
+    Update,
+}
+
+impl Operation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Check => "check",
+            Operation::Update => "update",
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// How `check` and `update` print their summary (`--output`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    // The `--summary-file` document, for jq and other tools
+    Json,
+    Yaml,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            _ => Err(format!(
+                "unknown output format '{}' (expected text, json or yaml)",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            OutputFormat::Text => "text",
+            OutputFormat::Json => "json",
+            OutputFormat::Yaml => "yaml",
+        })
+    }
+}
+
+// Outcome of running one package manager
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecutionStatus {
+    Success,
+    // A check found pending updates
+    UpdatesAvailable { count: usize },
+    // Nothing was run (e.g. no command for this operation)
+    Skipped { reason: String },
+    Failed,
+    TimedOut,
+    Cancelled,
+    // The update ran, but the manager's `verify` commands failed afterwards
+    VerifyFailed,
+    // The manager's ssh host couldn't be reached (or logged in to)
+    Unreachable,
+}
+
+impl ExecutionStatus {
+    // Short machine-friendly label, used in history and reports
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExecutionStatus::Success => "success",
+            ExecutionStatus::UpdatesAvailable { .. } => "updates_available",
+            ExecutionStatus::Skipped { .. } => "skipped",
+            ExecutionStatus::Failed => "failed",
+            ExecutionStatus::TimedOut => "timed_out",
+            ExecutionStatus::Cancelled => "cancelled",
+            ExecutionStatus::VerifyFailed => "verify_failed",
+            ExecutionStatus::Unreachable => "unreachable",
+        }
+    }
+
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self,
+            ExecutionStatus::Failed
+                | ExecutionStatus::TimedOut
+                | ExecutionStatus::VerifyFailed
+                | ExecutionStatus::Unreachable
+        )
+    }
+
+    // Section of the human readable summary this status is listed under
+    pub fn summary_heading(&self) -> &'static str {
+        match self {
+            ExecutionStatus::Success => "✅ Successful:",
+            ExecutionStatus::UpdatesAvailable { .. } => "📦 Updates available:",
+            ExecutionStatus::Skipped { .. } => "⏭️  Skipped:",
+            ExecutionStatus::Cancelled => "🛑 Cancelled:",
+            ExecutionStatus::TimedOut => "⏱️  Timed out:",
+            ExecutionStatus::VerifyFailed => "⚠️  Updated, but verification failed:",
+            ExecutionStatus::Unreachable => "📡 Unreachable:",
+            ExecutionStatus::Failed => "❌ Failed:",
+        }
+    }
+}
+
+// Order of the sections in the human readable summary
+const SUMMARY_SECTIONS: [&str; 8] = [
+    "✅ Successful:",
+    "📦 Updates available:",
+    "⏭️  Skipped:",
+    "🛑 Cancelled:",
+    "⏱️  Timed out:",
+    "⚠️  Updated, but verification failed:",
+    "📡 Unreachable:",
+    "❌ Failed:",
+];
+
+// Execution result tracking struct
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionResult {
+    pub manager: String,
+    pub subcommand: Option<String>,
+    pub status: ExecutionStatus,
+    pub message: String,
+    pub duration: Duration,
+    // Captured stdout (empty when output was passed through to the terminal)
+    pub output: String,
+    // Steps that ran, in order (only for multi-step sequences)
+    pub steps: Vec<StepResult>,
+    // Exit code of the last command that ran, if it exited normally
+    pub exit_code: Option<i32>,
+    // Number of packages an update upgraded, when its parser could tell
+    pub upgraded: Option<usize>,
+    // Reused from an earlier check within the manager's cooldown
+    pub cached: bool,
+    // The update left a reboot pending (firmware and OS presets)
+    pub reboot_required: bool,
+    // Removed by the entry's cleanup, listed apart from the upgrades
+    pub cleaned: Vec<String>,
+    // Warnings raised while it ran, shown in the summary's own section
+    pub warnings: Vec<String>,
+    // The manager's `summary_columns` found in its output, by name
+    pub columns: BTreeMap<String, String>,
+}
+
+impl ExecutionResult {
+    // Display name for logs and results ("manager" or "manager:subcommand")
+    pub fn name(&self) -> String {
+        match &self.subcommand {
+            Some(sc) => format!("{}:{}", self.manager, sc),
+            None => self.manager.clone(),
+        }
+    }
+
+    // A manager that didn't run, with the reason
+    pub fn skipped(
+        manager: String,
+        subcommand: Option<String>,
+        reason: &str,
+        message: String,
+    ) -> Self {
+        Self {
+            manager,
+            subcommand,
+            status: ExecutionStatus::Skipped {
+                reason: reason.to_string(),
+            },
+            message,
+            duration: Duration::ZERO,
+            output: String::new(),
+            steps: Vec::new(),
+            exit_code: None,
+            upgraded: None,
+            cached: false,
+            reboot_required: false,
+            cleaned: Vec::new(),
+            warnings: Vec::new(),
+            columns: BTreeMap::new(),
+        }
+    }
+
+    // Message for the summary, followed by the `summary_columns` found
+    pub fn summary_line(&self) -> String {
+        if self.columns.is_empty() {
+            return self.message.clone();
+        }
+        let columns: Vec<String> = self
+            .columns
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, value))
+            .collect();
+        format!("{}  [{}]", self.message, columns.join(", "))
+    }
+
+    // Number of pending updates found by a successful check
+    pub fn pending(&self) -> Option<usize> {
+        match self.status {
+            ExecutionStatus::UpdatesAvailable { count } => Some(count),
+            ExecutionStatus::Success => Some(0),
+            _ => None,
+        }
+    }
+}
+
+// All results of one check/update invocation, in execution order
+#[derive(Debug, Clone)]
+pub struct ExecutionReport {
+    // Identifies the run in history, logs, alerts and the audit log
+    pub run_id: String,
+    pub operation: Operation,
+    pub started_at: SystemTime,
+    pub results: Vec<ExecutionResult>,
+    pub metadata: Option<RunMetadata>,
+    // Annotation stored with the run in history
+    pub note: Option<String>,
+    // `category` of the managers that have one, by manager id
+    pub categories: BTreeMap<String, String>,
+    // Warnings about the run as a whole (config, selection)
+    pub warnings: Vec<String>,
+    // `manual` managers the selection left out, as a reminder they exist
+    pub manual: Vec<String>,
+    // Results of the check phase an update run started with, if it had one
+    pub checks: Vec<ExecutionResult>,
+}
+
+impl ExecutionReport {
+    pub fn new(operation: Operation, started_at: SystemTime) -> Self {
+        Self {
+            run_id: new_run_id(),
+            operation,
+            started_at,
+            results: Vec::new(),
+            metadata: None,
+            note: None,
+            categories: BTreeMap::new(),
+            warnings: Vec::new(),
+            manual: Vec::new(),
+            checks: Vec::new(),
+        }
+    }
+
+    pub fn count(&self, predicate: impl Fn(&ExecutionStatus) -> bool) -> usize {
+        self.results.iter().filter(|r| predicate(&r.status)).count()
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.results.iter().any(|r| r.status.is_failure())
+    }
+
+    // Process exit code: 1 for failures, 130 (SIGINT convention) if cancelled
+    pub fn exit_code(&self) -> i32 {
+        if self.has_failures() {
+            1
+        } else if self.count(|s| *s == ExecutionStatus::Cancelled) > 0 {
+            130
+        } else {
+            0
+        }
+    }
+
+    // Convert into a history record
+    pub fn to_run_record(&self) -> RunRecord {
+        let mut run = RunRecord::new(self.operation.as_str(), self.started_at);
+        run.run_id = Some(self.run_id.clone());
+        run.results = self
+            .results
+            .iter()
+            .filter(|r| !r.cached)
+            .map(|r| ManagerRecord {
+                manager: r.manager.clone(),
+                subcommand: r.subcommand.clone(),
+                success: !r.status.is_failure(),
+                status: Some(r.status.label().to_string()),
+                duration_secs: r.duration.as_secs_f64(),
+                packages: match self.operation {
+                    Operation::Check => r.pending(),
+                    Operation::Update => r.upgraded,
+                },
+                message: r.message.clone(),
+                steps: r
+                    .steps
+                    .iter()
+                    .map(|step| StepRecord {
+                        step: step.step.clone(),
+                        success: step.success,
+                        exit_code: step.exit_code,
+                        duration_secs: step.duration.as_secs_f64(),
+                    })
+                    .collect(),
+                output: strip_ansi(&r.output),
+                warnings: r.warnings.clone(),
+                columns: r.columns.clone(),
+            })
+            .collect();
+        run.metadata = self.metadata.clone();
+        run.note = self.note.clone();
+        run.warnings = self.warnings.clone();
+        run
+    }
+
+    // What the human readable summary shows, as JSON (`--summary-file`).
+    // Unlike the history record, it has every result, cached ones too.
+    pub fn to_summary_json(&self) -> serde_json::Value {
+        let mut totals = BTreeMap::new();
+        for result in &self.results {
+            *totals.entry(result.status.label()).or_insert(0) += 1;
+        }
+        let entry = |r: &ExecutionResult| {
+            json!({
+                "manager": r.manager,
+                "subcommand": r.subcommand,
+                "status": r.status.label(),
+                "success": !r.status.is_failure(),
+                "message": r.message,
+                "duration_secs": r.duration.as_secs_f64(),
+                // Of the last step that ran
+                "exit_code": r.exit_code,
+                "steps": r
+                    .steps
+                    .iter()
+                    .map(|step| {
+                        json!({
+                            "step": step.step,
+                            "success": step.success,
+                            "exit_code": step.exit_code,
+                            "duration_secs": step.duration.as_secs_f64(),
+                        })
+                    })
+                    .collect::<Vec<_>>(),
+                "pending": r.pending(),
+                "upgraded": r.upgraded,
+                "cached": r.cached,
+                "reboot_required": r.reboot_required,
+                "cleaned": r.cleaned,
+                "warnings": r.warnings,
+                "columns": r.columns,
+                "output": strip_ansi(&r.output),
+            })
+        };
+        json!({
+            "run_id": self.run_id,
+            "operation": self.operation,
+            "started_at": unix_seconds(self.started_at),
+            "exit_code": self.exit_code(),
+            "totals": totals,
+            "results": self.results.iter().map(entry).collect::<Vec<_>>(),
+            "checks": self.checks.iter().map(entry).collect::<Vec<_>>(),
+            "reboot_required": self
+                .results
+                .iter()
+                .filter(|r| r.reboot_required)
+                .map(|r| r.name())
+                .collect::<Vec<_>>(),
+            "manual": self.manual,
+            "warnings": self.warnings,
+            "note": self.note,
+            "metadata": self.metadata,
+        })
+    }
+
+    // The summary in the requested format, ready to print
+    pub fn render(&self, format: OutputFormat, width: Option<usize>) -> String {
+        match format {
+            OutputFormat::Text => self.render_summary(width),
+            OutputFormat::Json => {
+                let mut json = serde_json::to_string_pretty(&self.to_summary_json())
+                    .expect("the summary serializes to JSON");
+                json.push('\n');
+                json
+            }
+            OutputFormat::Yaml => serde_yaml::to_string(&self.to_summary_json())
+                .expect("the summary serializes to YAML"),
+        }
+    }
+
+    // Human readable summary of the execution results. With a terminal
+    // `width`, messages are wrapped into a column next to the aligned names.
+    // When managers have categories, results are grouped by category (in
+    // order of appearance, uncategorized last), each with its own totals.
+    pub fn render_summary(&self, width: Option<usize>) -> String {
+        let title = match self.operation {
+            Operation::Check => "Check",
+            Operation::Update => "Update",
+        };
+        let mut out = format!("\n{} Summary:\n", title);
+        out.push_str("==============================================\n");
+
+        let all: Vec<&ExecutionResult> = self.results.iter().collect();
+        if self.categories.is_empty() {
+            out.push_str(&render_sections(&all, width));
+        } else {
+            for (i, (category, items)) in self.by_category().iter().enumerate() {
+                if i > 0 {
+                    out.push('\n');
+                }
+                out.push_str(&format!("{} — {}\n", category, totals(items)));
+                out.push_str("----------------------------------------------\n");
+                out.push_str(&render_sections(items, width));
+            }
+        }
+
+        let warnings: Vec<String> = self
+            .warnings
+            .iter()
+            .cloned()
+            .chain(all.iter().flat_map(|r| {
+                r.warnings
+                    .iter()
+                    .map(|warning| format!("{}: {}", r.name(), warning))
+            }))
+            .collect();
+        if !warnings.is_empty() {
+            out.push_str("\n⚠️  Warnings:\n");
+            for warning in warnings {
+                out.push_str(&format!("  - {}\n", warning));
+            }
+        }
+
+        out.push_str(&format!("\n{}\n", totals(&all)));
+        if !self.manual.is_empty() {
+            out.push_str(&format!(
+                "✋ Manual, not run: {} (name them to update)\n",
+                self.manual.join(", ")
+            ));
+        }
+        let reboots: Vec<String> = all
+            .iter()
+            .filter(|r| r.reboot_required)
+            .map(|r| r.name())
+            .collect();
+        if !reboots.is_empty() {
+            out.push_str(&format!(
+                "🔁 Reboot required to finish: {}\n",
+                reboots.join(", ")
+            ));
+        }
+        let cleanups: Vec<&&ExecutionResult> =
+            all.iter().filter(|r| !r.cleaned.is_empty()).collect();
+        if !cleanups.is_empty() {
+            out.push_str("🧹 Cleaned up:\n");
+            for result in cleanups {
+                out.push_str(&format!(
+                    "  - {}: {}\n",
+                    result.name(),
+                    result.cleaned.join(", ")
+                ));
+            }
+        }
+        out
+    }
+
+    // Results grouped by the category of their manager
+    fn by_category(&self) -> Vec<(&str, Vec<&ExecutionResult>)> {
+        let mut groups: Vec<(&str, Vec<&ExecutionResult>)> = Vec::new();
+        let mut uncategorized = Vec::new();
+        for result in &self.results {
+            match self.categories.get(&result.manager) {
+                Some(category) => match groups.iter_mut().find(|(c, _)| c == category) {
+                    Some((_, items)) => items.push(result),
+                    None => groups.push((category, vec![result])),
+                },
+                None => uncategorized.push(result),
+            }
+        }
+        if !uncategorized.is_empty() {
+            groups.push(("uncategorized", uncategorized));
+        }
+        groups
+    }
+}
+
+// The results under a heading for each status, in SUMMARY_SECTIONS order
+fn render_sections(results: &[&ExecutionResult], width: Option<usize>) -> String {
+    let mut out = String::new();
+    for heading in SUMMARY_SECTIONS {
+        let items: Vec<&ExecutionResult> = results
+            .iter()
+            .copied()
+            .filter(|r| r.status.summary_heading() == heading)
+            .collect();
+        if items.is_empty() {
+            continue;
+        }
+        if !out.is_empty() {
+            out.push('\n'); // Add empty line between sections
+        }
+
+        out.push_str(heading);
+        out.push('\n');
+        match width {
+            Some(width) => out.push_str(&render_items(&items, width)),
+            None => {
+                for result in items {
+                    out.push_str(&format!(
+                        "  - {}: {}\n",
+                        result.name(),
+                        result.summary_line()
+                    ));
+                }
+            }
+        }
+    }
+    out
+}
+
+// "Total: 4, Successful: 2, Failed: 1", plus skipped and cancelled if any
+fn totals(results: &[&ExecutionResult]) -> String {
+    let count = |predicate: &dyn Fn(&ExecutionStatus) -> bool| {
+        results.iter().filter(|r| predicate(&r.status)).count()
+    };
+    let successful = count(&|s| {
+        matches!(
+            s,
+            ExecutionStatus::Success | ExecutionStatus::UpdatesAvailable { .. }
+        )
+    });
+    let mut out = format!(
+        "Total: {}, Successful: {}, Failed: {}",
+        results.len(),
+        successful,
+        count(&ExecutionStatus::is_failure)
+    );
+    let skipped = count(&|s| matches!(s, ExecutionStatus::Skipped { .. }));
+    if skipped > 0 {
+        out.push_str(&format!(", Skipped: {}", skipped));
+    }
+    let cancelled = count(&|s| *s == ExecutionStatus::Cancelled);
+    if cancelled > 0 {
+        out.push_str(&format!(", Cancelled: {}", cancelled));
+    }
+    out
+}
+
+// Summary lines for a terminal `width` columns wide. Long names are cut so
+// that messages keep at least half of the line.
+fn render_items(items: &[&ExecutionResult], width: usize) -> String {
+    const INDENT: &str = "  - ";
+    let names: Vec<String> = items.iter().map(|r| format!("{}:", r.name())).collect();
+    let name_width = names
+        .iter()
+        .map(|name| terminal::display_width(name))
+        .max()
+        .unwrap_or(0)
+        .min(width.saturating_sub(INDENT.len()) / 2);
+    let message_column = INDENT.len() + name_width + 1;
+    let message_width = width.saturating_sub(message_column);
+
+    let mut out = String::new();
+    for (name, result) in names.iter().zip(items) {
+        let name = terminal::pad(&terminal::truncate(name, name_width), name_width);
+        for (i, line) in terminal::wrap(&result.summary_line(), message_width)
+            .iter()
+            .enumerate()
+        {
+            let lead = match i {
+                0 => format!("{}{} ", INDENT, name),
+                _ => " ".repeat(message_column),
+            };
+            out.push_str(lead.as_str());
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    fn result(manager: &str, status: ExecutionStatus) -> ExecutionResult {
+        ExecutionResult {
+            manager: manager.to_string(),
+            subcommand: None,
+            status,
+            message: "msg".to_string(),
+            duration: Duration::from_secs(1),
+            output: String::new(),
+            steps: Vec::new(),
+            exit_code: None,
+            upgraded: None,
+            cached: false,
+            reboot_required: false,
+            cleaned: Vec::new(),
+            warnings: Vec::new(),
+            columns: BTreeMap::new(),
+        }
+    }
+
+    fn report(statuses: Vec<ExecutionStatus>) -> ExecutionReport {
+        let mut report = ExecutionReport::new(Operation::Check, UNIX_EPOCH);
+        for (i, status) in statuses.into_iter().enumerate() {
+            report.results.push(result(&format!("m{}", i), status));
+        }
+        report
+    }
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(report(vec![ExecutionStatus::Success]).exit_code(), 0);
+        let skipped = ExecutionStatus::Skipped {
+            reason: "no check command".to_string(),
+        };
+        assert_eq!(
+            report(vec![ExecutionStatus::Success, skipped]).exit_code(),
+            0
+        );
+        assert_eq!(report(vec![ExecutionStatus::Cancelled]).exit_code(), 130);
+        assert_eq!(
+            report(vec![ExecutionStatus::Cancelled, ExecutionStatus::TimedOut]).exit_code(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_summary_sections() {
+        let summary = report(vec![
+            ExecutionStatus::Success,
+            ExecutionStatus::UpdatesAvailable { count: 3 },
+            ExecutionStatus::Skipped {
+                reason: "no check command".to_string(),
+            },
+            ExecutionStatus::Failed,
+        ])
+        .render_summary(None);
+
+        assert!(summary.contains("Check Summary:"));
+        assert!(summary.contains("📦 Updates available:\n  - m1: msg"));
+        assert!(summary.contains("Skipped:\n  - m2: msg"));
+        assert!(summary.contains("❌ Failed:\n  - m3: msg"));
+        assert!(summary.contains("Total: 4, Successful: 2, Failed: 1, Skipped: 1"));
+    }
+
+    #[test]
+    fn test_summary_lists_reboots() {
+        let mut report = report(vec![ExecutionStatus::Success, ExecutionStatus::Success]);
+        assert!(!report.render_summary(None).contains("Reboot"));
+        report.results[1].reboot_required = true;
+        assert!(report
+            .render_summary(None)
+            .ends_with("🔁 Reboot required to finish: m1\n"));
+    }
+
+    #[test]
+    fn test_summary_json() {
+        let mut report = report(vec![
+            ExecutionStatus::UpdatesAvailable { count: 3 },
+            ExecutionStatus::Failed,
+            ExecutionStatus::Success,
+        ]);
+        report.results[0].output = "\x1b[1mgit\x1b[0m 2.40 -> 2.41\n".to_string();
+        report.results[1].exit_code = Some(100);
+        report.results[1].steps = vec![
+            StepResult {
+                step: "apt-get update".to_string(),
+                success: true,
+                exit_code: Some(0),
+                duration: Duration::from_millis(500),
+                output: "Hit:1 http://deb.debian.org".to_string(),
+            },
+            StepResult {
+                step: "apt-get upgrade -y".to_string(),
+                success: false,
+                exit_code: Some(100),
+                duration: Duration::from_secs(3),
+                output: String::new(),
+            },
+        ];
+        report.results[2].cached = true;
+        report.results[2].reboot_required = true;
+        report.manual = vec!["fwupd".to_string()];
+        let json = report.to_summary_json();
+        assert_eq!(json["operation"], "check");
+        assert_eq!(json["started_at"], 0);
+        assert_eq!(json["exit_code"], 1);
+        assert_eq!(
+            json["totals"],
+            serde_json::json!({"updates_available": 1, "failed": 1, "success": 1})
+        );
+        assert_eq!(json["results"].as_array().unwrap().len(), 3);
+        assert_eq!(json["results"][0]["pending"], 3);
+        assert_eq!(json["results"][0]["success"], true);
+        assert_eq!(json["results"][1]["success"], false);
+        assert_eq!(json["results"][0]["output"], "git 2.40 -> 2.41\n");
+        assert!(json["results"][1]["pending"].is_null());
+        assert_eq!(json["results"][1]["exit_code"], 100);
+        assert!(json["results"][0]["exit_code"].is_null());
+        assert_eq!(json["results"][2]["cached"], true);
+        assert_eq!(json["reboot_required"], serde_json::json!(["m2"]));
+        assert_eq!(json["manual"], serde_json::json!(["fwupd"]));
+        assert_eq!(json["checks"], serde_json::json!([]));
+        assert_eq!(json["results"][0]["steps"], serde_json::json!([]));
+        assert_eq!(
+            json["results"][1]["steps"],
+            serde_json::json!([
+                {"step": "apt-get update", "success": true, "exit_code": 0, "duration_secs": 0.5},
+                {"step": "apt-get upgrade -y", "success": false, "exit_code": 100, "duration_secs": 3.0},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_render_formats() {
+        let report = report(vec![ExecutionStatus::Success]);
+        assert_eq!(
+            report.render(OutputFormat::Text, None),
+            report.render_summary(None)
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&report.render(OutputFormat::Json, None)).unwrap();
+        assert_eq!(json, report.to_summary_json());
+        let yaml: serde_json::Value =
+            serde_yaml::from_str(&report.render(OutputFormat::Yaml, None)).unwrap();
+        assert_eq!(yaml, report.to_summary_json());
+        assert_eq!("YAML".parse(), Ok(OutputFormat::Yaml));
+        assert!("csv".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn test_summary_lists_manual_managers() {
+        let mut report = report(vec![ExecutionStatus::Success]);
+        assert!(!report.render_summary(None).contains("Manual"));
+        report.manual = vec!["fwupd".to_string(), "do-release-upgrade".to_string()];
+        assert!(report.render_summary(None).ends_with(
+            "Total: 1, Successful: 1, Failed: 0\n\
+             ✋ Manual, not run: fwupd, do-release-upgrade (name them to update)\n"
+        ));
+    }
+
+    #[test]
+    fn test_summary_lists_cleanups() {
+        let mut report = report(vec![ExecutionStatus::Success, ExecutionStatus::Success]);
+        report.results[0].cleaned = vec![
+            "org.gnome.Platform//45".to_string(),
+            "org.kde.Platform//5.15-22.08".to_string(),
+        ];
+        assert!(report.render_summary(None).ends_with(concat!(
+            "🧹 Cleaned up:\n",
+            "  - m0: org.gnome.Platform//45, org.kde.Platform//5.15-22.08\n"
+        )));
+    }
+
+    #[test]
+    fn test_summary_lists_warnings() {
+        let mut report = report(vec![ExecutionStatus::Success, ExecutionStatus::Success]);
+        assert!(!report.render_summary(None).contains("Warnings"));
+        report.warnings = vec!["Nothing matched 'pip'".to_string()];
+        report.results[1].warnings =
+            vec!["Captured output truncated by 12 bytes (output_limit)".to_string()];
+        assert!(report.render_summary(None).contains(concat!(
+            "⚠️  Warnings:\n",
+            "  - Nothing matched 'pip'\n",
+            "  - m1: Captured output truncated by 12 bytes (output_limit)\n"
+        )));
+
+        let run = report.to_run_record();
+        assert_eq!(run.warnings, report.warnings);
+        assert!(run.results[0].warnings.is_empty());
+        assert_eq!(run.results[1].warnings, report.results[1].warnings);
+    }
+
+    #[test]
+    fn test_summary_shows_columns() {
+        let mut report = report(vec![ExecutionStatus::Success]);
+        report.results[0].columns = [("outdated", "5"), ("abandoned", "1")]
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        assert!(report
+            .render_summary(None)
+            .contains("  - m0: msg  [abandoned: 1, outdated: 5]\n"));
+        assert!(report
+            .render_summary(Some(80))
+            .contains("  - m0: msg  [abandoned: 1, outdated: 5]\n"));
+        assert_eq!(
+            report.to_run_record().results[0].columns,
+            report.results[0].columns
+        );
+    }
+
+    #[test]
+    fn test_summary_by_category() {
+        let mut report = report(vec![
+            ExecutionStatus::Success,
+            ExecutionStatus::UpdatesAvailable { count: 3 },
+            ExecutionStatus::Failed,
+            ExecutionStatus::Success,
+        ]);
+        report.categories = [("m0", "system"), ("m1", "language"), ("m2", "system")]
+            .iter()
+            .map(|(m, c)| (m.to_string(), c.to_string()))
+            .collect();
+
+        let summary = report.render_summary(None);
+        assert!(
+            summary.ends_with(concat!(
+                "==============================================\n",
+                "system — Total: 2, Successful: 1, Failed: 1\n",
+                "----------------------------------------------\n",
+                "✅ Successful:\n",
+                "  - m0: msg\n",
+                "\n",
+                "❌ Failed:\n",
+                "  - m2: msg\n",
+                "\n",
+                "language — Total: 1, Successful: 1, Failed: 0\n",
+                "----------------------------------------------\n",
+                "📦 Updates available:\n",
+                "  - m1: msg\n",
+                "\n",
+                "uncategorized — Total: 1, Successful: 1, Failed: 0\n",
+                "----------------------------------------------\n",
+                "✅ Successful:\n",
+                "  - m3: msg\n",
+                "\n",
+                "Total: 4, Successful: 3, Failed: 1\n",
+            )),
+            "{}",
+            summary
+        );
+    }
+
+    #[test]
+    fn test_summary_for_terminal_width() {
+        let mut report = ExecutionReport::new(Operation::Update, UNIX_EPOCH);
+        report.results.push(ExecutionResult {
+            message: "Error: Failed at step 2/3: npm cache clean --force (exit status: 1)"
+                .to_string(),
+            ..result("npm", ExecutionStatus::Failed)
+        });
+        report.results.push(ExecutionResult {
+            subcommand: Some("a-very-long-subcommand-name".to_string()),
+            ..result("npm", ExecutionStatus::Failed)
+        });
+
+        let summary = report.render_summary(Some(40));
+        assert!(
+            summary.contains(concat!(
+                "  - npm:               Error: Failed at\n",
+                "                       step 2/3: npm\n",
+                "                       cache clean\n",
+                "                       --force (exit\n",
+                "                       status: 1)\n",
+                "  - npm:a-very-long-s… msg\n",
+            )),
+            "{}",
+            summary
+        );
+        assert!(summary
+            .lines()
+            .filter(|line| line.starts_with("  "))
+            .all(|line| terminal::display_width(line) <= 40));
+    }
+
+    #[test]
+    fn test_to_run_record() {
+        let run = report(vec![
+            ExecutionStatus::UpdatesAvailable { count: 3 },
+            ExecutionStatus::Skipped {
+                reason: "none".to_string(),
+            },
+            ExecutionStatus::Failed,
+        ])
+        .to_run_record();
+
+        assert_eq!(run.operation, "check");
+        assert_eq!(run.results[0].packages, Some(3));
+        assert!(run.results[1].success);
+        assert_eq!(run.results[1].status.as_deref(), Some("skipped"));
+        assert!(!run.results[2].success);
+        assert!(run.metadata.is_none());
+    }
+
+    #[test]
+    fn test_to_run_record_keeps_steps() {
+        let mut report = report(vec![ExecutionStatus::Failed, ExecutionStatus::Success]);
+        report.results[0].steps = vec![
+            StepResult {
+                step: "brew update".to_string(),
+                success: true,
+                exit_code: Some(0),
+                duration: Duration::from_millis(1500),
+                output: "Already up-to-date.".to_string(),
+            },
+            StepResult {
+                step: "download tool.tar.gz".to_string(),
+                success: false,
+                exit_code: None,
+                duration: Duration::from_secs(2),
+                output: String::new(),
+            },
+        ];
+
+        let run = report.to_run_record();
+        let steps: Vec<_> = run.results[0]
+            .steps
+            .iter()
+            .map(|s| (s.step.as_str(), s.success, s.exit_code, s.duration_secs))
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                ("brew update", true, Some(0), 1.5),
+                ("download tool.tar.gz", false, None, 2.0)
+            ]
+        );
+        // A single command has no steps of its own
+        assert!(run.results[1].steps.is_empty());
+    }
+
+    #[test]
+    fn test_to_run_record_keeps_metadata() {
+        let mut report = report(vec![ExecutionStatus::Success]);
+        report.metadata = Some(RunMetadata {
+            hostname: Some("laptop".to_string()),
+            os: "linux-x86_64".to_string(),
+            updog_version: "0.1.0".to_string(),
+            ..Default::default()
+        });
+
+        let run = report.to_run_record();
+        assert_eq!(run.metadata, report.metadata);
+    }
+}
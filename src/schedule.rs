@@ -0,0 +1,491 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::cron::CronSchedule;
+use crate::hash::Sha256;
+use crate::history::unix_seconds;
+use crate::state_file::write_atomic;
+use crate::timezone::Timezone;
+
+#[derive(Debug, Error)]
+pub enum ScheduleError {
+    #[error("Failed to access schedule state: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Failed to parse schedule state: {0}")]
+    ParseError(#[from] serde_yaml::Error),
+}
+
+// How often something runs: "30m", "6h", "2d", "1w" or hourly/daily/weekly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Interval(Duration);
+
+impl Interval {
+    pub const HOUR: Interval = Interval(Duration::from_secs(3600));
+    pub const DAY: Interval = Interval(Duration::from_secs(86_400));
+    pub const WEEK: Interval = Interval(Duration::from_secs(7 * 86_400));
+
+    pub fn duration(&self) -> Duration {
+        self.0
+    }
+}
+
+impl TryFrom<String> for Interval {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Interval> for String {
+    fn from(interval: Interval) -> Self {
+        interval.to_string()
+    }
+}
+
+impl std::str::FromStr for Interval {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "hourly" => return Ok(Interval::HOUR),
+            "daily" => return Ok(Interval::DAY),
+            "weekly" => return Ok(Interval::WEEK),
+            _ => {}
+        }
+
+        let duration = parse_duration(value)?;
+        if duration.is_zero() {
+            return Err(format!("interval must be positive: '{}'", value));
+        }
+        Ok(Interval(duration))
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format_duration_spec(self.0))
+    }
+}
+
+// Parse a duration such as "90s", "15m", "6h", "2d", "1w" or "1h30m"
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration '{}' (expected e.g. 30m, 6h, 1d)", value);
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut total = 0u64;
+    let mut digits = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86_400,
+            'w' => 7 * 86_400,
+            _ => return Err(invalid()),
+        };
+        let amount: u64 = digits.parse().map_err(|_| invalid())?;
+        total += amount * unit;
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(total))
+}
+
+// Inverse of parse_duration, using the largest units that fit
+pub fn format_duration_spec(duration: Duration) -> String {
+    let mut secs = duration.as_secs();
+    if secs == 0 {
+        return "0s".to_string();
+    }
+    let mut out = String::new();
+    for (unit, size) in [
+        ("w", 7 * 86_400),
+        ("d", 86_400),
+        ("h", 3600),
+        ("m", 60),
+        ("s", 1),
+    ] {
+        if secs >= size {
+            out.push_str(&format!("{}{}", secs / size, unit));
+            secs %= size;
+        }
+    }
+    out
+}
+
+// When a manager runs: at a fixed interval after its last run, or on a
+// cron schedule evaluated in a time zone
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cadence {
+    Every(Interval),
+    Cron(CronSchedule, Timezone),
+}
+
+impl Cadence {
+    // Next run after `time`; None if a cron expression never matches again
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        match self {
+            Cadence::Every(interval) => Some(time + interval.duration()),
+            Cadence::Cron(schedule, timezone) => schedule.next_after(time, *timezone),
+        }
+    }
+}
+
+impl fmt::Display for Cadence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Cadence::Every(interval) => write!(f, "every {}", interval),
+            Cadence::Cron(schedule, _) => write!(f, "cron {}", schedule),
+        }
+    }
+}
+
+// Persisted scheduling state of one manager
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ScheduleEntry {
+    // Seconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<u64>,
+    pub next_due: u64,
+    // Random delay (seconds) added on top of the cadence
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub jitter: u64,
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+impl ScheduleEntry {
+    // When the manager should run next under the given cadence. A cadence
+    // changed to run sooner since the last run takes effect immediately.
+    pub fn due_at(&self, cadence: &Cadence) -> SystemTime {
+        let stored = UNIX_EPOCH + Duration::from_secs(self.next_due);
+        match self.last_run {
+            Some(last) => cadence
+                .next_after(UNIX_EPOCH + Duration::from_secs(last))
+                .map_or(stored, |due| {
+                    (due + Duration::from_secs(self.jitter)).min(stored)
+                }),
+            None => stored,
+        }
+    }
+}
+
+// Fallback for schedules that never match again
+const FAR_FUTURE: Duration = Duration::from_secs(100 * 365 * 86_400);
+
+// Pseudo-random delay in 0..=max derived from `seed`. Seeding with the host
+// name spreads a fleet sharing one config while keeping each host predictable.
+pub fn jitter_delay(seed: &str, max: Duration) -> Duration {
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(seed.as_bytes());
+    let digest = hasher.finalize();
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    Duration::from_secs(u64::from_be_bytes(bytes) % (max.as_secs() + 1))
+}
+
+// Scheduler state kept across daemon restarts
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ScheduleState {
+    #[serde(default)]
+    pub managers: BTreeMap<String, ScheduleEntry>,
+    // When the runs of the next digest started (seconds since the epoch)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest_since: Option<u64>,
+}
+
+impl ScheduleState {
+    // Default location: <data dir>/updog/schedule.yaml
+    pub fn default_path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("updog")
+            .join("schedule.yaml")
+    }
+
+    // A missing file is an empty state (everything is due)
+    pub fn load(path: &Path) -> Result<Self, ScheduleError> {
+        match fs::read_to_string(path) {
+            Ok(content) if content.trim().is_empty() => Ok(Self::default()),
+            Ok(content) => Ok(serde_yaml::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ScheduleError> {
+        write_atomic(path, serde_yaml::to_string(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    // When `manager` is next due. Managers never seen before are due now
+    // with an interval, or at the next matching time with a cron schedule.
+    pub fn due_at(&self, manager: &str, cadence: &Cadence, now: SystemTime) -> SystemTime {
+        match self.managers.get(manager) {
+            Some(entry) => entry.due_at(cadence),
+            None => match cadence {
+                Cadence::Every(_) => now,
+                Cadence::Cron(..) => cadence.next_after(now).unwrap_or(now + FAR_FUTURE),
+            },
+        }
+    }
+
+    // Start tracking a manager that has no entry yet, so that a cron
+    // schedule's first due time doesn't move with every check. `jitter` is
+    // added to that first due time. Returns whether an entry was added.
+    pub fn register(
+        &mut self,
+        manager: &str,
+        cadence: &Cadence,
+        now: SystemTime,
+        jitter: Duration,
+    ) -> bool {
+        if self.managers.contains_key(manager) {
+            return false;
+        }
+        let due = self.due_at(manager, cadence, now) + jitter;
+        self.managers.insert(
+            manager.to_string(),
+            ScheduleEntry {
+                last_run: None,
+                next_due: unix_seconds(due),
+                jitter: jitter.as_secs(),
+            },
+        );
+        true
+    }
+
+    // Record a run at `now` and schedule the next one, `jitter` after the
+    // time the cadence gives
+    pub fn record_run(
+        &mut self,
+        manager: &str,
+        cadence: &Cadence,
+        now: SystemTime,
+        jitter: Duration,
+    ) {
+        let next_due = cadence.next_after(now).unwrap_or(now + FAR_FUTURE) + jitter;
+        self.managers.insert(
+            manager.to_string(),
+            ScheduleEntry {
+                last_run: Some(unix_seconds(now)),
+                next_due: unix_seconds(next_due),
+                jitter: jitter.as_secs(),
+            },
+        );
+    }
+}
+
+// Maintenance switch set by `updog pause` and cleared by `updog resume`.
+// Kept in its own file so a running daemon never overwrites it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Pause {
+    pub since: u64,
+    // Paused indefinitely when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<u64>,
+}
+
+impl Pause {
+    pub fn new(now: SystemTime, duration: Option<Duration>) -> Self {
+        Self {
+            since: unix_seconds(now),
+            until: duration.map(|d| unix_seconds(now + d)),
+        }
+    }
+
+    // Default location: <data dir>/updog/pause.yaml
+    pub fn default_path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("updog")
+            .join("pause.yaml")
+    }
+
+    // The pause in effect at `now`, if any. An expired pause is ignored.
+    pub fn load_active(path: &Path, now: SystemTime) -> Result<Option<Self>, ScheduleError> {
+        let pause: Self = match fs::read_to_string(path) {
+            Ok(content) if content.trim().is_empty() => return Ok(None),
+            Ok(content) => serde_yaml::from_str(&content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(pause.is_active(now).then_some(pause))
+    }
+
+    pub fn is_active(&self, now: SystemTime) -> bool {
+        self.until.is_none_or(|until| unix_seconds(now) < until)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ScheduleError> {
+        write_atomic(path, serde_yaml::to_string(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    // Remove the pause file. Returns whether there was one.
+    pub fn clear(path: &Path) -> Result<bool, ScheduleError> {
+        match fs::remove_file(path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(
+            "6h".parse::<Interval>().unwrap().duration(),
+            Duration::from_secs(6 * 3600)
+        );
+        assert_eq!(
+            "1h30m".parse::<Interval>().unwrap().duration(),
+            Duration::from_secs(5400)
+        );
+        assert_eq!("weekly".parse::<Interval>().unwrap(), Interval::WEEK);
+        assert_eq!("daily".parse::<Interval>().unwrap(), Interval::DAY);
+        assert!("0m".parse::<Interval>().is_err());
+        assert!("6".parse::<Interval>().is_err());
+        assert!("6x".parse::<Interval>().is_err());
+        assert!("".parse::<Interval>().is_err());
+    }
+
+    #[test]
+    fn test_interval_round_trip() {
+        let interval: Interval = serde_yaml::from_str("1d12h").unwrap();
+        assert_eq!(interval.to_string(), "1d12h");
+        assert_eq!(serde_yaml::to_string(&Interval::WEEK).unwrap().trim(), "1w");
+    }
+
+    #[test]
+    fn test_state_persists_next_due() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("schedule.yaml");
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+
+        let daily = Cadence::Every(Interval::DAY);
+        let mut state = ScheduleState::load(&path).unwrap();
+        assert_eq!(state.due_at("brew", &daily, now), now);
+
+        state.record_run("brew", &daily, now, Duration::ZERO);
+        state.save(&path).unwrap();
+
+        let loaded = ScheduleState::load(&path).unwrap();
+        assert_eq!(loaded, state);
+        assert_eq!(
+            loaded.due_at("brew", &daily, now),
+            now + Duration::from_secs(86_400)
+        );
+    }
+
+    #[test]
+    fn test_shortened_interval_takes_effect() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let mut state = ScheduleState::default();
+        state.record_run("brew", &Cadence::Every(Interval::WEEK), now, Duration::ZERO);
+        assert_eq!(
+            state.due_at("brew", &Cadence::Every(Interval::HOUR), now),
+            now + Duration::from_secs(3600)
+        );
+    }
+
+    #[test]
+    fn test_cron_first_due_time_is_registered() {
+        // 2024-01-01T10:00:00Z, a Monday
+        let now = UNIX_EPOCH + Duration::from_secs(1_704_103_200);
+        let cadence = Cadence::Cron("0 9 * * MON".parse().unwrap(), Timezone::Utc);
+        let next_week = now + Duration::from_secs(7 * 86_400 - 3600);
+
+        let mut state = ScheduleState::default();
+        assert_eq!(state.due_at("brew", &cadence, now), next_week);
+        assert!(state.register("brew", &cadence, now, Duration::ZERO));
+        assert!(!state.register("brew", &cadence, now, Duration::ZERO));
+
+        // Later checks still see the registered time, not a moving target
+        let later = now + Duration::from_secs(3 * 86_400);
+        assert_eq!(state.due_at("brew", &cadence, later), next_week);
+    }
+
+    #[test]
+    fn test_jitter_delay() {
+        let max = Duration::from_secs(900);
+        let a = jitter_delay("host-a:brew:100", max);
+        assert!(a <= max);
+        assert_eq!(a, jitter_delay("host-a:brew:100", max));
+        assert_eq!(jitter_delay("anything", Duration::ZERO), Duration::ZERO);
+
+        // Different hosts are spread out
+        let delays: std::collections::HashSet<Duration> = (0..20)
+            .map(|i| jitter_delay(&format!("host-{}:brew:100", i), max))
+            .collect();
+        assert!(delays.len() > 10);
+    }
+
+    #[test]
+    fn test_jitter_survives_shortened_cadence_check() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let jitter = Duration::from_secs(600);
+        let cadence = Cadence::Every(Interval::HOUR);
+        let mut state = ScheduleState::default();
+        state.record_run("brew", &cadence, now, jitter);
+        assert_eq!(
+            state.due_at("brew", &cadence, now),
+            now + Duration::from_secs(3600) + jitter
+        );
+    }
+
+    #[test]
+    fn test_pause_roundtrip_and_expiry() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("pause.yaml");
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        assert_eq!(Pause::load_active(&path, now).unwrap(), None);
+
+        Pause::new(now, Some(Duration::from_secs(7200)))
+            .save(&path)
+            .unwrap();
+        assert!(Pause::load_active(&path, now + Duration::from_secs(3600))
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            Pause::load_active(&path, now + Duration::from_secs(7200)).unwrap(),
+            None
+        );
+
+        Pause::new(now, None).save(&path).unwrap();
+        assert!(
+            Pause::load_active(&path, now + Duration::from_secs(365 * 86_400))
+                .unwrap()
+                .is_some()
+        );
+
+        assert!(Pause::clear(&path).unwrap());
+        assert!(!Pause::clear(&path).unwrap());
+    }
+}
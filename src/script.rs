@@ -0,0 +1,163 @@
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::metadata;
+
+// Rhai snippets for logic that's awkward in shell
+//
+//   scripting:
+//     env: [CI, XDG_SESSION_TYPE]   # environment variables scripts may read
+//   commands:
+//     - id: apt
+//       check: "apt list --upgradable"
+//       when_script: 'os == "linux" && !("CI" in env)'
+//       transform_output: 'output.split("\n").filter(|l| !l.starts_with("Listing"))'
+//
+// Scripts see `os`, `arch`, `hostname`, `manager` and `env` (only the listed
+// variables); `transform_output` also gets the captured `output`. There is no
+// file, network or process access, and runaway scripts are stopped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ScriptingConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub env: Vec<String>,
+}
+
+// Build the sandboxed engine
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.set_max_operations(1_000_000);
+    engine.set_max_call_levels(32);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(16 << 20);
+    engine.set_max_array_size(100_000);
+    engine.set_max_map_size(10_000);
+    engine.on_print(|text| info!("script: {}", text));
+    engine.on_debug(|text, _, _| debug!("script: {}", text));
+    engine
+}
+
+// Check a script's syntax
+pub fn compile(script: &str) -> Result<(), String> {
+    engine()
+        .compile(script)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Variables every script sees
+fn scope(config: &ScriptingConfig, manager: &str) -> Scope<'static> {
+    let env: Map = config
+        .env
+        .iter()
+        .filter_map(|name| {
+            let value = std::env::var(name).ok()?;
+            Some((name.as_str().into(), Dynamic::from(value)))
+        })
+        .collect();
+    let mut scope = Scope::new();
+    scope.push_constant("os", std::env::consts::OS);
+    scope.push_constant("arch", std::env::consts::ARCH);
+    scope.push_constant("hostname", metadata::hostname().unwrap_or_default());
+    scope.push_constant("manager", manager.to_string());
+    scope.push_constant("env", env);
+    scope
+}
+
+fn eval(engine: &Engine, scope: &mut Scope, script: &str) -> Result<Dynamic, String> {
+    let ast: AST = engine.compile(script).map_err(|e| e.to_string())?;
+    engine
+        .eval_ast_with_scope::<Dynamic>(scope, &ast)
+        .map_err(|e| e.to_string())
+}
+
+// Evaluate a `when_script`, which must return true or false
+pub fn when(config: &ScriptingConfig, manager: &str, script: &str) -> Result<bool, String> {
+    let value = eval(&engine(), &mut scope(config, manager), script)?;
+    value
+        .as_bool()
+        .map_err(|kind| format!("expected true or false, got {}", kind))
+}
+
+// Evaluate a `transform_output` on captured output. A list of lines is
+// joined back into text.
+pub fn transform(
+    config: &ScriptingConfig,
+    manager: &str,
+    script: &str,
+    output: &str,
+) -> Result<String, String> {
+    let mut scope = scope(config, manager);
+    scope.push_constant("output", output.to_string());
+    let value = eval(&engine(), &mut scope, script)?;
+    if value.is_string() {
+        return Ok(value.into_string().expect("checked string"));
+    }
+    let kind = value.type_name();
+    let lines = value
+        .try_cast::<rhai::Array>()
+        .ok_or_else(|| format!("expected a string or a list of lines, got {}", kind))?;
+    lines
+        .into_iter()
+        .map(|line| {
+            let kind = line.type_name();
+            line.into_string()
+                .map(|line| line + "\n")
+                .map_err(|_| format!("expected lines to be strings, got {}", kind))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_when() {
+        let config = ScriptingConfig {
+            env: vec!["PATH".to_string()],
+        };
+        let os = std::env::consts::OS;
+        assert!(when(&config, "apt", &format!(r#"os == "{}""#, os)).unwrap());
+        assert!(!when(&config, "apt", r#"manager != "apt""#).unwrap());
+        assert!(when(&config, "apt", r#""PATH" in env && !("HOME" in env)"#).unwrap());
+        assert!(when(&config, "apt", "42")
+            .unwrap_err()
+            .contains("expected true or false"));
+        assert!(when(&config, "apt", "os ==").is_err());
+    }
+
+    #[test]
+    fn test_transform() {
+        let config = ScriptingConfig::default();
+        let output = "Listing...\ngit/stable 2.41\ncurl/stable 8.1\n";
+        assert_eq!(
+            transform(
+                &config,
+                "apt",
+                r#"output.split("\n").filter(|l| l != "" && !l.starts_with("Listing"))"#,
+                output
+            )
+            .unwrap(),
+            "git/stable 2.41\ncurl/stable 8.1\n"
+        );
+        assert_eq!(
+            transform(&config, "apt", "output.to_upper()", "git\n").unwrap(),
+            "GIT\n"
+        );
+        assert!(transform(&config, "apt", "[1, 2]", output).is_err());
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let config = ScriptingConfig::default();
+        assert!(when(&config, "x", "loop {}")
+            .unwrap_err()
+            .contains("Too many operations"));
+        assert!(when(&config, "x", r#"import "other" as o; true"#).is_err());
+        assert!(compile(r#"eval("true")"#).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use std::ffi::OsString;
+use std::io;
+use std::time::Duration;
+
+// systemd's notification protocol (sd_notify), for running the daemon as a
+// Type=notify service with a watchdog:
+//
+//   [Service]
+//   Type=notify
+//   ExecStart=/usr/local/bin/updog daemon
+//   WatchdogSec=2min
+//
+// systemd passes the socket in NOTIFY_SOCKET and the watchdog timeout in
+// WATCHDOG_USEC. Outside systemd there's no socket and nothing is sent.
+#[derive(Debug)]
+pub struct SystemdNotifier {
+    #[cfg(unix)]
+    socket: std::os::unix::net::UnixDatagram,
+    #[cfg(unix)]
+    address: std::os::unix::net::SocketAddr,
+    // How often to ping the watchdog: half its timeout, as systemd suggests
+    watchdog: Option<Duration>,
+}
+
+impl SystemdNotifier {
+    // Notifier for the socket systemd gave this process, if any
+    pub fn from_env() -> Option<Self> {
+        Self::from_vars(
+            std::env::var_os("NOTIFY_SOCKET"),
+            std::env::var("WATCHDOG_USEC").ok().as_deref(),
+            std::env::var("WATCHDOG_PID").ok().as_deref(),
+        )
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn from_vars(
+        socket: Option<OsString>,
+        watchdog_usec: Option<&str>,
+        watchdog_pid: Option<&str>,
+    ) -> Option<Self> {
+        use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+        let socket_path = socket.filter(|s| !s.is_empty())?;
+        let address = match socket_path.to_str().and_then(|s| s.strip_prefix('@')) {
+            Some(name) => abstract_address(name)?,
+            None => SocketAddr::from_pathname(&socket_path).ok()?,
+        };
+        // The watchdog may be meant for another process (e.g. a wrapper)
+        let for_us = watchdog_pid.is_none_or(|pid| pid.trim() == std::process::id().to_string());
+        let watchdog = watchdog_usec
+            .and_then(|usec| usec.trim().parse::<u64>().ok())
+            .filter(|&usec| usec > 0 && for_us)
+            .map(|usec| Duration::from_micros(usec) / 2);
+        Some(Self {
+            socket: UnixDatagram::unbound().ok()?,
+            address,
+            watchdog,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn from_vars(
+        _socket: Option<OsString>,
+        _watchdog_usec: Option<&str>,
+        _watchdog_pid: Option<&str>,
+    ) -> Option<Self> {
+        None
+    }
+
+    // Interval to ping the watchdog at, if systemd supervises us
+    pub fn watchdog_interval(&self) -> Option<Duration> {
+        self.watchdog
+    }
+
+    // Send newline-separated VARIABLE=value assignments
+    #[cfg(unix)]
+    pub fn notify(&self, state: &str) -> io::Result<()> {
+        self.socket
+            .send_to_addr(state.as_bytes(), &self.address)
+            .map(|_| ())
+    }
+
+    #[cfg(not(unix))]
+    pub fn notify(&self, _state: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn ready(&self, status: &str) -> io::Result<()> {
+        self.notify(&format!("READY=1\nSTATUS={}", one_line(status)))
+    }
+
+    // Free-form text shown by `systemctl status`
+    pub fn status(&self, status: &str) -> io::Result<()> {
+        self.notify(&format!("STATUS={}", one_line(status)))
+    }
+
+    pub fn watchdog(&self) -> io::Result<()> {
+        self.notify("WATCHDOG=1")
+    }
+
+    pub fn stopping(&self) -> io::Result<()> {
+        self.notify("STOPPING=1")
+    }
+}
+
+fn one_line(text: &str) -> String {
+    text.replace('\n', " ")
+}
+
+#[cfg(target_os = "linux")]
+fn abstract_address(name: &str) -> Option<std::os::unix::net::SocketAddr> {
+    use std::os::linux::net::SocketAddrExt;
+    std::os::unix::net::SocketAddr::from_abstract_name(name).ok()
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn abstract_address(_name: &str) -> Option<std::os::unix::net::SocketAddr> {
+    None
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixDatagram;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_notify_over_socket() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let receiver = UnixDatagram::bind(&path).unwrap();
+        let notifier =
+            SystemdNotifier::from_vars(Some(path.clone().into()), Some("120000000"), None).unwrap();
+        assert_eq!(notifier.watchdog_interval(), Some(Duration::from_secs(60)));
+
+        notifier.ready("Idle\nwaiting").unwrap();
+        notifier.watchdog().unwrap();
+        let mut buffer = [0u8; 256];
+        let n = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..n], b"READY=1\nSTATUS=Idle waiting");
+        let n = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..n], b"WATCHDOG=1");
+    }
+
+    #[test]
+    fn test_from_vars() {
+        assert!(SystemdNotifier::from_vars(None, Some("1000"), None).is_none());
+        assert!(SystemdNotifier::from_vars(Some("".into()), None, None).is_none());
+
+        // A watchdog for another process isn't ours to ping
+        let notifier =
+            SystemdNotifier::from_vars(Some("/run/notify".into()), Some("1000"), Some("1"))
+                .unwrap();
+        assert_eq!(notifier.watchdog_interval(), None);
+        let pid = std::process::id().to_string();
+        let notifier =
+            SystemdNotifier::from_vars(Some("/run/notify".into()), Some("1000"), Some(&pid))
+                .unwrap();
+        assert_eq!(
+            notifier.watchdog_interval(),
+            Some(Duration::from_micros(500))
+        );
+    }
+}
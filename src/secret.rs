@@ -0,0 +1,136 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::executor::{CommandRequest, Executor};
+use crate::package_manager::UpdateError;
+
+// Prefix marking a config value that is resolved at runtime
+pub const SECRET_PREFIX: &str = "secret://";
+
+// Where a secret value comes from
+//
+//   env:
+//     NPM_TOKEN: secret://env/CI_NPM_TOKEN           # environment variable
+//     GH_TOKEN: secret://file/~/.config/gh/token     # file contents
+//     REGISTRY_TOKEN: secret://cmd/pass show npm/registry  # command stdout
+#[derive(Debug, Clone, PartialEq)]
+pub enum SecretRef {
+    Env(String),
+    File(PathBuf),
+    Command(String),
+}
+
+impl SecretRef {
+    // Parse a `secret://` reference. Returns None for plain values.
+    pub fn parse(value: &str) -> Option<Result<Self, String>> {
+        let reference = value.strip_prefix(SECRET_PREFIX)?;
+        let (source, target) = reference.split_once('/').unwrap_or((reference, ""));
+        if target.trim().is_empty() {
+            return Some(Err(format!("missing target in '{}'", value)));
+        }
+
+        Some(match source {
+            "env" => Ok(SecretRef::Env(target.to_string())),
+            "file" => Ok(SecretRef::File(expand_home(target))),
+            "cmd" => Ok(SecretRef::Command(target.to_string())),
+            _ => Err(format!(
+                "unknown secret source '{}' (expected env, file or cmd)",
+                source
+            )),
+        })
+    }
+
+    // Fetch the secret value. Error messages never contain the value itself.
+    pub fn resolve(&self, executor: &Arc<dyn Executor>) -> Result<String, UpdateError> {
+        let value = match self {
+            SecretRef::Env(name) => std::env::var(name).map_err(|_| {
+                UpdateError::new(format!("environment variable {} is not set", name))
+            })?,
+            SecretRef::File(path) => fs::read_to_string(path).map_err(|e| {
+                UpdateError::new(format!("failed to read {}: {}", path.display(), e))
+            })?,
+            SecretRef::Command(command) => {
+                let output = executor.run(&CommandRequest::captured(command))?;
+                if !output.success() {
+                    return Err(UpdateError::new(format!(
+                        "'{}' failed with {}",
+                        command,
+                        output.status_description()
+                    )));
+                }
+                output.stdout
+            }
+        };
+
+        // Files and command output usually end with a newline
+        Ok(value.trim_end_matches(['\r', '\n']).to_string())
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+    use tempfile::tempdir;
+
+    fn executor() -> Arc<dyn Executor> {
+        Arc::new(ScriptedExecutor::new(Arc::new(FakeClock::new())))
+    }
+
+    #[test]
+    fn test_parse_references() {
+        assert_eq!(SecretRef::parse("plain value"), None);
+        assert_eq!(
+            SecretRef::parse("secret://env/NPM_TOKEN"),
+            Some(Ok(SecretRef::Env("NPM_TOKEN".to_string())))
+        );
+        assert_eq!(
+            SecretRef::parse("secret://cmd/pass show npm/token"),
+            Some(Ok(SecretRef::Command("pass show npm/token".to_string())))
+        );
+        assert!(matches!(SecretRef::parse("secret://vault/x"), Some(Err(_))));
+        assert!(matches!(SecretRef::parse("secret://env/"), Some(Err(_))));
+    }
+
+    #[test]
+    fn test_resolve_file_and_command() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("token");
+        fs::write(&path, "file-token\n").unwrap();
+        let file_ref = SecretRef::parse(&format!("secret://file/{}", path.display()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(file_ref.resolve(&executor()).unwrap(), "file-token");
+
+        let clock = Arc::new(FakeClock::new());
+        let scripted = Arc::new(ScriptedExecutor::new(clock));
+        scripted
+            .script(
+                "pass show npm",
+                ScriptedCommand::success().stdout("cmd-token\n"),
+            )
+            .script("pass show broken", ScriptedCommand::exit(1));
+        let scripted: Arc<dyn Executor> = scripted;
+
+        let cmd_ref = SecretRef::Command("pass show npm".to_string());
+        assert_eq!(cmd_ref.resolve(&scripted).unwrap(), "cmd-token");
+
+        let broken = SecretRef::Command("pass show broken".to_string());
+        assert!(broken.resolve(&scripted).is_err());
+    }
+
+    #[test]
+    fn test_missing_env_error_omits_value() {
+        let reference = SecretRef::Env("UPDOG_TEST_SURELY_UNSET".to_string());
+        let err = reference.resolve(&executor()).unwrap_err();
+        assert!(err.message.contains("UPDOG_TEST_SURELY_UNSET is not set"));
+    }
+}
@@ -0,0 +1,502 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use crate::config::{Config, ConfigError};
+use crate::warnings;
+
+// Which package managers a command applies to. Selectors are
+//
+//   brew            a manager (with its default subcommand)
+//   brew:cask       a manager's subcommand
+//   brew:*          a manager's subcommands matching a glob
+//   essentials      a group, expanded to its members
+//   @dev            every manager tagged `dev`
+//   docker*         managers whose id matches a glob (`*` and `?`)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Selection {
+    // Nothing included means every manager
+    pub include: Vec<String>,
+    pub except: Vec<String>,
+    // Resolve to nothing instead of failing when nothing matches
+    pub allow_empty: bool,
+}
+
+impl Selection {
+    pub fn is_everything(&self) -> bool {
+        self.include.is_empty() && self.except.is_empty()
+    }
+
+    // The (manager, subcommand) pairs to run, in order. Fails when nothing
+    // is left to run, unless `allow_empty` is set: an empty run is most
+    // likely a typo'd tag or glob, and a scheduled run would hide it.
+    pub fn resolve(&self, config: &Config) -> Result<Vec<(String, Option<String>)>, ConfigError> {
+        let mut selected: Vec<(String, Option<String>)> = Vec::new();
+        if self.include.is_empty() {
+            selected.extend(config.managers().map(|m| (m.id.clone(), None)));
+        }
+        let mut unmatched: Vec<&str> = Vec::new();
+        for selector in &self.include {
+            let items = matching(config, selector, false)?;
+            if items.is_empty() {
+                unmatched.push(selector);
+            }
+            for item in items {
+                if !selected.contains(&item) {
+                    selected.push(item);
+                }
+            }
+        }
+
+        for selector in &self.except {
+            let excluded = matching(config, selector, true)?;
+            // Excluding a manager excludes all of its subcommands
+            selected.retain(|(manager, subcommand)| {
+                !excluded.iter().any(|(m, sc)| {
+                    m == manager && (sc.is_none() || sc.as_ref() == subcommand.as_ref())
+                })
+            });
+        }
+
+        if !selected.is_empty() || self.allow_empty {
+            if !unmatched.is_empty() {
+                warnings::warn(format_args!("Nothing matched: {}", unmatched.join(", ")));
+            }
+            return Ok(selected);
+        }
+        let reason = if !unmatched.is_empty() {
+            format!("nothing matched {}", unmatched.join(", "))
+        } else if self.include.is_empty() && config.managers().next().is_none() {
+            "no package managers are configured".to_string()
+        } else {
+            "everything selected was left out by --except".to_string()
+        };
+        Err(ConfigError::ValidationError(format!(
+            "no package managers selected: {} (use --allow-empty to run anyway)",
+            reason
+        )))
+    }
+
+    // Take the `manual` managers out of resolved `items`, except the ones an
+    // include selector names (`fwupd` or `fwupd:sub`, not a tag, glob or
+    // group). Returns the names left out.
+    pub fn leave_out_manual(
+        &self,
+        config: &Config,
+        items: &mut Vec<(String, Option<String>)>,
+    ) -> Vec<String> {
+        let named = |id: &str| {
+            self.include.iter().any(|selector| {
+                !selector.contains(['*', '?']) && selector.split(':').next() == Some(id)
+            })
+        };
+        let mut left_out = Vec::new();
+        items.retain(|(manager, subcommand)| {
+            let manual = config
+                .find_package_manager(manager)
+                .is_some_and(|m| m.manual && !named(&m.id));
+            if manual {
+                left_out.push(match subcommand {
+                    Some(sc) => format!("{}:{}", manager, sc),
+                    None => manager.clone(),
+                });
+            }
+            !manual
+        });
+        left_out
+    }
+}
+
+// Order to run the selected package managers in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExecutionOrder {
+    // As listed in the config (or on the command line)
+    #[default]
+    Config,
+    Alphabetical,
+    // Shortest median duration in history first, so quick wins finish early
+    Shortest,
+    Longest,
+}
+
+impl std::str::FromStr for ExecutionOrder {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "config" => Ok(ExecutionOrder::Config),
+            "alphabetical" => Ok(ExecutionOrder::Alphabetical),
+            "shortest" | "duration" => Ok(ExecutionOrder::Shortest),
+            "longest" => Ok(ExecutionOrder::Longest),
+            _ => Err(format!(
+                "unknown order '{}' (expected config, alphabetical, shortest or longest)",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ExecutionOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ExecutionOrder::Config => "config",
+            ExecutionOrder::Alphabetical => "alphabetical",
+            ExecutionOrder::Shortest => "shortest",
+            ExecutionOrder::Longest => "longest",
+        })
+    }
+}
+
+impl ExecutionOrder {
+    // Whether sorting needs durations from history
+    pub fn uses_durations(&self) -> bool {
+        matches!(self, ExecutionOrder::Shortest | ExecutionOrder::Longest)
+    }
+
+    // Reorder resolved (manager, subcommand) pairs. `durations` maps
+    // "manager[:subcommand]" to a typical duration; managers without one run
+    // last, in their original order.
+    pub fn sort(
+        &self,
+        items: &mut [(String, Option<String>)],
+        durations: &BTreeMap<String, Duration>,
+    ) {
+        let name = |(manager, subcommand): &(String, Option<String>)| match subcommand {
+            Some(sc) => format!("{}:{}", manager, sc),
+            None => manager.clone(),
+        };
+        match self {
+            ExecutionOrder::Config => {}
+            ExecutionOrder::Alphabetical => items.sort_by_key(name),
+            ExecutionOrder::Shortest | ExecutionOrder::Longest => {
+                let longest = *self == ExecutionOrder::Longest;
+                items.sort_by_key(|item| match durations.get(&name(item)) {
+                    Some(&duration) if longest => (0, Duration::MAX - duration),
+                    Some(&duration) => (0, duration),
+                    None => (1, Duration::ZERO),
+                });
+            }
+        }
+    }
+}
+
+// Everything a single selector refers to. Unknown plain names are passed
+// through when including (and reported when run) but rejected when excluding,
+// where a typo would silently exclude nothing, as are unused tags there.
+fn matching(
+    config: &Config,
+    selector: &str,
+    excluding: bool,
+) -> Result<Vec<(String, Option<String>)>, ConfigError> {
+    if let Some(tag) = selector.strip_prefix('@') {
+        let tagged: Vec<_> = config
+            .managers()
+            .filter(|m| m.tags.iter().any(|t| t == tag))
+            .map(|m| (m.id.clone(), None))
+            .collect();
+        if excluding && tagged.is_empty() {
+            return Err(ConfigError::ValidationError(format!(
+                "no package manager is tagged '{}'",
+                tag
+            )));
+        }
+        return Ok(tagged);
+    }
+
+    if let Some((id, pattern)) = selector
+        .split_once(':')
+        .filter(|(_, pattern)| pattern.contains(['*', '?']))
+    {
+        let Some(manager) = config.find_package_manager(id) else {
+            if excluding {
+                return Err(ConfigError::ValidationError(format!(
+                    "unknown package manager or group in --except: {}",
+                    selector
+                )));
+            }
+            return Ok(Vec::new());
+        };
+        return Ok(manager
+            .subcommands
+            .iter()
+            .filter(|sc| glob_match(pattern, &sc.id))
+            .map(|sc| (manager.id.clone(), Some(sc.id.clone())))
+            .collect());
+    }
+
+    if selector.contains(['*', '?']) {
+        return Ok(config
+            .managers()
+            .filter(|m| glob_match(selector, &m.id))
+            .map(|m| (m.id.clone(), None))
+            .collect());
+    }
+
+    let id = selector.split(':').next().unwrap_or(selector);
+    if excluding && config.find_package_manager(id).is_none() {
+        return Err(ConfigError::ValidationError(format!(
+            "unknown package manager or group in --except: {}",
+            selector
+        )));
+    }
+    config.expand(selector)
+}
+
+// Shell-style wildcard match: `*` is any run of characters, `?` any one
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` and the text position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        let yaml = r#"
+        commands:
+          - id: apt
+            update: apt upgrade
+            tags: [system]
+          - id: flatpak
+            update: flatpak update
+            tags: [system]
+          - id: docker-images
+            check: docker images
+            tags: [dev]
+          - id: docker-compose
+            update: docker compose pull
+            tags: [dev]
+          - id: npm
+            subcommands:
+              - id: global
+                update: npm update -g
+              - id: local
+                update: npm update
+            tags: [dev]
+          - id: desktop
+            type: group
+            members: [flatpak, npm]
+        "#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        config
+    }
+
+    fn resolve(include: &[&str], except: &[&str]) -> Vec<String> {
+        let selection = Selection {
+            include: include.iter().map(|s| s.to_string()).collect(),
+            except: except.iter().map(|s| s.to_string()).collect(),
+            allow_empty: true,
+        };
+        selection
+            .resolve(&config())
+            .unwrap()
+            .into_iter()
+            .map(|(m, sc)| match sc {
+                Some(sc) => format!("{}:{}", m, sc),
+                None => m,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_include_and_except() {
+        assert_eq!(resolve(&[], &[]).len(), 5);
+        assert_eq!(resolve(&["@system"], &["apt"]), vec!["flatpak"]);
+        assert_eq!(resolve(&["@dev"], &["docker*"]), vec!["npm"]);
+        assert_eq!(resolve(&[], &["@dev"]), vec!["apt", "flatpak"]);
+        assert_eq!(
+            resolve(&["desktop", "npm:local"], &["npm:global"]),
+            vec!["flatpak", "npm", "npm:local"]
+        );
+        assert_eq!(
+            resolve(&["npm:global", "npm:local"], &["npm"]),
+            Vec::<String>::new()
+        );
+        assert_eq!(resolve(&["npm:*"], &[]), vec!["npm:global", "npm:local"]);
+        assert_eq!(resolve(&["npm:*"], &["npm:l*"]), vec!["npm:global"]);
+        assert_eq!(resolve(&["apt:*", "yarn:*"], &[]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_manual_managers_need_naming() {
+        let mut config = config();
+        for id in ["apt", "npm"] {
+            config
+                .commands
+                .iter_mut()
+                .find(|m| m.id == id)
+                .unwrap()
+                .manual = true;
+        }
+        let left_out = |include: &[&str]| {
+            let selection = Selection {
+                include: include.iter().map(|s| s.to_string()).collect(),
+                ..Default::default()
+            };
+            let mut items = selection.resolve(&config).unwrap();
+            let left_out = selection.leave_out_manual(&config, &mut items);
+            let kept: Vec<String> = items.into_iter().map(|(m, _)| m).collect();
+            (kept, left_out)
+        };
+        assert_eq!(
+            left_out(&[]),
+            (
+                vec![
+                    "flatpak".to_string(),
+                    "docker-images".to_string(),
+                    "docker-compose".to_string()
+                ],
+                vec!["apt".to_string(), "npm".to_string()]
+            )
+        );
+        assert_eq!(left_out(&["@system"]).1, ["apt"]);
+        assert_eq!(
+            left_out(&["desktop", "npm:*"]).1,
+            ["npm", "npm:global", "npm:local"]
+        );
+        assert_eq!(left_out(&["apt", "npm:local"]).1, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_invalid_selectors() {
+        let selection = |include: &str, except: &str| Selection {
+            include: vec![include.to_string()],
+            except: vec![except.to_string()],
+            ..Default::default()
+        };
+        assert!(selection("@nothing", "apt").resolve(&config()).is_err());
+        let err = selection("@system", "atp").resolve(&config()).unwrap_err();
+        assert!(err.to_string().contains("--except: atp"));
+        // Unmatched globs just exclude nothing
+        assert!(selection("apt", "zypper*").resolve(&config()).is_ok());
+    }
+
+    #[test]
+    fn test_empty_selection() {
+        let selection = |include: &[&str], except: &[&str]| Selection {
+            include: include.iter().map(|s| s.to_string()).collect(),
+            except: except.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        let err = selection(&["@sytem", "zypper*"], &[])
+            .resolve(&config())
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("nothing matched @sytem, zypper*"),
+            "{}",
+            err
+        );
+        let err = selection(&["npm:global"], &["npm"])
+            .resolve(&config())
+            .unwrap_err();
+        assert!(err.to_string().contains("left out by --except"), "{}", err);
+        let err = selection(&[], &[])
+            .resolve(&Config {
+                commands: Vec::new(),
+                ..Default::default()
+            })
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("no package managers are configured"));
+
+        // Anything else matching is enough
+        assert_eq!(
+            selection(&["@sytem", "apt"], &[])
+                .resolve(&config())
+                .unwrap(),
+            vec![("apt".to_string(), None)]
+        );
+        let allowed = Selection {
+            allow_empty: true,
+            ..selection(&["@sytem"], &[])
+        };
+        assert!(allowed.resolve(&config()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_execution_order() {
+        let items = || {
+            vec![
+                ("npm".to_string(), Some("global".to_string())),
+                ("apt".to_string(), None),
+                ("brew".to_string(), None),
+                ("flatpak".to_string(), None),
+            ]
+        };
+        let names = |items: Vec<(String, Option<String>)>| -> Vec<String> {
+            items.into_iter().map(|(m, _)| m).collect()
+        };
+        let durations: BTreeMap<String, Duration> = [
+            ("npm:global", 5),
+            ("apt", 60),
+            ("flatpak", 20),
+            // A default subcommand's record doesn't match a subcommand
+            ("brew:cask", 1),
+        ]
+        .into_iter()
+        .map(|(name, secs)| (name.to_string(), Duration::from_secs(secs)))
+        .collect();
+
+        let sorted = |order: ExecutionOrder| {
+            let mut items = items();
+            order.sort(&mut items, &durations);
+            names(items)
+        };
+        assert_eq!(
+            sorted(ExecutionOrder::Config),
+            ["npm", "apt", "brew", "flatpak"]
+        );
+        assert_eq!(
+            sorted(ExecutionOrder::Alphabetical),
+            ["apt", "brew", "flatpak", "npm"]
+        );
+        assert_eq!(
+            sorted(ExecutionOrder::Shortest),
+            ["npm", "flatpak", "apt", "brew"]
+        );
+        assert_eq!(
+            sorted(ExecutionOrder::Longest),
+            ["apt", "flatpak", "npm", "brew"]
+        );
+        assert_eq!("duration".parse(), Ok(ExecutionOrder::Shortest));
+        assert!("random".parse::<ExecutionOrder>().is_err());
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("docker*", "docker-images"));
+        assert!(glob_match("*-images", "docker-images"));
+        assert!(glob_match("n?m", "npm"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("docker*", "podman"));
+        assert!(!glob_match("a*b", "acbd"));
+    }
+}
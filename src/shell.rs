@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::process::Command;
+
+use crate::lint::binary_exists;
+
+// Shell the configured commands run in (`shell:` at the top of the config)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Shell {
+    Bash,
+    Sh,
+    // cmd.exe, for configs written for it
+    Cmd,
+    // Windows PowerShell 5.1, installed with Windows
+    Powershell,
+    // PowerShell 7
+    Pwsh,
+}
+
+impl fmt::Display for Shell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.program())
+    }
+}
+
+// Run before a PowerShell command: a failing cmdlet fails the command, as
+// a failing program does through the exit code appended after it, and
+// progress bars don't end up in captured output
+const POWERSHELL_PRELUDE: &str =
+    "$ErrorActionPreference = 'Stop'\n$ProgressPreference = 'SilentlyContinue'\n";
+const POWERSHELL_EPILOGUE: &str = "\nif ($LASTEXITCODE) { exit $LASTEXITCODE }\n";
+
+impl Shell {
+    // bash, or on Windows PowerShell 7 when it's installed and Windows
+    // PowerShell otherwise
+    pub fn platform_default() -> Self {
+        if !cfg!(windows) {
+            Shell::Bash
+        } else if binary_exists("pwsh") {
+            Shell::Pwsh
+        } else {
+            Shell::Powershell
+        }
+    }
+
+    pub fn program(&self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Sh => "sh",
+            Shell::Cmd => "cmd",
+            Shell::Powershell => "powershell",
+            Shell::Pwsh => "pwsh",
+        }
+    }
+
+    // The process running `command`. PowerShell gets it base64-encoded, so
+    // that no quote, space or pipe in it is reinterpreted on the way, and
+    // cmd gets it verbatim, as cmd doesn't follow the quoting rules other
+    // programs' arguments are encoded with.
+    pub fn command(&self, command: &str) -> Command {
+        let mut process = Command::new(self.program());
+        match self {
+            Shell::Bash | Shell::Sh => {
+                process.arg("-c").arg(command);
+            }
+            Shell::Cmd => cmd_args(&mut process, command),
+            Shell::Powershell | Shell::Pwsh => {
+                process.args(powershell_args(command));
+            }
+        }
+        process
+    }
+}
+
+// `/s /c "<command>"`: with /s, cmd drops the outer quotes and runs the rest
+// as typed
+fn cmd_line(command: &str) -> String {
+    format!("/d /s /c \"{}\"", command)
+}
+
+#[cfg(windows)]
+fn cmd_args(process: &mut Command, command: &str) {
+    use std::os::windows::process::CommandExt;
+    process.raw_arg(cmd_line(command));
+}
+
+// No cmd to run outside Windows; spawning it fails with "not found"
+#[cfg(not(windows))]
+fn cmd_args(process: &mut Command, command: &str) {
+    process.arg(cmd_line(command));
+}
+
+fn powershell_args(command: &str) -> Vec<String> {
+    let script = format!("{}{}{}", POWERSHELL_PRELUDE, command, POWERSHELL_EPILOGUE);
+    let utf16: Vec<u8> = script.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    [
+        "-NoLogo",
+        "-NoProfile",
+        "-NonInteractive",
+        "-ExecutionPolicy",
+        "Bypass",
+        "-EncodedCommand",
+    ]
+    .into_iter()
+    .map(str::to_string)
+    .chain([base64(&utf16)])
+    .collect()
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => out.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What PowerShell runs for `-EncodedCommand <encoded>`
+    fn decode(encoded: &str) -> String {
+        let mut bits = 0u32;
+        let mut count = 0;
+        let mut bytes = Vec::new();
+        for c in encoded.bytes().filter(|&c| c != b'=') {
+            let value = BASE64.iter().position(|&b| b == c).unwrap() as u32;
+            bits = bits << 6 | value;
+            count += 6;
+            if count >= 8 {
+                count -= 8;
+                bytes.push((bits >> count) as u8);
+                bits &= (1 << count) - 1;
+            }
+        }
+        let utf16: Vec<u16> = bytes
+            .chunks(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16(&utf16).unwrap()
+    }
+
+    fn args(command: &Command) -> Vec<String> {
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar!"), "Zm9vYmFyIQ==");
+    }
+
+    #[test]
+    fn test_powershell_commands_arrive_intact() {
+        let commands = [
+            r#"winget upgrade --all --accept-source-agreements"#,
+            r#"Get-ChildItem "C:\Program Files" | Where-Object { $_.Name -like 'Win*' }"#,
+            r#"echo "it's ""quoted""" 'and ''this''' | Select-String -Pattern "a|b""#,
+            "choco upgrade all -y\nscoop update *",
+            r#"& "C:\Tools\my tool.exe" --flag="x y" ; exit 3"#,
+        ];
+        for shell in [Shell::Powershell, Shell::Pwsh] {
+            for command in commands {
+                let process = shell.command(command);
+                assert_eq!(process.get_program(), shell.program());
+                let args = args(&process);
+                assert_eq!(
+                    args[..6],
+                    [
+                        "-NoLogo",
+                        "-NoProfile",
+                        "-NonInteractive",
+                        "-ExecutionPolicy",
+                        "Bypass",
+                        "-EncodedCommand"
+                    ]
+                );
+                assert_eq!(args.len(), 7);
+                assert!(!args[6].contains([' ', '"', '\'', '|']));
+                assert_eq!(
+                    decode(&args[6]),
+                    format!("{}{}{}", POWERSHELL_PRELUDE, command, POWERSHELL_EPILOGUE)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_cmd_gets_the_command_verbatim() {
+        assert_eq!(
+            cmd_line(r#"dir "C:\Program Files" | findstr /i "win""#),
+            r#"/d /s /c "dir "C:\Program Files" | findstr /i "win"""#
+        );
+        assert_eq!(cmd_line("echo a&&echo b"), r#"/d /s /c "echo a&&echo b""#);
+    }
+
+    #[test]
+    fn test_posix_shells() {
+        let process = Shell::Sh.command("echo 'a b' | tr a c");
+        assert_eq!(process.get_program(), "sh");
+        assert_eq!(args(&process), ["-c", "echo 'a b' | tr a c"]);
+        assert_eq!(serde_yaml::from_str::<Shell>("pwsh").unwrap(), Shell::Pwsh);
+        if !cfg!(windows) {
+            assert_eq!(Shell::platform_default(), Shell::Bash);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+// `updog generate shell-hook <shell>` prints a snippet to eval from the
+// shell's rc file:
+//
+//   eval "$(updog generate shell-hook zsh --precmd)"
+//
+// It defines a function (`up` by default) running `updog update` with the
+// given arguments, completed like `updog update` itself. With --precmd, the
+// first prompt of each day also shows how many updates are pending, from
+// what the recorded runs found (no check is run). A --config given to
+// `updog generate` is passed on to every updog the snippet runs.
+
+use std::path::Path;
+
+use crate::step::shell_quote;
+
+pub const SHELLS: &[&str] = &["bash", "zsh"];
+
+// Function names that can't be taken for something else by accident
+pub fn parse_name(name: &str) -> Result<String, String> {
+    let valid = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    match valid {
+        true => Ok(name.to_string()),
+        false => Err(format!(
+            "'{}' isn't a valid function name (letters, digits, _ and -)",
+            name
+        )),
+    }
+}
+
+// The snippet for `shell` (one of SHELLS), defining the function `name`
+pub fn script(shell: &str, name: &str, precmd: bool, config: Option<&Path>) -> String {
+    let updog = match config {
+        Some(path) => format!(
+            "command updog --config {}",
+            shell_quote(&path.to_string_lossy())
+        ),
+        None => "command updog".to_string(),
+    };
+    let mut out = format!(
+        "# updog shell hook, generated by `updog generate shell-hook {}`\n\n\
+         {}() {{\n    {} update \"$@\"\n}}\n\n",
+        shell, name, updog
+    );
+    let function = format!("_updog_{}", name.replace('-', "_"));
+    match shell {
+        "zsh" => {
+            out.push_str(&zsh_completion(name, &function));
+            if precmd {
+                out.push_str(&zsh_precmd(name, &updog));
+            }
+        }
+        _ => {
+            out.push_str(&bash_completion(name, &function));
+            if precmd {
+                out.push_str(&bash_precmd(name, &updog));
+            }
+        }
+    }
+    out
+}
+
+// Complete the function's arguments as those of `updog update`, with the
+// completion script of `updog completions` loaded when it isn't yet
+fn bash_completion(name: &str, function: &str) -> String {
+    format!(
+        r#"{function}() {{
+    declare -F _updog >/dev/null || source <(command updog completions bash)
+    COMP_LINE="updog update${{COMP_LINE#"$1"}}"
+    ((COMP_POINT += 12 - ${{#1}}))
+    COMP_WORDS=(updog update "${{COMP_WORDS[@]:1}}")
+    ((COMP_CWORD++))
+    _updog updog "$2" "$3"
+}}
+complete -o default -F {function} {name}
+"#
+    )
+}
+
+fn zsh_completion(name: &str, function: &str) -> String {
+    format!(
+        r#"{function}() {{
+    (( $+functions[_updog] )) || source <(command updog completions zsh)
+    words=(updog update "${{(@)words[2,-1]}}")
+    (( CURRENT++ ))
+    _updog
+}}
+(( $+functions[compdef] )) && compdef {function} {name}
+"#
+    )
+}
+
+// Shared by both shells: print the pending count once `today` differs
+// from the day in the stamp file
+fn notice(name: &str, updog: &str) -> String {
+    format!(
+        r#"    local stamp=${{XDG_CACHE_HOME:-$HOME/.cache}}/updog/shell-hook-day pending count
+    [[ -r $stamp && $(<"$stamp") == "$today" ]] && return
+    mkdir -p "${{stamp%/*}}" && printf '%s\n' "$today" >"$stamp"
+    pending=$({updog} status --format polybar 2>/dev/null) || return
+    count=${{pending%% *}}
+    [[ $pending == "0" ]] && return
+    printf 'updog: %s pending updates%s; run %s to update\n' "$count" "${{pending#"$count"}}" '{name}'
+"#
+    )
+}
+
+fn bash_precmd(name: &str, updog: &str) -> String {
+    format!(
+        r#"
+_updog_daily_notice() {{
+    local today
+    printf -v today '%(%F)T' -1
+{}}}
+[[ ";${{PROMPT_COMMAND[*]}};" == *";_updog_daily_notice;"* ]] ||
+    PROMPT_COMMAND="_updog_daily_notice${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}"
+"#,
+        notice(name, updog)
+    )
+}
+
+fn zsh_precmd(name: &str, updog: &str) -> String {
+    format!(
+        r#"
+zmodload -F zsh/datetime b:strftime
+_updog_daily_notice() {{
+    local today
+    strftime -s today %F $EPOCHSECONDS
+{}}}
+autoload -Uz add-zsh-hook
+add-zsh-hook precmd _updog_daily_notice
+"#,
+        notice(name, updog)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_name() {
+        assert_eq!(parse_name("up").unwrap(), "up");
+        assert_eq!(parse_name("sys-up_2").unwrap(), "sys-up_2");
+        assert!(parse_name("").is_err());
+        assert!(parse_name("2up").is_err());
+        assert!(parse_name("up;rm").is_err());
+    }
+
+    #[test]
+    fn test_script() {
+        let bash = script("bash", "sys-up", false, None);
+        assert!(bash.contains("sys-up() {\n    command updog update \"$@\"\n}\n"));
+        assert!(bash.contains("complete -o default -F _updog_sys_up sys-up\n"));
+        assert!(!bash.contains("PROMPT_COMMAND"));
+        let bash = script("bash", "up", true, Some(Path::new("/etc/my updog.yaml")));
+        assert!(bash.contains("up() {\n    command updog --config '/etc/my updog.yaml' update"));
+        assert!(bash.contains(
+            "pending=$(command updog --config '/etc/my updog.yaml' status --format polybar"
+        ));
+        assert!(bash.contains("PROMPT_COMMAND=\"_updog_daily_notice"));
+        assert!(bash.contains("run %s to update\\n' \"$count\" \"${pending#\"$count\"}\" 'up'"));
+
+        let zsh = script("zsh", "up", true, None);
+        assert!(zsh.contains("compdef _updog_up up\n"));
+        assert!(zsh.contains("add-zsh-hook precmd _updog_daily_notice\n"));
+        assert!(!zsh.contains("PROMPT_COMMAND"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_bash_parses_the_script() {
+        let Ok(output) = std::process::Command::new("bash")
+            .arg("-n")
+            .arg("-c")
+            .arg(script("bash", "up", true, None))
+            .output()
+        else {
+            return;
+        };
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::config::{Config, Verbosity};
+use crate::proxy::ProxyConfig;
+use crate::secret::SecretRef;
+use crate::step::{shell_quote, Step};
+
+// `updog export-script`: the resolved update plan as a plain POSIX shell
+// script, for machines where updog itself can't be installed. Each manager
+// runs in a subshell with its environment, stops at its first failing step
+// like updog does, and a failure doesn't keep the others from running.
+// Built-in steps only updog can run fail their manager instead, and are
+// reported along with other settings the script ignores (when_script).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShellScript {
+    pub text: String,
+    // Parts of the plan the script can't reproduce
+    pub warnings: Vec<String>,
+}
+
+pub fn render(
+    config: &Config,
+    items: &[(String, Option<String>)],
+    verbosity: Verbosity,
+) -> ShellScript {
+    let mut text = String::from(
+        "#!/bin/sh\n\
+         # Update script exported by updog. Each package manager runs in a\n\
+         # subshell that stops at its first failing command; failures are\n\
+         # reported at the end.\n\n\
+         failed=\"\"\n",
+    );
+    let mut warnings = Vec::new();
+
+    for (manager_name, subcommand_name) in items {
+        let name = match subcommand_name {
+            Some(sc) => format!("{}:{}", manager_name, sc),
+            None => manager_name.clone(),
+        };
+        let (Some(manager), Some(subcommand)) = (
+            config.find_package_manager(manager_name),
+            config.find_subcommand(manager_name, subcommand_name.as_deref()),
+        ) else {
+            warnings.push(format!("{}: not configured", name));
+            continue;
+        };
+        let Some(update) = subcommand.command.update_for(verbosity) else {
+            text.push_str(&format!("\n# {}: no update command\n", name));
+            continue;
+        };
+
+        text.push_str(&format!("\n# {}\n", name));
+        if let Some(script) = &manager.when_script {
+            text.push_str(&format!(
+                "# (updog only runs this where `{}` holds)\n",
+                script
+            ));
+            warnings.push(format!("{}: when_script isn't evaluated", name));
+        }
+        if config.clean_env_for(Some(manager)).is_some() {
+            warnings.push(format!(
+                "{}: clean_env isn't applied, the commands inherit the shell's environment",
+                name
+            ));
+        }
+        text.push_str("(\n");
+        for (key, value) in config.locale_for(Some(manager)).env().into_iter().chain(
+            config
+                .proxy_for(Some(manager))
+                .map(ProxyConfig::env)
+                .unwrap_or_default(),
+        ) {
+            text.push_str(&format!("  export {}={}\n", key, shell_quote(&value)));
+        }
+        for (key, value) in &manager.env {
+            let value = match SecretRef::parse(value) {
+                None => shell_quote(value),
+                Some(Ok(SecretRef::Env(variable))) => format!("\"${{{}}}\"", variable),
+                Some(Ok(SecretRef::File(path))) => {
+                    format!("\"$(cat {})\"", shell_quote(&path.display().to_string()))
+                }
+                Some(Ok(SecretRef::Command(command))) => format!("\"$({})\"", command),
+                Some(Err(e)) => {
+                    warnings.push(format!("{}: env {}: {}", name, key, e));
+                    continue;
+                }
+            };
+            text.push_str(&format!("  export {}={}\n", key, value));
+        }
+
+        let verify = manager.verify.iter().flat_map(|v| v.steps());
+        // Registered outputs become shell variables
+        let mut registered: Vec<(String, String)> = Vec::new();
+        let steps: Vec<String> = update
+            .steps()
+            .into_iter()
+            .chain(verify)
+            .map(|step| match step.interpolate(&registered) {
+                Step::Register { run, register } => {
+                    registered.push((register.clone(), format!("\"${{{}}}\"", register)));
+                    format!("{}=$({})", register, run)
+                }
+                // A multi-line command keeps its own exit status as a group
+                Step::Command(command) if command.contains('\n') => {
+                    format!("{{\n{}\n  }}", command.trim_end())
+                }
+                Step::Command(command) => command,
+                step => {
+                    warnings.push(format!(
+                        "{}: built-in step '{}' can't be exported",
+                        name, step
+                    ));
+                    format!(
+                        "echo {} >&2 && false",
+                        shell_quote(&format!("{}: needs updog for: {}", name, step))
+                    )
+                }
+            })
+            .collect();
+        text.push_str(&format!("  {}\n", steps.join(" &&\n  ")));
+        text.push_str(&format!(") || failed=\"$failed {}\"\n", name));
+    }
+
+    text.push_str(
+        "\nif [ -n \"$failed\" ]; then\n  \
+         echo \"Failed:$failed\" >&2\n  \
+         exit 1\n\
+         fi\n",
+    );
+    ShellScript { text, warnings }
+}
+
+// Write the script to `path`, executable by its owner and readers
+pub fn write(path: &Path, text: &str) -> io::Result<()> {
+    fs::write(path, text)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        serde_yaml::from_str(
+            r#"
+            commands:
+              - id: homebrew
+                env:
+                  HOMEBREW_NO_ANALYTICS: "1"
+                  HOMEBREW_GITHUB_API_TOKEN: "secret://cmd/pass show github"
+                update:
+                  - brew update
+                  - brew upgrade
+                update_quiet: brew upgrade --quiet
+                verify: brew doctor
+              - id: tool
+                update:
+                  - download:
+                      url: https://example.com/tool
+                      path: /tmp/tool
+                  - install /tmp/tool ~/.local/bin
+              - id: pinned
+                update:
+                  - run: latest-version pinned
+                    register: version
+                  - install "pinned@{{version}}"
+              - id: mas
+                check: mas outdated
+              - id: npm
+                update: npm update -g
+                proxy: { https: "http://egress.lan:8080" }
+                locale: native
+            proxy:
+              no_proxy: [localhost]
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_render_script() {
+        let items = vec![("homebrew".to_string(), None), ("mas".to_string(), None)];
+        let script = render(&config(), &items, Verbosity::Normal);
+        assert!(script.warnings.is_empty(), "{:?}", script.warnings);
+        assert!(script.text.starts_with("#!/bin/sh\n"));
+        assert!(
+            script.text.contains(concat!(
+                "# homebrew\n",
+                "(\n",
+                "  export LC_ALL='C'\n",
+                "  export LANG='C'\n",
+                "  export LANGUAGE='C'\n",
+                "  export no_proxy='localhost'\n",
+                "  export NO_PROXY='localhost'\n",
+                "  export HOMEBREW_GITHUB_API_TOKEN=\"$(pass show github)\"\n",
+                "  export HOMEBREW_NO_ANALYTICS='1'\n",
+                "  brew update &&\n",
+                "  brew upgrade &&\n",
+                "  brew doctor\n",
+                ") || failed=\"$failed homebrew\"\n",
+            )),
+            "{}",
+            script.text
+        );
+        assert!(script.text.contains("\n# mas: no update command\n"));
+
+        let npm = render(&config(), &[("npm".to_string(), None)], Verbosity::Normal);
+        assert!(npm.text.contains(
+            "(\n  export https_proxy='http://egress.lan:8080'\n  export HTTPS_PROXY='http://egress.lan:8080'\n  npm update -g\n"
+        ));
+
+        let pinned = render(
+            &config(),
+            &[("pinned".to_string(), None)],
+            Verbosity::Normal,
+        );
+        assert!(pinned.text.contains(
+            "  version=$(latest-version pinned) &&\n  install \"pinned@\"${version}\"\"\n"
+        ));
+
+        let quiet = render(&config(), &items[..1], Verbosity::Quiet);
+        assert!(quiet
+            .text
+            .contains("  brew upgrade --quiet &&\n  brew doctor\n"));
+    }
+
+    #[test]
+    fn test_unsupported_steps_fail_the_manager() {
+        let items = vec![("tool".to_string(), None)];
+        let script = render(&config(), &items, Verbosity::Normal);
+        assert_eq!(
+            script.warnings,
+            ["tool: built-in step 'download https://example.com/tool to /tmp/tool' can't be exported"]
+        );
+        assert!(script.text.contains(
+            "  echo 'tool: needs updog for: download https://example.com/tool to /tmp/tool' >&2 && false &&\n  install /tmp/tool ~/.local/bin\n"
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_script_runs_and_reports_failures() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+            commands:
+              - id: ok
+                env: { GREETING: "it's fine" }
+                update: 'echo "$GREETING"'
+              - id: broken
+                update: [ "false", "echo not reached" ]
+            "#,
+        )
+        .unwrap();
+        let items = vec![("ok".to_string(), None), ("broken".to_string(), None)];
+        let script = render(&config, &items, Verbosity::Normal);
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(&script.text)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "it's fine\n");
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "Failed: broken\n");
+    }
+}
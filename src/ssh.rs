@@ -0,0 +1,310 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::executor::CommandOutput;
+use crate::schedule::Interval;
+
+// Host a manager's commands run on, through the ssh client:
+//
+//   commands:
+//     - id: web1
+//       ssh:
+//         host: web1.example.com      # or a Host from ~/.ssh/config
+//         user: admin
+//         proxy_jump: bastion.example.com
+//         connect_timeout: 5s
+//         host_key: accept-new
+//       check: apt list --upgradable
+//       update: sudo apt-get upgrade -y
+//
+// Authentication is left to ssh: keys from the agent (SSH_AUTH_SOCK, or
+// `agent_socket` for runs started without one), ~/.ssh/config and so on.
+// Prompts for passwords, passphrases and unknown host keys would hang an
+// unattended run, so they're turned off unless asked for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SshTarget {
+    pub host: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    // Jump hosts, as for `ssh -J`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_jump: Option<String>,
+    // How long to wait for the connection (10s by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<Interval>,
+    #[serde(default)]
+    pub host_key: HostKeyPolicy,
+    // Let ssh ask for passwords and passphrases on the terminal
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interactive_auth: bool,
+    // Forward the agent, for commands that connect on from the host (git pulls)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub forward_agent: bool,
+    // Agent socket to use instead of SSH_AUTH_SOCK
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_socket: Option<String>,
+}
+
+// What to do about a host key that isn't in known_hosts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HostKeyPolicy {
+    // Refuse to connect
+    #[default]
+    Fail,
+    // Record it and connect; a changed key still fails
+    AcceptNew,
+    // Ask on the terminal
+    Ask,
+}
+
+impl HostKeyPolicy {
+    fn strict_host_key_checking(&self) -> &'static str {
+        match self {
+            HostKeyPolicy::Fail => "yes",
+            HostKeyPolicy::AcceptNew => "accept-new",
+            HostKeyPolicy::Ask => "ask",
+        }
+    }
+}
+
+// The ssh client exits with 255 when it couldn't connect or authenticate
+const SSH_ERROR: i32 = 255;
+
+const DEFAULT_CONNECT_TIMEOUT: u64 = 10;
+
+impl fmt::Display for SshTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.user {
+            Some(user) => write!(f, "{}@{}", user, self.host),
+            None => f.write_str(&self.host),
+        }
+    }
+}
+
+impl SshTarget {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.host.trim().is_empty() || self.host.starts_with('-') {
+            return Err(format!("invalid ssh host '{}'", self.host));
+        }
+        Ok(())
+    }
+
+    // Arguments to ssh, up to the destination. `send_env` are the names of
+    // variables to pass on, which the server has to accept (AcceptEnv).
+    pub fn args(&self, send_env: &[&str]) -> Vec<String> {
+        let prompts = self.interactive_auth || self.host_key == HostKeyPolicy::Ask;
+        let timeout = self
+            .connect_timeout
+            .map_or(DEFAULT_CONNECT_TIMEOUT, |t| t.duration().as_secs().max(1));
+        let mut options = vec![
+            format!("BatchMode={}", if prompts { "no" } else { "yes" }),
+            format!("ConnectTimeout={}", timeout),
+            format!(
+                "StrictHostKeyChecking={}",
+                self.host_key.strict_host_key_checking()
+            ),
+        ];
+        if self.forward_agent {
+            options.push("ForwardAgent=yes".to_string());
+        }
+        if let Some(socket) = &self.agent_socket {
+            options.push(format!("IdentityAgent={}", socket));
+        }
+        options.extend(send_env.iter().map(|name| format!("SendEnv={}", name)));
+
+        let mut args: Vec<String> = options
+            .into_iter()
+            .flat_map(|option| ["-o".to_string(), option])
+            .collect();
+        if let Some(jump) = &self.proxy_jump {
+            args.extend(["-J".to_string(), jump.clone()]);
+        }
+        if let Some(port) = self.port {
+            args.extend(["-p".to_string(), port.to_string()]);
+        }
+        if let Some(user) = &self.user {
+            args.extend(["-l".to_string(), user.clone()]);
+        }
+        args.extend(["--".to_string(), self.host.clone()]);
+        args
+    }
+
+    // The local shell command running `command` on the host
+    pub fn wrap(&self, command: &str, send_env: &[&str]) -> String {
+        std::iter::once("ssh".to_string())
+            .chain(self.args(send_env))
+            .chain([command.to_string()])
+            .map(|arg| quote(&arg))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // `command` started on the host from an empty environment (`clean_env`),
+    // with the host's PATH and those of the variables named in `keep` that
+    // the session has. Names that aren't shell variables are left out.
+    pub fn clean_env(command: &str, keep: &[&str]) -> String {
+        let mut names: Vec<&str> = vec!["PATH"];
+        for name in keep {
+            let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if valid && !names.contains(name) {
+                names.push(name);
+            }
+        }
+        let kept: Vec<String> = names
+            .iter()
+            .map(|name| format!("${{{0}+\"{0}=${0}\"}}", name))
+            .collect();
+        format!("env -i {} sh -c {}", kept.join(" "), quote(command))
+    }
+
+    // Why the host couldn't be reached, if that's what `output` says. ssh's
+    // own error is the last line it wrote.
+    pub fn unreachable(&self, output: &CommandOutput) -> Option<String> {
+        if output.exit_code != Some(SSH_ERROR) {
+            return None;
+        }
+        let reason = output
+            .stderr
+            .lines()
+            .rev()
+            .map(str::trim)
+            .find(|line| !line.is_empty());
+        Some(match reason {
+            Some(reason) => format!("{} unreachable: {}", self, reason),
+            None => format!("{} unreachable (ssh exited with {})", self, SSH_ERROR),
+        })
+    }
+}
+
+// Single-quoted for sh, `~` included: paths are for the host to expand
+fn quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_=./:@,".contains(c))
+    {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(yaml: &str) -> SshTarget {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_wrap() {
+        let web1 = target("host: web1.example.com");
+        assert_eq!(
+            web1.wrap("apt list --upgradable 2>/dev/null", &[]),
+            "ssh -o BatchMode=yes -o ConnectTimeout=10 -o StrictHostKeyChecking=yes \
+             -- web1.example.com 'apt list --upgradable 2>/dev/null'"
+        );
+
+        let web2 = target(
+            r#"
+            host: web2
+            user: admin
+            port: 2222
+            proxy_jump: bastion
+            connect_timeout: 5s
+            host_key: accept-new
+            forward_agent: true
+            agent_socket: ~/.1password/agent.sock
+            "#,
+        );
+        assert_eq!(web2.to_string(), "admin@web2");
+        assert_eq!(
+            web2.wrap("uptime", &["UPDOG_RUN_ID"]),
+            "ssh -o BatchMode=yes -o ConnectTimeout=5 -o StrictHostKeyChecking=accept-new \
+             -o ForwardAgent=yes -o 'IdentityAgent=~/.1password/agent.sock' \
+             -o SendEnv=UPDOG_RUN_ID -J bastion -p 2222 -l admin -- web2 uptime"
+        );
+
+        // Prompts need BatchMode off
+        let ask = target("{ host: web3, host_key: ask }");
+        assert!(ask
+            .args(&[])
+            .contains(&"StrictHostKeyChecking=ask".to_string()));
+        assert!(ask.args(&[]).contains(&"BatchMode=no".to_string()));
+        assert!(target("{ host: '-oProxyCommand=x' }").validate().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_wrapped_command_reaches_ssh_intact() {
+        let command = target("host: web1").wrap(r#"printf '%s\n' "$HOME" it\'s"#, &[]);
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!(
+                "set -- {}; printf '%s\\n' \"$#\" \"$1\" \"${{9}}\"",
+                &command[4..]
+            ))
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "9\n-o\nprintf '%s\\n' \"$HOME\" it\\'s\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_clean_env() {
+        let command = SshTarget::clean_env(
+            r#"echo "${HOME-unset} ${PATH:+path} $UPDOG_RUN_ID ${LANG-unset}""#,
+            &["UPDOG_RUN_ID", "LANG", "BAD NAME", "PATH"],
+        );
+        assert!(command.starts_with(r#"env -i ${PATH+"PATH=$PATH"} ${UPDOG_RUN_ID+"#));
+        assert!(!command.contains("BAD"));
+        // As the host's login shell would run it
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .env("HOME", "/home/admin")
+            .env("UPDOG_RUN_ID", "run 1")
+            .env_remove("LANG")
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "unset path run 1 unset\n"
+        );
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let web1 = target("{ host: web1, user: admin }");
+        let output = |code, stderr: &str| CommandOutput {
+            exit_code: Some(code),
+            stderr: stderr.to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            web1.unreachable(&output(
+                255,
+                "ssh: connect to host web1 port 22: Connection refused\r\n"
+            )),
+            Some(
+                "admin@web1 unreachable: ssh: connect to host web1 port 22: Connection refused"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            web1.unreachable(&output(255, "")),
+            Some("admin@web1 unreachable (ssh exited with 255)".to_string())
+        );
+        assert_eq!(
+            web1.unreachable(&output(1, "E: Could not open lock file")),
+            None
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// updog's state files (history, check cache, schedule, approvals, ...) can be
+// written by the daemon, the TUI and manual runs at the same time. Every
+// write goes through a temporary file renamed into place, so a reader or a
+// crash never sees half a file, and read-modify-write cycles hold an advisory
+// lock on `<file>.lock` so concurrent runs don't lose each other's changes.
+
+// An advisory lock on a state file, released when dropped. Locks are per
+// open file: code holding one must not take another on the same path.
+#[derive(Debug)]
+pub struct StateLock {
+    _file: File,
+}
+
+impl StateLock {
+    // Wait until no other process holds a lock on `path`
+    pub fn exclusive(path: &Path) -> io::Result<Self> {
+        Self::acquire(path, true)
+    }
+
+    // Wait until no other process holds an exclusive lock on `path`; shared
+    // locks (readers) don't exclude each other
+    pub fn shared(path: &Path) -> io::Result<Self> {
+        Self::acquire(path, false)
+    }
+
+    fn acquire(path: &Path, exclusive: bool) -> io::Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(lock_path(path))?;
+        lock(&file, exclusive)?;
+        Ok(Self { _file: file })
+    }
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    let mut lock = path.as_os_str().to_owned();
+    lock.push(".lock");
+    PathBuf::from(lock)
+}
+
+#[cfg(unix)]
+fn lock(file: &File, exclusive: bool) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let operation = if exclusive {
+        libc::LOCK_EX
+    } else {
+        libc::LOCK_SH
+    };
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+            return Ok(());
+        }
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn lock(_file: &File, _exclusive: bool) -> io::Result<()> {
+    Ok(())
+}
+
+// Replace `path` with `contents`: written and synced to a temporary file in
+// the same directory (named after this process, so concurrent writers don't
+// share one), then renamed over the old file
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}.tmp", std::process::id()));
+    let tmp = PathBuf::from(tmp);
+    let result = File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("state/schedule.yaml");
+        write_atomic(&path, b"one").unwrap();
+        write_atomic(&path, b"two").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "two");
+        // No temporary files are left behind
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exclusive_lock_waits() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("history.yaml");
+        let held = StateLock::exclusive(&path).unwrap();
+        let (sender, receiver) = mpsc::channel();
+        let writer = std::thread::spawn({
+            let path = path.clone();
+            move || {
+                let _lock = StateLock::exclusive(&path).unwrap();
+                sender.send(()).unwrap();
+            }
+        });
+        assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+        drop(held);
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        writer.join().unwrap();
+
+        // Shared locks don't exclude each other
+        let _first = StateLock::shared(&path).unwrap();
+        let _second = StateLock::shared(&path).unwrap();
+    }
+}
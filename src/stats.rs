@@ -0,0 +1,528 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::history::{ManagerRecord, RunRecord};
+use crate::timezone::Timezone;
+
+// Aggregated statistics for a single manager (or manager:subcommand)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManagerStats {
+    pub name: String,
+    pub runs: usize,
+    pub failures: usize,
+    pub average: Duration,
+    pub median: Duration,
+    pub p95: Duration,
+    // Median number of packages per run, when the manager reports counts
+    pub typical_packages: Option<usize>,
+    // Relative change of the average duration between the older and newer half of runs
+    pub trend: Option<f64>,
+}
+
+impl ManagerStats {
+    pub fn failure_rate(&self) -> f64 {
+        if self.runs == 0 {
+            0.0
+        } else {
+            self.failures as f64 / self.runs as f64
+        }
+    }
+}
+
+pub(crate) fn display_name(record: &ManagerRecord) -> String {
+    match &record.subcommand {
+        Some(sc) => format!("{}:{}", record.manager, sc),
+        None => record.manager.clone(),
+    }
+}
+
+// Nearest-rank percentile over an already sorted slice
+fn percentile(sorted: &[Duration], pct: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn average(durations: &[Duration]) -> Duration {
+    if durations.is_empty() {
+        return Duration::ZERO;
+    }
+    durations.iter().sum::<Duration>() / durations.len() as u32
+}
+
+// Aggregate history into per-manager statistics, optionally restricted to one operation
+pub fn compute(runs: &[RunRecord], operation: Option<&str>) -> Vec<ManagerStats> {
+    // Records grouped by manager, in chronological order
+    let mut grouped: BTreeMap<String, Vec<&ManagerRecord>> = BTreeMap::new();
+    for run in runs {
+        if operation.is_some_and(|op| op != run.operation) {
+            continue;
+        }
+        for record in run.results.iter().filter(|r| r.was_executed()) {
+            grouped
+                .entry(display_name(record))
+                .or_default()
+                .push(record);
+        }
+    }
+
+    grouped
+        .into_iter()
+        .map(|(name, records)| {
+            let durations: Vec<Duration> = records.iter().map(|r| r.duration()).collect();
+            let mut sorted = durations.clone();
+            sorted.sort();
+
+            let mut packages: Vec<usize> = records.iter().filter_map(|r| r.packages).collect();
+            packages.sort();
+
+            // Compare the newer half against the older half once there is enough data
+            let trend = if durations.len() >= 4 {
+                let (older, newer) = durations.split_at(durations.len() / 2);
+                let older_avg = average(older).as_secs_f64();
+                if older_avg > 0.0 {
+                    Some((average(newer).as_secs_f64() - older_avg) / older_avg)
+                } else {
+                    None
+                }
+            } else {
+                None
+            };
+
+            ManagerStats {
+                name,
+                runs: records.len(),
+                failures: records.iter().filter(|r| !r.success).count(),
+                average: average(&durations),
+                median: percentile(&sorted, 50.0),
+                p95: percentile(&sorted, 95.0),
+                typical_packages: packages.get(packages.len().saturating_sub(1) / 2).copied(),
+                trend,
+            }
+        })
+        .collect()
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+    if secs < 60.0 {
+        format!("{:.1}s", secs)
+    } else if secs < 3600.0 {
+        format!(
+            "{}m{:02}s",
+            duration.as_secs() / 60,
+            duration.as_secs() % 60
+        )
+    } else {
+        format!(
+            "{}h{:02}m",
+            duration.as_secs() / 3600,
+            (duration.as_secs() % 3600) / 60
+        )
+    }
+}
+
+fn format_trend(trend: Option<f64>) -> String {
+    match trend {
+        // Changes under 10% are treated as noise
+        Some(t) if t >= 0.1 => format!("↑ {:.0}% slower", t * 100.0),
+        Some(t) if t <= -0.1 => format!("↓ {:.0}% faster", -t * 100.0),
+        Some(_) => "→ stable".to_string(),
+        None => "-".to_string(),
+    }
+}
+
+// Render statistics as a plain text table
+pub fn render(stats: &[ManagerStats]) -> String {
+    if stats.is_empty() {
+        return "No history recorded yet.\n".to_string();
+    }
+
+    let name_width = stats
+        .iter()
+        .map(|s| s.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Manager".len());
+
+    let mut out = format!(
+        "{:<width$}  {:>5}  {:>8}  {:>8}  {:>8}  {:>7}  {:>8}  Trend\n",
+        "Manager",
+        "Runs",
+        "Average",
+        "Median",
+        "95p",
+        "Failed",
+        "Packages",
+        width = name_width
+    );
+    for s in stats {
+        out.push_str(&format!(
+            "{:<width$}  {:>5}  {:>8}  {:>8}  {:>8}  {:>6.0}%  {:>8}  {}\n",
+            s.name,
+            s.runs,
+            format_duration(s.average),
+            format_duration(s.median),
+            format_duration(s.p95),
+            s.failure_rate() * 100.0,
+            s.typical_packages
+                .map_or("-".to_string(), |p| p.to_string()),
+            format_trend(s.trend),
+            width = name_width
+        ));
+    }
+    out
+}
+
+// Number of consecutive failed runs per manager, counting back from the
+// newest. Skipped and cancelled entries neither count nor break a streak.
+pub fn failure_streaks(runs: &[RunRecord]) -> BTreeMap<String, usize> {
+    let mut streaks: BTreeMap<String, usize> = BTreeMap::new();
+    for run in runs {
+        for record in run.results.iter().filter(|r| r.was_executed()) {
+            let streak = streaks.entry(display_name(record)).or_default();
+            *streak = if record.success { 0 } else { *streak + 1 };
+        }
+    }
+    streaks
+}
+
+// Latest known state of a manager, for `updog status`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManagerStatus {
+    pub name: String,
+    // Start of the newest run that executed the manager (seconds since the Unix epoch)
+    pub last_run: Option<u64>,
+    pub last_status: Option<String>,
+    // Whether the newest run failed
+    pub failed: bool,
+    // Pending updates according to the newest check (0 after a successful update)
+    pub pending: Option<usize>,
+    pub failure_streak: usize,
+    // `summary_columns` of the newest run that found them, until an update
+    // succeeds without
+    pub columns: BTreeMap<String, String>,
+}
+
+// Latest status of every manager in `names`, plus any of their subcommands
+// found in history
+pub fn status(runs: &[RunRecord], names: &[String]) -> Vec<ManagerStatus> {
+    let streaks = failure_streaks(runs);
+    let mut latest: BTreeMap<String, (u64, &ManagerRecord)> = BTreeMap::new();
+    let mut pending: BTreeMap<String, usize> = BTreeMap::new();
+    let mut columns: BTreeMap<String, &BTreeMap<String, String>> = BTreeMap::new();
+    for run in runs {
+        for record in run.results.iter().filter(|r| r.was_executed()) {
+            if !names.contains(&record.manager) {
+                continue;
+            }
+            let name = display_name(record);
+            match (run.operation.as_str(), record.packages) {
+                ("check", Some(count)) => {
+                    pending.insert(name.clone(), count);
+                }
+                ("update", _) if record.success => {
+                    pending.insert(name.clone(), 0);
+                }
+                _ => {}
+            }
+            if !record.columns.is_empty() {
+                columns.insert(name.clone(), &record.columns);
+            } else if run.operation == "update" && record.success {
+                columns.remove(&name);
+            }
+            latest.insert(name, (run.started_at, record));
+        }
+    }
+
+    let mut statuses = Vec::new();
+    for name in names {
+        let prefix = format!("{}:", name);
+        let entries: Vec<_> = latest
+            .iter()
+            .filter(|(key, _)| *key == name || key.starts_with(&prefix))
+            .collect();
+        if entries.is_empty() {
+            statuses.push(ManagerStatus {
+                name: name.clone(),
+                last_run: None,
+                last_status: None,
+                failed: false,
+                pending: None,
+                failure_streak: 0,
+                columns: BTreeMap::new(),
+            });
+        }
+        for (key, (started_at, record)) in entries {
+            let label = match (&record.status, record.success) {
+                (Some(status), _) => status.clone(),
+                (None, true) => "success".to_string(),
+                (None, false) => "failed".to_string(),
+            };
+            statuses.push(ManagerStatus {
+                name: key.clone(),
+                last_run: Some(*started_at),
+                last_status: Some(label),
+                failed: !record.success,
+                pending: pending.get(key).copied(),
+                failure_streak: streaks.get(key).copied().unwrap_or(0),
+                columns: columns.get(key).copied().cloned().unwrap_or_default(),
+            });
+        }
+    }
+    statuses
+}
+
+// Render statuses as a plain text table. Managers failing at least
+// `flag_after` times in a row are flagged.
+pub fn render_status(statuses: &[ManagerStatus], flag_after: usize, timezone: Timezone) -> String {
+    let name_width = statuses
+        .iter()
+        .map(|s| s.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Manager".len());
+    // A column for each of the `summary_columns` found, as wide as its values
+    let mut extra: BTreeMap<&str, usize> = BTreeMap::new();
+    for (name, value) in statuses.iter().flat_map(|s| &s.columns) {
+        let width = extra.entry(name).or_insert(name.chars().count());
+        *width = (*width).max(value.chars().count());
+    }
+
+    let mut out = format!(
+        "{:<width$}  {:<23}  {:<17}  ",
+        "Manager",
+        "Last run",
+        "Result",
+        width = name_width
+    );
+    for (name, width) in &extra {
+        out.push_str(&format!("{:<width$}  ", name, width = width));
+    }
+    out.push_str("Failing\n");
+    for s in statuses {
+        let last_run = s.last_run.map_or("never".to_string(), |secs| {
+            timezone
+                .civil(std::time::UNIX_EPOCH + Duration::from_secs(secs))
+                .to_string()
+        });
+        let failing = match s.failure_streak {
+            0 => "-".to_string(),
+            n if n >= flag_after => format!("{} runs in a row  ⚠", n),
+            n => format!("{} runs in a row", n),
+        };
+        out.push_str(&format!(
+            "{:<width$}  {:<23}  {:<17}  ",
+            s.name,
+            last_run,
+            s.last_status.as_deref().unwrap_or("-"),
+            width = name_width
+        ));
+        for (name, width) in &extra {
+            let value = s.columns.get(*name).map_or("-", String::as_str);
+            out.push_str(&format!("{:<width$}  ", value, width = width));
+        }
+        out.push_str(&failing);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(operation: &str, results: &[(&str, bool, f64, Option<usize>)]) -> RunRecord {
+        RunRecord {
+            version: 1,
+            run_id: None,
+            started_at: 0,
+            operation: operation.to_string(),
+            results: results
+                .iter()
+                .map(|(manager, success, secs, packages)| ManagerRecord {
+                    manager: manager.to_string(),
+                    subcommand: None,
+                    success: *success,
+                    status: None,
+                    duration_secs: *secs,
+                    packages: *packages,
+                    message: String::new(),
+                    steps: Vec::new(),
+                    output: String::new(),
+                    warnings: Vec::new(),
+                    columns: BTreeMap::new(),
+                })
+                .collect(),
+            metadata: None,
+            note: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_compute_basic_statistics() {
+        let runs = vec![
+            run(
+                "update",
+                &[("brew", true, 10.0, Some(3)), ("npm", true, 1.0, None)],
+            ),
+            run("update", &[("brew", false, 20.0, Some(5))]),
+            run("update", &[("brew", true, 30.0, Some(4))]),
+            run("check", &[("brew", true, 100.0, None)]),
+        ];
+
+        let stats = compute(&runs, Some("update"));
+        assert_eq!(stats.len(), 2);
+
+        let brew = &stats[0];
+        assert_eq!(brew.name, "brew");
+        assert_eq!(brew.runs, 3);
+        assert_eq!(brew.failures, 1);
+        assert_eq!(brew.average, Duration::from_secs(20));
+        assert_eq!(brew.median, Duration::from_secs(20));
+        assert_eq!(brew.p95, Duration::from_secs(30));
+        assert_eq!(brew.typical_packages, Some(4));
+        assert!(brew.trend.is_none());
+
+        // Without a filter the check run is included too
+        let stats = compute(&runs, None);
+        assert_eq!(stats[0].runs, 4);
+    }
+
+    #[test]
+    fn test_trend_detection() {
+        let runs: Vec<RunRecord> = [10.0, 10.0, 20.0, 20.0]
+            .iter()
+            .map(|secs| run("update", &[("brew", true, *secs, None)]))
+            .collect();
+        let stats = compute(&runs, None);
+        assert_eq!(stats[0].trend, Some(1.0));
+        assert!(render(&stats).contains("↑ 100% slower"));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1.5s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m05s");
+        assert_eq!(format_duration(Duration::from_secs(3720)), "1h02m");
+    }
+
+    #[test]
+    fn test_render_empty() {
+        assert_eq!(render(&[]), "No history recorded yet.\n");
+    }
+
+    #[test]
+    fn test_skipped_records_are_ignored() {
+        let mut skipped = run("check", &[("brew", true, 0.0, None)]);
+        skipped.results[0].status = Some("skipped".to_string());
+        let runs = vec![skipped, run("check", &[("brew", true, 4.0, None)])];
+
+        let stats = compute(&runs, None);
+        assert_eq!(stats[0].runs, 1);
+        assert_eq!(stats[0].average, Duration::from_secs(4));
+    }
+
+    #[test]
+    fn test_failure_streaks() {
+        let mut skipped = run("check", &[("brew", true, 0.0, None)]);
+        skipped.results[0].status = Some("skipped".to_string());
+        let runs = vec![
+            run(
+                "update",
+                &[("brew", false, 1.0, None), ("npm", false, 1.0, None)],
+            ),
+            run(
+                "update",
+                &[("brew", true, 1.0, None), ("npm", false, 1.0, None)],
+            ),
+            run("update", &[("brew", false, 1.0, None)]),
+            skipped,
+            run(
+                "update",
+                &[("brew", false, 1.0, None), ("npm", true, 1.0, None)],
+            ),
+        ];
+        let streaks = failure_streaks(&runs);
+        assert_eq!(streaks["brew"], 2);
+        assert_eq!(streaks["npm"], 0);
+    }
+
+    #[test]
+    fn test_status_flags_failing_managers() {
+        let mut runs = vec![
+            run("update", &[("brew", false, 1.0, None)]),
+            run(
+                "update",
+                &[("brew", false, 1.0, None), ("npm", true, 1.0, None)],
+            ),
+        ];
+        runs[1].started_at = 3600;
+        let names = vec!["brew".to_string(), "npm".to_string(), "cargo".to_string()];
+
+        let statuses = status(&runs, &names);
+        assert_eq!(statuses.len(), 3);
+        assert_eq!(statuses[0].failure_streak, 2);
+        assert_eq!(statuses[0].last_run, Some(3600));
+        assert_eq!(statuses[2].last_status, None);
+        assert!(statuses[0].failed);
+        assert_eq!(statuses[1].pending, Some(0));
+
+        let rendered = render_status(&statuses, 2, Timezone::Utc);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert!(lines[1].starts_with("brew     1970-01-01 01:00 +00:00"));
+        assert!(lines[1].ends_with("2 runs in a row  ⚠"));
+        assert!(lines[2].ends_with("  -"));
+        assert!(lines[3].contains("never"));
+        assert!(!render_status(&statuses, 3, Timezone::Utc).contains('⚠'));
+    }
+
+    #[test]
+    fn test_status_shows_summary_columns() {
+        let names = vec!["composer".to_string(), "npm".to_string()];
+        let mut runs = vec![run(
+            "check",
+            &[
+                ("composer", true, 1.0, Some(0)),
+                ("npm", true, 1.0, Some(2)),
+            ],
+        )];
+        runs[0].results[0].columns = BTreeMap::from([("outdated".to_string(), "12".to_string())]);
+
+        let statuses = status(&runs, &names);
+        assert_eq!(statuses[0].columns["outdated"], "12");
+        let rendered = render_status(&statuses, 3, Timezone::Utc);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert!(lines[0].ends_with("Result             outdated  Failing"));
+        assert!(lines[1].ends_with("success            12        -"));
+        assert!(lines[2].ends_with("success            -         -"));
+
+        // Gone once an update succeeds without finding them
+        runs.push(run("update", &[("composer", true, 1.0, None)]));
+        assert!(status(&runs, &names)[0].columns.is_empty());
+    }
+
+    #[test]
+    fn test_status_tracks_pending_updates() {
+        let names = vec!["brew".to_string(), "npm".to_string()];
+        let mut runs = vec![
+            run(
+                "check",
+                &[("brew", true, 1.0, Some(4)), ("npm", true, 1.0, Some(2))],
+            ),
+            run("update", &[("brew", true, 1.0, None)]),
+        ];
+        let statuses = status(&runs, &names);
+        assert_eq!(statuses[0].pending, Some(0));
+        assert_eq!(statuses[1].pending, Some(2));
+
+        // A failed check keeps the last known count
+        runs.push(run("check", &[("npm", false, 1.0, None)]));
+        let statuses = status(&runs, &names);
+        assert_eq!(statuses[1].pending, Some(2));
+        assert!(statuses[1].failed);
+    }
+}
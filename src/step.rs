@@ -0,0 +1,534 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::docker_images::DockerImages;
+use crate::download::DownloadStep;
+use crate::executor::{CommandRequest, Executor};
+use crate::git_repo::GitRepo;
+use crate::github_release::GithubRelease;
+use crate::hash::sha256_file;
+use crate::http_check::HttpCheckStep;
+use crate::package_manager::UpdateError;
+use crate::preset::Preset;
+
+// Outcome of one step of a command sequence
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepResult {
+    // The command, or a built-in step's description
+    pub step: String,
+    pub success: bool,
+    // None for built-in steps and commands that didn't exit normally
+    pub exit_code: Option<i32>,
+    pub duration: Duration,
+    // Captured stdout of this step
+    pub output: String,
+}
+
+// One entry of a command sequence: a shell command, or a built-in step
+//
+//   update:
+//     - download:
+//         url: https://example.com/tool.tar.gz
+//         path: tool.tar.gz
+//     - verify:
+//         file: tool.tar.gz
+//         sha256: 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
+//     - tar xzf tool.tar.gz -C ~/.local/bin
+//
+// A command can `register` its output (without trailing whitespace) for the
+// steps after it, which use it as `{{name}}`:
+//
+//   update:
+//     - run: gh release view --repo junegunn/fzf --json tagName -q .tagName
+//       register: latest_tag
+//     - download:
+//         url: https://github.com/junegunn/fzf/releases/download/{{latest_tag}}/fzf.tar.gz
+//         path: fzf.tar.gz
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(untagged)]
+pub enum Step {
+    Command(String),
+    Register {
+        run: String,
+        register: String,
+    },
+    Verify {
+        verify: VerifyStep,
+    },
+    Download {
+        download: DownloadStep,
+    },
+    CheckHttp {
+        check_http: HttpCheckStep,
+    },
+    // Generated for `type: github-release` entries, never written in configs
+    #[serde(skip)]
+    ReleaseCheck(GithubRelease),
+    #[serde(skip)]
+    ReleaseInstall(GithubRelease),
+    // Generated for `type: git-repo` entries
+    #[serde(skip)]
+    GitCheck(GitRepo),
+    // Generated for `type: docker-images` entries
+    #[serde(skip)]
+    ImageCheck(DockerImages),
+    #[serde(skip)]
+    ImagePull(DockerImages),
+    // Generated for entries with a `preset`
+    #[serde(skip)]
+    PresetCheck(Preset),
+    #[serde(skip)]
+    PresetUpdate(Preset),
+    #[serde(skip)]
+    PresetCleanup(Preset),
+}
+
+impl Step {
+    // Parse a command string or a `{ <type>: { ... } }` mapping, with errors
+    // that name the step type
+    pub fn from_yaml(value: &serde_yaml::Value) -> Result<Self, String> {
+        let mapping = match value {
+            serde_yaml::Value::String(command) => return Ok(Step::Command(command.clone())),
+            serde_yaml::Value::Mapping(mapping) if mapping.contains_key("register") => {
+                return Self::register_from_yaml(mapping)
+            }
+            serde_yaml::Value::Mapping(mapping) if mapping.len() == 1 => mapping,
+            _ => return Err("expected a command or a single built-in step".to_string()),
+        };
+        let (kind, body) = mapping.iter().next().expect("mapping has one entry");
+        let kind = kind.as_str().unwrap_or_default();
+        let parsed = match kind {
+            "verify" => serde_yaml::from_value(body.clone()).map(|verify| Step::Verify { verify }),
+            "download" => {
+                serde_yaml::from_value(body.clone()).map(|download| Step::Download { download })
+            }
+            "check_http" => serde_yaml::from_value(body.clone())
+                .map(|check_http| Step::CheckHttp { check_http }),
+            "run" => serde_yaml::from_value(body.clone()).map(Step::Command),
+            _ => {
+                return Err(format!(
+                    "unknown step type '{}' (expected run, verify, download or check_http)",
+                    kind
+                ))
+            }
+        };
+        parsed.map_err(|e| format!("{}: {}", kind, e))
+    }
+
+    // `{ run: <command>, register: <name> }`
+    fn register_from_yaml(mapping: &serde_yaml::Mapping) -> Result<Self, String> {
+        let field = |key: &str| mapping.get(key).and_then(serde_yaml::Value::as_str);
+        if let Some(key) = mapping
+            .keys()
+            .filter_map(serde_yaml::Value::as_str)
+            .find(|key| !["run", "register"].contains(key))
+        {
+            return Err(format!("register only works with run, not {}", key));
+        }
+        let run = field("run").ok_or("register needs a run command")?;
+        let register = field("register").ok_or("register must be a name")?;
+        let mut chars = register.chars();
+        let valid = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(format!(
+                "invalid register name '{}' (letters, digits and _)",
+                register
+            ));
+        }
+        Ok(Step::Register {
+            run: run.to_string(),
+            register: register.to_string(),
+        })
+    }
+
+    // The step with `{{name}}` replaced by the registered `values`. Other
+    // `{{...}}`, like docker's format templates, are left alone.
+    pub fn interpolate(&self, values: &[(String, String)]) -> Step {
+        let substitute = |text: &str| {
+            values.iter().fold(text.to_string(), |text, (name, value)| {
+                text.replace(&format!("{{{{{}}}}}", name), value)
+            })
+        };
+        match self {
+            _ if values.is_empty() => self.clone(),
+            Step::Command(command) => Step::Command(substitute(command)),
+            Step::Register { run, register } => Step::Register {
+                run: substitute(run),
+                register: register.clone(),
+            },
+            Step::ReleaseCheck(_)
+            | Step::ReleaseInstall(_)
+            | Step::GitCheck(_)
+            | Step::ImageCheck(_)
+            | Step::ImagePull(_)
+            | Step::PresetCheck(_)
+            | Step::PresetUpdate(_)
+            | Step::PresetCleanup(_) => self.clone(),
+            // Built-in steps are substituted in every string they're written with
+            step => {
+                fn walk(value: &mut serde_yaml::Value, substitute: &dyn Fn(&str) -> String) {
+                    match value {
+                        serde_yaml::Value::String(text) => *text = substitute(text),
+                        serde_yaml::Value::Sequence(items) => {
+                            items.iter_mut().for_each(|item| walk(item, substitute))
+                        }
+                        serde_yaml::Value::Mapping(mapping) => mapping
+                            .iter_mut()
+                            .for_each(|(_, item)| walk(item, substitute)),
+                        _ => {}
+                    }
+                }
+                let Ok(mut value) = serde_yaml::to_value(step) else {
+                    return step.clone();
+                };
+                walk(&mut value, &substitute);
+                Step::from_yaml(&value).unwrap_or_else(|_| step.clone())
+            }
+        }
+    }
+
+    pub fn as_command(&self) -> Option<&str> {
+        match self {
+            Step::Command(command) | Step::Register { run: command, .. } => Some(command),
+            _ => None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Step::Command(_)
+            | Step::Register { .. }
+            | Step::ImageCheck(_)
+            | Step::ImagePull(_)
+            | Step::PresetCheck(_)
+            | Step::PresetUpdate(_)
+            | Step::PresetCleanup(_) => Ok(()),
+            Step::Verify { verify } => verify.validate(),
+            Step::Download { download } => download.validate(),
+            Step::CheckHttp { check_http } => check_http.validate(),
+            Step::ReleaseCheck(release) | Step::ReleaseInstall(release) => release.validate(),
+            Step::GitCheck(repo) => repo.validate(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Step {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let value = serde_yaml::Value::deserialize(deserializer)?;
+        Self::from_yaml(&value).map_err(serde::de::Error::custom)
+    }
+}
+
+impl PartialEq<&str> for Step {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_command() == Some(*other)
+    }
+}
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Step::Command(command) => write!(f, "{}", command),
+            Step::Register { run, register } => write!(f, "{} (register {})", run, register),
+            Step::Verify { verify } => write!(f, "verify {}", verify.file),
+            Step::Download { download } => {
+                write!(f, "download {} to {}", download.url, download.path)
+            }
+            Step::CheckHttp { check_http } => write!(f, "check_http {}", check_http.url),
+            Step::ReleaseCheck(release) => write!(f, "check github release {}", release.repo),
+            Step::ReleaseInstall(release) => {
+                write!(f, "install github release {}", release.repo)
+            }
+            Step::GitCheck(repo) => write!(f, "check git repo {}", repo.path),
+            Step::ImageCheck(_) => write!(f, "check docker images"),
+            Step::ImagePull(_) => write!(f, "pull docker images"),
+            Step::PresetCheck(preset) => write!(f, "check {}", preset),
+            Step::PresetUpdate(preset) => write!(f, "update {}", preset),
+            Step::PresetCleanup(preset) => write!(f, "clean up {}", preset),
+        }
+    }
+}
+
+// Check a downloaded file against a SHA-256 digest or a GPG signature
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VerifyStep {
+    pub file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    // Fingerprint (or long key ID) of the key that must have signed the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpg_key: Option<String>,
+    // Detached signature, <file>.sig by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl VerifyStep {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.file.is_empty() {
+            return Err("verify: file is empty".to_string());
+        }
+        match (&self.sha256, &self.gpg_key) {
+            (Some(_), Some(_)) | (None, None) => Err(format!(
+                "verify {}: set either sha256 or gpg_key",
+                self.file
+            )),
+            (Some(digest), None)
+                if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                Err(format!(
+                    "verify {}: sha256 must be 64 hex digits",
+                    self.file
+                ))
+            }
+            // Short key IDs are easy to collide
+            (None, Some(key)) if !is_long_key_id(&normalize_key(key)) => Err(format!(
+                "verify {}: gpg_key must be a fingerprint or a 16 digit key ID",
+                self.file
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn signature(&self) -> String {
+        self.signature
+            .clone()
+            .unwrap_or_else(|| format!("{}.sig", self.file))
+    }
+
+    // Verify the file. GPG runs through `executor`, like any other command.
+    pub fn run(&self, executor: &dyn Executor) -> Result<(), UpdateError> {
+        if let Some(expected) = &self.sha256 {
+            let actual = sha256_file(&expand_home(&self.file))
+                .map_err(|e| UpdateError::new(format!("Failed to read {}: {}", self.file, e)))?;
+            if !actual.eq_ignore_ascii_case(expected) {
+                return Err(UpdateError::new(format!(
+                    "SHA-256 mismatch for {}: expected {}, got {}",
+                    self.file, expected, actual
+                )));
+            }
+        }
+
+        if let Some(key) = &self.gpg_key {
+            let command = format!(
+                "gpg --batch --status-fd 1 --verify {} {}",
+                shell_quote(&self.signature()),
+                shell_quote(&self.file)
+            );
+            let output = executor.run(&CommandRequest::captured(&command))?;
+            if !output.success() {
+                return Err(UpdateError::new(format!(
+                    "Signature check of {} failed with {}",
+                    self.file,
+                    output.status_description()
+                )));
+            }
+            if !signed_by(&output.stdout, key) {
+                return Err(UpdateError::new(format!(
+                    "{} is not signed by {}",
+                    self.file, key
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Whether gpg's status output has a valid signature by `key`. VALIDSIG
+// carries the signing key's fingerprint and, last, its primary key's.
+fn signed_by(status: &str, key: &str) -> bool {
+    let key = normalize_key(key);
+    status
+        .lines()
+        .filter_map(|line| line.strip_prefix("[GNUPG:] VALIDSIG "))
+        .any(|fields| {
+            let fields: Vec<&str> = fields.split_whitespace().collect();
+            [fields.first().copied(), fields.last().copied()]
+                .into_iter()
+                .flatten()
+                .any(|fingerprint| fingerprint.to_ascii_uppercase().ends_with(&key))
+        })
+}
+
+// Fingerprints are often written in groups of four, or with a 0x prefix
+fn normalize_key(key: &str) -> String {
+    key.chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .trim_start_matches("0x")
+        .to_ascii_uppercase()
+}
+
+fn is_long_key_id(key: &str) -> bool {
+    key.len() >= 16 && key.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// `~/` is expanded like the shell would for the other steps
+pub(crate) fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+// Single-quote an argument for `sh -c`, leaving `~/` for the shell to expand
+pub fn shell_quote(arg: &str) -> String {
+    match arg.strip_prefix("~/") {
+        Some(rest) => format!("~/{}", shell_quote(rest)),
+        None => format!("'{}'", arg.replace('\'', r"'\''")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    const FINGERPRINT: &str = "27EDEAF22F3ABCEB50DB9A125CC908FDB71E12C2";
+
+    fn verify_sha256(file: &str, sha256: &str) -> VerifyStep {
+        VerifyStep {
+            file: file.to_string(),
+            sha256: Some(sha256.to_string()),
+            gpg_key: None,
+            signature: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_steps() {
+        let steps: Vec<Step> = serde_yaml::from_str(
+            "- curl -LO https://example.com/tool\n- verify:\n    file: tool\n    gpg_key: 5CC908FDB71E12C2\n",
+        )
+        .unwrap();
+        assert_eq!(steps[0], "curl -LO https://example.com/tool");
+        let Step::Verify { verify } = &steps[1] else {
+            panic!("expected a verify step");
+        };
+        assert_eq!(verify.signature(), "tool.sig");
+        assert!(verify.validate().is_ok());
+
+        assert!(verify_sha256("tool", "abc").validate().is_err());
+        let mut both = verify_sha256("tool", &"0".repeat(64));
+        assert!(both.validate().is_ok());
+        both.gpg_key = Some(FINGERPRINT.to_string());
+        assert!(both.validate().is_err());
+        both.sha256 = None;
+        assert!(both.validate().is_ok());
+        both.gpg_key = Some("B71E12C2".to_string());
+        assert!(both.validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_register() {
+        let steps: Vec<Step> = serde_yaml::from_str(
+            "- run: git describe --tags\n  register: latest_tag\n- run: make install\n",
+        )
+        .unwrap();
+        assert_eq!(
+            steps[0],
+            Step::Register {
+                run: "git describe --tags".to_string(),
+                register: "latest_tag".to_string(),
+            }
+        );
+        assert_eq!(steps[1], "make install");
+
+        for (yaml, error) in [
+            ("register: tag", "register needs a run command"),
+            ("run: x\nregister: 1st", "invalid register name '1st'"),
+            (
+                "download: {}\nregister: tag",
+                "register only works with run",
+            ),
+        ] {
+            let err = serde_yaml::from_str::<Step>(yaml).unwrap_err().to_string();
+            assert!(err.contains(error), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let values = [("tag".to_string(), "v1.2".to_string())];
+        let step = Step::Command(
+            "cargo install tool --version {{tag}} && docker ps --format '{{.Names}}'".to_string(),
+        );
+        assert_eq!(
+            step.interpolate(&values),
+            "cargo install tool --version v1.2 && docker ps --format '{{.Names}}'"
+        );
+
+        let step: Step = serde_yaml::from_str(
+            "download:\n  url: https://example.com/{{tag}}/tool.tar.gz\n  path: tool-{{tag}}.tar.gz\n",
+        )
+        .unwrap();
+        let Step::Download { download } = step.interpolate(&values) else {
+            panic!("expected a download step");
+        };
+        assert_eq!(download.url, "https://example.com/v1.2/tool.tar.gz");
+        assert_eq!(download.path, "tool-v1.2.tar.gz");
+    }
+
+    #[test]
+    fn test_sha256_verification() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("tool");
+        std::fs::write(&path, "abc").unwrap();
+        let file = path.to_str().unwrap();
+        let clock = Arc::new(FakeClock::new());
+        let executor = ScriptedExecutor::new(clock);
+
+        let digest = "BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD";
+        assert!(verify_sha256(file, digest).run(&executor).is_ok());
+        let err = verify_sha256(file, &"0".repeat(64))
+            .run(&executor)
+            .unwrap_err();
+        assert!(err.message.contains("SHA-256 mismatch"));
+        assert!(verify_sha256(&format!("{}.missing", file), digest)
+            .run(&executor)
+            .is_err());
+    }
+
+    #[test]
+    fn test_gpg_verification() {
+        let clock = Arc::new(FakeClock::new());
+        let executor = ScriptedExecutor::new(clock);
+        let command = "gpg --batch --status-fd 1 --verify 'tool.asc' 'tool'";
+        executor.script(
+            command,
+            ScriptedCommand::success().stdout(&format!(
+                "[GNUPG:] GOODSIG 5CC908FDB71E12C2 Someone\n[GNUPG:] VALIDSIG {} 2024-01-01 0 4 0 1 10 00 {}\n",
+                FINGERPRINT, FINGERPRINT
+            )),
+        );
+        let step = |key: &str| VerifyStep {
+            file: "tool".to_string(),
+            sha256: None,
+            gpg_key: Some(key.to_string()),
+            signature: Some("tool.asc".to_string()),
+        };
+
+        assert!(step("0x5CC908FDB71E12C2").run(&executor).is_ok());
+        assert!(step("27ED EAF2 2F3A BCEB 50DB  9A12 5CC9 08FD B71E 12C2")
+            .run(&executor)
+            .is_ok());
+        let err = step("DEADBEEFDEADBEEF").run(&executor).unwrap_err();
+        assert!(err.message.contains("not signed by"));
+        assert_eq!(executor.commands()[0], command);
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote("~/a b"), "~/'a b'");
+    }
+}
@@ -0,0 +1,184 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::schedule::Cadence;
+use crate::timezone::Timezone;
+
+// Delay before the first run of interval timers after boot
+const ON_BOOT_SEC: &str = "15min";
+
+// A generated systemd unit file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unit {
+    pub name: String,
+    pub contents: String,
+}
+
+// Quote a command line argument for ExecStart= when needed
+fn quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@+".contains(c))
+    {
+        arg.to_string()
+    } else {
+        format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+// Service and timer units that run each configured package manager on the
+// same cadence (and with the same jitter) the daemon would use
+pub fn generate(config: &Config, exe: &Path, config_path: Option<&Path>) -> Vec<Unit> {
+    let operation = config.daemon.operation;
+    let mut units = Vec::new();
+
+    for manager in config.managers() {
+        let mut command = vec![quote(&exe.to_string_lossy())];
+        if let Some(path) = config_path {
+            command.push("--config".to_string());
+            command.push(quote(&path.to_string_lossy()));
+        }
+        command.push(operation.to_string());
+        command.push("--scheduled".to_string());
+        command.push(quote(&manager.id));
+
+        units.push(Unit {
+            name: format!("updog-{}.service", manager.id),
+            contents: format!(
+                "[Unit]\nDescription=updog {} {}\n\n[Service]\nType=oneshot\nExecStart={}\n",
+                operation,
+                manager.id,
+                command.join(" ")
+            ),
+        });
+
+        let mut timer = format!(
+            "[Unit]\nDescription=Scheduled updog {} {} ({})\n\n[Timer]\n",
+            operation,
+            manager.id,
+            config.cadence(&manager.id)
+        );
+        match config.cadence(&manager.id) {
+            Cadence::Every(interval) => {
+                timer.push_str(&format!("OnBootSec={}\n", ON_BOOT_SEC));
+                timer.push_str(&format!(
+                    "OnUnitActiveSec={}s\n",
+                    interval.duration().as_secs()
+                ));
+            }
+            Cadence::Cron(schedule, timezone) => {
+                let suffix = match timezone {
+                    Timezone::Local => String::new(),
+                    Timezone::Utc => " UTC".to_string(),
+                    Timezone::Fixed(_) => {
+                        // systemd only understands zone names
+                        timer.push_str(&format!(
+                            "# daemon.timezone {} has no systemd equivalent; using local time\n",
+                            timezone
+                        ));
+                        String::new()
+                    }
+                };
+                for calendar in schedule.on_calendar() {
+                    timer.push_str(&format!("OnCalendar={}{}\n", calendar, suffix));
+                }
+                timer.push_str("Persistent=true\n");
+            }
+        }
+        if let Some(jitter) = config.jitter(&manager.id) {
+            timer.push_str(&format!(
+                "RandomizedDelaySec={}s\n",
+                jitter.duration().as_secs()
+            ));
+        }
+        timer.push_str("\n[Install]\nWantedBy=timers.target\n");
+
+        units.push(Unit {
+            name: format!("updog-{}.timer", manager.id),
+            contents: timer,
+        });
+    }
+    units
+}
+
+// Write units into `dir`, returning the paths written
+pub fn write_units(units: &[Unit], dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    units
+        .iter()
+        .map(|unit| {
+            let path = dir.join(&unit.name);
+            fs::write(&path, &unit.contents)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn config() -> Config {
+        serde_yaml::from_str(
+            r#"
+            daemon:
+              every: daily
+              jitter: 15m
+              timezone: UTC
+            commands:
+              - id: brew
+                check: brew outdated
+                schedule: "0 9 * * MON"
+              - id: npm
+                check: npm outdated
+                every: 6h
+                jitter: 1h
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn unit<'a>(units: &'a [Unit], name: &str) -> &'a str {
+        &units.iter().find(|u| u.name == name).unwrap().contents
+    }
+
+    #[test]
+    fn test_generate_units() {
+        let units = generate(
+            &config(),
+            Path::new("/usr/bin/updog"),
+            Some(Path::new("/home/me/my config.yaml")),
+        );
+        assert_eq!(units.len(), 4);
+
+        let service = unit(&units, "updog-brew.service");
+        assert!(service.contains("Type=oneshot"));
+        assert!(service.contains(
+            "ExecStart=/usr/bin/updog --config \"/home/me/my config.yaml\" check --scheduled brew"
+        ));
+
+        let timer = unit(&units, "updog-brew.timer");
+        assert!(timer.contains("OnCalendar=Mon *-*-* 09:00:00 UTC\n"));
+        assert!(timer.contains("Persistent=true"));
+        assert!(timer.contains("RandomizedDelaySec=900s"));
+        assert!(timer.contains("WantedBy=timers.target"));
+
+        let timer = unit(&units, "updog-npm.timer");
+        assert!(timer.contains("OnUnitActiveSec=21600s"));
+        assert!(timer.contains("RandomizedDelaySec=3600s"));
+        assert!(!timer.contains("OnCalendar"));
+    }
+
+    #[test]
+    fn test_write_units() {
+        let dir = tempdir().unwrap();
+        let units = generate(&config(), Path::new("updog"), None);
+        let written = write_units(&units, &dir.path().join("user")).unwrap();
+        assert_eq!(written.len(), 4);
+        let service = fs::read_to_string(&written[0]).unwrap();
+        assert!(service.contains("ExecStart=updog check --scheduled brew"));
+    }
+}
@@ -0,0 +1,132 @@
+use std::io::IsTerminal;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const DEFAULT_WIDTH: usize = 80;
+// Narrower than this, wrapping does more harm than good
+const MIN_WIDTH: usize = 40;
+
+// Width to lay out tables and summaries for, or None when stdout isn't a
+// terminal, in which case output stays simple and unwrapped for pipes and logs.
+// `COLUMNS` overrides the detected width.
+pub fn width() -> Option<usize> {
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    let columns = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .or_else(window_width)
+        .unwrap_or(DEFAULT_WIDTH);
+    Some(columns.max(MIN_WIDTH))
+}
+
+#[cfg(unix)]
+fn window_width() -> Option<usize> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (result == 0 && size.ws_col > 0).then_some(size.ws_col as usize)
+}
+
+#[cfg(not(unix))]
+fn window_width() -> Option<usize> {
+    None
+}
+
+// Columns `text` takes up on screen (wide characters such as emoji count twice)
+pub fn display_width(text: &str) -> usize {
+    text.width()
+}
+
+// Cut `text` to at most `width` columns, marking the cut with "…"
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > width {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    if width > 0 {
+        out.push('…');
+    }
+    out
+}
+
+// Pad `text` with spaces to `width` columns
+pub fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(text.width())))
+}
+
+// Break `text` into lines of at most `width` columns, at spaces where
+// possible. Existing line breaks are kept.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        let mut used = 0;
+        for word in paragraph.split(' ') {
+            let word_width = word.width();
+            if used > 0 && used + 1 + word_width <= width {
+                line.push(' ');
+                line.push_str(word);
+                used += 1 + word_width;
+                continue;
+            }
+            if used > 0 {
+                lines.push(std::mem::take(&mut line));
+                used = 0;
+            }
+            // Words longer than a line are split wherever they hit the edge
+            for c in word.chars() {
+                let w = c.width().unwrap_or(0);
+                if used + w > width && used > 0 {
+                    lines.push(std::mem::take(&mut line));
+                    used = 0;
+                }
+                line.push(c);
+                used += w;
+            }
+        }
+        lines.push(line);
+    }
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_and_pad() {
+        assert_eq!(truncate("homebrew", 10), "homebrew");
+        assert_eq!(truncate("homebrew:cask", 8), "homebre…");
+        assert_eq!(display_width(&truncate("✅✅✅", 4)), 3);
+        assert_eq!(pad("apt", 5), "apt  ");
+        assert_eq!(pad("✅", 3), "✅ ");
+        assert_eq!(pad("homebrew", 3), "homebrew");
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(
+            wrap("Error: Command failed with exit status: 1", 16),
+            vec!["Error: Command", "failed with exit", "status: 1"]
+        );
+        assert_eq!(
+            wrap("see /very/long/path/to/a/log", 10),
+            vec!["see", "/very/long", "/path/to/a", "/log"]
+        );
+        assert_eq!(wrap("first\nsecond", 80), vec!["first", "second"]);
+        assert_eq!(wrap("", 80), vec![""]);
+    }
+}
@@ -0,0 +1,311 @@
+// Test support: a scripted executor and a controllable clock, so that
+// timing, retries and summaries can be tested without spawning shells.
+//
+// Enabled for this crate's own tests and for dependents via the `test-util` feature.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::executor::{Clock, CommandOutput, CommandRequest, Executor};
+use crate::package_manager::{UpdateError, UpdateErrorKind};
+
+// Clock that only moves when told to (or when something sleeps on it)
+#[derive(Debug)]
+pub struct FakeClock {
+    now: Mutex<SystemTime>,
+}
+
+impl Default for FakeClock {
+    fn default() -> Self {
+        // A fixed, recognizable starting point: 2024-01-01T00:00:00Z
+        Self::at(UNIX_EPOCH + Duration::from_secs(1_704_067_200))
+    }
+}
+
+impl FakeClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn at(time: SystemTime) -> Self {
+        Self {
+            now: Mutex::new(time),
+        }
+    }
+
+    // Move time forward
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now += duration;
+    }
+
+    pub fn set(&self, time: SystemTime) {
+        *self.now.lock().unwrap() = time;
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+// Declared behaviour of a scripted command
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptedCommand {
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub delay: Duration,
+    // Return an executor error (e.g. spawn failure) instead of an output
+    pub error: Option<String>,
+    pub error_kind: UpdateErrorKind,
+}
+
+impl ScriptedCommand {
+    pub fn success() -> Self {
+        Self::exit(0)
+    }
+
+    pub fn exit(code: i32) -> Self {
+        Self {
+            exit_code: Some(code),
+            stdout: String::new(),
+            stderr: String::new(),
+            delay: Duration::ZERO,
+            error: None,
+            error_kind: UpdateErrorKind::Failed,
+        }
+    }
+
+    // Process killed by a signal (no exit code)
+    pub fn killed() -> Self {
+        Self {
+            exit_code: None,
+            ..Self::success()
+        }
+    }
+
+    pub fn error(message: &str) -> Self {
+        Self {
+            error: Some(message.to_string()),
+            ..Self::success()
+        }
+    }
+
+    // Interrupted by a shutdown request
+    pub fn cancelled() -> Self {
+        Self {
+            error: Some("Operation was cancelled".to_string()),
+            error_kind: UpdateErrorKind::Cancelled,
+            ..Self::success()
+        }
+    }
+
+    pub fn stdout(mut self, stdout: &str) -> Self {
+        self.stdout = stdout.to_string();
+        self
+    }
+
+    pub fn stderr(mut self, stderr: &str) -> Self {
+        self.stderr = stderr.to_string();
+        self
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+// A recorded invocation of the scripted executor
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutedCommand {
+    pub command: String,
+    pub request: CommandRequest,
+    pub started_at: SystemTime,
+}
+
+// Executor that answers from a script instead of spawning processes.
+// Scripting the same command several times queues the responses; the last one
+// repeats once the queue is drained. Unscripted commands behave like a shell
+// that can't find the command (exit code 127). Every program is found,
+// except those declared `missing`.
+pub struct ScriptedExecutor {
+    clock: Arc<FakeClock>,
+    scripts: Mutex<HashMap<String, VecDeque<ScriptedCommand>>>,
+    calls: Mutex<Vec<ExecutedCommand>>,
+    missing: Mutex<Vec<String>>,
+    shutdown: AtomicBool,
+}
+
+impl ScriptedExecutor {
+    pub fn new(clock: Arc<FakeClock>) -> Self {
+        Self {
+            clock,
+            scripts: Mutex::new(HashMap::new()),
+            calls: Mutex::new(Vec::new()),
+            missing: Mutex::new(Vec::new()),
+            shutdown: AtomicBool::new(false),
+        }
+    }
+
+    // Behave as if an interrupt signal had been received
+    pub fn request_shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+    }
+
+    pub fn script(&self, command: &str, response: ScriptedCommand) -> &Self {
+        self.scripts
+            .lock()
+            .unwrap()
+            .entry(command.to_string())
+            .or_default()
+            .push_back(response);
+        self
+    }
+
+    // Behave as if `program` wasn't installed
+    pub fn missing(&self, program: &str) -> &Self {
+        self.missing.lock().unwrap().push(program.to_string());
+        self
+    }
+
+    pub fn calls(&self) -> Vec<ExecutedCommand> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    // Just the command strings, in execution order
+    pub fn commands(&self) -> Vec<String> {
+        self.calls().into_iter().map(|c| c.command).collect()
+    }
+
+    fn next_response(&self, command: &str) -> ScriptedCommand {
+        let mut scripts = self.scripts.lock().unwrap();
+        match scripts.get_mut(command) {
+            Some(queue) if queue.len() > 1 => queue.pop_front().unwrap(),
+            Some(queue) if !queue.is_empty() => queue[0].clone(),
+            _ => ScriptedCommand::exit(127).stderr(&format!("{}: command not scripted", command)),
+        }
+    }
+}
+
+impl Executor for ScriptedExecutor {
+    fn shutdown_requested(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    fn finds(&self, program: &str) -> bool {
+        !self.missing.lock().unwrap().iter().any(|p| p == program)
+    }
+
+    fn run(&self, request: &CommandRequest) -> Result<CommandOutput, UpdateError> {
+        self.calls.lock().unwrap().push(ExecutedCommand {
+            command: request.command.clone(),
+            request: request.clone(),
+            started_at: self.clock.now(),
+        });
+
+        let response = self.next_response(&request.command);
+        self.clock.advance(response.delay);
+
+        if let Some(message) = response.error {
+            return Err(UpdateError::with_kind(response.error_kind, message));
+        }
+
+        Ok(CommandOutput {
+            exit_code: response.exit_code,
+            stdout: response.stdout,
+            stderr: response.stderr,
+            duration: response.delay,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fake_clock_advances() {
+        let clock = FakeClock::new();
+        let start = clock.now();
+        clock.sleep(Duration::from_secs(30));
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(
+            clock.now().duration_since(start).unwrap(),
+            Duration::from_secs(60)
+        );
+    }
+
+    #[test]
+    fn test_scripted_responses_queue_and_repeat() {
+        let clock = Arc::new(FakeClock::new());
+        let executor = ScriptedExecutor::new(clock.clone());
+        executor
+            .script("flaky", ScriptedCommand::exit(1))
+            .script("flaky", ScriptedCommand::success().stdout("ok"));
+
+        assert_eq!(
+            executor
+                .run(&CommandRequest::new("flaky"))
+                .unwrap()
+                .exit_code,
+            Some(1)
+        );
+        assert_eq!(
+            executor.run(&CommandRequest::new("flaky")).unwrap().stdout,
+            "ok"
+        );
+        assert_eq!(
+            executor.run(&CommandRequest::new("flaky")).unwrap().stdout,
+            "ok"
+        );
+        assert_eq!(executor.commands(), vec!["flaky", "flaky", "flaky"]);
+    }
+
+    #[test]
+    fn test_scripted_delay_moves_clock() {
+        let clock = Arc::new(FakeClock::new());
+        let executor = ScriptedExecutor::new(clock.clone());
+        executor.script(
+            "slow",
+            ScriptedCommand::success().delay(Duration::from_secs(90)),
+        );
+
+        let start = clock.now();
+        let output = executor.run(&CommandRequest::new("slow")).unwrap();
+        assert_eq!(output.duration, Duration::from_secs(90));
+        assert_eq!(
+            clock.now().duration_since(start).unwrap(),
+            Duration::from_secs(90)
+        );
+    }
+
+    #[test]
+    fn test_unscripted_and_error_commands() {
+        let executor = ScriptedExecutor::new(Arc::new(FakeClock::new()));
+        assert_eq!(
+            executor
+                .run(&CommandRequest::new("unknown"))
+                .unwrap()
+                .exit_code,
+            Some(127)
+        );
+
+        executor.script("broken", ScriptedCommand::error("spawn failed"));
+        assert_eq!(
+            executor
+                .run(&CommandRequest::new("broken"))
+                .unwrap_err()
+                .message,
+            "spawn failed"
+        );
+    }
+}
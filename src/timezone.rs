@@ -0,0 +1,290 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Time zone schedules are evaluated in: the system's local zone (which
+// honors the TZ environment variable), UTC, or a fixed offset like "+09:00"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Timezone {
+    #[default]
+    Local,
+    Utc,
+    // Offset east of UTC in seconds
+    Fixed(i32),
+}
+
+impl TryFrom<String> for Timezone {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Timezone> for String {
+    fn from(timezone: Timezone) -> Self {
+        timezone.to_string()
+    }
+}
+
+impl std::str::FromStr for Timezone {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid timezone '{}' (expected local, UTC or an offset like +09:00)",
+                value
+            )
+        };
+        match value.trim() {
+            v if v.eq_ignore_ascii_case("local") => Ok(Timezone::Local),
+            v if v.eq_ignore_ascii_case("utc") || v == "Z" => Ok(Timezone::Utc),
+            v => {
+                let (sign, rest) = match v.as_bytes().first() {
+                    Some(b'+') => (1, &v[1..]),
+                    Some(b'-') => (-1, &v[1..]),
+                    _ => return Err(invalid()),
+                };
+                let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+                let hours: i32 = hours.parse().map_err(|_| invalid())?;
+                let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+                if hours > 14 || minutes >= 60 {
+                    return Err(invalid());
+                }
+                Ok(Timezone::Fixed(sign * (hours * 3600 + minutes * 60)))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Timezone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Timezone::Local => f.write_str("local"),
+            Timezone::Utc => f.write_str("UTC"),
+            Timezone::Fixed(offset) => f.write_str(&format_offset(*offset)),
+        }
+    }
+}
+
+fn format_offset(offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let offset = offset.abs();
+    format!("{}{:02}:{:02}", sign, offset / 3600, (offset % 3600) / 60)
+}
+
+fn unix_seconds(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    }
+}
+
+fn from_unix_seconds(secs: i64) -> SystemTime {
+    if secs >= 0 {
+        UNIX_EPOCH + Duration::from_secs(secs as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs())
+    }
+}
+
+#[cfg(unix)]
+fn local_offset(secs: i64) -> i32 {
+    let time = secs as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::localtime_r(&time, &mut tm) };
+    if result.is_null() {
+        0
+    } else {
+        tm.tm_gmtoff as i32
+    }
+}
+
+#[cfg(not(unix))]
+fn local_offset(_secs: i64) -> i32 {
+    0
+}
+
+impl Timezone {
+    // Offset from UTC in effect at the given instant, in seconds
+    pub fn offset_at(&self, time: SystemTime) -> i32 {
+        match self {
+            Timezone::Local => local_offset(unix_seconds(time)),
+            Timezone::Utc => 0,
+            Timezone::Fixed(offset) => *offset,
+        }
+    }
+
+    // Wall clock time in this zone
+    pub fn civil(&self, time: SystemTime) -> CivilTime {
+        let offset = self.offset_at(time);
+        CivilTime::from_unix(unix_seconds(time) + offset as i64, offset)
+    }
+
+    // Instant of a wall clock time in this zone. For times that don't exist
+    // or are ambiguous (DST changes) the offset of the nearby instant is used.
+    pub fn instant(&self, civil: &CivilTime) -> SystemTime {
+        self.instant_from_wall(civil.wall_seconds())
+    }
+
+    // Like `instant`, for wall clock seconds since 1970-01-01T00:00 in this zone
+    pub fn instant_from_wall(&self, wall: i64) -> SystemTime {
+        let guess = wall - self.offset_at(from_unix_seconds(wall)) as i64;
+        from_unix_seconds(wall - self.offset_at(from_unix_seconds(guess)) as i64)
+    }
+}
+
+// Calendar date and wall clock time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CivilTime {
+    pub year: i64,
+    // 1-12
+    pub month: u32,
+    // 1-31
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    // 0 = Sunday
+    pub weekday: u32,
+    // Offset from UTC this time was computed with, in seconds
+    pub offset: i32,
+}
+
+impl CivilTime {
+    // `wall` is seconds since the epoch in wall clock terms (UTC plus offset)
+    fn from_unix(wall: i64, offset: i32) -> Self {
+        let days = wall.div_euclid(86_400);
+        let secs = wall.rem_euclid(86_400) as u32;
+        let (year, month, day) = civil_from_days(days);
+        Self {
+            year,
+            month,
+            day,
+            hour: secs / 3600,
+            minute: (secs % 3600) / 60,
+            second: secs % 60,
+            weekday: (days + 4).rem_euclid(7) as u32,
+            offset,
+        }
+    }
+
+    // Seconds since 1970-01-01T00:00 in wall clock terms
+    pub fn wall_seconds(&self) -> i64 {
+        self.day_start_wall() + (self.hour * 3600 + self.minute * 60 + self.second) as i64
+    }
+
+    // Wall clock seconds of this day's midnight
+    pub fn day_start_wall(&self) -> i64 {
+        days_from_civil(self.year, self.month, self.day) * 86_400
+    }
+
+    // Wall clock seconds of midnight on the first day of the next month
+    pub fn next_month_start_wall(&self) -> i64 {
+        let (year, month) = if self.month == 12 {
+            (self.year + 1, 1)
+        } else {
+            (self.year, self.month + 1)
+        };
+        days_from_civil(year, month, 1) * 86_400
+    }
+}
+
+impl fmt::Display for CivilTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02} {}",
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            format_offset(self.offset)
+        )
+    }
+}
+
+pub fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        _ => 31,
+    }
+}
+
+// Days since 1970-01-01 to (year, month, day); Howard Hinnant's algorithm
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = month as i64;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timezone() {
+        assert_eq!("local".parse::<Timezone>().unwrap(), Timezone::Local);
+        assert_eq!("UTC".parse::<Timezone>().unwrap(), Timezone::Utc);
+        assert_eq!(
+            "+09:00".parse::<Timezone>().unwrap(),
+            Timezone::Fixed(9 * 3600)
+        );
+        assert_eq!(
+            "-05:30".parse::<Timezone>().unwrap(),
+            Timezone::Fixed(-19_800)
+        );
+        assert_eq!(Timezone::Fixed(-19_800).to_string(), "-05:30");
+        assert!("Mars/Olympus".parse::<Timezone>().is_err());
+        assert!("+25:00".parse::<Timezone>().is_err());
+    }
+
+    #[test]
+    fn test_civil_conversion() {
+        // 2024-02-29T13:45:10Z, a Thursday
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_214_310);
+        let civil = Timezone::Utc.civil(time);
+        assert_eq!((civil.year, civil.month, civil.day), (2024, 2, 29));
+        assert_eq!((civil.hour, civil.minute, civil.second), (13, 45, 10));
+        assert_eq!(civil.weekday, 4);
+        assert_eq!(civil.to_string(), "2024-02-29 13:45 +00:00");
+        assert_eq!(Timezone::Utc.instant(&civil), time);
+
+        // Crossing midnight into the next day with a positive offset
+        let sydney = Timezone::Fixed(11 * 3600).civil(time);
+        assert_eq!((sydney.month, sydney.day, sydney.hour), (3, 1, 0));
+        assert_eq!(sydney.weekday, 5);
+        assert_eq!(Timezone::Fixed(11 * 3600).instant(&sydney), time);
+    }
+
+    #[test]
+    fn test_days_in_month() {
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(1900, 2), 28);
+        assert_eq!(days_in_month(2000, 2), 29);
+        assert_eq!(days_in_month(2023, 11), 30);
+    }
+}
@@ -0,0 +1,588 @@
+use ratatui::crossterm::event::{self, Event as TermEvent, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::Frame;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::output::strip_ansi;
+use crate::package_manager::PackageManager;
+use crate::report::{ExecutionReport, ExecutionResult, ExecutionStatus, Operation};
+use crate::stats::format_duration;
+
+// `updog tui`: the config's managers and their subcommands in a list, with
+// the output of the selected one's latest check or update beside it, as it
+// runs. Entries run side by side when started one after another.
+//
+//   ↑/↓ or k/j   select an entry
+//   c / u        check / update it
+//   x            stop its commands
+//   PgUp/PgDn    scroll its output (End follows it again)
+//   q            quit (twice to stop what's running and quit)
+//
+// Commands get an empty stdin, as with `update --keys`, so they can't prompt.
+
+// Lines kept of an entry's output, the oldest dropped first
+const SCROLLBACK: usize = 5000;
+
+// How often the screen is redrawn while waiting for keys
+const TICK: Duration = Duration::from_millis(100);
+
+// What the threads running entries tell the screen
+enum Event {
+    Output(usize, Vec<u8>),
+    Finished(usize, Box<ExecutionResult>),
+}
+
+// What a key asks the loop to do, beyond changing what's shown
+#[derive(Debug, PartialEq)]
+enum Request {
+    Start(usize, Operation),
+    Stop(usize),
+    StopAll,
+}
+
+// A manager, or one of its subcommands
+struct Entry {
+    manager: String,
+    subcommand: Option<String>,
+    description: String,
+    // Output of the latest run, in complete lines
+    lines: Vec<String>,
+    // Output after its last newline
+    partial: Vec<u8>,
+    // Lines scrolled back from the end; 0 follows the output
+    scroll: usize,
+    running: Option<Operation>,
+    result: Option<ExecutionResult>,
+    // Streams into this entry's pane, made when it first runs
+    pm: Option<PackageManager>,
+}
+
+impl Entry {
+    fn name(&self) -> String {
+        match &self.subcommand {
+            Some(sc) => format!("{}:{}", self.manager, sc),
+            None => self.manager.clone(),
+        }
+    }
+
+    // The name in the list, subcommands indented under their manager
+    fn label(&self) -> String {
+        match &self.subcommand {
+            Some(sc) => format!("  :{}", sc),
+            None => self.manager.clone(),
+        }
+    }
+
+    // Progress bars rewrite their line after a carriage return, so only
+    // what follows the last one is shown
+    fn push_output(&mut self, chunk: &[u8]) {
+        self.partial.extend_from_slice(chunk);
+        while let Some(end) = self.partial.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=end).collect();
+            let line = visible(&String::from_utf8_lossy(&line[..end]));
+            self.lines.push(line);
+            if self.scroll > 0 {
+                self.scroll += 1;
+            }
+        }
+        if self.lines.len() > SCROLLBACK {
+            self.lines.drain(..self.lines.len() - SCROLLBACK);
+        }
+        self.scroll = self.scroll.min(self.lines.len());
+    }
+
+    // The output lines, the partial one included
+    fn output(&self) -> Vec<String> {
+        let mut lines = self.lines.clone();
+        if !self.partial.is_empty() {
+            lines.push(visible(&String::from_utf8_lossy(&self.partial)));
+        }
+        lines
+    }
+
+    // What the list shows next to the name
+    fn status(&self) -> (String, Color) {
+        if let Some(operation) = self.running {
+            let verb = match operation {
+                Operation::Check => "checking",
+                Operation::Update => "updating",
+            };
+            return (format!("{}…", verb), Color::Yellow);
+        }
+        let Some(result) = &self.result else {
+            return (String::new(), Color::Reset);
+        };
+        match &result.status {
+            ExecutionStatus::Success => ("ok".to_string(), Color::Green),
+            ExecutionStatus::UpdatesAvailable { count } => {
+                (format!("{} pending", count), Color::Cyan)
+            }
+            ExecutionStatus::Skipped { .. } => ("skipped".to_string(), Color::DarkGray),
+            ExecutionStatus::Cancelled => ("stopped".to_string(), Color::DarkGray),
+            status => (status.label().replace('_', " "), Color::Red),
+        }
+    }
+}
+
+// A line as a terminal would show it, without colors
+fn visible(line: &str) -> String {
+    let line = line.trim_end_matches('\r');
+    strip_ansi(line.rsplit('\r').next().unwrap_or(line))
+}
+
+struct App {
+    entries: Vec<Entry>,
+    selected: usize,
+    // Shown instead of the key help until the next key
+    message: Option<String>,
+    // q was pressed with entries running; the next q stops them
+    quitting: bool,
+    quit: bool,
+}
+
+impl App {
+    fn new(config: &Config) -> Self {
+        let entry = |manager: &str, subcommand: Option<&str>, description: &Option<String>| Entry {
+            manager: manager.to_string(),
+            subcommand: subcommand.map(str::to_string),
+            description: description.clone().unwrap_or_default(),
+            lines: Vec::new(),
+            partial: Vec::new(),
+            scroll: 0,
+            running: None,
+            result: None,
+            pm: None,
+        };
+        let mut entries = Vec::new();
+        for manager in config.managers() {
+            entries.push(entry(&manager.id, None, &manager.description));
+            for sc in &manager.subcommands {
+                entries.push(entry(&manager.id, Some(&sc.id), &sc.description));
+            }
+        }
+        Self {
+            entries,
+            selected: 0,
+            message: None,
+            quitting: false,
+            quit: false,
+        }
+    }
+
+    fn running(&self) -> usize {
+        self.entries.iter().filter(|e| e.running.is_some()).count()
+    }
+
+    fn key(&mut self, code: KeyCode, height: usize) -> Option<Request> {
+        self.message = None;
+        let last = self.entries.len().saturating_sub(1);
+        let page = height.saturating_sub(3).max(1);
+        let selected = self.entries.get_mut(self.selected);
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => {
+                if let Some(entry) = selected {
+                    entry.scroll = (entry.scroll + page).min(entry.lines.len());
+                }
+            }
+            KeyCode::PageDown => {
+                if let Some(entry) = selected {
+                    entry.scroll = entry.scroll.saturating_sub(page);
+                }
+            }
+            KeyCode::Home => {
+                if let Some(entry) = selected {
+                    entry.scroll = entry.lines.len();
+                }
+            }
+            KeyCode::End => {
+                if let Some(entry) = selected {
+                    entry.scroll = 0;
+                }
+            }
+            KeyCode::Char(key @ ('c' | 'u')) => {
+                let entry = selected?;
+                if entry.running.is_some() {
+                    self.message = Some(format!("{} is still running", entry.name()));
+                    return None;
+                }
+                let operation = match key {
+                    'c' => Operation::Check,
+                    _ => Operation::Update,
+                };
+                entry.running = Some(operation);
+                entry.result = None;
+                entry.lines.clear();
+                entry.partial.clear();
+                entry.scroll = 0;
+                self.quitting = false;
+                return Some(Request::Start(self.selected, operation));
+            }
+            KeyCode::Char('x') => {
+                let entry = selected?;
+                return entry
+                    .running
+                    .is_some()
+                    .then_some(Request::Stop(self.selected));
+            }
+            KeyCode::Char('q') | KeyCode::Esc => match self.running() {
+                0 => self.quit = true,
+                _ if self.quitting => return Some(Request::StopAll),
+                running => {
+                    self.quitting = true;
+                    self.message = Some(format!(
+                        "{} still running: q again stops {} and quits",
+                        running,
+                        if running == 1 { "it" } else { "them" }
+                    ));
+                }
+            },
+            _ => {}
+        }
+        None
+    }
+
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Output(index, chunk) => self.entries[index].push_output(&chunk),
+            Event::Finished(index, result) => {
+                let entry = &mut self.entries[index];
+                if !entry.partial.is_empty() {
+                    entry.push_output(b"\n");
+                }
+                entry.push_output(
+                    format!(
+                        "\n── {} ({})\n",
+                        result.summary_line(),
+                        format_duration(result.duration)
+                    )
+                    .as_bytes(),
+                );
+                for warning in &result.warnings {
+                    entry.push_output(format!("⚠ {}\n", warning).as_bytes());
+                }
+                entry.running = None;
+                entry.result = Some(*result);
+                if self.quitting && self.running() == 0 {
+                    self.quit = true;
+                }
+            }
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let statuses: Vec<(String, Color)> = self.entries.iter().map(Entry::status).collect();
+        let name_width = self
+            .entries
+            .iter()
+            .map(|e| e.label().chars().count())
+            .max()
+            .unwrap_or(0);
+        let status_width = statuses
+            .iter()
+            .map(|(s, _)| s.chars().count())
+            .max()
+            .unwrap_or(0);
+        let list_width = (name_width + status_width + 5) as u16;
+        let [list_area, pane_area] =
+            Layout::horizontal([Constraint::Max(list_width.max(20)), Constraint::Min(20)])
+                .areas(main);
+
+        let items: Vec<ListItem> = self
+            .entries
+            .iter()
+            .zip(&statuses)
+            .map(|(entry, (status, color))| {
+                let name = entry.label();
+                let padding = (name_width + 1).saturating_sub(name.chars().count());
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("{}{}", name, " ".repeat(padding))),
+                    Span::styled(status.clone(), Style::default().fg(*color)),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(" updog "))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(list, list_area, &mut state);
+
+        if let Some(entry) = self.entries.get(self.selected) {
+            let title = match (&entry.running, &entry.result) {
+                (Some(operation), _) => format!(" {} {} ", operation, entry.name()),
+                (None, Some(_)) => format!(" {} (done) ", entry.name()),
+                (None, None) => format!(" {} ", entry.name()),
+            };
+            let height = pane_area.height.saturating_sub(2) as usize;
+            let output = entry.output();
+            let lines: Vec<Line> = if output.is_empty() && entry.running.is_none() {
+                let mut hint = Vec::new();
+                if !entry.description.is_empty() {
+                    hint.push(Line::raw(entry.description.clone()));
+                    hint.push(Line::raw(""));
+                }
+                hint.push(Line::styled(
+                    "c checks, u updates",
+                    Style::default().fg(Color::DarkGray),
+                ));
+                hint
+            } else {
+                let end = output.len().saturating_sub(entry.scroll);
+                let start = end.saturating_sub(height);
+                output[start..end].iter().cloned().map(Line::raw).collect()
+            };
+            frame.render_widget(
+                Paragraph::new(lines).block(Block::bordered().title(title)),
+                pane_area,
+            );
+        }
+
+        let help = match &self.message {
+            Some(message) => Line::styled(message.clone(), Style::default().fg(Color::Yellow)),
+            None => Line::styled(
+                " ↑↓ select  c check  u update  x stop  PgUp/PgDn scroll  q quit",
+                Style::default().fg(Color::DarkGray),
+            ),
+        };
+        frame.render_widget(Paragraph::new(help), footer);
+    }
+}
+
+// Start the selected entry's check or update on a thread of `scope`,
+// handing its output and result to `events`. The report of each run goes
+// to `on_report` (for history).
+fn start<'scope, 'env>(
+    scope: &'scope std::thread::Scope<'scope, 'env>,
+    app: &mut App,
+    pm: &PackageManager,
+    index: usize,
+    operation: Operation,
+    events: &Sender<Event>,
+    on_report: &'env (dyn Fn(&ExecutionReport) + Sync),
+) {
+    let entry = &mut app.entries[index];
+    let runner = entry
+        .pm
+        .get_or_insert_with(|| {
+            let output = events.clone();
+            pm.streaming_to(Arc::new(move |chunk: &[u8]| {
+                let _ = output.send(Event::Output(index, chunk.to_vec()));
+            }))
+        })
+        .clone();
+    let (manager, subcommand) = (entry.manager.clone(), entry.subcommand.clone());
+    let events = events.clone();
+    scope.spawn(move || {
+        let mut report = ExecutionReport::new(operation, runner.clock().now());
+        let runner = runner.for_run(&report.run_id);
+        let result = runner.execute(operation, &manager, subcommand.as_deref());
+        report.results.push(result.clone());
+        on_report(&report);
+        let _ = events.send(Event::Finished(index, Box::new(result)));
+    });
+}
+
+// Run the TUI until it's quit, on the terminal's alternate screen
+pub fn run(
+    pm: &PackageManager,
+    on_report: &(dyn Fn(&ExecutionReport) + Sync),
+) -> std::io::Result<()> {
+    let mut app = App::new(&pm.config);
+    if app.entries.is_empty() {
+        return Err(std::io::Error::other("the config has no package managers"));
+    }
+    let mut terminal = ratatui::try_init()?;
+    let (events, received): (Sender<Event>, Receiver<Event>) = mpsc::channel();
+    let result = std::thread::scope(|scope| -> std::io::Result<()> {
+        while !app.quit {
+            terminal.draw(|frame| app.draw(frame))?;
+            if event::poll(TICK)? {
+                if let TermEvent::Key(key) = event::read()? {
+                    if key.kind != KeyEventKind::Press {
+                        continue;
+                    }
+                    // Raw mode turns Ctrl-C into a key
+                    let code = match key.code {
+                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            KeyCode::Char('q')
+                        }
+                        code => code,
+                    };
+                    let height = terminal.size()?.height as usize;
+                    match app.key(code, height) {
+                        Some(Request::Start(index, operation)) => {
+                            start(scope, &mut app, pm, index, operation, &events, on_report)
+                        }
+                        Some(Request::Stop(index)) => {
+                            if let Some(runner) = &app.entries[index].pm {
+                                runner.interrupt();
+                            }
+                        }
+                        Some(Request::StopAll) => {
+                            for runner in app.entries.iter().filter_map(|e| e.pm.as_ref()) {
+                                runner.interrupt();
+                            }
+                        }
+                        None => {}
+                    }
+                }
+            }
+            while let Ok(event) = received.try_recv() {
+                app.event(event);
+            }
+        }
+        Ok(())
+    });
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn app() -> App {
+        let config: Config = serde_yaml::from_str(
+            r#"
+            commands:
+              - id: brew
+                description: Homebrew formulae and casks
+                subcommands:
+                  - id: formulae
+                    update: brew upgrade --formula
+                  - id: casks
+                    update: brew upgrade --cask
+              - id: npm
+                check: npm outdated -g
+              - id: dev
+                type: group
+                members: [npm]
+            "#,
+        )
+        .unwrap();
+        App::new(&config)
+    }
+
+    fn result(status: ExecutionStatus, message: &str) -> Box<ExecutionResult> {
+        Box::new(ExecutionResult {
+            status,
+            message: message.to_string(),
+            duration: Duration::from_secs(3),
+            ..ExecutionResult::skipped("npm".to_string(), None, "", String::new())
+        })
+    }
+
+    #[test]
+    fn test_entries_and_keys() {
+        let mut app = app();
+        let names: Vec<String> = app.entries.iter().map(Entry::name).collect();
+        assert_eq!(names, ["brew", "brew:formulae", "brew:casks", "npm"]);
+
+        assert_eq!(app.key(KeyCode::Up, 20), None);
+        for _ in 0..5 {
+            app.key(KeyCode::Char('j'), 20);
+        }
+        assert_eq!(app.selected, 3);
+        assert_eq!(
+            app.key(KeyCode::Char('c'), 20),
+            Some(Request::Start(3, Operation::Check))
+        );
+        assert_eq!(app.key(KeyCode::Char('u'), 20), None);
+        assert_eq!(app.message.as_deref(), Some("npm is still running"));
+        assert_eq!(app.key(KeyCode::Char('x'), 20), Some(Request::Stop(3)));
+
+        // Quitting while something runs takes a second q
+        assert_eq!(app.key(KeyCode::Char('q'), 20), None);
+        assert!(!app.quit);
+        assert_eq!(app.key(KeyCode::Char('q'), 20), Some(Request::StopAll));
+        app.event(Event::Finished(
+            3,
+            result(ExecutionStatus::Cancelled, "Operation was cancelled"),
+        ));
+        assert!(app.quit);
+    }
+
+    #[test]
+    fn test_output() {
+        let mut app = app();
+        app.selected = 3;
+        app.key(KeyCode::Char('c'), 20);
+        for chunk in [
+            &b"\x1b[1mPackage\x1b[0m  Current\nlodash  4.17.20"[..],
+            b"\ndownloading 10%\rdownloading 100%\r\n\xe2\x9c",
+            b"\x93 done",
+        ] {
+            app.event(Event::Output(3, chunk.to_vec()));
+        }
+        assert_eq!(
+            app.entries[3].output(),
+            [
+                "Package  Current",
+                "lodash  4.17.20",
+                "downloading 100%",
+                "✓ done"
+            ]
+        );
+        app.event(Event::Finished(
+            3,
+            result(
+                ExecutionStatus::UpdatesAvailable { count: 1 },
+                "1 updates available",
+            ),
+        ));
+        let entry = &app.entries[3];
+        assert_eq!(entry.status(), ("1 pending".to_string(), Color::Cyan));
+        assert_eq!(entry.output()[4..], ["", "── 1 updates available (3.0s)"]);
+
+        // Scrolling back holds the view while output comes in
+        app.key(KeyCode::PageUp, 5);
+        assert_eq!(app.entries[3].scroll, 2);
+        app.event(Event::Output(3, b"more\n".to_vec()));
+        assert_eq!(app.entries[3].scroll, 3);
+        app.key(KeyCode::End, 5);
+        assert_eq!(app.entries[3].scroll, 0);
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut app = app();
+        app.key(KeyCode::Char('c'), 20);
+        app.event(Event::Output(
+            0,
+            b"==> Upgrading 2 outdated packages\n".to_vec(),
+        ));
+        let mut terminal = Terminal::new(TestBackend::new(80, 8)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let screen: Vec<String> = terminal
+            .backend()
+            .buffer()
+            .content()
+            .chunks(80)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect();
+        assert!(screen[0].starts_with("┌ updog ───"), "{:?}", screen);
+        assert!(screen[0].contains("┌ check brew ───"), "{:?}", screen);
+        assert!(
+            screen[1].starts_with("│brew        checking…  ││==> Upgrading 2 outdated packages  "),
+            "{:?}",
+            screen
+        );
+        assert!(
+            screen[2].starts_with("│  :formulae            ││   "),
+            "{:?}",
+            screen
+        );
+        assert!(screen[7].starts_with(" ↑↓ select  c check"), "{:?}", screen);
+    }
+}
@@ -0,0 +1,51 @@
+use std::cell::RefCell;
+use std::fmt;
+
+// Warnings worth seeing after a run: things that didn't fail it, but that
+// shouldn't scroll past in the log either (truncated output, a dirty git
+// tree, a skipped image). They are logged like any other warning and, while
+// `collect` runs on the same thread, recorded for the run's summary and
+// history.
+thread_local! {
+    static COLLECTED: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+// Log `message` and record it with the run being collected, if any
+pub fn warn(message: impl fmt::Display) {
+    let message = message.to_string();
+    tracing::warn!("{}", message);
+    COLLECTED.with(|collected| {
+        if let Some(warnings) = collected.borrow_mut().as_mut() {
+            warnings.push(message);
+        }
+    });
+}
+
+// Run `run`, returning what it returned and the warnings it raised
+pub fn collect<T>(run: impl FnOnce() -> T) -> (T, Vec<String>) {
+    let outer = COLLECTED.with(|collected| collected.replace(Some(Vec::new())));
+    let result = run();
+    let warnings = COLLECTED
+        .with(|collected| collected.replace(outer))
+        .unwrap_or_default();
+    (result, warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect() {
+        warn("not collected");
+        let (value, warnings) = collect(|| {
+            warn("outer");
+            let ((), inner) = collect(|| warn("inner"));
+            assert_eq!(inner, ["inner"]);
+            warn(format_args!("{} {}", "outer", 2));
+            42
+        });
+        assert_eq!(value, 42);
+        assert_eq!(warnings, ["outer", "outer 2"]);
+    }
+}
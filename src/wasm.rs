@@ -0,0 +1,237 @@
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use wasmi::{
+    Caller, Engine, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+};
+
+use crate::report::Operation;
+
+// Output parser compiled to WebAssembly, run in a sandbox
+//
+//   commands:
+//     - id: homebrew
+//       check: brew outdated --json=v2
+//       parser: ./parsers/brew.wasm     # relative to the config file
+//
+// The module can't touch the host: its only imports are, from "updog",
+//   read_stdin(ptr: i32, len: i32) -> i32   copy up to `len` input bytes, 0 at the end
+//   write_stdout(ptr: i32, len: i32)        append to the output
+// and it must export `memory` and `parse: () -> ()`. The input is
+// {"manager": ..., "operation": ..., "output": ...}, with operation "check"
+// for a check's output or "update" for an update's; the output is the same
+// JSON as a parser plugin's, {"updates": [{"name", "current", "latest"}]}
+// (for an update, the packages it upgraded). CPU time, memory and output size
+// are capped.
+
+// Instruction budget: plenty for parsing, but ends endless loops
+const FUEL: u64 = 1_000_000_000;
+const MEMORY_LIMIT: usize = 64 << 20;
+const OUTPUT_LIMIT: usize = 16 << 20;
+
+#[derive(Debug, Error)]
+pub enum WasmError {
+    #[error("Failed to read parser {path}: {source}")]
+    IoError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Parser {path} failed: {message}")]
+    RuntimeError { path: PathBuf, message: String },
+}
+
+// Whether a manager's `parser` names a WASM module rather than a plugin
+pub fn is_wasm_parser(parser: &str) -> bool {
+    parser.ends_with(".wasm")
+}
+
+// State the host functions work on
+struct Sandbox {
+    input: Vec<u8>,
+    position: usize,
+    output: Vec<u8>,
+    limits: StoreLimits,
+}
+
+fn memory(caller: &Caller<'_, Sandbox>) -> Result<Memory, wasmi::Error> {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| wasmi::Error::new("module doesn't export its memory"))
+}
+
+fn read_stdin(mut caller: Caller<'_, Sandbox>, ptr: i32, len: i32) -> Result<i32, wasmi::Error> {
+    let memory = memory(&caller)?;
+    let sandbox = caller.data();
+    let end = sandbox
+        .input
+        .len()
+        .min(sandbox.position + len.max(0) as usize);
+    let chunk = sandbox.input[sandbox.position..end].to_vec();
+    memory
+        .write(&mut caller, ptr as u32 as usize, &chunk)
+        .map_err(|e| wasmi::Error::new(e.to_string()))?;
+    caller.data_mut().position = end;
+    Ok(chunk.len() as i32)
+}
+
+fn write_stdout(mut caller: Caller<'_, Sandbox>, ptr: i32, len: i32) -> Result<(), wasmi::Error> {
+    let memory = memory(&caller)?;
+    let mut chunk = vec![0; len.max(0) as usize];
+    memory
+        .read(&caller, ptr as u32 as usize, &mut chunk)
+        .map_err(|e| wasmi::Error::new(e.to_string()))?;
+    let output = &mut caller.data_mut().output;
+    if output.len() + chunk.len() > OUTPUT_LIMIT {
+        return Err(wasmi::Error::new("output limit exceeded"));
+    }
+    output.extend_from_slice(&chunk);
+    Ok(())
+}
+
+// Run the parser at `path` on the output of `operation` and return what it
+// wrote
+pub fn run_parser(
+    path: &Path,
+    manager: &str,
+    operation: Operation,
+    output: &str,
+) -> Result<String, WasmError> {
+    let input = serde_json::json!({
+        "manager": manager,
+        "operation": operation,
+        "output": output,
+    });
+    run(path, &input.to_string(), FUEL)
+}
+
+fn run(path: &Path, input: &str, fuel: u64) -> Result<String, WasmError> {
+    let wasm = std::fs::read(path).map_err(|source| WasmError::IoError {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let failed = |e: wasmi::Error| WasmError::RuntimeError {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    };
+
+    let mut config = wasmi::Config::default();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config);
+    let module = Module::new(&engine, &wasm).map_err(failed)?;
+
+    let mut store = Store::new(
+        &engine,
+        Sandbox {
+            input: input.as_bytes().to_vec(),
+            position: 0,
+            output: Vec::new(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MEMORY_LIMIT)
+                .instances(1)
+                .build(),
+        },
+    );
+    store.limiter(|sandbox| &mut sandbox.limits);
+    store.set_fuel(fuel).map_err(|e| failed(e.into()))?;
+
+    let mut linker = Linker::new(&engine);
+    linker
+        .func_wrap("updog", "read_stdin", read_stdin)
+        .and_then(|linker| linker.func_wrap("updog", "write_stdout", write_stdout))
+        .map_err(|e| failed(e.into()))?;
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .and_then(|pre| pre.start(&mut store))
+        .map_err(failed)?;
+    instance
+        .get_typed_func::<(), ()>(&store, "parse")
+        .and_then(|parse| parse.call(&mut store, ()))
+        .map_err(failed)?;
+
+    String::from_utf8(store.into_data().output).map_err(|_| WasmError::RuntimeError {
+        path: path.to_path_buf(),
+        message: "output isn't valid UTF-8".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    // Copies its input to the output
+    const ECHO: &str = r#"
+        (module
+          (import "updog" "read_stdin" (func $read (param i32 i32) (result i32)))
+          (import "updog" "write_stdout" (func $write (param i32 i32)))
+          (memory (export "memory") 1)
+          (func (export "parse")
+            (local $n i32)
+            (loop $more
+              (local.set $n (call $read (i32.const 0) (i32.const 4096)))
+              (if (i32.gt_s (local.get $n) (i32.const 0))
+                (then
+                  (call $write (i32.const 0) (local.get $n))
+                  (br $more))))))
+    "#;
+
+    const SPIN: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "parse") (loop $forever (br $forever))))
+    "#;
+
+    const ESCAPE: &str = r#"
+        (module
+          (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (func (export "parse")))
+    "#;
+
+    fn module(dir: &Path, name: &str, wat: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, wat::parse_str(wat).unwrap()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_stdin_stdout_contract() {
+        let dir = tempdir().unwrap();
+        let echo = module(dir.path(), "echo.wasm", ECHO);
+        let output = run_parser(&echo, "brew", Operation::Update, "git 2.40 < 2.41\n").unwrap();
+        let input: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(input["manager"], "brew");
+        assert_eq!(input["operation"], "update");
+        assert_eq!(input["output"], "git 2.40 < 2.41\n");
+    }
+
+    #[test]
+    fn test_sandbox_limits() {
+        let dir = tempdir().unwrap();
+        let spin = module(dir.path(), "spin.wasm", SPIN);
+        let err = run(&spin, "{}", 1_000_000).unwrap_err();
+        assert!(err.to_string().contains("fuel"), "{}", err);
+
+        // Nothing but the updog imports can be linked
+        let err = run_parser(
+            &module(dir.path(), "wasi.wasm", ESCAPE),
+            "brew",
+            Operation::Check,
+            "",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("fd_write"), "{}", err);
+
+        assert!(matches!(
+            run_parser(
+                &dir.path().join("missing.wasm"),
+                "brew",
+                Operation::Check,
+                ""
+            ),
+            Err(WasmError::IoError { .. })
+        ));
+        assert!(is_wasm_parser("./parsers/brew.wasm"));
+        assert!(!is_wasm_parser("flatpak"));
+    }
+}
@@ -0,0 +1,233 @@
+use std::time::Duration;
+
+use crate::output::strip_ansi;
+use crate::package_manager::PackageManager;
+use crate::report::{ExecutionResult, Operation};
+
+// Longest uninterrupted sleep, so that shutdown requests are noticed quickly
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// What a check found, reduced to the parts compared between polls
+#[derive(Debug, Clone, PartialEq)]
+struct Snapshot {
+    pending: Option<usize>,
+    lines: Vec<String>,
+}
+
+impl Snapshot {
+    fn of(result: &ExecutionResult) -> Self {
+        Self {
+            pending: result.pending(),
+            lines: strip_ansi(&result.output)
+                .lines()
+                .map(str::trim_end)
+                .filter(|line| !line.is_empty())
+                .map(String::from)
+                .collect(),
+        }
+    }
+}
+
+// Outcome of one watch poll
+#[derive(Debug)]
+pub enum WatchEvent {
+    // The first check
+    Initial(ExecutionResult),
+    // The check found something different from the previous poll
+    Changed {
+        result: ExecutionResult,
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    Unchanged(ExecutionResult),
+}
+
+impl WatchEvent {
+    pub fn result(&self) -> &ExecutionResult {
+        match self {
+            WatchEvent::Initial(result)
+            | WatchEvent::Changed { result, .. }
+            | WatchEvent::Unchanged(result) => result,
+        }
+    }
+
+    pub fn is_change(&self) -> bool {
+        matches!(self, WatchEvent::Changed { .. })
+    }
+
+    // Text printed for this event (nothing when unchanged)
+    pub fn render(&self, time: &str) -> String {
+        match self {
+            WatchEvent::Initial(result) => {
+                format!("[{}] {}: {}\n", time, result.name(), result.message)
+            }
+            WatchEvent::Changed {
+                result,
+                added,
+                removed,
+            } => {
+                let mut out = format!("[{}] {}: {}\n", time, result.name(), result.message);
+                for line in removed {
+                    out.push_str(&format!("  - {}\n", line));
+                }
+                for line in added {
+                    out.push_str(&format!("  + {}\n", line));
+                }
+                out
+            }
+            WatchEvent::Unchanged(_) => String::new(),
+        }
+    }
+}
+
+// Repeatedly checks one package manager and reports when the result changes
+pub struct Watcher<'a> {
+    pm: &'a PackageManager,
+    manager: String,
+    subcommand: Option<String>,
+    last: Option<Snapshot>,
+}
+
+impl<'a> Watcher<'a> {
+    pub fn new(pm: &'a PackageManager, manager: String, subcommand: Option<String>) -> Self {
+        Self {
+            pm,
+            manager,
+            subcommand,
+            last: None,
+        }
+    }
+
+    // Run one check and compare it with the previous one
+    pub fn poll(&mut self) -> WatchEvent {
+        let result = self
+            .pm
+            .execute(Operation::Check, &self.manager, self.subcommand.as_deref());
+        let snapshot = Snapshot::of(&result);
+
+        let event = match &self.last {
+            None => WatchEvent::Initial(result),
+            Some(last) if *last == snapshot => WatchEvent::Unchanged(result),
+            Some(last) => WatchEvent::Changed {
+                added: snapshot
+                    .lines
+                    .iter()
+                    .filter(|line| !last.lines.contains(line))
+                    .cloned()
+                    .collect(),
+                removed: last
+                    .lines
+                    .iter()
+                    .filter(|line| !snapshot.lines.contains(line))
+                    .cloned()
+                    .collect(),
+                result,
+            },
+        };
+        self.last = Some(snapshot);
+        event
+    }
+
+    // Poll every `interval` until shutdown is requested or `on_event`
+    // returns false
+    pub fn run(&mut self, interval: Duration, mut on_event: impl FnMut(&WatchEvent) -> bool) {
+        let clock = self.pm.clock();
+        while !self.pm.shutdown_requested() {
+            let started = clock.now();
+            if !on_event(&self.poll()) {
+                return;
+            }
+
+            let next = started + interval;
+            while !self.pm.shutdown_requested() {
+                let now = clock.now();
+                match next.duration_since(now) {
+                    Ok(wait) if !wait.is_zero() => clock.sleep(wait.min(POLL_INTERVAL)),
+                    _ => break,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::executor::Clock;
+    use crate::test_util::{FakeClock, ScriptedCommand, ScriptedExecutor};
+    use std::sync::Arc;
+
+    fn setup(responses: &[&str]) -> (Arc<FakeClock>, Arc<ScriptedExecutor>, PackageManager) {
+        let config: Config = serde_yaml::from_str(
+            r#"
+            commands:
+              - id: apt
+                check: apt list --upgradable
+            "#,
+        )
+        .unwrap();
+        let clock = Arc::new(FakeClock::new());
+        let executor = Arc::new(ScriptedExecutor::new(clock.clone()));
+        for output in responses {
+            executor.script(
+                "apt list --upgradable",
+                ScriptedCommand::success().stdout(output),
+            );
+        }
+        let mut pm = PackageManager::with_executor(config, executor.clone(), clock.clone());
+        pm.echo_output = false;
+        (clock, executor, pm)
+    }
+
+    #[test]
+    fn test_reports_changes_only() {
+        let (_clock, _executor, pm) = setup(&["", "", "curl 8.5\n", "curl 8.5\nvim 9.1\n"]);
+        let mut watcher = Watcher::new(&pm, "apt".to_string(), None);
+
+        assert!(matches!(watcher.poll(), WatchEvent::Initial(_)));
+        assert!(matches!(watcher.poll(), WatchEvent::Unchanged(_)));
+
+        match watcher.poll() {
+            WatchEvent::Changed { added, removed, .. } => {
+                assert_eq!(added, vec!["curl 8.5"]);
+                assert!(removed.is_empty());
+            }
+            other => panic!("expected a change, got {:?}", other),
+        }
+
+        let event = watcher.poll();
+        assert!(event.is_change());
+        let text = event.render("12:00");
+        assert!(text.starts_with("[12:00] apt: 2 updates available\n"));
+        assert!(text.contains("  + vim 9.1\n"));
+        assert!(!text.contains("curl"));
+    }
+
+    #[test]
+    fn test_run_polls_on_interval_until_stopped() {
+        let (clock, _executor, pm) = setup(&["", "curl 8.5\n"]);
+        let mut watcher = Watcher::new(&pm, "apt".to_string(), None);
+        let start = clock.now();
+        let mut polls = Vec::new();
+
+        watcher.run(Duration::from_secs(300), |event| {
+            polls.push(clock.now().duration_since(start).unwrap());
+            !event.is_change()
+        });
+        assert_eq!(polls, vec![Duration::ZERO, Duration::from_secs(300)]);
+    }
+
+    #[test]
+    fn test_run_stops_on_shutdown() {
+        let (_clock, executor, pm) = setup(&[""]);
+        let mut watcher = Watcher::new(&pm, "apt".to_string(), None);
+        let mut polls = 0;
+        watcher.run(Duration::from_secs(300), |_| {
+            polls += 1;
+            executor.request_shutdown();
+            true
+        });
+        assert_eq!(polls, 1);
+    }
+}
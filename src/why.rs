@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::history::RunRecord;
+use crate::stats::display_name;
+use crate::timezone::Timezone;
+
+// What the run history says about one package, per manager that has ever
+// mentioned it in its check or update output (`updog why <package>`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageInfo {
+    pub manager: String,
+    // Outcome of the manager's latest successful check
+    pub state: PackageState,
+    // Latest update that mentioned the package, with the line that did
+    pub last_upgraded: Option<(u64, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PackageState {
+    // The latest check listed it as pending, with this line
+    Outdated { checked_at: u64, line: String },
+    // A check since the last mention didn't list it
+    UpToDate { checked_at: u64 },
+    // No successful check has run since it was last mentioned
+    Unknown,
+}
+
+// Whether an output line is about `package`: a word equal to it, or
+// followed by a version or repository ("git/stable", "typescript@5.4.2")
+pub fn mentions(line: &str, package: &str) -> bool {
+    line.split(|c: char| c.is_whitespace() || matches!(c, ',' | '(' | ')' | '"' | '\''))
+        .map(|word| word.trim_end_matches(':'))
+        .any(|word| {
+            word.strip_prefix(package)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '@', '=']))
+        })
+}
+
+fn mention<'a>(output: &'a str, package: &str) -> Option<&'a str> {
+    output
+        .lines()
+        .map(str::trim)
+        .find(|line| mentions(line, package))
+}
+
+// What `runs` (oldest first) say about `package`, by manager
+pub fn lookup(runs: &[RunRecord], package: &str) -> Vec<PackageInfo> {
+    let mut found: BTreeMap<String, PackageInfo> = BTreeMap::new();
+    // Latest successful check of every manager, mentioning the package or not
+    let mut latest_check: BTreeMap<String, (u64, Option<String>)> = BTreeMap::new();
+
+    for run in runs {
+        for record in run.results.iter().filter(|r| r.success && r.was_executed()) {
+            let name = display_name(record);
+            let line = mention(&record.output, package).map(str::to_string);
+            if run.operation == "check" {
+                latest_check.insert(name.clone(), (run.started_at, line.clone()));
+            }
+            let Some(line) = line else {
+                continue;
+            };
+            let info = found.entry(name.clone()).or_insert_with(|| PackageInfo {
+                manager: name,
+                state: PackageState::Unknown,
+                last_upgraded: None,
+            });
+            if run.operation == "update" {
+                info.last_upgraded = Some((run.started_at, line));
+            }
+        }
+    }
+
+    let mut infos: Vec<PackageInfo> = found.into_values().collect();
+    for info in &mut infos {
+        let upgraded_at = info.last_upgraded.as_ref().map(|(at, _)| *at);
+        info.state = match latest_check.get(&info.manager) {
+            // Upgraded since, so the check no longer says much
+            Some((checked_at, _)) if upgraded_at.is_some_and(|at| at > *checked_at) => {
+                PackageState::Unknown
+            }
+            Some((checked_at, Some(line))) => PackageState::Outdated {
+                checked_at: *checked_at,
+                line: line.clone(),
+            },
+            Some((checked_at, None)) => PackageState::UpToDate {
+                checked_at: *checked_at,
+            },
+            None => PackageState::Unknown,
+        };
+    }
+    infos
+}
+
+pub fn render(package: &str, infos: &[PackageInfo], timezone: Timezone) -> String {
+    if infos.is_empty() {
+        return format!(
+            "No package manager has mentioned {} in the recorded history.\n",
+            package
+        );
+    }
+    let time = |secs: u64| timezone.civil(UNIX_EPOCH + Duration::from_secs(secs));
+    let mut out = format!("{}:\n", package);
+    for info in infos {
+        let state = match &info.state {
+            PackageState::Outdated { checked_at, line } => {
+                format!("outdated as of {} ({})", time(*checked_at), line)
+            }
+            PackageState::UpToDate { checked_at } => {
+                format!("up to date as of {}", time(*checked_at))
+            }
+            PackageState::Unknown => "not checked since it was last mentioned".to_string(),
+        };
+        out.push_str(&format!("  {}: {}\n", info.manager, state));
+        if let Some((at, line)) = &info.last_upgraded {
+            out.push_str(&format!("    last upgraded {} ({})\n", time(*at), line));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::ManagerRecord;
+
+    fn run(operation: &str, at: u64, results: &[(&str, &str)]) -> RunRecord {
+        let mut run = RunRecord::new(operation, UNIX_EPOCH + Duration::from_secs(at));
+        for (manager, output) in results {
+            run.results.push(ManagerRecord {
+                manager: manager.to_string(),
+                subcommand: None,
+                success: true,
+                status: None,
+                duration_secs: 1.0,
+                packages: None,
+                message: String::new(),
+                steps: Vec::new(),
+                output: output.to_string(),
+                warnings: Vec::new(),
+                columns: BTreeMap::new(),
+            });
+        }
+        run
+    }
+
+    #[test]
+    fn test_mentions() {
+        assert!(mentions("git 2.40 -> 2.41", "git"));
+        assert!(mentions(
+            "git/stable 2.41 amd64 [upgradable from: 2.40]",
+            "git"
+        ));
+        assert!(mentions("@types/node@20.1.0", "@types/node"));
+        assert!(mentions("==> Upgrading git: 2.40 -> 2.41", "git"));
+        assert!(!mentions("git-lfs 3.3 -> 3.4", "git"));
+        assert!(!mentions("legit 1.0", "git"));
+    }
+
+    #[test]
+    fn test_lookup_and_render() {
+        let runs = [
+            run("check", 0, &[("brew", "git 2.39 -> 2.40\n"), ("apt", "")]),
+            run(
+                "update",
+                3600,
+                &[("brew", "git 2.39 -> 2.40\nnode 20 -> 21\n")],
+            ),
+            run("check", 7200, &[("brew", ""), ("npm", "git-lfs 1 -> 2")]),
+            run(
+                "check",
+                10800,
+                &[("apt", "git/stable 2.41 [upgradable from: 2.40]")],
+            ),
+        ];
+        let infos = lookup(&runs, "git");
+        assert_eq!(
+            infos,
+            vec![
+                PackageInfo {
+                    manager: "apt".to_string(),
+                    state: PackageState::Outdated {
+                        checked_at: 10800,
+                        line: "git/stable 2.41 [upgradable from: 2.40]".to_string()
+                    },
+                    last_upgraded: None,
+                },
+                PackageInfo {
+                    manager: "brew".to_string(),
+                    state: PackageState::UpToDate { checked_at: 7200 },
+                    last_upgraded: Some((3600, "git 2.39 -> 2.40".to_string())),
+                },
+            ]
+        );
+        assert_eq!(
+            render("git", &infos, Timezone::Utc),
+            "git:\n  \
+             apt: outdated as of 1970-01-01 03:00 +00:00 (git/stable 2.41 [upgradable from: 2.40])\n  \
+             brew: up to date as of 1970-01-01 02:00 +00:00\n    \
+             last upgraded 1970-01-01 01:00 +00:00 (git 2.39 -> 2.40)\n"
+        );
+
+        assert!(lookup(&runs, "ripgrep").is_empty());
+        assert_eq!(
+            render("ripgrep", &[], Timezone::Utc),
+            "No package manager has mentioned ripgrep in the recorded history.\n"
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use crate::migrate::CONFIG_VERSION;
+
+// First-run setup: when there's no config file, offer the package managers
+// found on this machine and write a config with the ones the user keeps
+
+// A package manager the wizard knows how to set up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KnownManager {
+    pub id: &'static str,
+    // Program whose presence on PATH means the manager is installed
+    pub program: &'static str,
+    pub check: Option<&'static str>,
+    pub update: &'static str,
+    pub sudo: bool,
+}
+
+const fn known(
+    id: &'static str,
+    program: &'static str,
+    check: Option<&'static str>,
+    update: &'static str,
+    sudo: bool,
+) -> KnownManager {
+    KnownManager {
+        id,
+        program,
+        check,
+        update,
+        sudo,
+    }
+}
+
+pub const KNOWN_MANAGERS: &[KnownManager] = &[
+    known(
+        "homebrew",
+        "brew",
+        Some("brew outdated"),
+        "brew upgrade",
+        false,
+    ),
+    known("mas", "mas", Some("mas outdated"), "mas upgrade", false),
+    known(
+        "apt",
+        "apt-get",
+        Some("apt list --upgradable 2>/dev/null | tail -n +2"),
+        "sudo apt-get update && sudo apt-get upgrade -y",
+        true,
+    ),
+    known(
+        "dnf",
+        "dnf",
+        Some("dnf list --upgrades --quiet"),
+        "sudo dnf upgrade -y",
+        true,
+    ),
+    known(
+        "pacman",
+        "pacman",
+        Some("pacman -Qu || true"),
+        "sudo pacman -Syu --noconfirm",
+        true,
+    ),
+    known(
+        "flatpak",
+        "flatpak",
+        Some("flatpak remote-ls --updates"),
+        "flatpak update -y",
+        false,
+    ),
+    known(
+        "snap",
+        "snap",
+        Some("snap refresh --list"),
+        "sudo snap refresh",
+        true,
+    ),
+    known(
+        "npm",
+        "npm",
+        Some("npm outdated -g"),
+        "npm update -g",
+        false,
+    ),
+    known(
+        "rustup",
+        "rustup",
+        Some("rustup check"),
+        "rustup update",
+        false,
+    ),
+    known(
+        "cargo",
+        "cargo-install-update",
+        None,
+        "cargo install-update -a",
+        false,
+    ),
+    known("pipx", "pipx", None, "pipx upgrade-all", false),
+];
+
+// The known package managers whose program `binary_exists`
+pub fn detect(binary_exists: &dyn Fn(&str) -> bool) -> Vec<KnownManager> {
+    KNOWN_MANAGERS
+        .iter()
+        .filter(|m| binary_exists(m.program))
+        .copied()
+        .collect()
+}
+
+// Ask which of the `detected` managers to include. Returns None when the user
+// skips the wizard or input ends.
+pub fn choose<R: BufRead, W: Write>(
+    detected: &[KnownManager],
+    input: &mut R,
+    output: &mut W,
+) -> io::Result<Option<Vec<KnownManager>>> {
+    let mut included = vec![true; detected.len()];
+    loop {
+        writeln!(output, "\nPackage managers found on this machine:")?;
+        for (i, manager) in detected.iter().enumerate() {
+            let mark = if included[i] { 'x' } else { ' ' };
+            writeln!(output, "  {:>2}. [{}] {}", i + 1, mark, manager.id)?;
+        }
+        write!(
+            output,
+            "Numbers to toggle (e.g. \"1 3\"), Enter to write the config, q to skip: "
+        )?;
+        output.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim();
+        if line.eq_ignore_ascii_case("q") {
+            return Ok(None);
+        }
+        if line.is_empty() {
+            if included.contains(&true) {
+                break;
+            }
+            writeln!(output, "Select at least one package manager, or q to skip.")?;
+            continue;
+        }
+        for word in line.split([' ', ',']).filter(|w| !w.is_empty()) {
+            match word.parse::<usize>() {
+                Ok(n) if (1..=detected.len()).contains(&n) => included[n - 1] = !included[n - 1],
+                _ => writeln!(output, "Ignoring '{}': not a number from the list.", word)?,
+            }
+        }
+    }
+    Ok(Some(
+        detected
+            .iter()
+            .zip(included)
+            .filter(|(_, included)| *included)
+            .map(|(manager, _)| *manager)
+            .collect(),
+    ))
+}
+
+// Config file contents for the chosen managers
+pub fn render_config(managers: &[KnownManager]) -> String {
+    let mut out = format!(
+        "# Written by updog's first-run wizard. See the README for everything\n\
+         # else that can go in here (schedules, tags, groups, alerts, ...).\n\
+         version: {}\n\
+         commands:\n",
+        CONFIG_VERSION
+    );
+    for manager in managers {
+        out.push_str(&render_entry(manager));
+    }
+    out
+}
+
+// The `commands:` list entry for one manager
+pub fn render_entry(manager: &KnownManager) -> String {
+    let mut out = format!("  - id: {}\n", manager.id);
+    if let Some(check) = manager.check {
+        out.push_str(&format!("    check: {:?}\n", check));
+    }
+    out.push_str(&format!("    update: {:?}\n", manager.update));
+    if manager.sudo {
+        out.push_str("    sudo: true\n");
+    }
+    out
+}
+
+// Run the wizard on the terminal and write the config to `path`. Returns
+// whether a config was written.
+pub fn run(path: &Path) -> io::Result<bool> {
+    let mut output = io::stdout();
+    writeln!(
+        output,
+        "No config file found at {}. Let's create one.",
+        path.display()
+    )?;
+    let detected = detect(&crate::lint::binary_exists);
+    if detected.is_empty() {
+        writeln!(output, "No package managers updog knows about were found.")?;
+        return Ok(false);
+    }
+    let Some(managers) = choose(&detected, &mut io::stdin().lock(), &mut output)? else {
+        return Ok(false);
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, render_config(&managers))?;
+    writeln!(output, "Wrote {}\n", path.display())?;
+    Ok(true)
+}
+
+// The user's editor: $VISUAL, then $EDITOR, then the platform's default
+pub fn editor() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .find_map(|var| {
+            std::env::var(var)
+                .ok()
+                .filter(|editor| !editor.trim().is_empty())
+        })
+        .unwrap_or_else(|| {
+            if cfg!(windows) {
+                "notepad".to_string()
+            } else {
+                "vi".to_string()
+            }
+        })
+}
+
+// After a config failed to load, ask whether to fix it in `editor`. Enter
+// means yes; input ending means no.
+pub fn ask_to_edit<R: BufRead, W: Write>(
+    path: &Path,
+    editor: &str,
+    input: &mut R,
+    output: &mut W,
+) -> io::Result<bool> {
+    write!(
+        output,
+        "Open {} in {} to fix it? [Y/n] ",
+        path.display(),
+        editor
+    )?;
+    output.flush()?;
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        writeln!(output)?;
+        return Ok(false);
+    }
+    Ok(matches!(
+        line.trim().to_ascii_lowercase().as_str(),
+        "" | "y" | "yes"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_detect() {
+        let found = detect(&|program| program == "brew" || program == "npm");
+        let ids: Vec<&str> = found.iter().map(|m| m.id).collect();
+        assert_eq!(ids, ["homebrew", "npm"]);
+    }
+
+    #[test]
+    fn test_choose() {
+        let detected = detect(&|program| ["brew", "apt-get", "npm"].contains(&program));
+        let mut output = Vec::new();
+        let chosen = choose(&detected, &mut Cursor::new("2 x\n\n"), &mut output)
+            .unwrap()
+            .unwrap();
+        let ids: Vec<&str> = chosen.iter().map(|m| m.id).collect();
+        assert_eq!(ids, ["homebrew", "npm"]);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("   2. [ ] apt"), "{}", output);
+        assert!(output.contains("Ignoring 'x'"));
+
+        // Deselecting everything needs another answer
+        let mut output = Vec::new();
+        let chosen = choose(&detected, &mut Cursor::new("1,2,3\n\nq\n"), &mut output).unwrap();
+        assert!(chosen.is_none());
+        assert!(String::from_utf8(output).unwrap().contains("at least one"));
+        assert!(choose(&detected, &mut Cursor::new(""), &mut Vec::new())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_ask_to_edit() {
+        let path = Path::new("/home/me/.config/updog/updog.yaml");
+        let ask = |input: &str| {
+            let mut output = Vec::new();
+            let answer = ask_to_edit(path, "nvim", &mut Cursor::new(input), &mut output).unwrap();
+            (answer, String::from_utf8(output).unwrap())
+        };
+        let (answer, output) = ask("\n");
+        assert!(answer);
+        assert_eq!(
+            output,
+            "Open /home/me/.config/updog/updog.yaml in nvim to fix it? [Y/n] "
+        );
+        assert!(ask("Yes\n").0);
+        assert!(!ask("n\n").0);
+        assert!(!ask("").0);
+    }
+
+    #[test]
+    fn test_render_config() {
+        let yaml = render_config(KNOWN_MANAGERS);
+        let config: Config = serde_yaml::from_str(&yaml).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.commands.len(), KNOWN_MANAGERS.len());
+        let apt = config.find_package_manager("apt").unwrap();
+        assert!(apt.sudo);
+        assert_eq!(
+            apt.check.as_ref().unwrap().as_single_str().unwrap(),
+            "apt list --upgradable 2>/dev/null | tail -n +2"
+        );
+        assert!(config.find_package_manager("pipx").unwrap().check.is_none());
+    }
+}
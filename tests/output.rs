@@ -0,0 +1,62 @@
+// `--output json` end to end: stdout holds the summary document and nothing
+// else, so that it can be piped into jq
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+fn updog(home: &Path, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_updog"))
+        .arg("--config")
+        .arg(home.join("config.yaml"))
+        .args(args)
+        .env("HOME", home)
+        .env("XDG_CONFIG_HOME", home.join("config"))
+        .env("XDG_DATA_HOME", home.join("data"))
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[cfg(unix)]
+#[test]
+fn test_update_checking_first_prints_only_json() {
+    let home = tempfile::tempdir().unwrap();
+    std::fs::write(
+        home.path().join("config.yaml"),
+        r#"
+commands:
+  - id: alpha
+    check: echo "alpha 1.0 -> 1.1"
+    update: echo updated alpha
+  - id: beta
+    check: "true"
+    update: echo updated beta
+"#,
+    )
+    .unwrap();
+
+    let output = updog(
+        home.path(),
+        &["update", "--check-parallel", "--output", "json"],
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let summary: serde_json::Value = serde_json::from_str(&stdout)
+        .unwrap_or_else(|e| panic!("stdout isn't JSON ({}):\n{}", e, stdout));
+
+    assert_eq!(summary["operation"], "update");
+    let checks = summary["checks"].as_array().unwrap();
+    assert_eq!(checks.len(), 2);
+    assert_eq!(checks[0]["manager"], "alpha");
+    assert_eq!(checks[0]["pending"], 1);
+    // beta's check found nothing, so only alpha is updated
+    let results = summary["results"].as_array().unwrap();
+    let status = |manager: &str| {
+        results
+            .iter()
+            .find(|r| r["manager"] == manager)
+            .map(|r| r["status"].clone())
+            .unwrap()
+    };
+    assert_eq!(status("alpha"), "success");
+    assert_eq!(status("beta"), "skipped");
+}